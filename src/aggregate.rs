@@ -0,0 +1,284 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::stats::{BattleStats, FleetStats};
+
+/// The mean of some numbers and how spread out they are.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    pub mean: f64,
+    /// The population variance.
+    pub variance: f64,
+}
+
+impl Spread {
+    /// The spread of `values`, or `None` when there aren't any.
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        Some(Self { mean, variance })
+    }
+}
+
+/// One fleet's numbers over many battles: totals, and the means per battle.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FleetAggregate {
+    pub name: String,
+    pub battles: u64,
+    pub wins: u64,
+    pub losses: u64,
+    /// Battles without a winner: draws, and replays that don't say.
+    pub undecided: u64,
+    pub ships_lost: u64,
+    pub ships_destroyed: u64,
+    pub missiles_fired: u64,
+    pub shells_fired: u64,
+    pub missile_hits: u64,
+    pub shell_hits: u64,
+    pub damage_dealt: f64,
+    pub damage_received: f64,
+    pub mean_damage_dealt: f64,
+    pub mean_damage_received: f64,
+    pub mean_battle_length: Option<f64>,
+    /// The missile hit rate over the battles the fleet fired missiles in.
+    pub missile_hit_rate: Option<Spread>,
+    /// The shell hit rate over the battles the fleet fired shells in.
+    pub shell_hit_rate: Option<Spread>,
+}
+
+/// Many battles merged per fleet.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub battles: u64,
+    pub mean_battle_length: Option<f64>,
+    /// Sorted by wins, most first.
+    pub fleets: Vec<FleetAggregate>,
+    /// The replays that couldn't be read, and so aren't counted.
+    pub failed: Vec<PathBuf>,
+}
+
+impl Aggregate {
+    pub fn new(battles: &[BattleStats], failed: Vec<PathBuf>) -> Self {
+        let mut fleets: Vec<(String, Vec<(&BattleStats, &FleetStats)>)> = Vec::new();
+        for battle in battles {
+            for fleet in &battle.fleets {
+                match fleets.iter_mut().find(|(name, _)| *name == fleet.name) {
+                    Some((_, sides)) => sides.push((battle, fleet)),
+                    None => fleets.push((fleet.name.clone(), vec![(battle, fleet)])),
+                }
+            }
+        }
+
+        let mut fleets: Vec<FleetAggregate> = fleets
+            .into_iter()
+            .map(|(name, sides)| FleetAggregate::new(name, &sides))
+            .collect();
+        fleets.sort_by(|a, b| b.wins.cmp(&a.wins).then_with(|| a.name.cmp(&b.name)));
+
+        let lengths: Vec<f64> = battles.iter().filter_map(|battle| battle.length).collect();
+        Self {
+            battles: battles.len() as u64,
+            mean_battle_length: Spread::of(&lengths).map(|spread| spread.mean),
+            fleets,
+            failed,
+        }
+    }
+}
+
+impl FleetAggregate {
+    /// Merges the fleet's side of each battle it was in.
+    fn new(name: String, sides: &[(&BattleStats, &FleetStats)]) -> Self {
+        let total = |stat: fn(&FleetStats) -> u64| sides.iter().map(|(_, fleet)| stat(fleet)).sum();
+        let damage_dealt: f64 = sides.iter().map(|(_, fleet)| fleet.damage_dealt).sum();
+        let damage_received: f64 = sides.iter().map(|(_, fleet)| fleet.damage_received).sum();
+        let battles = sides.len() as u64;
+        let wins = sides
+            .iter()
+            .filter(|(battle, _)| battle.winner.as_ref() == Some(&name))
+            .count() as u64;
+        let undecided = sides
+            .iter()
+            .filter(|(battle, _)| battle.winner.is_none())
+            .count() as u64;
+
+        let lengths: Vec<f64> = sides
+            .iter()
+            .filter_map(|(battle, _)| battle.length)
+            .collect();
+        let rates = |rate: fn(&FleetStats) -> Option<f64>| {
+            let rates: Vec<f64> = sides.iter().filter_map(|(_, fleet)| rate(fleet)).collect();
+            Spread::of(&rates)
+        };
+        Self {
+            battles,
+            wins,
+            losses: battles - wins - undecided,
+            undecided,
+            ships_lost: total(|fleet| fleet.ships_lost),
+            ships_destroyed: total(|fleet| fleet.ships_destroyed),
+            missiles_fired: total(|fleet| fleet.missiles_fired),
+            shells_fired: total(|fleet| fleet.shells_fired),
+            missile_hits: total(|fleet| fleet.missile_hits),
+            shell_hits: total(|fleet| fleet.shell_hits),
+            damage_dealt,
+            damage_received,
+            mean_damage_dealt: damage_dealt / battles as f64,
+            mean_damage_received: damage_received / battles as f64,
+            mean_battle_length: Spread::of(&lengths).map(|spread| spread.mean),
+            missile_hit_rate: rates(FleetStats::missile_hit_rate),
+            shell_hit_rate: rates(FleetStats::shell_hit_rate),
+            name,
+        }
+    }
+}
+
+/// The aggregate as a table, a row per fleet.
+pub fn aggregate_table(aggregate: &Aggregate) -> String {
+    let rate = |spread: Option<Spread>| {
+        spread.map_or("-".to_owned(), |spread| {
+            format!(
+                "{:.0}% ±{:.0}",
+                spread.mean * 100.0,
+                spread.variance.sqrt() * 100.0
+            )
+        })
+    };
+    let length =
+        |length: Option<f64>| length.map_or("-".to_owned(), |length| format!("{length:.1}"));
+    let rows: Vec<[String; 9]> = aggregate
+        .fleets
+        .iter()
+        .map(|fleet| {
+            [
+                fleet.name.clone(),
+                fleet.battles.to_string(),
+                format!("{}/{}/{}", fleet.wins, fleet.losses, fleet.undecided),
+                format!("{:.1}", fleet.mean_damage_dealt),
+                format!("{:.1}", fleet.mean_damage_received),
+                format!("{}/{}", fleet.ships_destroyed, fleet.ships_lost),
+                rate(fleet.missile_hit_rate),
+                rate(fleet.shell_hit_rate),
+                length(fleet.mean_battle_length),
+            ]
+        })
+        .collect();
+
+    let headers = [
+        "Fleet",
+        "Battles",
+        "Won/lost/undecided",
+        "Mean damage dealt",
+        "Mean damage received",
+        "Destroyed/lost",
+        "Missile hit rate",
+        "Shell hit rate",
+        "Mean length",
+    ];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut table = String::new();
+    for row in [headers.map(str::to_owned)].iter().chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    writeln!(
+        table,
+        "\n{} battles, lasting {} on average",
+        aggregate.battles,
+        length(aggregate.mean_battle_length)
+    )
+    .unwrap();
+    if !aggregate.failed.is_empty() {
+        writeln!(
+            table,
+            "{} replays couldn't be read and aren't counted",
+            aggregate.failed.len()
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregate, Spread};
+    use crate::stats::{BattleStats, FleetStats};
+
+    fn battle(winner: Option<&str>, damage: [f64; 2], length: f64) -> BattleStats {
+        let fleet = |name: &str, damage_dealt: f64| FleetStats {
+            name: name.to_owned(),
+            damage_dealt,
+            missiles_fired: 4,
+            missile_hits: (damage_dealt / 10.0) as u64,
+            ..Default::default()
+        };
+        BattleStats {
+            fleets: [
+                fleet("glass_cannon", damage[0]),
+                fleet("iron_armada", damage[1]),
+            ],
+            winner: winner.map(str::to_owned),
+            length: Some(length),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spread_has_the_population_variance() {
+        assert_eq!(
+            Spread::of(&[0.25, 0.75]),
+            Some(Spread {
+                mean: 0.5,
+                variance: 0.0625
+            })
+        );
+        assert_eq!(Spread::of(&[]), None);
+    }
+
+    #[test]
+    fn aggregate_merges_each_fleet_over_the_battles() {
+        let battles = [
+            battle(Some("iron_armada"), [10.0, 30.0], 100.0),
+            battle(Some("iron_armada"), [30.0, 40.0], 200.0),
+            battle(None, [20.0, 20.0], 300.0),
+        ];
+        let aggregate = Aggregate::new(&battles, Vec::new());
+        assert_eq!(aggregate.battles, 3);
+        assert_eq!(aggregate.mean_battle_length, Some(200.0));
+
+        let [iron, glass] = &aggregate.fleets[..] else {
+            panic!("{aggregate:?}");
+        };
+        assert_eq!(iron.name, "iron_armada");
+        assert_eq!((iron.wins, iron.losses, iron.undecided), (2, 0, 1));
+        assert_eq!((glass.wins, glass.losses, glass.undecided), (0, 2, 1));
+        assert_eq!(glass.damage_dealt, 60.0);
+        assert_eq!(glass.mean_damage_dealt, 20.0);
+        assert_eq!(glass.missiles_fired, 12);
+        let rate = glass.missile_hit_rate.unwrap();
+        assert_eq!(rate.mean, 0.5);
+        assert!((rate.variance - 1.0 / 24.0).abs() < 1e-9, "{rate:?}");
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::results::{result_path, BattleRecord};
+
+/// The directory next to the replays where archived fleets are kept, named by their SHA-256.
+///
+/// Keeping them by digest means a fleet that battles a hundred times is only stored once.
+pub const FLEET_OBJECTS_DIR: &str = "fleet_objects";
+
+/// Where the archived copy of a fleet with this digest lives, for replays in `dir`.
+pub fn archived_fleet_path(dir: &Path, sha256: &str) -> PathBuf {
+    dir.join(FLEET_OBJECTS_DIR).join(format!("{sha256}.wasm"))
+}
+
+/// Copies a fleet into the archive for replays in `dir`, unless an identical copy is already there.
+pub fn archive_fleet(dir: &Path, fleet: &Path, sha256: &str) -> anyhow::Result<PathBuf> {
+    let archived = archived_fleet_path(dir, sha256);
+    if archived.exists() {
+        return Ok(archived);
+    }
+
+    let objects = dir.join(FLEET_OBJECTS_DIR);
+    std::fs::create_dir_all(&objects)
+        .with_context(|| format!("trying to create fleet archive {objects:?}"))?;
+
+    // Copied under a temporary name first, so a half-copied fleet never looks archived
+    let temp = objects.join(format!(".{sha256}.{}.tmp", std::process::id()));
+    std::fs::copy(fleet, &temp)
+        .with_context(|| format!("trying to copy {fleet:?} into the fleet archive"))?;
+    std::fs::rename(&temp, &archived)
+        .with_context(|| format!("trying to move {temp:?} to {archived:?}"))?;
+
+    Ok(archived)
+}
+
+/// Finds the archived copies of the fleets that battled in a replay, going through the replay's
+/// result JSON for their digests.
+pub fn archived_fleets(replay: &Path) -> anyhow::Result<[PathBuf; 2]> {
+    let name = replay
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("{replay:?} isn't a replay file"))?;
+    let base = name
+        .strip_suffix(".json.deflate")
+        .with_context(|| format!("{replay:?} isn't a `.json.deflate` replay"))?;
+    let dir = replay.parent().unwrap_or(Path::new("."));
+
+    let record = BattleRecord::read_json(&result_path(&dir.join(base)))?;
+    let archived = |fleet: &str, sha256: &Option<String>| {
+        let sha256 = sha256.as_deref().with_context(|| {
+            format!("the result for {replay:?} doesn't say which build of '{fleet}' battled")
+        })?;
+        let path = archived_fleet_path(dir, sha256);
+        if !path.exists() {
+            anyhow::bail!(
+                "'{fleet}' from {replay:?} wasn't archived, battle with `--archive-fleets` to keep fleets around"
+            );
+        }
+        Ok(path)
+    };
+
+    Ok([
+        archived(&record.fleet_a, &record.fleet_a_sha256)?,
+        archived(&record.fleet_b, &record.fleet_b_sha256)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_fleet, archived_fleets};
+    use crate::hash::file_sha256;
+    use crate::results::{result_path, BattleRecord};
+
+    #[test]
+    fn archived_fleets_resolve_from_the_replay() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fleet = dir.path().join("a.wasm");
+        std::fs::write(&fleet, b"\0asm\x01\0\0\0")?;
+        let sha256 = file_sha256(&fleet)?;
+
+        let archived = archive_fleet(dir.path(), &fleet, &sha256)?;
+        assert_eq!(archive_fleet(dir.path(), &fleet, &sha256)?, archived);
+        // Rebuilding the fleet mustn't touch the archived copy
+        std::fs::write(&fleet, b"rebuilt")?;
+
+        let output = dir.path().join("1718476403_a_vs_a");
+        BattleRecord {
+            fleet_a: "a".to_owned(),
+            fleet_b: "a".to_owned(),
+            fleet_a_sha256: Some(sha256.clone()),
+            fleet_b_sha256: Some(sha256),
+            ..Default::default()
+        }
+        .write_json(&result_path(&output))?;
+
+        let [a, b] = archived_fleets(&dir.path().join("1718476403_a_vs_a.json.deflate"))?;
+        assert_eq!(a, archived);
+        assert_eq!(b, archived);
+        assert_eq!(std::fs::read(a)?, b"\0asm\x01\0\0\0");
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+//! `archive`: bundling a battle into one zip to send someone, with everything needed to look into
+//! it or battle it again, and `archive --extract` for unpacking one.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::archived_fleet_path;
+use crate::fleet::find_built_fleets;
+use crate::hash::{file_sha256, sha256};
+use crate::output::{dry_run, say};
+use crate::replay::{battle_files, find_replay};
+use crate::results::{result_path, BattleRecord};
+use crate::run::fleet_file_name;
+use crate::sim::{locate_protologic_path, protologic_sim_path};
+use crate::version::release_version;
+
+/// The file in a battle archive saying what's in it and what the battle was played with.
+pub const ARCHIVE_MANIFEST_FILE: &str = "archive.json";
+/// The directory in a battle archive the fleets go in.
+const FLEETS_DIR: &str = "fleets";
+const SCENARIO_DIR: &str = "scenario";
+
+/// Arguments for `cargo protologic archive`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ArchiveArgs {
+    /// The replay of the battle to archive, or `latest` for the newest one in the battle output directory, which is also the default.
+    #[arg(conflicts_with = "extract")]
+    replay: Option<PathBuf>,
+    /// The battle output directory to find the newest replay in, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Where to write the archive. Defaults to `<replay>.battle.zip` in the current directory.
+    #[arg(short, long, value_name = "PATH", conflicts_with = "extract")]
+    output: Option<PathBuf>,
+    /// The location of the Protologic/Release repo, to record the sim's version.
+    #[arg(long, env)]
+    protologic_path: Option<PathBuf>,
+    /// Unpack a battle archive instead, checking its fleets are the ones that battled.
+    #[arg(long, value_name = "ZIP")]
+    extract: Option<PathBuf>,
+    /// With `--extract`, the directory to unpack into. Defaults to one named after the archive, in the current directory.
+    #[arg(long, value_name = "DIR", requires = "extract")]
+    into: Option<PathBuf>,
+}
+
+/// What `archive.json` says about a battle archive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveManifest {
+    pub tool_version: String,
+    pub os: String,
+    pub arch: String,
+    /// The version of the Release the archive was made with, which is most likely the one the
+    /// battle was played with.
+    pub sim_version: Option<String>,
+    /// The replay's file name in the archive.
+    pub replay: String,
+    /// The battle's result JSON and logs, by their file names in the archive.
+    pub files: Vec<String>,
+    pub seed: Option<u64>,
+    pub scenario: Option<String>,
+    /// The scenario file in the archive, when the battle was on one from a file.
+    pub scenario_file: Option<String>,
+    pub max_ticks: Option<u64>,
+    pub fuel_limit: Option<u64>,
+    pub label: Option<String>,
+    pub fleets: Vec<ArchivedFleet>,
+}
+
+/// A fleet that battled, and the copy of it in the archive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedFleet {
+    /// `a` or `b`.
+    pub side: String,
+    pub name: String,
+    pub sha256: Option<String>,
+    /// Its path in the archive, or `None` when the build that battled couldn't be found.
+    pub file: Option<String>,
+}
+
+/// The exact build of a fleet that battled: its archived copy, or the fleet as it's built now if
+/// it hasn't been rebuilt since.
+fn find_fleet_build(dir: &Path, name: &str, sha256: &str) -> anyhow::Result<Option<PathBuf>> {
+    let archived = archived_fleet_path(dir, sha256);
+    if archived.is_file() {
+        return Ok(Some(archived));
+    }
+    for debug in [false, true] {
+        for fleet in find_built_fleets(debug)? {
+            if fleet.is_named(name) && file_sha256(&fleet.path)? == sha256 {
+                return Ok(Some(fleet.path));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The replay's base name, like `2024-06-15_183323_a_vs_b`, that the battle's other files share.
+fn replay_base(replay: &Path) -> anyhow::Result<&str> {
+    let name = replay
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("{replay:?} isn't a replay file"))?;
+    Ok(name
+        .strip_suffix(".json.deflate")
+        .or_else(|| name.strip_suffix(".json"))
+        .unwrap_or(name))
+}
+
+/// Bundles a battle's replay, result, logs and fleets into a zip, with a manifest of what it was
+/// played with.
+fn write_archive(replay: &Path, path: &Path, sim_version: Option<String>) -> anyhow::Result<()> {
+    let dir = replay.parent().unwrap_or(Path::new("."));
+    let base = replay_base(replay)?;
+    let record = BattleRecord::read_json(&result_path(&dir.join(base))).ok();
+    if record.is_none() {
+        say!("Warning: there's no result JSON next to the replay, so the fleets that battled can't be told and aren't in the archive");
+    }
+
+    let mut entries: Vec<(String, PathBuf)> = Vec::new();
+    let replay_name = replay
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    entries.push((replay_name.clone(), replay.to_owned()));
+    let mut files = Vec::new();
+    for file in battle_files(dir, base) {
+        let name = file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        // A plain `.json` next to a `.json.deflate` is only the replay again, inflated
+        if name != replay_name && name != format!("{base}.json") {
+            files.push(name.clone());
+            entries.push((name, file));
+        }
+    }
+
+    let mut fleets = Vec::new();
+    let mut scenario_file = None;
+    if let Some(record) = &record {
+        for (side, name, sha256) in [
+            ("a", &record.fleet_a, &record.fleet_a_sha256),
+            ("b", &record.fleet_b, &record.fleet_b_sha256),
+        ] {
+            let build = match sha256 {
+                Some(sha256) => find_fleet_build(dir, name, sha256)?,
+                None => None,
+            };
+            let file = build.map(|build| {
+                let file = format!("{FLEETS_DIR}/fleet_{side}_{}.wasm", fleet_file_name(name));
+                entries.push((file.clone(), build));
+                file
+            });
+            if file.is_none() {
+                say!("Warning: can't find the build of '{name}' that battled, so it's not in the archive. It wasn't archived, and the built '{name}' here isn't it. Battle with `--archive-fleets` to keep fleets around");
+            }
+            fleets.push(ArchivedFleet {
+                side: side.to_owned(),
+                name: name.clone(),
+                sha256: sha256.clone(),
+                file,
+            });
+        }
+        if let Some(scenario) = record.scenario.as_deref().map(Path::new) {
+            if scenario.is_file() {
+                let file = format!(
+                    "{SCENARIO_DIR}/{}",
+                    scenario.file_name().unwrap_or_default().to_string_lossy()
+                );
+                entries.push((file.clone(), scenario.to_owned()));
+                scenario_file = Some(file);
+            }
+        }
+    }
+
+    let manifest = ArchiveManifest {
+        tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+        os: std::env::consts::OS.to_owned(),
+        arch: std::env::consts::ARCH.to_owned(),
+        sim_version,
+        replay: replay_name,
+        files,
+        seed: record.as_ref().and_then(|record| record.seed),
+        scenario: record.as_ref().and_then(|record| record.scenario.clone()),
+        scenario_file,
+        max_ticks: record.as_ref().and_then(|record| record.max_ticks),
+        fuel_limit: record.as_ref().and_then(|record| record.fuel_limit),
+        label: record.as_ref().and_then(|record| record.label.clone()),
+        fleets,
+    };
+
+    if dry_run() {
+        say!("Would archive into {}:", path.display());
+        for (name, file) in &entries {
+            say!("  {name} from {}", file.display());
+        }
+        return Ok(());
+    }
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let file = std::fs::File::create(path).with_context(|| format!("trying to create {path:?}"))?;
+    let mut zip = zip::ZipWriter::new(file);
+    for (name, file) in &entries {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(&std::fs::read(file).with_context(|| format!("trying to read {file:?}"))?)?;
+    }
+    zip.start_file(ARCHIVE_MANIFEST_FILE, options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish()
+        .with_context(|| format!("trying to finish writing {path:?}"))?;
+    Ok(())
+}
+
+/// Reads the manifest of a battle archive, and checks each fleet in it is the build that
+/// battled. Fleets that don't match are errors, since battling them wouldn't be the same battle.
+fn read_archive_manifest(zip_path: &Path) -> anyhow::Result<ArchiveManifest> {
+    let file =
+        std::fs::File::open(zip_path).with_context(|| format!("trying to open {zip_path:?}"))?;
+    let mut zip =
+        zip::ZipArchive::new(file).with_context(|| format!("{zip_path:?} isn't a zip file"))?;
+    let mut json = String::new();
+    zip.by_name(ARCHIVE_MANIFEST_FILE)
+        .with_context(|| {
+            format!("{zip_path:?} has no {ARCHIVE_MANIFEST_FILE}, it isn't a battle archive")
+        })?
+        .read_to_string(&mut json)?;
+    let manifest: ArchiveManifest = serde_json::from_str(&json)
+        .with_context(|| format!("trying to parse {ARCHIVE_MANIFEST_FILE} in {zip_path:?}"))?;
+
+    for fleet in &manifest.fleets {
+        let (Some(file), Some(expected)) = (&fleet.file, &fleet.sha256) else {
+            continue;
+        };
+        let mut bytes = Vec::new();
+        zip.by_name(file)
+            .with_context(|| format!("{zip_path:?} is missing {file}"))?
+            .read_to_end(&mut bytes)?;
+        if &sha256(&bytes) != expected {
+            anyhow::bail!(
+                "{file} in {zip_path:?} isn't the build of '{}' that battled, its SHA-256 doesn't match",
+                fleet.name
+            );
+        }
+    }
+    Ok(manifest)
+}
+
+/// The command that battles the archived fleets again as they battled.
+fn rerun_command(dir: &Path, manifest: &ArchiveManifest) -> Option<String> {
+    let mut command = "cargo protologic run".to_owned();
+    for fleet in &manifest.fleets {
+        command.push_str(&format!(
+            " --vs {}",
+            dir.join(fleet.file.as_ref()?).display()
+        ));
+    }
+    if manifest.fleets.len() != 2 {
+        return None;
+    }
+    if let Some(seed) = manifest.seed {
+        command.push_str(&format!(" --seed {seed}"));
+    }
+    match (&manifest.scenario_file, &manifest.scenario) {
+        (Some(file), _) => command.push_str(&format!(" --scenario {}", dir.join(file).display())),
+        (None, Some(scenario)) => command.push_str(&format!(" --scenario {scenario}")),
+        (None, None) => {}
+    }
+    if let Some(max_ticks) = manifest.max_ticks {
+        command.push_str(&format!(" --max-ticks {max_ticks}"));
+    }
+    if let Some(fuel_limit) = manifest.fuel_limit {
+        command.push_str(&format!(" --fuel-limit {fuel_limit}"));
+    }
+    Some(command)
+}
+
+/// Unpacks a battle archive into `dir`, after checking it.
+fn extract_archive(zip_path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let manifest = read_archive_manifest(zip_path)?;
+    if dir.exists() {
+        anyhow::bail!(
+            "{} already exists, pick somewhere else with `--into`",
+            dir.display()
+        );
+    }
+    if dry_run() {
+        say!(
+            "Would extract {} into {}",
+            zip_path.display(),
+            dir.display()
+        );
+        return Ok(());
+    }
+    let file =
+        std::fs::File::open(zip_path).with_context(|| format!("trying to open {zip_path:?}"))?;
+    zip::ZipArchive::new(file)
+        .and_then(|mut zip| zip.extract(dir))
+        .with_context(|| format!("trying to extract {zip_path:?} into {dir:?}"))?;
+
+    say!("Extracted the battle into {}", dir.display());
+    say!("  replay: {}", dir.join(&manifest.replay).display());
+    for fleet in &manifest.fleets {
+        match &fleet.file {
+            Some(file) => say!(
+                "  fleet {}: '{}' at {}",
+                fleet.side.to_uppercase(),
+                fleet.name,
+                dir.join(file).display()
+            ),
+            None => say!(
+                "  fleet {}: '{}' wasn't in the archive",
+                fleet.side.to_uppercase(),
+                fleet.name
+            ),
+        }
+    }
+    let sim_version = manifest.sim_version.as_deref().unwrap_or("unknown");
+    say!(
+        "Archived with cargo-protologic {} and sim {sim_version}",
+        manifest.tool_version
+    );
+    if let Some(command) = rerun_command(dir, &manifest) {
+        say!("Battle it again with: {command}");
+    }
+    Ok(())
+}
+
+/// Bundles a battle into a zip, or with `--extract` unpacks one.
+pub fn archive(args: ArchiveArgs) -> anyhow::Result<()> {
+    if let Some(zip_path) = &args.extract {
+        let dir = match args.into {
+            Some(dir) => dir,
+            None => {
+                let name = zip_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .with_context(|| format!("{zip_path:?} isn't a file name"))?;
+                let stem = name
+                    .strip_suffix(".battle.zip")
+                    .or_else(|| name.strip_suffix(".zip"))
+                    .unwrap_or(name);
+                PathBuf::from(stem)
+            }
+        };
+        return extract_archive(zip_path, &dir);
+    }
+
+    let replay = find_replay(args.replay, args.output_dir)?;
+    let path = match args.output {
+        Some(path) => path,
+        None => PathBuf::from(format!("{}.battle.zip", replay_base(&replay)?)),
+    };
+    let release = match args.protologic_path {
+        Some(path) => Ok(path),
+        None => locate_protologic_path().map(|(path, _)| path),
+    };
+    let sim_version = release
+        .ok()
+        .and_then(|release| release_version(&release, &protologic_sim_path(&release)));
+    write_archive(&replay, &path, sim_version)?;
+    if !dry_run() {
+        say!("Archived the battle to {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_archive, write_archive};
+    use crate::archive::archive_fleet;
+    use crate::hash::{file_sha256, sha256};
+    use crate::results::{result_path, BattleRecord};
+
+    #[test]
+    fn archives_extract_with_their_fleets() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let battles = dir.path().join("battles");
+        std::fs::create_dir(&battles)?;
+        let base = battles.join("1718476403_a_vs_b");
+        let replay = battles.join("1718476403_a_vs_b.json.deflate");
+        std::fs::write(&replay, b"replay")?;
+        std::fs::write(battles.join("1718476403_a_vs_b.sim.log"), b"Fleet 0 wins!")?;
+        let mut hashes = Vec::new();
+        for fleet in ["a", "b"] {
+            let wasm = dir.path().join(format!("{fleet}.wasm"));
+            std::fs::write(&wasm, fleet)?;
+            hashes.push(sha256(fleet.as_bytes()));
+            archive_fleet(&battles, &wasm, &hashes[hashes.len() - 1])?;
+        }
+        BattleRecord {
+            fleet_a: "a".to_owned(),
+            fleet_b: "b".to_owned(),
+            fleet_a_sha256: Some(hashes[0].clone()),
+            fleet_b_sha256: Some(hashes[1].clone()),
+            seed: Some(42),
+            ..Default::default()
+        }
+        .write_json(&result_path(&base))?;
+
+        let zip = dir.path().join("battle.zip");
+        write_archive(&replay, &zip, Some("1.2.3".to_owned()))?;
+        let out = dir.path().join("out");
+        extract_archive(&zip, &out)?;
+        assert_eq!(
+            std::fs::read(out.join("1718476403_a_vs_b.json.deflate"))?,
+            b"replay"
+        );
+        assert!(out.join("1718476403_a_vs_b.sim.log").is_file());
+        assert!(out.join("1718476403_a_vs_b.result.json").is_file());
+        assert_eq!(file_sha256(&out.join("fleets/fleet_b_b.wasm"))?, hashes[1]);
+        Ok(())
+    }
+}
@@ -0,0 +1,554 @@
+//! Measuring what a fleet's ticks cost, in the fuel the sim budgets them by, without a battle.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use wasmtime::{Config, Engine, ExternType, Linker, Module, Store, Trap, Val, ValType};
+
+use crate::fleet::{find_built_fleets, find_fleet, Fleet};
+use crate::output::say;
+use crate::preflight::ENTRY_POINTS;
+
+/// The most fuel a tick can take before it counts as stuck, far more than the sim allows.
+const TICK_FUEL: u64 = 2_000_000_000;
+
+/// Arguments for `cargo protologic bench`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchArgs {
+    /// The fleets to measure, by name or as paths to wasm files. Give two to compare them side by side.
+    #[arg(required = true)]
+    fleets: Vec<String>,
+    /// How many ticks to run each fleet for.
+    #[arg(long, value_name = "N", default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+    ticks: u32,
+    /// Measure the debug builds of the fleets, rather than the optimized ones.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+    /// A JSON file of what the sim's functions return on each tick, instead of zeros, like `{"ship_get_position_x": [0, 1.5, 3]}`. A single number is returned every tick, and a list's last value once it runs out.
+    #[arg(long, value_name = "PATH")]
+    inputs: Option<PathBuf>,
+    /// Print the measurements as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// What an import returns, by tick.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum Input {
+    Every(f64),
+    ByTick(Vec<f64>),
+}
+
+impl Input {
+    fn on_tick(&self, tick: u32) -> f64 {
+        match self {
+            Self::Every(value) => *value,
+            Self::ByTick(values) => values
+                .get(tick as usize)
+                .or(values.last())
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// What imports return, by their name. Anything not in here returns zeros.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Inputs(HashMap<String, Input>);
+
+impl Inputs {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("trying to read bench inputs {path:?}"))?;
+        serde_json::from_str(&json).with_context(|| {
+            format!("trying to parse bench inputs {path:?}, which should map function names to numbers or lists of them")
+        })
+    }
+
+    /// What `module::name` returns on `tick`, as `ty`.
+    pub fn value(&self, module: &str, name: &str, tick: u32, ty: &ValType) -> Val {
+        let input = self
+            .0
+            .get(&format!("{module}::{name}"))
+            .or_else(|| self.0.get(name));
+        let Some(input) = input else {
+            return ty.default_value().unwrap_or(Val::I32(0));
+        };
+        let value = input.on_tick(tick);
+        match ty {
+            ValType::I32 => Val::I32(value as i32),
+            ValType::I64 => Val::I64(value as i64),
+            ValType::F32 => Val::F32((value as f32).to_bits()),
+            ValType::F64 => Val::F64(value.to_bits()),
+            ty => ty.default_value().unwrap_or(Val::I32(0)),
+        }
+    }
+}
+
+/// The fuel ticks took.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct FuelStats {
+    pub min: u64,
+    pub mean: f64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl FuelStats {
+    fn of(costs: &[u64]) -> Option<Self> {
+        let mut sorted = costs.to_vec();
+        sorted.sort_unstable();
+        let (&min, &max) = (sorted.first()?, sorted.last()?);
+        let p99 = sorted[(sorted.len() * 99).div_ceil(100) - 1];
+        Some(Self {
+            min,
+            mean: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+            p99,
+            max,
+        })
+    }
+}
+
+/// What running a fleet's ticks measured.
+#[derive(Serialize, Debug, Clone)]
+pub struct Measurement {
+    /// How many ticks it got through.
+    pub ticks: u32,
+    /// The fuel each tick took, up to the `sched_yield` that ended it.
+    pub fuel: Option<FuelStats>,
+    pub compile_secs: f64,
+    /// How long instantiating it and running `_initialize` took.
+    pub instantiate_secs: f64,
+    pub memory_start: u64,
+    pub memory_end: u64,
+    /// Why it stopped before the ticks were up, if it did.
+    pub stopped: Option<String>,
+    /// Whether that was a failure, like a trap, rather than the fleet finishing.
+    pub failed: bool,
+}
+
+/// Why the bench stopped a fleet, smuggled out of a host function as an error.
+#[derive(Debug)]
+enum Stop {
+    Done,
+    Exited(i32),
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Done => f.write_str("ran all its ticks"),
+            Stop::Exited(code) => write!(f, "exited with code {code}"),
+        }
+    }
+}
+
+impl std::error::Error for Stop {}
+
+struct TickState {
+    target: u32,
+    costs: Vec<u64>,
+    inputs: Arc<Inputs>,
+}
+
+/// The engine benches run in, metering fuel as the sim does.
+pub fn bench_engine() -> anyhow::Result<Engine> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config)
+        .map_err(anyhow::Error::from)
+        .context("trying to set up wasmtime for the bench")
+}
+
+/// Compiles the fleet and runs `ticks` of it, with its imports stubbed to return `inputs`, measuring
+/// the fuel each tick takes.
+pub fn bench_wasm(
+    engine: &Engine,
+    bytes: &[u8],
+    ticks: u32,
+    inputs: Arc<Inputs>,
+) -> anyhow::Result<Measurement> {
+    let compiling = Instant::now();
+    let module = Module::new(engine, bytes).map_err(anyhow::Error::from)?;
+    let compile_secs = compiling.elapsed().as_secs_f64();
+
+    let mut linker = Linker::new(engine);
+    for import in module.imports() {
+        let ExternType::Func(ty) = import.ty() else {
+            anyhow::bail!(
+                "it imports `{}::{}`, which isn't a function",
+                import.module(),
+                import.name()
+            );
+        };
+        let (module_name, name) = (import.module().to_owned(), import.name().to_owned());
+        let result_types: Vec<ValType> = ty.results().collect();
+        linker.func_new(
+            import.module(),
+            import.name(),
+            ty,
+            move |mut caller, params, results| {
+                match name.as_str() {
+                    "sched_yield" => {
+                        let remaining = caller.get_fuel()?;
+                        let state: &mut TickState = caller.data_mut();
+                        state.costs.push(TICK_FUEL - remaining);
+                        if state.costs.len() as u32 >= state.target {
+                            return Err(wasmtime::Error::new(Stop::Done));
+                        }
+                        caller.set_fuel(TICK_FUEL)?;
+                    }
+                    "proc_exit" => {
+                        let code = params.first().and_then(Val::i32).unwrap_or(0);
+                        return Err(wasmtime::Error::new(Stop::Exited(code)));
+                    }
+                    _ => {}
+                }
+                let tick = caller.data().costs.len() as u32;
+                let inputs = Arc::clone(&caller.data().inputs);
+                for (result, ty) in results.iter_mut().zip(&result_types) {
+                    *result = inputs.value(&module_name, &name, tick, ty);
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    let state = TickState {
+        target: ticks,
+        costs: Vec::new(),
+        inputs,
+    };
+    let mut store = Store::new(engine, state);
+    store.set_fuel(TICK_FUEL)?;
+    let instantiating = Instant::now();
+    let instance = linker.instantiate(&mut store, &module)?;
+    if let Ok(initialize) = instance.get_typed_func::<(), ()>(&mut store, "_initialize") {
+        initialize.call(&mut store, ())?;
+    }
+    let instantiate_secs = instantiating.elapsed().as_secs_f64();
+
+    let memory = instance.get_memory(&mut store, "memory");
+    let memory_size = |store: &Store<TickState>| memory.map_or(0, |memory| memory.data_size(store));
+    let memory_start = memory_size(&store) as u64;
+
+    let Some(entry) = ENTRY_POINTS
+        .iter()
+        .find_map(|name| instance.get_typed_func::<(), ()>(&mut store, name).ok())
+    else {
+        anyhow::bail!(
+            "it doesn't export an entry point, one of `{}`",
+            ENTRY_POINTS.join("`, `")
+        );
+    };
+    store.set_fuel(TICK_FUEL)?;
+    let result = entry.call(&mut store, ());
+
+    let tick = store.data().costs.len();
+    let (stopped, failed) = match result {
+        Ok(()) => (Some(format!("returned on tick {tick}")), false),
+        Err(err) => match (err.downcast_ref::<Stop>(), err.downcast_ref::<Trap>()) {
+            (Some(Stop::Done), _) => (None, false),
+            (Some(Stop::Exited(0)), _) => (Some(format!("exited on tick {tick}")), false),
+            (Some(Stop::Exited(code)), _) => (
+                Some(format!("exited with code {code} on tick {tick}")),
+                true,
+            ),
+            (None, Some(Trap::OutOfFuel)) => (
+                Some(format!(
+                    "ran out of fuel on tick {tick}, is it stuck in a loop?"
+                )),
+                true,
+            ),
+            (None, Some(trap)) => (Some(format!("trapped on tick {tick}: {trap}")), true),
+            (None, None) => (Some(format!("failed on tick {tick}: {err:#}")), true),
+        },
+    };
+
+    Ok(Measurement {
+        ticks: tick as u32,
+        fuel: FuelStats::of(&store.data().costs),
+        compile_secs,
+        instantiate_secs,
+        memory_start,
+        memory_end: memory_size(&store) as u64,
+        stopped,
+        failed,
+    })
+}
+
+/// A fleet as `bench` measured it.
+#[derive(Serialize, Debug, Clone)]
+struct FleetBench {
+    fleet: String,
+    path: PathBuf,
+    #[serde(flatten)]
+    measurement: Measurement,
+}
+
+/// The fleets the arguments name: paths to wasm files as they are, and names among the built fleets.
+fn bench_fleets(names: &[String], debug: bool) -> anyhow::Result<Vec<Fleet>> {
+    let mut built = None;
+    let mut fleets = Vec::new();
+    for name in names {
+        let path = Path::new(name);
+        if path.extension().is_some_and(|ext| ext == "wasm") || path.is_file() {
+            fleets.push(Fleet::from_external(path)?);
+            continue;
+        }
+        let built = match &built {
+            Some(built) => built,
+            None => built.insert(find_built_fleets(debug)?),
+        };
+        fleets.push(find_fleet(built, name)?);
+    }
+    Ok(fleets)
+}
+
+fn millis(secs: f64) -> String {
+    format!("{:.1}ms", secs * 1000.0)
+}
+
+/// How `b` compares to `a`, as a percentage when they're both there.
+fn change(a: Option<f64>, b: Option<f64>) -> String {
+    match (a, b) {
+        (Some(a), Some(b)) if a > 0.0 => format!("{:+.1}%", (b - a) / a * 100.0),
+        _ => String::new(),
+    }
+}
+
+/// The measurements as a table, a column per fleet, and for two fleets how the second compares.
+fn bench_table(benches: &[FleetBench]) -> String {
+    let fuel =
+        |bench: &FleetBench, stat: fn(&FuelStats) -> f64| bench.measurement.fuel.as_ref().map(stat);
+    type Stat = fn(&FuelStats) -> f64;
+    let stats: [(&str, Stat); 4] = [
+        ("fuel per tick, min", |fuel| fuel.min as f64),
+        ("fuel per tick, mean", |fuel| fuel.mean),
+        ("fuel per tick, p99", |fuel| fuel.p99 as f64),
+        ("fuel per tick, max", |fuel| fuel.max as f64),
+    ];
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let compare = benches.len() == 2;
+    let mut header = vec![String::new()];
+    header.extend(benches.iter().map(|bench| bench.fleet.clone()));
+    if compare {
+        header.push("change".to_owned());
+    }
+    rows.push(header);
+
+    let mut row = |label: &str, cells: Vec<String>, changed: String| {
+        let mut row = vec![label.to_owned()];
+        row.extend(cells);
+        if compare {
+            row.push(changed);
+        }
+        rows.push(row);
+    };
+    row(
+        "ticks",
+        benches
+            .iter()
+            .map(|bench| bench.measurement.ticks.to_string())
+            .collect(),
+        String::new(),
+    );
+    for (label, stat) in stats {
+        let values: Vec<Option<f64>> = benches.iter().map(|bench| fuel(bench, stat)).collect();
+        let cells = values
+            .iter()
+            .map(|value| value.map_or("-".to_owned(), |value| format!("{value:.0}")))
+            .collect();
+        let changed = change(values[0], values.get(1).copied().flatten());
+        row(label, cells, changed);
+    }
+    type Time = fn(&Measurement) -> f64;
+    let times: [(&str, Time); 2] = [
+        ("compile time", |measurement| measurement.compile_secs),
+        ("instantiation time", |measurement| {
+            measurement.instantiate_secs
+        }),
+    ];
+    for (label, time) in times {
+        let cells = benches
+            .iter()
+            .map(|bench| millis(time(&bench.measurement)))
+            .collect();
+        row(label, cells, String::new());
+    }
+    let memory = benches
+        .iter()
+        .map(|bench| {
+            let (start, end) = (bench.measurement.memory_start, bench.measurement.memory_end);
+            if end > start {
+                format!(
+                    "{} → {} (+{})",
+                    ByteSize::b(start),
+                    ByteSize::b(end),
+                    ByteSize::b(end - start)
+                )
+            } else {
+                ByteSize::b(start).to_string()
+            }
+        })
+        .collect();
+    let memory_change = change(
+        benches
+            .first()
+            .map(|bench| bench.measurement.memory_end as f64),
+        benches
+            .get(1)
+            .map(|bench| bench.measurement.memory_end as f64),
+    );
+    row("memory", memory, memory_change);
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    for bench in benches {
+        if let Some(stopped) = &bench.measurement.stopped {
+            writeln!(table, "'{}' {stopped}", bench.fleet).unwrap();
+        }
+    }
+    table
+}
+
+/// Runs each fleet's ticks in wasmtime and reports the fuel they cost, with how long it took to
+/// start and how much its memory grew. Fails if a fleet traps or gets stuck.
+pub fn bench(args: BenchArgs) -> anyhow::Result<()> {
+    let fleets = bench_fleets(&args.fleets, args.debug)?;
+    let inputs = Arc::new(match &args.inputs {
+        Some(path) => Inputs::read(path)?,
+        None => Inputs::default(),
+    });
+    let engine = bench_engine()?;
+
+    let mut benches = Vec::new();
+    for fleet in fleets {
+        say!("Running {} ticks of '{}'", args.ticks, fleet.name);
+        let bytes = std::fs::read(&fleet.path)
+            .with_context(|| format!("trying to read fleet {:?}", fleet.path))?;
+        let measurement = bench_wasm(&engine, &bytes, args.ticks, Arc::clone(&inputs))
+            .with_context(|| format!("trying to bench '{}'", fleet.name))?;
+        benches.push(FleetBench {
+            fleet: fleet.name,
+            path: fleet.path,
+            measurement,
+        });
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&benches)?);
+    } else {
+        say!("{}", bench_table(&benches).trim_end());
+    }
+
+    let failed: Vec<&str> = benches
+        .iter()
+        .filter(|bench| bench.measurement.failed)
+        .map(|bench| bench.fleet.as_str())
+        .collect();
+    if !failed.is_empty() {
+        anyhow::bail!("'{}' failed partway through", failed.join("', '"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{bench_engine, bench_wasm, FuelStats, Inputs, Measurement};
+
+    fn bench(wat: &str, ticks: u32, inputs: &str) -> anyhow::Result<Measurement> {
+        let inputs: Inputs = serde_json::from_str(inputs)?;
+        bench_wasm(
+            &bench_engine()?,
+            &wat::parse_str(wat)?,
+            ticks,
+            Arc::new(inputs),
+        )
+    }
+
+    #[test]
+    fn fuel_stats_has_the_99th_percentile() {
+        let costs: Vec<u64> = (1..=200).collect();
+        let stats = FuelStats::of(&costs).unwrap();
+        assert_eq!((stats.min, stats.p99, stats.max), (1, 198, 200));
+        assert_eq!(stats.mean, 100.5);
+        assert_eq!(FuelStats::of(&[]), None);
+    }
+
+    // Each tick spins for as many rounds as the input says, so later ticks cost more
+    const SPINNER: &str = r#"(module
+        (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+        (import "protologic" "work" (func $work (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start") (local $n i32)
+            (loop $tick
+                (local.set $n (call $work))
+                (loop $spin
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $spin (i32.gt_s (local.get $n) (i32.const 0))))
+                (drop (memory.grow (i32.const 1)))
+                (drop (call $yield))
+                (br $tick))))"#;
+
+    #[test]
+    fn bench_wasm_measures_each_tick() -> anyhow::Result<()> {
+        let measurement = bench(SPINNER, 4, r#"{"work": [10, 10, 1000]}"#)?;
+        assert_eq!(measurement.ticks, 4);
+        assert_eq!(measurement.stopped, None);
+        let fuel = measurement.fuel.unwrap();
+        assert!(fuel.max > fuel.min * 10, "{fuel:?}");
+        assert_eq!(measurement.memory_start, 65536);
+        assert_eq!(measurement.memory_end, 5 * 65536);
+
+        let zeros = bench(SPINNER, 4, "{}")?;
+        assert!(zeros.fuel.unwrap().max < fuel.max);
+        Ok(())
+    }
+
+    #[test]
+    fn bench_wasm_reports_fleets_that_stop() -> anyhow::Result<()> {
+        let trapped = bench(
+            r#"(module
+                (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+                (func (export "_start") (drop (call $yield)) unreachable))"#,
+            10,
+            "{}",
+        )?;
+        assert!(trapped.failed);
+        assert_eq!(trapped.ticks, 1);
+        assert!(trapped.stopped.unwrap().starts_with("trapped on tick 1"));
+
+        let returned = bench(r#"(module (func (export "main")))"#, 10, "{}")?;
+        assert!(!returned.failed);
+        assert_eq!(returned.stopped.as_deref(), Some("returned on tick 0"));
+        assert_eq!(returned.fuel, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+//! Building a fleet once and optimizing it every way wasm-opt can, to see which way pays off.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Serialize;
+use wasm_opt::OptimizationOptions;
+
+use crate::bench::{bench_engine, bench_wasm, FuelStats, Inputs};
+use crate::events::{emit, Event, Phase};
+use crate::manifest::artifact_file_name;
+use crate::output::say;
+
+/// The directory in the fleet output directory that `bench-opt` optimizes into. Each run replaces it.
+pub const BENCH_OPT_DIR: &str = "bench-opt";
+
+/// Arguments for `cargo protologic bench-opt`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchOptArgs {
+    /// The workspace package to build, by its package or fleet name.
+    package: String,
+    /// How many ticks to run each optimized build for.
+    #[arg(long, value_name = "N", default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+    ticks: u32,
+    /// A JSON file of what the sim's functions return on each tick, as `bench` takes it.
+    #[arg(long, value_name = "PATH")]
+    inputs: Option<PathBuf>,
+    /// Print the measurements as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// What wasm-opt optimizes for, as its `-O` flags say it.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    O2,
+    O3,
+    O4,
+    Os,
+    Oz,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [Self::O2, Self::O3, Self::O4, Self::Os, Self::Oz];
+
+    /// The level named like `O4`, as the `wasm-opt.level` setting names it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.to_string() == name)
+    }
+
+    pub fn options(self) -> OptimizationOptions {
+        match self {
+            Self::O2 => OptimizationOptions::new_opt_level_2(),
+            Self::O3 => OptimizationOptions::new_opt_level_3(),
+            Self::O4 => OptimizationOptions::new_opt_level_4(),
+            Self::Os => OptimizationOptions::new_optimize_for_size(),
+            Self::Oz => OptimizationOptions::new_optimize_for_size_aggressively(),
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::O2 => "O2",
+            Self::O3 => "O3",
+            Self::O4 => "O4",
+            Self::Os => "Os",
+            Self::Oz => "Oz",
+        })
+    }
+}
+
+/// One way of running wasm-opt over the fleet.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct OptConfig {
+    level: Level,
+    /// Whether wasm-opt runs its passes again until they stop shrinking the fleet.
+    converge: bool,
+}
+
+impl OptConfig {
+    /// Every level, without and with converging.
+    fn matrix() -> Vec<OptConfig> {
+        Level::ALL
+            .into_iter()
+            .flat_map(|level| [false, true].map(|converge| OptConfig { level, converge }))
+            .collect()
+    }
+
+    /// Optimizes the fleet at `input` this way into `output`.
+    fn run(self, input: &Path, output: &Path) -> anyhow::Result<()> {
+        if !self.converge {
+            return Ok(crate::with_fleet_passes(self.level.options(), false).run(input, output)?);
+        }
+
+        // Converging reruns every pass, and Asyncify can't run over its own output, so the fleet
+        // is converged first, then goes through the usual passes with Asyncify
+        let converged = output.with_extension("converged.wasm");
+        let mut options = self.level.options();
+        options
+            .set_converge()
+            .enable_feature(wasm_opt::Feature::BulkMemory)
+            .enable_feature(wasm_opt::Feature::Simd);
+        options.run(input, &converged)?;
+        crate::with_fleet_passes(self.level.options(), false).run(&converged, output)?;
+        std::fs::remove_file(&converged).with_context(|| format!("trying to delete {converged:?}"))
+    }
+
+    /// The file name of the fleet optimized this way, like `glass_cannon-O4-converge.wasm`.
+    fn file_name(self, fleet: &str) -> String {
+        let converge = if self.converge { "-converge" } else { "" };
+        format!("{fleet}-{}{converge}.wasm", self.level)
+    }
+}
+
+impl std::fmt::Display for OptConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "-{}", self.level)?;
+        if self.converge {
+            f.write_str(" --converge")?;
+        }
+        Ok(())
+    }
+}
+
+/// The fleet optimized one way, and what that did for it.
+#[derive(Serialize, Debug, Clone)]
+struct OptResult {
+    #[serde(flatten)]
+    config: OptConfig,
+    path: PathBuf,
+    size: u64,
+    opt_secs: f64,
+    fuel: Option<FuelStats>,
+    /// Why the build couldn't be measured, like it trapping partway through.
+    error: Option<String>,
+}
+
+/// Which configuration did best by each measure, by their index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Winners {
+    size: Option<usize>,
+    opt_time: Option<usize>,
+    fuel_mean: Option<usize>,
+    fuel_p99: Option<usize>,
+}
+
+impl Winners {
+    fn of(results: &[OptResult]) -> Self {
+        fn lowest(
+            results: &[OptResult],
+            measure: impl Fn(&OptResult) -> Option<f64>,
+        ) -> Option<usize> {
+            results
+                .iter()
+                .enumerate()
+                .filter_map(|(i, result)| Some((i, measure(result)?)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(i, _)| i)
+        }
+
+        Self {
+            size: lowest(results, |result| Some(result.size as f64)),
+            opt_time: lowest(results, |result| Some(result.opt_secs)),
+            // Fuel up to a trap isn't comparable with a build that ran all its ticks
+            fuel_mean: lowest(results, |result| match result.error {
+                None => Some(result.fuel?.mean),
+                Some(_) => None,
+            }),
+            fuel_p99: lowest(results, |result| match result.error {
+                None => Some(result.fuel?.p99 as f64),
+                Some(_) => None,
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct BenchOptReport<'a> {
+    package: &'a str,
+    ticks: u32,
+    configs: &'a [OptResult],
+    /// The best configuration by each measure, like `-Oz --converge`.
+    best: BTreeMap<&'static str, String>,
+}
+
+/// The results as a table, a row per configuration, with the best by each measure starred.
+fn bench_opt_table(results: &[OptResult], winners: Winners) -> String {
+    let star = |winner: Option<usize>, i: usize, cell: String| {
+        if winner == Some(i) {
+            format!("{cell} *")
+        } else {
+            cell
+        }
+    };
+
+    let mut rows = vec![[
+        "wasm-opt".to_owned(),
+        "size".to_owned(),
+        "opt time".to_owned(),
+        "fuel per tick, mean".to_owned(),
+        "fuel per tick, p99".to_owned(),
+    ]];
+    for (i, result) in results.iter().enumerate() {
+        let fuel = |stat: fn(&FuelStats) -> String| match (&result.fuel, &result.error) {
+            (_, Some(_)) => "failed".to_owned(),
+            (Some(fuel), None) => stat(fuel),
+            (None, None) => "-".to_owned(),
+        };
+        rows.push([
+            result.config.to_string(),
+            star(winners.size, i, ByteSize::b(result.size).to_string()),
+            star(winners.opt_time, i, format!("{:.2}s", result.opt_secs)),
+            star(
+                winners.fuel_mean,
+                i,
+                fuel(|fuel| format!("{:.0}", fuel.mean)),
+            ),
+            star(winners.fuel_p99, i, fuel(|fuel| fuel.p99.to_string())),
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut table = String::new();
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    writeln!(table, "* the best by that measure").unwrap();
+    for result in results {
+        if let Some(error) = &result.error {
+            writeln!(table, "{} {error}", result.config).unwrap();
+        }
+    }
+    table
+}
+
+/// Builds the package once, optimizes it with every wasm-opt configuration in the matrix, and
+/// measures the size, how long optimizing took and the fuel per tick of each.
+pub fn bench_opt(args: BenchOptArgs) -> anyhow::Result<()> {
+    let metadata = crate::cargo_metadata()?;
+    let package = metadata.workspace_package(&args.package)?;
+    let configs = OptConfig::matrix();
+    let dir = crate::fleet_output_path(false)?.join(BENCH_OPT_DIR);
+    if crate::output::dry_run() {
+        say!(
+            "Would build '{}' with: {}",
+            package.name,
+            crate::shell_command(&crate::build_command(&package.name, &[], false)?)
+        );
+        say!(
+            "Would optimize it {} ways into {dir:?}, and run {} ticks of each",
+            configs.len(),
+            args.ticks
+        );
+        return Ok(());
+    }
+    let inputs = Arc::new(match &args.inputs {
+        Some(path) => Inputs::read(path)?,
+        None => Inputs::default(),
+    });
+
+    emit(Event::PhaseStarted {
+        phase: Phase::Build,
+    });
+    say!("Building '{}'...", package.name);
+    let status = crate::build(&package.name, &[], false)?
+        .wait()
+        .context("trying to wait until the `cargo build` execution has finished")?;
+    if !status.success() {
+        anyhow::bail!(
+            "cargo couldn't build '{}', it exited with {status}",
+            package.name
+        );
+    }
+    emit(Event::PackageBuilt {
+        package: package.name.clone(),
+    });
+
+    // Builds from an earlier run could be from other code
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("trying to clear out old builds in {dir:?}"))?;
+    }
+    std::fs::create_dir_all(&dir).with_context(|| format!("trying to create {dir:?}"))?;
+
+    let fleet = artifact_file_name(&package.name);
+    let cargo_output = crate::cargo_output_base_path(false)?.join(&fleet);
+    let fleet = fleet.trim_end_matches(".wasm");
+    let engine = bench_engine()?;
+    emit(Event::PhaseStarted {
+        phase: Phase::Optimize,
+    });
+    let mut results = Vec::new();
+    for (i, &config) in configs.iter().enumerate() {
+        say!("Optimizing {} of {}: {config}", i + 1, configs.len());
+        let path = dir.join(config.file_name(fleet));
+        let optimizing = Instant::now();
+        config
+            .run(&cargo_output, &path)
+            .with_context(|| format!("trying to optimize '{}' with {config}", package.name))?;
+        let opt_secs = optimizing.elapsed().as_secs_f64();
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("trying to read the build {path:?}"))?;
+
+        let (fuel, error) = match bench_wasm(&engine, &bytes, args.ticks, Arc::clone(&inputs)) {
+            Ok(measurement) if measurement.failed => (measurement.fuel, measurement.stopped),
+            Ok(measurement) => (measurement.fuel, None),
+            Err(err) => (None, Some(format!("couldn't be run: {err:#}"))),
+        };
+        results.push(OptResult {
+            config,
+            path,
+            size: bytes.len() as u64,
+            opt_secs,
+            fuel,
+            error,
+        });
+    }
+
+    let winners = Winners::of(&results);
+    if args.json {
+        let best = [
+            ("size", winners.size),
+            ("opt_time", winners.opt_time),
+            ("fuel_mean", winners.fuel_mean),
+            ("fuel_p99", winners.fuel_p99),
+        ]
+        .into_iter()
+        .filter_map(|(measure, winner)| Some((measure, results[winner?].config.to_string())))
+        .collect();
+        let report = BenchOptReport {
+            package: &package.name,
+            ticks: args.ticks,
+            configs: &results,
+            best,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        say!("{}", bench_opt_table(&results, winners).trim_end());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{bench_opt_table, Level, OptConfig, OptResult, Winners};
+    use crate::bench::FuelStats;
+
+    fn result(level: Level, size: u64, mean: Option<f64>) -> OptResult {
+        OptResult {
+            config: OptConfig {
+                level,
+                converge: false,
+            },
+            path: PathBuf::new(),
+            size,
+            opt_secs: size as f64 / 1000.0,
+            fuel: mean.map(|mean| FuelStats {
+                min: 0,
+                mean,
+                p99: mean as u64,
+                max: mean as u64,
+            }),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn the_matrix_has_every_level_with_and_without_converging() {
+        let configs = OptConfig::matrix();
+        assert_eq!(configs.len(), 10);
+        assert_eq!(configs[0].to_string(), "-O2");
+        assert_eq!(configs[9].to_string(), "-Oz --converge");
+        assert_eq!(
+            configs[5].file_name("glass_cannon"),
+            "glass_cannon-O4-converge.wasm"
+        );
+    }
+
+    #[test]
+    fn winners_are_the_lowest_of_what_was_measured() {
+        let results = [
+            result(Level::O4, 3000, Some(120.0)),
+            result(Level::Oz, 2000, None),
+            result(Level::O2, 4000, Some(140.0)),
+        ];
+        let winners = Winners::of(&results);
+        assert_eq!(winners.size, Some(1));
+        assert_eq!(winners.opt_time, Some(1));
+        assert_eq!(winners.fuel_mean, Some(0));
+        assert_eq!(winners.fuel_p99, Some(0));
+
+        let table = bench_opt_table(&results, winners);
+        let rows: Vec<&str> = table.lines().collect();
+        assert!(rows[1].starts_with("-O4 "), "{table}");
+        assert!(rows[1].ends_with("120 *"), "{table}");
+        assert!(rows[2].contains("2.0 KB *"), "{table}");
+    }
+}
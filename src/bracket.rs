@@ -0,0 +1,310 @@
+//! Single-elimination brackets: who meets whom, who goes through, and where everyone placed.
+//!
+//! Fleets are known here by their seed, from 0 for the best.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+/// One series of a bracket, between the winners of two series of the round before.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Series {
+    /// Numbered through the bracket from 0, round by round.
+    pub id: usize,
+    /// The round it's in, from 0 for the first.
+    pub round: usize,
+    /// The seed on each side, `None` for a bye in the first round or while it's still to be decided
+    /// in later ones.
+    pub seeds: [Option<usize>; 2],
+    /// Games won by each side.
+    pub wins: [u32; 2],
+    /// Games played, draws and failed games included.
+    pub played: u32,
+    pub winner: Option<usize>,
+}
+
+impl Series {
+    /// Whether one side goes straight through, for want of anyone to play.
+    pub fn is_bye(&self) -> bool {
+        self.round == 0 && self.seeds.contains(&None)
+    }
+
+    /// The seed on the other side from `winner`, once there's been a winner.
+    pub fn loser(&self) -> Option<usize> {
+        let winner = self.winner?;
+        self.seeds
+            .into_iter()
+            .flatten()
+            .find(|&seed| seed != winner)
+    }
+}
+
+/// Games won by each side of a series, and how many it's played, as [`resolve`] asks for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeriesScore {
+    pub wins: [u32; 2],
+    pub played: u32,
+}
+
+/// How many places the first round of a bracket for `count` fleets has, with byes for the rest.
+pub fn bracket_size(count: usize) -> usize {
+    count.next_power_of_two().max(2)
+}
+
+/// The seeds in the first round's places, in order, so the best seeds can only meet as late as
+/// possible, like `[0, 7, 3, 4, 1, 6, 2, 5]` for 8. Any byes go to the best seeds.
+pub fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let len = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|&seed| [seed, len - 1 - seed])
+            .collect();
+    }
+    order
+}
+
+/// What a round is called, from 0 for the first of `rounds`, like `Semi-finals`.
+pub fn round_name(round: usize, rounds: usize) -> String {
+    match rounds - round {
+        1 => "Final".to_owned(),
+        2 => "Semi-finals".to_owned(),
+        3 => "Quarter-finals".to_owned(),
+        left => format!("Round of {}", 1 << left),
+    }
+}
+
+/// Which seed plays as fleet A and which as fleet B in a game of a series, from 1. The better seed
+/// is fleet A in odd games, so the sides swap every game.
+pub fn sides(seeds: [usize; 2], game: u32) -> [usize; 2] {
+    let better = seeds[0].min(seeds[1]);
+    let worse = seeds[0].max(seeds[1]);
+    if game % 2 == 1 {
+        [better, worse]
+    } else {
+        [worse, better]
+    }
+}
+
+/// Who's won a best-of-`best_of` series between `seeds`, if anyone yet.
+///
+/// A side with a majority of the games goes through, or whoever's ahead once `best_of` have been
+/// played, since draws and failed games can leave no one with a majority. Still tied, the series
+/// goes on a game at a time until someone's ahead, for up to `best_of` more games, and then the
+/// better seed goes through.
+pub fn series_winner(best_of: u32, seeds: [usize; 2], score: SeriesScore) -> Option<usize> {
+    let leader = match score.wins[0].cmp(&score.wins[1]) {
+        Ordering::Greater => Some(seeds[0]),
+        Ordering::Less => Some(seeds[1]),
+        Ordering::Equal => None,
+    };
+    if score.wins[0].max(score.wins[1]) > best_of / 2
+        || (score.played >= best_of && leader.is_some())
+    {
+        return leader;
+    }
+    (score.played >= best_of * 2).then(|| seeds[0].min(seeds[1]))
+}
+
+/// Works out the bracket for `count` fleets as far as it's been played, round by round, given
+/// the `score` of each series so far by its id and seeds.
+pub fn resolve(
+    count: usize,
+    best_of: u32,
+    mut score: impl FnMut(usize, [usize; 2]) -> SeriesScore,
+) -> Vec<Vec<Series>> {
+    let mut sides: Vec<Option<usize>> = seed_order(bracket_size(count))
+        .into_iter()
+        .map(|seed| (seed < count).then_some(seed))
+        .collect();
+    let mut rounds: Vec<Vec<Series>> = Vec::new();
+    let mut id = 0;
+
+    while sides.len() > 1 {
+        let round = rounds.len();
+        let series: Vec<Series> = sides
+            .chunks(2)
+            .map(|pair| {
+                let mut series = Series {
+                    id,
+                    round,
+                    seeds: [pair[0], pair[1]],
+                    ..Series::default()
+                };
+                id += 1;
+                match series.seeds {
+                    [Some(a), Some(b)] => {
+                        let seeds = [a, b];
+                        let played = score(series.id, seeds);
+                        series.wins = played.wins;
+                        series.played = played.played;
+                        series.winner = series_winner(best_of, seeds, played);
+                    }
+                    [Some(seed), None] | [None, Some(seed)] if round == 0 => {
+                        series.winner = Some(seed);
+                    }
+                    _ => {}
+                }
+                series
+            })
+            .collect();
+        sides = series.iter().map(|series| series.winner).collect();
+        rounds.push(series);
+    }
+    rounds
+}
+
+/// The winner of the bracket, once the final's been decided.
+pub fn champion(rounds: &[Vec<Series>]) -> Option<usize> {
+    rounds.last()?.first()?.winner
+}
+
+/// The places decided so far, as the place and the seed, best first. Everyone knocked out in the
+/// same round shares a place, like two third places for the losing semi-finalists.
+pub fn placements(rounds: &[Vec<Series>]) -> Vec<(u32, usize)> {
+    let mut placements: Vec<(u32, usize)> =
+        champion(rounds).map(|seed| (1, seed)).into_iter().collect();
+    for (round, series) in rounds.iter().enumerate() {
+        // Knocked out in the final is 2nd, in the semi-finals 3rd, in the quarter-finals 5th...
+        let place = (1 << (rounds.len() - round - 1)) + 1;
+        placements.extend(
+            series
+                .iter()
+                .filter_map(Series::loser)
+                .map(|seed| (place, seed)),
+        );
+    }
+    placements.sort();
+    placements
+}
+
+/// What the winner of a series did, in a bracket of `rounds` rounds.
+pub fn went_on(series: &Series, rounds: usize) -> &'static str {
+    if series.round + 1 == rounds {
+        "wins the tournament"
+    } else {
+        "goes through"
+    }
+}
+
+/// The bracket round by round, with each fleet as its seed and name from `names`.
+pub fn describe_bracket(names: &[String], rounds: &[Vec<Series>]) -> String {
+    let side = |seed: Option<usize>| {
+        seed.map_or_else(
+            || "(to be decided)".to_owned(),
+            |seed| format!("({}) '{}'", seed + 1, names[seed]),
+        )
+    };
+
+    let mut description = String::new();
+    for (round, series) in rounds.iter().enumerate() {
+        if round > 0 {
+            description.push('\n');
+        }
+        write!(description, "{}:", round_name(round, rounds.len())).unwrap();
+        for series in series {
+            let [a, b] = series.seeds;
+            if series.is_bye() {
+                write!(description, "\n  {} has a bye", side(a.or(b))).unwrap();
+                continue;
+            }
+            write!(description, "\n  {} vs {}", side(a), side(b)).unwrap();
+            if series.played > 0 {
+                write!(description, ", {} - {}", series.wins[0], series.wins[1]).unwrap();
+                let undecided = series.played - series.wins[0] - series.wins[1];
+                if undecided > 0 {
+                    write!(description, " with {undecided} drawn or failed").unwrap();
+                }
+            }
+            if let Some(winner) = series.winner {
+                write!(
+                    description,
+                    ", '{}' {}",
+                    names[winner],
+                    went_on(series, rounds.len())
+                )
+                .unwrap();
+            }
+        }
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bracket_size, champion, describe_bracket, placements, resolve, round_name, seed_order,
+        series_winner, sides, SeriesScore,
+    };
+
+    fn score(wins: [u32; 2], played: u32) -> SeriesScore {
+        SeriesScore { wins, played }
+    }
+
+    #[test]
+    fn the_best_seeds_meet_last_and_get_the_byes() {
+        assert_eq!(seed_order(2), [0, 1]);
+        assert_eq!(seed_order(8), [0, 7, 3, 4, 1, 6, 2, 5]);
+        assert_eq!(bracket_size(2), 2);
+        assert_eq!(bracket_size(5), 8);
+        assert_eq!(round_name(0, 4), "Round of 16");
+        assert_eq!(round_name(3, 4), "Final");
+
+        // Five fleets: seeds 0, 1 and 2 have byes into the semi-finals
+        let rounds = resolve(5, 1, |_, _| SeriesScore::default());
+        assert_eq!(rounds.len(), 3);
+        let byes: Vec<Option<usize>> = rounds[0]
+            .iter()
+            .filter(|series| series.is_bye())
+            .map(|series| series.winner)
+            .collect();
+        assert_eq!(byes, [Some(0), Some(1), Some(2)]);
+        assert_eq!(rounds[1][0].seeds, [Some(0), None]);
+        assert_eq!(rounds[1][1].seeds, [Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn series_go_to_the_majority_or_whoever_leads_at_the_end() {
+        assert_eq!(series_winner(3, [4, 1], score([1, 1], 2)), None);
+        assert_eq!(series_winner(3, [4, 1], score([2, 0], 2)), Some(4));
+        // A draw leaves it to whoever's ahead after three games
+        assert_eq!(series_winner(3, [4, 1], score([0, 1], 3)), Some(1));
+        assert_eq!(series_winner(3, [4, 1], score([1, 1], 3)), None);
+        assert_eq!(series_winner(3, [4, 1], score([1, 1], 6)), Some(1));
+        assert_eq!(series_winner(1, [0, 1], score([0, 0], 2)), Some(0));
+
+        assert_eq!(sides([4, 1], 1), [1, 4]);
+        assert_eq!(sides([4, 1], 2), [4, 1]);
+    }
+
+    #[test]
+    fn winners_advance_to_the_final_and_losers_are_placed() {
+        let names: Vec<String> = ["a", "b", "c", "d"].map(str::to_owned).to_vec();
+        // The worse seed wins every series
+        let rounds = resolve(4, 1, |_, _| score([0, 1], 1));
+        assert_eq!(champion(&rounds), Some(2));
+        assert_eq!(rounds[1][0].seeds, [Some(3), Some(2)]);
+        assert_eq!(placements(&rounds), [(1, 2), (2, 3), (3, 0), (3, 1)]);
+        assert_eq!(
+            describe_bracket(&names, &rounds),
+            "Semi-finals:\n  (1) 'a' vs (4) 'd', 0 - 1, 'd' goes through\n  (2) 'b' vs (3) 'c', 0 - 1, 'c' goes through\nFinal:\n  (4) 'd' vs (3) 'c', 0 - 1, 'c' wins the tournament"
+        );
+
+        // Nothing's decided past a series still being played
+        let rounds = resolve(3, 3, |id, _| {
+            if id == 1 {
+                score([1, 0], 2)
+            } else {
+                SeriesScore::default()
+            }
+        });
+        assert_eq!(rounds[1][0].seeds, [Some(0), None]);
+        assert_eq!(champion(&rounds), None);
+        assert!(placements(&rounds).is_empty());
+        assert!(describe_bracket(&names, &rounds).ends_with(
+            "  (2) 'b' vs (3) 'c', 1 - 0 with 1 drawn or failed\nFinal:\n  (1) 'a' vs (to be decided)"
+        ));
+    }
+}
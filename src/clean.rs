@@ -0,0 +1,345 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+
+use crate::archive::FLEET_OBJECTS_DIR;
+use crate::bench_opt::BENCH_OPT_DIR;
+use crate::compare::COMPARE_DIR;
+use crate::config::find_battle_output_dir;
+use crate::history::configured_history_path;
+use crate::manifest::MANIFEST_FILE;
+use crate::matrix::MATRIX_DIR;
+use crate::output::{dry_run, say};
+use crate::ratings::RATINGS_FILE;
+use crate::replay::replay_files;
+use crate::tournament::tournaments_dir;
+use crate::{DEBUG_FLEET_DIR, FLEET_OUTPUT_DIR};
+
+/// Something `clean` can delete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanTarget {
+    /// The optimized fleets, their manifests, the feature matrix's variants, `bench-opt`'s builds and `compare`'s builds at git refs.
+    Fleets,
+    /// Replays with their results and logs, and the archived fleets, in the battle output
+    /// directory.
+    Replays,
+    /// The battle history log, and the Elo ratings rated battles keep next to it.
+    History,
+}
+
+impl CleanTarget {
+    pub const ALL: [CleanTarget; 3] = [Self::Fleets, Self::Replays, Self::History];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Fleets => "built fleets",
+            Self::Replays => "replays",
+            Self::History => "battle history and Elo ratings",
+        }
+    }
+
+    /// What there is to delete of this, found the way the other subcommands find it.
+    fn paths(
+        self,
+        output_dir: Option<&Path>,
+        history_file: Option<&Path>,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let fleet_dir = Path::new(FLEET_OUTPUT_DIR);
+        match self {
+            Self::Fleets => fleet_files(fleet_dir),
+            Self::Replays => {
+                let (dir, _) = find_battle_output_dir(output_dir.map(Path::to_owned))?;
+                battle_output_files(&dir)
+            }
+            Self::History => {
+                let history = configured_history_path(history_file.map(Path::to_owned))?;
+                Ok(history_files(history, fleet_dir))
+            }
+        }
+    }
+}
+
+/// Deletes the `targets`, printing how much space that frees. With no targets, it asks which on a
+/// terminal, and refuses otherwise, so a script can't delete everything by leaving the flags off.
+///
+/// Only files this tool names and places are deleted, found the way the other subcommands find
+/// them, so anything else kept alongside them is safe.
+pub fn clean(
+    targets: Vec<CleanTarget>,
+    output_dir: Option<PathBuf>,
+    history_file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let mut found = Vec::new();
+    if targets.is_empty() {
+        if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+            anyhow::bail!(
+                "pick what to clean with `--fleets`, `--replays`, `--history` or `--all`"
+            );
+        }
+        for target in CleanTarget::ALL {
+            found.push((
+                target,
+                target.paths(output_dir.as_deref(), history_file.as_deref())?,
+            ));
+        }
+        found = pick_targets(found)?;
+    } else {
+        for target in targets {
+            found.push((
+                target,
+                target.paths(output_dir.as_deref(), history_file.as_deref())?,
+            ));
+        }
+    }
+
+    let paths: Vec<PathBuf> = found.into_iter().flat_map(|(_, paths)| paths).collect();
+    if paths.is_empty() {
+        say!("Nothing to clean");
+        return Ok(());
+    }
+
+    let mut total = 0;
+    for path in &paths {
+        let size = disk_size(path);
+        total += size;
+        if dry_run() {
+            say!("Would delete {} ({})", path.display(), ByteSize::b(size));
+            continue;
+        }
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        result.with_context(|| format!("trying to delete {path:?}"))?;
+        say!("Deleted {} ({})", path.display(), ByteSize::b(size));
+    }
+
+    if dry_run() {
+        say!("Would free {}", ByteSize::b(total));
+        return Ok(());
+    }
+    // Only goes if nothing else was kept in it
+    let fleet_dir = Path::new(FLEET_OUTPUT_DIR);
+    for dir in [fleet_dir.join(DEBUG_FLEET_DIR), fleet_dir.to_owned()] {
+        let _ = std::fs::remove_dir(dir);
+    }
+    say!("Freed {}", ByteSize::b(total));
+    Ok(())
+}
+
+/// Asks which of what was found to delete, with how much of each there is.
+fn pick_targets(
+    found: Vec<(CleanTarget, Vec<PathBuf>)>,
+) -> anyhow::Result<Vec<(CleanTarget, Vec<PathBuf>)>> {
+    let items: Vec<String> = found
+        .iter()
+        .map(|(target, paths)| {
+            let size: u64 = paths.iter().map(|path| disk_size(path)).sum();
+            format!(
+                "{} ({} files, {})",
+                target.describe(),
+                paths.len(),
+                ByteSize::b(size)
+            )
+        })
+        .collect();
+
+    let chosen = dialoguer::MultiSelect::new()
+        .with_prompt("Pick what to delete (space to select, enter to confirm)")
+        .items(&items)
+        .interact_opt()
+        .context("trying to prompt for what to clean")?
+        .unwrap_or_default();
+    Ok(found
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| chosen.contains(i))
+        .map(|(_, found)| found)
+        .collect())
+}
+
+/// The optimized fleets and manifests for both profiles, the feature matrix's variants,
+/// `bench-opt`'s builds and `compare`'s builds at git refs.
+fn fleet_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for dir in [dir.to_owned(), dir.join(DEBUG_FLEET_DIR)] {
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("trying to read {dir:?}"))? {
+            let entry = entry.with_context(|| format!("trying to read an entry of {dir:?}"))?;
+            let path = entry.path();
+            let ours = path.extension().is_some_and(|ext| ext == "wasm")
+                || entry.file_name() == MANIFEST_FILE;
+            if ours && entry.file_type()?.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        paths.extend(files);
+
+        for subdir in [MATRIX_DIR, BENCH_OPT_DIR, COMPARE_DIR] {
+            let subdir = dir.join(subdir);
+            if subdir.is_dir() {
+                paths.push(subdir);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// The replays and everything else battles wrote to the battle output directory, including the
+/// archived fleets and the tournament results.
+fn battle_output_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths = replay_files(dir)?;
+    let objects = dir.join(FLEET_OBJECTS_DIR);
+    if objects.is_dir() {
+        paths.push(objects);
+    }
+    let tournaments = tournaments_dir(dir);
+    if tournaments.is_dir() {
+        paths.push(tournaments);
+    }
+    Ok(paths)
+}
+
+/// The battle history at `history` and the ratings in `fleet_dir`, those of them that exist.
+fn history_files(history: PathBuf, fleet_dir: &Path) -> Vec<PathBuf> {
+    [history, fleet_dir.join(RATINGS_FILE)]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// How many bytes `path` takes up, counting everything in it for a directory. Symlinks count as
+/// themselves, not what they point to.
+pub fn disk_size(path: &Path) -> u64 {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| disk_size(&entry.path()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{battle_output_files, disk_size, fleet_files, history_files, CleanTarget};
+
+    fn write(path: &Path, contents: &str) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fleet_files_only_picks_out_what_build_made() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        for file in [
+            "glass_cannon.wasm",
+            "fleet_manifest.json",
+            "battle_history.jsonl",
+            "notes.txt",
+            "debug/glass_cannon.wasm",
+            "matrix/glass_cannon+none.wasm",
+        ] {
+            write(&dir.join(file), "(module)")?;
+        }
+
+        assert_eq!(
+            fleet_files(dir)?,
+            [
+                dir.join("fleet_manifest.json"),
+                dir.join("glass_cannon.wasm"),
+                dir.join("matrix"),
+                dir.join("debug/glass_cannon.wasm"),
+            ]
+        );
+        assert_eq!(disk_size(&dir.join("matrix")), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn battle_output_files_leave_other_files_alone() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        for file in [
+            "1718476403_a_vs_b.json.deflate",
+            "1718476403_a_vs_b.result.json",
+            "1718476403_a_vs_b.sim.log",
+            "fleet_objects/abc.wasm",
+            "tournaments/1718476403_tournament.json",
+            "my_notes.json.deflate",
+            "Cargo.toml",
+        ] {
+            write(&dir.join(file), "")?;
+        }
+
+        assert_eq!(
+            battle_output_files(dir)?,
+            [
+                dir.join("1718476403_a_vs_b.json.deflate"),
+                dir.join("1718476403_a_vs_b.result.json"),
+                dir.join("1718476403_a_vs_b.sim.log"),
+                dir.join("fleet_objects"),
+                dir.join("tournaments"),
+            ]
+        );
+        assert!(battle_output_files(&dir.join("missing"))?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn history_is_found_where_it_was_configured() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history = dir.path().join("battles/history.jsonl");
+        assert!(!CleanTarget::History
+            .paths(None, Some(&history))?
+            .contains(&history));
+
+        write(&history, "{}\n")?;
+        assert!(CleanTarget::History
+            .paths(None, Some(&history))?
+            .contains(&history));
+        Ok(())
+    }
+
+    #[test]
+    fn history_goes_with_the_ratings() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let history = dir.path().join("history.jsonl");
+        let fleets = dir.path().join("fleets");
+        assert!(history_files(history.clone(), &fleets).is_empty());
+
+        write(&fleets.join("ratings.json"), "{}")?;
+        assert_eq!(
+            history_files(history.clone(), &fleets),
+            [fleets.join("ratings.json")]
+        );
+        write(&history, "{}\n")?;
+        assert_eq!(
+            history_files(history.clone(), &fleets),
+            [history, fleets.join("ratings.json")]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+//! `compare`: battling two builds of a fleet against the same opponent on the same seeds, to tell
+//! whether a change made it better, and which games to replay to see what it changed.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::events::{emit, Event, Phase};
+use crate::fleet::{find_fleet, find_fleets_for_run, Fleet};
+use crate::git::{repo_prefix, resolve_commit, Worktree};
+use crate::hash::file_sha256;
+use crate::manifest::artifact_file_name;
+use crate::matrix::matrix_package;
+use crate::output::{dry_run, say, shell_command};
+use crate::run::{
+    apply_unpruned_battle_settings, battle_options, check_before_battle, unix_now, BattleArgs,
+};
+use crate::series::{
+    play_series, print_scoreboard, read_seed_file, series_result_path, Game, GameResult,
+    SeriesPlan, SeriesRecord,
+};
+use crate::settings::settings;
+use crate::sim::{find_protologic_path, random_seed, resolve_scenario};
+use crate::{ParsedMetadata, WASI_TARGET};
+
+/// The directory in the fleet output directory that builds at git refs are optimized into.
+pub const COMPARE_DIR: &str = "compare";
+
+/// Below this p-value, a difference is called more than chance.
+const SIGNIFICANCE: f64 = 0.05;
+
+/// Arguments for `cargo protologic compare`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct CompareArgs {
+    /// The build to compare against: a fleet wasm file, or a git ref like `main` or `HEAD~1` to build the fleet at.
+    #[arg(long, value_name = "WASM_OR_REF")]
+    base: String,
+    /// The build to try out, as `--base` takes it.
+    #[arg(long, value_name = "WASM_OR_REF")]
+    candidate: String,
+    /// The fleet both builds battle: a built fleet, a reference fleet like `@reference/tutorial-bot`, or a wasm file.
+    #[arg(long, value_name = "FLEET")]
+    vs: String,
+    /// How many seeds to battle each build on.
+    #[arg(long, value_name = "N", default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    games: u32,
+    /// Battle on the seeds in this file, one per line, instead of `--games` random ones.
+    #[arg(long, value_name = "PATH", conflicts_with = "games")]
+    seed_file: Option<PathBuf>,
+    /// Play each seed from both sides.
+    #[arg(long, default_value = "false")]
+    swap_sides: bool,
+    /// The workspace package to build at a git ref, by its package or fleet name. Defaults to the only workspace fleet besides the opponent.
+    #[arg(short, long, value_name = "PACKAGE")]
+    package: Option<String>,
+    #[command(flatten)]
+    battle: BattleArgs,
+}
+
+/// Where the fleet's builds at git refs go.
+pub fn compare_dir(debug: bool) -> anyhow::Result<PathBuf> {
+    Ok(crate::fleet_output_path(debug)?.join(COMPARE_DIR))
+}
+
+/// The fleet `--vs` names: a wasm file as it is, or one of the fleets `run` can battle.
+fn find_opponent(name: &str, debug: bool) -> anyhow::Result<Fleet> {
+    let path = Path::new(name);
+    if path.extension().is_some_and(|ext| ext == "wasm") || path.is_file() {
+        return Fleet::from_external(path);
+    }
+    find_fleet(&find_fleets_for_run(debug)?, name)
+}
+
+/// The build `--base` or `--candidate` names, building the fleet first when it's a git ref.
+fn find_build(
+    flag: &str,
+    value: &str,
+    args: &CompareArgs,
+    opponent: &Fleet,
+) -> anyhow::Result<Fleet> {
+    let path = Path::new(value);
+    if path.extension().is_some_and(|ext| ext == "wasm") || path.is_file() {
+        return Fleet::from_external(path);
+    }
+    let metadata = crate::cargo_metadata()?;
+    let commit = resolve_commit(&metadata.workspace_root, value).with_context(|| {
+        format!("`{flag} {value}` isn't a wasm file, or a git ref of the workspace's repository")
+    })?;
+    let package = matrix_package(&metadata, args.package.as_deref(), opponent, "--package")?;
+    build_at_commit(&metadata, &package.name, value, &commit, args.battle.debug)
+}
+
+/// Builds `package` as it was at `commit`, in a temporary checkout of the workspace's repository,
+/// and optimizes it into the [`compare_dir`]. The checkouts share a target directory, so only
+/// what changed between them is rebuilt.
+fn build_at_commit(
+    metadata: &ParsedMetadata,
+    package: &str,
+    rev: &str,
+    commit: &str,
+    debug: bool,
+) -> anyhow::Result<Fleet> {
+    let short = &commit[..commit.len().min(8)];
+    let artifact = artifact_file_name(package);
+    let stem = artifact.trim_end_matches(".wasm");
+    let path = compare_dir(debug)?.join(format!("{stem}@{short}.wasm"));
+    let fleet = Fleet {
+        name: format!("{package}@{short}"),
+        path: path.clone(),
+    };
+
+    let dir = metadata.target_directory.join("protologic_compare");
+    let checkout = dir.join(format!("checkout_{short}"));
+    let target_dir = dir.join("target");
+    let prefix = repo_prefix(&metadata.workspace_root).unwrap_or_default();
+    let mut cargo = crate::build_command(package, &[], debug)?;
+    cargo
+        .current_dir(checkout.join(prefix))
+        .env("CARGO_TARGET_DIR", &target_dir);
+    if dry_run() {
+        say!(
+            "Would check out {rev} ({short}) into {checkout:?}, and build '{package}' there with: {}",
+            shell_command(&cargo)
+        );
+        say!("Would optimize it into {path:?}");
+        return Ok(fleet);
+    }
+
+    say!("Building '{package}' at {rev} ({short}) in a temporary checkout");
+    // Left behind by a run that was killed part way through
+    if checkout.exists() {
+        std::fs::remove_dir_all(&checkout)
+            .with_context(|| format!("trying to remove the old checkout in {checkout:?}"))?;
+    }
+    let _worktree = Worktree::add(&metadata.workspace_root, &checkout, commit)?;
+    let status = cargo
+        .status()
+        .context("trying to build packages with cargo")?;
+    if !status.success() {
+        anyhow::bail!("cargo couldn't build '{package}' at {rev}, it exited with {status}");
+    }
+    emit(Event::PackageBuilt {
+        package: fleet.name.clone(),
+    });
+
+    let profile = if debug { "debug" } else { "release" };
+    let cargo_output = target_dir.join(WASI_TARGET).join(profile).join(&artifact);
+    let out_dir = compare_dir(debug)?;
+    std::fs::create_dir_all(&out_dir)
+        .with_context(|| format!("trying to create compare directory {out_dir:?}"))?;
+    crate::optimize_wasm(&cargo_output, &path, debug)?;
+    Ok(fleet)
+}
+
+/// A game the builds got different results in, with the same seed and side.
+#[derive(Debug, Clone, Copy)]
+pub struct Difference<'a> {
+    pub base: &'a Game,
+    pub candidate: &'a Game,
+}
+
+/// Points for a game between a build and the opponent: 2 for a win, 1 for a draw. `None` when it
+/// isn't known who won, so the game can't be compared.
+fn points(game: &Game) -> Option<u32> {
+    match game.result() {
+        GameResult::First => Some(2),
+        GameResult::Draw => Some(1),
+        GameResult::Second => Some(0),
+        GameResult::Unknown => None,
+    }
+}
+
+/// The games the base did better in, and those the candidate did better in. Both series were
+/// played from the same plan, so their games line up.
+pub fn differences<'a>(
+    base: &'a [Game],
+    candidate: &'a [Game],
+) -> (Vec<Difference<'a>>, Vec<Difference<'a>>) {
+    let mut base_better = Vec::new();
+    let mut candidate_better = Vec::new();
+    for (base, candidate) in base.iter().zip(candidate) {
+        let (Some(base_points), Some(candidate_points)) = (points(base), points(candidate)) else {
+            continue;
+        };
+        let difference = Difference { base, candidate };
+        if base_points > candidate_points {
+            base_better.push(difference);
+        } else if candidate_points > base_points {
+            candidate_better.push(difference);
+        }
+    }
+    (base_better, candidate_better)
+}
+
+/// The two-sided p-value of the sign test: how likely it is the games the builds disagree on would
+/// split at least this unevenly if neither build was better.
+pub fn sign_test(better: u32, worse: u32) -> f64 {
+    let n = better + worse;
+    if n == 0 {
+        return 1.0;
+    }
+    // In logs, so long runs don't underflow
+    let ln_half = -(n as f64) * std::f64::consts::LN_2;
+    let mut ln_choose = 0.0;
+    let mut tail = 0.0;
+    for k in 0..=better.min(worse) {
+        if k > 0 {
+            ln_choose += ((n - k + 1) as f64).ln() - (k as f64).ln();
+        }
+        tail += (ln_choose + ln_half).exp();
+    }
+    (2.0 * tail).min(1.0)
+}
+
+/// Whether the difference between the builds looks like more than chance.
+pub fn significance_hint(better: u32, worse: u32) -> String {
+    if better + worse == 0 {
+        return "The builds got the same result in every game, so nothing tells them apart."
+            .to_owned();
+    }
+    let p = sign_test(better, worse);
+    let split = format!("The candidate did better in {better} games and worse in {worse}");
+    if p < SIGNIFICANCE {
+        let verdict = if better > worse { "better" } else { "worse" };
+        format!("{split}, so it's likely {verdict} (p = {p:.3}, from a sign test).")
+    } else {
+        format!("{split}, which could well be chance (p = {p:.2}, from a sign test). More `--games` would tell.")
+    }
+}
+
+/// Which game a difference is, like `seed 123 as fleet B on scenario asteroids`.
+fn describe_game(game: &Game) -> String {
+    let mut description = match game.record.seed {
+        Some(seed) => format!("seed {seed}"),
+        None => "the sim's seed".to_owned(),
+    };
+    if game.swapped {
+        description.push_str(" as fleet B");
+    }
+    if let Some(scenario) = &game.record.scenario {
+        write!(description, " on scenario {scenario}").unwrap();
+    }
+    description
+}
+
+fn describe_result(game: &Game) -> &'static str {
+    match game.result() {
+        GameResult::First => "won",
+        GameResult::Second => "lost",
+        GameResult::Draw => "drew",
+        GameResult::Unknown => "unknown",
+    }
+}
+
+/// One build's record against the opponent.
+fn score_line(role: &str, series: &SeriesRecord) -> String {
+    let score = series.score;
+    let win_rate = score
+        .win_rate(true)
+        .map_or_else(|| "no".to_owned(), |rate| format!("a {:.1}%", rate * 100.0));
+    format!(
+        "{role} '{}': {} won, {} lost, {} drawn, {win_rate} win-rate against '{}'",
+        series.fleets[0], score.first, score.second, score.draws, series.fleets[1]
+    )
+}
+
+/// The builds' win-rates, whether the difference looks real, and the games they differ in, with
+/// their replays.
+pub fn comparison_report(base: &SeriesRecord, candidate: &SeriesRecord) -> String {
+    let mut report = String::new();
+    writeln!(report, "{}", score_line("Base", base)).unwrap();
+    writeln!(report, "{}", score_line("Candidate", candidate)).unwrap();
+    let (base_better, candidate_better) = differences(&base.games, &candidate.games);
+    writeln!(
+        report,
+        "{}",
+        significance_hint(candidate_better.len() as u32, base_better.len() as u32)
+    )
+    .unwrap();
+
+    for (title, differences) in [
+        (
+            "Games the base did better in, the ones to replay:",
+            &base_better,
+        ),
+        ("Games the candidate did better in:", &candidate_better),
+    ] {
+        if differences.is_empty() {
+            continue;
+        }
+        writeln!(report, "\n{title}").unwrap();
+        for Difference { base, candidate } in differences {
+            writeln!(
+                report,
+                "  {}: base {} ({}), candidate {} ({})",
+                describe_game(base),
+                describe_result(base),
+                base.record.replay.display(),
+                describe_result(candidate),
+                candidate.record.replay.display()
+            )
+            .unwrap();
+        }
+    }
+    report
+}
+
+/// Battles both builds against the opponent on the same seeds, then says how they compare.
+pub fn compare(mut args: CompareArgs) -> anyhow::Result<()> {
+    apply_unpruned_battle_settings(&mut args.battle, settings()?, "the comparison's")?;
+    let protologic_path = find_protologic_path(args.battle.protologic_path.clone())?;
+    let options = battle_options(&args.battle, &protologic_path)?;
+    let opponent = find_opponent(&args.vs, args.battle.debug)?;
+    let scenarios = args
+        .battle
+        .scenarios
+        .iter()
+        .map(|scenario| resolve_scenario(scenario))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let seeds = match &args.seed_file {
+        Some(seed_file) => read_seed_file(seed_file)?,
+        None => (0..args.games).map(|_| random_seed()).collect(),
+    };
+    let plan = SeriesPlan {
+        scenarios,
+        ..SeriesPlan::repeat(seeds, args.swap_sides)
+    };
+
+    emit(Event::PhaseStarted {
+        phase: Phase::Build,
+    });
+    let base = find_build("--base", &args.base, &args, &opponent)?;
+    let candidate = find_build("--candidate", &args.candidate, &args, &opponent)?;
+    say!(
+        "Comparing '{}' with '{}', each playing {} games against '{}'",
+        base.name,
+        candidate.name,
+        plan.battles(&base, &opponent).len(),
+        opponent.name
+    );
+    if dry_run() {
+        say!(
+            "Would battle each of them against '{}' on the same seeds",
+            opponent.name
+        );
+        return Ok(());
+    }
+    if file_sha256(&base.path)? == file_sha256(&candidate.path)? {
+        say!(
+            "Warning: the base and candidate are the same build, so they can only differ by chance"
+        );
+    }
+    for fleet in [&base, &candidate] {
+        check_before_battle(
+            &args.battle,
+            true,
+            &options,
+            &protologic_path,
+            [fleet, &opponent],
+        )?;
+    }
+
+    emit(Event::PhaseStarted {
+        phase: Phase::Battle,
+    });
+    let mut results = Vec::new();
+    for (role, fleet) in [("base", &base), ("candidate", &candidate)] {
+        say!(
+            "Battling the {role}, '{}', against '{}'",
+            fleet.name,
+            opponent.name
+        );
+        let timestamp = unix_now()?;
+        let series = play_series(&options, fleet, &opponent, &plan, timestamp)?;
+        print_scoreboard(&series);
+        series.write_json(&series_result_path(&options, fleet, &opponent, timestamp)?)?;
+        results.push(series);
+    }
+
+    say!("{}", comparison_report(&results[0], &results[1]).trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{differences, sign_test, significance_hint, CompareArgs};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::run::apply_unpruned_battle_settings;
+    use crate::series::Game;
+    use crate::settings::{Settings, WORKSPACE_FILE};
+
+    #[derive(Parser)]
+    struct Compare {
+        #[command(flatten)]
+        args: CompareArgs,
+    }
+
+    fn game(seed: u64, outcome: Outcome) -> Game {
+        Game {
+            record: BattleRecord {
+                fleet_a: "build".to_owned(),
+                fleet_b: "opponent".to_owned(),
+                seed: Some(seed),
+                outcome,
+                ..Default::default()
+            },
+            swapped: false,
+        }
+    }
+
+    #[test]
+    fn sign_test_matches_the_binomial_tail() {
+        assert_eq!(sign_test(0, 0), 1.0);
+        assert_eq!(sign_test(3, 3), 1.0);
+        assert!((sign_test(5, 0) - 0.0625).abs() < 1e-12);
+        assert!((sign_test(2, 8) - 0.109375).abs() < 1e-12);
+        assert!(sign_test(600, 400) < 1e-9);
+        assert!(significance_hint(6, 0).contains("likely better"));
+        assert!(significance_hint(1, 3).contains("could well be chance"));
+    }
+
+    #[test]
+    fn differences_line_up_games_by_seed() {
+        let base = [
+            game(1, Outcome::FleetAWon),
+            game(2, Outcome::FleetAWon),
+            game(3, Outcome::FleetBWon),
+            game(4, Outcome::Draw),
+            game(5, Outcome::Unknown),
+        ];
+        let candidate = [
+            game(1, Outcome::FleetAWon),
+            game(2, Outcome::FleetBWon),
+            game(3, Outcome::Draw),
+            game(4, Outcome::FleetBWon),
+            game(5, Outcome::FleetAWon),
+        ];
+        let seeds = |differences: Vec<super::Difference>| -> Vec<u64> {
+            differences
+                .iter()
+                .filter_map(|difference| difference.candidate.record.seed)
+                .collect()
+        };
+        let (base_better, candidate_better) = differences(&base, &candidate);
+        assert_eq!(seeds(base_better), [2, 4]);
+        assert_eq!(seeds(candidate_better), [3]);
+    }
+
+    #[test]
+    fn comparisons_never_prune_their_replays() -> anyhow::Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::write(
+            workspace.path().join(WORKSPACE_FILE),
+            "[run]\nkeep-last = 5\n",
+        )?;
+        let settings = Settings::load(Some(workspace.path()))?;
+        let compare = |extra: &[&str]| {
+            let args = [
+                "compare",
+                "--base",
+                "main",
+                "--candidate",
+                "HEAD",
+                "--vs",
+                "b",
+            ];
+            Compare::try_parse_from(args.iter().chain(extra)).map(|compare| compare.args)
+        };
+
+        let mut args = compare(&[])?;
+        apply_unpruned_battle_settings(&mut args.battle, &settings, "the comparison's")?;
+        assert_eq!(args.battle.keep_last, None);
+
+        let mut args = compare(&["--keep-last", "3"])?;
+        let err = apply_unpruned_battle_settings(&mut args.battle, &settings, "the comparison's")
+            .unwrap_err();
+        assert!(err.to_string().contains("`--keep-last`"), "{err}");
+        Ok(())
+    }
+}
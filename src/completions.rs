@@ -0,0 +1,435 @@
+//! Shell completion scripts, and the fleet names and replays they complete from what's on disk.
+//!
+//! The scripts come from clap_complete, with the arguments in [`DYNAMIC_ARGS`] hooked up to the
+//! hidden `cargo protologic completion-candidates` afterwards. Only bash, zsh and fish get those hooks, the
+//! other shells complete just subcommands and flags.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use crate::fleet::fleets_in_dir;
+use crate::replay::{battle_replays, LATEST_REPLAY};
+use crate::settings::{find_workspace_root, Settings};
+use crate::{CargoProtologic, DEBUG_FLEET_DIR, FLEET_OUTPUT_DIR};
+
+/// What an argument is completed from.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Candidates {
+    /// The names of the built fleets.
+    Fleets,
+    /// The replays in the battle output directory.
+    Replays,
+}
+
+impl Candidates {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fleets => "fleets",
+            Self::Replays => "replays",
+        }
+    }
+}
+
+/// The arguments completed from disk: the subcommand, the argument's id, and what it's completed
+/// from.
+const DYNAMIC_ARGS: &[(&str, &str, Candidates)] = &[
+    ("run", "fleet_a", Candidates::Fleets),
+    ("run", "fleet_b", Candidates::Fleets),
+    ("run", "vs_fleet", Candidates::Fleets),
+    ("run", "assert_fleet", Candidates::Fleets),
+    ("run", "assert_winner", Candidates::Fleets),
+    ("replay", "replay", Candidates::Replays),
+    ("stats", "replays", Candidates::Replays),
+];
+
+/// One of [`DYNAMIC_ARGS`], as found in the command.
+struct DynamicArg {
+    subcommand: &'static str,
+    id: &'static str,
+    /// The flags that take its value, like `--fleet-a`, or none when it's positional.
+    flags: Vec<String>,
+    /// For positional arguments, the subcommand's flags that take a value, after which a word
+    /// isn't the positional argument.
+    value_flags: Vec<String>,
+    candidates: Candidates,
+}
+
+fn dynamic_args(command: &clap::Command) -> Vec<DynamicArg> {
+    let Some(protologic) = command.find_subcommand("protologic") else {
+        return Vec::new();
+    };
+    let flags = |arg: &clap::Arg| -> Vec<String> {
+        let longs = arg.get_long().into_iter();
+        let aliases = arg.get_visible_aliases().unwrap_or_default().into_iter();
+        let shorts = arg.get_short().map(|short| format!("-{short}"));
+        longs
+            .chain(aliases)
+            .map(|long| format!("--{long}"))
+            .chain(shorts)
+            .collect()
+    };
+
+    let mut found = Vec::new();
+    for &(subcommand, id, candidates) in DYNAMIC_ARGS {
+        let Some(sub) = protologic.find_subcommand(subcommand) else {
+            continue;
+        };
+        let Some(arg) = sub.get_arguments().find(|arg| arg.get_id() == id) else {
+            continue;
+        };
+        let (flags, value_flags) = if arg.is_positional() {
+            let value_flags = sub
+                .get_arguments()
+                .filter(|arg| !arg.is_positional() && arg.get_action().takes_values())
+                .flat_map(flags)
+                .collect();
+            (Vec::new(), value_flags)
+        } else {
+            (flags(arg), Vec::new())
+        };
+        found.push(DynamicArg {
+            subcommand,
+            id,
+            flags,
+            value_flags,
+            candidates,
+        });
+    }
+    found
+}
+
+/// The completion script for `shell`. It's generated from the whole `cargo protologic` command,
+/// cargo layer and all, so it completes what people type rather than `cargo-protologic`, which
+/// nobody runs directly.
+pub fn completion_script(shell: Shell) -> String {
+    let mut command = CargoProtologic::command();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, "cargo", &mut script);
+    let script = String::from_utf8_lossy(&script).into_owned();
+
+    let args = dynamic_args(&command);
+    match shell {
+        Shell::Bash => bash_hooks(script, &args),
+        Shell::Zsh => zsh_hooks(&script, &args),
+        Shell::Fish => fish_hooks(script, &args),
+        _ => script,
+    }
+}
+
+/// Wraps the generated `_cargo` in a function that completes the dynamic arguments itself.
+fn bash_hooks(script: String, args: &[DynamicArg]) -> String {
+    let mut cases = String::new();
+    // Values of other flags come first, so positional arguments only match the words left
+    for arg in args.iter().filter(|arg| arg.flags.is_empty()) {
+        let flags: Vec<String> = arg
+            .value_flags
+            .iter()
+            .map(|flag| format!("\"{} {flag}\"", arg.subcommand))
+            .collect();
+        if !flags.is_empty() {
+            cases.push_str(&format!("            {}) ;;\n", flags.join("|")));
+        }
+    }
+    for arg in args {
+        let patterns: Vec<String> = if arg.flags.is_empty() {
+            vec![format!("\"{} \"*", arg.subcommand)]
+        } else {
+            arg.flags
+                .iter()
+                .map(|flag| format!("\"{} {flag}\"", arg.subcommand))
+                .collect()
+        };
+        let guard = if arg.flags.is_empty() {
+            "[[ \"$cur\" != -* ]] && "
+        } else {
+            ""
+        };
+        cases.push_str(&format!(
+            "            {}) {guard}candidates={} ;;\n",
+            patterns.join("|"),
+            arg.candidates.name()
+        ));
+    }
+
+    let wrapper = format!(
+        r#"_cargo_protologic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" subcommand="" candidates="" i
+    if [[ "${{COMP_WORDS[1]}}" == protologic ]]; then
+        for ((i = 2; i < COMP_CWORD; i++)); do
+            if [[ "${{COMP_WORDS[i]}}" != -* ]]; then
+                subcommand="${{COMP_WORDS[i]}}"
+                break
+            fi
+        done
+        case "$subcommand $prev" in
+{cases}        esac
+    fi
+    if [[ -n "$candidates" ]]; then
+        COMPREPLY=($(compgen -W "$(cargo protologic completion-candidates "$candidates" 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    _cargo "$@"
+}}
+
+"#
+    );
+    wrapper + &script.replace("complete -F _cargo ", "complete -F _cargo_protologic ")
+}
+
+/// Points the generated specs for the dynamic arguments at functions that ask `completion-candidates`.
+fn zsh_hooks(script: &str, args: &[DynamicArg]) -> String {
+    let mut lines = Vec::new();
+    let mut block = "";
+    for line in script.lines() {
+        if let Some(name) = line
+            .strip_prefix('(')
+            .and_then(|line| line.strip_suffix(')'))
+        {
+            block = name;
+        }
+        let dynamic = args.iter().find(|arg| {
+            arg.subcommand == block
+                && line.ends_with("' \\")
+                && if arg.flags.is_empty() {
+                    line.trim_start_matches(['\'', '*', ':'])
+                        .starts_with(&format!("{} -- ", arg.id))
+                } else {
+                    arg.flags
+                        .iter()
+                        .any(|flag| line.contains(&format!("{flag}=[")))
+                }
+        });
+        // The action is whatever's after the spec's last colon
+        match dynamic.and_then(|arg| Some((arg, line[..line.len() - 3].rfind(':')?))) {
+            Some((arg, action)) => lines.push(format!(
+                "{}_cargo_protologic_{}' \\",
+                &line[..=action],
+                arg.candidates.name()
+            )),
+            None => lines.push(line.to_owned()),
+        }
+    }
+
+    let mut hooked = String::new();
+    let mut lines = lines.into_iter();
+    // The `#compdef` line has to stay first
+    if let Some(first) = lines.next() {
+        hooked.push_str(&first);
+        hooked.push('\n');
+    }
+    hooked.push_str(
+        r#"
+_cargo_protologic_fleets() {
+    local -a candidates
+    candidates=(${(f)"$(cargo protologic completion-candidates fleets 2>/dev/null)"})
+    compadd -a candidates
+}
+
+_cargo_protologic_replays() {
+    local -a candidates
+    candidates=(${(f)"$(cargo protologic completion-candidates replays 2>/dev/null)"})
+    compadd -a candidates
+    _files
+}
+"#,
+    );
+    for line in lines {
+        hooked.push_str(&line);
+        hooked.push('\n');
+    }
+    hooked
+}
+
+/// Adds completions for the dynamic arguments alongside the generated ones.
+fn fish_hooks(mut script: String, args: &[DynamicArg]) -> String {
+    for arg in args {
+        let condition = format!(
+            "__fish_seen_subcommand_from protologic; and __fish_seen_subcommand_from {}",
+            arg.subcommand
+        );
+        let source = format!(
+            "(cargo protologic completion-candidates {} 2>/dev/null)",
+            arg.candidates.name()
+        );
+        if arg.flags.is_empty() {
+            // Still offering files, since any replay can be given by its path
+            script.push_str(&format!(
+                "complete -c cargo -n \"{condition}\" -a \"{source}\"\n"
+            ));
+            continue;
+        }
+        let mut options = String::new();
+        for flag in &arg.flags {
+            match flag.strip_prefix("--") {
+                Some(long) => options.push_str(&format!(" -l {long}")),
+                None => options.push_str(&format!(" -s {}", flag.trim_start_matches('-'))),
+            }
+        }
+        script.push_str(&format!(
+            "complete -c cargo -n \"{condition}\"{options} -r -f -a \"{source}\"\n"
+        ));
+    }
+    script
+}
+
+/// Prints the completion script for `shell` on stdout, to redirect into its completion directory.
+pub fn completions(shell: Shell) -> anyhow::Result<()> {
+    std::io::stdout().write_all(completion_script(shell).as_bytes())?;
+    Ok(())
+}
+
+/// The names of the fleets built into `dir` and its debug build directory.
+fn fleet_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = [dir.to_owned(), dir.join(DEBUG_FLEET_DIR)]
+        .iter()
+        .filter_map(|dir| fleets_in_dir(dir).ok())
+        .flatten()
+        .map(|fleet| fleet.name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// `latest` and the battle replays in `dir`, newest first, as paths from `cwd`.
+fn replay_paths(dir: &Path, cwd: &Path) -> Vec<String> {
+    let mut replays = battle_replays(dir).unwrap_or_default();
+    replays.sort_by(|a, b| b.cmp(a));
+    let dir = dir.strip_prefix(cwd).unwrap_or(dir);
+    let mut paths = vec!["latest".to_owned()];
+    let latest = dir.join(LATEST_REPLAY);
+    if latest.is_file() {
+        paths.push(latest.display().to_string());
+    }
+    paths.extend(replays.into_iter().map(|(_, base)| {
+        dir.join(format!("{base}.json.deflate"))
+            .display()
+            .to_string()
+    }));
+    paths
+}
+
+/// The battle output directory, as `run` picks it, but finding the workspace without asking cargo,
+/// which is too slow to wait for on every tab.
+fn replay_dir(cwd: &Path) -> PathBuf {
+    Settings::load(find_workspace_root(cwd).as_deref())
+        .ok()
+        .and_then(|settings| settings.path("output-dir", None))
+        .unwrap_or_else(|| cwd.to_owned())
+}
+
+/// Prints the candidates for a dynamic argument, one per line, for the completion scripts to
+/// filter. Anything going wrong just means fewer candidates, since errors would end up in the
+/// middle of someone's prompt.
+pub fn complete(candidates: Candidates) {
+    let Ok(cwd) = std::env::current_dir() else {
+        return;
+    };
+    let candidates = match candidates {
+        Candidates::Fleets => fleet_names(&cwd.join(FLEET_OUTPUT_DIR)),
+        Candidates::Replays => replay_paths(&replay_dir(&cwd), &cwd),
+    };
+    let mut stdout = std::io::stdout().lock();
+    for candidate in candidates {
+        if writeln!(stdout, "{candidate}").is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::{CommandFactory, ValueEnum};
+    use clap_complete::Shell;
+
+    use super::{completion_script, dynamic_args, replay_dir, replay_paths, DYNAMIC_ARGS};
+    use crate::CargoProtologic;
+
+    #[test]
+    fn completion_script_covers_the_subcommands_under_protologic() {
+        for &shell in Shell::value_variants() {
+            let script = completion_script(shell);
+            for name in [
+                "protologic",
+                "build",
+                "run",
+                "stats",
+                "validate",
+                "completions",
+            ] {
+                assert!(script.contains(name), "{shell} is missing {name}");
+            }
+            // Flags of subcommands have to come through the `protologic` layer too
+            assert!(script.contains("print-replay-path"), "{shell}");
+        }
+    }
+
+    #[test]
+    fn dynamic_args_are_all_in_the_command() {
+        let mut command = CargoProtologic::command();
+        command.build();
+        let args = dynamic_args(&command);
+        let found: Vec<(&str, &str)> = args.iter().map(|arg| (arg.subcommand, arg.id)).collect();
+        let expected: Vec<(&str, &str)> = DYNAMIC_ARGS
+            .iter()
+            .map(|&(subcommand, id, _)| (subcommand, id))
+            .collect();
+        assert_eq!(found, expected);
+
+        let fleet_b = args.iter().find(|arg| arg.id == "fleet_b").unwrap();
+        assert_eq!(fleet_b.flags, ["--fleet-b", "--baseline"]);
+        let replay = args.iter().find(|arg| arg.id == "replay").unwrap();
+        assert!(replay.flags.is_empty());
+        assert!(replay.value_flags.contains(&"--output-dir".to_owned()));
+    }
+
+    #[test]
+    fn completion_script_hooks_up_the_dynamic_args() {
+        let bash = completion_script(Shell::Bash);
+        assert!(bash.contains(r#""run --fleet-a") candidates=fleets ;;"#));
+        assert!(bash.contains("complete -F _cargo_protologic "));
+        assert!(!bash.contains("complete -F _cargo "));
+
+        let zsh = completion_script(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef cargo\n"));
+        assert!(zsh.contains(":FLEET_A:_cargo_protologic_fleets' \\"));
+        assert!(zsh.contains(":_cargo_protologic_replays' \\"));
+
+        let fish = completion_script(Shell::Fish);
+        assert!(fish.contains(
+            "-l fleet-b -l baseline -r -f -a \"(cargo protologic completion-candidates fleets 2>/dev/null)\""
+        ));
+    }
+
+    #[test]
+    fn replays_complete_from_the_configured_output_dir() -> anyhow::Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::write(
+            workspace.path().join("Cargo.toml"),
+            "[workspace]\n[workspace.metadata.protologic]\noutput-dir = \"battles\"\n",
+        )?;
+        let member = workspace.path().join("my_fleet");
+        std::fs::create_dir(&member)?;
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"my_fleet\"\n",
+        )?;
+        let battles = workspace.path().join("battles");
+        assert_eq!(replay_dir(&member), battles);
+
+        std::fs::create_dir(&battles)?;
+        std::fs::write(battles.join("1718476403_a_vs_b.json.deflate"), "")?;
+        std::fs::write(battles.join("notes.json.deflate"), "")?;
+        let replay = Path::new("battles").join("1718476403_a_vs_b.json.deflate");
+        assert_eq!(
+            replay_paths(&battles, workspace.path()),
+            ["latest".to_owned(), replay.display().to_string()]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::output::{dry_run, say};
+use crate::settings::settings;
+
+/// Settings from `[workspace.metadata.protologic]` in the workspace's `Cargo.toml` that are read
+/// through `cargo metadata`. The rest are read with the other config files, in [`crate::settings`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProtologicConfig {
+    /// The packages that are fleets, by name. Without it, the workspace's default members are.
+    #[serde(default)]
+    pub fleets: Option<Vec<String>>,
+}
+
+/// Settings from `[package.metadata.protologic]` in a fleet's `Cargo.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackageConfig {
+    /// What to call the fleet in messages and file names, instead of its wasm's file name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Who wrote the fleet, for tournament submissions.
+    #[serde(default)]
+    pub author: Option<String>,
+    /// The team the fleet is entered for, if any.
+    #[serde(default)]
+    pub team: Option<String>,
+}
+
+/// A package's `package.metadata` table, of which we only care about our own part.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PackageMetadata {
+    #[serde(default)]
+    pub protologic: Option<PackageConfig>,
+}
+
+/// Cargo's `workspace.metadata` table, of which we only care about our own part.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WorkspaceMetadata {
+    #[serde(default)]
+    pub protologic: Option<ProtologicConfig>,
+}
+
+/// Settings for every workspace, from `config.toml` in the user's config directory, like
+/// `~/.config/cargo-protologic/config.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct UserConfig {
+    /// The Protologic/Release checkout to use when `--protologic-path` isn't given.
+    #[serde(default)]
+    pub protologic_path: Option<PathBuf>,
+    /// The version `install` put at `protologic_path`, if it's an installed Release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_version: Option<String>,
+    /// The other settings, which [`crate::settings`] reads.
+    #[serde(flatten)]
+    pub settings: toml::Table,
+}
+
+impl UserConfig {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        let dir = dirs::config_dir().context("can't find your user config directory")?;
+        Ok(dir.join("cargo-protologic").join("config.toml"))
+    }
+
+    /// Reads the user config, which is empty until something's been set.
+    pub fn read() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("trying to read user config {path:?}"))?;
+        toml::from_str(&contents).with_context(|| format!("trying to parse user config {path:?}"))
+    }
+
+    pub fn write(&self) -> anyhow::Result<PathBuf> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("trying to create user config directory {dir:?}"))?;
+        }
+
+        let contents = toml::to_string(self).context("trying to serialize user config")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("trying to write user config {path:?}"))?;
+        Ok(path)
+    }
+
+    pub fn set_protologic_path(&mut self, value: &str) -> anyhow::Result<()> {
+        // Relative paths would mean something different from every workspace
+        let path = std::path::absolute(value)
+            .with_context(|| format!("trying to make {value:?} absolute"))?;
+        self.protologic_path = Some(path);
+        // Whatever's there now isn't necessarily what `install` put there
+        self.release_version = None;
+        Ok(())
+    }
+}
+
+/// Where battle outputs go: `explicit` if given, then the configured `output-dir`, and the current
+/// directory otherwise.
+///
+/// The directory is created the first time it's used, with a `.gitignore` so its contents stay out
+/// of `git status`.
+pub fn battle_output_dir(explicit: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    let (dir, current) = find_battle_output_dir(explicit)?;
+    if !current && !dir.exists() && !dry_run() {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("trying to create battle output directory {dir:?}"))?;
+        let gitignore = dir.join(".gitignore");
+        std::fs::write(&gitignore, "# Created by cargo-protologic\n*\n")
+            .with_context(|| format!("trying to write {gitignore:?}"))?;
+        say!("Created {} for replays and results", dir.display());
+    }
+
+    Ok(dir)
+}
+
+/// Where battle outputs go, as [`battle_output_dir`] picks it but without creating it, and whether
+/// that's just the current directory.
+pub fn find_battle_output_dir(explicit: Option<PathBuf>) -> anyhow::Result<(PathBuf, bool)> {
+    match settings()?.path("output-dir", explicit) {
+        Some(dir) => Ok((dir, false)),
+        None => Ok((std::env::current_dir()?, true)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::UserConfig;
+
+    #[test]
+    fn user_config_round_trips_through_toml() -> anyhow::Result<()> {
+        let contents = "protologic-path = \"/opt/Protologic/Release\"\n";
+        let config: UserConfig = toml::from_str(contents)?;
+        assert_eq!(
+            config.protologic_path.as_deref(),
+            Some(Path::new("/opt/Protologic/Release"))
+        );
+        assert_eq!(toml::to_string(&config)?, contents);
+
+        // Along with settings it doesn't know itself
+        let contents = "protologic-path = \"/opt/Protologic/Release\"\n\n[run]\nretries = 2\n";
+        let config: UserConfig = toml::from_str(contents)?;
+        assert_eq!(config.settings["run"]["retries"].as_integer(), Some(2));
+        assert_eq!(toml::to_string(&config)?, contents);
+        Ok(())
+    }
+}
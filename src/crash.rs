@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::fleet::Fleet;
+use crate::run::fleet_file_name;
+use crate::sim::{Sim, SimRun};
+
+/// The file in a crash directory saying what we know about the crash.
+pub const CRASH_REPORT_FILE: &str = "crash.json";
+
+/// What we know about a sim crash, written to `crash.json` in the crash directory.
+#[derive(Serialize, Debug)]
+struct CrashReport<'a> {
+    tool_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    sim: &'a Path,
+    /// The command the sim was run with, ready to paste into a shell.
+    command: &'a str,
+    exit_status: String,
+    seed: Option<u64>,
+    fleets: [CrashFleet<'a>; 2],
+}
+
+#[derive(Serialize, Debug)]
+struct CrashFleet<'a> {
+    name: &'a str,
+    path: &'a Path,
+    sha256: &'a str,
+}
+
+/// Everything needed to look into a sim crash after the fact.
+pub struct Crash<'a> {
+    pub sim: &'a Sim,
+    pub sim_run: &'a SimRun,
+    pub command: &'a str,
+    pub seed: Option<u64>,
+    pub fleets: [&'a Fleet; 2],
+    pub hashes: &'a [String; 2],
+}
+
+impl Crash<'_> {
+    /// Gathers the sim output, command line, fleets and environment of the crash into a new
+    /// `crash_<time>` directory in `dir`, and returns the directory.
+    pub fn capture(&self, dir: &Path, time: &str) -> anyhow::Result<PathBuf> {
+        let crash_dir = unused_dir(dir, &format!("crash_{time}"));
+        std::fs::create_dir_all(&crash_dir)
+            .with_context(|| format!("trying to create crash directory {crash_dir:?}"))?;
+
+        let write = |name: &str, contents: &[u8]| {
+            let path = crash_dir.join(name);
+            std::fs::write(&path, contents).with_context(|| format!("trying to write {path:?}"))
+        };
+
+        write("sim_output.log", self.sim_run.output.join("\n").as_bytes())?;
+        write("command.txt", self.command.as_bytes())?;
+
+        let [fleet_a, fleet_b] = self.fleets;
+        for (side, fleet) in [("fleet_a", fleet_a), ("fleet_b", fleet_b)] {
+            let copy = crash_dir.join(format!("{side}_{}.wasm", fleet_file_name(&fleet.name)));
+            std::fs::copy(&fleet.path, &copy)
+                .with_context(|| format!("trying to copy {:?} to {copy:?}", fleet.path))?;
+        }
+
+        let report = CrashReport {
+            tool_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            sim: &self.sim.path,
+            command: self.command,
+            exit_status: self.sim_run.status.to_string(),
+            seed: self.seed,
+            fleets: [
+                CrashFleet {
+                    name: &fleet_a.name,
+                    path: &fleet_a.path,
+                    sha256: &self.hashes[0],
+                },
+                CrashFleet {
+                    name: &fleet_b.name,
+                    path: &fleet_b.path,
+                    sha256: &self.hashes[1],
+                },
+            ],
+        };
+        let json =
+            serde_json::to_string_pretty(&report).context("trying to serialize crash report")?;
+        write(CRASH_REPORT_FILE, json.as_bytes())?;
+
+        Ok(crash_dir)
+    }
+}
+
+fn unused_dir(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.join(name);
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{name}_{n}"));
+        n += 1;
+    }
+
+    path
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::{Path, PathBuf};
+    use std::process::ExitStatus;
+    use std::time::Duration;
+
+    use super::Crash;
+    use crate::fleet::Fleet;
+    use crate::sim::{Sim, SimRun};
+
+    #[test]
+    fn capture_saves_everything_about_the_crash() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fleet_path = dir.path().join("a.wasm");
+        std::fs::write(&fleet_path, b"\0asm\x01\0\0\0")?;
+        let fleet = Fleet {
+            name: "a".to_owned(),
+            path: fleet_path,
+        };
+        let sim_run = SimRun {
+            // Killed by SIGSEGV
+            status: ExitStatus::from_raw(11),
+            output: vec!["tick 1".to_owned(), "Segmentation fault".to_owned()],
+            duration: Duration::ZERO,
+        };
+        let crash = Crash {
+            sim: &Sim::new(Path::new("release"), false),
+            sim_run: &sim_run,
+            command: "Protologic.Terminal --fleets a.wasm a.wasm",
+            seed: Some(7),
+            fleets: [&fleet, &fleet],
+            hashes: &["abc".to_owned(), "abc".to_owned()],
+        };
+
+        let crash_dir = crash.capture(dir.path(), "2024-06-15_183323")?;
+        assert_eq!(crash_dir, dir.path().join("crash_2024-06-15_183323"));
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&crash_dir)?
+            .map(|entry| Ok(PathBuf::from(entry?.file_name())))
+            .collect::<anyhow::Result<_>>()?;
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "command.txt",
+                "crash.json",
+                "fleet_a_a.wasm",
+                "fleet_b_a.wasm",
+                "sim_output.log"
+            ]
+            .map(PathBuf::from)
+        );
+        assert!(std::fs::read_to_string(crash_dir.join("crash.json"))?.contains("\"seed\": 7"));
+
+        let second = crash.capture(dir.path(), "2024-06-15_183323")?;
+        assert_eq!(second, dir.path().join("crash_2024-06-15_183323_2"));
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! GET requests through curl, which every platform the sim runs on already has, for the
+//! subcommands that fetch things.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+/// What to send with a GET besides its URL.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Get<'a> {
+    /// What the request is for, to say when curl can't be run, like `check for a newer version`.
+    pub purpose: &'a str,
+    /// Who's asking, for servers like crates.io that want to know.
+    pub user_agent: Option<&'a str>,
+    pub headers: &'a [&'a str],
+    /// A bearer token to authorize with. Its header is passed through stdin, so it stays out of
+    /// the process list.
+    pub token: Option<&'a str>,
+}
+
+impl Get<'_> {
+    /// The curl command for a GET of `url`, with everything but the token.
+    fn command(&self, url: &str) -> Command {
+        let mut curl = Command::new("curl");
+        curl.args(["--fail", "--silent", "--show-error", "--location"]);
+        if let Some(user_agent) = self.user_agent {
+            curl.args(["--user-agent", user_agent]);
+        }
+        for header in self.headers {
+            curl.args(["--header", header]);
+        }
+        if self.token.is_some() {
+            curl.args(["--header", "@-"]);
+        }
+        curl.arg(url);
+        curl
+    }
+
+    /// Fetches `url`, failing with what curl said when it can't.
+    pub fn fetch(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        let purpose = self.purpose;
+        let mut child = self
+            .command(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("trying to run curl to {purpose}, is it installed?"))?;
+        let mut stdin = child.stdin.take().context("curl's stdin wasn't piped")?;
+        if let Some(token) = self.token {
+            writeln!(stdin, "Authorization: Bearer {token}")
+                .context("trying to pass the token to curl")?;
+        }
+        drop(stdin);
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("trying to wait for curl to {purpose}"))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "couldn't reach {url}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Get;
+
+    #[test]
+    fn tokens_stay_off_the_command_line() {
+        let get = Get {
+            purpose: "test",
+            user_agent: Some("cargo-protologic/1.0.0"),
+            headers: &["Accept: application/json"],
+            token: Some("sekrit"),
+        };
+        let command = get.command("https://example.com/");
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--location",
+                "--user-agent",
+                "cargo-protologic/1.0.0",
+                "--header",
+                "Accept: application/json",
+                "--header",
+                "@-",
+                "https://example.com/",
+            ]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Printing a built fleet in the wasm text format, to see what the compiler and wasm-opt made of
+//! it.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use wasmparser::{ExternalKind, KnownCustom, Name, Parser, Payload, TypeRef};
+
+use crate::fleet::find_fleet_or_file;
+use crate::output::say;
+
+/// Arguments for `cargo protologic disasm`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct DisasmArgs {
+    /// The fleet to print, by name or as a path to a wasm file.
+    fleet: String,
+    /// Write the text to this file, instead of stdout.
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Print just this function, by its name, the name it's exported as, or its index.
+    #[arg(long, value_name = "NAME_OR_INDEX")]
+    function: Option<String>,
+    /// Leave out the functions' bodies, to see the module's outline.
+    #[arg(long, default_value = "false")]
+    skeleton: bool,
+    /// Print the debug build of the fleet, rather than the optimized one.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+}
+
+/// The index of the function `name` picks out: an index as it is, or a function's name from the
+/// name section or its export.
+fn function_index(bytes: &[u8], name: &str) -> anyhow::Result<u32> {
+    let mut imported_functions = 0;
+    let mut defined_functions = 0;
+    let mut named = None;
+    let mut exported = None;
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    if matches!(import?.ty, TypeRef::Func(_) | TypeRef::FuncExact(_)) {
+                        imported_functions += 1;
+                    }
+                }
+            }
+            Payload::FunctionSection(reader) => defined_functions = reader.count(),
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if export.kind == ExternalKind::Func && export.name == name {
+                        exported = Some(export.index);
+                    }
+                }
+            }
+            Payload::CustomSection(reader) => {
+                let KnownCustom::Name(reader) = reader.as_known() else {
+                    continue;
+                };
+                for name_map in reader.into_iter().flatten() {
+                    if let Name::Function(map) = name_map {
+                        named = named.or(map
+                            .into_iter()
+                            .flatten()
+                            .find(|naming| naming.name == name)
+                            .map(|naming| naming.index));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let count = imported_functions + defined_functions;
+    let index = match named.or(exported) {
+        Some(index) => index,
+        None => match name.parse::<u32>() {
+            Ok(index) if index < count => index,
+            Ok(index) => anyhow::bail!("there's no function {index}, the module has {count}"),
+            Err(_) => anyhow::bail!("there's no function named '{name}'"),
+        },
+    };
+    if index < imported_functions {
+        anyhow::bail!("function '{name}' is imported, so there's no body to print");
+    }
+    Ok(index)
+}
+
+/// The lines of the module's text that are function `index`.
+fn function_text(text: &str, index: u32) -> Option<String> {
+    let id = format!("(;{index};)");
+    let mut lines = text.lines().skip_while(|line| {
+        !(line.starts_with("  (func ") && line.split_whitespace().any(|word| word == id))
+    });
+    let header = lines.next()?;
+    let body = lines.take_while(|line| !line.starts_with("  (") && *line != ")");
+    Some(
+        std::iter::once(header)
+            .chain(body)
+            .map(|line| format!("{line}\n"))
+            .collect(),
+    )
+}
+
+/// The module's text, or just one function's.
+fn disassemble(bytes: &[u8], function: Option<&str>, skeleton: bool) -> anyhow::Result<String> {
+    let mut config = wasmprinter::Config::new();
+    config.print_skeleton(skeleton);
+    let mut text = String::new();
+    config
+        .print(bytes, &mut wasmprinter::PrintFmtWrite(&mut text))
+        .context("trying to print the module's text")?;
+
+    let Some(function) = function else {
+        return Ok(text);
+    };
+    let index = function_index(bytes, function)?;
+    function_text(&text, index)
+        .with_context(|| format!("couldn't find function {index} in the module's text"))
+}
+
+/// Prints a fleet's wasm as text, or writes it to a file.
+pub fn disasm(args: DisasmArgs) -> anyhow::Result<()> {
+    let fleet = find_fleet_or_file(&args.fleet, args.debug)?;
+    let bytes = std::fs::read(&fleet.path)
+        .with_context(|| format!("trying to read fleet {:?}", fleet.path))?;
+    let text = disassemble(&bytes, args.function.as_deref(), args.skeleton)
+        .with_context(|| format!("trying to disassemble '{}'", fleet.name))?;
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, &text).with_context(|| format!("trying to write {path:?}"))?;
+            say!("Wrote '{}' as text to {path:?}", fleet.name);
+        }
+        None => match std::io::stdout().lock().write_all(text.as_bytes()) {
+            // Like quitting the pager it's piped into
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result.context("trying to print the module's text")?,
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+
+    const FLEET: &str = r#"(module
+        (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+        (memory (export "memory") 1)
+        (func $steer (drop (call $yield)))
+        (func $aim (result i32) (i32.add (i32.const 1) (i32.const 2)))
+        (func (export "_start") (call $steer) (drop (call $aim))))"#;
+
+    #[test]
+    fn functions_are_picked_by_name_export_or_index() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(FLEET)?;
+        let whole = disassemble(&bytes, None, false)?;
+        assert!(whole.starts_with("(module"));
+        assert!(whole.contains("i32.const 2"));
+
+        let aim = disassemble(&bytes, Some("aim"), false)?;
+        assert!(aim.starts_with("  (func $aim (;2;)"), "{aim}");
+        assert!(aim.contains("i32.add"));
+        assert!(!aim.contains("steer"));
+        assert_eq!(disassemble(&bytes, Some("2"), false)?, aim);
+
+        let start = disassemble(&bytes, Some("_start"), false)?;
+        assert!(start.contains("call $steer"), "{start}");
+        assert!(!start.contains("i32.add"));
+
+        let err = |function| {
+            format!(
+                "{:#}",
+                disassemble(&bytes, Some(function), false).unwrap_err()
+            )
+        };
+        assert!(err("yield").contains("is imported"));
+        assert!(err("fire").contains("no function named 'fire'"));
+        assert!(err("9").contains("the module has 4"));
+        Ok(())
+    }
+
+    #[test]
+    fn skeletons_leave_out_function_bodies() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(FLEET)?;
+        let skeleton = disassemble(&bytes, None, true)?;
+        assert!(skeleton.contains("(func $aim"));
+        assert!(!skeleton.contains("i32.add"), "{skeleton}");
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+use crate::output::say;
+use crate::sim::{find_player, find_protologic_path, protologic_sim_path};
+use crate::{FLEET_OUTPUT_DIR, WASI_TARGET};
+
+/// What git-lfs leaves in place of a file it didn't download.
+const LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/";
+/// A fleet-like module for checking wasm-opt can do what `build` asks of it: asyncify around
+/// `sched_yield`, with bulk memory and SIMD in use.
+const WASM_OPT_PROBE: &str = r#"(module
+  (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+  (memory 1)
+  (func (export "_start")
+    (drop (call $yield))
+    (memory.fill (i32.const 0) (i32.const 0) (i32.const 16))
+    (v128.store (i32.const 0) (v128.const i64x2 0 0))))"#;
+
+/// How a check went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    Pass,
+    /// It failed, and whether that stops fleets being built or battled.
+    Fail {
+        required: bool,
+        fix: String,
+    },
+    /// It couldn't be checked, which isn't held against the environment.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Check {
+    name: &'static str,
+    detail: String,
+    status: Status,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            detail: detail.into(),
+            status: Status::Pass,
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            detail: detail.into(),
+            status: Status::Fail {
+                required: true,
+                fix: fix.into(),
+            },
+        }
+    }
+
+    fn unknown(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            detail: detail.into(),
+            status: Status::Unknown,
+        }
+    }
+
+    /// Turns a failure into one that doesn't fail `doctor`, for things only some workflows need.
+    fn optional(mut self) -> Self {
+        if let Status::Fail { required, .. } = &mut self.status {
+            *required = false;
+        }
+        self
+    }
+
+    /// The check's line in the report, with the fix on the next line when it failed.
+    fn report(&self) -> String {
+        let (mark, suffix) = match &self.status {
+            Status::Pass => ("✓", ""),
+            Status::Fail { required: true, .. } => ("✗", ""),
+            Status::Fail {
+                required: false, ..
+            } => ("✗", " (optional)"),
+            Status::Unknown => ("?", ""),
+        };
+        let mut line = format!("{mark} {}{suffix}: {}", self.name, self.detail);
+        if let Status::Fail { fix, .. } = &self.status {
+            line.push_str(&format!("\n    fix: {fix}"));
+        }
+        line
+    }
+}
+
+/// Checks everything building and battling fleets needs, printing a report with a fix for each
+/// problem. Fails if any required check does, so it can go first in CI.
+pub fn doctor(protologic_path: Option<PathBuf>) -> anyhow::Result<()> {
+    let mut checks = vec![
+        tool_version("cargo"),
+        tool_version("rustc"),
+        wasm_target(),
+        workspace_fleets(),
+    ];
+    // Outside a workspace, there's nowhere `build` would put fleets to check
+    if crate::cargo_metadata().is_ok() {
+        checks.push(fleet_output_dir());
+    }
+    checks.push(wasm_opt());
+    match find_protologic_path(protologic_path) {
+        Ok(path) => {
+            checks.push(Check::pass(
+                "Protologic Release",
+                path.display().to_string(),
+            ));
+            checks.push(sim(&path));
+            checks.push(player(&path).optional());
+        }
+        Err(_) => checks.push(Check::fail(
+            "Protologic Release",
+            "not found",
+            "download it from https://github.com/Protologic/Release, then pass `--protologic-path` or save it with `cargo protologic config set protologic-path <PATH>`",
+        )),
+    }
+
+    say!("");
+    for check in &checks {
+        say!("{}", check.report());
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| matches!(check.status, Status::Fail { required: true, .. }))
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of the required checks failed");
+    }
+    say!("Everything needed to build and battle fleets is in place");
+    Ok(())
+}
+
+/// Runs `program` with `args`, returning its trimmed stdout if it succeeded.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn tool_version(tool: &'static str) -> Check {
+    match command_output(tool, &["--version"]) {
+        Some(version) => Check::pass(tool, version),
+        None => Check::fail(
+            tool,
+            "couldn't run it",
+            "install Rust with rustup, from https://rustup.rs",
+        ),
+    }
+}
+
+/// The targets rustup has installed for the current toolchain, or `None` when rustup can't be
+/// asked, like for toolchains it doesn't manage.
+pub fn installed_targets() -> Option<Vec<String>> {
+    let targets = command_output("rustup", &["target", "list", "--installed"])?;
+    Some(targets.lines().map(|line| line.trim().to_owned()).collect())
+}
+
+fn wasm_target() -> Check {
+    const NAME: &str = "wasm target";
+    let Some(targets) = installed_targets() else {
+        return Check::unknown(
+            NAME,
+            format!("couldn't ask rustup whether {WASI_TARGET} is installed"),
+        );
+    };
+    if targets.iter().any(|target| target == WASI_TARGET) {
+        return Check::pass(NAME, format!("{WASI_TARGET} is installed"));
+    }
+
+    let detail = if targets.iter().any(|target| target == "wasm32-wasip1") {
+        format!("only wasm32-wasip1 is installed, but fleets are built for {WASI_TARGET}")
+    } else {
+        format!("{WASI_TARGET} isn't installed")
+    };
+    Check::fail(NAME, detail, format!("rustup target add {WASI_TARGET}"))
+}
+
+fn workspace_fleets() -> Check {
+    const NAME: &str = "workspace fleets";
+    let Ok(metadata) = crate::cargo_metadata() else {
+        return Check::fail(
+            NAME,
+            "not in a cargo workspace",
+            "run this in your fleets' workspace, or start one with `cargo protologic new my_fleet`",
+        );
+    };
+
+    let ids = metadata.fleet_ids();
+    let names: Vec<&str> = metadata
+        .packages
+        .iter()
+        .filter(|package| ids.contains(&package.id))
+        .map(|package| package.name.as_str())
+        .collect();
+    if names.is_empty() {
+        return Check::fail(
+            NAME,
+            "the workspace has no fleet packages",
+            "add one with `cargo protologic new my_fleet`, or mark them with `cargo protologic init --fleet <PACKAGE>`",
+        );
+    }
+    Check::pass(NAME, names.join(", "))
+}
+
+fn fleet_output_dir() -> Check {
+    const NAME: &str = "fleet output directory";
+    let dir = Path::new(FLEET_OUTPUT_DIR);
+    let probe = dir.join(format!(".doctor.{}.tmp", std::process::id()));
+    let writable = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, b""))
+        .and_then(|()| std::fs::remove_file(&probe));
+    match writable {
+        Ok(()) => Check::pass(NAME, format!("{} is writable", dir.display())),
+        Err(err) => Check::fail(
+            NAME,
+            format!("can't write to {}: {err}", dir.display()),
+            "check the permissions of the workspace's `target` directory",
+        ),
+    }
+}
+
+fn wasm_opt() -> Check {
+    const NAME: &str = "wasm-opt";
+    match probe_wasm_opt() {
+        Ok(()) => Check::pass(NAME, "optimizes fleets with bulk memory, SIMD and asyncify"),
+        Err(err) => Check::fail(
+            NAME,
+            format!("{err:#}"),
+            "reinstall cargo-protologic, its bundled wasm-opt is broken",
+        ),
+    }
+}
+
+/// Optimizes [`WASM_OPT_PROBE`] the way `build` optimizes fleets.
+fn probe_wasm_opt() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("cargo-protologic-doctor-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).with_context(|| format!("trying to create {dir:?}"))?;
+    let input = dir.join("probe.wat");
+    let output = dir.join("probe.wasm");
+
+    let result = std::fs::write(&input, WASM_OPT_PROBE)
+        .with_context(|| format!("trying to write {input:?}"))
+        .and_then(|()| {
+            crate::make_wasm_opt(false)?
+                .run(&input, &output)
+                .context("it couldn't optimize a fleet")
+        })
+        .and_then(|()| {
+            let bytes = std::fs::read(&output).context("trying to read what it wrote")?;
+            wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+                .validate_all(&bytes)
+                .context("what it wrote isn't valid wasm")?;
+            Ok(())
+        });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+fn sim(protologic_path: &Path) -> Check {
+    const NAME: &str = "sim";
+    let path = protologic_sim_path(protologic_path);
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        return Check::fail(
+            NAME,
+            format!("{} doesn't exist", path.display()),
+            "point `--protologic-path` at a full Protologic/Release checkout",
+        );
+    };
+    if is_lfs_pointer(&path) {
+        return Check::fail(
+            NAME,
+            format!("{} is a git-lfs pointer, not the sim", path.display()),
+            format!(
+                "install git-lfs, then run `git lfs pull` in {}",
+                protologic_path.display()
+            ),
+        );
+    }
+    if !is_executable(&metadata) {
+        return Check::fail(
+            NAME,
+            format!("{} isn't executable", path.display()),
+            format!("chmod +x {}", path.display()),
+        );
+    }
+    Check::pass(NAME, path.display().to_string())
+}
+
+fn player(protologic_path: &Path) -> Check {
+    const NAME: &str = "player";
+    let player = match find_player(protologic_path, false) {
+        Ok(player) => player,
+        Err(err) => return Check::fail(NAME, "not available", format!("{}", err.root_cause())),
+    };
+    if is_lfs_pointer(&player.path) {
+        return Check::fail(
+            NAME,
+            format!(
+                "{} is a git-lfs pointer, not the player",
+                player.path.display()
+            ),
+            format!(
+                "install git-lfs, then run `git lfs pull` in {}",
+                protologic_path.display()
+            ),
+        );
+    }
+    let through = if player.wine { " (through Wine)" } else { "" };
+    Check::pass(NAME, format!("{}{through}", player.path.display()))
+}
+
+/// Whether the file is what git-lfs checks out when it isn't installed, instead of the real file.
+pub fn is_lfs_pointer(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut start = [0; LFS_POINTER_PREFIX.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|()| start == LFS_POINTER_PREFIX)
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_lfs_pointer, probe_wasm_opt, Check};
+
+    #[test]
+    fn report_shows_the_fix_for_failures() {
+        assert_eq!(
+            Check::pass("rustc", "rustc 1.80.0").report(),
+            "✓ rustc: rustc 1.80.0"
+        );
+        assert_eq!(
+            Check::fail("sim", "it's missing", "download the Release").report(),
+            "✗ sim: it's missing\n    fix: download the Release"
+        );
+        assert_eq!(
+            Check::fail("player", "not available", "install Wine")
+                .optional()
+                .report(),
+            "✗ player (optional): not available\n    fix: install Wine"
+        );
+    }
+
+    #[test]
+    fn is_lfs_pointer_spots_missing_downloads() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pointer = dir.path().join("Protologic.Terminal");
+        std::fs::write(
+            &pointer,
+            "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a\nsize 81234\n",
+        )?;
+        assert!(is_lfs_pointer(&pointer));
+
+        let binary = dir.path().join("PROTOLOGIC.exe");
+        std::fs::write(&binary, b"MZ\x90\x00")?;
+        assert!(!is_lfs_pointer(&binary));
+        assert!(!is_lfs_pointer(&dir.path().join("missing")));
+        Ok(())
+    }
+
+    #[test]
+    fn wasm_opt_handles_what_fleets_use() -> anyhow::Result<()> {
+        probe_wasm_opt()
+    }
+}
@@ -0,0 +1,82 @@
+//! The Elo rating system: each game moves the two ratings towards what it says about the fleets,
+//! by more the more surprising the result was.
+
+use crate::outcome::Outcome;
+
+/// What a fleet rated `rating` is expected to score against one rated `opponent`, from 0 to 1.
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// The ratings after a game, given the first fleet's `score` in it: 1 for a win, 0.5 for a draw
+/// and 0 for a loss. What one gains the other loses.
+pub fn update(ratings: [f64; 2], score: f64, k_factor: f64) -> [f64; 2] {
+    let change = k_factor * (score - expected_score(ratings[0], ratings[1]));
+    [ratings[0] + change, ratings[1] - change]
+}
+
+/// Fleet A's score in a game with this outcome, or `None` for a game that doesn't say who's
+/// better, like a crash.
+pub fn score(outcome: Outcome) -> Option<f64> {
+    match outcome {
+        Outcome::FleetAWon => Some(1.0),
+        Outcome::FleetBWon => Some(0.0),
+        Outcome::Draw => Some(0.5),
+        Outcome::SimCrashed | Outcome::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_score, score, update};
+    use crate::outcome::Outcome;
+
+    fn close(a: f64, b: f64, within: f64) -> bool {
+        (a - b).abs() < within
+    }
+
+    #[test]
+    fn expected_scores_match_the_elo_table() {
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        // 400 points ahead is ten to one
+        assert!(close(expected_score(1800.0, 1400.0), 10.0 / 11.0, 1e-9));
+        assert!(close(
+            expected_score(1400.0, 1800.0) + expected_score(1800.0, 1400.0),
+            1.0,
+            1e-9
+        ));
+        assert!(close(expected_score(1613.0, 1609.0), 0.506, 0.001));
+        assert!(close(expected_score(1613.0, 1720.0), 0.351, 0.001));
+    }
+
+    #[test]
+    fn ratings_move_by_how_surprising_the_result_was() {
+        assert_eq!(update([1500.0, 1500.0], 1.0, 32.0), [1516.0, 1484.0]);
+        assert_eq!(update([1500.0, 1500.0], 0.5, 32.0), [1500.0, 1500.0]);
+        // A draw with a weaker fleet costs the stronger one
+        let [strong, weak] = update([1800.0, 1400.0], 0.5, 32.0);
+        assert!(close(strong, 1800.0 - 32.0 * (10.0 / 11.0 - 0.5), 1e-9));
+        assert!(close(strong + weak, 3200.0, 1e-9));
+
+        // The usual worked example: 1613 scores 2.5 against these five, expecting 2.867
+        let games = [
+            (1609.0, 0.0),
+            (1477.0, 0.5),
+            (1388.0, 1.0),
+            (1586.0, 1.0),
+            (1720.0, 0.0),
+        ];
+        let expected: f64 = games
+            .iter()
+            .map(|&(opponent, _)| expected_score(1613.0, opponent))
+            .sum();
+        assert!(close(expected, 2.867, 0.001));
+        let rating = games.iter().fold(1613.0, |rating, &(opponent, result)| {
+            rating + update([1613.0, opponent], result, 32.0)[0] - 1613.0
+        });
+        assert_eq!(rating.round(), 1601.0);
+
+        assert_eq!(score(Outcome::FleetBWon), Some(0.0));
+        assert_eq!(score(Outcome::SimCrashed), None);
+    }
+}
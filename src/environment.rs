@@ -0,0 +1,309 @@
+//! `env`: everything this tool would use, resolved the way the subcommands resolve it, and where
+//! each came from, for working out why it's looking somewhere.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::config::UserConfig;
+use crate::history::HISTORY_FILE;
+use crate::output::say;
+use crate::settings::{display_value, find_workspace_root, settings, WORKSPACE_FILE};
+use crate::sim::{find_player, locate_protologic_path, protologic_sim_path};
+use crate::version::release_version;
+use crate::{DEBUG_FLEET_DIR, FLEET_OUTPUT_DIR, WASI_TARGET};
+
+/// The environment variable `--protologic-path` is read from.
+const PROTOLOGIC_PATH_ENV: &str = "PROTOLOGIC_PATH";
+const DEFAULT: &str = "the default";
+
+/// Arguments for `cargo protologic env`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct EnvArgs {
+    /// The location of the Protologic/Release repo, as `run` takes it.
+    #[arg(long, value_name = "PATH")]
+    protologic_path: Option<PathBuf>,
+    /// The battle output directory, as `run` takes it.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Resolve for debug builds, as `--debug` does for `build` and `run`.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+    /// Print everything as JSON on stdout, for scripts and bug reports.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// One thing `env` reports: what it resolved to and where that came from, or why it couldn't be
+/// resolved.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub name: String,
+    pub value: Option<String>,
+    /// Like `the command line`, a config file, or `the default`.
+    pub origin: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Resolved {
+    fn new(name: impl Into<String>, resolved: anyhow::Result<(String, String)>) -> Self {
+        let name = name.into();
+        match resolved {
+            Ok((value, origin)) => Self {
+                name,
+                value: Some(value),
+                origin: Some(origin),
+                error: None,
+            },
+            Err(err) => Self {
+                name,
+                value: None,
+                origin: None,
+                error: Some(format!("{err:#}")),
+            },
+        }
+    }
+}
+
+fn display(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_owned())
+        .display()
+        .to_string()
+}
+
+/// A path, with where it came from.
+fn path_from(path: &Path, origin: impl ToString) -> anyhow::Result<(String, String)> {
+    Ok((display(path), origin.to_string()))
+}
+
+/// A config file, which is marked when it isn't there to be read.
+fn config_file(path: &Path, origin: &str) -> anyhow::Result<(String, String)> {
+    let mut value = display(path);
+    if !path.is_file() {
+        value.push_str(" (not created yet)");
+    }
+    Ok((value, origin.to_owned()))
+}
+
+/// Where the Release is, following `run`: `--protologic-path`, which can also come from the
+/// environment, and otherwise where it's looked for.
+fn protologic_path(args: &EnvArgs) -> anyhow::Result<(PathBuf, String)> {
+    if let Some(path) = &args.protologic_path {
+        return Ok((path.clone(), "the command line".to_owned()));
+    }
+    if let Some(path) = std::env::var_os(PROTOLOGIC_PATH_ENV).filter(|path| !path.is_empty()) {
+        return Ok((
+            path.into(),
+            format!("the {PROTOLOGIC_PATH_ENV} environment variable"),
+        ));
+    }
+    locate_protologic_path()
+}
+
+/// The sim and player in the Release, as `run` finds them.
+fn release_tools(release: &Path) -> Vec<Resolved> {
+    let mut tools = Vec::new();
+    let sim = protologic_sim_path(release);
+    tools.push(Resolved::new(
+        "sim",
+        if sim.is_file() {
+            path_from(&sim, "in the Release")
+        } else {
+            Err(anyhow::anyhow!("there's no sim at {}", sim.display()))
+        },
+    ));
+    tools.push(Resolved::new(
+        "sim version",
+        release_version(release, &sim)
+            .map(|version| (version, "the Release".to_owned()))
+            .context("the Release doesn't say its version"),
+    ));
+    let wine = settings().is_ok_and(|settings| settings.flag("run.player-wine", false));
+    tools.push(Resolved::new(
+        "player",
+        find_player(release, wine).and_then(|player| {
+            let origin = if player.wine {
+                "in the Release, run through Wine"
+            } else {
+                "in the Release"
+            };
+            path_from(&player.path, origin)
+        }),
+    ));
+    tools
+}
+
+/// Resolves everything, carrying on past what fails so there's as much to go on as there can be.
+pub fn resolve(args: &EnvArgs) -> Vec<Resolved> {
+    let mut resolved = Vec::new();
+    let cwd = std::env::current_dir().ok();
+    let root = cwd.as_deref().and_then(find_workspace_root);
+
+    resolved.push(Resolved::new(
+        "workspace root",
+        root.as_deref()
+            .context("not in a cargo workspace")
+            .and_then(|root| path_from(root, "the closest Cargo.toml")),
+    ));
+    resolved.push(Resolved::new(
+        "target directory",
+        crate::cargo_metadata().and_then(|metadata| {
+            let origin = if std::env::var_os("CARGO_TARGET_DIR").is_some() {
+                "the CARGO_TARGET_DIR environment variable"
+            } else {
+                "cargo metadata"
+            };
+            path_from(&metadata.target_directory, origin)
+        }),
+    ));
+    let profile = if args.debug { "debug" } else { "release" };
+    let profile_origin = if args.debug {
+        "the command line"
+    } else {
+        DEFAULT
+    };
+    resolved.push(Resolved::new(
+        "profile",
+        Ok((profile.to_owned(), profile_origin.to_owned())),
+    ));
+    resolved.push(Resolved::new(
+        "wasm target",
+        Ok((WASI_TARGET.to_owned(), DEFAULT.to_owned())),
+    ));
+
+    let mut fleets = PathBuf::from(FLEET_OUTPUT_DIR);
+    if args.debug {
+        fleets.push(DEBUG_FLEET_DIR);
+    }
+    resolved.push(Resolved::new(
+        "fleet output dir",
+        path_from(&fleets, DEFAULT),
+    ));
+    resolved.push(Resolved::new(
+        "battle output dir",
+        settings().and_then(|settings| {
+            match settings.path_with_origin("output-dir", args.output_dir.clone()) {
+                Some((dir, origin)) => path_from(&dir, origin),
+                None => path_from(
+                    cwd.as_deref().context("can't find the current directory")?,
+                    "the default, the current directory",
+                ),
+            }
+        }),
+    ));
+    resolved.push(Resolved::new(
+        "battle history",
+        settings().and_then(
+            |settings| match settings.path_with_origin("run.history-file", None) {
+                Some((path, origin)) => path_from(&path, origin),
+                None => path_from(&Path::new(FLEET_OUTPUT_DIR).join(HISTORY_FILE), DEFAULT),
+            },
+        ),
+    ));
+
+    let release = protologic_path(args);
+    resolved.push(Resolved::new(
+        "protologic path",
+        release
+            .as_ref()
+            .map_err(|err| anyhow::anyhow!("{err:#}"))
+            .and_then(|(path, origin)| path_from(path, origin)),
+    ));
+    match &release {
+        Ok((release, _)) => resolved.extend(release_tools(release)),
+        Err(_) => {
+            for name in ["sim", "sim version", "player"] {
+                resolved.push(Resolved::new(
+                    name,
+                    Err(anyhow::anyhow!("needs the Protologic Release")),
+                ));
+            }
+        }
+    }
+
+    resolved.push(Resolved::new(
+        "user config",
+        UserConfig::path().and_then(|path| config_file(&path, "your user config directory")),
+    ));
+    if let Some(root) = &root {
+        resolved.push(Resolved::new(
+            "workspace config",
+            config_file(&root.join(WORKSPACE_FILE), "the workspace root"),
+        ));
+    }
+    match settings() {
+        Ok(settings) => {
+            for (key, value, origin) in settings.values() {
+                resolved.push(Resolved::new(
+                    format!("setting {key}"),
+                    Ok((display_value(&value), origin.to_string())),
+                ));
+            }
+        }
+        Err(err) => resolved.push(Resolved::new("settings", Err(err))),
+    }
+    resolved
+}
+
+/// The resolved values as a table, with where each came from.
+pub fn env_table(resolved: &[Resolved]) -> String {
+    let width = resolved
+        .iter()
+        .map(|resolved| resolved.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut table = String::new();
+    for resolved in resolved {
+        let name = &resolved.name;
+        match (&resolved.value, &resolved.origin, &resolved.error) {
+            (Some(value), Some(origin), _) => {
+                writeln!(table, "{name:<width$}  {value}  (from {origin})").unwrap()
+            }
+            (_, _, error) => writeln!(
+                table,
+                "{name:<width$}  ✗ {}",
+                error.as_deref().unwrap_or("unknown")
+            )
+            .unwrap(),
+        }
+    }
+    table
+}
+
+/// Prints everything this tool would use and where it came from. What can't be resolved is shown
+/// as an error in its place, rather than stopping the rest from being shown.
+pub fn env(args: EnvArgs) -> anyhow::Result<()> {
+    let resolved = resolve(&args);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&resolved)?);
+        return Ok(());
+    }
+    say!("{}", env_table(&resolved).trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{env_table, Resolved};
+
+    #[test]
+    fn env_table_shows_errors_in_place() {
+        let resolved = [
+            Resolved::new(
+                "profile",
+                Ok(("release".to_owned(), "the default".to_owned())),
+            ),
+            Resolved::new(
+                "protologic path",
+                Err(anyhow::anyhow!("can't find the Protologic Release")),
+            ),
+        ];
+        assert_eq!(
+            env_table(&resolved),
+            "profile          release  (from the default)\nprotologic path  ✗ can't find the Protologic Release\n"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Machine readable progress for `--log-format ndjson`, one JSON event per line on stdout.
+//!
+//! Every line is an object with the `schema` version, the kind of `event`, and that event's fields.
+//! Adding fields or events keeps the schema version, anything a consumer could trip over bumps it.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::ndjson;
+use crate::results::BattleRecord;
+
+/// The version of the event schema, in every event.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How `build` and `run` report what they're up to.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Text for people, on stdout.
+    #[default]
+    Human,
+    /// Newline-delimited JSON events on stdout, with the text for people on stderr.
+    Ndjson,
+}
+
+/// The big steps of a command, announced as they start.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    Build,
+    Optimize,
+    Battle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    PhaseStarted {
+        phase: Phase,
+    },
+    /// Cargo finished building a package.
+    PackageBuilt {
+        package: String,
+    },
+    /// A fleet's wasm was optimized into the fleet output directory.
+    OptFinished {
+        fleet: String,
+        input_bytes: u64,
+        output_bytes: u64,
+        path: PathBuf,
+    },
+    BattleStarted {
+        fleet_a: String,
+        fleet_b: String,
+        seed: Option<u64>,
+        scenario: Option<String>,
+    },
+    /// A battle finished, with the same result that's written next to its replay.
+    BattleFinished {
+        result: Box<BattleRecord>,
+    },
+    /// The command failed. Always the last event.
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Envelope {
+    schema: u32,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Writes the event to stdout if we're logging NDJSON, and does nothing otherwise.
+pub fn emit(event: Event) {
+    if !ndjson() {
+        return;
+    }
+
+    let envelope = Envelope {
+        schema: SCHEMA_VERSION,
+        event,
+    };
+    // Every field serializes fine, so this could only fail on a broken stdout, which there's no
+    // one to tell about anyway
+    if let Ok(mut line) = serde_json::to_string(&envelope) {
+        line.push('\n');
+        let _ = std::io::stdout().lock().write_all(line.as_bytes());
+    }
+}
+
+/// Parses a line of `--log-format ndjson` output, the way a consumer would.
+#[cfg(test)]
+fn parse_event(line: &str) -> anyhow::Result<Event> {
+    let envelope: Envelope = serde_json::from_str(line)?;
+    if envelope.schema != SCHEMA_VERSION {
+        anyhow::bail!("unsupported event schema {}", envelope.schema);
+    }
+    Ok(envelope.event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_event, Envelope, Event, Phase, SCHEMA_VERSION};
+    use crate::outcome::Outcome;
+
+    // These lines are the schema. If this test needs changing, so does `SCHEMA_VERSION`.
+    const TRANSCRIPT: &str = r#"{"schema":1,"event":"phase-started","phase":"build"}
+{"schema":1,"event":"package-built","package":"glass_cannon"}
+{"schema":1,"event":"phase-started","phase":"optimize"}
+{"schema":1,"event":"opt-finished","fleet":"glass_cannon","input_bytes":2048,"output_bytes":1024,"path":"target/protologic_fleets/glass_cannon.wasm"}
+{"schema":1,"event":"phase-started","phase":"battle"}
+{"schema":1,"event":"battle-started","fleet_a":"glass_cannon","fleet_b":"iron_armada","seed":7,"scenario":null}
+{"schema":1,"event":"battle-finished","result":{"timestamp":1718476403,"fleet_a":"glass_cannon","fleet_b":"iron_armada","seed":7,"outcome":"fleet_b_won","winner":"iron_armada","replay":"a_vs_b.json.deflate"}}
+{"schema":1,"event":"error","message":"the protologic player exited with exit status: 4"}"#;
+
+    #[test]
+    fn consumer_can_follow_a_run() -> anyhow::Result<()> {
+        let events = TRANSCRIPT
+            .lines()
+            .map(parse_event)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        assert_eq!(
+            events[0],
+            Event::PhaseStarted {
+                phase: Phase::Build
+            }
+        );
+        let Event::BattleFinished { result } = &events[6] else {
+            panic!("expected a battle result, got {:?}", events[6]);
+        };
+        assert_eq!(result.outcome, Outcome::FleetBWon);
+        assert_eq!(result.winner.as_deref(), Some("iron_armada"));
+        assert!(matches!(events.last(), Some(Event::Error { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn events_serialize_to_the_schema() -> anyhow::Result<()> {
+        let event = Event::PackageBuilt {
+            package: "glass_cannon".to_owned(),
+        };
+        let line = serde_json::to_string(&Envelope {
+            schema: SCHEMA_VERSION,
+            event,
+        })?;
+        assert_eq!(line, TRANSCRIPT.lines().nth(1).unwrap());
+        Ok(())
+    }
+}
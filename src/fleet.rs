@@ -0,0 +1,666 @@
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+
+use crate::fleet_output_path;
+use crate::manifest::{FleetManifest, ManifestEntry};
+use crate::output::say;
+use crate::reference::find_namespaced;
+use crate::wasm::validate_wasm_file;
+
+/// A built fleet artifact, as found in the fleet output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fleet {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Fleet {
+    /// A built fleet, named by the fleet manifest of the directory it was built into.
+    pub fn from_path(path: PathBuf, manifest: &FleetManifest) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: manifest.fleet_name(&path)?,
+            path,
+        })
+    }
+
+    /// What to pass to `--fleet-a` or `--fleet-b` to pick this fleet, which stays the same however
+    /// the fleet is displayed and is easy to type into a shell.
+    pub fn selector(&self) -> String {
+        extract_fleet_name(&self.path).unwrap_or_else(|_| self.name.clone())
+    }
+
+    /// Whether `name` picks this fleet: either its name, or its wasm's file name without `.wasm`.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || extract_fleet_name(&self.path).is_ok_and(|stem| stem == name)
+    }
+
+    /// What `build` recorded about the fleet, if it was built by us.
+    pub fn manifest_entry(&self) -> Option<ManifestEntry> {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        FleetManifest::read(dir).ok()?.entry(&self.path).cloned()
+    }
+
+    /// Checks the fleet is a wasm module the sim has a chance of running, which is much quicker to
+    /// find out here than from the sim.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        validate_wasm_file(&self.path).with_context(|| {
+            format!(
+                "fleet '{}' is broken, try rebuilding it with `cargo protologic build`",
+                self.name
+            )
+        })
+    }
+
+    /// A fleet from outside the fleet output directory, like one a friend sent over.
+    ///
+    /// These haven't gone through our build, so they're validated up front.
+    pub fn from_external(path: &Path) -> anyhow::Result<Self> {
+        validate_wasm_file(path)?;
+
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .with_context(|| format!("can't find a fleet name in the path {path:?}"))?
+            .to_owned();
+
+        Ok(Self {
+            name,
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// Copies of a fleet's wasm that sit next to it in the fleet output directory, but aren't fleets.
+const AUXILIARY_SUFFIXES: &[&str] = &[".unopt.wasm"];
+
+/// Finds the fleets built with the given profile.
+pub fn find_built_fleets(debug: bool) -> anyhow::Result<Vec<Fleet>> {
+    let path = fleet_output_path(debug)?;
+    // Only in a dry run, which doesn't create it
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    fleets_in_dir(&path)
+}
+
+/// The fleets in a fleet output directory, sorted by name.
+///
+/// The directory also holds things like the fleet manifest, the debug build directory and
+/// unoptimized copies of fleets, so only regular `.wasm` files count.
+pub fn fleets_in_dir(dir: &Path) -> anyhow::Result<Vec<Fleet>> {
+    let is_fleet = |path: &Path| {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        // Leaving out hidden files skips half-written temporary files too
+        name.ends_with(".wasm")
+            && !name.starts_with('.')
+            && !AUXILIARY_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+            && path.is_file()
+    };
+
+    let manifest = FleetManifest::read(dir)?;
+    let mut fleets = Vec::new();
+    for entry in std::fs::read_dir(dir).context("trying to list fleet output directory")? {
+        let path = entry
+            .context("trying to collect fleets in output directory")?
+            .path();
+        if is_fleet(&path) {
+            fleets.push(Fleet::from_path(path, &manifest)?);
+        }
+    }
+
+    // `read_dir` order is platform dependent, sort so fleet listings are stable
+    fleets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(fleets)
+}
+
+/// Finds the fleets to battle, preferring ones built with the same profile as the battle.
+///
+/// Falls back to the other profile with a warning, since battling something beats battling nothing.
+pub fn find_fleets_for_run(debug: bool) -> anyhow::Result<Vec<Fleet>> {
+    let fleets = find_built_fleets(debug)?;
+    if !fleets.is_empty() {
+        return Ok(fleets);
+    }
+
+    let fallback = find_built_fleets(!debug)?;
+    if !fallback.is_empty() {
+        if debug {
+            say!("Warning: no debug fleets are built, so battling release fleets. Build with `cargo protologic build --debug` for debug fleets");
+        } else {
+            say!("Warning: only debug fleets are built, so battling those. They can be much slower than release fleets");
+        }
+    }
+
+    Ok(fallback)
+}
+
+/// Takes the path to a fleet, extracting out the name of the fleet the correct way
+pub fn extract_fleet_name(fleet_path: impl AsRef<Path>) -> anyhow::Result<String> {
+    fleet_path
+        .as_ref()
+        // drop the `.wasm`
+        .with_extension("")
+        .file_name()
+        .context("fleet name wouldn't be found in fleet path. Try again?")?
+        .to_str()
+        .context("you need to name your fleet valid unicode!")
+        .map(ToOwned::to_owned)
+}
+
+/// Which fleets the user asked to battle on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct FleetSelection {
+    pub fleet_a: Option<String>,
+    pub fleet_b: Option<String>,
+    pub latest: bool,
+    /// Paths to external fleet wasm files, taking the place of built fleets.
+    pub vs: Vec<PathBuf>,
+    pub non_interactive: bool,
+}
+
+/// Picks the two fleets to battle out of the built fleets.
+///
+/// Explicitly named fleets always win. When the choice is ambiguous, the user gets an interactive
+/// picker if we're attached to a terminal, and a hard error otherwise.
+pub fn select_fleets(
+    fleets: Vec<Fleet>,
+    selection: &FleetSelection,
+) -> anyhow::Result<(Fleet, Fleet)> {
+    let external = selection
+        .vs
+        .iter()
+        .map(|path| match path.to_str().and_then(find_namespaced) {
+            Some(fleet) => fleet,
+            None => Fleet::from_external(path),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    match <[Fleet; 2]>::try_from(external) {
+        Ok([a, b]) => return Ok((a, b)),
+        Err(external) => match <[Fleet; 1]>::try_from(external) {
+            Ok([opponent]) => return Ok((select_own_fleet(fleets, selection)?, opponent)),
+            Err(external) if !external.is_empty() => {
+                anyhow::bail!("`--vs` can be given at most twice, once for each side of the battle")
+            }
+            Err(_) => {}
+        },
+    }
+
+    match fleets.as_slice() {
+        [] => return Err(no_fleets_built()),
+        // Naming it on both sides battles the fleet against itself
+        [fleet] if selection.fleet_a.is_none() || selection.fleet_b.is_none() => {
+            return Err(one_fleet_built(fleet))
+        }
+        _ => {}
+    }
+
+    if selection.latest {
+        let (a, b) = latest_fleets(fleets)?;
+        say!(
+            "Picked the two most recently built fleets: '{}' and '{}'",
+            a.name,
+            b.name
+        );
+        return Ok((a, b));
+    }
+
+    let find = |name: &str| find_fleet(&fleets, name);
+
+    match (&selection.fleet_a, &selection.fleet_b) {
+        (Some(a), Some(b)) => return Ok((find(a)?, find(b)?)),
+        (Some(name), None) | (None, Some(name)) => {
+            let chosen = find(name)?;
+            let others = fleets
+                .iter()
+                .filter(|fleet| fleet.name != chosen.name)
+                .collect::<Vec<_>>();
+
+            if let [other] = others.as_slice() {
+                let other = (*other).clone();
+                return Ok(if selection.fleet_a.is_some() {
+                    (chosen, other)
+                } else {
+                    (other, chosen)
+                });
+            }
+        }
+        (None, None) => {
+            if let [a, b] = fleets.as_slice() {
+                return Ok((a.clone(), b.clone()));
+            }
+        }
+    }
+
+    if !selection.is_interactive() {
+        anyhow::bail!(
+            "found {} built fleets, pick two with `--fleet-a` and `--fleet-b`. Built fleets are: {}",
+            fleets.len(),
+            fleet_names(&fleets)
+        );
+    }
+
+    let (a, b) = pick_fleets(&fleets)?;
+    say!(
+        "Picked fleets '{}' and '{}'. To rerun this battle: cargo protologic run --fleet-a {} --fleet-b {}",
+        a.name,
+        b.name,
+        a.selector(),
+        b.selector()
+    );
+
+    Ok((a, b))
+}
+
+/// Picks the built fleet to battle against an external `--vs` fleet.
+fn select_own_fleet(fleets: Vec<Fleet>, selection: &FleetSelection) -> anyhow::Result<Fleet> {
+    if fleets.is_empty() {
+        return Err(no_fleets_built());
+    }
+    if let Some(name) = &selection.fleet_a {
+        return find_fleet(&fleets, name);
+    }
+    if let [fleet] = fleets.as_slice() {
+        return Ok(fleet.clone());
+    }
+
+    if !selection.is_interactive() {
+        anyhow::bail!(
+            "found {} built fleets, pick one to battle the `--vs` fleet with `--fleet-a`. Built fleets are: {}",
+            fleets.len(),
+            fleet_names(&fleets)
+        );
+    }
+
+    let items = fleets
+        .iter()
+        .map(describe_fleet)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let chosen = dialoguer::Select::new()
+        .with_prompt("Pick a fleet to battle the `--vs` fleet")
+        .items(&items)
+        .interact_opt()
+        .context("trying to prompt for a fleet to battle")?
+        .context("no fleet was picked, cancelling the battle")?;
+
+    let fleet = fleets[chosen].clone();
+    say!(
+        "Picked fleet '{}'. To rerun this battle, pass `--fleet-a {}`",
+        fleet.name,
+        fleet.selector()
+    );
+
+    Ok(fleet)
+}
+
+impl FleetSelection {
+    /// Whether we're allowed to prompt the user to resolve an ambiguous selection.
+    fn is_interactive(&self) -> bool {
+        !self.non_interactive && std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+    }
+}
+
+/// The fleet `name` picks, suggesting what it might be a typo of when there isn't one.
+///
+/// Names starting with `@` pick a fleet from outside the workspace, like `@reference/tutorial-bot`.
+pub fn find_fleet(fleets: &[Fleet], name: &str) -> anyhow::Result<Fleet> {
+    if let Some(fleet) = find_namespaced(name) {
+        return fleet;
+    }
+    fleets
+        .iter()
+        .find(|fleet| fleet.is_named(name))
+        .cloned()
+        .with_context(|| {
+            let suggestions = match similar_fleets(fleets, name).as_slice() {
+                [] => ".".to_owned(),
+                [fleet] => format!(", did you mean `{fleet}`?"),
+                fleets => format!(", did you mean one of `{}`?", fleets.join("`, `")),
+            };
+            format!(
+                "no built fleet named '{name}'{suggestions} Built fleets are: {}",
+                fleet_names(fleets)
+            )
+        })
+}
+
+/// The fleet an argument names: a path to a wasm file as it is, or a name among the fleets built
+/// with the given profile.
+pub fn find_fleet_or_file(name: &str, debug: bool) -> anyhow::Result<Fleet> {
+    let path = Path::new(name);
+    if path.extension().is_some_and(|ext| ext == "wasm") || path.is_file() {
+        return Fleet::from_external(path);
+    }
+    find_fleet(&find_built_fleets(debug)?, name)
+}
+
+/// The names to pick fleets by that are close to `name`, closest first, for when it's a typo.
+fn similar_fleets(fleets: &[Fleet], name: &str) -> Vec<String> {
+    // Close enough to be a typo, without suggesting everything for short names
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut similar: Vec<_> = fleets
+        .iter()
+        .filter_map(|fleet| {
+            let distance =
+                edit_distance(&fleet.name, name).min(edit_distance(&fleet.selector(), name));
+            (distance <= max_distance).then(|| (distance, fleet.selector()))
+        })
+        .collect();
+
+    similar.sort();
+    similar.dedup();
+    similar
+        .into_iter()
+        .take(3)
+        .map(|(_, selector)| selector)
+        .collect()
+}
+
+/// The Levenshtein distance between two strings, ignoring case: how many single character
+/// insertions, deletions or substitutions it takes to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    // Only the previous row of the table is needed for the next one
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Explains what to do in a workspace without any built fleets, like a fresh one.
+fn no_fleets_built() -> anyhow::Error {
+    let dir = fleet_output_path(false).map_or_else(
+        |_| "the fleet output directory".to_owned(),
+        |dir| {
+            let dir = std::path::absolute(&dir).unwrap_or(dir);
+            format!("{}", dir.display())
+        },
+    );
+    anyhow::anyhow!(
+        "no fleets are built yet, {dir} is empty. Build the fleets in your workspace with `cargo protologic build`"
+    )
+}
+
+/// Explains the ways to battle when there's only one fleet built.
+fn one_fleet_built(fleet: &Fleet) -> anyhow::Error {
+    let dir = fleet.path.parent().unwrap_or(Path::new("."));
+    let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_owned());
+    let selector = fleet.selector();
+    anyhow::anyhow!(
+        "only one fleet is built, '{}' in {}. Build a second fleet to battle it, battle it against itself with `--fleet-a {selector} --fleet-b {selector}`, or battle a fleet from elsewhere with `--vs path/to/fleet.wasm`",
+        fleet.name,
+        dir.display()
+    )
+}
+
+/// Picks the two most recently built fleets, newest first.
+fn latest_fleets(fleets: Vec<Fleet>) -> anyhow::Result<(Fleet, Fleet)> {
+    let mut fleets = fleets
+        .into_iter()
+        .map(|fleet| {
+            let modified = std::fs::metadata(&fleet.path)
+                .and_then(|metadata| metadata.modified())
+                .with_context(|| format!("trying to read build time of fleet {:?}", fleet.path))?;
+            Ok((modified, fleet))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    fleets.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut fleets = fleets.into_iter().map(|(_, fleet)| fleet);
+    match (fleets.next(), fleets.next()) {
+        (Some(a), Some(b)) => Ok((a, b)),
+        (Some(a), None) => anyhow::bail!(
+            "`--latest` needs at least two built fleets, but only '{}' is built",
+            a.name
+        ),
+        _ => anyhow::bail!("`--latest` needs at least two built fleets, but none are built"),
+    }
+}
+
+fn fleet_names(fleets: &[Fleet]) -> String {
+    fleets
+        .iter()
+        .map(|fleet| format!("'{}'", fleet.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prompts the user to pick exactly two fleets from a multi-select list.
+fn pick_fleets(fleets: &[Fleet]) -> anyhow::Result<(Fleet, Fleet)> {
+    let items = fleets
+        .iter()
+        .map(describe_fleet)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    loop {
+        let chosen = dialoguer::MultiSelect::new()
+            .with_prompt("Pick two fleets to battle (space to select, enter to confirm)")
+            .items(&items)
+            .interact_opt()
+            .context("trying to prompt for fleets to battle")?
+            .context("no fleets were picked, cancelling the battle")?;
+
+        match chosen.as_slice() {
+            [a, b] => return Ok((fleets[*a].clone(), fleets[*b].clone())),
+            _ => eprintln!(
+                "Please pick exactly two fleets, you picked {}.",
+                chosen.len()
+            ),
+        }
+    }
+}
+
+/// Describes a fleet for the picker, like `my_fleet (123.4 KB, built 5m ago)`.
+fn describe_fleet(fleet: &Fleet) -> anyhow::Result<String> {
+    let metadata = std::fs::metadata(&fleet.path)
+        .with_context(|| format!("trying to read metadata of fleet {:?}", fleet.path))?;
+
+    let built = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or_else(|| "unknown".to_owned(), |age| format_age(age.as_secs()));
+
+    Ok(format!(
+        "{} ({}, built {built})",
+        fleet.name,
+        ByteSize::b(metadata.len())
+    ))
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        edit_distance, extract_fleet_name, fleets_in_dir, select_fleets, Fleet, FleetSelection,
+    };
+
+    fn fleet(name: &str) -> Fleet {
+        Fleet {
+            name: name.to_owned(),
+            path: PathBuf::from(format!("{name}.wasm")),
+        }
+    }
+
+    #[test]
+    fn fleets_in_dir_only_finds_fleets() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in [
+            "iron_armada.wasm",
+            "glass_cannon.wasm",
+            "glass_cannon.unopt.wasm",
+            ".glass_cannon.wasm.1234.tmp",
+            ".hidden.wasm",
+            "fleets.json",
+            "history.jsonl",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.path().join(name), b"\0asm\x01\0\0\0")?;
+        }
+        std::fs::write(
+            dir.path().join("fleet_manifest.json"),
+            r#"{"fleets": {"iron_armada.wasm": {"package": "iron-armada"}}}"#,
+        )?;
+        std::fs::create_dir(dir.path().join("debug"))?;
+        std::fs::write(dir.path().join("debug/glass_cannon.wasm"), b"")?;
+        std::fs::create_dir(dir.path().join("odd.wasm"))?;
+
+        let fleets = fleets_in_dir(dir.path())?;
+        let names: Vec<_> = fleets.iter().map(|fleet| fleet.name.as_str()).collect();
+        assert_eq!(names, ["glass_cannon", "iron_armada"]);
+        assert_eq!(fleets[0].path, dir.path().join("glass_cannon.wasm"));
+        Ok(())
+    }
+
+    #[test]
+    fn select_fleets_explains_a_lone_fleet() -> anyhow::Result<()> {
+        let selection = FleetSelection {
+            non_interactive: true,
+            ..Default::default()
+        };
+        let err = select_fleets(vec![fleet("glass_cannon")], &selection).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("`--fleet-a glass_cannon --fleet-b glass_cannon`"));
+
+        let selection = FleetSelection {
+            fleet_a: Some("glass_cannon".to_owned()),
+            fleet_b: Some("glass_cannon".to_owned()),
+            ..selection
+        };
+        let (a, b) = select_fleets(vec![fleet("glass_cannon")], &selection)?;
+        assert_eq!(a, b);
+        Ok(())
+    }
+
+    #[test]
+    fn edit_distance_counts_typos() {
+        assert_eq!(edit_distance("iron_armada", "iron_armada"), 0);
+        assert_eq!(edit_distance("iron_armada", "iron_armda"), 1);
+        assert_eq!(edit_distance("iron_armada", "Iron_Armada"), 0);
+        assert_eq!(edit_distance("glass_cannon", "glass_canon_"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn unknown_fleets_get_suggestions() {
+        let fleets = [
+            fleet("iron_armada"),
+            fleet("glass_cannon"),
+            fleet("drone_swarm"),
+        ];
+        let selection = FleetSelection {
+            fleet_a: Some("iron_armda".to_owned()),
+            fleet_b: Some("glass_cannon".to_owned()),
+            non_interactive: true,
+            ..Default::default()
+        };
+
+        let err = select_fleets(fleets.to_vec(), &selection).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("no built fleet named 'iron_armda', did you mean `iron_armada`?"));
+
+        let selection = FleetSelection {
+            fleet_a: Some("battleship".to_owned()),
+            ..selection
+        };
+        let err = select_fleets(fleets.to_vec(), &selection).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("no built fleet named 'battleship'. Built fleets are"));
+    }
+
+    #[test]
+    fn extract_fleet_name_is_sane() -> anyhow::Result<()> {
+        let path = PathBuf::from("fleet_demo_fleet_foo_bar.wasm");
+        let name = extract_fleet_name(path)?;
+        assert_eq!("fleet_demo_fleet_foo_bar", name);
+
+        let path = PathBuf::from("demo_fleet_foo_bar");
+        let name = extract_fleet_name(path)?;
+        assert_eq!("demo_fleet_foo_bar", name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_fleets_resolves_unambiguous_selections() -> anyhow::Result<()> {
+        let selection = FleetSelection {
+            non_interactive: true,
+            ..Default::default()
+        };
+        let (a, b) = select_fleets(vec![fleet("one"), fleet("two")], &selection)?;
+        assert_eq!((a.name.as_str(), b.name.as_str()), ("one", "two"));
+
+        let selection = FleetSelection {
+            fleet_b: Some("one".to_owned()),
+            non_interactive: true,
+            ..Default::default()
+        };
+        let (a, b) = select_fleets(vec![fleet("one"), fleet("two")], &selection)?;
+        assert_eq!((a.name.as_str(), b.name.as_str()), ("two", "one"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_fleets_takes_display_names_or_file_names() -> anyhow::Result<()> {
+        let renamed = Fleet {
+            name: "Glass Cannon".to_owned(),
+            path: PathBuf::from("glass_cannon.wasm"),
+        };
+        for name in ["Glass Cannon", "glass_cannon"] {
+            let selection = FleetSelection {
+                fleet_a: Some(name.to_owned()),
+                non_interactive: true,
+                ..Default::default()
+            };
+            let (a, _) = select_fleets(vec![renamed.clone(), fleet("two")], &selection)?;
+            assert_eq!(a.name, "Glass Cannon");
+            assert_eq!(a.selector(), "glass_cannon");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn select_fleets_errors_when_ambiguous_and_non_interactive() {
+        let selection = FleetSelection {
+            non_interactive: true,
+            ..Default::default()
+        };
+        let fleets = vec![fleet("one"), fleet("two"), fleet("three")];
+        assert!(select_fleets(fleets, &selection).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+
+use crate::naming::add_extension;
+
+/// Splits a line of sim output into the index of the fleet that printed it and what it printed.
+///
+/// Recent sims tag fleet output like `[Fleet 0] message`, `[Player 1] message`, `[0] message` or
+/// `Fleet 1: message`. Anything else wasn't printed by a fleet, as far as we can tell.
+pub fn parse_fleet_line(line: &str) -> Option<(usize, &str)> {
+    let (tag, message, bracketed) = match line.strip_prefix('[') {
+        Some(rest) => {
+            let (tag, message) = rest.split_once(']')?;
+            (tag, message, true)
+        }
+        None => {
+            let (tag, message) = line.split_once(':')?;
+            (tag, message, false)
+        }
+    };
+
+    let tag = tag.trim().to_lowercase();
+    let index = match tag
+        .strip_prefix("fleet")
+        .or_else(|| tag.strip_prefix("player"))
+    {
+        Some(index) => index.trim_start(),
+        // A bare index is too easy to mistake for something else, unless it's in brackets
+        None if bracketed => &tag,
+        None => return None,
+    };
+
+    match index {
+        "0" => Some((0, message.strip_prefix(' ').unwrap_or(message))),
+        "1" => Some((1, message.strip_prefix(' ').unwrap_or(message))),
+        _ => None,
+    }
+}
+
+/// Labels sim output lines with the name of the fleet that printed them.
+pub struct FleetTagger {
+    names: [String; 2],
+    color: bool,
+    /// Separate logs of what each fleet printed, if they were asked for.
+    logs: Option<[Mutex<File>; 2]>,
+}
+
+impl FleetTagger {
+    /// With `split_logs` set, each fleet's lines are also written to [`fleet_log_paths`] for it.
+    pub fn new(names: [&str; 2], color: bool, split_logs: Option<&Path>) -> anyhow::Result<Self> {
+        let logs = split_logs
+            .map(|output| {
+                let [a, b] = fleet_log_paths(output);
+                let create = |path: PathBuf| {
+                    File::create(&path)
+                        .map(Mutex::new)
+                        .with_context(|| format!("trying to create fleet log {path:?}"))
+                };
+                anyhow::Ok([create(a)?, create(b)?])
+            })
+            .transpose()?;
+
+        Ok(Self {
+            names: names.map(ToOwned::to_owned),
+            color,
+            logs,
+        })
+    }
+
+    /// The line with the fleet's tag swapped for its name, or untouched if no fleet printed it.
+    /// Colored when writing to a terminal.
+    pub fn tag<'a>(&self, line: &'a str, terminal: bool) -> Cow<'a, str> {
+        let Some((index, message)) = parse_fleet_line(line) else {
+            return Cow::Borrowed(line);
+        };
+
+        let name = &self.names[index];
+        if terminal && self.color {
+            // Cyan for the first fleet, magenta for the second
+            let color = if index == 0 { 36 } else { 35 };
+            Cow::Owned(format!("\x1b[{color}m[{name}]\x1b[0m {message}"))
+        } else {
+            Cow::Owned(format!("[{name}] {message}"))
+        }
+    }
+
+    /// Writes the line to the log of the fleet that printed it, if there are fleet logs.
+    pub fn write_fleet_log(&self, line: &str) -> std::io::Result<()> {
+        let (Some(logs), Some((index, message))) = (&self.logs, parse_fleet_line(line)) else {
+            return Ok(());
+        };
+
+        writeln!(
+            logs[index].lock().expect("fleet log lock poisoned"),
+            "{message}"
+        )
+    }
+}
+
+/// Where each fleet's lines go for a given battle output base path.
+pub fn fleet_log_paths(output: &Path) -> [PathBuf; 2] {
+    [
+        add_extension(output, "fleet_a.log"),
+        add_extension(output, "fleet_b.log"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_fleet_line, FleetTagger};
+
+    const TRANSCRIPT: &str = include_str!("testdata/sim_transcript.txt");
+
+    #[test]
+    fn parse_fleet_line_understands_sim_transcripts() {
+        let tagged: Vec<_> = TRANSCRIPT.lines().filter_map(parse_fleet_line).collect();
+        assert_eq!(
+            tagged,
+            [
+                (0, "spawned 4 ships"),
+                (1, "spawned 6 ships"),
+                (0, "target acquired: ship 12"),
+                (1, "debug: fuel=0.82"),
+                (1, "missile launched at 12"),
+                (0, "retreating"),
+                (1, "ship 3 destroyed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_names_fleets_and_passes_other_lines_through() -> anyhow::Result<()> {
+        let tagger = FleetTagger::new(["glass_cannon", "iron_armada"], true, None)?;
+        assert_eq!(
+            tagger.tag("[Fleet 1] debug: fuel=0.82", false),
+            "[iron_armada] debug: fuel=0.82"
+        );
+        assert_eq!(
+            tagger.tag("[Fleet 0] hi", true),
+            "\x1b[36m[glass_cannon]\x1b[0m hi"
+        );
+        assert_eq!(
+            tagger.tag("Winner: iron_armada", true),
+            "Winner: iron_armada"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,713 @@
+//! Testing a fleet's logic without the sim: its ticks run in wasmtime, with the sim's functions
+//! returning what a scenario file scripts, and the calls it makes checked against what the
+//! scenario expects.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use wasmtime::{Caller, Config, Engine, Extern, ExternType, Linker, Module, Store, Trap, Val};
+
+use crate::bench::Inputs;
+use crate::fleet::find_fleet_or_file;
+use crate::output::say;
+use crate::preflight::ENTRY_POINTS;
+use crate::validate::ALLOWED_IMPORT_MODULES;
+use crate::ExitError;
+
+/// How many ticks a test runs, unless the scenario or `--ticks` says.
+const DEFAULT_TICKS: u32 = 1000;
+/// The most fuel a tick can take before it counts as stuck, unless the scenario or `--fuel-limit`
+/// says. Far more than the sim allows.
+const DEFAULT_FUEL_LIMIT: u64 = 2_000_000_000;
+/// How many of the other calls to a function to show when an expectation about it fails.
+const CALLS_SHOWN: usize = 3;
+
+/// Arguments for `cargo protologic test`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct TestArgs {
+    /// The fleet to test, by name or as a path to a wasm file.
+    fleet: String,
+    /// A TOML file of what the sim's functions return and which calls the fleet is expected to make.
+    #[arg(long, value_name = "PATH")]
+    scenario: Option<PathBuf>,
+    /// How many ticks to run, if not the scenario's `ticks` or 1000.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    ticks: Option<u32>,
+    /// The fuel a tick can take before the fleet counts as stuck, if not the scenario's `fuel-limit`.
+    #[arg(long, value_name = "FUEL", value_parser = clap::value_parser!(u64).range(1..))]
+    fuel_limit: Option<u64>,
+    /// Write every call the fleet made, and what it printed, to this file as JSON lines.
+    #[arg(long, value_name = "PATH")]
+    trace: Option<PathBuf>,
+    /// Test the debug build of the fleet, rather than the optimized one.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+}
+
+/// A scenario file: what the sim's functions return on each tick, like `bench --inputs`, and
+/// the calls the fleet should make.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Scenario {
+    pub ticks: Option<u32>,
+    pub fuel_limit: Option<u64>,
+    #[serde(default)]
+    pub inputs: Inputs,
+    #[serde(default, rename = "expect")]
+    pub expectations: Vec<Expectation>,
+}
+
+impl Scenario {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("trying to read the scenario {path:?}"))?;
+        let scenario: Self = toml::from_str(&contents)
+            .with_context(|| format!("trying to parse the scenario {path:?}"))?;
+        for expectation in &scenario.expectations {
+            if expectation.tick.is_some() && expectation.by_tick.is_some() {
+                anyhow::bail!(
+                    "the scenario {path:?} expects `{}` both on and by a tick, pick one",
+                    expectation.call
+                );
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+/// An `[[expect]]` of a scenario: a call the fleet should make, or with `max-count = 0` one it
+/// shouldn't.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Expectation {
+    /// The sim function, like `engine_set_throttle`.
+    pub call: String,
+    /// The arguments it's called with. Any arguments count, if not given.
+    pub args: Option<Vec<f64>>,
+    /// Only calls on this tick count.
+    pub tick: Option<u32>,
+    /// Only calls up to and including this tick count.
+    pub by_tick: Option<u32>,
+    /// The fewest calls there should be. Defaults to 1, unless `max-count` is given.
+    pub min_count: Option<u32>,
+    pub max_count: Option<u32>,
+}
+
+impl Expectation {
+    fn counts(&self, call: &Call) -> bool {
+        call.call == self.call
+            && self.tick.is_none_or(|tick| call.tick == tick)
+            && self.by_tick.is_none_or(|tick| call.tick <= tick)
+            && self
+                .args
+                .as_ref()
+                .is_none_or(|args| same_args(&call.args, args))
+    }
+
+    fn bounds(&self) -> (u32, Option<u32>) {
+        let min = self
+            .min_count
+            .unwrap_or(if self.max_count.is_some() { 0 } else { 1 });
+        (min, self.max_count)
+    }
+
+    /// What's expected, like `engine_set_throttle(1) by tick 10, at least once`.
+    pub fn describe(&self) -> String {
+        let mut description = self.call.clone();
+        if let Some(args) = &self.args {
+            let args: Vec<String> = args.iter().map(f64::to_string).collect();
+            write!(description, "({})", args.join(", ")).unwrap();
+        }
+        if let Some(tick) = self.tick {
+            write!(description, " on tick {tick}").unwrap();
+        }
+        if let Some(tick) = self.by_tick {
+            write!(description, " by tick {tick}").unwrap();
+        }
+        let count = match self.bounds() {
+            (_, Some(0)) => "never".to_owned(),
+            (min, Some(max)) if min == max => format!("exactly {}", times(min)),
+            (0, Some(max)) => format!("at most {}", times(max)),
+            (min, Some(max)) => format!("{min} to {max} times"),
+            (min, None) => format!("at least {}", times(min)),
+        };
+        format!("{description}, {count}")
+    }
+
+    /// Checks the expectation against the calls, with why it failed if it did.
+    pub fn check(&self, calls: &[Call]) -> Result<(), String> {
+        let count = calls.iter().filter(|call| self.counts(call)).count() as u32;
+        let (min, max) = self.bounds();
+        if count >= min && max.is_none_or(|max| count <= max) {
+            return Ok(());
+        }
+
+        let mut failure = format!("it was called {}", times(count));
+        let others: Vec<String> = calls
+            .iter()
+            .filter(|call| call.call == self.call && !self.counts(call))
+            .take(CALLS_SHOWN)
+            .map(Call::describe)
+            .collect();
+        if !others.is_empty() {
+            write!(failure, " like that, but there's {}", others.join(", ")).unwrap();
+        }
+        Err(failure)
+    }
+}
+
+fn times(count: u32) -> String {
+    match count {
+        1 => "once".to_owned(),
+        count => format!("{count} times"),
+    }
+}
+
+/// Whether a call's arguments are the expected ones, allowing for them being `f32`s.
+fn same_args(args: &[f64], expected: &[f64]) -> bool {
+    args.len() == expected.len()
+        && args
+            .iter()
+            .zip(expected)
+            .all(|(arg, expected)| (arg - expected).abs() <= 1e-6 * expected.abs().max(1.0))
+}
+
+/// A call the fleet made to one of the sim's functions that does something, rather than reading
+/// something: one that doesn't return anything, like `engine_set_throttle`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Call {
+    pub tick: u32,
+    pub call: String,
+    pub args: Vec<f64>,
+}
+
+impl Call {
+    fn describe(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(f64::to_string).collect();
+        format!("{}({}) on tick {}", self.call, args.join(", "), self.tick)
+    }
+}
+
+/// A line of the trace: a call, or something the fleet printed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum TraceLine<'a> {
+    Call(&'a Call),
+    Print { tick: u32, print: &'a str },
+}
+
+/// What running a fleet's ticks did.
+#[derive(Debug, Clone, Default)]
+pub struct TestRun {
+    /// How many ticks it got through.
+    pub ticks: u32,
+    pub calls: Vec<Call>,
+    /// The sim functions the fleet imports whose calls are recorded.
+    pub recorded: Vec<String>,
+    /// The lines it printed to stdout or stderr, with the tick it printed them on.
+    pub printed: Vec<(u32, String)>,
+    /// Why it stopped before the ticks were up, if it did.
+    pub stopped: Option<String>,
+    /// Whether that was a failure, like a trap, rather than the fleet finishing.
+    pub failed: bool,
+}
+
+/// Why the test stopped a fleet, smuggled out of a host function as an error.
+#[derive(Debug)]
+enum Stop {
+    Done,
+    Exited(i32),
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Done => f.write_str("ran all its ticks"),
+            Stop::Exited(code) => write!(f, "exited with code {code}"),
+        }
+    }
+}
+
+impl std::error::Error for Stop {}
+
+struct TestState {
+    target: u32,
+    fuel_limit: u64,
+    tick: u32,
+    calls: Vec<Call>,
+    /// What's been printed since the last newline.
+    line: Vec<u8>,
+    printed: Vec<(u32, String)>,
+}
+
+impl TestState {
+    fn print(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.end_line();
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+        self.printed.push((self.tick, line));
+    }
+}
+
+fn as_f64(value: &Val) -> f64 {
+    match value {
+        Val::I32(value) => *value as f64,
+        Val::I64(value) => *value as f64,
+        Val::F32(bits) => f32::from_bits(*bits) as f64,
+        Val::F64(bits) => f64::from_bits(*bits),
+        _ => f64::NAN,
+    }
+}
+
+/// WASI's `fd_write`, so what the fleet prints, like a panic message, ends up in the test's
+/// output rather than failing.
+fn fd_write(caller: &mut Caller<TestState>, params: &[Val]) -> wasmtime::Result<i32> {
+    let [fd, iovs, iovs_len, written] = [0, 1, 2, 3].map(|i| {
+        params
+            .get(i)
+            .and_then(Val::i32)
+            .map_or(0, |value| value as u32 as usize)
+    });
+    let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+        return Ok(0);
+    };
+
+    let mut bytes = Vec::new();
+    for i in 0..iovs_len {
+        let mut iov = [0; 8];
+        memory.read(&*caller, iovs + i * 8, &mut iov)?;
+        let ptr = u32::from_le_bytes(iov[..4].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(iov[4..].try_into().unwrap()) as usize;
+        let start = bytes.len();
+        bytes.resize(start + len, 0);
+        memory.read(&*caller, ptr, &mut bytes[start..])?;
+    }
+    memory.write(&mut *caller, written, &(bytes.len() as u32).to_le_bytes())?;
+    if fd == 1 || fd == 2 {
+        caller.data_mut().print(&bytes);
+    }
+    Ok(0)
+}
+
+/// The engine tests run in, metering fuel as the sim does.
+pub fn test_engine() -> anyhow::Result<Engine> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config)
+        .map_err(anyhow::Error::from)
+        .context("trying to set up wasmtime for the test")
+}
+
+/// Runs `ticks` of the fleet, with the sim's functions returning `inputs` and the calls it makes
+/// recorded. Fails if it imports something the sim doesn't have.
+pub fn run_test(
+    engine: &Engine,
+    bytes: &[u8],
+    ticks: u32,
+    fuel_limit: u64,
+    inputs: Arc<Inputs>,
+) -> anyhow::Result<TestRun> {
+    let module = Module::new(engine, bytes).map_err(anyhow::Error::from)?;
+
+    let mut linker = Linker::new(engine);
+    let mut recorded = Vec::new();
+    for import in module.imports() {
+        let (module_name, name) = (import.module().to_owned(), import.name().to_owned());
+        if !ALLOWED_IMPORT_MODULES.contains(&module_name.as_str()) {
+            anyhow::bail!("it imports `{module_name}::{name}`, which the sim doesn't provide");
+        }
+        let ExternType::Func(ty) = import.ty() else {
+            anyhow::bail!("it imports `{module_name}::{name}`, which isn't a function");
+        };
+        let result_types: Vec<_> = ty.results().collect();
+        let is_action = module_name == "protologic" && result_types.is_empty();
+        if is_action {
+            recorded.push(name.clone());
+        }
+        let inputs = Arc::clone(&inputs);
+        linker.func_new(
+            import.module(),
+            import.name(),
+            ty,
+            move |mut caller: Caller<TestState>, params, results| {
+                match name.as_str() {
+                    "sched_yield" => {
+                        let state = caller.data_mut();
+                        state.tick += 1;
+                        if state.tick >= state.target {
+                            return Err(wasmtime::Error::new(Stop::Done));
+                        }
+                        let fuel_limit = state.fuel_limit;
+                        caller.set_fuel(fuel_limit)?;
+                    }
+                    "proc_exit" => {
+                        let code = params.first().and_then(Val::i32).unwrap_or(0);
+                        return Err(wasmtime::Error::new(Stop::Exited(code)));
+                    }
+                    "fd_write" if module_name == "wasi_snapshot_preview1" => {
+                        let errno = fd_write(&mut caller, params)?;
+                        if let Some(result) = results.first_mut() {
+                            *result = Val::I32(errno);
+                        }
+                        return Ok(());
+                    }
+                    _ if is_action => {
+                        let state = caller.data_mut();
+                        let call = Call {
+                            tick: state.tick,
+                            call: name.clone(),
+                            args: params.iter().map(as_f64).collect(),
+                        };
+                        state.calls.push(call);
+                    }
+                    _ => {}
+                }
+                let tick = caller.data().tick;
+                for (result, ty) in results.iter_mut().zip(&result_types) {
+                    *result = inputs.value(&module_name, &name, tick, ty);
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    let state = TestState {
+        target: ticks,
+        fuel_limit,
+        tick: 0,
+        calls: Vec::new(),
+        line: Vec::new(),
+        printed: Vec::new(),
+    };
+    let mut store = Store::new(engine, state);
+    store.set_fuel(fuel_limit)?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let Some(entry) = ENTRY_POINTS
+        .iter()
+        .find_map(|name| instance.get_typed_func::<(), ()>(&mut store, name).ok())
+    else {
+        anyhow::bail!(
+            "it doesn't export an entry point, one of `{}`",
+            ENTRY_POINTS.join("`, `")
+        );
+    };
+    let result = match instance.get_typed_func::<(), ()>(&mut store, "_initialize") {
+        Ok(initialize) => initialize.call(&mut store, ()),
+        Err(_) => Ok(()),
+    }
+    .and_then(|()| {
+        store.set_fuel(fuel_limit)?;
+        entry.call(&mut store, ())
+    });
+
+    let state = store.data_mut();
+    if !state.line.is_empty() {
+        state.end_line();
+    }
+    let tick = state.tick;
+    let (stopped, failed) = match result {
+        Ok(()) => (Some(format!("returned on tick {tick}")), false),
+        Err(err) => match (err.downcast_ref::<Stop>(), err.downcast_ref::<Trap>()) {
+            (Some(Stop::Done), _) => (None, false),
+            (Some(Stop::Exited(0)), _) => (Some(format!("exited on tick {tick}")), false),
+            (Some(Stop::Exited(code)), _) => (
+                Some(format!("exited with code {code} on tick {tick}")),
+                true,
+            ),
+            (None, Some(Trap::OutOfFuel)) => (
+                Some(format!(
+                    "ran out of fuel on tick {tick}, is it stuck in a loop?"
+                )),
+                true,
+            ),
+            // The error has the wasm backtrace, which says where it was
+            (None, _) => (Some(format!("trapped on tick {tick}: {err:#}")), true),
+        },
+    };
+
+    Ok(TestRun {
+        ticks: tick,
+        calls: std::mem::take(&mut state.calls),
+        recorded,
+        printed: std::mem::take(&mut state.printed),
+        stopped,
+        failed,
+    })
+}
+
+fn write_trace(path: &Path, run: &TestRun) -> anyhow::Result<()> {
+    let mut lines: Vec<(u32, TraceLine)> = run
+        .calls
+        .iter()
+        .map(|call| (call.tick, TraceLine::Call(call)))
+        .collect();
+    lines.extend(
+        run.printed
+            .iter()
+            .map(|(tick, print)| (*tick, TraceLine::Print { tick: *tick, print })),
+    );
+    // Stable, so calls come before what was printed on the same tick
+    lines.sort_by_key(|(tick, _)| *tick);
+
+    let mut trace = String::new();
+    for (_, line) in lines {
+        trace.push_str(&serde_json::to_string(&line)?);
+        trace.push('\n');
+    }
+    std::fs::File::create(path)
+        .and_then(|mut file| file.write_all(trace.as_bytes()))
+        .with_context(|| format!("trying to write the trace {path:?}"))
+}
+
+/// Runs a fleet's ticks against a scenario, and checks the calls it made. Fails if any
+/// expectation doesn't hold, or the fleet traps, runs out of fuel or exits with an error.
+pub fn fleet_test(args: TestArgs) -> anyhow::Result<()> {
+    let fleet = find_fleet_or_file(&args.fleet, args.debug)?;
+    let scenario = match &args.scenario {
+        Some(path) => Scenario::read(path)?,
+        None => Scenario::default(),
+    };
+    let ticks = args.ticks.or(scenario.ticks).unwrap_or(DEFAULT_TICKS);
+    let fuel_limit = args
+        .fuel_limit
+        .or(scenario.fuel_limit)
+        .unwrap_or(DEFAULT_FUEL_LIMIT);
+
+    say!("Running {ticks} ticks of '{}'", fleet.name);
+    let bytes = std::fs::read(&fleet.path)
+        .with_context(|| format!("trying to read fleet {:?}", fleet.path))?;
+    let run = run_test(
+        &test_engine()?,
+        &bytes,
+        ticks,
+        fuel_limit,
+        Arc::new(scenario.inputs),
+    )
+    .with_context(|| format!("trying to test '{}'", fleet.name))?;
+    if let Some(path) = &args.trace {
+        write_trace(path, &run)?;
+        say!("Wrote the trace to {path:?}");
+    }
+
+    say!(
+        "'{}' ran {} ticks and made {} calls",
+        fleet.name,
+        run.ticks,
+        run.calls.len()
+    );
+    let mut failures = Vec::new();
+    if let Some(stopped) = &run.stopped {
+        say!("It {stopped}");
+        if run.failed {
+            failures.push(format!("'{}' {stopped}", fleet.name));
+            // What it printed last, like a panic message, is likely why
+            for (tick, line) in run.printed.iter().rev().take(CALLS_SHOWN).rev() {
+                say!("    tick {tick}: {line}");
+            }
+        }
+    }
+    for expectation in &scenario.expectations {
+        let description = expectation.describe();
+        // Otherwise a typo would pass as a call that's never made
+        let result = if run.recorded.contains(&expectation.call) {
+            expectation.check(&run.calls)
+        } else {
+            Err(format!(
+                "'{}' doesn't import `{}`, or it returns something, so its calls aren't recorded",
+                fleet.name, expectation.call
+            ))
+        };
+        match result {
+            Ok(()) => say!("  ok      {description}"),
+            Err(failure) => {
+                say!("  FAILED  {description}: {failure}");
+                failures.push(format!("expected {description}, but {failure}"));
+            }
+        }
+    }
+
+    match failures.as_slice() {
+        [] => Ok(()),
+        [failure] => Err(ExitError::new(ExitError::ASSERTION_FAILED, failure.clone()).into()),
+        failures => Err(ExitError::new(
+            ExitError::ASSERTION_FAILED,
+            format!(
+                "{} checks failed:\n    {}",
+                failures.len(),
+                failures.join("\n    ")
+            ),
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{run_test, test_engine, Call, Expectation, Scenario, TestRun};
+
+    fn run(wat: &str, ticks: u32, scenario: &str) -> anyhow::Result<TestRun> {
+        let scenario: Scenario = toml::from_str(scenario)?;
+        run_test(
+            &test_engine()?,
+            &wat::parse_str(wat)?,
+            ticks,
+            1_000_000,
+            Arc::new(scenario.inputs),
+        )
+    }
+
+    // Thrusts as hard as the radar says there are targets, and fires on every tick it sees one
+    const FLEET: &str = r#"(module
+        (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+        (import "protologic" "radar_get_target_count" (func $targets (result i32)))
+        (import "protologic" "engine_set_throttle" (func $throttle (param f32)))
+        (import "protologic" "gun_fire" (func $fire (param i32)))
+        (memory (export "memory") 1)
+        (func (export "_start") (local $n i32)
+            (loop $tick
+                (local.set $n (call $targets))
+                (call $throttle (f32.mul (f32.convert_i32_s (local.get $n)) (f32.const 0.5)))
+                (if (local.get $n) (then (call $fire (i32.const 0))))
+                (drop (call $yield))
+                (br $tick))))"#;
+
+    #[test]
+    fn calls_follow_the_scripted_inputs() -> anyhow::Result<()> {
+        let run = run(FLEET, 4, "[inputs]\nradar_get_target_count = [0, 0, 2]")?;
+        assert_eq!(run.ticks, 4);
+        assert!(!run.failed);
+        assert_eq!(run.stopped, None);
+        assert_eq!(run.calls.len(), 6);
+        assert_eq!(
+            run.calls[2],
+            Call {
+                tick: 2,
+                call: "engine_set_throttle".to_owned(),
+                args: vec![1.0]
+            }
+        );
+        assert_eq!(run.calls[3].call, "gun_fire");
+        Ok(())
+    }
+
+    #[test]
+    fn expectations_count_matching_calls() -> anyhow::Result<()> {
+        let run = run(FLEET, 4, "[inputs]\nradar_get_target_count = [0, 0, 2]")?;
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[expect]]
+            call = "gun_fire"
+            by-tick = 2
+
+            [[expect]]
+            call = "gun_fire"
+            by-tick = 1
+
+            [[expect]]
+            call = "engine_set_throttle"
+            args = [1.0]
+            tick = 0
+
+            [[expect]]
+            call = "engine_set_throttle"
+            max-count = 0
+            "#,
+        )?;
+        let results: Vec<_> = scenario
+            .expectations
+            .iter()
+            .map(|expectation| expectation.check(&run.calls))
+            .collect();
+        assert_eq!(results[0], Ok(()));
+        assert_eq!(
+            results[1],
+            Err("it was called 0 times like that, but there's gun_fire(0) on tick 2, gun_fire(0) on tick 3".to_owned())
+        );
+        assert!(results[2].is_err());
+        assert_eq!(results[3], Err("it was called 4 times".to_owned()));
+        assert_eq!(
+            scenario.expectations[2].describe(),
+            "engine_set_throttle(1) on tick 0, at least once"
+        );
+        assert_eq!(
+            scenario.expectations[3].describe(),
+            "engine_set_throttle, never"
+        );
+        assert_eq!(
+            Expectation {
+                call: "gun_fire".to_owned(),
+                min_count: Some(2),
+                max_count: Some(2),
+                ..Expectation::default()
+            }
+            .describe(),
+            "gun_fire, exactly 2 times"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn traps_fuel_and_unknown_imports_fail() -> anyhow::Result<()> {
+        let trap = run(
+            r#"(module
+                (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write" (func $write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "oh no\n")
+                (func (export "_start")
+                    (i32.store (i32.const 0) (i32.const 16))
+                    (i32.store (i32.const 4) (i32.const 6))
+                    (drop (call $yield))
+                    (drop (call $write (i32.const 2) (i32.const 0) (i32.const 1) (i32.const 8)))
+                    unreachable))"#,
+            10,
+            "",
+        )?;
+        assert!(trap.failed);
+        assert!(
+            trap.stopped
+                .as_deref()
+                .unwrap()
+                .starts_with("trapped on tick 1"),
+            "{:?}",
+            trap.stopped
+        );
+        assert_eq!(trap.printed, [(1, "oh no".to_owned())]);
+
+        let stuck = run(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "_start") (loop $spin (br $spin))))"#,
+            10,
+            "",
+        )?;
+        assert!(stuck.failed);
+        assert!(stuck.stopped.unwrap().contains("ran out of fuel on tick 0"));
+
+        let err = run(
+            r#"(module
+                (import "env" "abort" (func))
+                (memory (export "memory") 1)
+                (func (export "_start")))"#,
+            10,
+            "",
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("imports `env::abort`, which the sim doesn't provide"));
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::output::say;
+
+/// Where the workspace's git checkout was at, so battle results can be tied back to a commit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitState {
+    pub commit: String,
+    /// `None` with a detached `HEAD`.
+    pub branch: Option<String>,
+    /// Whether the fleet sources had uncommitted changes, in which case the commit alone can't
+    /// reproduce the battle.
+    pub dirty: bool,
+}
+
+/// The git state of the workspace we're run in, with its members' sources deciding whether it's
+/// dirty. `None` outside a workspace or a git repository, it's only extra detail for the results.
+pub fn workspace_git_state() -> Option<GitState> {
+    let metadata = crate::cargo_metadata().ok()?;
+    let sources: Vec<PathBuf> = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .filter_map(|package| Some(package.manifest_path.parent()?.to_owned()))
+        .collect();
+    git_state(&metadata.workspace_root, &sources)
+}
+
+/// The git state of `workspace`, or `None` if it isn't in a git repository or git isn't
+/// installed. Only changes under `sources` count towards it being dirty.
+pub fn git_state(workspace: &Path, sources: &[PathBuf]) -> Option<GitState> {
+    let commit = git(workspace, &["rev-parse", "HEAD"])?;
+    let branch = git(workspace, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD");
+
+    // Anything ignored, like `target`, doesn't show up here
+    let mut status = vec!["status", "--porcelain", "--"];
+    status.extend(sources.iter().filter_map(|path| path.to_str()));
+    let dirty = !git(workspace, &status)?.is_empty();
+
+    Some(GitState {
+        commit,
+        branch,
+        dirty,
+    })
+}
+
+/// The full hash of the commit `rev` names in the repository at `dir`, like a branch, tag or
+/// `HEAD~2`. `None` if it doesn't name a commit there.
+pub fn resolve_commit(dir: &Path, rev: &str) -> Option<String> {
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+}
+
+/// Where `dir` is in its repository, like `fleets/` for a workspace in a subdirectory of it, or
+/// empty at the top.
+pub fn repo_prefix(dir: &Path) -> Option<PathBuf> {
+    git(dir, &["rev-parse", "--show-prefix"]).map(PathBuf::from)
+}
+
+/// A checkout of a commit in a temporary worktree of the repository, which is removed again when
+/// this is dropped.
+pub struct Worktree {
+    repo: PathBuf,
+    pub dir: PathBuf,
+}
+
+impl Worktree {
+    /// Checks out `commit` of the repository at `repo` into `dir`, which mustn't exist yet.
+    pub fn add(repo: &Path, dir: &Path, commit: &str) -> anyhow::Result<Self> {
+        // A checkout left behind by a run that was killed would still be registered
+        git(repo, &["worktree", "prune"]);
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["worktree", "add", "--detach", "--quiet"])
+            .arg(dir)
+            .arg(commit)
+            .stdin(Stdio::null())
+            .output()
+            .context("trying to run git, is it installed?")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git couldn't check out {commit} into {dir:?}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(Self {
+            repo: repo.to_owned(),
+            dir: dir.to_owned(),
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let removed = Command::new("git")
+            .arg("-C")
+            .arg(&self.repo)
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.dir)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !removed {
+            say!(
+                "Warning: couldn't remove the checkout in {}, `git worktree prune` will tidy it up once it's deleted",
+                self.dir.display()
+            );
+        }
+    }
+}
+
+/// Runs git in `dir`, returning its trimmed output if it succeeded.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{git, git_state, resolve_commit, Worktree};
+
+    #[test]
+    fn git_state_tracks_commit_branch_and_dirtiness() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = dir.path();
+        assert_eq!(git_state(repo, &[]), None);
+
+        let run = |args: &[&str]| {
+            let mut all = vec!["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+            all.extend(args);
+            git(repo, &all).expect("git to work");
+        };
+        run(&["init", "--initial-branch", "main"]);
+        std::fs::create_dir_all(repo.join("fleet/src"))?;
+        std::fs::write(repo.join("fleet/src/lib.rs"), "// aim better")?;
+        run(&["add", "."]);
+        run(&["commit", "-m", "Add fleet"]);
+
+        let sources = [repo.join("fleet")];
+        let state = git_state(repo, &sources).expect("a git repository");
+        assert_eq!(state.commit.len(), 40);
+        assert_eq!(state.branch.as_deref(), Some("main"));
+        assert!(!state.dirty);
+
+        // Battle outputs next to the workspace aren't fleet source
+        std::fs::write(repo.join("battle.json.deflate"), "")?;
+        assert!(!git_state(repo, &sources).unwrap().dirty);
+
+        std::fs::write(repo.join(Path::new("fleet/src/lib.rs")), "// aim worse")?;
+        assert!(git_state(repo, &sources).unwrap().dirty);
+        Ok(())
+    }
+
+    #[test]
+    fn worktree_checks_out_a_commit_and_cleans_up() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo)?;
+        let run = |args: &[&str]| {
+            let mut all = vec!["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+            all.extend(args);
+            git(&repo, &all).expect("git to work");
+        };
+        run(&["init", "--initial-branch", "main"]);
+        std::fs::write(repo.join("lib.rs"), "// aim better")?;
+        run(&["add", "."]);
+        run(&["commit", "-m", "Add fleet"]);
+        std::fs::write(repo.join("lib.rs"), "// aim worse")?;
+        run(&["commit", "-am", "Break fleet"]);
+
+        let commit = resolve_commit(&repo, "main~1").expect("a commit");
+        assert_eq!(resolve_commit(&repo, "no-such-branch"), None);
+        let checkout = dir.path().join("checkout");
+        {
+            let worktree = Worktree::add(&repo, &checkout, &commit)?;
+            assert_eq!(
+                std::fs::read_to_string(worktree.dir.join("lib.rs"))?,
+                "// aim better"
+            );
+        }
+        assert!(!checkout.exists());
+        Ok(())
+    }
+}
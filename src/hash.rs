@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use crate::fleet::{find_built_fleets, find_fleet};
+
+/// The SHA-256 digest of a file, as lowercase hex.
+pub fn file_sha256(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path).with_context(|| format!("trying to open {path:?} to hash"))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).with_context(|| format!("trying to hash {path:?}"))?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// The SHA-256 digest of some bytes, as lowercase hex.
+pub fn sha256(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+/// The first few characters of a digest, enough to tell builds apart at a glance.
+pub fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(8)]
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The digests `hash` can print.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// What `sha256sum` prints, and what tournaments ask for.
+    #[default]
+    Sha256,
+    /// What `b3sum` prints.
+    Blake3,
+}
+
+impl Algorithm {
+    /// The digest of a file, as lowercase hex.
+    pub fn file_digest(self, path: &Path) -> anyhow::Result<String> {
+        match self {
+            Self::Sha256 => file_sha256(path),
+            Self::Blake3 => {
+                let mut file =
+                    File::open(path).with_context(|| format!("trying to open {path:?} to hash"))?;
+                let mut hasher = blake3::Hasher::new();
+                std::io::copy(&mut file, &mut hasher)
+                    .with_context(|| format!("trying to hash {path:?}"))?;
+                Ok(hasher.finalize().to_hex().to_string())
+            }
+        }
+    }
+}
+
+/// Arguments for `cargo protologic hash`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct HashArgs {
+    /// The fleets to hash, by name or as paths to files. Defaults to every built fleet.
+    fleets: Vec<String>,
+    /// Which digest to print.
+    #[arg(long, value_enum, default_value_t)]
+    algo: Algorithm,
+    /// Hash the debug builds, instead of the release builds.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+    /// Check the files listed in FILE against their digests, as `sha256sum -c` does. `-` reads them from stdin.
+    #[arg(short, long, value_name = "FILE", conflicts_with = "fleets")]
+    check: Option<PathBuf>,
+}
+
+/// A line as `sha256sum` prints it: the digest, two spaces and the path. Paths with a backslash
+/// or a newline in them are escaped, with a backslash in front of the line to say so.
+pub fn digest_line(digest: &str, path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.contains(['\\', '\n']) {
+        let path = path.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{digest}  {path}")
+    } else {
+        format!("{digest}  {path}")
+    }
+}
+
+/// The digest and path of a line as `sha256sum` prints it, binary mode's `*` and escaping
+/// included, or `None` if it isn't one.
+pub fn parse_digest_line(line: &str) -> Option<(&str, PathBuf)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (digest, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*'])?;
+    if digest.is_empty() || !digest.chars().all(|c| c.is_ascii_hexdigit()) || path.is_empty() {
+        return None;
+    }
+    let path = if escaped {
+        let mut unescaped = String::new();
+        let mut chars = path.chars();
+        while let Some(c) = chars.next() {
+            let next = if c == '\\' { chars.next() } else { None };
+            unescaped.push(match next {
+                Some('n') => '\n',
+                Some(escaped) => escaped,
+                None => c,
+            });
+        }
+        unescaped
+    } else {
+        path.to_owned()
+    };
+    Some((digest, PathBuf::from(path)))
+}
+
+/// Prints the digests of the fleets, or every built one, in `sha256sum`'s format, or with
+/// `--check` checks the files in a list of them.
+pub fn hash(args: HashArgs) -> anyhow::Result<()> {
+    if let Some(list) = &args.check {
+        return check(list, args.algo);
+    }
+
+    let paths = if args.fleets.is_empty() {
+        let fleets = find_built_fleets(args.debug)?;
+        if fleets.is_empty() {
+            anyhow::bail!(
+                "there are no built fleets to hash, build them with `cargo protologic build`"
+            );
+        }
+        fleets.into_iter().map(|fleet| fleet.path).collect()
+    } else {
+        let built = find_built_fleets(args.debug)?;
+        args.fleets
+            .iter()
+            .map(|name| {
+                let path = Path::new(name);
+                if path.is_file() {
+                    return Ok(path.to_owned());
+                }
+                find_fleet(&built, name).map(|fleet| fleet.path)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+
+    for path in paths {
+        println!("{}", digest_line(&args.algo.file_digest(&path)?, &path));
+    }
+    Ok(())
+}
+
+/// Checks each file in `list` against its digest, printing `OK` or `FAILED` for it as
+/// `sha256sum -c` does, and failing if any didn't match.
+fn check(list: &Path, algo: Algorithm) -> anyhow::Result<()> {
+    let mut contents = String::new();
+    if list == Path::new("-") {
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("trying to read the digests to check from stdin")?;
+    } else {
+        contents = std::fs::read_to_string(list)
+            .with_context(|| format!("trying to read the digests to check from {list:?}"))?;
+    }
+
+    let (mut checked, mut malformed, mut mismatched, mut unreadable) = (0, 0, 0, 0);
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((expected, path)) = parse_digest_line(line) else {
+            malformed += 1;
+            continue;
+        };
+        checked += 1;
+        match algo.file_digest(&path) {
+            Ok(digest) if digest.eq_ignore_ascii_case(expected) => {
+                println!("{}: OK", path.display());
+            }
+            Ok(_) => {
+                mismatched += 1;
+                println!("{}: FAILED", path.display());
+            }
+            Err(_) => {
+                unreadable += 1;
+                println!("{}: FAILED open or read", path.display());
+            }
+        }
+    }
+
+    if checked == 0 {
+        anyhow::bail!("no digests to check in {list:?}, it should have lines like `cargo protologic hash` prints");
+    }
+    let mut problems = Vec::new();
+    if mismatched > 0 {
+        problems.push(format!(
+            "{mismatched} of the files didn't match their digest"
+        ));
+    }
+    if unreadable > 0 {
+        problems.push(format!("{unreadable} of the files couldn't be read"));
+    }
+    if malformed > 0 {
+        problems.push(format!("{malformed} of the lines weren't digests"));
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{digest_line, file_sha256, parse_digest_line, Algorithm};
+
+    #[test]
+    fn file_sha256_matches_known_digest() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("fleet.wasm");
+        std::fs::write(&path, "abc")?;
+
+        assert_eq!(
+            file_sha256(&path)?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn digest_lines_read_back_as_they_were_written() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("fleet.wasm");
+        std::fs::write(&path, "abc")?;
+        assert_eq!(
+            Algorithm::Blake3.file_digest(&path)?,
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+
+        let line = digest_line("ab12", Path::new("target/glass_cannon.wasm"));
+        assert_eq!(line, "ab12  target/glass_cannon.wasm");
+        assert_eq!(
+            parse_digest_line(&line),
+            Some(("ab12", PathBuf::from("target/glass_cannon.wasm")))
+        );
+        assert_eq!(
+            parse_digest_line("ab12 *fleet.wasm"),
+            Some(("ab12", PathBuf::from("fleet.wasm")))
+        );
+
+        let odd = Path::new("odd\\name\n.wasm");
+        let line = digest_line("ab12", odd);
+        assert_eq!(line, "\\ab12  odd\\\\name\\n.wasm");
+        assert_eq!(parse_digest_line(&line), Some(("ab12", odd.to_owned())));
+
+        assert_eq!(parse_digest_line("not a digest"), None);
+        assert_eq!(parse_digest_line("ab12 fleet.wasm"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+//! The battle history: every battle's result appended to one log, and `history` for querying it.
+
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone};
+use serde::Serialize;
+
+use crate::outcome::Outcome;
+use crate::output::say;
+use crate::prune::parse_age;
+use crate::results::BattleRecord;
+use crate::run::unix_now;
+use crate::series::Score;
+use crate::settings::settings;
+use crate::{fleet_output_base_path, FLEET_OUTPUT_DIR};
+
+/// The file name of the battle history log.
+pub const HISTORY_FILE: &str = "battle_history.jsonl";
+
+/// The battle history log lives with the fleets, unless configured otherwise.
+pub fn default_history_path() -> anyhow::Result<PathBuf> {
+    Ok(fleet_output_base_path()?.join(HISTORY_FILE))
+}
+
+/// Where the battle history is when reading or deleting it: `flag` when given, else the
+/// `run.history-file` setting `run` appends to, else [`HISTORY_FILE`] in the fleet output directory.
+pub fn configured_history_path(flag: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    Ok(settings()?
+        .path("run.history-file", flag)
+        .unwrap_or_else(|| Path::new(FLEET_OUTPUT_DIR).join(HISTORY_FILE)))
+}
+
+/// Appends a battle to the history log, one JSON record per line.
+///
+/// The file is locked while writing, and each record goes out in a single write, so concurrent runs
+/// can share a log without interleaving their lines.
+pub fn append_history(path: &Path, record: &BattleRecord) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(record).context("trying to serialize battle history")?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("trying to open battle history {path:?}"))?;
+
+    file.lock()
+        .with_context(|| format!("trying to lock battle history {path:?}"))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("trying to append to battle history {path:?}"))
+}
+
+/// Reads every battle in the history log, oldest first. Lines that don't parse, like half a line
+/// from a run that was killed mid-write, are skipped with a warning.
+pub fn read_history(path: &Path) -> anyhow::Result<Vec<BattleRecord>> {
+    let history =
+        std::fs::read(path).with_context(|| format!("trying to read battle history {path:?}"))?;
+    // Half a line can end part way through a character, which mustn't cost the rest of the log
+    let history = String::from_utf8_lossy(&history);
+
+    let mut skipped = 0;
+    let records = history
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let record = serde_json::from_str(line).ok();
+            skipped += usize::from(record.is_none());
+            record
+        })
+        .collect();
+    if skipped > 0 {
+        say!("Warning: skipped {skipped} lines of {path:?} that aren't battle records");
+    }
+    Ok(records)
+}
+
+/// Arguments for `cargo protologic history`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct HistoryArgs {
+    /// Only battles this fleet was in, with their outcomes from its side.
+    #[arg(long, value_name = "FLEET")]
+    fleet: Option<String>,
+    /// Only battles against this fleet. With `--fleet`, only the battles between the two.
+    #[arg(long, value_name = "FLEET")]
+    vs: Option<String>,
+    /// Only battles from this date on, like `2024-06-01`, or from this long ago, like `7d` or `12h`.
+    #[arg(long, value_name = "DATE_OR_AGE", value_parser = parse_since)]
+    since: Option<Since>,
+    /// How many of the newest matching battles to list. The summary counts every one.
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    limit: usize,
+    /// The battle history to read. Defaults to the `run.history-file` setting, or `battle_history.jsonl` in the fleet output directory.
+    #[arg(long, value_name = "PATH")]
+    history_file: Option<PathBuf>,
+    /// Print the matching battles and the summary as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// Where `--since` starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Since {
+    /// Midnight at the start of the day, in local time.
+    Date(NaiveDate),
+    Age(Duration),
+}
+
+impl Since {
+    /// The Unix timestamp battles have to be from to count.
+    fn cutoff(self, now: u64) -> u64 {
+        match self {
+            Self::Date(date) => Local
+                .from_local_datetime(&date.and_time(NaiveTime::MIN))
+                .earliest()
+                .map_or(0, |start| start.timestamp().max(0) as u64),
+            Self::Age(age) => now.saturating_sub(age.as_secs()),
+        }
+    }
+}
+
+fn parse_since(value: &str) -> Result<Since, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Since::Date(date));
+    }
+    parse_age(value)
+        .map(Since::Age)
+        .map_err(|_| "it has to be a date like `2024-06-01`, or an age like `7d`".to_owned())
+}
+
+/// Whether `name` picks the fleet recorded as `recorded`: the same name, with dashes and
+/// underscores alike, since the package and its wasm are named both ways, and ignoring case.
+fn same_fleet(recorded: &str, name: &str) -> bool {
+    let normalize = |name: &str| name.replace('-', "_").to_lowercase();
+    normalize(recorded) == normalize(name)
+}
+
+/// Which battles `history` shows.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub fleet: Option<String>,
+    pub vs: Option<String>,
+    /// The earliest Unix timestamp to include.
+    pub since: Option<u64>,
+}
+
+impl HistoryFilter {
+    /// Whether the battle matches, and if there's a `--fleet`, whether it was fleet A in it.
+    fn side(&self, record: &BattleRecord) -> Option<bool> {
+        if self.since.is_some_and(|since| record.timestamp < since) {
+            return None;
+        }
+        let is = |fleet: &str, name: &Option<String>| {
+            name.as_deref().is_none_or(|name| same_fleet(fleet, name))
+        };
+        let (a, b) = (record.fleet_a.as_str(), record.fleet_b.as_str());
+        if is(a, &self.fleet) && is(b, &self.vs) {
+            Some(true)
+        } else if is(b, &self.fleet) && is(a, &self.vs) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the `--fleet` did over the matching battles.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FleetRecord {
+    pub fleet: String,
+    pub won: u32,
+    pub lost: u32,
+    pub drawn: u32,
+    /// Battles the sim crashed in, or whose outcome couldn't be told.
+    pub unknown: u32,
+}
+
+impl FleetRecord {
+    fn win_rate(&self) -> Option<f64> {
+        Score {
+            first: self.won,
+            second: self.lost,
+            draws: self.drawn,
+            unknown: self.unknown,
+        }
+        .win_rate(true)
+    }
+}
+
+/// A battle that matched, as `history --json` prints it.
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub record: BattleRecord,
+    /// With `--fleet`, whether it was fleet A.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fleet_is_a: Option<bool>,
+}
+
+/// What `history --json` prints.
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryQuery {
+    /// How many battles matched, of which the newest `--limit` are listed.
+    pub total: usize,
+    pub record: Option<FleetRecord>,
+    pub battles: Vec<HistoryEntry>,
+}
+
+/// The matching battles, newest first, and with a `--fleet` how it did in all of them.
+pub fn query_history(
+    records: Vec<BattleRecord>,
+    filter: &HistoryFilter,
+    limit: usize,
+) -> HistoryQuery {
+    let mut battles: Vec<HistoryEntry> = records
+        .into_iter()
+        .filter_map(|record| {
+            let side = filter.side(&record)?;
+            Some(HistoryEntry {
+                fleet_is_a: filter.fleet.is_some().then_some(side),
+                record,
+            })
+        })
+        .collect();
+    // The log is in the order battles finished, which isn't quite the order they started in
+    battles.sort_by_key(|entry| std::cmp::Reverse(entry.record.timestamp));
+
+    let record = filter.fleet.as_ref().map(|fleet| {
+        let mut record = FleetRecord {
+            fleet: fleet.clone(),
+            won: 0,
+            lost: 0,
+            drawn: 0,
+            unknown: 0,
+        };
+        for entry in &battles {
+            match (entry.record.outcome, entry.fleet_is_a) {
+                (Outcome::FleetAWon, Some(true)) | (Outcome::FleetBWon, Some(false)) => {
+                    record.won += 1
+                }
+                (Outcome::FleetAWon | Outcome::FleetBWon, _) => record.lost += 1,
+                (Outcome::Draw, _) => record.drawn += 1,
+                (Outcome::SimCrashed | Outcome::Unknown, _) => record.unknown += 1,
+            }
+        }
+        record
+    });
+
+    let total = battles.len();
+    battles.truncate(limit);
+    HistoryQuery {
+        total,
+        record,
+        battles,
+    }
+}
+
+/// A battle's outcome from the `--fleet`'s side, like `won`.
+fn describe_result(entry: &HistoryEntry) -> String {
+    let record = &entry.record;
+    let Some(fleet_is_a) = entry.fleet_is_a else {
+        return record.describe_outcome();
+    };
+    match (record.outcome, fleet_is_a) {
+        (Outcome::FleetAWon, true) | (Outcome::FleetBWon, false) => "won".to_owned(),
+        (Outcome::FleetAWon | Outcome::FleetBWon, _) => "lost".to_owned(),
+        _ => record.describe_outcome(),
+    }
+}
+
+/// The matching battles as a table to print. With a `--fleet`, each row has its opponent and how
+/// it did, and otherwise both fleets and who won.
+pub fn history_table(battles: &[HistoryEntry]) -> String {
+    let with_fleet = battles.iter().any(|entry| entry.fleet_is_a.is_some());
+    let rows: Vec<[String; 5]> = battles
+        .iter()
+        .map(|entry| {
+            let record = &entry.record;
+            let date = i64::try_from(record.timestamp)
+                .ok()
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                .map_or("-".to_owned(), |datetime| {
+                    datetime
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                });
+            let fleets = match entry.fleet_is_a {
+                Some(true) => record.fleet_b.clone(),
+                Some(false) => record.fleet_a.clone(),
+                None => format!("{} vs {}", record.fleet_a, record.fleet_b),
+            };
+            [
+                date,
+                fleets,
+                describe_result(entry),
+                record.seed.map_or("-".to_owned(), |seed| seed.to_string()),
+                record.replay.display().to_string(),
+            ]
+        })
+        .collect();
+
+    let headers = [
+        "Date",
+        if with_fleet { "Opponent" } else { "Fleets" },
+        if with_fleet { "Result" } else { "Outcome" },
+        "Seed",
+        "Replay",
+    ];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut table = String::new();
+    for row in [headers.map(str::to_owned)].iter().chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    table
+}
+
+/// The `--fleet`'s record over the matching battles, like `'iron_armada': 12 won, 5 lost, 1 drawn
+/// over 18 battles, a 69.4% win-rate`.
+fn describe_record(record: &FleetRecord, vs: Option<&str>) -> String {
+    let battles = record.won + record.lost + record.drawn + record.unknown;
+    let vs = vs.map(|vs| format!(" against '{vs}'")).unwrap_or_default();
+    let mut summary = format!(
+        "'{}'{vs}: {} won, {} lost, {} drawn over {battles} battles",
+        record.fleet, record.won, record.lost, record.drawn
+    );
+    if record.unknown > 0 {
+        write!(summary, " ({} with an unknown outcome)", record.unknown).unwrap();
+    }
+    if let Some(rate) = record.win_rate() {
+        write!(summary, ", a {:.1}% win-rate", rate * 100.0).unwrap();
+    }
+    summary
+}
+
+/// Lists the battles in the battle history that match the filters, newest first, with a summary.
+pub fn history(args: HistoryArgs) -> anyhow::Result<()> {
+    let path = configured_history_path(args.history_file)?;
+    let records = if path.is_file() {
+        read_history(&path)?
+    } else if args.json {
+        Vec::new()
+    } else {
+        say!(
+            "No battle history at {} yet, `run` adds every battle it plays to it",
+            path.display()
+        );
+        return Ok(());
+    };
+    let filter = HistoryFilter {
+        fleet: args.fleet,
+        vs: args.vs,
+        since: args
+            .since
+            .map(|since| unix_now().map(|now| since.cutoff(now)))
+            .transpose()?,
+    };
+    let query = query_history(records, &filter, args.limit);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&query)?);
+        return Ok(());
+    }
+    if query.battles.is_empty() {
+        say!("No battles in {} match", path.display());
+        return Ok(());
+    }
+    say!("{}", history_table(&query.battles).trim_end());
+    if query.total > query.battles.len() {
+        say!(
+            "Showing the newest {} of {} battles, pass `--limit` to see more",
+            query.battles.len(),
+            query.total
+        );
+    }
+    match &query.record {
+        Some(record) => say!("{}", describe_record(record, filter.vs.as_deref())),
+        None => say!("{} battles matched", query.total),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_history, parse_since, query_history, read_history, HistoryFilter, Since};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+
+    #[test]
+    fn append_history_writes_one_record_per_line() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("battle_history.jsonl");
+
+        let record = BattleRecord {
+            fleet_a: "a".to_owned(),
+            fleet_b: "b".to_owned(),
+            ..Default::default()
+        };
+        append_history(&path, &record)?;
+        append_history(&path, &record)?;
+
+        let history = std::fs::read_to_string(&path)?;
+        let records = history
+            .lines()
+            .map(serde_json::from_str::<BattleRecord>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].fleet_b, "b");
+
+        std::fs::write(&path, history + "{\"timestamp\": 17184")?;
+        assert_eq!(read_history(&path)?, records);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_history_filters_and_sums_up_from_the_fleets_side() {
+        let battle = |timestamp, fleet_a: &str, fleet_b: &str, outcome| BattleRecord {
+            timestamp,
+            fleet_a: fleet_a.to_owned(),
+            fleet_b: fleet_b.to_owned(),
+            outcome,
+            ..Default::default()
+        };
+        let records = vec![
+            battle(100, "iron_armada", "glass_cannon", Outcome::FleetAWon),
+            battle(200, "glass_cannon", "iron_armada", Outcome::FleetAWon),
+            battle(300, "iron_armada", "tutorial_bot", Outcome::FleetAWon),
+            battle(400, "glass-cannon", "iron_armada", Outcome::Draw),
+            battle(500, "iron_armada", "glass_cannon", Outcome::SimCrashed),
+        ];
+        let filter = HistoryFilter {
+            fleet: Some("iron_armada".to_owned()),
+            vs: Some("glass_cannon".to_owned()),
+            since: Some(200),
+        };
+
+        let query = query_history(records, &filter, 2);
+        assert_eq!(query.total, 3);
+        let timestamps: Vec<u64> = query
+            .battles
+            .iter()
+            .map(|entry| entry.record.timestamp)
+            .collect();
+        assert_eq!(timestamps, [500, 400]);
+        let record = query.record.unwrap();
+        assert_eq!(
+            (record.won, record.lost, record.drawn, record.unknown),
+            (0, 1, 1, 1)
+        );
+
+        assert!(matches!(parse_since("2024-06-01"), Ok(Since::Date(_))));
+        assert_eq!(
+            parse_since("7d").map(|since| since.cutoff(10 * 24 * 60 * 60)),
+            Ok(3 * 24 * 60 * 60)
+        );
+        assert!(parse_since("last week").is_err());
+    }
+}
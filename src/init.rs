@@ -0,0 +1,327 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+
+use crate::config::UserConfig;
+use crate::doctor::installed_targets;
+use crate::output::{dry_run, say};
+use crate::scaffold::workspace_manifest;
+use crate::sim::protologic_sim_path;
+use crate::{MetadataPackage, ParsedMetadata, WASI_TARGET};
+
+/// What `init` adds to the workspace's `.gitignore`, so built fleets and battle outputs stay out of
+/// `git status`.
+const GITIGNORE_PATTERNS: &[&str] = &[
+    "/target/protologic_fleets/",
+    "*.json.deflate",
+    "*.result.json",
+    "*.sim.log",
+    "*.fleet_a.log",
+    "*.fleet_b.log",
+    "crash_*/",
+    "fleet_objects/",
+];
+
+/// Sets up an existing workspace for cargo-protologic: marks which packages are fleets, ignores its
+/// outputs in git, checks the wasm target is installed and saves the Release's path. Whatever is
+/// already set up is reported and left alone, so it's safe to run again.
+pub fn init(fleets: Vec<String>, protologic_path: Option<PathBuf>) -> anyhow::Result<()> {
+    let manifest_path = workspace_manifest().context(
+        "not in a cargo workspace, `cargo protologic new my_fleet` starts a new one with a fleet in it",
+    )?;
+    let metadata = crate::cargo_metadata()?;
+    let root = metadata.workspace_root.clone();
+    let mut changed = false;
+
+    changed |= configure_fleets(&metadata, &manifest_path, fleets)?;
+    changed |= ignore_outputs(&root.join(".gitignore"))?;
+    check_wasm_target();
+    if let Some(path) = protologic_path {
+        changed |= save_protologic_path(&path)?;
+    }
+
+    if !changed {
+        say!("Nothing to change, the workspace is already set up");
+    } else if !dry_run() {
+        say!("Set up {} for cargo-protologic", root.display());
+    }
+    Ok(())
+}
+
+/// Records the fleets in `[workspace.metadata.protologic]`: the `explicit` ones, or those depending
+/// on the Protologic SDK. Returns whether the manifest changed.
+fn configure_fleets(
+    metadata: &ParsedMetadata,
+    manifest_path: &Path,
+    explicit: Vec<String>,
+) -> anyhow::Result<bool> {
+    let members: Vec<&MetadataPackage> = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .collect();
+    let configured = metadata.configured_fleets();
+
+    let fleets = if !explicit.is_empty() {
+        for fleet in &explicit {
+            if !members.iter().any(|package| package.name == *fleet) {
+                anyhow::bail!(
+                    "'{fleet}' isn't a workspace member, the members are: {}",
+                    names(&members).join(", ")
+                );
+            }
+        }
+        explicit
+    } else if let Some(configured) = configured {
+        say!("Fleets are already configured: {}", configured.join(", "));
+        return Ok(false);
+    } else {
+        detect_fleets(metadata, &members)?
+    };
+
+    if configured == Some(&fleets) {
+        say!("Fleets are already configured: {}", fleets.join(", "));
+        return Ok(false);
+    }
+
+    let manifest = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("trying to read the workspace manifest {manifest_path:?}"))?;
+    let manifest = set_fleets(&manifest, &fleets)
+        .with_context(|| format!("trying to configure the fleets in {manifest_path:?}"))?;
+    if dry_run() {
+        say!("Would write {}:\n{manifest}", manifest_path.display());
+        return Ok(true);
+    }
+
+    std::fs::write(manifest_path, manifest)
+        .with_context(|| format!("trying to write the workspace manifest {manifest_path:?}"))?;
+    say!(
+        "Configured the fleets in {}: {}",
+        manifest_path.display(),
+        fleets.join(", ")
+    );
+    Ok(true)
+}
+
+/// The members that depend on the Protologic SDK, or the default members when none of them do (the
+/// SDK might be vendored under another name).
+fn detect_fleets(
+    metadata: &ParsedMetadata,
+    members: &[&MetadataPackage],
+) -> anyhow::Result<Vec<String>> {
+    let sdk_users: Vec<&MetadataPackage> = members
+        .iter()
+        .filter(|package| crate::sdk_version(metadata, &package.id).is_some())
+        .copied()
+        .collect();
+    if !sdk_users.is_empty() {
+        say!("Found fleets depending on the Protologic SDK");
+        return Ok(names(&sdk_users));
+    }
+
+    let defaults: Vec<&MetadataPackage> = members
+        .iter()
+        .filter(|package| metadata.workspace_default_members.contains(&package.id))
+        .copied()
+        .collect();
+    if defaults.is_empty() {
+        anyhow::bail!("couldn't tell which packages are fleets, pick them with `--fleet`");
+    }
+    say!("No members depend on the Protologic SDK, so taking the default members as fleets. Pick them with `--fleet` otherwise");
+    Ok(names(&defaults))
+}
+
+fn names(packages: &[&MetadataPackage]) -> Vec<String> {
+    packages
+        .iter()
+        .map(|package| package.name.clone())
+        .collect()
+}
+
+/// Sets `fleets` in the manifest's `[workspace.metadata.protologic]`, keeping the rest of it as it
+/// was.
+fn set_fleets(manifest: &str, fleets: &[String]) -> anyhow::Result<String> {
+    let mut doc: DocumentMut = manifest.parse().context("trying to parse Cargo.toml")?;
+    let workspace = table_entry(doc.as_table_mut(), "workspace")?;
+    let metadata = table_entry(workspace, "metadata")?;
+    let protologic = table_entry(metadata, "protologic")?;
+    protologic.set_implicit(false);
+    protologic.insert(
+        "fleets",
+        value(Array::from_iter(fleets.iter().map(String::as_str))),
+    );
+    Ok(doc.to_string())
+}
+
+/// The table `key` in `parent`, made if it's missing. New tables are implicit, so they don't get a
+/// header of their own unless they have keys.
+fn table_entry<'a>(parent: &'a mut Table, key: &str) -> anyhow::Result<&'a mut Table> {
+    let mut table = Table::new();
+    table.set_implicit(true);
+    parent
+        .entry(key)
+        .or_insert(Item::Table(table))
+        .as_table_mut()
+        .with_context(|| format!("`{key}` isn't a table"))
+}
+
+/// Adds whatever the `.gitignore` is missing of [`GITIGNORE_PATTERNS`]. Returns whether it changed.
+fn ignore_outputs(path: &Path) -> anyhow::Result<bool> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("trying to read {path:?}")),
+    };
+
+    let missing = missing_patterns(&existing);
+    if missing.is_empty() {
+        say!("{} already ignores the battle outputs", path.display());
+        return Ok(false);
+    }
+
+    let mut contents = existing;
+    if !contents.is_empty() {
+        if !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push('\n');
+    }
+    contents.push_str("# Added by cargo-protologic\n");
+    for pattern in &missing {
+        contents.push_str(pattern);
+        contents.push('\n');
+    }
+
+    if dry_run() {
+        say!("Would add to {}:\n{}", path.display(), missing.join("\n"));
+        return Ok(true);
+    }
+    std::fs::write(path, contents).with_context(|| format!("trying to write {path:?}"))?;
+    say!("Added {} to {}", missing.join(", "), path.display());
+    Ok(true)
+}
+
+/// The patterns a `.gitignore` with `existing` in it doesn't have yet. Ignoring all of `target`
+/// covers the built fleets.
+fn missing_patterns(existing: &str) -> Vec<&'static str> {
+    let normalize = |pattern: &str| pattern.trim().trim_matches('/').to_owned();
+    let lines: Vec<String> = existing.lines().map(normalize).collect();
+    GITIGNORE_PATTERNS
+        .iter()
+        .copied()
+        .filter(|&pattern| {
+            let pattern = normalize(pattern);
+            let covered =
+                pattern.starts_with("target/") && lines.iter().any(|line| line == "target");
+            !covered && !lines.contains(&pattern)
+        })
+        .collect()
+}
+
+/// Warns when rustup doesn't have the target fleets are built for. Toolchains not managed by rustup
+/// can't be asked, so they're left to `build` to find out.
+fn check_wasm_target() {
+    let installed =
+        installed_targets().map(|targets| targets.iter().any(|target| target == WASI_TARGET));
+
+    match installed {
+        Some(true) => say!("The {WASI_TARGET} target is installed"),
+        Some(false) => say!(
+            "Warning: the {WASI_TARGET} target isn't installed, so fleets can't be built. Install it with `rustup target add {WASI_TARGET}`"
+        ),
+        None => say!(
+            "Warning: couldn't ask rustup whether the {WASI_TARGET} target is installed, fleets need it to build"
+        ),
+    }
+}
+
+/// Saves the Release's path in the user config, as `config set protologic-path` does. Returns
+/// whether the config changed.
+fn save_protologic_path(path: &Path) -> anyhow::Result<bool> {
+    if !protologic_sim_path(path).is_file() {
+        say!(
+            "Warning: there's no sim in {}, is it a Protologic Release checkout?",
+            path.display()
+        );
+    }
+
+    let mut user_config = UserConfig::read()?;
+    let before = user_config.clone();
+    user_config.set_protologic_path(&path.to_string_lossy())?;
+    if user_config == before {
+        say!(
+            "The Protologic Release path is already saved as {}",
+            path.display()
+        );
+        return Ok(false);
+    }
+
+    if dry_run() {
+        say!(
+            "Would write to {}:\n{}",
+            UserConfig::path()?.display(),
+            toml::to_string(&user_config)?
+        );
+        return Ok(true);
+    }
+    let config_path = user_config.write()?;
+    say!(
+        "Saved the Protologic Release path to {}",
+        config_path.display()
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{missing_patterns, set_fleets, GITIGNORE_PATTERNS};
+
+    #[test]
+    fn set_fleets_adds_our_table_to_the_workspace() -> anyhow::Result<()> {
+        let fleets = ["glass-cannon".to_owned(), "iron-armada".to_owned()];
+        assert_eq!(
+            set_fleets(
+                "[workspace]\nmembers = [\"glass-cannon\", \"iron-armada\", \"targeting\"]\n",
+                &fleets
+            )?,
+            r#"[workspace]
+members = ["glass-cannon", "iron-armada", "targeting"]
+
+[workspace.metadata.protologic]
+fleets = ["glass-cannon", "iron-armada"]
+"#
+        );
+
+        let manifest = r#"[package]
+name = "iron-armada"
+
+[workspace.metadata.protologic]
+output-dir = "battles"
+fleets = ["iron-armada"]
+"#;
+        assert_eq!(
+            set_fleets(manifest, &fleets)?,
+            manifest.replace(r#"["iron-armada"]"#, r#"["glass-cannon", "iron-armada"]"#)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_patterns_skips_what_is_already_ignored() {
+        assert_eq!(missing_patterns(""), GITIGNORE_PATTERNS);
+        assert_eq!(
+            missing_patterns("/target\n*.json.deflate\n  *.result.json\n"),
+            [
+                "*.sim.log",
+                "*.fleet_a.log",
+                "*.fleet_b.log",
+                "crash_*/",
+                "fleet_objects/"
+            ]
+        );
+
+        let everything = GITIGNORE_PATTERNS.join("\n");
+        assert!(missing_patterns(&everything).is_empty());
+    }
+}
@@ -0,0 +1,732 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Deserialize;
+
+use crate::config::UserConfig;
+use crate::curl::Get;
+use crate::doctor::is_lfs_pointer;
+use crate::hash::file_sha256;
+use crate::naming::add_extension;
+use crate::output::{dry_run, say};
+use crate::run::fleet_file_name;
+use crate::sim::protologic_sim_path;
+use crate::version::release_version;
+
+/// The GitHub repository Releases are published from.
+const RELEASE_REPO: &str = "Protologic/Release";
+/// Marks a directory as a Release this tool installed, so installing again may replace it. Holds
+/// the release's tag.
+pub const INSTALL_MARKER: &str = ".cargo-protologic-install";
+/// Names of assets that list the checksums of the others, in `sha256sum` format.
+const CHECKSUM_LISTS: &[&str] = &["sha256sums", "sha256sums.txt", "checksums.txt"];
+
+/// A GitHub release, as much of it as installing needs.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GithubRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<GithubAsset>,
+    /// GitHub's archive of the repository at the release's tag.
+    pub zipball_url: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GithubAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+    /// What GitHub computed of the upload, like `sha256:ba78…`.
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+/// How an archive is packed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    fn of(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// What to download for a release: its name, where from, and how big it is when that's known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Download {
+    file_name: String,
+    url: String,
+    size: Option<u64>,
+    kind: ArchiveKind,
+    /// The asset GitHub has it as, which the source archive isn't.
+    asset: Option<GithubAsset>,
+}
+
+/// Installs the Protologic Release tagged `version` (the latest without it) under `path` (or the
+/// user data directory), and makes it the one `run` uses. With `list`, shows the versions there are
+/// instead.
+pub fn install(path: Option<PathBuf>, version: Option<String>, list: bool) -> anyhow::Result<()> {
+    let root = match path {
+        Some(path) => std::path::absolute(&path)
+            .with_context(|| format!("trying to make {path:?} absolute"))?,
+        None => default_releases_dir()?,
+    };
+    if list {
+        return list_versions(&root);
+    }
+
+    let release = match version {
+        Some(tag) => release_by_tag(&tag)?,
+        None => latest_release()?,
+    };
+    install_release(&release, &root)
+}
+
+/// Installs `release` into its version's directory under `root` unless it's already there, and
+/// makes it the active one.
+pub fn install_release(release: &GithubRelease, root: &Path) -> anyhow::Result<()> {
+    let tag = &release.tag_name;
+    let dir = root.join(fleet_file_name(tag));
+    if installed_version(&dir).as_ref() == Some(tag) && protologic_sim_path(&dir).is_file() {
+        say!(
+            "Protologic Release {tag} is already installed in {}",
+            dir.display()
+        );
+        return activate(&dir, tag);
+    }
+    check_replaceable(&dir)?;
+
+    let download = pick_download(release, std::env::consts::OS);
+    let archive = downloads_dir()?.join(format!("{}-{}", fleet_file_name(tag), download.file_name));
+    if dry_run() {
+        say!(
+            "Would download Protologic Release {tag} from {}{} to {}",
+            download.url,
+            download
+                .size
+                .map_or(String::new(), |size| format!(" ({})", ByteSize::b(size))),
+            archive.display()
+        );
+        say!(
+            "Would check it against the published checksum and extract it into {}",
+            dir.display()
+        );
+        say!("Would make {tag} the active Release in your user config");
+        return Ok(());
+    }
+
+    let checksum = published_checksum(release, &download)?;
+    if checksum.is_none() {
+        discard_download(&archive)?;
+    }
+    say!("Downloading Protologic Release {tag} from {}", download.url);
+    fetch(&download.url, &archive)?;
+    match checksum {
+        Some(expected) => verify_checksum(&archive, &expected)?,
+        None => say!(
+            "Warning: {tag} has no published checksum for {}, so the download can't be verified",
+            download.file_name
+        ),
+    }
+
+    say!("Extracting into {}", dir.display());
+    unpack(&archive, download.kind, &dir)?;
+    std::fs::write(dir.join(INSTALL_MARKER), tag)
+        .with_context(|| format!("trying to mark {dir:?} as installed"))?;
+
+    let sim = protologic_sim_path(&dir);
+    if is_lfs_pointer(&sim) {
+        say!("Warning: the downloaded sim is a git-lfs pointer rather than the sim itself, so it won't run. Clone {RELEASE_REPO} with git-lfs installed instead");
+    } else if sim.is_file() {
+        make_executable(&sim)?;
+    } else {
+        say!(
+            "Warning: there's no sim at {} in the download, is it a full Release?",
+            sim.display()
+        );
+    }
+
+    let version = release_version(&dir, &sim).unwrap_or_else(|| tag.clone());
+    say!(
+        "Installed Protologic Release {version} in {}",
+        dir.display()
+    );
+    activate(&dir, tag)
+}
+
+/// Saves `dir` as protologic-path in the user config, and `tag` as the version there.
+fn activate(dir: &Path, tag: &str) -> anyhow::Result<()> {
+    let mut user_config = UserConfig::read()?;
+    user_config.set_protologic_path(&dir.to_string_lossy())?;
+    user_config.release_version = Some(tag.to_owned());
+    if dry_run() {
+        say!("Would make {tag} the active Release in your user config");
+        return Ok(());
+    }
+    let config_path = user_config.write()?;
+    say!(
+        "{tag} is now the active Release, saved in {}",
+        config_path.display()
+    );
+    Ok(())
+}
+
+/// Where Releases are installed when `--path` isn't given, each in a directory named for its
+/// version, like `~/.local/share/cargo-protologic/releases/v1.4.2`.
+pub fn default_releases_dir() -> anyhow::Result<PathBuf> {
+    let dir = dirs::data_dir().context("can't find your user data directory")?;
+    Ok(dir.join("cargo-protologic").join("releases"))
+}
+
+/// Where downloads are kept while they're going, so an interrupted one can pick up where it left
+/// off.
+fn downloads_dir() -> anyhow::Result<PathBuf> {
+    let dir = dirs::cache_dir().context("can't find your cache directory")?;
+    Ok(dir.join("cargo-protologic").join("downloads"))
+}
+
+/// Only empty directories and earlier installs get replaced, never anything else someone keeps
+/// there.
+fn check_replaceable(dir: &Path) -> anyhow::Result<()> {
+    let empty = std::fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none());
+    if empty || dir.join(INSTALL_MARKER).is_file() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "{} already has things in it that this tool didn't install, pick another `--path`",
+        dir.display()
+    )
+}
+
+/// The version installed in `dir`, when this tool installed it.
+pub fn installed_version(dir: &Path) -> Option<String> {
+    let tag = std::fs::read_to_string(dir.join(INSTALL_MARKER)).ok()?;
+    Some(tag.trim().to_owned()).filter(|tag| !tag.is_empty())
+}
+
+/// The versions installed under `root`, sorted by tag.
+fn installed_versions(root: &Path) -> Vec<(String, PathBuf)> {
+    let mut versions: Vec<(String, PathBuf)> = std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((installed_version(&entry.path())?, entry.path())))
+        .collect();
+    versions.sort();
+    versions
+}
+
+/// Prints the published versions, newest first, with which are installed under `root` and which is
+/// active. Without GitHub, only the installed ones are shown.
+fn list_versions(root: &Path) -> anyhow::Result<()> {
+    let installed = installed_versions(root);
+    let user_config = UserConfig::read()?;
+    let active = user_config.release_version.filter(|_| {
+        user_config
+            .protologic_path
+            .as_deref()
+            .is_some_and(|path| path.starts_with(root))
+    });
+
+    let (mut tags, gone): (Vec<(String, &str)>, &str) = match releases() {
+        Ok(releases) => {
+            let tags = releases
+                .into_iter()
+                .map(|release| {
+                    let note = if release.prerelease {
+                        " (prerelease)"
+                    } else {
+                        ""
+                    };
+                    (release.tag_name, note)
+                })
+                .collect();
+            (tags, " (no longer published)")
+        }
+        Err(err) => {
+            say!("Warning: couldn't list the releases on GitHub, so only showing what's installed: {err:#}");
+            (Vec::new(), "")
+        }
+    };
+    for (tag, _) in &installed {
+        if !tags.iter().any(|(published, _)| published == tag) {
+            tags.push((tag.clone(), gone));
+        }
+    }
+    if tags.is_empty() {
+        say!("No Protologic Releases published or installed");
+        return Ok(());
+    }
+
+    say!(
+        "Protologic Releases (* is active), installed in {}:",
+        root.display()
+    );
+    for (tag, note) in tags {
+        let mark = if active.as_ref() == Some(&tag) {
+            "*"
+        } else {
+            " "
+        };
+        let state = if installed.iter().any(|(installed, _)| *installed == tag) {
+            " installed"
+        } else {
+            ""
+        };
+        say!("{mark} {tag}{state}{note}");
+    }
+    Ok(())
+}
+
+/// Fetches `url`, adding what the GitHub API wants (and a `GITHUB_TOKEN`, which is easier on rate
+/// limits) for `api` requests.
+fn github_get(url: &str, api: bool) -> anyhow::Result<Vec<u8>> {
+    let token = std::env::var("GITHUB_TOKEN").ok().filter(|_| api);
+    let headers: &[&str] = if api {
+        &["Accept: application/vnd.github+json"]
+    } else {
+        &[]
+    };
+    Get {
+        purpose: "ask GitHub about releases",
+        headers,
+        token: token.as_deref(),
+        ..Get::default()
+    }
+    .fetch(url)
+}
+
+fn github_api<T: serde::de::DeserializeOwned>(path: &str) -> anyhow::Result<T> {
+    let url = format!("https://api.github.com/repos/{RELEASE_REPO}/{path}");
+    let json = github_get(&url, true)?;
+    serde_json::from_slice(&json)
+        .with_context(|| format!("trying to parse the response from {url}"))
+}
+
+/// The newest published Release.
+pub fn latest_release() -> anyhow::Result<GithubRelease> {
+    github_api("releases/latest")
+}
+
+/// The Release tagged `tag`.
+fn release_by_tag(tag: &str) -> anyhow::Result<GithubRelease> {
+    github_api(&format!("releases/tags/{tag}")).with_context(|| {
+        format!("couldn't find Protologic Release {tag}, `cargo protologic install --list` shows the versions there are")
+    })
+}
+
+/// The published Releases, newest first.
+fn releases() -> anyhow::Result<Vec<GithubRelease>> {
+    github_api("releases?per_page=100")
+}
+
+/// The release's archive for `os`: an asset named for it, the only archive asset, or failing both
+/// the source archive GitHub makes of the tag.
+fn pick_download(release: &GithubRelease, os: &str) -> Download {
+    let archives: Vec<(&GithubAsset, ArchiveKind)> = release
+        .assets
+        .iter()
+        .filter_map(|asset| Some((asset, ArchiveKind::of(&asset.name)?)))
+        .collect();
+    let os_names: &[&str] = match os {
+        "windows" => &["windows", "win64", "win"],
+        "macos" => &["macos", "osx", "mac"],
+        _ => &["linux"],
+    };
+    let for_os = archives.iter().find(|(asset, _)| {
+        let name = asset.name.to_lowercase();
+        os_names.iter().any(|os| name.contains(os))
+    });
+
+    match for_os.or(archives.first().filter(|_| archives.len() == 1)) {
+        Some((asset, kind)) => Download {
+            file_name: asset.name.clone(),
+            url: asset.browser_download_url.clone(),
+            size: Some(asset.size).filter(|&size| size > 0),
+            kind: *kind,
+            asset: Some((*asset).clone()),
+        },
+        None => Download {
+            file_name: "source.zip".to_owned(),
+            url: release.zipball_url.clone(),
+            size: None,
+            kind: ArchiveKind::Zip,
+            asset: None,
+        },
+    }
+}
+
+/// The SHA-256 the release publishes for the download: GitHub's digest of the asset, a
+/// `<asset>.sha256` file next to it, or its line in a checksum list. GitHub's source archives are
+/// made on demand, so they never have one.
+fn published_checksum(
+    release: &GithubRelease,
+    download: &Download,
+) -> anyhow::Result<Option<String>> {
+    let Some(asset) = &download.asset else {
+        return Ok(None);
+    };
+    if let Some(digest) = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+    {
+        return Ok(Some(digest.to_lowercase()));
+    }
+
+    let name = asset.name.to_lowercase();
+    for checksums in &release.assets {
+        let checksums_name = checksums.name.to_lowercase();
+        let single = checksums_name == format!("{name}.sha256");
+        if !single && !CHECKSUM_LISTS.contains(&checksums_name.as_str()) {
+            continue;
+        }
+
+        let text = github_get(&checksums.browser_download_url, false)
+            .with_context(|| format!("trying to download the checksums in {}", checksums.name))?;
+        let text = String::from_utf8_lossy(&text);
+        let found = if single {
+            text.split_whitespace().next().map(str::to_lowercase)
+        } else {
+            checksum_for(&text, &asset.name)
+        };
+        match found {
+            Some(checksum) => return Ok(Some(checksum)),
+            None => anyhow::bail!(
+                "{} doesn't have a checksum for {}",
+                checksums.name,
+                asset.name
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// The checksum for the file `name` in a `sha256sum` style list, with lines like `<hex>  <name>`,
+/// or `<hex> *<name>` for binary mode.
+fn checksum_for(list: &str, name: &str) -> Option<String> {
+    list.lines().find_map(|line| {
+        let (checksum, file) = line.trim().split_once(char::is_whitespace)?;
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name || Path::new(file).file_name()? == name).then(|| checksum.to_lowercase())
+    })
+}
+
+/// Fails when `archive` isn't what was published, deleting it so the next try downloads it again.
+fn verify_checksum(archive: &Path, expected: &str) -> anyhow::Result<()> {
+    let actual = file_sha256(archive)?;
+    if actual != expected {
+        let _ = std::fs::remove_file(archive);
+        anyhow::bail!(
+            "the download's SHA-256 is {actual}, but the published checksum is {expected}. It's been deleted, so running this again downloads it afresh"
+        );
+    }
+    say!("The download matches the published checksum");
+    Ok(())
+}
+
+/// Downloads `url` to `output` with curl, showing its progress. The download goes to a `.part`
+/// file first, which a later run picks up from if this one is interrupted.
+fn fetch(url: &str, output: &Path) -> anyhow::Result<()> {
+    if output.is_file() {
+        say!("Using the download from before, {}", output.display());
+        return Ok(());
+    }
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("trying to create download directory {dir:?}"))?;
+    }
+
+    let partial = add_extension(output, "part");
+    let resuming = partial.is_file();
+    if resuming {
+        say!("Picking up the earlier download where it left off");
+    }
+    let curl = |resume: bool| -> anyhow::Result<bool> {
+        let mut curl = Command::new("curl");
+        curl.args(["--fail", "--location", "--retry", "3", "--progress-bar"]);
+        if resume {
+            curl.args(["--continue-at", "-"]);
+        }
+        let status = curl
+            .arg("--output")
+            .arg(&partial)
+            .arg(url)
+            .stdin(Stdio::null())
+            .status()
+            .context("trying to run curl to download the Release, is it installed?")?;
+        Ok(status.success())
+    };
+
+    // A server that can't resume, or a partial download that's gone bad, gets a fresh start
+    let done = curl(resuming)? || {
+        if resuming {
+            say!("Couldn't pick up the earlier download, starting again");
+            let _ = std::fs::remove_file(&partial);
+            curl(false)?
+        } else {
+            false
+        }
+    };
+    if !done {
+        anyhow::bail!("couldn't download {url}, running this again picks up where it left off");
+    }
+
+    std::fs::rename(&partial, output)
+        .with_context(|| format!("trying to move the download to {output:?}"))
+}
+
+/// Deletes what's left of an earlier download to `output`, finished or not. Without a published
+/// checksum, there's no telling whether it's what was published, so it's downloaded afresh.
+fn discard_download(output: &Path) -> anyhow::Result<()> {
+    for path in [output.to_owned(), add_extension(output, "part")] {
+        match std::fs::remove_file(&path) {
+            Ok(()) => say!(
+                "Not reusing the unverified download from before, {}",
+                path.display()
+            ),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("trying to delete {path:?}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the archive into `dir`, replacing whatever install was there. Archives with everything
+/// in one top directory, like GitHub's, have it taken off.
+fn unpack(archive: &Path, kind: ArchiveKind, dir: &Path) -> anyhow::Result<()> {
+    let staging = add_extension(dir, "extracting");
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .with_context(|| format!("trying to clear out {staging:?}"))?;
+    }
+    std::fs::create_dir_all(&staging).with_context(|| format!("trying to create {staging:?}"))?;
+
+    let file = File::open(archive).with_context(|| format!("trying to open {archive:?}"))?;
+    match kind {
+        ArchiveKind::Zip => zip::ZipArchive::new(BufReader::new(file))
+            .and_then(|mut zip| zip.extract(&staging))
+            .with_context(|| format!("trying to extract {archive:?}"))?,
+        ArchiveKind::TarGz => tar::Archive::new(flate2::read::GzDecoder::new(BufReader::new(file)))
+            .unpack(&staging)
+            .with_context(|| format!("trying to extract {archive:?}"))?,
+    }
+
+    let root = single_top_dir(&staging)?.unwrap_or_else(|| staging.clone());
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("trying to remove the old install in {dir:?}"))?;
+    }
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("trying to create {parent:?}"))?;
+    }
+    std::fs::rename(&root, dir)
+        .with_context(|| format!("trying to move the extracted Release to {dir:?}"))?;
+    let _ = std::fs::remove_dir_all(&staging);
+    Ok(())
+}
+
+/// The only thing in `dir`, if that's a directory.
+fn single_top_dir(dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("trying to read {dir:?}"))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<_>>()
+        .with_context(|| format!("trying to read {dir:?}"))?;
+    match entries.as_slice() {
+        [only] if only.is_dir() => Ok(Some(only.clone())),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)
+        .with_context(|| format!("trying to read the permissions of {path:?}"))?
+        .permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    std::fs::set_permissions(path, permissions)
+        .with_context(|| format!("trying to make {path:?} executable"))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{
+        check_replaceable, checksum_for, discard_download, installed_versions, pick_download,
+        unpack, verify_checksum, ArchiveKind, GithubAsset, GithubRelease, INSTALL_MARKER,
+    };
+
+    fn github_release(assets: &[&str]) -> GithubRelease {
+        GithubRelease {
+            tag_name: "v0.7.2".to_owned(),
+            prerelease: false,
+            assets: assets
+                .iter()
+                .map(|name| GithubAsset {
+                    name: (*name).to_owned(),
+                    browser_download_url: format!("https://example.com/{name}"),
+                    size: 1024,
+                    digest: None,
+                })
+                .collect(),
+            zipball_url: "https://api.github.com/repos/Protologic/Release/zipball/v0.7.2"
+                .to_owned(),
+        }
+    }
+
+    #[test]
+    fn pick_download_prefers_the_archive_for_this_os() {
+        let release = github_release(&["Release-Windows.zip", "Release-Linux.tar.gz", "notes.txt"]);
+        let download = pick_download(&release, "linux");
+        assert_eq!(download.file_name, "Release-Linux.tar.gz");
+        assert_eq!(download.kind, ArchiveKind::TarGz);
+        assert_eq!(download.size, Some(1024));
+        assert_eq!(
+            pick_download(&release, "windows").file_name,
+            "Release-Windows.zip"
+        );
+
+        // Nothing for macOS, and more than one archive to guess between
+        let download = pick_download(&release, "macos");
+        assert_eq!(download.file_name, "source.zip");
+        assert_eq!(download.url, release.zipball_url);
+
+        assert_eq!(
+            pick_download(&github_release(&["Release.zip"]), "macos").file_name,
+            "Release.zip"
+        );
+    }
+
+    #[test]
+    fn unpack_takes_off_the_top_directory() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("release.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file(
+            "Protologic-Release-1a2b3c/Sim/Linux/Protologic.Terminal",
+            options,
+        )?;
+        zip.write_all(b"#!/bin/sh\n")?;
+        zip.finish()?;
+
+        let install = dir.path().join("Release");
+        unpack(&archive, ArchiveKind::Zip, &install)?;
+        assert!(install.join("Sim/Linux/Protologic.Terminal").is_file());
+        assert!(!dir.path().join("Release.extracting").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn check_replaceable_protects_other_directories() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(check_replaceable(&dir.path().join("missing")).is_ok());
+        assert!(check_replaceable(dir.path()).is_ok());
+
+        std::fs::write(dir.path().join("thesis.tex"), "")?;
+        assert!(check_replaceable(dir.path()).is_err());
+        std::fs::write(dir.path().join(INSTALL_MARKER), "v0.7.2")?;
+        assert!(check_replaceable(dir.path()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn checksum_for_finds_the_file_in_a_list() {
+        let list = "\
+ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  Release-Linux.tar.gz
+E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855 *dist/Release-Windows.zip
+";
+        assert_eq!(
+            checksum_for(list, "Release-Linux.tar.gz").as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            checksum_for(list, "Release-Windows.zip").as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(checksum_for(list, "Release-macOS.zip"), None);
+    }
+
+    #[test]
+    fn verify_checksum_deletes_mismatched_downloads() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("release.zip");
+        std::fs::write(&archive, "abc")?;
+
+        verify_checksum(
+            &archive,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        )?;
+        assert!(verify_checksum(&archive, &"0".repeat(64)).is_err());
+        assert!(!archive.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn discard_download_deletes_finished_and_partial_downloads() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("release.zip");
+        let partial = dir.path().join("release.zip.part");
+        discard_download(&archive)?;
+
+        std::fs::write(&archive, "abc")?;
+        std::fs::write(&partial, "a")?;
+        discard_download(&archive)?;
+        assert!(!archive.exists() && !partial.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn installed_versions_are_the_marked_directories() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        for (dir, tag) in [
+            ("v1.4.2", Some("v1.4.2")),
+            ("v1.3.0", Some("v1.3.0\n")),
+            ("mine", None),
+        ] {
+            let dir = root.path().join(dir);
+            std::fs::create_dir(&dir)?;
+            if let Some(tag) = tag {
+                std::fs::write(dir.join(INSTALL_MARKER), tag)?;
+            }
+        }
+
+        assert_eq!(
+            installed_versions(root.path()),
+            [
+                ("v1.3.0".to_owned(), root.path().join("v1.3.0")),
+                ("v1.4.2".to_owned(), root.path().join("v1.4.2")),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches Ctrl-C, so long-running modes can stop the sim and shut down cleanly rather than dying
+/// halfway through writing a result. A second Ctrl-C quits straight away, as usual.
+pub fn install_handler() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        extern "C" fn handle(_signal: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            // SAFETY: `signal` is async-signal-safe
+            unsafe {
+                libc::signal(libc::SIGINT, libc::SIG_DFL);
+            }
+        }
+
+        let handle: extern "C" fn(libc::c_int) = handle;
+        // SAFETY: the handler only touches an atomic and calls async-signal-safe functions
+        let previous = unsafe { libc::signal(libc::SIGINT, handle as libc::sighandler_t) };
+        if previous == libc::SIG_ERR {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::BOOL;
+        use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_C_EVENT};
+
+        unsafe extern "system" fn handle(event: u32) -> BOOL {
+            if event != CTRL_C_EVENT || INTERRUPTED.swap(true, Ordering::SeqCst) {
+                // Let the default handler quit on a second Ctrl-C, or anything else
+                return 0;
+            }
+            1
+        }
+
+        // SAFETY: the handler only touches an atomic
+        if unsafe { SetConsoleCtrlHandler(Some(handle), 1) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    HANDLER_INSTALLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Whether Ctrl-C is being caught, rather than killing us outright.
+pub fn handler_installed() -> bool {
+    HANDLER_INSTALLED.load(Ordering::SeqCst)
+}
+
+/// Whether Ctrl-C has been pressed since the handler was installed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,498 @@
+//! `lint`: checks of built fleets for mistakes that show in the wasm, each with why it matters and
+//! how to fix it. A lint is a function over the parsed module and a row in [`LINTS`], so new ones
+//! can be added as the sim changes.
+
+use std::fmt;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Serialize;
+use wasmparser::{KnownCustom, Name, Parser, Payload, TypeRef};
+
+use crate::fleet::{find_built_fleets, find_fleet_or_file, Fleet};
+use crate::output::say;
+use crate::settings::settings;
+use crate::validate::ALLOWED_IMPORT_MODULES;
+
+const WASI_MODULE: &str = "wasi_snapshot_preview1";
+/// The WASI functions the sim provides. Fleets have no files, sockets or threads to work with.
+const SIM_WASI_FUNCTIONS: &[&str] = &[
+    "args_get",
+    "args_sizes_get",
+    "clock_res_get",
+    "clock_time_get",
+    "environ_get",
+    "environ_sizes_get",
+    "fd_close",
+    "fd_fdstat_get",
+    "fd_prestat_dir_name",
+    "fd_prestat_get",
+    "fd_seek",
+    "fd_write",
+    "proc_exit",
+    "random_get",
+    "sched_yield",
+];
+/// The most memory a fleet can start with, unless the `lint.max-memory` setting says otherwise.
+const DEFAULT_MAX_MEMORY: u64 = 64 * 1024 * 1024;
+/// The biggest a data segment can be before it's called out, unless the `lint.max-data-segment`
+/// setting says otherwise.
+const DEFAULT_MAX_DATA_SEGMENT: u64 = 256 * 1024;
+const WASM_PAGE_BYTES: u64 = 65536;
+/// Functions of Rust's panic and formatting machinery, as they show in the name section, mangled
+/// or not.
+const PANIC_FUNCTIONS: &[&str] = &[
+    "core::panicking::panic_fmt",
+    "core::fmt::write",
+    "std::panicking::begin_panic_handler",
+    "4core9panicking9panic_fmt",
+    "4core3fmt5write",
+    "3std9panicking19begin_panic_handler",
+];
+/// What the same machinery leaves in the data, for fleets built without a name section.
+const PANIC_STRINGS: &[&[u8]] = &[
+    b" panicked at ",
+    b"called `Option::unwrap()` on a `None` value",
+];
+
+/// How bad what a lint found is.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    /// The fleet won't work in the sim, so `lint` fails.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// What a lint is checked against: the parts of the module the lints look at, parsed once.
+#[derive(Debug, Default)]
+pub struct Module<'a> {
+    /// The functions imported, by module and name.
+    pub imports: Vec<(&'a str, &'a str)>,
+    pub initial_memory_pages: Option<u64>,
+    pub data_segments: Vec<&'a [u8]>,
+    /// The functions named in the name section, if it's there.
+    pub function_names: Vec<&'a str>,
+}
+
+impl<'a> Module<'a> {
+    pub fn parse(bytes: &'a [u8]) -> anyhow::Result<Self> {
+        if !Parser::is_core_wasm(bytes) {
+            anyhow::bail!("it's missing the wasm module header");
+        }
+        let mut module = Self::default();
+        for payload in Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader.into_imports() {
+                        let import = import?;
+                        if let TypeRef::Func(_) = import.ty {
+                            module.imports.push((import.module, import.name));
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader {
+                        module.initial_memory_pages.get_or_insert(memory?.initial);
+                    }
+                }
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        module.data_segments.push(data?.data);
+                    }
+                }
+                Payload::CustomSection(reader) => {
+                    let KnownCustom::Name(reader) = reader.as_known() else {
+                        continue;
+                    };
+                    for name in reader.into_iter().flatten() {
+                        if let Name::Function(map) = name {
+                            module
+                                .function_names
+                                .extend(map.into_iter().flatten().map(|naming| naming.name));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+}
+
+/// The limits lints check against, from the settings.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_memory: u64,
+    pub max_data_segment: u64,
+}
+
+/// A check of a fleet's wasm. Each problem it finds is a message saying what's wrong, and the lint's
+/// `fix` says what to do about it.
+pub struct Lint {
+    pub name: &'static str,
+    pub severity: Severity,
+    /// What the lint checks, for `lint --list`.
+    pub description: &'static str,
+    pub fix: &'static str,
+    check: fn(&Module, Limits) -> Vec<String>,
+}
+
+/// Every lint there is, in the order they're reported.
+pub const LINTS: &[Lint] = &[
+    Lint {
+        name: "unsupported-wasi",
+        severity: Severity::Error,
+        description: "imports of WASI functions the sim doesn't provide",
+        fix: "something is using files, sockets, threads or stdin, often a dependency through std. The sim only gives fleets a clock, randomness, stdout and `sched_yield`",
+        check: unsupported_wasi,
+    },
+    Lint {
+        name: "foreign-import",
+        severity: Severity::Error,
+        description: "imports from modules other than WASI and the sim's own",
+        fix: "a dependency was built for another host, like the web through wasm-bindgen. Turn off its features that need one, or replace it",
+        check: foreign_imports,
+    },
+    Lint {
+        name: "no-sched-yield",
+        severity: Severity::Error,
+        description: "fleets that don't import `sched_yield`, which Asyncify pauses them at",
+        fix: "call the SDK's yield at the end of each tick of your main loop, or the sim can never hand control to the other fleet",
+        check: no_sched_yield,
+    },
+    Lint {
+        name: "memory-cap",
+        severity: Severity::Error,
+        description: "fleets that start with more memory than the sim gives them",
+        fix: "look for big statics or arrays sized for the worst case, and allocate what's needed at startup instead. Set `lint.max-memory` if your sim allows more",
+        check: memory_cap,
+    },
+    Lint {
+        name: "large-data",
+        severity: Severity::Warning,
+        description: "big data segments, like an asset embedded with `include_bytes!`",
+        fix: "shrink or compress the asset, or compute the table at startup. It all counts towards the fleet's size. Set `lint.max-data-segment` to change the threshold",
+        check: large_data,
+    },
+    Lint {
+        name: "panic-formatting",
+        severity: Severity::Warning,
+        description: "Rust's panic and formatting machinery pulled into the fleet",
+        fix: "set `panic = \"abort\"` in the release profile, and avoid `unwrap`, `expect` and `format!` where a fallback will do. `cargo protologic diff` shows what it costs",
+        check: panic_formatting,
+    },
+];
+
+fn unsupported_wasi(module: &Module, _: Limits) -> Vec<String> {
+    module
+        .imports
+        .iter()
+        .filter(|(from, name)| *from == WASI_MODULE && !SIM_WASI_FUNCTIONS.contains(name))
+        .map(|(from, name)| format!("it imports {from}.{name}, which the sim doesn't provide"))
+        .collect()
+}
+
+fn foreign_imports(module: &Module, _: Limits) -> Vec<String> {
+    module
+        .imports
+        .iter()
+        .filter(|(from, _)| !ALLOWED_IMPORT_MODULES.contains(from))
+        .map(|(from, name)| {
+            format!(
+                "it imports {from}.{name}, but the sim only provides {}",
+                ALLOWED_IMPORT_MODULES.join(" and ")
+            )
+        })
+        .collect()
+}
+
+fn no_sched_yield(module: &Module, _: Limits) -> Vec<String> {
+    if module.imports.contains(&(WASI_MODULE, "sched_yield")) {
+        return Vec::new();
+    }
+    vec![
+        "it doesn't import sched_yield, so it will never yield and the sim can't end its ticks"
+            .to_owned(),
+    ]
+}
+
+fn memory_cap(module: &Module, limits: Limits) -> Vec<String> {
+    let Some(pages) = module.initial_memory_pages else {
+        return Vec::new();
+    };
+    let bytes = pages * WASM_PAGE_BYTES;
+    if bytes <= limits.max_memory {
+        return Vec::new();
+    }
+    vec![format!(
+        "it starts with {pages} pages of memory ({}), over the sim's {}",
+        ByteSize::b(bytes),
+        ByteSize::b(limits.max_memory)
+    )]
+}
+
+fn large_data(module: &Module, limits: Limits) -> Vec<String> {
+    module
+        .data_segments
+        .iter()
+        .enumerate()
+        .filter(|(_, data)| data.len() as u64 > limits.max_data_segment)
+        .map(|(i, data)| {
+            format!(
+                "data segment {i} is {}, over {}",
+                ByteSize::b(data.len() as u64),
+                ByteSize::b(limits.max_data_segment)
+            )
+        })
+        .collect()
+}
+
+fn panic_formatting(module: &Module, _: Limits) -> Vec<String> {
+    let named = module.function_names.iter().find(|name| {
+        PANIC_FUNCTIONS
+            .iter()
+            .any(|function| name.contains(function))
+    });
+    if let Some(name) = named {
+        return vec![format!(
+            "it has {name}, part of the panic formatting machinery"
+        )];
+    }
+    let in_data = module.data_segments.iter().any(|data| {
+        PANIC_STRINGS
+            .iter()
+            .any(|marker| data.windows(marker.len()).any(|window| window == *marker))
+    });
+    if in_data {
+        return vec![
+            "its data has panic messages, so the panic formatting machinery is in it".to_owned(),
+        ];
+    }
+    Vec::new()
+}
+
+/// Something a lint found.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub lint: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub fix: &'static str,
+}
+
+/// Runs every lint that isn't in `allowed` over the module.
+pub fn lint_module(module: &Module, limits: Limits, allowed: &[String]) -> Vec<Finding> {
+    LINTS
+        .iter()
+        .filter(|lint| !allowed.iter().any(|allowed| allowed == lint.name))
+        .flat_map(|lint| {
+            (lint.check)(module, limits)
+                .into_iter()
+                .map(|message| Finding {
+                    lint: lint.name,
+                    severity: lint.severity,
+                    message,
+                    fix: lint.fix,
+                })
+        })
+        .collect()
+}
+
+/// Checks the lints named to be allowed exist, so a typo doesn't quietly allow nothing.
+fn check_allowed(allowed: &[String]) -> anyhow::Result<()> {
+    for name in allowed {
+        if !LINTS.iter().any(|lint| lint.name == name) {
+            let names: Vec<&str> = LINTS.iter().map(|lint| lint.name).collect();
+            anyhow::bail!(
+                "there's no lint called '{name}' to allow, the lints are: {}",
+                names.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Arguments for `cargo protologic lint`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct LintArgs {
+    /// The fleets to lint, by name or as wasm files. Defaults to every built fleet.
+    fleets: Vec<String>,
+    /// Lint the fleets from `build --debug`.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+    /// Don't run this lint, on top of the `lint.allow` setting. Can be given more than once.
+    #[arg(long = "allow", value_name = "LINT")]
+    allowed: Vec<String>,
+    /// List the lints, instead of running them.
+    #[arg(long, default_value = "false", conflicts_with = "fleets")]
+    list: bool,
+    /// Print what was found in each fleet as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// What linting one fleet found.
+#[derive(Serialize, Debug, Clone)]
+struct Report {
+    fleet: String,
+    path: std::path::PathBuf,
+    findings: Vec<Finding>,
+}
+
+/// Lints built fleets, printing what each has wrong with a fix for it. Fails if any have errors.
+pub fn lint(args: LintArgs) -> anyhow::Result<()> {
+    if args.list {
+        for lint in LINTS {
+            say!("{} ({}): {}", lint.name, lint.severity, lint.description);
+        }
+        return Ok(());
+    }
+    let settings = settings()?;
+    let mut allowed = settings.list("lint.allow", None).unwrap_or_default();
+    allowed.extend(args.allowed);
+    check_allowed(&allowed)?;
+    let limits = Limits {
+        max_memory: settings
+            .size("lint.max-memory", None)
+            .map_or(DEFAULT_MAX_MEMORY, |size| size.as_u64()),
+        max_data_segment: settings
+            .size("lint.max-data-segment", None)
+            .map_or(DEFAULT_MAX_DATA_SEGMENT, |size| size.as_u64()),
+    };
+
+    let fleets: Vec<Fleet> = if args.fleets.is_empty() {
+        find_built_fleets(args.debug)?
+    } else {
+        args.fleets
+            .iter()
+            .map(|name| find_fleet_or_file(name, args.debug))
+            .collect::<anyhow::Result<_>>()?
+    };
+    if fleets.is_empty() {
+        anyhow::bail!(
+            "there are no built fleets to lint, build them with `cargo protologic build`"
+        );
+    }
+
+    let mut reports = Vec::new();
+    for fleet in fleets {
+        let bytes = std::fs::read(&fleet.path)
+            .with_context(|| format!("trying to read fleet {:?}", fleet.path))?;
+        let module = Module::parse(&bytes)
+            .with_context(|| format!("'{}' isn't a wasm module this can read", fleet.name))?;
+        reports.push(Report {
+            findings: lint_module(&module, limits, &allowed),
+            fleet: fleet.name,
+            path: fleet.path,
+        });
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            let mark = if report.findings.is_empty() {
+                '✓'
+            } else {
+                '✗'
+            };
+            say!("{mark} '{}' ({})", report.fleet, report.path.display());
+            for finding in &report.findings {
+                say!(
+                    "    {}[{}]: {}",
+                    finding.severity,
+                    finding.lint,
+                    finding.message
+                );
+                say!("      fix: {}", finding.fix);
+            }
+        }
+        if !allowed.is_empty() {
+            say!("Allowed: {}", allowed.join(", "));
+        }
+    }
+
+    let failed = reports
+        .iter()
+        .filter(|report| {
+            report
+                .findings
+                .iter()
+                .any(|finding| finding.severity == Severity::Error)
+        })
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} fleets have lint errors", reports.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_module, Limits, Module};
+
+    fn lints(wat: &str, allowed: &[&str]) -> anyhow::Result<Vec<&'static str>> {
+        let bytes = wat::parse_str(wat)?;
+        let module = Module::parse(&bytes)?;
+        let limits = Limits {
+            max_memory: 2 * 65536,
+            max_data_segment: 8,
+        };
+        let allowed: Vec<String> = allowed.iter().map(|name| name.to_string()).collect();
+        Ok(lint_module(&module, limits, &allowed)
+            .iter()
+            .map(|finding| finding.lint)
+            .collect())
+    }
+
+    #[test]
+    fn lints_find_fleet_mistakes() -> anyhow::Result<()> {
+        assert_eq!(
+            lints(
+                r#"(module
+                    (import "wasi_snapshot_preview1" "sched_yield" (func (result i32)))
+                    (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                    (memory (export "memory") 2)
+                    (data (i32.const 0) "small"))"#,
+                &[]
+            )?,
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            lints(
+                r#"(module
+                    (import "wasi_snapshot_preview1" "path_open" (func))
+                    (import "__wbindgen_placeholder__" "__wbindgen_describe" (func))
+                    (memory (export "memory") 3)
+                    (data (i32.const 0) "thread 'main' panicked at src/lib.rs"))"#,
+                &[]
+            )?,
+            [
+                "unsupported-wasi",
+                "foreign-import",
+                "no-sched-yield",
+                "memory-cap",
+                "large-data",
+                "panic-formatting"
+            ]
+        );
+        assert_eq!(
+            lints(
+                r#"(module (memory 1) (func $_ZN4core3fmt5write17h0123456789abcdefE))"#,
+                &["no-sched-yield"]
+            )?,
+            ["panic-formatting"]
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,6 @@
 use std::fs::DirEntry;
-use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command};
-use std::time::SystemTime;
+use std::process::{Child, Command, ExitCode};
 
 use anyhow::Context;
 use bytesize::ByteSize;
@@ -10,6 +8,82 @@ use clap::Parser;
 use serde::{Deserialize, Serialize};
 use wasm_opt::OptimizationOptions;
 
+use events::{emit, Event, LogFormat, Phase};
+use fleet::{extract_fleet_name, find_built_fleets};
+use manifest::{artifact_file_name, FleetManifest, ManifestEntry};
+use output::{say, shell_command};
+use run::RunArgs;
+use version::SDK_PACKAGES;
+
+mod aggregate;
+mod archive;
+mod battle_archive;
+mod bench;
+mod bench_opt;
+mod bracket;
+mod clean;
+mod compare;
+mod completions;
+mod config;
+mod crash;
+mod curl;
+mod disasm;
+mod doctor;
+mod elo;
+mod environment;
+mod events;
+mod fleet;
+mod fleet_lines;
+mod fleet_test;
+mod git;
+mod hash;
+mod history;
+mod init;
+mod install;
+mod interrupt;
+mod lint;
+mod manifest;
+mod matrix;
+mod naming;
+mod notify;
+mod open;
+mod outcome;
+mod output;
+mod preflight;
+mod priority;
+mod prune;
+mod ratings;
+mod reference;
+mod replay;
+mod replay_list;
+mod report;
+mod results;
+mod run;
+mod scaffold;
+mod self_update;
+mod series;
+mod serve;
+mod settings;
+mod sim;
+mod size_history;
+mod standings;
+mod stats;
+mod stats_csv;
+mod submission;
+mod submit;
+mod swiss;
+mod template;
+mod tournament;
+mod tournament_config;
+mod tournament_report;
+mod update;
+mod validate;
+mod verify_replay;
+mod version;
+mod wasm;
+mod wasm_diff;
+mod watch;
+
 /// You shouldn't see this! Run this tool like `cargo protologic`.
 #[derive(clap::Parser, Debug)]
 #[command(name = "cargo-protologic", bin_name = "cargo")]
@@ -22,8 +96,23 @@ struct CargoProtologic {
 #[derive(clap::Subcommand, Debug, Clone)]
 enum ProtologicCommand {
     /// A helper for creating Protologic fleets in rust!
+    ///
+    /// `cargo proto` is short for it, with a `cargo-proto` copy or link of this binary, or a cargo alias.
+    #[command(alias = "proto")]
+    Protologic(ProtologicArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct ProtologicArgs {
+    /// Print the commands that would be run, without running anything or writing any files.
+    #[arg(long, global = true, default_value = "false")]
+    dry_run: bool,
+    /// How to report progress. `ndjson` prints one JSON event per line on stdout, for scripts and
+    /// editors to follow along, and moves everything else to stderr.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
     #[command(subcommand)]
-    Protologic(Commands),
+    command: Commands,
 }
 
 #[derive(clap::Subcommand, Debug, Clone)]
@@ -40,122 +129,635 @@ enum Commands {
         debug: bool,
     },
 
+    /// Creates a new fleet crate in the workspace, ready to build and battle.
+    ///
+    /// Outside a cargo workspace, it starts a new workspace in the current directory.
+    New {
+        /// The fleet's package name, which is also the directory it goes in.
+        #[arg(required_unless_present = "list_templates")]
+        name: Option<String>,
+        /// The template to start from: a built-in one like `missile-boat`, or a git repository like `gh:someuser/fleet-template`. Files in it can use `{{name}}`, `{{crate_name}}` and `{{sdk_version}}`.
+        #[arg(long, default_value = template::DEFAULT_TEMPLATE)]
+        template: String,
+        /// List the templates to start from, instead of creating a fleet.
+        #[arg(long, default_value = "false", conflicts_with = "name")]
+        list_templates: bool,
+        /// The version of the Protologic SDK to depend on. Defaults to one matching your Protologic Release.
+        #[arg(long, value_name = "VERSION")]
+        sdk_version: Option<String>,
+    },
+
+    /// Sets up an existing workspace for cargo-protologic.
+    ///
+    /// Marks which packages are fleets in `[workspace.metadata.protologic]`, ignores built fleets and battle outputs in `.gitignore`, and checks the wasm target is installed. Whatever's already set up is left alone, so it's safe to run again.
+    Init {
+        /// A package that's a fleet. May be repeated! Defaults to the members that depend on the Protologic SDK.
+        #[arg(long = "fleet", value_name = "PACKAGE")]
+        fleets: Vec<String>,
+        /// The Protologic/Release checkout to save in your user config, as `config set protologic-path` does.
+        #[arg(long, value_name = "PATH")]
+        protologic_path: Option<PathBuf>,
+    },
+
     /// List all built fleets. If you see none, try building them!
-    List {},
+    List {
+        /// Also check that each fleet is a valid wasm module, as `run` does before battling.
+        #[arg(long, default_value = "false")]
+        check: bool,
+    },
 
-    /// Run battle between two fleets. The replay file will be put in your current directory. Requires your workspace to have exactly two fleets!
+    /// Run battle between two fleets. The replay file will be put in your current directory.
     ///
+    /// With more than two built fleets, pick them with `--fleet-a` and `--fleet-b` or from the interactive picker.
     /// Optionally can open the replay in the player.
-    Run {
-        /// The location of the Protologic/Release repo. Can specify as an environment variable for ease of use!
+    Run(Box<RunArgs>),
+
+    /// Battles two builds of a fleet against the same opponent on the same seeds, and says whether the candidate is better than the base.
+    ///
+    /// Each build is a wasm file or a git ref to build the fleet at, in a temporary checkout. The games the builds did differently in are listed with their replays, the ones the base did better in first.
+    Compare(Box<compare::CompareArgs>),
+
+    /// Battles every built fleet against every other, from both sides, and ranks them by points.
+    ///
+    /// A win is 3 points and a draw 1. Battles that fail are recorded as errors, and the tournament carries on.
+    Tournament(Box<tournament::TournamentArgs>),
+
+    /// Sums up every tournament run so far: standings across them all, with `--vs A B` how two fleets did against each other, or with `--elo` every fleet build's rating.
+    ///
+    /// Rebuilds of a fleet are told apart by their hash, like `glass_cannon@1a2b3c4d`, unless `--merge-versions` is given.
+    Standings(standings::StandingsArgs),
+
+    /// Opens a replay from an earlier battle in the player.
+    ///
+    /// With no replay given, it opens the newest one in the battle output directory.
+    Replay(replay::ReplayArgs),
+
+    /// Checks a replay reads all the way through, and prints its fleets, tick count and sim version.
+    ///
+    /// Exits with an error saying where it breaks when it doesn't, like a replay cut short by a download. `--repair` then saves what can be read of it to a new replay, ending after the last whole event.
+    VerifyReplay(verify_replay::VerifyReplayArgs),
+
+    /// Bundles a battle into one zip to share: its replay, result, logs, the exact fleet builds that battled, and what it was played with.
+    ///
+    /// With no replay given, it bundles the newest battle. `--extract` unpacks a bundle, checks its fleets are the builds that battled, and prints how to battle them again.
+    Archive(battle_archive::ArchiveArgs),
+
+    /// Lists past battles, newest first: their replays, and the battle history.
+    Replays {
+        /// The battle output directory to look in, if not the configured one.
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+        /// The battle history to read. Defaults to `battle_history.jsonl` in the fleet output directory.
+        #[arg(long, value_name = "PATH")]
+        history_file: Option<PathBuf>,
+        /// How many of the newest battles to list.
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+        /// Print the battles as JSON on stdout, for scripts.
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Serves the battle output directory over HTTP, with a page listing the replays to download, for sharing them on a LAN.
+    ///
+    /// It only listens on localhost unless given `--bind`, like `--bind 0.0.0.0` for the whole network, and prints the URLs to share.
+    Serve(serve::ServeArgs),
+
+    /// Lists the battles in the battle history that match, newest first, with `--fleet` its record over them.
+    ///
+    /// `--fleet`, `--vs` and `--since` narrow it down, like `--fleet iron_armada --vs glass_cannon --since 2024-06-01`. Lines of the log that can't be read, like one cut short by a crash, are skipped with a warning.
+    History(history::HistoryArgs),
+
+    /// Sums up battles from their replays: ships lost and destroyed, shots fired, hit rates, damage and kills, per fleet.
+    ///
+    /// With `--aggregate`, many battles are merged into totals and averages per fleet.
+    Stats(stats::StatsArgs),
+
+    /// Reports on how fleets have changed over their builds, like `report sizes` for how their optimized size has gone.
+    ///
+    /// `build` records each fleet's size, hash and commit in `size_history.jsonl` in the fleet output directory as it builds it.
+    Report {
+        #[command(subcommand)]
+        command: size_history::ReportCommand,
+    },
+
+    /// Checks everything building and battling fleets needs, with a fix for each problem.
+    ///
+    /// Exits with an error if a required check fails, so it can be the first step in CI.
+    Doctor {
+        /// The location of the Protologic/Release repo to check, as `run` takes it.
         #[arg(long, env)]
-        protologic_path: PathBuf,
-        /// Whether to set the `--debug` flag in Protologic.
-        #[arg(short, long, default_value = "false")]
-        debug: bool,
-        /// Do you want the replay opened in the player?
-        #[arg(short, long, default_value = "false")]
-        player: bool,
+        protologic_path: Option<PathBuf>,
+    },
+
+    /// Prints the SHA-256 of built fleets, or of files, in `sha256sum`'s format so standard tools can check it.
+    ///
+    /// With no fleets given, it hashes every built fleet. `--algo blake3` prints BLAKE3 digests instead, as `b3sum` does, and `--check FILE` checks the files listed in FILE as `sha256sum -c` does.
+    Hash(hash::HashArgs),
+
+    /// Checks fleet wasm files the way the sim will load them, without building anything.
+    ///
+    /// Each file is validated with the sim's wasm features, and checked for the exports it needs, imports the sim doesn't provide, the Asyncify transformation and its declared memory. Exits with an error if any of them have problems.
+    Validate {
+        /// The fleets to check. Directories are checked for the `.wasm` files in them.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// The biggest a fleet can be, like `2MiB`, as a tournament caps them. Defaults to the `package.max-size` setting.
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<ByteSize>,
+        /// Print a report of each file as JSON on stdout, for scripts.
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Checks built fleets for mistakes that show in their wasm, like WASI imports the sim doesn't provide, never yielding, or too much memory, with a fix for each.
+    ///
+    /// Exits with an error if a fleet has an error, and warnings don't fail it. Lints can be turned off with `--allow LINT` or the `lint.allow` setting, and `--list` lists them.
+    Lint(lint::LintArgs),
+
+    /// Measures the fuel a fleet's ticks take, without a battle. Give two fleets to compare them.
+    ///
+    /// The fleet runs in wasmtime with fuel metering, and the sim's functions stubbed to return zeros, or what `--inputs` says. Exits with an error if a fleet traps or gets stuck.
+    Bench(bench::BenchArgs),
+
+    /// Tests a fleet's logic without the sim: its ticks run in wasmtime, with the sim's functions returning what `--scenario` scripts, checking the calls it makes.
+    ///
+    /// Calls to the sim's functions that don't return anything, like `engine_set_throttle`, are recorded, and can be written to `--trace`. Exits with an error if an expectation of the scenario doesn't hold, or the fleet traps, gets stuck or imports something the sim doesn't have.
+    Test(fleet_test::TestArgs),
+
+    /// Builds a fleet once and optimizes it with each wasm-opt level, with and without converging, comparing their size, optimization time and fuel per tick.
+    ///
+    /// The builds are left in `target/protologic_fleets/bench-opt` to try out, replaced on the next run.
+    BenchOpt(bench_opt::BenchOptArgs),
+
+    /// Compares two fleet wasm files: each section's size, the functions added, removed or resized, and the imports and exports.
+    ///
+    /// Any two wasm files work, like a build against an archived submission. Functions are only compared when both have a name section.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// How many of the functions that changed the most to list.
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+        /// Print the whole diff as JSON on stdout, for scripts.
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Prints a built fleet in the wasm text format, or writes it to a file with `-o`.
+    ///
+    /// Pick one function with `--function`, or leave out every function's body with `--skeleton`, to keep a big fleet's text navigable.
+    Disasm(disasm::DisasmArgs),
+
+    /// Builds a fleet and bundles it up for a tournament submission, as `submission_<name>_<version>.zip`.
+    ///
+    /// The bundle has the fleet's wasm, a `fleet.toml` saying what it is and who made it, and its README if it has one. The fleet is checked as `validate` checks it, and its SHA-256 is printed for the submission form.
+    Package(submission::PackageArgs),
+
+    /// Builds a fleet, bundles it up as `package` does, and uploads it to a tournament server's `--server` URL.
+    ///
+    /// The bundle goes up as multipart form data with its `fleet.toml` fields and SHA-256, with the token from `--token` or PROTOLOGIC_SUBMIT_TOKEN. Exits with code 5 if the server rejects it, and 6 if it doesn't take the token. `--dry-run` does everything but the upload.
+    Submit(submit::SubmitArgs),
+
+    /// Deletes what this tool made: built fleets, replays or the battle history and ratings.
+    ///
+    /// Pick what with the flags, which can be combined. Without any, it asks on a terminal, and refuses otherwise. Only files named and placed like this tool makes them are deleted.
+    Clean {
+        /// Delete the optimized fleets, their manifests, the feature matrix's variants and `bench-opt`'s builds.
+        #[arg(long, default_value = "false")]
+        fleets: bool,
+        /// Delete the replays, with their results, logs and archived fleets, and the tournament results, from the battle output directory.
+        #[arg(long, default_value = "false")]
+        replays: bool,
+        /// Delete the battle history and the Elo ratings.
+        #[arg(long, default_value = "false")]
+        history: bool,
+        /// Delete all of the above.
+        #[arg(long, default_value = "false")]
+        all: bool,
+        /// The battle output directory to delete replays from, if not the configured one.
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+        /// The battle history to delete, if not the configured one. Defaults to the `run.history-file` setting, or `battle_history.jsonl` in the fleet output directory.
+        #[arg(long, env = "PROTOLOGIC_HISTORY_FILE", value_name = "PATH")]
+        history_file: Option<PathBuf>,
+    },
+
+    /// Deletes battles from the battle output directory older than `--older-than`: their replays, results and logs, and the crash directories of sim crashes.
+    ///
+    /// `--keep-labeled` keeps battles with a label. Only files named like this tool names them are deleted, and only in the battle output directory.
+    Prune(prune::PruneArgs),
+
+    /// Downloads a Protologic Release and makes it the one `run` uses.
+    ///
+    /// Each version goes in a directory of its own, so several can be installed side by side. Downloads are checked against the published checksum, and an interrupted one picks up where it left off the next time.
+    Install {
+        /// The release to install, by its tag, if not the latest.
+        #[arg(long, value_name = "TAG")]
+        version: Option<String>,
+        /// Show the published versions and which are installed, instead of installing.
+        #[arg(long, default_value = "false", conflicts_with = "version")]
+        list: bool,
+        /// Where to install Releases, if not your user data directory.
+        #[arg(long, value_name = "DIR")]
+        path: Option<PathBuf>,
+    },
+
+    /// Downloads reference fleets to battle, to measure progress against the same opponents: `run --vs @reference/tutorial-bot`.
+    ///
+    /// The fleets come from an index of them, the community's unless `--index` says otherwise, and are checked against the SHA-256 it lists before they're cached. `@reference/<name>` picks a cached one anywhere a fleet name goes.
+    FetchReference(reference::FetchReferenceArgs),
+
+    /// Checks for a newer Protologic Release than yours, and installs it and switches to it.
+    ///
+    /// Installed Releases know their version, while for a checkout of your own it's read from its VERSION file. Not being able to reach GitHub is only a warning.
+    Update {
+        /// Only say whether there's a newer Release, without installing it.
+        #[arg(long, default_value = "false")]
+        check: bool,
+        /// The location of the Protologic/Release repo to compare, as `run` takes it.
+        #[arg(long, env)]
+        protologic_path: Option<PathBuf>,
+        /// Where to install Releases, if not next to the installed one or your user data directory.
+        #[arg(long, value_name = "DIR")]
+        path: Option<PathBuf>,
+    },
+
+    /// Checks crates.io for a newer cargo-protologic, shows what's changed since yours, and installs it with `cargo install` once you say so.
+    ///
+    /// With `--check`, it only says, and exits with code 4 when there's a newer version, for shell prompts to pick up. Not being able to reach crates.io is only a warning.
+    SelfUpdate(self_update::SelfUpdateArgs),
+
+    /// Opens the fleet output directory, the battle output directory or the Protologic Release in the file manager, or the config in your editor.
+    ///
+    /// Each is found the way the subcommands that use it find it, so what opens is what they'd use.
+    Open(open::OpenArgs),
+
+    /// Prints everything this tool would use, resolved as the subcommands resolve it, and where each came from: the workspace, the fleet and battle output directories, the Release, sim and player, the config files and the settings.
+    ///
+    /// What can't be resolved is shown with why in its place, and the rest still printed.
+    Env(environment::EnvArgs),
+
+    /// Reads and changes settings, which stand in for flags: in your user config, for every workspace, or in the workspace's `protologic.toml`.
+    ///
+    /// Flags win over the workspace's settings, and those over your user config's.
+    Config {
+        #[command(subcommand)]
+        command: settings::ConfigCommand,
+    },
+
+    /// Prints a completion script for your shell, like `cargo protologic completions bash > ~/.local/share/bash-completion/completions/cargo`.
+    ///
+    /// The script completes `cargo protologic` and its subcommands, and is registered for `cargo`. In bash, zsh and PowerShell that takes over from cargo's own completions, while fish adds to them.
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Prints the fleet names or replays there are, for the completion scripts to complete from.
+    #[command(name = "completion-candidates", hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        candidates: completions::Candidates,
     },
 }
 
-fn main() -> anyhow::Result<()> {
+/// An error that exits with a specific code, so scripts and CI can tell failures apart.
+#[derive(Debug)]
+pub struct ExitError {
+    pub code: u8,
+    pub message: String,
+}
+
+impl ExitError {
+    /// A check like `--assert-winrate` didn't pass.
+    pub const ASSERTION_FAILED: u8 = 3;
+    /// `self-update --check` found a newer version.
+    pub const UPDATE_AVAILABLE: u8 = 4;
+    /// The tournament server turned down a `submit`.
+    pub const SUBMISSION_REJECTED: u8 = 5;
+    /// The tournament server didn't take the `submit` token.
+    pub const UNAUTHORIZED: u8 = 6;
+
+    pub fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ExitError {}
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            emit(Event::Error {
+                message: format!("{err:#}"),
+            });
+            eprintln!("Error: {err:?}");
+            err.downcast_ref::<ExitError>()
+                .map_or(ExitCode::FAILURE, |err| ExitCode::from(err.code))
+        }
+    }
+}
+
+fn try_main() -> anyhow::Result<()> {
     let CargoProtologic {
-        command: ProtologicCommand::Protologic(command),
+        command:
+            ProtologicCommand::Protologic(ProtologicArgs {
+                dry_run,
+                log_format,
+                command,
+            }),
     } = CargoProtologic::parse();
-    println!("{command:?}");
-
+    // Before anything else can print, since it ends up in the middle of someone's prompt
+    if let Commands::Complete { candidates } = command {
+        completions::complete(candidates);
+        return Ok(());
+    }
+    if dry_run {
+        output::set_dry_run();
+    }
+    if log_format == LogFormat::Ndjson {
+        output::set_ndjson();
+    }
+    if let Commands::Run(args) = &command {
+        if args.print_replay_path {
+            if output::ndjson() {
+                anyhow::bail!("--print-replay-path can't share stdout with `--log-format ndjson`, use the replay path from the battle-finished event instead");
+            }
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Replays { json: true, .. } = &command {
+        output::reserve_stdout();
+    }
+    if let Commands::History(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Completions { .. } = &command {
+        output::reserve_stdout();
+    }
+    if let Commands::Config {
+        command: settings::ConfigCommand::Get { .. } | settings::ConfigCommand::List { .. },
+    } = &command
+    {
+        output::reserve_stdout();
+    }
+    if let Commands::Diff { json: true, .. } = &command {
+        output::reserve_stdout();
+    }
+    if let Commands::Disasm(args) = &command {
+        if args.output.is_none() {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Env(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Hash(_) = &command {
+        output::reserve_stdout();
+    }
+    if let Commands::Validate { json: true, .. } = &command {
+        output::reserve_stdout();
+    }
+    if let Commands::Lint(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Tournament(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Standings(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Bench(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::BenchOpt(args) = &command {
+        if args.json {
+            output::reserve_stdout();
+        }
+    }
+    if let Commands::Report {
+        command: size_history::ReportCommand::Sizes { json: true, .. },
+    } = &command
+    {
+        output::reserve_stdout();
+    }
+    if let Commands::Stats(args) = &command {
+        if args.format() != stats::StatsFormat::Table {
+            output::reserve_stdout();
+        }
+    }
     match command {
         Commands::Build { package, debug } => {
-            println!("Building packages...");
-            for package in package.map_or_else(list_workspace_fleets, Result::Ok)? {
-                build(package, debug)?
-                    .wait()
-                    .context("trying to wait until the `cargo build` execution has finished")?;
-            }
-
-            let is_wasm_output = |entry: &DirEntry| {
-                entry
-                    .path()
-                    .extension()
-                    .and_then(|ext| Some(ext.to_str()? == "wasm"))
-                    .unwrap_or(false)
-            };
-
-            let wasm_output = std::fs::read_dir(cargo_output_base_path(debug)?)
-                .context("Can't find wasm output from build")?
-                .filter(|entry| entry.as_ref().is_ok_and(is_wasm_output))
-                .collect::<Vec<_>>();
-
-            if wasm_output.is_empty() {
-                println!("No wasm output found. Your build didn't produce any .wasm files!");
-            } else {
-                println!("Optimizing wasm outputs...");
-                for entry in wasm_output {
-                    optimize_wasm(entry?.path(), debug)?;
+            if dry_run {
+                say!("Would build packages with:");
+                for package in package.map_or_else(list_workspace_fleets, Result::Ok)? {
+                    say!("  {}", shell_command(&build_command(&package, &[], debug)?));
                 }
-                println!("Done optimizing!");
+                say!(
+                    "Would optimize each .wasm in {:?} into {:?}, with wasm-opt options: {:#?}",
+                    cargo_output_base_path(debug)?,
+                    fleet_output_path(debug)?,
+                    make_wasm_opt(debug)?
+                );
+                return Ok(());
             }
+
+            let packages = package.map_or_else(list_workspace_fleets, Result::Ok)?;
+            build_fleets(&packages, debug)?;
         }
-        Commands::List {} => {
-            println!("Listing built fleets...");
+        Commands::List { check } => {
+            say!("Listing built fleets...");
 
-            for entry in find_built_fleets()? {
-                println!("Found fleet: {entry:?}");
+            let mut invalid = 0;
+            for (debug, fleet) in find_built_fleets(false)?
+                .into_iter()
+                .map(|fleet| (false, fleet))
+                .chain(
+                    find_built_fleets(true)?
+                        .into_iter()
+                        .map(|fleet| (true, fleet)),
+                )
+            {
+                let kind = if debug { "debug fleet" } else { "fleet" };
+                match check.then(|| fleet.validate()) {
+                    Some(Err(err)) => {
+                        invalid += 1;
+                        say!(
+                            "Found {kind}: {:?} (invalid: {})",
+                            fleet.path,
+                            err.root_cause()
+                        );
+                    }
+                    Some(Ok(())) => say!("Found {kind}: {:?} (valid)", fleet.path),
+                    None => say!("Found {kind}: {:?}", fleet.path),
+                }
+            }
+            if invalid > 0 {
+                anyhow::bail!("{invalid} of the fleets aren't valid wasm modules, try rebuilding them with `cargo protologic build`");
             }
         }
-        Commands::Run {
+        Commands::New {
+            list_templates: true,
+            ..
+        } => template::list_templates()?,
+        Commands::New {
+            name,
+            template,
+            sdk_version,
+            ..
+        } => {
+            let name = name.context("`new` needs a name for the fleet")?;
+            scaffold::new_fleet(&name, &template, sdk_version)?;
+        }
+        Commands::Init {
+            fleets,
             protologic_path,
-            debug,
-            player,
+        } => init::init(fleets, protologic_path)?,
+        Commands::Run(args) => run::run(*args)?,
+        Commands::Compare(args) => compare::compare(*args)?,
+        Commands::Tournament(args) => tournament::tournament(*args)?,
+        Commands::Standings(args) => standings::show_standings(args)?,
+        Commands::Replay(args) => replay::replay(args)?,
+        Commands::VerifyReplay(args) => verify_replay::verify_replay(args)?,
+        Commands::Archive(args) => battle_archive::archive(args)?,
+        Commands::Replays {
+            output_dir,
+            history_file,
+            limit,
+            json,
+        } => replay_list::list_replays(output_dir, history_file, limit, json)?,
+        Commands::Serve(args) => serve::serve(args)?,
+        Commands::History(args) => history::history(args)?,
+        Commands::Stats(args) => stats::stats(args)?,
+        Commands::Report { command } => size_history::report(command)?,
+        Commands::Doctor { protologic_path } => doctor::doctor(protologic_path)?,
+        Commands::Hash(args) => hash::hash(args)?,
+        Commands::Validate {
+            paths,
+            max_size,
+            json,
+        } => validate::validate(paths, max_size, json)?,
+        Commands::Lint(args) => lint::lint(args)?,
+        Commands::Bench(args) => bench::bench(args)?,
+        Commands::BenchOpt(args) => bench_opt::bench_opt(args)?,
+        Commands::Test(args) => fleet_test::fleet_test(args)?,
+        Commands::Diff {
+            old,
+            new,
+            limit,
+            json,
+        } => wasm_diff::wasm_diff(old, new, limit, json)?,
+        Commands::Disasm(args) => disasm::disasm(args)?,
+        Commands::Package(args) => submission::package(args)?,
+        Commands::Submit(args) => submit::submit(args)?,
+        Commands::Clean {
+            fleets,
+            replays,
+            history,
+            all,
+            output_dir,
+            history_file,
         } => {
-            let fleets = find_built_fleets()?;
-            let [fleet1, fleet2] = fleets
-                .first_chunk()
-                .context("tried to get find fleets 1 and 2")?;
-
-            let battle_output = battle_output_path(fleet1, fleet2)?;
-
-            println!("Starting the protologic sim...");
-
-            std::process::Command::new(protologic_sim_path(&protologic_path))
-                .arg("--fleets")
-                .args([fleet1.path(), fleet2.path()])
-                .arg("--debug")
-                .arg(debug.to_string())
-                .arg("--output")
-                .arg(battle_output.clone())
-                .spawn()
-                .context("trying to run sim on fleets")?
-                .wait()
-                .context("trying to wait until the protologic sim has finished running")?;
-
-            println!("Protologic sim complete!");
-
-            if player {
-                println!("Starting the protologic player! The command will exit now.");
-
-                let mut command =
-                    std::process::Command::new(protologic_player_path(&protologic_path)?);
-                command.arg(battle_output.with_extension("json.deflate"));
-                println!("Command to open player: {:?}", command);
-
-                command
-                    .spawn()
-                    .context("trying to open protologic player from sim output")?;
-            }
+            let targets = clean::CleanTarget::ALL
+                .into_iter()
+                .zip([fleets, replays, history])
+                .filter(|&(_, picked)| picked || all)
+                .map(|(target, _)| target)
+                .collect();
+            clean::clean(targets, output_dir, history_file)?;
         }
+        Commands::Prune(args) => prune::prune(args)?,
+        Commands::Install {
+            version,
+            list,
+            path,
+        } => install::install(path, version, list)?,
+        Commands::FetchReference(args) => reference::fetch_reference(args)?,
+        Commands::Update {
+            check,
+            protologic_path,
+            path,
+        } => update::update(check, protologic_path, path)?,
+        Commands::SelfUpdate(args) => self_update::self_update(args)?,
+        Commands::Open(args) => open::open(args)?,
+        Commands::Env(args) => environment::env(args)?,
+        Commands::Config { command } => settings::config(command)?,
+        Commands::Completions { shell } => completions::completions(shell)?,
+        Commands::Complete { .. } => unreachable!("completions are handled before anything else"),
     }
 
     Ok(())
 }
 
 const WASI_TARGET: &str = "wasm32-wasi";
+/// Where optimized fleets go, relative to the workspace we're run in.
+const FLEET_OUTPUT_DIR: &str = "./target/protologic_fleets/";
+/// The directory in [`FLEET_OUTPUT_DIR`] debug builds go in.
+const DEBUG_FLEET_DIR: &str = "debug";
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ParsedMetadata {
+    workspace_members: Vec<String>,
     workspace_default_members: Vec<String>,
     target_directory: PathBuf,
+    workspace_root: PathBuf,
+    /// The workspace's `[workspace.metadata]`, where our settings live.
+    #[serde(default)]
+    metadata: Option<config::WorkspaceMetadata>,
+    packages: Vec<MetadataPackage>,
+    /// The dependency graph, which `--no-deps` leaves out.
+    #[serde(default)]
+    resolve: Option<MetadataResolve>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MetadataResolve {
+    nodes: Vec<MetadataNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MetadataNode {
+    id: String,
+    /// IDs of the package's direct dependencies.
+    dependencies: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+    manifest_path: PathBuf,
+    #[serde(default)]
+    authors: Vec<String>,
+    /// The package's features, and what each turns on.
+    #[serde(default)]
+    features: std::collections::BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    metadata: Option<config::PackageMetadata>,
 }
 
 fn cargo_metadata() -> anyhow::Result<ParsedMetadata> {
@@ -169,69 +771,263 @@ fn cargo_metadata() -> anyhow::Result<ParsedMetadata> {
     serde_json::from_slice(&output.stdout).context("trying to parse `cargo metadata` output")
 }
 
+impl ParsedMetadata {
+    /// The workspace package `name` picks, by its package name or its fleet's file name.
+    fn workspace_package(&self, name: &str) -> anyhow::Result<&MetadataPackage> {
+        let members = self
+            .packages
+            .iter()
+            .filter(|package| self.workspace_members.contains(&package.id));
+        members
+            .clone()
+            .find(|package| package.name == name || package.name.replace('-', "_") == name)
+            .with_context(|| {
+                let names: Vec<&str> = members.map(|package| package.name.as_str()).collect();
+                format!(
+                    "no workspace package named '{name}', the workspace packages are: {}",
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// The fleets configured with `fleets` in `[workspace.metadata.protologic]`, if any.
+    fn configured_fleets(&self) -> Option<&Vec<String>> {
+        self.metadata.as_ref()?.protologic.as_ref()?.fleets.as_ref()
+    }
+
+    /// IDs of the workspace packages that are fleets: the configured `fleets`, or the
+    /// `default-members` of the workspace without them.
+    fn fleet_ids(&self) -> Vec<String> {
+        let Some(fleets) = self.configured_fleets() else {
+            return self.workspace_default_members.clone();
+        };
+        self.packages
+            .iter()
+            .filter(|package| {
+                self.workspace_members.contains(&package.id) && fleets.contains(&package.name)
+            })
+            .map(|package| package.id.clone())
+            .collect()
+    }
+}
+
 /// Lists the fleets in the workspace.
 ///
-/// Currently it provides all `default-members` of the cargo workspace. The intended workflow is
-/// to make non-fleet packages (i.e. helpers) non-default members.
+/// These are the `fleets` configured in `[workspace.metadata.protologic]`, as `init` sets up, or
+/// otherwise all `default-members` of the cargo workspace. Without the config, the intended
+/// workflow is to make non-fleet packages (i.e. helpers) non-default members.
 fn list_workspace_fleets() -> anyhow::Result<Vec<String>> {
     let metadata = cargo_metadata()?;
-    println!("Metadata: {metadata:?}");
+    if let Some(fleets) = metadata.configured_fleets() {
+        for fleet in fleets {
+            let member = metadata.packages.iter().any(|package| {
+                package.name == *fleet && metadata.workspace_members.contains(&package.id)
+            });
+            if !member {
+                say!("Warning: '{fleet}' is configured as a fleet, but isn't a workspace member");
+            }
+        }
+    }
+
+    let fleets = metadata.fleet_ids();
+    say!("Workspace fleets: {fleets:?}");
+    Ok(fleets)
+}
+
+/// Builds the packages as fleets, optimizing their wasm into the fleet output directory.
+fn build_fleets(packages: &[String], debug: bool) -> anyhow::Result<()> {
+    say!("Building packages...");
+    emit(Event::PhaseStarted {
+        phase: Phase::Build,
+    });
+    for package in packages {
+        let status = build(package, &[], debug)?
+            .wait()
+            .context("trying to wait until the `cargo build` execution has finished")?;
+        if !status.success() {
+            anyhow::bail!("cargo couldn't build '{package}', it exited with {status}");
+        }
+        emit(Event::PackageBuilt {
+            package: package.clone(),
+        });
+    }
+
+    let is_wasm_output = |entry: &DirEntry| {
+        entry
+            .path()
+            .extension()
+            .and_then(|ext| Some(ext.to_str()? == "wasm"))
+            .unwrap_or(false)
+    };
+
+    let wasm_output = std::fs::read_dir(cargo_output_base_path(debug)?)
+        .context("Can't find wasm output from build")?
+        .filter(|entry| entry.as_ref().is_ok_and(is_wasm_output))
+        .collect::<Vec<_>>();
+
+    if wasm_output.is_empty() {
+        say!("No wasm output found. Your build didn't produce any .wasm files!");
+    } else {
+        say!("Optimizing wasm outputs...");
+        emit(Event::PhaseStarted {
+            phase: Phase::Optimize,
+        });
+        let mut optimized = Vec::new();
+        for entry in wasm_output {
+            let input_path = entry?.path();
+            let output_path = wasm_opt_output_path(
+                input_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .expect("Input path must be a wasm file!"),
+                debug,
+            )?;
+            optimize_wasm(&input_path, &output_path, debug)?;
+            optimized.push(output_path);
+        }
+        say!("Done optimizing!");
+        update_manifest(packages, debug)?;
+        size_history::record_sizes(&fleet_output_path(debug)?, &optimized)?;
+    }
+
+    Ok(())
+}
+
+/// Records the packages just built in the fleet manifest, along with their display names.
+fn update_manifest(packages: &[String], debug: bool) -> anyhow::Result<()> {
+    let metadata = cargo_metadata()?;
+    let dir = fleet_output_path(debug)?;
+    let mut manifest = FleetManifest::read(&dir)?;
+
+    // Workspace members come as package IDs, while `--package` takes names
+    for package in packages {
+        let Some(package) = metadata
+            .packages
+            .iter()
+            .find(|metadata| metadata.id == *package || metadata.name == *package)
+        else {
+            continue;
+        };
+        manifest.fleets.insert(
+            artifact_file_name(&package.name),
+            manifest_entry(&metadata, package),
+        );
+    }
 
-    Ok(metadata.workspace_default_members)
+    manifest.write(&dir)
 }
 
-fn find_built_fleets() -> anyhow::Result<Vec<DirEntry>> {
-    std::fs::read_dir(fleet_output_base_path()?)
-        .context("trying to list fleet output directory")?
-        .collect::<io::Result<Vec<DirEntry>>>()
-        .context("trying to collect fleets in output directory")
+/// What the fleet manifest records about a fleet built from `package`.
+fn manifest_entry(metadata: &ParsedMetadata, package: &MetadataPackage) -> ManifestEntry {
+    let display_name = package
+        .metadata
+        .clone()
+        .and_then(|metadata| metadata.protologic?.name);
+    ManifestEntry {
+        package: package.name.clone(),
+        display_name,
+        version: Some(package.version.clone()),
+        sdk_version: sdk_version(metadata, &package.id),
+    }
 }
 
-fn build(package: String, debug: bool) -> anyhow::Result<Child> {
+/// The version of the Protologic SDK the package is built against, directly or through another
+/// dependency.
+fn sdk_version(metadata: &ParsedMetadata, package_id: &str) -> Option<String> {
+    let nodes = &metadata.resolve.as_ref()?.nodes;
+    let mut seen = std::collections::BTreeSet::new();
+    let mut queue = std::collections::VecDeque::from([package_id]);
+    while let Some(id) = queue.pop_front() {
+        let Some(node) = nodes.iter().find(|node| node.id == id) else {
+            continue;
+        };
+        for dependency in &node.dependencies {
+            if !seen.insert(dependency.as_str()) {
+                continue;
+            }
+            let package = metadata
+                .packages
+                .iter()
+                .find(|package| package.id == *dependency);
+            if let Some(package) =
+                package.filter(|package| SDK_PACKAGES.contains(&package.name.as_str()))
+            {
+                return Some(package.version.clone());
+            }
+            queue.push_back(dependency);
+        }
+    }
+
+    None
+}
+
+fn build(package: &str, features: &[String], debug: bool) -> anyhow::Result<Child> {
+    let mut cargo = build_command(package, features, debug)?;
+    if output::stdout_reserved() {
+        cargo.stdout(std::io::stderr());
+    }
+    cargo.spawn().context("trying to build packages with cargo")
+}
+
+/// The cargo command to build `package` as a fleet, with `features` and the `build.features`
+/// setting's on top of its defaults.
+fn build_command(package: &str, features: &[String], debug: bool) -> anyhow::Result<Command> {
+    let mut features = features.to_vec();
+    for feature in settings::settings()?
+        .list("build.features", None)
+        .unwrap_or_default()
+    {
+        if !features.contains(&feature) {
+            features.push(feature);
+        }
+    }
+
     let mut cargo = Command::new("cargo");
     cargo
         // Using `rustc` instead of `build` so we can pass `--crate-type`
         .arg("rustc")
-        .args(["-p", &package])
+        .args(["-p", package])
         // This is needed for rustc to produce a .wasm artifact
         .args(["--crate-type", "cdylib"])
         .args(["--target", WASI_TARGET]);
 
+    if !features.is_empty() {
+        cargo.args(["--features", &features.join(",")]);
+    }
     if !debug {
         cargo.arg("--release");
     }
 
-    cargo.spawn().context("trying to build packages with cargo")
+    Ok(cargo)
 }
 
-fn optimize_wasm(input_path: impl AsRef<Path>, debug: bool) -> anyhow::Result<()> {
+fn optimize_wasm(input_path: &Path, output_path: &Path, debug: bool) -> anyhow::Result<()> {
     fn size_from_fs(path: impl AsRef<Path>) -> anyhow::Result<u64> {
         std::fs::metadata(path)
             .context("trying to access path to query size")
             .map(|m| m.len())
     }
 
-    let input_size = size_from_fs(&input_path)?;
-
-    let wasm_file_name = input_path
-        .as_ref()
-        .file_name()
-        .and_then(|name| name.to_str())
-        .expect("Input path must be a wasm file!");
+    let input_size = size_from_fs(input_path)?;
 
-    let output_path = wasm_opt_output_path(wasm_file_name)?;
-    make_wasm_opt(debug)
-        .run(&input_path, &output_path)
+    make_wasm_opt(debug)?
+        .run(input_path, output_path)
         .context("Error optimizing wasm binary")?;
 
-    let output_size = size_from_fs(&output_path)?;
+    let output_size = size_from_fs(output_path)?;
 
-    let fleet_name = extract_fleet_name(&input_path)?;
-    println!(
+    let fleet_name = extract_fleet_name(output_path)?;
+    say!(
         "[Optimizing wasm] Fleet '{fleet_name}' optimized {} -> {}",
         ByteSize::b(input_size),
         ByteSize::b(output_size)
     );
+    emit(Event::OptFinished {
+        fleet: fleet_name,
+        input_bytes: input_size,
+        output_bytes: output_size,
+        path: output_path.to_owned(),
+    });
 
     Ok(())
 }
@@ -244,60 +1040,57 @@ fn cargo_output_base_path(debug: bool) -> anyhow::Result<PathBuf> {
         .join(format!("./{WASI_TARGET}/{profile}/")))
 }
 
-fn wasm_opt_output_path(input_file_name: impl AsRef<str>) -> anyhow::Result<PathBuf> {
-    Ok(fleet_output_base_path()?.join(input_file_name.as_ref()))
+fn wasm_opt_output_path(input_file_name: impl AsRef<str>, debug: bool) -> anyhow::Result<PathBuf> {
+    Ok(fleet_output_path(debug)?.join(input_file_name.as_ref()))
 }
 
-fn fleet_output_base_path() -> anyhow::Result<PathBuf> {
-    let path = PathBuf::from("./target/protologic_fleets/");
+/// Where built fleets of a profile go. Debug builds get their own directory, so they're never
+/// mistaken for release builds.
+fn fleet_output_path(debug: bool) -> anyhow::Result<PathBuf> {
+    let path = fleet_output_base_path()?;
+    if !debug {
+        return Ok(path);
+    }
 
-    if !path.exists() {
+    let path = path.join(DEBUG_FLEET_DIR);
+    if !path.exists() && !output::dry_run() {
         std::fs::create_dir(&path)
-            .with_context(|| format!("trying to create fleet output path: {path:?}",))?;
+            .with_context(|| format!("trying to create debug fleet output path: {path:?}",))?;
     }
 
     Ok(path)
 }
 
-fn protologic_sim_path(protologic_path: &Path) -> PathBuf {
-    #[cfg(target_os = "windows")]
-    {
-        protologic_path.join("Sim/Windows/Protologic.Terminal.exe")
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        protologic_path
-            .as_path()
-            .join("Sim/Linux/Protologic.Terminal")
-    }
-}
+fn fleet_output_base_path() -> anyhow::Result<PathBuf> {
+    let path = PathBuf::from(FLEET_OUTPUT_DIR);
 
-fn protologic_player_path(protologic_path: &Path) -> anyhow::Result<PathBuf> {
-    #[cfg(target_os = "windows")]
-    {
-        Ok(protologic_path.join("Player/Windows/PROTOLOGIC.exe"))
+    if !path.exists() && !output::dry_run() {
+        // A fresh workspace doesn't have a `target` directory yet
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("trying to create fleet output path: {path:?}",))?;
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        // Although this doesn't currently exist, it's a reasonably guess of what the path will be
-        let path = protologic_path.join("Player/Linux/PROTOLOGIC");
-        if !path.exists() {
-            anyhow::bail!("Can't find Protologic player for Linux! No such player exists as of 2024-02-08, so that may be why. Go bug Martin :)")
-        }
-
-        Ok(path)
-    }
+    Ok(path)
 }
 
-fn make_wasm_opt(debug: bool) -> OptimizationOptions {
-    let mut opt_options = if debug {
+/// The wasm-opt options fleets are optimized with: none for debug builds, and otherwise the
+/// `wasm-opt.level` setting's level, `O4` by default.
+fn make_wasm_opt(debug: bool) -> anyhow::Result<OptimizationOptions> {
+    let opt_options = if debug {
         wasm_opt::OptimizationOptions::new_opt_level_0()
     } else {
-        wasm_opt::OptimizationOptions::new_opt_level_4()
+        settings::settings()?
+            .text("wasm-opt.level", None)
+            .and_then(|level| bench_opt::Level::from_name(&level))
+            .unwrap_or(bench_opt::Level::O4)
+            .options()
     };
+    Ok(with_fleet_passes(opt_options, debug))
+}
 
+/// Adds what every fleet needs to some wasm-opt options, whatever they optimize for: the features
+/// fleets use, and Asyncify so the sim can pause them at `sched_yield`.
+fn with_fleet_passes(mut opt_options: OptimizationOptions, debug: bool) -> OptimizationOptions {
     if debug {
         opt_options.debug_info(true);
     } else {
@@ -315,45 +1108,28 @@ fn make_wasm_opt(debug: bool) -> OptimizationOptions {
     opt_options
 }
 
-/// Takes the path to a fleet, extracting out the name of the fleet the correct way
-fn extract_fleet_name(fleet_path: impl AsRef<Path>) -> anyhow::Result<String> {
-    fleet_path
-        .as_ref()
-        // drop the `.wasm`
-        .with_extension("")
-        .file_name()
-        .context("fleet name wouldn't be found in fleet path. Try again?")?
-        .to_str()
-        .context("you need to name your fleet valid unicode!")
-        .map(ToOwned::to_owned)
-}
-
-fn battle_output_path(fleet1: &DirEntry, fleet2: &DirEntry) -> anyhow::Result<PathBuf> {
-    let now = std::time::SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)?
-        .as_secs();
-    let fleet1_name = extract_fleet_name(fleet1.path())?;
-    let fleet2_name = extract_fleet_name(fleet2.path())?;
-
-    Ok(std::env::current_dir()?.join(format!("{now}_{fleet1_name}_{fleet2_name}")))
-}
-
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use clap::Parser;
 
-    use crate::extract_fleet_name;
+    use super::CargoProtologic;
 
     #[test]
-    fn extract_fleet_name_is_sane() -> anyhow::Result<()> {
-        let path = PathBuf::from("fleet_demo_fleet_foo_bar.wasm");
-        let name = extract_fleet_name(path)?;
-        assert_eq!("fleet_demo_fleet_foo_bar", name);
-
-        let path = PathBuf::from("demo_fleet_foo_bar");
-        let name = extract_fleet_name(path)?;
-        assert_eq!("demo_fleet_foo_bar", name);
-
+    fn proto_parses_the_same_as_protologic() -> anyhow::Result<()> {
+        for args in [
+            &["run", "--quick", "--fleet-a", "alpha"][..],
+            &["--dry-run", "build", "-p", "beta"],
+            &["config", "get", "output-dir"],
+        ] {
+            // `cargo proto` runs `cargo-proto proto`, and `cargo protologic` `cargo-protologic protologic`
+            let long = CargoProtologic::try_parse_from(
+                ["cargo-protologic", "protologic"].iter().chain(args),
+            )?;
+            let short =
+                CargoProtologic::try_parse_from(["cargo-proto", "proto"].iter().chain(args))?;
+            assert_eq!(format!("{short:?}"), format!("{long:?}"));
+        }
+        assert!(CargoProtologic::try_parse_from(["cargo-proto", "prot", "build"]).is_err());
         Ok(())
     }
 }
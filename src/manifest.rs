@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::fleet::extract_fleet_name;
+
+/// The file in a fleet output directory recording what each built fleet is.
+pub const MANIFEST_FILE: &str = "fleet_manifest.json";
+
+/// What `build` knows about the fleets it put in a fleet output directory, keyed by the file name
+/// of each fleet's wasm.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FleetManifest {
+    pub fleets: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The cargo package the fleet was built from.
+    pub package: String,
+    /// The `name` from the package's `[package.metadata.protologic]`, if it set one.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// The package's version when the fleet was built.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The version of the Protologic SDK the fleet was built against, which has to suit the sim.
+    #[serde(default)]
+    pub sdk_version: Option<String>,
+}
+
+impl FleetManifest {
+    /// Reads the manifest in a fleet output directory, which is empty if nothing's been built
+    /// there since fleets started getting one.
+    pub fn read(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let json = std::fs::read_to_string(&path)
+            .with_context(|| format!("trying to read fleet manifest {path:?}"))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("trying to parse fleet manifest {path:?}"))
+    }
+
+    pub fn write(&self, dir: &Path) -> anyhow::Result<()> {
+        let path = dir.join(MANIFEST_FILE);
+        let json =
+            serde_json::to_string_pretty(self).context("trying to serialize fleet manifest")?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("trying to write fleet manifest {path:?}"))
+    }
+
+    /// The name to show for the fleet at `path`: its display name from the manifest, or the file
+    /// name without `.wasm` for fleets the manifest doesn't know.
+    pub fn fleet_name(&self, path: &Path) -> anyhow::Result<String> {
+        let display_name = self
+            .entry(path)
+            .and_then(|entry| entry.display_name.clone());
+
+        display_name.map_or_else(|| extract_fleet_name(path), Ok)
+    }
+
+    /// What the manifest knows about the fleet at `path`, if anything.
+    pub fn entry(&self, path: &Path) -> Option<&ManifestEntry> {
+        let name = path.file_name()?.to_str()?;
+        self.fleets.get(name)
+    }
+}
+
+/// The file name cargo gives a package's wasm, which swaps `-` for `_`.
+pub fn artifact_file_name(package: &str) -> String {
+    format!("{}.wasm", package.replace('-', "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{artifact_file_name, FleetManifest, ManifestEntry};
+
+    #[test]
+    fn fleet_name_prefers_the_display_name() -> anyhow::Result<()> {
+        let mut manifest = FleetManifest::default();
+        manifest.fleets.insert(
+            artifact_file_name("glass-cannon"),
+            ManifestEntry {
+                package: "glass-cannon".to_owned(),
+                display_name: Some("Glass Cannon".to_owned()),
+                version: None,
+                sdk_version: None,
+            },
+        );
+
+        assert_eq!(
+            manifest.fleet_name(Path::new("fleets/glass_cannon.wasm"))?,
+            "Glass Cannon"
+        );
+        assert_eq!(
+            manifest.fleet_name(Path::new("fleets/iron_armada.wasm"))?,
+            "iron_armada"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::events::{emit, Event, Phase};
+use crate::fleet::Fleet;
+use crate::manifest::{artifact_file_name, FleetManifest};
+use crate::output::say;
+use crate::series::Score;
+use crate::{MetadataPackage, ParsedMetadata};
+
+/// The most features `--matrix-features` takes. Every combination of them is built and battled, so
+/// each one doubles how long the matrix takes.
+const MAX_MATRIX_FEATURES: usize = 6;
+
+/// One build of the matrix's fleet, with some of its features turned on.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub features: Vec<String>,
+    pub fleet: Fleet,
+}
+
+/// How a variant did against the fixed opponent.
+#[derive(Debug, Clone)]
+pub struct VariantResult {
+    pub features: Vec<String>,
+    pub score: Score,
+}
+
+/// The directory in the fleet output directory that variants are built in.
+pub const MATRIX_DIR: &str = "matrix";
+
+/// Where the variants are built, away from the fleets `run` and `list` find. Each matrix run
+/// replaces it.
+pub fn matrix_dir(debug: bool) -> anyhow::Result<PathBuf> {
+    Ok(crate::fleet_output_path(debug)?.join(MATRIX_DIR))
+}
+
+/// The workspace package to build and battle against the opponent, like the one whose features the
+/// matrix goes through: the one `name` picks by package or fleet name, or the only workspace fleet
+/// besides the opponent. `flag` is what picks it, for the error when it's needed.
+pub fn matrix_package<'a>(
+    metadata: &'a ParsedMetadata,
+    name: Option<&str>,
+    opponent: &Fleet,
+    flag: &str,
+) -> anyhow::Result<&'a MetadataPackage> {
+    let fleet_ids = metadata.fleet_ids();
+    let fleets: Vec<&MetadataPackage> = metadata
+        .packages
+        .iter()
+        .filter(|package| fleet_ids.contains(&package.id))
+        .collect();
+    let stem = |package: &MetadataPackage| package.name.replace('-', "_");
+    let names = || {
+        fleets
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    if let Some(name) = name {
+        return fleets
+            .iter()
+            .find(|package| package.name == name || stem(package) == name)
+            .copied()
+            .with_context(|| {
+                format!(
+                    "no workspace fleet named '{name}' to build, the workspace fleets are: {}",
+                    names()
+                )
+            });
+    }
+
+    // The opponent's manifest knows its package, but a fleet built by hand only has its file name
+    let opponent_package = opponent.manifest_entry().map(|entry| entry.package);
+    let candidates: Vec<_> = fleets
+        .iter()
+        .filter(|package| {
+            opponent_package.as_ref() != Some(&package.name) && stem(package) != opponent.selector()
+        })
+        .collect();
+    match candidates.as_slice() {
+        [package] => Ok(package),
+        _ => anyhow::bail!(
+            "pick the fleet to build with `{flag}`, the workspace fleets are: {}",
+            names()
+        ),
+    }
+}
+
+/// Checks the package has every one of the matrix's features, and that there aren't too many.
+pub fn check_features(package: &MetadataPackage, features: &[String]) -> anyhow::Result<()> {
+    if features.len() > MAX_MATRIX_FEATURES {
+        anyhow::bail!(
+            "{} features make {} variants to build and battle, the most the matrix takes is {MAX_MATRIX_FEATURES}",
+            features.len(),
+            1u64 << features.len()
+        );
+    }
+
+    for feature in features {
+        if !package.features.contains_key(feature) {
+            let known = package
+                .features
+                .keys()
+                .filter(|name| *name != "default")
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            anyhow::bail!(
+                "'{}' has no feature `{feature}`, its features are: {}",
+                package.name,
+                if known.is_empty() {
+                    "none".to_owned()
+                } else {
+                    known.join(", ")
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Every combination of the features, from none of them to all of them, smallest first and in the
+/// order they were given otherwise.
+pub fn feature_combinations(features: &[String]) -> Vec<Vec<String>> {
+    let mut combinations: Vec<Vec<String>> = (0..1u32 << features.len())
+        .map(|mask| {
+            features
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, feature)| feature.clone())
+                .collect()
+        })
+        .collect();
+    // Stable, so same-sized combinations keep the order of the bits, first feature first
+    combinations.sort_by_key(Vec::len);
+    combinations
+}
+
+/// The fleet name for a variant, with its features suffixed so it can't be mistaken for the real
+/// fleet, like `glass_cannon+aggressive+long-range`.
+pub fn variant_name(package: &str, features: &[String]) -> String {
+    let stem = package.replace('-', "_");
+    if features.is_empty() {
+        return format!("{stem}+none");
+    }
+    format!("{stem}+{}", features.join("+"))
+}
+
+/// The features of a variant for people, like `aggressive, long-range`.
+pub fn describe_features(features: &[String]) -> String {
+    if features.is_empty() {
+        return "no extra features".to_owned();
+    }
+    features.join(", ")
+}
+
+/// Builds the package once for each combination of features, optimizing each build into the
+/// matrix directory under its variant name.
+pub fn build_variants(
+    metadata: &ParsedMetadata,
+    package: &MetadataPackage,
+    combinations: &[Vec<String>],
+    debug: bool,
+) -> anyhow::Result<Vec<Variant>> {
+    let dir = matrix_dir(debug)?;
+    // Variants from an earlier matrix could have other features, or be stale builds
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)
+            .with_context(|| format!("trying to clear out old matrix variants in {dir:?}"))?;
+    }
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("trying to create matrix directory {dir:?}"))?;
+
+    // cargo builds every variant to the same place, so each is optimized out of it straight away
+    let cargo_output =
+        crate::cargo_output_base_path(debug)?.join(artifact_file_name(&package.name));
+    let mut manifest = FleetManifest::default();
+    let mut paths = Vec::new();
+
+    emit(Event::PhaseStarted {
+        phase: Phase::Build,
+    });
+    for (i, features) in combinations.iter().enumerate() {
+        let name = variant_name(&package.name, features);
+        say!(
+            "Building variant {} of {}: '{}' with {}",
+            i + 1,
+            combinations.len(),
+            package.name,
+            describe_features(features)
+        );
+        let status = crate::build(&package.name, features, debug)?
+            .wait()
+            .context("trying to wait until the `cargo build` execution has finished")?;
+        if !status.success() {
+            anyhow::bail!(
+                "cargo couldn't build '{}' with {}, it exited with {status}",
+                package.name,
+                describe_features(features)
+            );
+        }
+        emit(Event::PackageBuilt {
+            package: name.clone(),
+        });
+
+        let file_name = format!("{name}.wasm");
+        let path = dir.join(&file_name);
+        crate::optimize_wasm(&cargo_output, &path, debug)?;
+        manifest.fleets.insert(
+            file_name,
+            crate::ManifestEntry {
+                display_name: None,
+                ..crate::manifest_entry(metadata, package)
+            },
+        );
+        paths.push(path);
+    }
+    manifest.write(&dir)?;
+
+    combinations
+        .iter()
+        .zip(paths)
+        .map(|(features, path)| {
+            Ok(Variant {
+                features: features.clone(),
+                fleet: Fleet::from_path(path, &manifest)?,
+            })
+        })
+        .collect()
+}
+
+/// The variants ranked by win-rate against the opponent, best first, as a table to print.
+pub fn ranking_table(opponent: &str, results: &[VariantResult]) -> String {
+    let mut ranked: Vec<(&VariantResult, Option<f64>)> = results
+        .iter()
+        .map(|result| (result, result.score.win_rate(true)))
+        .collect();
+    // Variants with no known outcome go last, ties keep the order they were battled in
+    ranked.sort_by(|(_, a), (_, b)| b.unwrap_or(-1.0).total_cmp(&a.unwrap_or(-1.0)));
+
+    let features: Vec<String> = ranked
+        .iter()
+        .map(|(result, _)| describe_features(&result.features))
+        .collect();
+    let width = features
+        .iter()
+        .map(String::len)
+        .chain(["Features".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!("Feature matrix against '{opponent}':\n");
+    writeln!(
+        table,
+        "  Rank  {:<width$}  Won  Lost  Drawn  Win-rate",
+        "Features"
+    )
+    .unwrap();
+    for (i, ((result, win_rate), features)) in ranked.iter().zip(&features).enumerate() {
+        let score = &result.score;
+        let win_rate =
+            win_rate.map_or("unknown".to_owned(), |rate| format!("{:.1}%", rate * 100.0));
+        writeln!(
+            table,
+            "  {:<4}  {features:<width$}  {:<3}  {:<4}  {:<5}  {win_rate}",
+            i + 1,
+            score.first,
+            score.second,
+            score.draws
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{feature_combinations, ranking_table, variant_name, VariantResult};
+    use crate::series::Score;
+
+    fn features(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| name.to_owned()).collect()
+    }
+
+    #[test]
+    fn feature_combinations_go_from_none_to_all() {
+        assert_eq!(
+            feature_combinations(&features(&["aggressive", "long-range", "swarm"])),
+            vec![
+                features(&[]),
+                features(&["aggressive"]),
+                features(&["long-range"]),
+                features(&["swarm"]),
+                features(&["aggressive", "long-range"]),
+                features(&["aggressive", "swarm"]),
+                features(&["long-range", "swarm"]),
+                features(&["aggressive", "long-range", "swarm"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_names_carry_their_features() {
+        assert_eq!(variant_name("glass-cannon", &[]), "glass_cannon+none");
+        assert_eq!(
+            variant_name("glass-cannon", &features(&["aggressive", "long-range"])),
+            "glass_cannon+aggressive+long-range"
+        );
+    }
+
+    #[test]
+    fn ranking_table_puts_the_best_variant_first() {
+        let result = |names: &[&str], first, second, draws, unknown| VariantResult {
+            features: features(names),
+            score: Score {
+                first,
+                second,
+                draws,
+                unknown,
+            },
+        };
+        let results = [
+            result(&[], 4, 6, 0, 0),
+            result(&["aggressive"], 7, 2, 1, 0),
+            result(&["long-range"], 0, 0, 0, 10),
+            result(&["aggressive", "long-range"], 4, 6, 0, 0),
+        ];
+
+        assert_eq!(
+            ranking_table("baseline", &results),
+            "\
+Feature matrix against 'baseline':
+  Rank  Features                Won  Lost  Drawn  Win-rate
+  1     aggressive              7    2     1      75.0%
+  2     no extra features       4    6     0      40.0%
+  3     aggressive, long-range  4    6     0      40.0%
+  4     long-range              0    0     0      unknown
+"
+        );
+    }
+}
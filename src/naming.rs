@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::results::result_path;
+use crate::sim::replay_path;
+
+/// The longest a label can be in a file name, so replay paths stay well clear of Windows' limits.
+const MAX_LABEL_LEN: usize = 48;
+
+/// Cleans up a `--label` so it can go in a file name on any OS.
+///
+/// Anything other than ASCII letters, digits, `-`, `_` and `.` becomes `_`, runs of `_` are
+/// collapsed, and separators are trimmed from the ends. Returns `None` if nothing usable is left.
+pub fn sanitize_label(label: &str) -> Option<String> {
+    let mut sanitized = String::with_capacity(label.len());
+    for c in label.chars() {
+        let c = if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') {
+            c
+        } else {
+            '_'
+        };
+        if !(c == '_' && sanitized.ends_with('_')) {
+            sanitized.push(c);
+        }
+    }
+
+    // Windows quietly drops trailing dots, so they can't be left dangling at the end either
+    let sanitized: String = sanitized
+        .trim_matches(['_', '-', '.'])
+        .chars()
+        .take(MAX_LABEL_LEN)
+        .collect();
+    let sanitized = sanitized.trim_end_matches(['_', '-', '.']);
+
+    (!sanitized.is_empty()).then(|| sanitized.to_owned())
+}
+
+/// How the time a battle started is written in its file names.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// `YYYY-MM-DD_HHMMSS` in local time.
+    #[default]
+    Local,
+    /// `YYYY-MM-DD_HHMMSS` in UTC.
+    Utc,
+    /// Seconds since the Unix epoch, which always sorts correctly.
+    Epoch,
+}
+
+impl TimestampFormat {
+    /// Formats a Unix timestamp for use in a file name.
+    ///
+    /// Uses a fixed format string rather than the locale's, so there's never a `:` or `/` in it.
+    pub fn format(self, timestamp: u64) -> String {
+        const FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+        let datetime = i64::try_from(timestamp)
+            .ok()
+            .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0));
+        match (self, datetime) {
+            (Self::Local, Some(datetime)) => {
+                datetime.with_timezone(&Local).format(FORMAT).to_string()
+            }
+            (Self::Utc, Some(datetime)) => datetime.format(FORMAT).to_string(),
+            // Timestamps too far out for a date are nonsense anyway, but still make a valid name
+            (Self::Epoch, _) | (_, None) => timestamp.to_string(),
+        }
+    }
+}
+
+/// Adds an extension to a path without replacing one it already has, since file names made from
+/// labels can have dots in them.
+pub fn add_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
+
+/// Picks a battle output base path in `dir` that no earlier battle has used, adding `_2`, `_3`, ...
+/// to `stem` as needed.
+///
+/// Battles that start in the same second would otherwise overwrite each other's replays.
+pub fn unique_output_path(dir: &Path, stem: &str) -> PathBuf {
+    let taken = |path: &Path| {
+        [path.to_owned(), replay_path(path), result_path(path)]
+            .iter()
+            .any(|path| path.exists())
+    };
+
+    let mut path = dir.join(stem);
+    let mut n = 2;
+    while taken(&path) {
+        path = dir.join(format!("{stem}_{n}"));
+        n += 1;
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_extension, sanitize_label, TimestampFormat, MAX_LABEL_LEN};
+
+    #[test]
+    fn sanitize_label_cleans_unsafe_characters() {
+        assert_eq!(
+            sanitize_label("does 2x missiles help vs kiters?").as_deref(),
+            Some("does_2x_missiles_help_vs_kiters")
+        );
+        assert_eq!(
+            sanitize_label("../a\\b:c*d|e<f>\"g").as_deref(),
+            Some("a_b_c_d_e_f_g")
+        );
+        assert_eq!(sanitize_label("v1.2-rc.").as_deref(), Some("v1.2-rc"));
+        assert_eq!(sanitize_label(" /?* "), None);
+        assert_eq!(
+            sanitize_label(&"x".repeat(100)).map(|label| label.len()),
+            Some(MAX_LABEL_LEN)
+        );
+    }
+
+    #[test]
+    fn timestamp_format_is_filename_safe() {
+        assert_eq!(TimestampFormat::Utc.format(1718476403), "2024-06-15_183323");
+        assert_eq!(TimestampFormat::Epoch.format(1718476403), "1718476403");
+
+        let local = TimestampFormat::Local.format(1718476403);
+        assert_eq!(local.len(), "2024-06-15_183323".len());
+        assert!(local
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '_')));
+    }
+
+    #[test]
+    fn add_extension_keeps_dots_in_names() {
+        assert_eq!(
+            add_extension("a_vs_b_v1.2".as_ref(), "json.deflate"),
+            std::path::Path::new("a_vs_b_v1.2.json.deflate")
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+/// Shows a desktop notification, using whatever the OS has for it.
+///
+/// Where that means running a script, the title and body go through environment variables rather
+/// than being spliced into it, so fleet names can't break the quoting.
+pub fn notify(title: &str, body: &str) -> anyhow::Result<()> {
+    let mut command = notification_command(title, body);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let status = command
+        .status()
+        .with_context(|| format!("trying to run {:?}", command.get_program()))?;
+    if !status.success() {
+        anyhow::bail!("{:?} exited with {status}", command.get_program());
+    }
+
+    Ok(())
+}
+
+fn notification_command(title: &str, body: &str) -> Command {
+    let mut command = platform_notification_command(title, body);
+    command
+        .env("PROTOLOGIC_NOTIFY_TITLE", title)
+        .env("PROTOLOGIC_NOTIFY_BODY", body);
+    command
+}
+
+#[allow(unused_variables)]
+fn platform_notification_command(title: &str, body: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        const TOAST: &str = "\
+            $ErrorActionPreference = 'Stop'; \
+            [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null; \
+            $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+            $text = $xml.GetElementsByTagName('text'); \
+            $text.Item(0).AppendChild($xml.CreateTextNode($env:PROTOLOGIC_NOTIFY_TITLE)) | Out-Null; \
+            $text.Item(1).AppendChild($xml.CreateTextNode($env:PROTOLOGIC_NOTIFY_BODY)) | Out-Null; \
+            [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('cargo-protologic').Show([Windows.UI.Notifications.ToastNotification]::new($xml))";
+
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", TOAST]);
+        command
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            "display notification (system attribute \"PROTOLOGIC_NOTIFY_BODY\") with title (system attribute \"PROTOLOGIC_NOTIFY_TITLE\")",
+        ]);
+        command
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let mut command = Command::new("notify-send");
+        command
+            .arg("--app-name=cargo-protologic")
+            .args([title, body]);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::notification_command;
+
+    #[test]
+    fn title_and_body_go_through_the_environment() {
+        let title = "\"Alpha\" won";
+        let body = "'Beta' lost; $(rm -rf ~)";
+        let command = notification_command(title, body);
+
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&(
+            OsStr::new("PROTOLOGIC_NOTIFY_TITLE"),
+            Some(OsStr::new(title))
+        )));
+        assert!(envs.contains(&(OsStr::new("PROTOLOGIC_NOTIFY_BODY"), Some(OsStr::new(body)))));
+
+        // notify-send takes them as plain arguments, but the scripts mustn't have them spliced in.
+        if cfg!(any(target_os = "windows", target_os = "macos")) {
+            for arg in command.get_args() {
+                let arg = arg.to_string_lossy();
+                assert!(
+                    !arg.contains(title) && !arg.contains(body),
+                    "spliced into {arg}"
+                );
+            }
+        }
+    }
+}
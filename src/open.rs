@@ -0,0 +1,176 @@
+//! Opening the places this tool keeps things: the fleet and battle output directories and the
+//! Protologic Release in the file manager, and the config files in an editor.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context;
+
+use crate::config::{find_battle_output_dir, UserConfig};
+use crate::fleet_output_path;
+use crate::output::{dry_run, say, shell_command};
+use crate::settings::{find_workspace_root, WORKSPACE_FILE};
+use crate::sim::find_protologic_path;
+
+/// What `open` opens.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The fleet output directory, where `build` puts fleets.
+    Fleets,
+    /// The battle output directory, where `run` puts replays and results.
+    Replays,
+    /// The Protologic Release that `run` battles with.
+    Release,
+    /// Your user config, or with `--workspace` the workspace's `protologic.toml`, in `$VISUAL` or `$EDITOR`.
+    Config,
+}
+
+/// Arguments for `cargo protologic open`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct OpenArgs {
+    target: Target,
+    /// With `fleets`, the debug build directory instead.
+    #[arg(short, long, default_value = "false")]
+    debug: bool,
+    /// With `replays`, the battle output directory to open, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// With `release`, the location of the Protologic/Release repo, as `run` takes it.
+    #[arg(long, env)]
+    protologic_path: Option<PathBuf>,
+    /// With `config`, the workspace's `protologic.toml` instead of your user config.
+    #[arg(long, default_value = "false")]
+    workspace: bool,
+}
+
+/// The command that opens the OS file manager at `dir`.
+fn file_manager_command(dir: &Path) -> Command {
+    let program = if cfg!(target_os = "windows") {
+        "explorer.exe"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let mut command = Command::new(program);
+    command.arg(dir);
+    command
+}
+
+/// The command that edits `file` in the user's editor: `$VISUAL`, then `$EDITOR`, which can have
+/// arguments of their own like `code --wait`, then the platform's usual one.
+pub fn editor_command(file: &Path, visual: Option<&str>, editor: Option<&str>) -> Command {
+    let configured = [visual, editor]
+        .into_iter()
+        .flatten()
+        .map(str::split_whitespace)
+        .find_map(|mut words| Some((words.next()?, words)));
+    let mut command = match configured {
+        Some((program, args)) => {
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+        None if cfg!(target_os = "windows") => Command::new("notepad.exe"),
+        None => Command::new("vi"),
+    };
+    command.arg(file);
+    command
+}
+
+/// The config file `open config` edits.
+fn config_file(workspace: bool) -> anyhow::Result<PathBuf> {
+    if !workspace {
+        return UserConfig::path();
+    }
+    let cwd = std::env::current_dir().context("trying to find the current directory")?;
+    let root = find_workspace_root(&cwd)
+        .context("not in a cargo workspace, so there's no protologic.toml to open")?;
+    Ok(root.join(WORKSPACE_FILE))
+}
+
+/// The directory a file manager should open for `target`, found the way the subcommands that use
+/// it find it.
+fn directory(args: &OpenArgs) -> anyhow::Result<PathBuf> {
+    let dir = match args.target {
+        Target::Fleets => fleet_output_path(args.debug)?,
+        Target::Replays => find_battle_output_dir(args.output_dir.clone())?.0,
+        Target::Release => find_protologic_path(args.protologic_path.clone())?,
+        Target::Config => unreachable!("config is opened in an editor"),
+    };
+    if !dir.is_dir() && !dry_run() {
+        anyhow::bail!("{} doesn't exist yet", dir.display());
+    }
+    Ok(dir)
+}
+
+/// Opens the target in the file manager, or for the config in an editor, waiting for the editor
+/// to close.
+pub fn open(args: OpenArgs) -> anyhow::Result<()> {
+    if args.target != Target::Config {
+        let dir = directory(&args)?;
+        let mut command = file_manager_command(&dir);
+        if dry_run() {
+            say!("Would run: {}", shell_command(&command));
+            return Ok(());
+        }
+        say!("Opening {}", dir.display());
+        command
+            .spawn()
+            .with_context(|| format!("trying to open the file manager at {dir:?}"))?;
+        return Ok(());
+    }
+
+    let file = config_file(args.workspace)?;
+    let visual = std::env::var("VISUAL").ok();
+    let editor = std::env::var("EDITOR").ok();
+    let mut command = editor_command(&file, visual.as_deref(), editor.as_deref());
+    if dry_run() {
+        say!("Would run: {}", shell_command(&command));
+        return Ok(());
+    }
+    // Editors open files that don't exist yet, but not in directories that don't
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("trying to create {dir:?}"))?;
+    }
+    let status = command.status().with_context(|| {
+        format!(
+            "trying to run {} to edit {file:?}, set $EDITOR to the editor you use",
+            command.get_program().to_string_lossy()
+        )
+    })?;
+    if !status.success() {
+        anyhow::bail!("the editor exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::editor_command;
+
+    #[test]
+    fn editor_command_prefers_visual_then_editor() {
+        let args = |visual, editor| {
+            let command = editor_command(Path::new("config.toml"), visual, editor);
+            let mut words = vec![command.get_program().to_string_lossy().into_owned()];
+            words.extend(
+                command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().into_owned()),
+            );
+            words
+        };
+        assert_eq!(
+            args(Some("code --wait"), Some("nano")),
+            ["code", "--wait", "config.toml"]
+        );
+        assert_eq!(args(Some(" "), Some("nano")), ["nano", "config.toml"]);
+        assert_eq!(args(None, Some("nano")), ["nano", "config.toml"]);
+        if !cfg!(target_os = "windows") {
+            assert_eq!(args(None, None), ["vi", "config.toml"]);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+/// How a battle ended, relative to the fleets as they were passed to the sim.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    FleetAWon,
+    FleetBWon,
+    Draw,
+    /// The sim crashed, or exited with an error without saying who won. Set by whoever ran the sim,
+    /// since parsing its output can't tell.
+    SimCrashed,
+    /// The sim output didn't say who won, or said it in a way we don't understand.
+    #[default]
+    Unknown,
+}
+
+/// Figures out who won from the sim's console output.
+///
+/// The sim doesn't write a machine readable result, so this looks for the last line announcing a
+/// winner (`Winner: my_fleet`, `my_fleet wins`) or a draw. Winners can be named by fleet or by
+/// their index in `--fleets`.
+pub fn parse_outcome(output: &[String], fleet_a: &str, fleet_b: &str) -> Outcome {
+    output
+        .iter()
+        .rev()
+        .find_map(|line| parse_line(line, fleet_a, fleet_b))
+        .unwrap_or(Outcome::Unknown)
+}
+
+fn parse_line(line: &str, fleet_a: &str, fleet_b: &str) -> Option<Outcome> {
+    let line = line.trim().to_lowercase();
+
+    // Winners come first, since fleets can have names like `tie_fighter`
+    let winner = line
+        .strip_prefix("winner:")
+        .or_else(|| line.strip_prefix("winner is"))
+        .or_else(|| line.strip_suffix(" wins"))
+        .or_else(|| line.strip_suffix(" wins!"))
+        .or_else(|| line.strip_suffix(" won"))
+        .or_else(|| line.strip_suffix(" won!"));
+    let Some(winner) = winner else {
+        return is_draw(&line).then_some(Outcome::Draw);
+    };
+
+    let winner = normalize_fleet_name(winner);
+    let (fleet_a, fleet_b) = (normalize_fleet_name(fleet_a), normalize_fleet_name(fleet_b));
+
+    // Self-play battles have identical names, so the side can only come from the index
+    if winner == fleet_a && winner != fleet_b {
+        return Some(Outcome::FleetAWon);
+    }
+    if winner == fleet_b && winner != fleet_a {
+        return Some(Outcome::FleetBWon);
+    }
+
+    match winner.trim_start_matches("fleet").trim_start_matches('_') {
+        "0" => Some(Outcome::FleetAWon),
+        "1" => Some(Outcome::FleetBWon),
+        "none" | "nobody" | "draw" | "tie" => Some(Outcome::Draw),
+        _ => None,
+    }
+}
+
+/// Whether a line that didn't name a winner announces a draw, like `Draw!` or `Battle ended in a
+/// draw`. Only the whole line or its ending counts, so that fleet names mentioned along the way
+/// don't.
+fn is_draw(line: &str) -> bool {
+    let line = line.trim_end_matches(['!', '.']);
+    let line = line
+        .strip_prefix("result:")
+        .or_else(|| line.strip_prefix("outcome:"))
+        .unwrap_or(line)
+        .trim();
+
+    matches!(line, "draw" | "tie" | "stalemate" | "tied")
+        || [" a draw", " a tie", " a stalemate", " tied", " drawn"]
+            .iter()
+            .any(|ending| line.ends_with(ending))
+}
+
+/// Normalizes a fleet name so the different spellings of it compare equal.
+///
+/// Handles case, surrounding quotes, a leading path, a `.wasm` extension, and `-` vs `_` (cargo
+/// package names use `-`, but the wasm artifacts use `_`).
+pub fn normalize_fleet_name(name: &str) -> String {
+    let name = name
+        .trim()
+        .trim_matches(|c| matches!(c, '"' | '\'' | '`'))
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    name.strip_suffix(".wasm")
+        .unwrap_or(&name)
+        .replace(['-', ' '], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_fleet_name, parse_outcome, Outcome};
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_outcome_finds_winner_by_name_or_index() {
+        let output = lines(&["tick 1000", "Winner: Iron-Armada"]);
+        assert_eq!(
+            parse_outcome(&output, "glass_cannon", "iron_armada"),
+            Outcome::FleetBWon
+        );
+
+        let output = lines(&["Fleet 0 wins!", "Saving replay..."]);
+        assert_eq!(
+            parse_outcome(&output, "glass_cannon", "iron_armada"),
+            Outcome::FleetAWon
+        );
+    }
+
+    #[test]
+    fn parse_outcome_handles_draws_and_noise() {
+        let output = lines(&["Battle ended in a draw"]);
+        assert_eq!(parse_outcome(&output, "a", "b"), Outcome::Draw);
+
+        let output = lines(&["Loading fleets", "Done"]);
+        assert_eq!(parse_outcome(&output, "a", "b"), Outcome::Unknown);
+    }
+
+    #[test]
+    fn parse_outcome_doesnt_mistake_fleet_names_for_draws() {
+        let output = lines(&["Loading tie_fighter", "tie_fighter wins"]);
+        assert_eq!(
+            parse_outcome(&output, "tie_fighter", "iron_armada"),
+            Outcome::FleetAWon
+        );
+
+        let output = lines(&["Winner: iron-armada", "Saving tie-fighter vs iron-armada"]);
+        assert_eq!(
+            parse_outcome(&output, "tie_fighter", "iron_armada"),
+            Outcome::FleetBWon
+        );
+
+        let output = lines(&["Draw!"]);
+        assert_eq!(parse_outcome(&output, "tie_fighter", "b"), Outcome::Draw);
+    }
+
+    #[test]
+    fn normalize_fleet_name_is_sane() {
+        assert_eq!(normalize_fleet_name(" 'Iron-Armada.wasm' "), "iron_armada");
+        assert_eq!(
+            normalize_fleet_name("target/protologic_fleets/iron_armada.wasm"),
+            "iron_armada"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static NDJSON: AtomicBool = AtomicBool::new(false);
+
+/// Keeps stdout free for machine readable output, sending everything [`say!`] prints to stderr.
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+pub fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// Only print what would be run, without running anything or writing any files.
+pub fn set_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Log [`crate::events`] as NDJSON on stdout, which reserves it.
+pub fn set_ndjson() {
+    NDJSON.store(true, Ordering::Relaxed);
+    reserve_stdout();
+}
+
+pub fn ndjson() -> bool {
+    NDJSON.load(Ordering::Relaxed)
+}
+
+/// Writes a command out the way you'd type it into a shell, ready to copy and paste.
+pub fn shell_command(command: &Command) -> String {
+    let mut words = Vec::new();
+    if let Some(dir) = command.get_current_dir() {
+        words.push(format!("cd {} &&", shell_quote(&dir.to_string_lossy())));
+    }
+    for (key, value) in command.get_envs() {
+        if let Some(value) = value {
+            words.push(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                shell_quote(&value.to_string_lossy())
+            ));
+        }
+    }
+    words.push(shell_quote(&command.get_program().to_string_lossy()));
+    words.extend(
+        command
+            .get_args()
+            .map(|arg| shell_quote(&arg.to_string_lossy())),
+    );
+
+    words.join(" ")
+}
+
+pub fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Prints progress meant for a human, like `println!`. Goes to stderr when stdout is reserved.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::stdout_reserved() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+pub(crate) use say;
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::shell_command;
+
+    #[test]
+    fn shell_command_quotes_what_needs_it() {
+        let mut command = Command::new("/opt/Protologic Release/Protologic.Terminal");
+        command
+            .current_dir("/tmp/replays")
+            .env("TUNING", "a b")
+            .args(["--fleets", "a.wasm", "it's.wasm", "--seed", "7"]);
+
+        assert_eq!(
+            shell_command(&command),
+            "cd /tmp/replays && TUNING='a b' '/opt/Protologic Release/Protologic.Terminal' --fleets a.wasm 'it'\\''s.wasm' --seed 7"
+        );
+    }
+}
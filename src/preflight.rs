@@ -0,0 +1,209 @@
+use anyhow::Context;
+use wasmtime::{Config, Engine, ExternType, Instance, Linker, Module, Store, Trap, Val};
+
+use crate::fleet::Fleet;
+use crate::output::say;
+
+/// How many ticks a fleet has to get through to pass. Each call to `sched_yield` is a tick.
+const PREFLIGHT_TICKS: u32 = 5;
+/// How much fuel a fleet gets for the preflight ticks, rather more than the sim hands out, so only
+/// fleets that are stuck in a loop run out.
+const PREFLIGHT_FUEL: u64 = 2_000_000_000;
+/// The exports fleets start from, in the order they're tried.
+pub const ENTRY_POINTS: &[&str] = &["_start", "main"];
+
+/// Why a preflight stopped the fleet, smuggled out of a host function as an error.
+#[derive(Debug)]
+enum Stop {
+    /// The fleet got through all its ticks.
+    Ticked,
+    /// The fleet called WASI's `proc_exit`.
+    Exited(i32),
+}
+
+impl std::fmt::Display for Stop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stop::Ticked => write!(f, "ran {PREFLIGHT_TICKS} ticks"),
+            Stop::Exited(code) => write!(f, "exited with code {code}"),
+        }
+    }
+}
+
+impl std::error::Error for Stop {}
+
+/// Runs each fleet for a few ticks, failing if one traps or runs out of fuel, so a fleet that
+/// can't get going is caught before a whole sim run is spent on it.
+pub fn preflight(fleets: [&Fleet; 2]) -> anyhow::Result<()> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)
+        .map_err(anyhow::Error::from)
+        .context("trying to set up wasmtime for the preflight")?;
+
+    for fleet in fleets {
+        let bytes = std::fs::read(&fleet.path)
+            .with_context(|| format!("trying to read fleet {:?}", fleet.path))?;
+        // Fleets that validated but don't compile are more likely about wasmtime missing a
+        // feature the sim has than about the fleet, so don't hold the battle up over them
+        let module = match Module::new(&engine, &bytes) {
+            Ok(module) => module,
+            Err(err) => {
+                say!("Warning: couldn't preflight '{}': {err:#}", fleet.name);
+                continue;
+            }
+        };
+
+        run_ticks(&engine, &module).with_context(|| {
+            format!(
+                "fleet '{}' failed its preflight, it would likely fail in the sim too. Pass `--no-preflight` to battle anyway",
+                fleet.name
+            )
+        })?;
+        say!(
+            "Preflight: '{}' ran {PREFLIGHT_TICKS} ticks fine",
+            fleet.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs the fleet's entry point until it's had [`PREFLIGHT_TICKS`] ticks, with every import stubbed
+/// out to return zeros.
+fn run_ticks(engine: &Engine, module: &Module) -> anyhow::Result<()> {
+    let mut linker = Linker::new(engine);
+    for import in module.imports() {
+        let ExternType::Func(ty) = import.ty() else {
+            anyhow::bail!(
+                "it imports `{}::{}`, which isn't a function",
+                import.module(),
+                import.name()
+            );
+        };
+
+        let name = import.name().to_owned();
+        let result_types: Vec<_> = ty.results().collect();
+        linker.func_new(
+            import.module(),
+            import.name(),
+            ty,
+            move |mut caller, params, results| {
+                match name.as_str() {
+                    "sched_yield" => {
+                        *caller.data_mut() += 1;
+                        if *caller.data() >= PREFLIGHT_TICKS {
+                            return Err(wasmtime::Error::new(Stop::Ticked));
+                        }
+                    }
+                    "proc_exit" => {
+                        let code = params.first().and_then(Val::i32).unwrap_or(0);
+                        return Err(wasmtime::Error::new(Stop::Exited(code)));
+                    }
+                    _ => {}
+                }
+                for (result, ty) in results.iter_mut().zip(&result_types) {
+                    *result = ty.default_value().unwrap_or(Val::I32(0));
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    let mut store = Store::new(engine, 0u32);
+    store.set_fuel(PREFLIGHT_FUEL)?;
+    let result = start(&linker, &mut store, module);
+
+    let err = match result {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+    match err.downcast_ref::<Stop>() {
+        Some(Stop::Ticked) | Some(Stop::Exited(0)) => return Ok(()),
+        Some(Stop::Exited(code)) => {
+            anyhow::bail!("it exited with code {code} on tick {}", store.data())
+        }
+        None => {}
+    }
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => anyhow::bail!(
+            "it ran out of fuel on tick {}, is it stuck in a loop?",
+            store.data()
+        ),
+        Some(trap) => anyhow::bail!("it trapped on tick {}: {trap}", store.data()),
+        None => Err(err),
+    }
+}
+
+/// Instantiates the fleet and calls its entry point.
+fn start(linker: &Linker<u32>, store: &mut Store<u32>, module: &Module) -> anyhow::Result<()> {
+    let instance: Instance = linker.instantiate(&mut *store, module)?;
+    if let Ok(initialize) = instance.get_typed_func::<(), ()>(&mut *store, "_initialize") {
+        initialize.call(&mut *store, ())?;
+    }
+
+    let Some(entry) = ENTRY_POINTS
+        .iter()
+        .find_map(|name| instance.get_typed_func::<(), ()>(&mut *store, name).ok())
+    else {
+        anyhow::bail!(
+            "it doesn't export an entry point, one of `{}`",
+            ENTRY_POINTS.join("`, `")
+        );
+    };
+    entry.call(&mut *store, ())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::{Config, Engine, Module};
+
+    use super::run_ticks;
+
+    fn run(wat: &str) -> anyhow::Result<()> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wat::parse_str(wat)?)?;
+        run_ticks(&engine, &module)
+    }
+
+    #[test]
+    fn preflight_passes_fleets_that_keep_ticking() -> anyhow::Result<()> {
+        run(r#"(module
+            (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+            (import "protologic" "engine_set_throttle" (func $throttle (param f32)))
+            (func (export "_start")
+                (loop $tick
+                    (call $throttle (f32.const 1))
+                    (drop (call $yield))
+                    (br $tick))))"#)
+    }
+
+    #[test]
+    fn preflight_catches_traps_and_runaway_loops() {
+        let err = run(r#"(module
+            (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+            (func (export "_start")
+                (drop (call $yield))
+                unreachable))"#)
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with("it trapped on tick 1:"),
+            "{err:#}"
+        );
+
+        let err = run(r#"(module (func (export "_start") (loop $spin (br $spin))))"#).unwrap_err();
+        assert!(
+            err.to_string().starts_with("it ran out of fuel on tick 0"),
+            "{err:#}"
+        );
+
+        let err = run(r#"(module (func (export "tick")))"#).unwrap_err();
+        assert!(
+            err.to_string().contains("doesn't export an entry point"),
+            "{err:#}"
+        );
+    }
+}
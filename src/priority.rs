@@ -0,0 +1,192 @@
+use std::process::{Child, Command};
+
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+
+/// The cores to pin the sim to, from `--cpus`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuList(pub Vec<usize>);
+
+/// Parses a `--cpus` list of core indices and ranges, like `0-3` or `0,2,4-6`.
+pub fn parse_cpu_list(value: &str) -> Result<CpuList, String> {
+    if cfg!(all(unix, not(target_os = "linux"))) {
+        return Err("pinning the sim to cores isn't supported on this OS".to_owned());
+    }
+
+    let mut cpus = Vec::new();
+    for part in value.split(',').map(str::trim) {
+        let parse = |cpu: &str| {
+            cpu.trim()
+                .parse::<usize>()
+                .map_err(|err| format!("`{cpu}` isn't a core number: {err}"))
+        };
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("the range `{part}` is backwards"));
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+
+    cpus.sort_unstable();
+    cpus.dedup();
+    if let Some(&cpu) = cpus.iter().find(|&&cpu| cpu >= MAX_CPUS) {
+        return Err(format!(
+            "core {cpu} is out of range, the most supported is {}",
+            MAX_CPUS - 1
+        ));
+    }
+
+    Ok(CpuList(cpus))
+}
+
+#[cfg(windows)]
+const MAX_CPUS: usize = usize::BITS as usize;
+#[cfg(not(windows))]
+const MAX_CPUS: usize = 1024;
+
+/// Sets up `command` to run at a low scheduling priority and/or pinned to `cpus`, before it's
+/// spawned. Call [`pin_child`] once it's spawned too, since Windows can only pin a running process.
+pub fn limit_command(command: &mut Command, low_priority: bool, cpus: Option<&[usize]>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        if !low_priority && cpus.is_none() {
+            return;
+        }
+        let cpus = cpus.map(ToOwned::to_owned);
+        // SAFETY: only async-signal-safe libc calls are made between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if low_priority {
+                    // Lowering our own priority can't fail for lack of permission
+                    libc::nice(19);
+                }
+                if let Some(cpus) = &cpus {
+                    pin_current_process(cpus)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        let _ = cpus;
+        if low_priority {
+            command
+                .creation_flags(windows_sys::Win32::System::Threading::BELOW_NORMAL_PRIORITY_CLASS);
+        }
+    }
+}
+
+/// Pins a spawned child to `cpus`, where that couldn't be done before spawning it.
+pub fn pin_child(child: &Child, cpus: Option<&[usize]>) -> std::io::Result<()> {
+    #[cfg(windows)]
+    if let Some(cpus) = cpus {
+        use std::os::windows::io::AsRawHandle;
+
+        let mask = cpus.iter().fold(0usize, |mask, &cpu| mask | 1 << cpu);
+        // SAFETY: the handle belongs to `child`, which outlives the call
+        let pinned = unsafe {
+            windows_sys::Win32::System::Threading::SetProcessAffinityMask(
+                child.as_raw_handle() as windows_sys::Win32::Foundation::HANDLE,
+                mask,
+            )
+        };
+        if pinned == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    #[cfg(not(windows))]
+    let _ = (child, cpus);
+
+    Ok(())
+}
+
+/// Checks every core in `cpus` is one we're allowed to run on, since pinning the sim would fail
+/// with an unhelpful error otherwise.
+pub fn check_cpus_available(cpus: &[usize]) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: the set is zeroed before use and only read below its size
+        let available: Vec<usize> = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Err(std::io::Error::last_os_error())
+                    .context("trying to find which cores are available");
+            }
+            (0..MAX_CPUS)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect()
+        };
+
+        let missing: Vec<String> = cpus
+            .iter()
+            .filter(|cpu| !available.contains(cpu))
+            .map(ToString::to_string)
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "can't pin the sim to core {}, the cores available are {}",
+                missing.join(", "),
+                available
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = cpus;
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_process(cpus: &[usize]) -> std::io::Result<()> {
+    // SAFETY: the set is zeroed before use and only ever indexed below its size
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn pin_current_process(_cpus: &[usize]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "pinning the sim to cores with `--cpus` isn't supported on this OS",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cpu_list, CpuList};
+
+    #[test]
+    fn parse_cpu_list_takes_ranges_and_lists() {
+        assert_eq!(parse_cpu_list("0-3"), Ok(CpuList(vec![0, 1, 2, 3])));
+        assert_eq!(parse_cpu_list("4, 0,2-3,2"), Ok(CpuList(vec![0, 2, 3, 4])));
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("one").is_err());
+        assert!(parse_cpu_list("").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Deleting old battles from the battle output directory: their replays, results and logs, and the
+//! crash directories of sim crashes.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+
+use crate::clean::disk_size;
+use crate::config::find_battle_output_dir;
+use crate::crash::CRASH_REPORT_FILE;
+use crate::history::{default_history_path, read_history};
+use crate::output::{dry_run, say};
+use crate::replay::{battle_files, battle_replays, LATEST_REPLAY};
+use crate::results::{result_path, BattleRecord};
+
+/// Arguments for `cargo protologic prune`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PruneArgs {
+    /// Delete battles and crash directories older than this, like `30d`, `12h` or `2w`.
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    older_than: Duration,
+    /// Keep battles with a label, in their results or the battle history, like tournament games.
+    #[arg(long, default_value = "false")]
+    keep_labeled: bool,
+    /// The battle output directory to prune, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+}
+
+/// Parses an age like `30d`: a whole number of seconds, minutes, hours, days or weeks.
+pub fn parse_age(age: &str) -> Result<Duration, String> {
+    let split = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or("it needs a unit, like `30d`")?;
+    let (number, unit) = age.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| "it has to start with a whole number, like `30d`")?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "`{unit}` isn't a unit, pick one of s, m, h, d or w"
+            ))
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Something old enough to prune: a battle's files, or a crash directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prunable {
+    /// The battle's base name, or the crash directory's name.
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// When a battle was, going by its results and otherwise its replay's modification time.
+fn battle_time(record: Option<&BattleRecord>, modified: SystemTime) -> SystemTime {
+    record
+        .filter(|record| record.timestamp > 0)
+        .map_or(modified, |record| {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(record.timestamp)
+        })
+}
+
+/// The battles and crash directories in `dir` from before `cutoff`. Only files named like this tool
+/// names them are picked out, so anything else in `dir` is left alone.
+pub fn prunable(
+    dir: &Path,
+    cutoff: SystemTime,
+    keep_labeled: bool,
+    history: &[BattleRecord],
+) -> anyhow::Result<Vec<Prunable>> {
+    let mut found = Vec::new();
+    let mut replays = battle_replays(dir)?;
+    replays.sort_by(|a, b| a.1.cmp(&b.1));
+    for (modified, base) in replays {
+        let replay_name = format!("{base}.json.deflate");
+        let record = BattleRecord::read_json(&result_path(&dir.join(&base))).ok();
+        if battle_time(record.as_ref(), modified) >= cutoff {
+            continue;
+        }
+        // The history remembers battles whose results were deleted
+        let labeled = record.as_ref().is_some_and(|record| record.label.is_some())
+            || history.iter().any(|record| {
+                record.label.is_some()
+                    && record
+                        .replay
+                        .file_name()
+                        .is_some_and(|name| name == replay_name.as_str())
+            });
+        if keep_labeled && labeled {
+            continue;
+        }
+        found.push(Prunable {
+            paths: battle_files(dir, &base),
+            name: base,
+        });
+    }
+
+    let mut crashes = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("trying to read {dir:?}"))? {
+        let entry = entry.with_context(|| format!("trying to read an entry of {dir:?}"))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Not following symlinks, which could lead out of the directory
+        let report = entry.path().join(CRASH_REPORT_FILE);
+        if !name.starts_with("crash_") || !entry.file_type()?.is_dir() || !report.is_file() {
+            continue;
+        }
+        if std::fs::metadata(&report)?.modified()? < cutoff {
+            crashes.push(Prunable {
+                name,
+                paths: vec![entry.path()],
+            });
+        }
+    }
+    crashes.sort_by(|a, b| a.name.cmp(&b.name));
+    found.extend(crashes);
+    Ok(found)
+}
+
+/// Deletes the battles and crash directories in the battle output directory older than
+/// `--older-than`, printing how many files that was and how much space it freed.
+pub fn prune(args: PruneArgs) -> anyhow::Result<()> {
+    let (dir, _) = find_battle_output_dir(args.output_dir)?;
+    if !dir.is_dir() {
+        say!("Nothing to prune, there's no battle output directory {dir:?}");
+        return Ok(());
+    }
+    let history_path = default_history_path()?;
+    let history = if args.keep_labeled && history_path.is_file() {
+        read_history(&history_path)?
+    } else {
+        Vec::new()
+    };
+    let cutoff = SystemTime::now()
+        .checked_sub(args.older_than)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let found = prunable(&dir, cutoff, args.keep_labeled, &history)?;
+    if found.is_empty() {
+        say!("Nothing in {} to prune", dir.display());
+        return Ok(());
+    }
+
+    let (mut files, mut total) = (0, 0);
+    for prunable in &found {
+        let size: u64 = prunable.paths.iter().map(|path| disk_size(path)).sum();
+        files += prunable.paths.len();
+        total += size;
+        if dry_run() {
+            say!("Would delete {} ({})", prunable.name, ByteSize::b(size));
+            continue;
+        }
+        for path in &prunable.paths {
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            };
+            result.with_context(|| format!("trying to delete {path:?}"))?;
+        }
+        say!("Deleted {} ({})", prunable.name, ByteSize::b(size));
+    }
+
+    if dry_run() {
+        say!("Would delete {files} files, freeing {}", ByteSize::b(total));
+        return Ok(());
+    }
+    // The pointer to the latest replay would be left pointing at nothing
+    let latest = dir.join(LATEST_REPLAY);
+    if latest.symlink_metadata().is_ok() && !latest.exists() {
+        let _ = std::fs::remove_file(&latest);
+    }
+    say!("Deleted {files} files, freeing {}", ByteSize::b(total));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use super::{parse_age, prunable};
+    use crate::results::BattleRecord;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn write(path: &Path, contents: &str, age: Duration) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, contents)?;
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::now() - age)?;
+        Ok(())
+    }
+
+    #[test]
+    fn ages_have_units() {
+        assert_eq!(parse_age("30d"), Ok(30 * DAY));
+        assert_eq!(parse_age("2w"), Ok(14 * DAY));
+        assert_eq!(parse_age("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert!(parse_age("30").unwrap_err().contains("needs a unit"));
+        assert!(parse_age("3y").unwrap_err().contains("isn't a unit"));
+        assert!(parse_age("d").is_err());
+    }
+
+    #[test]
+    fn only_old_battles_and_crashes_are_pruned() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        let labeled = serde_json::to_string(&BattleRecord {
+            label: Some("cup".to_owned()),
+            ..BattleRecord::default()
+        })?;
+        for (file, age) in [
+            ("1000000000_old_vs_b.json.deflate", 40 * DAY),
+            ("1000000000_old_vs_b.sim.log", 40 * DAY),
+            ("1000000001_cup_vs_b.json.deflate", 40 * DAY),
+            ("1000000002_hist_vs_b.json.deflate", 40 * DAY),
+            ("1000000003_new_vs_b.json.deflate", DAY),
+            ("crash_2001-09-09_014640/crash.json", 40 * DAY),
+            ("crash_2001-09-10_014640/crash.json", DAY),
+            ("crash_notes/notes.txt", 40 * DAY),
+            ("notes.json.deflate", 40 * DAY),
+        ] {
+            write(&dir.join(file), "", age)?;
+        }
+        write(
+            &dir.join("1000000001_cup_vs_b.result.json"),
+            &labeled,
+            40 * DAY,
+        )?;
+        let history = [BattleRecord {
+            label: Some("ladder".to_owned()),
+            replay: dir.join("1000000002_hist_vs_b.json.deflate"),
+            ..BattleRecord::default()
+        }];
+
+        let cutoff = SystemTime::now() - 30 * DAY;
+        let names = |keep_labeled| -> anyhow::Result<Vec<String>> {
+            Ok(prunable(dir, cutoff, keep_labeled, &history)?
+                .into_iter()
+                .map(|prunable| prunable.name)
+                .collect())
+        };
+        assert_eq!(
+            names(true)?,
+            ["1000000000_old_vs_b", "crash_2001-09-09_014640"]
+        );
+        assert_eq!(
+            names(false)?,
+            [
+                "1000000000_old_vs_b",
+                "1000000001_cup_vs_b",
+                "1000000002_hist_vs_b",
+                "crash_2001-09-09_014640"
+            ]
+        );
+        let old = &prunable(dir, cutoff, true, &history)?[0];
+        assert_eq!(
+            old.paths,
+            [
+                dir.join("1000000000_old_vs_b.json.deflate"),
+                dir.join("1000000000_old_vs_b.sim.log")
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Elo ratings of every fleet build that's played a rated battle, kept in a state file with the
+//! fleets so they carry on from run to run.
+
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::elo::{score, update};
+use crate::fleet_output_base_path;
+use crate::hash::short_hash;
+use crate::results::BattleRecord;
+
+/// The file name of the ratings, next to the battle history.
+pub const RATINGS_FILE: &str = "ratings.json";
+
+/// How much a game can move a rating, unless it's set with `--k-factor`.
+pub const DEFAULT_K_FACTOR: u32 = 32;
+
+/// What a fleet's first rating is, unless it's set with `--initial-rating`.
+pub const DEFAULT_INITIAL_RATING: u32 = 1500;
+
+pub fn default_ratings_path() -> anyhow::Result<PathBuf> {
+    Ok(fleet_output_base_path()?.join(RATINGS_FILE))
+}
+
+/// How rated battles update the ratings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingOptions {
+    pub path: PathBuf,
+    pub k_factor: f64,
+    pub initial_rating: f64,
+}
+
+/// One build of a fleet's rating.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub fleet: String,
+    /// SHA-256 of the build's wasm, so a rebuild starts from the initial rating.
+    pub sha256: String,
+    pub rating: f64,
+    /// Rated games played.
+    pub games: u32,
+    /// The highest it's been.
+    pub peak: f64,
+}
+
+impl Rating {
+    /// What the build is called in the ratings, like `glass_cannon@1a2b3c4d`.
+    pub fn identity(&self) -> String {
+        format!("{}@{}", self.fleet, short_hash(&self.sha256))
+    }
+}
+
+/// Every build's rating.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RatingTable {
+    pub ratings: Vec<Rating>,
+}
+
+impl RatingTable {
+    pub fn get(&self, fleet: &str, sha256: &str) -> Option<&Rating> {
+        self.ratings
+            .iter()
+            .find(|rating| rating.fleet == fleet && rating.sha256 == sha256)
+    }
+
+    fn get_or_insert(&mut self, fleet: &str, sha256: &str, initial_rating: f64) -> &mut Rating {
+        let index = match self
+            .ratings
+            .iter()
+            .position(|rating| rating.fleet == fleet && rating.sha256 == sha256)
+        {
+            Some(index) => index,
+            None => {
+                self.ratings.push(Rating {
+                    fleet: fleet.to_owned(),
+                    sha256: sha256.to_owned(),
+                    rating: initial_rating,
+                    games: 0,
+                    peak: initial_rating,
+                });
+                self.ratings.len() - 1
+            }
+        };
+        &mut self.ratings[index]
+    }
+
+    /// Rates a battle, returning each side's rating before and after. Battles that don't say who's
+    /// better, like crashes, and ones without the fleets' hashes aren't rated.
+    pub fn rate(
+        &mut self,
+        record: &BattleRecord,
+        k_factor: f64,
+        initial_rating: f64,
+    ) -> Option<[(f64, f64); 2]> {
+        let score = score(record.outcome)?;
+        let sides = [
+            (&record.fleet_a, record.fleet_a_sha256.as_ref()?),
+            (&record.fleet_b, record.fleet_b_sha256.as_ref()?),
+        ];
+        let before =
+            sides.map(|(fleet, sha256)| self.get_or_insert(fleet, sha256, initial_rating).rating);
+        let after = update(before, score, k_factor);
+        for ((fleet, sha256), rating) in sides.into_iter().zip(after) {
+            let entry = self.get_or_insert(fleet, sha256, initial_rating);
+            entry.rating = rating;
+            entry.games += 1;
+            entry.peak = entry.peak.max(rating);
+        }
+        Some([(before[0], after[0]), (before[1], after[1])])
+    }
+
+    /// The ratings, highest first.
+    pub fn ranked(&self) -> Vec<&Rating> {
+        let mut ranked: Vec<&Rating> = self.ratings.iter().collect();
+        ranked.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        ranked
+    }
+}
+
+/// Reads the ratings, which are empty before the first rated battle.
+pub fn read_ratings(path: &Path) -> anyhow::Result<RatingTable> {
+    if !path.exists() {
+        return Ok(RatingTable::default());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("trying to read ratings {path:?}"))?;
+    parse(&contents, path)
+}
+
+fn parse(contents: &str, path: &Path) -> anyhow::Result<RatingTable> {
+    if contents.trim().is_empty() {
+        return Ok(RatingTable::default());
+    }
+    serde_json::from_str(contents).with_context(|| format!("trying to parse ratings {path:?}"))
+}
+
+/// Rates a battle in the ratings file, returning each side's rating before and after.
+///
+/// The file's locked from reading it to writing it back, so battles finishing at once, in a
+/// tournament with `--jobs` or separate runs, don't lose each other's updates.
+pub fn rate_battle(
+    options: &RatingOptions,
+    record: &BattleRecord,
+) -> anyhow::Result<Option<[(f64, f64); 2]>> {
+    let path = &options.path;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("trying to open ratings {path:?}"))?;
+    file.lock()
+        .with_context(|| format!("trying to lock ratings {path:?}"))?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .with_context(|| format!("trying to read ratings {path:?}"))?;
+    let mut table = parse(&contents, path)?;
+    let Some(change) = table.rate(record, options.k_factor, options.initial_rating) else {
+        return Ok(None);
+    };
+
+    let json = serde_json::to_string_pretty(&table).context("trying to serialize ratings")?;
+    file.set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| file.write_all(json.as_bytes()))
+        .with_context(|| format!("trying to write ratings {path:?}"))?;
+    Ok(Some(change))
+}
+
+/// A rating change to print, like `1516 (+16)`.
+pub fn describe_change((before, after): (f64, f64)) -> String {
+    format!("{:.0} ({:+.0})", after, after - before)
+}
+
+/// The ratings as a table to print, highest first.
+pub fn ratings_table(table: &RatingTable) -> String {
+    let ranked = table.ranked();
+    let identities: Vec<String> = ranked.iter().map(|rating| rating.identity()).collect();
+    let width = identities
+        .iter()
+        .map(String::len)
+        .chain(["Fleet".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!("  Rank  {:<width$}  Rating  Games  Peak", "Fleet")];
+    for (i, (rating, identity)) in ranked.iter().zip(&identities).enumerate() {
+        lines.push(format!(
+            "  {:<4}  {identity:<width$}  {:<6.0}  {:<5}  {:.0}",
+            i + 1,
+            rating.rating,
+            rating.games,
+            rating.peak
+        ));
+    }
+    format!("Elo ratings:\n{}", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rate_battle, ratings_table, read_ratings, RatingOptions};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+
+    fn battle(outcome: Outcome) -> BattleRecord {
+        BattleRecord {
+            fleet_a: "glass_cannon".to_owned(),
+            fleet_b: "iron_armada".to_owned(),
+            fleet_a_sha256: Some("1a2b3c4d5e".to_owned()),
+            fleet_b_sha256: Some("9f8e7d6c5b".to_owned()),
+            outcome,
+            ..BattleRecord::default()
+        }
+    }
+
+    #[test]
+    fn rated_battles_update_the_ratings_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = RatingOptions {
+            path: dir.path().join("ratings.json"),
+            k_factor: 32.0,
+            initial_rating: 1500.0,
+        };
+
+        let change = rate_battle(&options, &battle(Outcome::FleetAWon))?;
+        assert_eq!(change, Some([(1500.0, 1516.0), (1500.0, 1484.0)]));
+        assert_eq!(rate_battle(&options, &battle(Outcome::SimCrashed))?, None);
+        rate_battle(&options, &battle(Outcome::FleetBWon))?;
+
+        let table = read_ratings(&options.path)?;
+        let cannon = table.get("glass_cannon", "1a2b3c4d5e").unwrap();
+        assert_eq!(cannon.games, 2);
+        assert_eq!(cannon.peak, 1516.0);
+        assert!(cannon.rating < 1500.0);
+
+        // A rebuild is rated from scratch
+        let mut rebuilt = battle(Outcome::Draw);
+        rebuilt.fleet_a_sha256 = Some("0000aaaa".to_owned());
+        rate_battle(&options, &rebuilt)?;
+        let table = read_ratings(&options.path)?;
+        assert_eq!(table.ratings.len(), 3);
+        assert_eq!(
+            ratings_table(&table).lines().nth(2).unwrap(),
+            "  1     iron_armada@9f8e7d6c   1501    3      1501"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+//! Reference fleets: fixed sparring partners downloaded from an index, to measure a fleet's
+//! progress against. They're cached on this machine, and picked anywhere a fleet name goes as
+//! `@reference/<name>`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::curl::Get;
+use crate::fleet::Fleet;
+use crate::hash::{file_sha256, sha256};
+use crate::naming::sanitize_label;
+use crate::output::{dry_run, say};
+use crate::settings::settings;
+use crate::wasm::validate_wasm_file;
+
+/// The community-maintained index of reference fleets, unless `--index` or the `reference-index`
+/// setting says otherwise.
+pub const DEFAULT_INDEX: &str =
+    "https://raw.githubusercontent.com/Protologic/Reference/HEAD/index.toml";
+/// The namespace reference fleets are picked from, as in `@reference/tutorial-bot`.
+pub const NAMESPACE: &str = "reference";
+/// The last index fetched, kept in the cache so cached fleets can be checked and listed offline.
+const CACHED_INDEX: &str = "index.toml";
+
+/// Arguments for `cargo protologic fetch-reference`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct FetchReferenceArgs {
+    /// The reference fleets to fetch. Defaults to all of them.
+    names: Vec<String>,
+    /// Show the fleets in the index and which are cached, instead of fetching them.
+    #[arg(long, default_value = "false", conflicts_with = "names")]
+    list: bool,
+    /// The index to fetch from, as a URL or a path. Defaults to the `reference-index` setting, or the community index.
+    #[arg(long, value_name = "URL_OR_PATH")]
+    index: Option<String>,
+    /// Download the fleets again, even the ones that are cached already.
+    #[arg(long, default_value = "false", conflicts_with = "list")]
+    force: bool,
+}
+
+/// Where an index, or a fleet it lists, comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Url(String),
+    File(PathBuf),
+}
+
+impl Source {
+    /// A URL when it looks like one, and otherwise a path.
+    pub fn parse(source: &str) -> Self {
+        if source.contains("://") {
+            Self::Url(source.to_owned())
+        } else {
+            Self::File(PathBuf::from(source))
+        }
+    }
+
+    /// Where `location` points to, as the index at `self` lists it: a URL as it is, and otherwise
+    /// relative to the index.
+    pub fn join(&self, location: &str) -> Self {
+        match self {
+            _ if location.contains("://") => Self::Url(location.to_owned()),
+            Self::Url(url) => {
+                let dir = url.rsplit_once('/').map_or(url.as_str(), |(dir, _)| dir);
+                Self::Url(format!("{dir}/{location}"))
+            }
+            Self::File(path) => Self::File(path.parent().unwrap_or(Path::new(".")).join(location)),
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Url(url) => Get {
+                purpose: "fetch reference fleets",
+                ..Get::default()
+            }
+            .fetch(url),
+            Self::File(path) => {
+                std::fs::read(path).with_context(|| format!("trying to read {path:?}"))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(url) => f.write_str(url),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The reference fleets there are, from an index file.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    #[serde(rename = "fleet", default)]
+    pub fleets: Vec<IndexEntry>,
+}
+
+/// One fleet in the index.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// What it's picked by, as `@reference/<name>`.
+    pub name: String,
+    /// Where its wasm is, as a URL or relative to the index.
+    pub url: String,
+    /// The SHA-256 its wasm has to have.
+    pub sha256: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl Index {
+    /// Parses an index, checking every fleet has a name that can be a file name and a SHA-256.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let mut index: Self = toml::from_str(contents)?;
+        for entry in &mut index.fleets {
+            if sanitize_label(&entry.name).as_ref() != Some(&entry.name) {
+                anyhow::bail!(
+                    "the reference fleet name '{}' can only have letters, digits, `-`, `_` and `.`",
+                    entry.name
+                );
+            }
+            entry.sha256.make_ascii_lowercase();
+            if entry.sha256.len() != 64 || !entry.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                anyhow::bail!(
+                    "the SHA-256 of reference fleet '{}' should be 64 hex digits",
+                    entry.name
+                );
+            }
+        }
+        for (i, entry) in index.fleets.iter().enumerate() {
+            if index.fleets[..i]
+                .iter()
+                .any(|other| other.name == entry.name)
+            {
+                anyhow::bail!("the reference fleet '{}' is in the index twice", entry.name);
+            }
+        }
+        Ok(index)
+    }
+
+    fn entry(&self, name: &str) -> Option<&IndexEntry> {
+        self.fleets.iter().find(|entry| entry.name == name)
+    }
+
+    fn names(&self) -> String {
+        let names: Vec<&str> = self
+            .fleets
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        names.join(", ")
+    }
+}
+
+/// Where reference fleets are cached, so battling them works offline.
+fn reference_cache_dir() -> anyhow::Result<PathBuf> {
+    let dir = dirs::cache_dir().context("can't find your cache directory")?;
+    Ok(dir.join("cargo-protologic").join("reference"))
+}
+
+fn cached_fleet_path(cache: &Path, name: &str) -> PathBuf {
+    cache.join(format!("{name}.wasm"))
+}
+
+/// The index saved by the last fetch, if there's been one.
+fn cached_index(cache: &Path) -> anyhow::Result<Option<Index>> {
+    let path = cache.join(CACHED_INDEX);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("trying to read {path:?}"))?;
+    let index = Index::parse(&contents).with_context(|| format!("trying to parse {path:?}"))?;
+    Ok(Some(index))
+}
+
+/// The names of the cached fleets, sorted.
+fn cached_names(cache: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(cache)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let name = name.strip_suffix(".wasm")?;
+            (!name.starts_with('.')).then(|| name.to_owned())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// How a fleet in the index stands in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Cached,
+    /// Cached, but not the build the index lists any more.
+    Outdated,
+    Available,
+}
+
+fn status(cache: &Path, entry: &IndexEntry) -> anyhow::Result<Status> {
+    let path = cached_fleet_path(cache, &entry.name);
+    if !path.is_file() {
+        return Ok(Status::Available);
+    }
+    Ok(if file_sha256(&path)? == entry.sha256 {
+        Status::Cached
+    } else {
+        Status::Outdated
+    })
+}
+
+/// Downloads the fleets named in `names`, or all of them, from the index into the cache, checking
+/// each against the index's SHA-256. Fleets that are cached already are left alone unless `force`.
+pub fn fetch_into(
+    source: &Source,
+    cache: &Path,
+    names: &[String],
+    force: bool,
+) -> anyhow::Result<Index> {
+    let contents = source
+        .read()
+        .with_context(|| format!("trying to fetch the reference fleet index from {source}"))?;
+    let contents = String::from_utf8(contents).context("the reference fleet index isn't UTF-8")?;
+    let index = Index::parse(&contents)
+        .with_context(|| format!("trying to parse the reference fleet index from {source}"))?;
+
+    let entries = if names.is_empty() {
+        index.fleets.iter().collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                let name = name.strip_prefix(&format!("@{NAMESPACE}/")).unwrap_or(name);
+                index.entry(name).with_context(|| {
+                    format!(
+                        "there's no reference fleet '{name}' in the index, it has: {}",
+                        index.names()
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    };
+    if entries.is_empty() {
+        say!("The index at {source} doesn't have any reference fleets");
+    }
+
+    if dry_run() {
+        for entry in entries {
+            say!(
+                "Would download '{}' from {} to {:?}",
+                entry.name,
+                source.join(&entry.url),
+                cached_fleet_path(cache, &entry.name)
+            );
+        }
+        return Ok(index);
+    }
+    std::fs::create_dir_all(cache)
+        .with_context(|| format!("trying to create the reference fleet cache {cache:?}"))?;
+    std::fs::write(cache.join(CACHED_INDEX), &contents)
+        .with_context(|| format!("trying to save the reference fleet index in {cache:?}"))?;
+
+    let mut failed = Vec::new();
+    for entry in entries {
+        if !force && status(cache, entry)? == Status::Cached {
+            say!("'{}' is up to date", entry.name);
+            continue;
+        }
+        match fetch_fleet(source, cache, entry) {
+            Ok(()) => say!("Fetched @{NAMESPACE}/{}", entry.name),
+            Err(err) => {
+                say!("Warning: couldn't fetch '{}': {err:#}", entry.name);
+                failed.push(entry.name.as_str());
+            }
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("couldn't fetch {}", failed.join(", "));
+    }
+    Ok(index)
+}
+
+/// Downloads one fleet, only putting it in the cache once it matches the index.
+fn fetch_fleet(source: &Source, cache: &Path, entry: &IndexEntry) -> anyhow::Result<()> {
+    let bytes = source.join(&entry.url).read()?;
+    let actual = sha256(&bytes);
+    if actual != entry.sha256 {
+        anyhow::bail!(
+            "its SHA-256 is {actual}, but the index says it should be {}",
+            entry.sha256
+        );
+    }
+
+    let path = cached_fleet_path(cache, &entry.name);
+    // Hidden, so a half-written one isn't mistaken for a fleet
+    let partial = cache.join(format!(".{}.wasm.part", entry.name));
+    std::fs::write(&partial, &bytes).with_context(|| format!("trying to write {partial:?}"))?;
+    std::fs::rename(&partial, &path)
+        .with_context(|| format!("trying to move the download to {path:?}"))
+}
+
+/// Lists the fleets in the index and whether each is cached, along with cached fleets the index
+/// doesn't have any more.
+fn list(index: &Index, cache: &Path) -> anyhow::Result<()> {
+    let width = index
+        .fleets
+        .iter()
+        .map(|entry| entry.name.len())
+        .max()
+        .unwrap_or(0)
+        + NAMESPACE.len()
+        + 2;
+    for entry in &index.fleets {
+        let status = match status(cache, entry)? {
+            Status::Cached => "cached",
+            Status::Outdated => "outdated",
+            Status::Available => "available",
+        };
+        let name = format!("@{NAMESPACE}/{}", entry.name);
+        match &entry.description {
+            Some(description) => say!("  {name:<width$}  {status:<9}  {description}"),
+            None => say!("  {name:<width$}  {status}"),
+        }
+    }
+    for name in cached_names(cache) {
+        if index.entry(&name).is_none() {
+            say!(
+                "  {:<width$}  cached, but no longer in the index",
+                format!("@{NAMESPACE}/{name}")
+            );
+        }
+    }
+    say!("Fetch them with `cargo protologic fetch-reference`, and battle one with `run --vs @{NAMESPACE}/<name>`");
+    Ok(())
+}
+
+/// Fetches reference fleets from the index into the cache, or with `--list` shows what's there.
+pub fn fetch_reference(args: FetchReferenceArgs) -> anyhow::Result<()> {
+    let source = Source::parse(
+        &settings()?
+            .text("reference-index", args.index)
+            .unwrap_or_else(|| DEFAULT_INDEX.to_owned()),
+    );
+    let cache = reference_cache_dir()?;
+    if !args.list {
+        fetch_into(&source, &cache, &args.names, args.force)?;
+        return Ok(());
+    }
+
+    let index = match source.read() {
+        Ok(contents) => Index::parse(&String::from_utf8_lossy(&contents))
+            .with_context(|| format!("trying to parse the reference fleet index from {source}"))?,
+        Err(err) => {
+            let index = cached_index(&cache)?.with_context(|| {
+                format!("trying to fetch the reference fleet index from {source}, which hasn't been fetched before: {err:#}")
+            })?;
+            say!("Warning: couldn't fetch the index, so listing the last one fetched: {err:#}");
+            index
+        }
+    };
+    say!("Reference fleets in {source}:");
+    list(&index, &cache)
+}
+
+/// The fleet an `@`-prefixed name picks, like `@reference/tutorial-bot`, or `None` for any other
+/// name.
+pub fn find_namespaced(name: &str) -> Option<anyhow::Result<Fleet>> {
+    let namespaced = name.strip_prefix('@')?;
+    Some(reference_cache_dir().and_then(|cache| find_in(&cache, name, namespaced)))
+}
+
+fn find_in(cache: &Path, name: &str, namespaced: &str) -> anyhow::Result<Fleet> {
+    let fleet = match namespaced.split_once('/') {
+        Some((NAMESPACE, fleet)) => fleet,
+        _ => anyhow::bail!(
+            "'{name}' isn't a fleet name, names starting with `@` pick a reference fleet, like `@{NAMESPACE}/tutorial-bot`"
+        ),
+    };
+    let path = cached_fleet_path(cache, fleet);
+    if sanitize_label(fleet).as_deref() != Some(fleet) || !path.is_file() {
+        let cached = match cached_names(cache).as_slice() {
+            [] => "none are".to_owned(),
+            names => format!("the cached ones are {}", names.join(", ")),
+        };
+        anyhow::bail!(
+            "the reference fleet '{fleet}' hasn't been fetched, {cached}. Fetch it with `cargo protologic fetch-reference {fleet}`"
+        );
+    }
+
+    // Catches a fleet that was changed after it was fetched
+    if let Some(entry) = cached_index(cache)?
+        .as_ref()
+        .and_then(|index| index.entry(fleet))
+    {
+        if file_sha256(&path)? != entry.sha256 {
+            anyhow::bail!(
+                "the cached reference fleet '{fleet}' doesn't match the index, fetch it again with `cargo protologic fetch-reference --force {fleet}`"
+            );
+        }
+    }
+    validate_wasm_file(&path)
+        .with_context(|| format!("the cached reference fleet '{fleet}' is broken"))?;
+    Ok(Fleet {
+        name: name.to_owned(),
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{fetch_into, find_in, status, Index, Source, Status};
+    use crate::hash::sha256;
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn index_entry(name: &str, url: &str, sha256: &str) -> String {
+        format!("[[fleet]]\nname = \"{name}\"\nurl = \"{url}\"\nsha256 = \"{sha256}\"\n")
+    }
+
+    #[test]
+    fn fleets_are_relative_to_the_index() {
+        let url = Source::parse("https://example.com/fleets/index.toml");
+        assert_eq!(
+            url.join("bot.wasm"),
+            Source::Url("https://example.com/fleets/bot.wasm".to_owned())
+        );
+        assert_eq!(
+            url.join("https://elsewhere.com/bot.wasm"),
+            Source::Url("https://elsewhere.com/bot.wasm".to_owned())
+        );
+        assert_eq!(
+            Source::parse("refs/index.toml").join("bot.wasm"),
+            Source::File(Path::new("refs").join("bot.wasm"))
+        );
+    }
+
+    #[test]
+    fn indexes_are_checked() {
+        let hash = "A".repeat(64);
+        let index = Index::parse(&index_entry("tutorial-bot", "bot.wasm", &hash)).unwrap();
+        assert_eq!(index.fleets[0].sha256, "a".repeat(64));
+
+        let err = |contents: &str| Index::parse(contents).unwrap_err().to_string();
+        assert!(err(&index_entry("../bot", "bot.wasm", &hash)).contains("can only have"));
+        assert!(err(&index_entry("bot", "bot.wasm", "abc")).contains("64 hex digits"));
+        let twice = index_entry("bot", "a.wasm", &hash) + &index_entry("bot", "b.wasm", &hash);
+        assert!(err(&twice).contains("in the index twice"));
+    }
+
+    #[test]
+    fn fetched_fleets_are_verified_and_found() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = dir.path().join("cache");
+        std::fs::write(dir.path().join("bot.wasm"), WASM)?;
+        let index_path = dir.path().join("index.toml");
+        std::fs::write(
+            &index_path,
+            index_entry("bot", "bot.wasm", &sha256(WASM))
+                + &index_entry("liar", "bot.wasm", &"0".repeat(64)),
+        )?;
+        let source = Source::File(index_path);
+
+        let err = fetch_into(&source, &cache, &[], false).unwrap_err();
+        assert_eq!(err.to_string(), "couldn't fetch liar");
+        let index = fetch_into(&source, &cache, &["@reference/bot".to_owned()], false)?;
+        assert_eq!(status(&cache, &index.fleets[0])?, Status::Cached);
+        assert_eq!(status(&cache, &index.fleets[1])?, Status::Available);
+
+        let fleet = find_in(&cache, "@reference/bot", "reference/bot")?;
+        assert_eq!(fleet.name, "@reference/bot");
+        assert_eq!(fleet.path, PathBuf::from(&cache).join("bot.wasm"));
+        let err = |namespaced| format!("{:#}", find_in(&cache, "@x", namespaced).unwrap_err());
+        assert!(err("reference/liar").contains("the cached ones are bot"));
+        assert!(err("friends/bot").contains("isn't a fleet name"));
+
+        std::fs::write(cache.join("bot.wasm"), b"\0asm\x01\0\0\0\0")?;
+        assert_eq!(status(&cache, &index.fleets[0])?, Status::Outdated);
+        assert!(err("reference/bot").contains("doesn't match the index"));
+        Ok(())
+    }
+}
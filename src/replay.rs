@@ -0,0 +1,670 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context;
+use flate2::bufread::{DeflateDecoder, ZlibDecoder};
+
+use crate::archive::archived_fleets;
+use crate::config::find_battle_output_dir;
+use crate::output::{dry_run, say, shell_command};
+use crate::sim::{find_player, find_protologic_path, Player};
+use crate::ExitError;
+
+/// The file name of the pointer to the most recent replay, kept next to the replays.
+pub const LATEST_REPLAY: &str = "latest.json.deflate";
+/// The endings of files the player opens: replays as the sim writes them, and decompressed.
+const REPLAY_EXTENSIONS: &[&str] = &[".json.deflate", ".json"];
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReplayArgs {
+    /// The replay to open, or `latest` for the newest one in the battle output directory, which is also the default.
+    replay: Option<PathBuf>,
+    /// The battle output directory to find the newest replay in, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Decompress the replay into a plain `.json` next to it, instead of opening it in the player.
+    #[arg(long, default_value = "false")]
+    decompress: bool,
+    /// The location of the Protologic/Release repo, found the way `run` finds it when not given.
+    #[arg(long, env)]
+    protologic_path: Option<PathBuf>,
+    /// Wait for the player to close instead of leaving it running, and exit with its exit code if it fails.
+    #[arg(long, default_value = "false", conflicts_with = "decompress")]
+    player_wait: bool,
+    /// An extra argument for the player, put after the replay path. Can be given more than once.
+    #[arg(
+        long = "player-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        conflicts_with = "decompress"
+    )]
+    player_args: Vec<String>,
+    /// Run the Windows player through Wine, even when there's a native player. Off Windows, Wine is used anyway when there isn't one.
+    #[arg(long, default_value = "false", conflicts_with = "decompress")]
+    player_wine: bool,
+}
+
+/// Opens a replay from an earlier battle in the player, or decompresses it.
+pub fn replay(args: ReplayArgs) -> anyhow::Result<()> {
+    let replay = find_replay(args.replay, args.output_dir)?;
+    if let Ok([fleet_a, fleet_b]) = archived_fleets(&replay) {
+        say!(
+            "The fleets that battled are archived at {} and {}",
+            fleet_a.display(),
+            fleet_b.display()
+        );
+    }
+
+    if args.decompress {
+        if dry_run() {
+            say!("Would decompress {}", replay.display());
+            return Ok(());
+        }
+        let json = decompress_replay(&replay)?;
+        say!("Decompressed replay: {}", json.display());
+        return Ok(());
+    }
+
+    let protologic_path = find_protologic_path(args.protologic_path)?;
+    let player = find_player(&protologic_path, args.player_wine)?;
+    let command = player_command(&player, &replay, &args.player_args);
+    if dry_run() {
+        say!("Would open the player: {}", shell_command(&command));
+        return Ok(());
+    }
+    launch_player(command, args.player_wait)
+}
+
+/// The replay a subcommand was given, checked to be one, or the newest in the battle output
+/// directory for `None` or `latest`.
+pub fn find_replay(
+    replay: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+) -> anyhow::Result<PathBuf> {
+    match replay {
+        Some(path) if path != Path::new("latest") => check_replay(path),
+        _ => {
+            let (dir, _) = find_battle_output_dir(output_dir)?;
+            let replay = newest_replay(&dir)?.with_context(|| {
+                format!(
+                    "there are no replays in {}, battle with `cargo protologic run` first",
+                    dir.display()
+                )
+            })?;
+            say!("Newest replay: {}", replay.display());
+            Ok(replay)
+        }
+    }
+}
+
+/// Checks the replay exists and is named like one, so the player isn't left to fail on it.
+fn check_replay(path: PathBuf) -> anyhow::Result<PathBuf> {
+    if !path.is_file() {
+        anyhow::bail!("there's no replay at {}", path.display());
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if !REPLAY_EXTENSIONS
+        .iter()
+        .any(|extension| name.ends_with(extension))
+    {
+        anyhow::bail!(
+            "{} doesn't look like a replay, they end in `{}`",
+            path.display(),
+            REPLAY_EXTENSIONS.join("` or `")
+        );
+    }
+    Ok(path)
+}
+
+/// The most recently modified battle replay in `dir`, if there are any.
+pub fn newest_replay(dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let newest = battle_replays(dir)?.into_iter().max();
+    Ok(newest.map(|(_, base)| dir.join(format!("{base}.json.deflate"))))
+}
+
+/// Points `latest.json.deflate` in the replay's directory at `replay`, and returns its path.
+///
+/// The pointer is a symlink where possible, and a copy otherwise. Either way it's made under a
+/// temporary name and renamed into place, so anything reading it never sees half a replay.
+pub fn update_latest_replay(replay: &Path) -> anyhow::Result<PathBuf> {
+    static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+    let dir = replay.parent().unwrap_or(Path::new("."));
+    let latest = dir.join(LATEST_REPLAY);
+    // Unique to this call, since a tournament's games can finish at once on several threads
+    let temp = dir.join(format!(
+        ".{LATEST_REPLAY}.{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // A stale temp file from a crashed run would make the symlink fail
+    let _ = std::fs::remove_file(&temp);
+    if !link_replay(replay, &temp) {
+        // Created new, so the copy can never go through a link someone else made into their replay
+        let copied = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .and_then(|mut file| std::io::copy(&mut File::open(replay)?, &mut file));
+        if let Err(err) = copied {
+            let _ = std::fs::remove_file(&temp);
+            return Err(err).with_context(|| format!("trying to copy {replay:?} to {temp:?}"));
+        }
+    }
+
+    if let Err(err) = std::fs::rename(&temp, &latest) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("trying to point {latest:?} at {replay:?}"));
+    }
+
+    Ok(latest)
+}
+
+/// Inflates a `.json.deflate` replay into a plain `.json` next to it, and returns its path.
+///
+/// Replays can be hundreds of megabytes, so this streams rather than reading the whole thing in.
+pub fn decompress_replay(replay: &Path) -> anyhow::Result<PathBuf> {
+    let file_name = replay
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("{replay:?} isn't a replay file"))?;
+    let json_name = file_name
+        .strip_suffix(".deflate")
+        .with_context(|| format!("{replay:?} isn't a `.json.deflate` replay"))?;
+    let json = replay.with_file_name(json_name);
+    let temp = replay.with_file_name(format!(".{json_name}.{}.tmp", std::process::id()));
+
+    let mut decoder = open_replay(replay)?;
+    let mut output =
+        BufWriter::new(File::create(&temp).with_context(|| format!("trying to create {temp:?}"))?);
+
+    let copied = std::io::copy(&mut decoder, &mut output)
+        .with_context(|| format!("trying to decompress replay {replay:?}"))
+        .and_then(|_| {
+            output
+                .flush()
+                .context("trying to write the decompressed replay")
+        });
+    if let Err(err) = copied {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err);
+    }
+
+    std::fs::rename(&temp, &json).with_context(|| format!("trying to write {json:?}"))?;
+    Ok(json)
+}
+
+/// Opens a replay to read its JSON, inflating it unless it's already been decompressed. The sim
+/// writes raw deflate, though some versions wrapped it in zlib, so both are accepted.
+pub fn open_replay(replay: &Path) -> anyhow::Result<Box<dyn Read>> {
+    let file = File::open(replay).with_context(|| format!("trying to open replay {replay:?}"))?;
+    let mut input = BufReader::new(file);
+    if replay
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        return Ok(Box::new(input));
+    }
+    let zlib = is_zlib_header(
+        input
+            .fill_buf()
+            .with_context(|| format!("trying to read replay {replay:?}"))?,
+    );
+    Ok(if zlib {
+        Box::new(ZlibDecoder::new(input))
+    } else {
+        Box::new(DeflateDecoder::new(input))
+    })
+}
+
+/// Whether the data starts with a zlib header, rather than being a raw deflate stream.
+pub fn is_zlib_header(data: &[u8]) -> bool {
+    match data {
+        // Deflate with a window of at most 32K, and a check value making the pair a multiple of 31
+        [cmf, flg, ..] => {
+            cmf & 0x0f == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Builds the command to open a replay in the player, with any extra player arguments after it.
+pub fn player_command(player: &Player, replay: &Path, player_args: &[String]) -> Command {
+    if !player.wine {
+        let mut command = Command::new(&player.path);
+        command.arg(replay).args(player_args);
+        return command;
+    }
+
+    let mut command = Command::new("wine");
+    command
+        .arg(&player.path)
+        .arg(wine_path(replay))
+        .args(player_args);
+    command
+}
+
+/// Starts the player with `command`, from [`player_command`]. With `wait`, it waits for the player
+/// to close and fails with the player's exit code if it does, and otherwise leaves it running.
+pub fn launch_player(mut command: Command, wait: bool) -> anyhow::Result<()> {
+    if wait {
+        say!("Starting the protologic player! Waiting for it to close...");
+    } else {
+        say!("Starting the protologic player! The command will exit now.");
+    }
+    say!("Command to open player: {:?}", command);
+
+    let mut player = command.spawn().with_context(|| {
+        format!(
+            "couldn't start the protologic player at {:?}",
+            command.get_program()
+        )
+    })?;
+    if !wait {
+        return Ok(());
+    }
+
+    let status = player
+        .wait()
+        .context("trying to wait until the protologic player has closed")?;
+    if !status.success() {
+        // Exit codes outside of what we can exit with still need to fail
+        let code = status
+            .code()
+            .and_then(|code| u8::try_from(code).ok())
+            .filter(|&code| code != 0)
+            .unwrap_or(1);
+        return Err(
+            ExitError::new(code, format!("the protologic player exited with {status}")).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Translates a path into one the Windows player can open under Wine.
+fn wine_path(path: &Path) -> OsString {
+    let translated = Command::new("winepath")
+        .arg("-w")
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|stdout| stdout.trim().to_owned())
+        .filter(|stdout| !stdout.is_empty());
+
+    // Wine maps the root of the filesystem to `Z:` by default, which is good enough without winepath
+    translated.map_or_else(
+        || format!("Z:{}", path.display()).replace('/', "\\").into(),
+        OsString::from,
+    )
+}
+
+/// Opens the OS file manager at the replay's directory, with the replay selected where possible.
+pub fn open_folder(replay: &Path) -> anyhow::Result<()> {
+    folder_command(replay)?
+        .spawn()
+        .with_context(|| format!("trying to open the file manager at {replay:?}"))?;
+    Ok(())
+}
+
+fn folder_command(replay: &Path) -> anyhow::Result<Command> {
+    #[cfg(target_os = "windows")]
+    {
+        // Explorer wants `/select,<path>` as a single argument, so it can't go through `arg`
+        use std::os::windows::process::CommandExt;
+
+        let mut command = Command::new("explorer.exe");
+        command.raw_arg(format!("/select,\"{}\"", replay.display()));
+        Ok(command)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(replay);
+        Ok(command)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // xdg-open can't select a file, so settle for its directory
+        let dir = replay
+            .parent()
+            .context("the replay path doesn't have a directory")?;
+        let mut command = Command::new("xdg-open");
+        command.arg(dir);
+        Ok(command)
+    }
+}
+
+/// Deletes all but the newest `keep` replays in `dir`, along with their result JSON and logs, and returns
+/// what was deleted.
+///
+/// Only files named like this tool names replays are touched, anything else in `dir` is left alone.
+pub fn prune_replays(dir: &Path, keep: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let mut replays = battle_replays(dir)?;
+    // Newest first, with the name breaking ties so battles from the same second stay in order
+    replays.sort_by(|a, b| b.cmp(a));
+
+    let mut deleted = Vec::new();
+    for (_, base) in replays.into_iter().skip(keep) {
+        for path in battle_files(dir, &base) {
+            match std::fs::remove_file(&path) {
+                Ok(()) => deleted.push(path),
+                // Another battle pruning the same directory at once got to it first
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("trying to delete old replay file {path:?}"))
+                }
+            }
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Every file battles left in `dir`: the replays with their result JSON and logs, and the pointer
+/// to the latest replay. Like [`prune_replays`], it only picks out files named like ours.
+pub fn replay_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut replays = battle_replays(dir)?;
+    replays.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let mut files: Vec<PathBuf> = replays
+        .iter()
+        .flat_map(|(_, base)| battle_files(dir, base))
+        .collect();
+    let latest = dir.join(LATEST_REPLAY);
+    if latest.symlink_metadata().is_ok() {
+        files.push(latest);
+    }
+    Ok(files)
+}
+
+/// The base names of the battle replays in `dir`, with when each was last modified.
+pub fn battle_replays(dir: &Path) -> anyhow::Result<Vec<(std::time::SystemTime, String)>> {
+    let mut replays = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("trying to read {dir:?}"))? {
+        let entry = entry.with_context(|| format!("trying to read an entry of {dir:?}"))?;
+        let name = entry.file_name();
+        let Some(base) = name.to_str().and_then(replay_base_name) else {
+            continue;
+        };
+
+        // Not following symlinks, so a link someone made to an old replay doesn't count as new
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            // Deleted since the directory was listed, like by another battle pruning it
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).with_context(|| format!("trying to read {name:?}")),
+        };
+        if metadata.is_file() {
+            replays.push((metadata.modified()?, base.to_owned()));
+        }
+    }
+    Ok(replays)
+}
+
+/// The files one battle wrote to `dir` that are still there.
+pub fn battle_files(dir: &Path, base: &str) -> Vec<PathBuf> {
+    [
+        dir.join(format!("{base}.json.deflate")),
+        dir.join(format!("{base}.json")),
+        dir.join(format!("{base}.result.json")),
+        dir.join(format!("{base}.sim.log")),
+        dir.join(format!("{base}.fleet_a.log")),
+        dir.join(format!("{base}.fleet_b.log")),
+    ]
+    .into_iter()
+    .filter(|path| path.exists())
+    .collect()
+}
+
+/// The base name of a replay file from a battle, like `2024-06-15_183323_a_vs_b` for
+/// `2024-06-15_183323_a_vs_b.json.deflate`, or `None` if it isn't named like one.
+fn replay_base_name(file_name: &str) -> Option<&str> {
+    let base = file_name.strip_suffix(".json.deflate")?;
+    let digits = |s: &str, len: Option<usize>| {
+        !s.is_empty()
+            && len.is_none_or(|len| s.len() == len)
+            && s.bytes().all(|b| b.is_ascii_digit())
+    };
+
+    let (first, rest) = base.split_once('_')?;
+    let rest = match first.split('-').collect::<Vec<_>>()[..] {
+        // A `YYYY-MM-DD_HHMMSS` local or UTC time
+        [year, month, day] => {
+            let (time, rest) = rest.split_once('_')?;
+            (digits(year, Some(4))
+                && digits(month, Some(2))
+                && digits(day, Some(2))
+                && digits(time, Some(6)))
+            .then_some(rest)?
+        }
+        // Unix epoch seconds
+        _ => digits(first, None).then_some(rest)?,
+    };
+
+    (!rest.is_empty()).then_some(base)
+}
+
+#[cfg(unix)]
+fn link_replay(replay: &Path, link: &Path) -> bool {
+    // Relative to the link, so the directory can be moved around without breaking it
+    let target = replay.file_name().map_or(replay, Path::new);
+    std::os::unix::fs::symlink(target, link).is_ok()
+}
+
+#[cfg(not(unix))]
+fn link_replay(_replay: &Path, _link: &Path) -> bool {
+    // Symlinks on Windows need developer mode or admin rights, so always copy there
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::{DeflateEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    use super::{
+        check_replay, decompress_replay, newest_replay, prune_replays, replay_base_name,
+        update_latest_replay, LATEST_REPLAY,
+    };
+
+    #[test]
+    fn decompress_replay_handles_raw_deflate_and_zlib() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let json = br#"{"ticks": [{"ships": []}]}"#.repeat(100);
+
+        let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+        raw.write_all(&json)?;
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&json)?;
+
+        for (name, compressed) in [("raw", raw.finish()?), ("zlib", zlib.finish()?)] {
+            let replay = dir.path().join(format!("{name}.json.deflate"));
+            std::fs::write(&replay, compressed)?;
+            let decompressed = decompress_replay(&replay)?;
+            assert_eq!(decompressed, dir.path().join(format!("{name}.json")));
+            assert_eq!(std::fs::read(decompressed)?, json);
+        }
+
+        let broken = dir.path().join("broken.json.deflate");
+        std::fs::write(&broken, b"\xff\xff not a replay")?;
+        assert!(decompress_replay(&broken).is_err());
+        assert!(!dir.path().join("broken.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn update_latest_replay_follows_the_newest_replay() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let first = dir.path().join("first.json.deflate");
+        let second = dir.path().join("second.json.deflate");
+        std::fs::write(&first, "first")?;
+        std::fs::write(&second, "second")?;
+
+        let latest = update_latest_replay(&first)?;
+        assert_eq!(latest, dir.path().join(LATEST_REPLAY));
+        assert_eq!(std::fs::read_to_string(&latest)?, "first");
+
+        update_latest_replay(&second)?;
+        assert_eq!(std::fs::read_to_string(&latest)?, "second");
+        Ok(())
+    }
+
+    #[test]
+    fn battles_finishing_at_once_keep_their_own_replays() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replays: Vec<_> = (0..8)
+            .map(|i| {
+                let replay = dir.path().join(format!("171847640{i}_a_vs_b.json.deflate"));
+                std::fs::write(&replay, format!("game {i}"))?;
+                Ok(replay)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        std::thread::scope(|scope| {
+            let battles: Vec<_> = replays
+                .iter()
+                .map(|replay| {
+                    scope.spawn(move || -> anyhow::Result<()> {
+                        update_latest_replay(replay)?;
+                        prune_replays(replay.parent().unwrap(), 4)?;
+                        Ok(())
+                    })
+                })
+                .collect();
+            battles
+                .into_iter()
+                .try_for_each(|battle| battle.join().unwrap())
+        })?;
+
+        for (i, replay) in replays.iter().enumerate() {
+            if replay.exists() {
+                assert_eq!(std::fs::read_to_string(replay)?, format!("game {i}"));
+            }
+        }
+        let temps = std::fs::read_dir(dir.path())?
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .is_ok_and(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            })
+            .count();
+        assert_eq!(temps, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn replay_base_name_only_matches_battle_replays() {
+        assert_eq!(
+            replay_base_name("2024-06-15_183323_a_vs_b.json.deflate"),
+            Some("2024-06-15_183323_a_vs_b")
+        );
+        assert_eq!(
+            replay_base_name("1718476403_a_b_game1.json.deflate"),
+            Some("1718476403_a_b_game1")
+        );
+        assert_eq!(replay_base_name(LATEST_REPLAY), None);
+        assert_eq!(replay_base_name("my_notes.json.deflate"), None);
+        assert_eq!(
+            replay_base_name("2024-06-15_183323_a_vs_b.result.json"),
+            None
+        );
+    }
+
+    #[test]
+    fn prune_replays_keeps_the_newest() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let files = [
+            "1718476401_a_vs_b.json.deflate",
+            "1718476401_a_vs_b.result.json",
+            "1718476402_a_vs_b.json.deflate",
+            "1718476403_a_vs_b.json.deflate",
+            "1718476403_a_vs_b.result.json",
+            "keep_me.json.deflate",
+        ];
+        for (i, file) in files.iter().enumerate() {
+            let path = dir.path().join(file);
+            std::fs::write(&path, "")?;
+            let modified =
+                std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(i as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(modified)?;
+        }
+
+        let deleted = prune_replays(dir.path(), 1)?;
+        assert_eq!(deleted.len(), 3);
+
+        let mut left: Vec<_> = std::fs::read_dir(dir.path())?
+            .map(|entry| Ok(entry?.file_name().into_string().unwrap()))
+            .collect::<anyhow::Result<_>>()?;
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "1718476403_a_vs_b.json.deflate",
+                "1718476403_a_vs_b.result.json",
+                "keep_me.json.deflate"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn newest_replay_goes_by_modified_time() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(newest_replay(dir.path())?, None);
+
+        let older = dir.path().join("1718476499_a_vs_b.json.deflate");
+        let newer = dir.path().join("1718476403_b_vs_a.json.deflate");
+        for (i, path) in [&older, &newer].into_iter().enumerate() {
+            std::fs::write(path, "")?;
+            let modified =
+                std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(i as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(modified)?;
+        }
+        std::fs::write(dir.path().join("my_notes.json.deflate"), "")?;
+
+        assert_eq!(newest_replay(dir.path())?, Some(newer));
+        Ok(())
+    }
+
+    #[test]
+    fn check_replay_wants_a_replay_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replay = dir.path().join("1718476403_a_vs_b.json.deflate");
+        std::fs::write(&replay, "")?;
+        assert_eq!(check_replay(replay.clone())?, replay);
+
+        let log = dir.path().join("1718476403_a_vs_b.sim.log");
+        std::fs::write(&log, "")?;
+        let err = check_replay(log).unwrap_err();
+        assert!(
+            err.to_string().contains("doesn't look like a replay"),
+            "{err}"
+        );
+
+        let err = check_replay(dir.path().join("missing.json.deflate")).unwrap_err();
+        assert!(err.to_string().starts_with("there's no replay at"), "{err}");
+        Ok(())
+    }
+}
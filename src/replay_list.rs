@@ -0,0 +1,341 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+
+use crate::config::find_battle_output_dir;
+use crate::history::{read_history, HISTORY_FILE};
+use crate::outcome::Outcome;
+use crate::output::say;
+use crate::replay::battle_replays;
+use crate::results::{result_path, BattleRecord};
+use crate::FLEET_OUTPUT_DIR;
+
+/// A past battle, from its replay, its result JSON or the battle history.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+    /// Unix timestamp (in seconds) of when the battle started, when it's known.
+    pub timestamp: Option<u64>,
+    pub fleet_a: Option<String>,
+    pub fleet_b: Option<String>,
+    pub outcome: Option<Outcome>,
+    pub winner: Option<String>,
+    pub label: Option<String>,
+    /// The replay's size in bytes, or `None` when it's been deleted.
+    pub size: Option<u64>,
+    pub replay: PathBuf,
+    /// Whether the battle history remembers this battle, but its replay is gone.
+    pub deleted: bool,
+}
+
+impl ReplayEntry {
+    fn from_record(record: BattleRecord, size: Option<u64>) -> Self {
+        Self {
+            timestamp: Some(record.timestamp),
+            fleet_a: Some(record.fleet_a),
+            fleet_b: Some(record.fleet_b),
+            outcome: Some(record.outcome),
+            winner: record.winner,
+            label: record.label,
+            deleted: size.is_none(),
+            size,
+            replay: record.replay,
+        }
+    }
+
+    /// An entry from what the replay's file name says, for replays without a result JSON.
+    fn from_name(base: &str, replay: PathBuf, size: u64) -> Self {
+        let (timestamp, rest) = parse_battle_name(base);
+        let (fleet_a, fleet_b) = match rest.split_once("_vs_") {
+            Some((a, b)) => (Some(a.to_owned()), Some(b.to_owned())),
+            None => (None, None),
+        };
+        Self {
+            timestamp,
+            fleet_a,
+            fleet_b,
+            outcome: None,
+            winner: None,
+            label: None,
+            size: Some(size),
+            replay,
+            deleted: false,
+        }
+    }
+
+    pub fn describe_fleets(&self) -> String {
+        match (&self.fleet_a, &self.fleet_b) {
+            (Some(a), Some(b)) => format!("{a} vs {b}"),
+            _ => self
+                .replay
+                .file_name()
+                .map(|name| {
+                    name.to_string_lossy()
+                        .trim_end_matches(".json.deflate")
+                        .to_owned()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn describe_winner(&self) -> String {
+        match (self.outcome, &self.winner) {
+            (Some(Outcome::FleetAWon | Outcome::FleetBWon), Some(winner)) => winner.clone(),
+            (Some(Outcome::Draw), _) => "draw".to_owned(),
+            (Some(Outcome::SimCrashed), _) => "sim crashed".to_owned(),
+            _ => "-".to_owned(),
+        }
+    }
+}
+
+/// Splits a battle's base name into when it started and the rest of it, understanding both
+/// timestamp formats: Unix seconds like `1718476403_a_vs_b`, and dates like
+/// `2024-06-15_183323_a_vs_b`, which are taken as local time.
+pub fn parse_battle_name(base: &str) -> (Option<u64>, &str) {
+    let Some((first, rest)) = base.split_once('_') else {
+        return (None, base);
+    };
+    if let Ok(epoch) = first.parse::<u64>() {
+        return (Some(epoch), rest);
+    }
+
+    let Some((time, rest)) = rest.split_once('_') else {
+        return (None, base);
+    };
+    let datetime = NaiveDateTime::parse_from_str(&format!("{first}_{time}"), "%Y-%m-%d_%H%M%S")
+        .ok()
+        .and_then(|datetime| Local.from_local_datetime(&datetime).earliest())
+        .and_then(|datetime| u64::try_from(datetime.timestamp()).ok());
+    match datetime {
+        Some(timestamp) => (Some(timestamp), rest),
+        None => (None, base),
+    }
+}
+
+/// Every past battle in `dir` and the battle history at `history`, newest first. Battles in both
+/// are only listed once.
+pub fn find_replays(dir: &Path, history: Option<&Path>) -> anyhow::Result<Vec<ReplayEntry>> {
+    let mut entries = Vec::new();
+    if dir.is_dir() {
+        for (modified, base) in battle_replays(dir)? {
+            let replay = dir.join(format!("{base}.json.deflate"));
+            let size = std::fs::metadata(&replay).map_or(0, |metadata| metadata.len());
+            let mut entry = match BattleRecord::read_json(&result_path(&dir.join(&base))) {
+                Ok(record) => ReplayEntry::from_record(record, Some(size)),
+                Err(_) => ReplayEntry::from_name(&base, replay.clone(), size),
+            };
+            entry.replay = replay;
+            entry.timestamp = entry.timestamp.or_else(|| {
+                let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+                Some(since_epoch.as_secs())
+            });
+            entries.push(entry);
+        }
+    }
+
+    let records = match history {
+        Some(path) if path.is_file() => read_history(path)?,
+        _ => Vec::new(),
+    };
+    let listed: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| same_file(&entry.replay))
+        .collect();
+    for record in records {
+        let replay = same_file(&record.replay);
+        if listed.contains(&replay) {
+            continue;
+        }
+        let size = std::fs::metadata(&replay)
+            .ok()
+            .map(|metadata| metadata.len());
+        entries.push(ReplayEntry::from_record(record, size));
+    }
+
+    // Newest first, and the name keeps battles from the same second in a stable order
+    entries.sort_by(|a, b| (b.timestamp, &b.replay).cmp(&(a.timestamp, &a.replay)));
+    Ok(entries)
+}
+
+/// A path to compare with others for whether they're the same file, whether or not it still
+/// exists.
+fn same_file(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
+/// The battles as a table to print.
+pub fn replay_table(entries: &[ReplayEntry]) -> String {
+    let rows: Vec<[String; 6]> = entries
+        .iter()
+        .map(|entry| {
+            let date = entry
+                .timestamp
+                .and_then(|timestamp| i64::try_from(timestamp).ok())
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                .map_or("-".to_owned(), |datetime| {
+                    datetime
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                });
+            let size = entry
+                .size
+                .map_or("deleted".to_owned(), |size| ByteSize::b(size).to_string());
+            [
+                date,
+                entry.describe_fleets(),
+                entry.describe_winner(),
+                entry.label.clone().unwrap_or_else(|| "-".to_owned()),
+                size,
+                entry.replay.display().to_string(),
+            ]
+        })
+        .collect();
+
+    let headers = ["Date", "Fleets", "Winner", "Label", "Size", "Replay"];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut table = String::new();
+    for row in [headers.map(str::to_owned)].iter().chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    table
+}
+
+/// Lists past battles, newest first, as a table or with `json` as JSON on stdout.
+pub fn list_replays(
+    output_dir: Option<PathBuf>,
+    history_file: Option<PathBuf>,
+    limit: usize,
+    json: bool,
+) -> anyhow::Result<()> {
+    let (dir, _) = find_battle_output_dir(output_dir)?;
+    let history = history_file.unwrap_or_else(|| Path::new(FLEET_OUTPUT_DIR).join(HISTORY_FILE));
+    let mut entries = find_replays(&dir, Some(&history))?;
+    let total = entries.len();
+    entries.truncate(limit);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        say!(
+            "No battles in {} or the battle history yet, battle with `cargo protologic run`",
+            dir.display()
+        );
+        return Ok(());
+    }
+    say!("{}", replay_table(&entries).trim_end());
+    if total > entries.len() {
+        say!(
+            "Showing the newest {} of {total} battles, pass `--limit` to see more",
+            entries.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{find_replays, parse_battle_name, replay_table};
+    use crate::history::append_history;
+    use crate::outcome::Outcome;
+    use crate::results::{result_path, BattleRecord};
+
+    #[test]
+    fn parse_battle_name_understands_both_timestamps() {
+        assert_eq!(
+            parse_battle_name("1718476403_a_vs_b"),
+            (Some(1718476403), "a_vs_b")
+        );
+        let (timestamp, rest) = parse_battle_name("2024-06-15_183323_a_vs_b_duel");
+        assert!(timestamp.is_some());
+        assert_eq!(rest, "a_vs_b_duel");
+        assert_eq!(parse_battle_name("notes"), (None, "notes"));
+    }
+
+    #[test]
+    fn find_replays_merges_results_history_and_names() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+
+        // A battle with a result, one with only a name to go on, and one whose replay is gone
+        let replay = dir.join("1718476403_iron_armada_vs_glass_cannon.json.deflate");
+        std::fs::write(&replay, "replay")?;
+        let record = BattleRecord {
+            timestamp: 1718476403,
+            fleet_a: "iron_armada".to_owned(),
+            fleet_b: "glass_cannon".to_owned(),
+            outcome: Outcome::FleetBWon,
+            winner: Some("glass_cannon".to_owned()),
+            label: Some("duel".to_owned()),
+            replay: replay.clone(),
+            ..Default::default()
+        };
+        record.write_json(&result_path(
+            &dir.join("1718476403_iron_armada_vs_glass_cannon"),
+        ))?;
+        std::fs::write(dir.join("1718476500_a_vs_b.json.deflate"), "")?;
+
+        let history = dir.join("battle_history.jsonl");
+        append_history(&history, &record)?;
+        append_history(
+            &history,
+            &BattleRecord {
+                timestamp: 1718476300,
+                fleet_a: "a".to_owned(),
+                fleet_b: "b".to_owned(),
+                outcome: Outcome::Draw,
+                replay: dir.join("1718476300_a_vs_b.json.deflate"),
+                ..Default::default()
+            },
+        )?;
+
+        let entries = find_replays(dir, Some(&history))?;
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.timestamp, entry.fleet_b.as_deref(), entry.deleted))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some(1718476500), Some("b"), false),
+                (Some(1718476403), Some("glass_cannon"), false),
+                (Some(1718476300), Some("b"), true),
+            ]
+        );
+        assert_eq!(entries[1].size, Some(6));
+
+        let table = replay_table(&entries[1..]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("Date"), "{table}");
+        for cell in ["iron_armada vs glass_cannon", "glass_cannon", "duel", "6 B"] {
+            assert!(lines[1].contains(cell), "{table}");
+        }
+        for cell in ["a vs b", "draw", "deleted"] {
+            assert!(lines[2].contains(cell), "{table}");
+        }
+        assert!(!Path::new(&entries[2].replay).exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fleet::Fleet;
+use crate::hash::short_hash;
+use crate::outcome::Outcome;
+use crate::output::shell_quote;
+use crate::results::BattleRecord;
+use crate::series::{Game, Score};
+use crate::sim::format_duration;
+
+/// What `--report` writes.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A short document to paste into a chat, with a table of the battles.
+    Markdown,
+    /// The report's data, with each battle's full result.
+    Json,
+}
+
+/// Everything about a run worth sharing: who battled, how each battle went, and how to do it again.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Report {
+    /// The `cargo protologic` command the battles were run with.
+    pub command_line: String,
+    pub label: Option<String>,
+    pub fleets: [ReportFleet; 2],
+    pub games: Vec<Game>,
+    pub score: Score,
+    /// The first fleet's win-rate, with draws as half a win.
+    pub win_rate: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReportFleet {
+    pub name: String,
+    /// SHA-256 of the fleet's wasm when it battled.
+    pub sha256: Option<String>,
+    /// The version of the package the fleet was built from, if `build` recorded one.
+    pub version: Option<String>,
+}
+
+impl Report {
+    pub fn new(fleets: [&Fleet; 2], games: Vec<Game>, label: Option<String>) -> Self {
+        let score = Score::from_games(&games);
+        Self {
+            command_line: command_line(),
+            label,
+            fleets: [
+                ReportFleet::new(fleets[0], &games, true),
+                ReportFleet::new(fleets[1], &games, false),
+            ],
+            win_rate: score.win_rate(true),
+            score,
+            games,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> anyhow::Result<String> {
+        match format {
+            ReportFormat::Markdown => Ok(self.markdown()),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let [first, second] = &self.fleets;
+        let mut doc = format!("## {} vs {}", first.name, second.name);
+        if let Some(label) = &self.label {
+            write!(doc, " ({label})").unwrap();
+        }
+        doc.push_str("\n\n");
+
+        let scenarios = self.games.iter().any(|game| game.record.scenario.is_some());
+        doc.push_str("| Game | Fleet A | Fleet B | Seed |");
+        if scenarios {
+            doc.push_str(" Scenario |");
+        }
+        doc.push_str(" Winner | Duration |\n|---|---|---|---|");
+        if scenarios {
+            doc.push_str("---|");
+        }
+        doc.push_str("---|---|\n");
+        for (i, game) in self.games.iter().enumerate() {
+            let record = &game.record;
+            let seed = record
+                .seed
+                .map_or("random".to_owned(), |seed| seed.to_string());
+            write!(
+                doc,
+                "| {} | {} | {} | {seed} |",
+                i + 1,
+                record.fleet_a,
+                record.fleet_b
+            )
+            .unwrap();
+            if scenarios {
+                write!(
+                    doc,
+                    " {} |",
+                    record.scenario.as_deref().unwrap_or("default")
+                )
+                .unwrap();
+            }
+            writeln!(
+                doc,
+                " {} | {} |",
+                winner(record),
+                format_duration(Duration::from_secs_f64(record.duration_secs))
+            )
+            .unwrap();
+        }
+
+        let score = &self.score;
+        write!(
+            doc,
+            "\n**{}** {} - {} **{}**",
+            first.name, score.first, score.second, second.name
+        )
+        .unwrap();
+        if score.draws > 0 {
+            write!(doc, ", {} drawn", score.draws).unwrap();
+        }
+        if score.unknown > 0 {
+            write!(doc, ", {} unknown", score.unknown).unwrap();
+        }
+        match self.win_rate {
+            Some(win_rate) => writeln!(
+                doc,
+                ", a {:.1}% win-rate for {}",
+                win_rate * 100.0,
+                first.name
+            )
+            .unwrap(),
+            None => doc.push_str(", no game had a known outcome\n"),
+        }
+
+        doc.push_str("\n| Fleet | Version | SHA-256 |\n|---|---|---|\n");
+        for fleet in &self.fleets {
+            writeln!(
+                doc,
+                "| {} | {} | {} |",
+                fleet.name,
+                fleet.version.as_deref().unwrap_or("unknown"),
+                fleet
+                    .sha256
+                    .as_deref()
+                    .map_or("unknown".to_owned(), |sha| format!("`{}`", short_hash(sha)))
+            )
+            .unwrap();
+        }
+
+        write!(doc, "\n```sh\n{}\n```\n", self.command_line).unwrap();
+        doc
+    }
+}
+
+impl ReportFleet {
+    fn new(fleet: &Fleet, games: &[Game], first: bool) -> Self {
+        // The hash from when it battled, in case it's been rebuilt since
+        let sha256 = games.first().and_then(|game| {
+            if game.swapped == first {
+                game.record.fleet_b_sha256.clone()
+            } else {
+                game.record.fleet_a_sha256.clone()
+            }
+        });
+        let version = fleet.manifest_entry().and_then(|entry| entry.version);
+
+        Self {
+            name: fleet.name.clone(),
+            sha256,
+            version,
+        }
+    }
+}
+
+pub fn winner(record: &BattleRecord) -> String {
+    let winner = match (&record.winner, record.outcome) {
+        (Some(winner), _) => winner.clone(),
+        (None, Outcome::Draw) => "draw".to_owned(),
+        (None, Outcome::SimCrashed) => "sim crashed".to_owned(),
+        (None, _) => "unknown".to_owned(),
+    };
+
+    match record.retries {
+        0 => winner,
+        1 => format!("{winner} (after 1 retry)"),
+        retries => format!("{winner} (after {retries} retries)"),
+    }
+}
+
+/// How we were run, as a `cargo protologic` command to copy and paste.
+fn command_line() -> String {
+    let args = std::env::args_os()
+        .skip(1)
+        .map(|arg| shell_quote(&arg.to_string_lossy()));
+    std::iter::once("cargo".to_owned())
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Report, ReportFleet, ReportFormat};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::series::{Game, Score};
+
+    fn report(scenario: Option<&str>) -> Report {
+        let game = |outcome, swapped, seed, retries| {
+            let (fleet_a, fleet_b) = if swapped {
+                ("iron_armada", "glass_cannon")
+            } else {
+                ("glass_cannon", "iron_armada")
+            };
+            Game {
+                record: BattleRecord {
+                    fleet_a: fleet_a.to_owned(),
+                    fleet_b: fleet_b.to_owned(),
+                    seed: Some(seed),
+                    scenario: scenario.map(ToOwned::to_owned),
+                    outcome,
+                    winner: BattleRecord::winner_name(outcome, fleet_a, fleet_b),
+                    duration_secs: 83.0,
+                    retries,
+                    replay: PathBuf::from("replay.json.deflate"),
+                    ..BattleRecord::default()
+                },
+                swapped,
+            }
+        };
+        let games = vec![
+            game(Outcome::FleetAWon, false, 7, 0),
+            game(Outcome::FleetAWon, true, 7, 0),
+            game(Outcome::Draw, false, 12, 1),
+        ];
+        let score = Score::from_games(&games);
+
+        Report {
+            command_line: "cargo protologic run --repeat 3".to_owned(),
+            label: Some("new aiming".to_owned()),
+            fleets: [
+                ReportFleet {
+                    name: "glass_cannon".to_owned(),
+                    sha256: Some("93a44bbb96c751218e4c00d479e4c143".to_owned()),
+                    version: Some("0.2.0".to_owned()),
+                },
+                ReportFleet {
+                    name: "iron_armada".to_owned(),
+                    sha256: None,
+                    version: None,
+                },
+            ],
+            win_rate: score.win_rate(true),
+            score,
+            games,
+        }
+    }
+
+    #[test]
+    fn markdown_report_is_stable() -> anyhow::Result<()> {
+        assert_eq!(
+            report(None).render(ReportFormat::Markdown)?,
+            "\
+## glass_cannon vs iron_armada (new aiming)
+
+| Game | Fleet A | Fleet B | Seed | Winner | Duration |
+|---|---|---|---|---|---|
+| 1 | glass_cannon | iron_armada | 7 | glass_cannon | 1m 23s |
+| 2 | iron_armada | glass_cannon | 7 | iron_armada | 1m 23s |
+| 3 | glass_cannon | iron_armada | 12 | draw (after 1 retry) | 1m 23s |
+
+**glass_cannon** 1 - 1 **iron_armada**, 1 drawn, a 50.0% win-rate for glass_cannon
+
+| Fleet | Version | SHA-256 |
+|---|---|---|
+| glass_cannon | 0.2.0 | `93a44bbb` |
+| iron_armada | unknown | unknown |
+
+```sh
+cargo protologic run --repeat 3
+```
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn markdown_report_shows_scenarios_when_there_are_any() -> anyhow::Result<()> {
+        let markdown = report(Some("asteroids")).render(ReportFormat::Markdown)?;
+        assert!(
+            markdown.contains("| Game | Fleet A | Fleet B | Seed | Scenario | Winner | Duration |")
+        );
+        assert!(markdown.contains(
+            "| 1 | glass_cannon | iron_armada | 7 | asteroids | glass_cannon | 1m 23s |"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn json_report_has_the_full_results() -> anyhow::Result<()> {
+        let json: serde_json::Value =
+            serde_json::from_str(&report(None).render(ReportFormat::Json)?)?;
+        assert_eq!(json["score"]["draws"], 1);
+        assert_eq!(json["win_rate"], 0.5);
+        assert_eq!(json["games"][1]["record"]["winner"], "iron_armada");
+        assert_eq!(json["fleets"][0]["version"], "0.2.0");
+        Ok(())
+    }
+}
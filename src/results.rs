@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::git::GitState;
+use crate::naming::add_extension;
+use crate::outcome::Outcome;
+
+/// The result of one battle, written next to its replay and appended to the battle history.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BattleRecord {
+    /// Unix timestamp (in seconds) of when the battle started.
+    pub timestamp: u64,
+    pub fleet_a: String,
+    pub fleet_b: String,
+    /// SHA-256 of fleet A's wasm at battle time, so results can be tied to a particular build.
+    #[serde(default)]
+    pub fleet_a_sha256: Option<String>,
+    /// SHA-256 of fleet B's wasm at battle time.
+    #[serde(default)]
+    pub fleet_b_sha256: Option<String>,
+    pub seed: Option<u64>,
+    /// The `--scenario` the battle was played on, or `None` for the sim's default.
+    #[serde(default)]
+    pub scenario: Option<String>,
+    /// The `--max-ticks` the battle was played with. A win within a tick limit means less than one
+    /// without.
+    #[serde(default)]
+    pub max_ticks: Option<u64>,
+    /// The `--fuel-limit` the battle was played with.
+    #[serde(default)]
+    pub fuel_limit: Option<u64>,
+    /// Whether the sim ran at low priority, which can slow it down.
+    #[serde(default)]
+    pub low_priority: bool,
+    /// The cores the sim was pinned to with `--cpus`.
+    #[serde(default)]
+    pub cpus: Option<Vec<usize>>,
+    pub outcome: Outcome,
+    /// Name of the winning fleet, if there was one.
+    pub winner: Option<String>,
+    /// How long the sim took to run the battle, in seconds.
+    #[serde(default)]
+    pub duration_secs: f64,
+    /// How many times the battle was rerun because the sim crashed.
+    #[serde(default)]
+    pub retries: u32,
+    pub replay: PathBuf,
+    /// The `--label` the battle was run with.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Where the workspace's git checkout was at, if it's in one.
+    #[serde(default)]
+    pub git: Option<GitState>,
+    /// Whether it was a `--quick` battle, cut short and with the quick sim arguments, so not a
+    /// full one.
+    #[serde(default)]
+    pub quick: bool,
+}
+
+impl BattleRecord {
+    /// The name of the fleet that won, or `None` for a draw or unknown outcome.
+    pub fn winner_name(outcome: Outcome, fleet_a: &str, fleet_b: &str) -> Option<String> {
+        match outcome {
+            Outcome::FleetAWon => Some(fleet_a.to_owned()),
+            Outcome::FleetBWon => Some(fleet_b.to_owned()),
+            Outcome::Draw | Outcome::SimCrashed | Outcome::Unknown => None,
+        }
+    }
+
+    /// A short description of the outcome, like `'iron_armada' won`.
+    pub fn describe_outcome(&self) -> String {
+        let outcome = match (&self.outcome, &self.winner) {
+            (Outcome::Draw, _) => "draw".to_owned(),
+            (Outcome::FleetAWon | Outcome::FleetBWon, Some(winner)) => format!("'{winner}' won"),
+            (Outcome::SimCrashed, _) => "sim crashed".to_owned(),
+            _ => "unknown outcome".to_owned(),
+        };
+
+        let outcome = match self.retries {
+            0 => outcome,
+            1 => format!("{outcome} (after 1 retry)"),
+            retries => format!("{outcome} (after {retries} retries)"),
+        };
+        if self.quick {
+            format!("{outcome} in a quick battle")
+        } else {
+            outcome
+        }
+    }
+
+    pub fn read_json(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("trying to read battle result {path:?}"))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("trying to parse battle result {path:?}"))
+    }
+
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).context("trying to serialize battle result")?;
+        std::fs::write(path, json)
+            .with_context(|| format!("trying to write battle result to {path:?}"))
+    }
+}
+
+/// Where the result JSON goes for a given battle output base path.
+pub fn result_path(output: &Path) -> PathBuf {
+    add_extension(output, "result.json")
+}
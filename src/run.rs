@@ -0,0 +1,1434 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+
+use crate::archive::archive_fleet;
+use crate::config::battle_output_dir;
+use crate::crash::Crash;
+use crate::events::{emit, Event, Phase};
+use crate::fleet::{find_fleet, find_fleets_for_run, select_fleets, Fleet, FleetSelection};
+use crate::git::{workspace_git_state, GitState};
+use crate::hash::{file_sha256, short_hash};
+use crate::history::{append_history, default_history_path};
+use crate::interrupt::interrupted;
+use crate::matrix::{
+    build_variants, check_features, describe_features, feature_combinations, matrix_dir,
+    matrix_package, ranking_table, variant_name, VariantResult,
+};
+use crate::naming::{sanitize_label, unique_output_path, TimestampFormat};
+use crate::notify::notify;
+use crate::outcome::{normalize_fleet_name, parse_outcome, Outcome};
+use crate::output::{dry_run, say, shell_command};
+use crate::preflight::preflight;
+use crate::priority::{check_cpus_available, parse_cpu_list, CpuList};
+use crate::ratings::{
+    default_ratings_path, describe_change, rate_battle, RatingOptions, DEFAULT_INITIAL_RATING,
+    DEFAULT_K_FACTOR,
+};
+use crate::replay::{
+    decompress_replay, launch_player, open_folder, player_command, prune_replays,
+    update_latest_replay,
+};
+use crate::report::{Report, ReportFormat};
+use crate::results::{result_path, BattleRecord};
+use crate::series::{
+    parse_best_of, play_series, print_scoreboard, read_seed_file, series_result_path,
+    summarize_series, summarize_swapped, Game, Score, SeriesPlan,
+};
+use crate::settings::{settings, Settings};
+use crate::sim::{
+    find_player, find_protologic_path, format_duration, parse_env_var, random_seed, replay_path,
+    resolve_scenario, scenario_name, Player, Sim, SimOutputSink,
+};
+use crate::version::{check_versions, release_version};
+use crate::watch::watch;
+use crate::ExitError;
+
+/// How many ticks a `--quick` battle lasts, unless `--quick-max-ticks` says otherwise.
+const QUICK_MAX_TICKS: u64 = 3000;
+
+/// Arguments for `cargo protologic run`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct RunArgs {
+    /// The first fleet to battle, by name.
+    #[arg(long)]
+    fleet_a: Option<String>,
+    /// The second fleet to battle, by name. Call it `--baseline` when it's the fleet to measure against.
+    #[arg(long, visible_alias = "baseline")]
+    fleet_b: Option<String>,
+    /// Both fleets to battle, separated by a comma like the sim's own `--fleets`: `--fleets iron_armada,glass_cannon`.
+    #[arg(long, value_name = "A,B", value_delimiter = ',', conflicts_with_all = ["fleet_a", "fleet_b", "latest", "vs"])]
+    fleets: Vec<String>,
+    /// Battle the two most recently built fleets.
+    #[arg(long, default_value = "false", conflicts_with_all = ["fleet_a", "fleet_b"])]
+    latest: bool,
+    /// Path to a fleet wasm file from anywhere, to battle against one of the built fleets. Pass it twice to battle two external fleets.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["fleet_b", "latest"])]
+    vs: Vec<PathBuf>,
+    /// Build the `--fleet-a` fleet once for every combination of these cargo features, battle each build against `--vs-fleet`, and rank them by win-rate: `--matrix-features aggressive,long-range`.
+    #[arg(
+        long,
+        value_name = "FEATURES",
+        value_delimiter = ',',
+        requires = "vs_fleet",
+        conflicts_with_all = ["fleet_b", "fleets", "latest", "vs", "watch", "best_of", "assert_winner", "assert_winrate", "report", "player", "print_replay_path"]
+    )]
+    matrix_features: Vec<String>,
+    /// The built fleet every `--matrix-features` variant battles against.
+    #[arg(long, value_name = "FLEET", requires = "matrix_features")]
+    vs_fleet: Option<String>,
+    /// Never prompt for fleets to battle, error instead when the choice is ambiguous.
+    #[arg(long, default_value = "false")]
+    non_interactive: bool,
+    /// How the battles are run and recorded.
+    #[command(flatten)]
+    pub battle: BattleArgs,
+    /// Do you want the replay opened in the player?
+    #[arg(short, long, default_value = "false")]
+    player: bool,
+    /// Wait for the player to close instead of leaving it running, and exit with its exit code if it fails.
+    #[arg(long, default_value = "false", requires = "player")]
+    player_wait: bool,
+    /// An extra argument for the player, put after the replay path. Can be given more than once.
+    #[arg(
+        long = "player-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        requires = "player"
+    )]
+    player_args: Vec<String>,
+    /// Run the Windows player through Wine, even when there's a native player. Off Windows, Wine is used anyway when there isn't one.
+    #[arg(long, default_value = "false", requires = "player")]
+    player_wine: bool,
+    /// Open the folder with the replay in your file manager once the battle is done.
+    #[arg(long, default_value = "false")]
+    open_folder: bool,
+    /// Write a report of the battles, to paste into a chat or feed to a script. Printed unless `--report-file` is given.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    report: Option<ReportFormat>,
+    /// Where to write the `--report`, instead of printing it.
+    #[arg(long, value_name = "PATH", requires = "report")]
+    report_file: Option<PathBuf>,
+    /// Rebuild the fleets and rerun the battles whenever the workspace's sources change, until Ctrl-C. Press Enter to rerun without a change.
+    #[arg(long, default_value = "false")]
+    watch: bool,
+    /// Show a desktop notification with the result once the battles are done, for when you wander off during a long run.
+    #[arg(long, default_value = "false")]
+    notify: bool,
+    /// The seed for the sim. Random when not given, if the battle needs one.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Battle twice with the same seed, swapping which side each fleet plays on for the second battle.
+    ///
+    /// With `--best-of`, the fleets alternate sides every game instead.
+    #[arg(long, default_value = "false")]
+    swap_sides: bool,
+    /// Play a series of up to N games with fresh seeds, stopping once a fleet has won the majority. N must be odd.
+    #[arg(long, value_name = "N", value_parser = parse_best_of, conflicts_with = "seed")]
+    best_of: Option<u32>,
+    /// Battle N times with fresh seeds, then sum up the results.
+    ///
+    /// With `--swap-sides`, each seed is battled from both sides.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["seed", "best_of"])]
+    repeat: Option<u32>,
+    /// Battle once for each seed in a file, which has one seed per line. Lines can have `#` comments.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["seed", "best_of", "repeat"])]
+    seed_file: Option<PathBuf>,
+    /// Fail with exit code 3 when the first fleet (or `--assert-fleet`) wins less than this fraction of games. Draws count as half a win.
+    #[arg(long, value_name = "RATE", value_parser = parse_winrate)]
+    assert_winrate: Option<f64>,
+    /// The fleet whose win-rate `--assert-winrate` checks.
+    #[arg(long, value_name = "FLEET", requires = "assert_winrate")]
+    assert_fleet: Option<String>,
+    /// Fail with exit code 3 unless this fleet wins the battle. Only for single battles.
+    #[arg(long, value_name = "FLEET", conflicts_with_all = ["swap_sides", "best_of", "repeat", "seed_file"])]
+    assert_winner: Option<String>,
+    /// Let `--assert-winner` pass when the battle is a draw.
+    #[arg(long, default_value = "false", requires = "assert_winner")]
+    draws_ok: bool,
+    /// Update the fleets' Elo ratings with the battles' results, for `cargo protologic standings --elo`. Each build of a fleet has its own rating.
+    #[arg(long, default_value = "false")]
+    rated: bool,
+    /// Battle a short engagement, to see quickly whether a fleet does anything at all. Battles end after `--quick-max-ticks`, the sim gets the `--quick-sim-arg`s, and the results are marked as quick and kept out of the battle history and Elo ratings.
+    #[arg(long, default_value = "false", conflicts_with = "rated")]
+    quick: bool,
+    /// How many ticks a `--quick` battle lasts. Defaults to 3000.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), requires = "quick")]
+    quick_max_ticks: Option<u64>,
+    /// An extra argument for the sim in `--quick` battles, like a smaller arena for a sim that has one. Can be given more than once.
+    #[arg(
+        long = "quick-sim-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        requires = "quick"
+    )]
+    quick_sim_args: Vec<String>,
+    /// Print the absolute path of the replay as the last line of stdout, sending everything else to stderr. For scripts.
+    #[arg(long, default_value = "false")]
+    pub print_replay_path: bool,
+}
+
+/// How battles are run and recorded, for every command that battles fleets.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BattleArgs {
+    /// The location of the Protologic/Release repo. Can specify as an environment variable for ease of use! Defaults to the one saved with `cargo protologic config set protologic-path`, then `~/Protologic/Release` or a Release next to the workspace.
+    #[arg(long, env)]
+    pub protologic_path: Option<PathBuf>,
+    /// Whether to set the `--debug` flag in Protologic. Battles the fleets from `build --debug` too, when there are some.
+    #[arg(short, long, default_value = "false")]
+    pub debug: bool,
+    /// Once the battles are done, delete all but the newest N replays (and their results) in the battle output directory, `--output-dir` or the `output-dir` setting.
+    ///
+    /// The replays of the run itself are all kept, even when it plays more than N games.
+    ///
+    /// Only files named like this tool's replays are ever deleted.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "keep_all")]
+    pub keep_last: Option<u32>,
+    /// Keep every replay. This is the default, and overrides `--keep-last`.
+    #[arg(long, default_value = "false")]
+    pub keep_all: bool,
+    /// Where to put replays and results. Defaults to `output-dir` in `[workspace.metadata.protologic]`, or the current directory.
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+    /// Keep a copy of the exact fleets that battled in `fleet_objects/` next to the replays, so a battle can be rerun after the fleets are rebuilt. Each build is only stored once.
+    #[arg(long, default_value = "false", env = "PROTOLOGIC_ARCHIVE_FLEETS")]
+    pub archive_fleets: bool,
+    /// Also inflate each replay into a plain `.json` next to it, for jq and scripts.
+    #[arg(long, default_value = "false")]
+    pub decompress_replay: bool,
+    /// Set an environment variable for the sim, as `KEY=VALUE`. Can be given more than once.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+    /// The working directory for the sim, where it leaves its logs and crash dumps. Defaults to the directory the replay goes in.
+    #[arg(long, value_name = "PATH")]
+    pub sim_cwd: Option<PathBuf>,
+    /// Write the sim's console output to a `.sim.log` next to the replay, instead of the terminal.
+    #[arg(long, default_value = "false")]
+    pub quiet_sim: bool,
+    /// Throw away the sim's console output, instead of showing it in the terminal.
+    #[arg(long, default_value = "false", conflicts_with = "quiet_sim")]
+    pub discard_sim_output: bool,
+    /// Write what each fleet printed to its own `.fleet_a.log` and `.fleet_b.log` next to the replay.
+    #[arg(long, default_value = "false")]
+    pub split_fleet_logs: bool,
+    /// Rerun a battle up to N times with the same seed when the sim crashes, before recording it as a crash.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Don't save the sim output, command line, fleets and environment to a `crash_<time>` directory when the sim crashes.
+    #[arg(long, default_value = "false")]
+    pub no_crash_capture: bool,
+    /// Fail instead of warning when the fleets were built against an SDK that doesn't match the sim's version, or when it can't be told. For tournament prep.
+    #[arg(long, default_value = "false")]
+    pub require_version_match: bool,
+    /// Run each fleet for a few ticks in wasmtime before the battle, to catch fleets that trap or hang straight away. On by default with `--repeat` and `--seed-file`.
+    #[arg(long, default_value = "false")]
+    pub preflight: bool,
+    /// Skip the `--preflight` check, even for `--repeat`.
+    #[arg(long, default_value = "false", conflicts_with = "preflight")]
+    pub no_preflight: bool,
+    /// Battle even if a fleet doesn't look like a valid wasm module.
+    #[arg(long, default_value = "false")]
+    pub force: bool,
+    /// The scenario (or asteroid layout) to battle on, by name or path. Give it more than once to battle each seed of `--repeat` or `--seed-file` on each scenario.
+    #[arg(long = "scenario", value_name = "NAME_OR_PATH")]
+    pub scenarios: Vec<String>,
+    /// End each battle after N ticks, for quick iteration. Needs a sim that supports it.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_ticks: Option<u64>,
+    /// The wasm fuel each fleet gets per tick, for stress tests. Needs a sim that supports it.
+    #[arg(long, value_name = "FUEL", value_parser = clap::value_parser!(u64).range(1..))]
+    pub fuel_limit: Option<u64>,
+    /// Run the sim at a low scheduling priority, so the machine stays usable during long runs. Can slow battles down.
+    #[arg(long, default_value = "false")]
+    pub low_priority: bool,
+    /// Pin the sim to these cores, like `0-3` or `0,2,4-6`.
+    #[arg(long, value_name = "LIST", value_parser = parse_cpu_list)]
+    pub cpus: Option<CpuList>,
+    /// Where to append the result of every battle. Defaults to `battle_history.jsonl` in the fleet output directory.
+    #[arg(long, env = "PROTOLOGIC_HISTORY_FILE", value_name = "PATH")]
+    pub history_file: Option<PathBuf>,
+    /// Don't record battles in the battle history.
+    #[arg(long, default_value = "false", conflicts_with = "history_file")]
+    pub no_history: bool,
+    /// Include a short hash of each fleet's wasm in the replay file name, to tell builds apart.
+    #[arg(long, default_value = "false")]
+    pub hash_in_name: bool,
+    /// A note on what this battle is testing. Goes in the result JSON and battle history, and (cleaned up) in the replay file name.
+    #[arg(long, value_name = "TEXT")]
+    pub label: Option<String>,
+    /// How to write the battle's start time in file names.
+    #[arg(long, value_enum, env = "PROTOLOGIC_TIMESTAMP_FORMAT")]
+    pub timestamp_format: Option<TimestampFormat>,
+    /// How far one rated battle can move an Elo rating. Defaults to 32.
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u32).range(1..))]
+    pub k_factor: Option<u32>,
+    /// The Elo rating a fleet build starts from in its first rated battle. Defaults to 1500.
+    #[arg(long, value_name = "RATING")]
+    pub initial_rating: Option<u32>,
+}
+
+/// Fills in what the flags leave out from the `[run]` settings.
+fn apply_settings(args: &mut RunArgs, settings: &Settings) {
+    let flag = |key: &str, value: &mut bool| *value = settings.flag(key, *value);
+    flag("run.non-interactive", &mut args.non_interactive);
+    flag("run.player-wine", &mut args.player_wine);
+    flag("run.open-folder", &mut args.open_folder);
+    flag("run.notify", &mut args.notify);
+    args.quick_max_ticks = settings.number("run.quick-max-ticks", args.quick_max_ticks);
+    if args.quick_sim_args.is_empty() {
+        args.quick_sim_args = settings
+            .list("run.quick-sim-args", None)
+            .unwrap_or_default();
+    }
+    apply_battle_settings(&mut args.battle, settings);
+}
+
+/// Fills in what the battle flags leave out from the `[run]` settings, for every command that
+/// battles fleets. Where two flags conflict, giving either on the command line keeps the other's
+/// setting from applying.
+pub fn apply_battle_settings(args: &mut BattleArgs, settings: &Settings) {
+    let flag = |key: &str, value: &mut bool| *value = settings.flag(key, *value);
+    if !args.keep_all {
+        args.keep_last = settings.number("run.keep-last", args.keep_last);
+    }
+    if args.keep_last.is_none() {
+        flag("run.keep-all", &mut args.keep_all);
+    }
+    flag("run.archive-fleets", &mut args.archive_fleets);
+    flag("run.decompress-replay", &mut args.decompress_replay);
+    if !args.discard_sim_output {
+        flag("run.quiet-sim", &mut args.quiet_sim);
+    }
+    if !args.quiet_sim {
+        flag("run.discard-sim-output", &mut args.discard_sim_output);
+    }
+    flag("run.split-fleet-logs", &mut args.split_fleet_logs);
+    // It has a default, so the flag can only be told apart from it by being different
+    let retries = (args.retries != 0).then_some(args.retries);
+    args.retries = settings.number("run.retries", retries).unwrap_or(0);
+    flag("run.no-crash-capture", &mut args.no_crash_capture);
+    flag("run.require-version-match", &mut args.require_version_match);
+    if !args.no_preflight {
+        flag("run.preflight", &mut args.preflight);
+    }
+    if !args.preflight {
+        flag("run.no-preflight", &mut args.no_preflight);
+    }
+    args.max_ticks = settings.number("run.max-ticks", args.max_ticks);
+    args.fuel_limit = settings.number("run.fuel-limit", args.fuel_limit);
+    flag("run.low-priority", &mut args.low_priority);
+    if !args.no_history {
+        args.history_file = settings.path("run.history-file", args.history_file.take());
+    }
+    if args.history_file.is_none() {
+        flag("run.no-history", &mut args.no_history);
+    }
+    flag("run.hash-in-name", &mut args.hash_in_name);
+    args.timestamp_format = settings.choice("run.timestamp-format", args.timestamp_format);
+    args.k_factor = settings.number("run.k-factor", args.k_factor);
+    args.initial_rating = settings.number("run.initial-rating", args.initial_rating);
+}
+
+pub fn run(mut args: RunArgs) -> anyhow::Result<()> {
+    apply_settings(&mut args, settings()?);
+    // Once up front, so watching doesn't look it up again every round
+    args.battle.protologic_path = Some(find_protologic_path(args.battle.protologic_path.take())?);
+    if !args.matrix_features.is_empty() {
+        return run_matrix(args);
+    }
+
+    let (fleet_a, fleet_b) = match args.fleets.as_slice() {
+        [] => (args.fleet_a.clone(), args.fleet_b.clone()),
+        [a, b] => (Some(a.trim().to_owned()), Some(b.trim().to_owned())),
+        fleets => anyhow::bail!(
+            "`--fleets` takes the two fleets to battle, like `--fleets iron_armada,glass_cannon`, but got {}",
+            fleets.len()
+        ),
+    };
+    let selection = FleetSelection {
+        fleet_a,
+        fleet_b,
+        latest: args.latest,
+        vs: args.vs.clone(),
+        non_interactive: args.non_interactive,
+    };
+    let (fleet1, fleet2) = select_fleets(find_fleets_for_run(args.battle.debug)?, &selection)?;
+
+    // A dry run doesn't build anything, so there'd be nothing to watch for
+    if args.watch && !dry_run() {
+        return watch(args, fleet1, fleet2);
+    }
+    run_battles(args, fleet1, fleet2)
+}
+
+/// Fills in the battle flags from the `[run]` settings, except `keep-last`, for commands that link
+/// to their own replays afterwards, like the tournament's standings and the comparison's report.
+/// Pruning would delete them while they're played, so `--keep-last` is an error and
+/// `run.keep-last` is only for `run`. `whose` says whose replays they are, like
+/// `the tournament's`.
+pub fn apply_unpruned_battle_settings(
+    battle: &mut BattleArgs,
+    settings: &Settings,
+    whose: &str,
+) -> anyhow::Result<()> {
+    if battle.keep_last.is_some() {
+        anyhow::bail!(
+            "`--keep-last` would delete {whose} replays while they're played, prune them afterwards with `cargo protologic prune`"
+        );
+    }
+    apply_battle_settings(battle, settings);
+    battle.keep_last = None;
+    Ok(())
+}
+
+/// Battles the two fleets picked for the run, the way the arguments ask for.
+pub fn run_battles(args: RunArgs, fleet1: Fleet, fleet2: Fleet) -> anyhow::Result<()> {
+    let protologic_path = find_protologic_path(args.battle.protologic_path.clone())?;
+    let mut options = battle_options(&args.battle, &protologic_path)?;
+    if args.rated {
+        options.ratings = Some(rating_options(&args.battle)?);
+    }
+    if args.quick {
+        make_quick(&mut options, &args);
+    }
+    let long_run = args.repeat.is_some() || args.seed_file.is_some();
+    check_before_battle(
+        &args.battle,
+        long_run,
+        &options,
+        &protologic_path,
+        [&fleet1, &fleet2],
+    )?;
+    emit(Event::PhaseStarted {
+        phase: Phase::Battle,
+    });
+    let player = args
+        .player
+        .then(|| find_player(&protologic_path, args.player_wine))
+        .transpose()?;
+    let expected_winner = args
+        .assert_winner
+        .as_deref()
+        .map(|name| expected_outcome(name, &fleet1, &fleet2))
+        .transpose()?;
+
+    let scenarios = args
+        .battle
+        .scenarios
+        .iter()
+        .map(|scenario| resolve_scenario(scenario))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let plan = if let Some(seed_file) = &args.seed_file {
+        Some(SeriesPlan::repeat(
+            read_seed_file(seed_file)?,
+            args.swap_sides,
+        ))
+    } else if let Some(repeat) = args.repeat {
+        let seeds = (0..repeat).map(|_| random_seed()).collect();
+        Some(SeriesPlan::repeat(seeds, args.swap_sides))
+    } else {
+        args.best_of
+            .map(|best_of| SeriesPlan::best_of(best_of, args.swap_sides))
+    }
+    .map(|plan| SeriesPlan {
+        scenarios: scenarios.clone(),
+        ..plan
+    });
+    // Everything but a series battles once, so only gets one scenario
+    let scenario = match scenarios.as_slice() {
+        [] => None,
+        [scenario] => Some(scenario.as_str()),
+        _ if plan.as_ref().is_some_and(|plan| plan.best_of.is_none()) => None,
+        _ => anyhow::bail!(
+            "battling on several scenarios needs `--repeat` or `--seed-file`, to battle each seed on each of them"
+        ),
+    };
+
+    if dry_run() {
+        let battles: Vec<_> = if let Some(plan) = &plan {
+            if plan.best_of.is_some() {
+                say!("A best-of series stops once it's decided, so it may not run every battle");
+            }
+            plan.battles(&fleet1, &fleet2)
+        } else if args.swap_sides {
+            let seed = Some(args.seed.unwrap_or_else(random_seed));
+            vec![
+                Battle {
+                    fleet_a: &fleet1,
+                    fleet_b: &fleet2,
+                    seed,
+                    scenario,
+                    suffix: Some("original".to_owned()),
+                },
+                Battle {
+                    fleet_a: &fleet2,
+                    fleet_b: &fleet1,
+                    seed,
+                    scenario,
+                    suffix: Some("swapped".to_owned()),
+                },
+            ]
+        } else {
+            vec![Battle {
+                fleet_a: &fleet1,
+                fleet_b: &fleet2,
+                seed: args.seed,
+                scenario,
+                suffix: None,
+            }]
+        };
+
+        let player = player
+            .as_ref()
+            .map(|player| (player, args.player_args.as_slice()));
+        return print_dry_run(&options, &battles, player);
+    }
+
+    let (games, replay, summary) = if let Some(plan) = plan {
+        let timestamp = unix_now()?;
+        let series = play_series(&options, &fleet1, &fleet2, &plan, timestamp)?;
+        print_scoreboard(&series);
+        series.write_json(&series_result_path(&options, &fleet1, &fleet2, timestamp)?)?;
+        let summary = summarize_series(&series);
+
+        let replay = series
+            .games
+            .last()
+            .context("a series always plays at least one game")?
+            .record
+            .replay
+            .clone();
+        (series.games, replay, summary)
+    } else if args.swap_sides {
+        // Both orderings need to battle on the same scenario for the comparison to mean anything
+        let seed = args.seed.unwrap_or_else(random_seed);
+        say!("Battling twice with swapped sides, using seed {seed}");
+
+        let games = [
+            Game {
+                record: play_battle(
+                    &options,
+                    &Battle {
+                        fleet_a: &fleet1,
+                        fleet_b: &fleet2,
+                        seed: Some(seed),
+                        scenario,
+                        suffix: Some("original".to_owned()),
+                    },
+                )?,
+                swapped: false,
+            },
+            Game {
+                record: play_battle(
+                    &options,
+                    &Battle {
+                        fleet_a: &fleet2,
+                        fleet_b: &fleet1,
+                        seed: Some(seed),
+                        scenario,
+                        suffix: Some("swapped".to_owned()),
+                    },
+                )?,
+                swapped: true,
+            },
+        ];
+
+        match &options.label {
+            Some(label) => say!("Results with sides swapped ({label}):"),
+            None => say!("Results with sides swapped:"),
+        }
+        for (i, game) in games.iter().enumerate() {
+            say!("  Game {}: {}", i + 1, game.describe());
+        }
+        let summary = summarize_swapped(&fleet1.name, &fleet2.name, &games);
+        say!("{summary}");
+
+        let replay = games[0].record.replay.clone();
+        (games.into(), replay, summary)
+    } else {
+        let record = play_battle(
+            &options,
+            &Battle {
+                fleet_a: &fleet1,
+                fleet_b: &fleet2,
+                seed: args.seed,
+                scenario,
+                suffix: None,
+            },
+        )?;
+        match &record.label {
+            Some(label) => say!("Battle result ({label}): {}", record.describe_outcome()),
+            None => say!("Battle result: {}", record.describe_outcome()),
+        }
+
+        let summary = format!(
+            "'{}' vs '{}': {}",
+            record.fleet_a,
+            record.fleet_b,
+            record.describe_outcome()
+        );
+        let replay = record.replay.clone();
+        let game = Game {
+            record,
+            swapped: false,
+        };
+        (vec![game], replay, summary)
+    };
+    prune_after_run(&options, games.len())?;
+
+    if let Some(format) = args.report {
+        let report =
+            Report::new([&fleet1, &fleet2], games.clone(), options.label.clone()).render(format)?;
+        match &args.report_file {
+            Some(path) => {
+                std::fs::write(path, report)
+                    .with_context(|| format!("trying to write report {path:?}"))?;
+                say!("Wrote the report to {}", path.display());
+            }
+            None => say!("{report}"),
+        }
+    }
+
+    if args.notify {
+        let title = match &options.label {
+            Some(label) => format!("Protologic battles done ({label})"),
+            None => "Protologic battles done".to_owned(),
+        };
+        // Headless machines have nowhere to show it, which is no reason to fail the run
+        if let Err(err) = notify(&title, &summary) {
+            say!("Warning: couldn't show a desktop notification: {err:#}");
+        }
+    }
+
+    if args.open_folder {
+        if let Err(err) = open_folder(&replay) {
+            say!("Warning: couldn't open the replay's folder: {err:#}");
+        }
+    }
+
+    if let Some(player) = &player {
+        let mut command = player_command(player, &replay, &args.player_args);
+        if args.print_replay_path {
+            // The player outlives us, so it could print after the replay path otherwise
+            command.stdout(std::io::stderr());
+        }
+        launch_player(command, args.player_wait)?;
+    }
+
+    if let Some(expected) = expected_winner {
+        let [game] = games.as_slice() else {
+            unreachable!("`--assert-winner` only allows single battles")
+        };
+        assert_winner(&game.record, expected, args.draws_ok)?;
+    }
+
+    if let Some(threshold) = args.assert_winrate {
+        let first = match &args.assert_fleet {
+            Some(name) if fleet1.is_named(name) => true,
+            Some(name) if fleet2.is_named(name) => false,
+            Some(name) => anyhow::bail!(
+                "`--assert-fleet {name}` isn't one of the battling fleets, '{}' and '{}'",
+                fleet1.name,
+                fleet2.name
+            ),
+            None => true,
+        };
+        let fleet = if first { &fleet1.name } else { &fleet2.name };
+        assert_winrate(&Score::from_games(&games), first, fleet, threshold)?;
+    }
+
+    if args.print_replay_path {
+        println!("{}", printed_replay_path(&replay)?.display());
+    }
+
+    Ok(())
+}
+
+/// Builds a variant of a fleet for every combination of `--matrix-features`, battles each against
+/// `--vs-fleet` on the same seeds, and ranks them by win-rate.
+fn run_matrix(args: RunArgs) -> anyhow::Result<()> {
+    let metadata = crate::cargo_metadata()?;
+    let opponent = args
+        .vs_fleet
+        .as_deref()
+        .context("`--matrix-features` needs a `--vs-fleet` to battle against")?;
+    let opponent = find_fleet(&find_fleets_for_run(args.battle.debug)?, opponent)?;
+    let package = matrix_package(&metadata, args.fleet_a.as_deref(), &opponent, "--fleet-a")?;
+    check_features(package, &args.matrix_features)?;
+    let combinations = feature_combinations(&args.matrix_features);
+
+    let protologic_path = find_protologic_path(args.battle.protologic_path.clone())?;
+    let mut options = battle_options(&args.battle, &protologic_path)?;
+    if args.rated {
+        options.ratings = Some(rating_options(&args.battle)?);
+    }
+    if args.quick {
+        make_quick(&mut options, &args);
+    }
+    let scenarios = args
+        .battle
+        .scenarios
+        .iter()
+        .map(|scenario| resolve_scenario(scenario))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // Every variant battles on the same seeds, so they're compared on equal terms
+    let seeds = if let Some(seed_file) = &args.seed_file {
+        read_seed_file(seed_file)?
+    } else if let Some(repeat) = args.repeat {
+        (0..repeat).map(|_| random_seed()).collect()
+    } else {
+        vec![args.seed.unwrap_or_else(random_seed)]
+    };
+    let plan = SeriesPlan {
+        scenarios,
+        ..SeriesPlan::repeat(seeds, args.swap_sides)
+    };
+
+    let dir = matrix_dir(args.battle.debug)?;
+    say!(
+        "Feature matrix: {} variants of '{}', each playing {} games against '{}'",
+        combinations.len(),
+        package.name,
+        plan.battles(&opponent, &opponent).len(),
+        opponent.name
+    );
+    if dry_run() {
+        for features in &combinations {
+            say!(
+                "Would build '{}' with: {}",
+                variant_name(&package.name, features),
+                shell_command(&crate::build_command(
+                    &package.name,
+                    features,
+                    args.battle.debug
+                )?)
+            );
+        }
+        say!(
+            "Would optimize each variant into {:?}, then battle it against '{}'",
+            dir,
+            opponent.name
+        );
+        return Ok(());
+    }
+
+    let variants = build_variants(&metadata, package, &combinations, args.battle.debug)?;
+    for variant in &variants {
+        check_before_battle(
+            &args.battle,
+            true,
+            &options,
+            &protologic_path,
+            [&variant.fleet, &opponent],
+        )?;
+    }
+    emit(Event::PhaseStarted {
+        phase: Phase::Battle,
+    });
+
+    let mut results = Vec::new();
+    let mut played = 0;
+    for (i, variant) in variants.iter().enumerate() {
+        say!(
+            "Battling variant {} of {}: '{}' ({}) against '{}'",
+            i + 1,
+            variants.len(),
+            variant.fleet.name,
+            describe_features(&variant.features),
+            opponent.name
+        );
+        let timestamp = unix_now()?;
+        let series = play_series(&options, &variant.fleet, &opponent, &plan, timestamp)?;
+        print_scoreboard(&series);
+        series.write_json(&series_result_path(
+            &options,
+            &variant.fleet,
+            &opponent,
+            timestamp,
+        )?)?;
+        results.push(VariantResult {
+            features: variant.features.clone(),
+            score: series.score,
+        });
+        played += series.games.len();
+    }
+    prune_after_run(&options, played)?;
+
+    say!("{}", ranking_table(&opponent.name, &results));
+    say!(
+        "The variants are in {}, named by their features. The next matrix run replaces them.",
+        dir.display()
+    );
+    Ok(())
+}
+
+/// The replay's path for `--print-replay-path`, absolute even when `--output-dir` isn't, so scripts
+/// can use it from anywhere.
+fn printed_replay_path(replay: &Path) -> anyhow::Result<PathBuf> {
+    std::path::absolute(replay).with_context(|| format!("trying to make {replay:?} absolute"))
+}
+
+/// Deletes all but the newest `--keep-last` replays once a run has played its `games`. Pruning
+/// only after the whole run, and keeping at least all of its replays, means none of them are gone
+/// by the time they're printed, reported or watched.
+fn prune_after_run(options: &BattleOptions, games: usize) -> anyhow::Result<()> {
+    let Some(keep) = options.keep_last else {
+        return Ok(());
+    };
+    for deleted in prune_replays(&options.output_dir, (keep as usize).max(games))? {
+        say!("Deleted old replay file {}", deleted.display());
+    }
+    Ok(())
+}
+
+/// Fails with [`ExitError`] when a fleet's win-rate is below `threshold`, with draws as half a win.
+fn assert_winrate(score: &Score, first: bool, fleet: &str, threshold: f64) -> anyhow::Result<()> {
+    let (wins, losses) = if first {
+        (score.first, score.second)
+    } else {
+        (score.second, score.first)
+    };
+
+    if score.unknown > 0 {
+        say!(
+            "Warning: {} games had an unknown outcome, and don't count towards the win-rate",
+            score.unknown
+        );
+    }
+
+    let Some(win_rate) = score.win_rate(first) else {
+        return Err(ExitError::new(
+            ExitError::ASSERTION_FAILED,
+            format!("can't check the win-rate of '{fleet}', no games had a known outcome"),
+        )
+        .into());
+    };
+
+    let observed = format!(
+        "'{fleet}' has a win-rate of {:.1}% ({wins} won, {losses} lost, {} drawn)",
+        win_rate * 100.0,
+        score.draws
+    );
+
+    if win_rate < threshold {
+        return Err(ExitError::new(
+            ExitError::ASSERTION_FAILED,
+            format!("{observed}, below the required {:.1}%", threshold * 100.0),
+        )
+        .into());
+    }
+
+    say!(
+        "Win-rate check passed: {observed}, meeting the required {:.1}%",
+        threshold * 100.0
+    );
+    Ok(())
+}
+
+/// Works out which side `--assert-winner` expects to win, comparing names loosely.
+fn expected_outcome(name: &str, fleet_a: &Fleet, fleet_b: &Fleet) -> anyhow::Result<Outcome> {
+    let name = normalize_fleet_name(name);
+    let named = |fleet: &Fleet| {
+        normalize_fleet_name(&fleet.name) == name || normalize_fleet_name(&fleet.selector()) == name
+    };
+    let (is_a, is_b) = (named(fleet_a), named(fleet_b));
+
+    match (is_a, is_b) {
+        (true, false) => Ok(Outcome::FleetAWon),
+        (false, true) => Ok(Outcome::FleetBWon),
+        (true, true) => anyhow::bail!(
+            "both fleets are called '{name}', so `--assert-winner` can't tell them apart"
+        ),
+        (false, false) => anyhow::bail!(
+            "`--assert-winner {name}` isn't one of the battling fleets, '{}' and '{}'",
+            fleet_a.name,
+            fleet_b.name
+        ),
+    }
+}
+
+/// Fails with [`ExitError`] unless the battle went the way `--assert-winner` expected.
+fn assert_winner(record: &BattleRecord, expected: Outcome, draws_ok: bool) -> anyhow::Result<()> {
+    let expected_name = match expected {
+        Outcome::FleetAWon => &record.fleet_a,
+        _ => &record.fleet_b,
+    };
+
+    let passed = match record.outcome {
+        Outcome::Draw => draws_ok,
+        outcome => outcome == expected,
+    };
+
+    if !passed {
+        return Err(ExitError::new(
+            ExitError::ASSERTION_FAILED,
+            format!(
+                "expected '{expected_name}' to win, but the result was: {}",
+                record.describe_outcome()
+            ),
+        )
+        .into());
+    }
+
+    say!(
+        "Winner check passed: expected '{expected_name}' to win, and the result was: {}",
+        record.describe_outcome()
+    );
+    Ok(())
+}
+
+fn parse_winrate(value: &str) -> Result<f64, String> {
+    let rate = value
+        .parse::<f64>()
+        .map_err(|err| format!("`{value}` isn't a number: {err}"))?;
+
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!(
+            "the win-rate is a fraction between 0 and 1, like 0.55 for 55%, not `{value}`"
+        ));
+    }
+
+    Ok(rate)
+}
+
+/// How the run's battles are played and recorded, from its arguments.
+pub fn battle_options(args: &BattleArgs, protologic_path: &Path) -> anyhow::Result<BattleOptions> {
+    let history = if args.no_history {
+        None
+    } else {
+        Some(
+            args.history_file
+                .clone()
+                .map_or_else(default_history_path, Ok)?,
+        )
+    };
+    let options = BattleOptions {
+        output_dir: battle_output_dir(args.output_dir.clone())?,
+        sim: Sim {
+            env: args.env.clone(),
+            cwd: args.sim_cwd.clone(),
+            sink: if args.discard_sim_output {
+                SimOutputSink::Discard
+            } else if args.quiet_sim {
+                SimOutputSink::Log
+            } else {
+                SimOutputSink::Inherit
+            },
+            split_fleet_logs: args.split_fleet_logs,
+            max_ticks: args.max_ticks,
+            fuel_limit: args.fuel_limit,
+            low_priority: args.low_priority,
+            cpus: args.cpus.clone().map(|cpus| cpus.0),
+            ..Sim::new(protologic_path, args.debug)
+        },
+        history,
+        hash_in_name: args.hash_in_name,
+        label: args.label.clone(),
+        timestamp_format: args.timestamp_format.unwrap_or_default(),
+        keep_last: args.keep_last.filter(|_| !args.keep_all),
+        retries: args.retries,
+        crash_capture: !args.no_crash_capture,
+        archive_fleets: args.archive_fleets,
+        decompress_replay: args.decompress_replay,
+        git: workspace_git_state(),
+        ratings: None,
+        quick: false,
+    };
+
+    for (key, value) in &options.sim.env {
+        say!("Sim environment: {key}={value}");
+    }
+    if let Some(max_ticks) = options.sim.max_ticks {
+        say!("Battles end after {max_ticks} ticks");
+    }
+    if let Some(fuel_limit) = options.sim.fuel_limit {
+        say!("Fleets get {fuel_limit} fuel per tick");
+    }
+
+    Ok(options)
+}
+
+/// Turns the run's battles into `--quick` ones: shorter, with the quick sim arguments, and kept out
+/// of the battle history. A `--max-ticks` shorter than the quick one still applies.
+fn make_quick(options: &mut BattleOptions, args: &RunArgs) {
+    let quick_ticks = args.quick_max_ticks.unwrap_or(QUICK_MAX_TICKS);
+    let max_ticks = options
+        .sim
+        .max_ticks
+        .map_or(quick_ticks, |max_ticks| max_ticks.min(quick_ticks));
+    options.sim.max_ticks = Some(max_ticks);
+    options
+        .sim
+        .extra_args
+        .extend(args.quick_sim_args.iter().cloned());
+    options.history = None;
+    options.quick = true;
+    say!("Quick battles: they end after {max_ticks} ticks, and stay out of the battle history");
+}
+
+/// How rated battles update the Elo ratings, from the battle arguments.
+pub fn rating_options(args: &BattleArgs) -> anyhow::Result<RatingOptions> {
+    Ok(RatingOptions {
+        path: default_ratings_path()?,
+        k_factor: f64::from(args.k_factor.unwrap_or(DEFAULT_K_FACTOR)),
+        initial_rating: f64::from(args.initial_rating.unwrap_or(DEFAULT_INITIAL_RATING)),
+    })
+}
+
+/// Checks everything that can be checked before battling, a typo shouldn't cost a whole sim run.
+///
+/// `long_run` is for batches of battles, where fleets are preflighted unless it's turned off.
+pub fn check_before_battle(
+    args: &BattleArgs,
+    long_run: bool,
+    options: &BattleOptions,
+    protologic_path: &Path,
+    fleets: [&Fleet; 2],
+) -> anyhow::Result<()> {
+    if !args.force {
+        for fleet in fleets {
+            fleet.validate()?;
+        }
+    }
+    // A long batch is when a fleet that can't get going wastes the most time
+    if (args.preflight || long_run) && !args.no_preflight && !dry_run() {
+        preflight(fleets)?;
+    }
+    if !dry_run() {
+        let release = release_version(protologic_path, &options.sim.path);
+        check_versions(release.as_deref(), fleets, args.require_version_match)?;
+    }
+    if !dry_run() {
+        options.sim.check_limits_supported()?;
+    }
+    if let Some(cpus) = &options.sim.cpus {
+        check_cpus_available(cpus)?;
+    }
+    Ok(())
+}
+
+/// How battles are run and recorded, shared by every battle of a run.
+pub struct BattleOptions {
+    /// Where replays and results go.
+    pub output_dir: PathBuf,
+    pub sim: Sim,
+    /// Where to append each battle's result, unless history is turned off.
+    pub history: Option<PathBuf>,
+    /// Put a short hash of each fleet in the replay file names.
+    pub hash_in_name: bool,
+    /// What the battles are testing, as the user wrote it.
+    pub label: Option<String>,
+    pub timestamp_format: TimestampFormat,
+    /// How many replays to keep around, or `None` to keep them all.
+    pub keep_last: Option<u32>,
+    /// How many times to rerun a battle when the sim crashes.
+    pub retries: u32,
+    /// Save what we know about sim crashes to a `crash_<time>` directory next to the replays.
+    pub crash_capture: bool,
+    /// Copy the fleets into the fleet archive next to the replays.
+    pub archive_fleets: bool,
+    /// Inflate each replay into a plain `.json` next to it.
+    pub decompress_replay: bool,
+    /// The workspace's git state when the run started, recorded with each battle.
+    pub git: Option<GitState>,
+    /// How each battle updates the Elo ratings, if it's rated.
+    pub ratings: Option<RatingOptions>,
+    /// Whether these are `--quick` battles, which are marked as such in their results.
+    pub quick: bool,
+}
+
+/// One battle to play: which fleet is on which side, and what it's played on.
+#[derive(Debug, Clone)]
+pub struct Battle<'a> {
+    pub fleet_a: &'a Fleet,
+    pub fleet_b: &'a Fleet,
+    pub seed: Option<u64>,
+    pub scenario: Option<&'a str>,
+    /// Goes on the end of the replay file name, to tell the battles of a run apart.
+    pub suffix: Option<String>,
+}
+
+/// Battles two fleets in the sim, and writes out the result next to the replay.
+pub fn play_battle(options: &BattleOptions, battle: &Battle) -> anyhow::Result<BattleRecord> {
+    let Battle {
+        fleet_a,
+        fleet_b,
+        seed,
+        scenario,
+        ..
+    } = *battle;
+    let timestamp = unix_now()?;
+    let (hashes, battle_output) = prepare_battle(options, battle, timestamp)?;
+    if options.archive_fleets {
+        let dir = battle_output.parent().unwrap_or(Path::new("."));
+        for (fleet, hash) in [fleet_a, fleet_b].into_iter().zip(&hashes) {
+            archive_fleet(dir, &fleet.path, hash)?;
+        }
+    }
+
+    // Retries have to battle the same scenario, so they need a seed even when the sim would pick one
+    let seed = seed.or_else(|| (options.retries > 0).then(random_seed));
+    let sim = &options.sim;
+    emit(Event::BattleStarted {
+        fleet_a: fleet_a.name.clone(),
+        fleet_b: fleet_b.name.clone(),
+        seed,
+        scenario: scenario.map(ToOwned::to_owned),
+    });
+    let mut retries = 0;
+    let (sim_run, outcome) = loop {
+        say!("Starting the protologic sim...");
+        let command = sim.command(&fleet_a.path, &fleet_b.path, seed, scenario, &battle_output)?;
+        let command_line = shell_command(&command);
+        let sim_run = sim.run(command, &battle_output, [&fleet_a.name, &fleet_b.name])?;
+        if interrupted() {
+            anyhow::bail!("interrupted by Ctrl-C before the battle finished");
+        }
+        say!(
+            "Protologic sim complete! Battle completed in {}",
+            format_duration(sim_run.duration)
+        );
+        if !sim_run.status.success() {
+            say!("Warning: the sim exited with {}", sim_run.status);
+        }
+
+        // The sim only knows fleets by their file names
+        let outcome = parse_outcome(&sim_run.output, &fleet_a.selector(), &fleet_b.selector());
+        // A signal means a crash whatever was printed, otherwise trust the output over the status
+        let crashed = sim_run.status.code().is_none()
+            || (!sim_run.status.success() && outcome == Outcome::Unknown);
+        if !crashed {
+            break (sim_run, outcome);
+        }
+
+        if options.crash_capture {
+            let crash = Crash {
+                sim,
+                sim_run: &sim_run,
+                command: &command_line,
+                seed,
+                fleets: [fleet_a, fleet_b],
+                hashes: &hashes,
+            };
+            let dir = battle_output
+                .parent()
+                .context("battle output has a directory")?;
+            match crash.capture(dir, &options.timestamp_format.format(unix_now()?)) {
+                Ok(crash_dir) => say!("Saved the details of the crash to {}", crash_dir.display()),
+                Err(err) => say!("Warning: couldn't save the details of the crash: {err:#}"),
+            }
+        }
+        if retries == options.retries {
+            break (sim_run, Outcome::SimCrashed);
+        }
+
+        retries += 1;
+        say!(
+            "The sim crashed, retrying with the same seed (retry {retries} of {})",
+            options.retries
+        );
+    };
+
+    let record = BattleRecord {
+        timestamp,
+        fleet_a: fleet_a.name.clone(),
+        fleet_b: fleet_b.name.clone(),
+        fleet_a_sha256: Some(hashes[0].clone()),
+        fleet_b_sha256: Some(hashes[1].clone()),
+        seed,
+        scenario: scenario.map(ToOwned::to_owned),
+        max_ticks: sim.max_ticks,
+        fuel_limit: sim.fuel_limit,
+        low_priority: sim.low_priority,
+        cpus: sim.cpus.clone(),
+        outcome,
+        winner: BattleRecord::winner_name(outcome, &fleet_a.name, &fleet_b.name),
+        duration_secs: sim_run.duration.as_secs_f64(),
+        retries,
+        replay: replay_path(&battle_output),
+        label: options.label.clone(),
+        git: options.git.clone(),
+        quick: options.quick,
+    };
+    record.write_json(&result_path(&battle_output))?;
+
+    if sim_run.status.success() && record.replay.exists() {
+        if options.decompress_replay {
+            let json = decompress_replay(&record.replay)?;
+            say!("Decompressed replay: {}", json.display());
+        }
+        match update_latest_replay(&record.replay) {
+            Ok(latest) => say!("Latest replay: {}", latest.display()),
+            Err(err) => say!("Warning: couldn't update the latest replay: {err:#}"),
+        }
+    }
+
+    if let Some(history) = &options.history {
+        append_history(history, &record)?;
+    }
+    if let Some(ratings) = &options.ratings {
+        match rate_battle(ratings, &record)? {
+            Some([a, b]) => say!(
+                "Elo: '{}' {}, '{}' {}",
+                record.fleet_a,
+                describe_change(a),
+                record.fleet_b,
+                describe_change(b)
+            ),
+            None => say!("Not rated, since the battle doesn't say which fleet is better"),
+        }
+    }
+    emit(Event::BattleFinished {
+        result: Box::new(record.clone()),
+    });
+
+    Ok(record)
+}
+
+/// Hashes the fleets, and picks where the battle's replay and results go.
+fn prepare_battle(
+    options: &BattleOptions,
+    battle: &Battle,
+    timestamp: u64,
+) -> anyhow::Result<([String; 2], PathBuf)> {
+    let Battle {
+        fleet_a, fleet_b, ..
+    } = battle;
+    let hashes = [file_sha256(&fleet_a.path)?, file_sha256(&fleet_b.path)?];
+    let names = if options.hash_in_name {
+        [
+            format!("{}-{}", fleet_a.name, short_hash(&hashes[0])),
+            format!("{}-{}", fleet_b.name, short_hash(&hashes[1])),
+        ]
+    } else {
+        [fleet_a.name.clone(), fleet_b.name.clone()]
+    };
+    let battle_output = battle_output_path(
+        options,
+        &options.output_dir,
+        &names[0],
+        &names[1],
+        timestamp,
+        [
+            battle.scenario.map(scenario_name),
+            options.label.as_deref(),
+            battle.suffix.as_deref(),
+        ],
+    );
+
+    Ok((hashes, battle_output))
+}
+
+/// Prints the sim and player commands the run would use, without running them.
+pub fn print_dry_run(
+    options: &BattleOptions,
+    battles: &[Battle],
+    player: Option<(&Player, &[String])>,
+) -> anyhow::Result<()> {
+    let timestamp = unix_now()?;
+    let mut last_output = None;
+    for battle in battles {
+        let (_, output) = prepare_battle(options, battle, timestamp)?;
+        let command = options.sim.command(
+            &battle.fleet_a.path,
+            &battle.fleet_b.path,
+            battle.seed,
+            battle.scenario,
+            &output,
+        )?;
+        say!("Would run the sim: {}", shell_command(&command));
+        last_output = Some(output);
+    }
+
+    if let (Some((player, player_args)), Some(output)) = (player, last_output) {
+        let command = player_command(player, &replay_path(&output), player_args);
+        say!("Would open the player: {}", shell_command(&command));
+    }
+
+    Ok(())
+}
+
+pub fn unix_now() -> anyhow::Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs())
+}
+
+/// A fleet's name made safe for a file name, since display names can have spaces and the like.
+pub fn fleet_file_name(name: &str) -> String {
+    sanitize_label(name).unwrap_or_else(|| "fleet".to_owned())
+}
+
+/// Picks an unused output base path for a battle. `extras` are cleaned up and added to the end of
+/// the file name, like the scenario and label.
+fn battle_output_path(
+    options: &BattleOptions,
+    dir: &Path,
+    fleet1_name: &str,
+    fleet2_name: &str,
+    timestamp: u64,
+    extras: [Option<&str>; 3],
+) -> PathBuf {
+    let time = options.timestamp_format.format(timestamp);
+    let extras: String = extras
+        .into_iter()
+        .flatten()
+        .filter_map(sanitize_label)
+        .map(|extra| format!("_{extra}"))
+        .collect();
+
+    unique_output_path(
+        dir,
+        &format!(
+            "{time}_{}_vs_{}{extras}",
+            fleet_file_name(fleet1_name),
+            fleet_file_name(fleet2_name)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        assert_winner, battle_output_path, expected_outcome, printed_replay_path, prune_after_run,
+        BattleOptions,
+    };
+    use crate::fleet::Fleet;
+    use crate::naming::TimestampFormat;
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::sim::{replay_path, Sim};
+
+    fn fleet(name: &str) -> Fleet {
+        Fleet {
+            name: name.to_owned(),
+            path: PathBuf::from(format!("{name}.wasm")),
+        }
+    }
+
+    fn record(outcome: Outcome) -> BattleRecord {
+        BattleRecord {
+            timestamp: 0,
+            fleet_a: "iron_armada".to_owned(),
+            fleet_b: "dummy".to_owned(),
+            seed: None,
+            outcome,
+            winner: BattleRecord::winner_name(outcome, "iron_armada", "dummy"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expected_outcome_normalizes_names() -> anyhow::Result<()> {
+        let (a, b) = (fleet("iron_armada"), fleet("dummy"));
+        assert_eq!(expected_outcome("Iron-Armada", &a, &b)?, Outcome::FleetAWon);
+        assert_eq!(expected_outcome("dummy.wasm", &a, &b)?, Outcome::FleetBWon);
+        assert!(expected_outcome("glass_cannon", &a, &b).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn assert_winner_handles_draws() {
+        assert!(assert_winner(&record(Outcome::FleetAWon), Outcome::FleetAWon, false).is_ok());
+        assert!(assert_winner(&record(Outcome::FleetBWon), Outcome::FleetAWon, true).is_err());
+        assert!(assert_winner(&record(Outcome::Draw), Outcome::FleetAWon, false).is_err());
+        assert!(assert_winner(&record(Outcome::Draw), Outcome::FleetAWon, true).is_ok());
+        assert!(assert_winner(&record(Outcome::Unknown), Outcome::FleetAWon, true).is_err());
+    }
+
+    fn options(dir: &Path) -> BattleOptions {
+        BattleOptions {
+            output_dir: dir.to_owned(),
+            sim: Sim::new(Path::new("release"), false),
+            history: None,
+            hash_in_name: false,
+            label: None,
+            timestamp_format: TimestampFormat::Epoch,
+            keep_last: None,
+            retries: 0,
+            crash_capture: false,
+            archive_fleets: false,
+            decompress_replay: false,
+            git: None,
+            ratings: None,
+            quick: false,
+        }
+    }
+
+    #[test]
+    fn battle_output_path_avoids_collisions() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = options(dir.path());
+        let path = |suffix| {
+            battle_output_path(
+                &options,
+                dir.path(),
+                "a",
+                "b",
+                1718476403,
+                [None, None, suffix],
+            )
+        };
+
+        let first = path(None);
+        assert_eq!(first, dir.path().join("1718476403_a_vs_b"));
+        std::fs::write(replay_path(&first), "")?;
+
+        let second = path(None);
+        assert_eq!(second, dir.path().join("1718476403_a_vs_b_2"));
+        std::fs::write(replay_path(&second), "")?;
+
+        assert_eq!(path(None), dir.path().join("1718476403_a_vs_b_3"));
+        assert_eq!(
+            path(Some("game1")),
+            dir.path().join("1718476403_a_vs_b_game1")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pruning_keeps_every_replay_of_the_run() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Two older battles, then a swapped-sides run's two games
+        let replays = [
+            "1718476401_a_vs_b",
+            "1718476402_a_vs_b",
+            "1718476403_a_vs_b_original",
+            "1718476403_b_vs_a_swapped",
+        ];
+        for (i, base) in replays.iter().enumerate() {
+            let path = replay_path(&dir.path().join(base));
+            std::fs::write(&path, "")?;
+            let modified =
+                std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(i as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(modified)?;
+        }
+
+        let options = BattleOptions {
+            keep_last: Some(1),
+            ..options(dir.path())
+        };
+        prune_after_run(&options, 2)?;
+
+        for (i, base) in replays.iter().enumerate() {
+            let kept = replay_path(&dir.path().join(base)).exists();
+            assert_eq!(kept, i >= 2, "{base}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn printed_replay_paths_are_absolute() -> anyhow::Result<()> {
+        let replay = Path::new("out").join("1718476403_a_vs_b.json.deflate");
+        let printed = printed_replay_path(&replay)?;
+        assert!(printed.is_absolute(), "{printed:?}");
+        assert_eq!(printed, std::env::current_dir()?.join(&replay));
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use toml_edit::{value, Array, DocumentMut, Item, Table};
+
+use crate::manifest::artifact_file_name;
+use crate::output::{dry_run, say};
+use crate::sim::find_protologic_path;
+use crate::template::{render, TemplateSource};
+use crate::version::release_version;
+
+/// The SDK version new fleets depend on when there's no Release around to match.
+const DEFAULT_SDK_VERSION: &str = "0.7";
+
+/// Creates a fleet crate called `name` from a template in the workspace, adding it to the workspace's members (and
+/// default members, when it has them, since those are the fleets `build` picks). Outside a
+/// workspace, it starts a new one in the current directory.
+pub fn new_fleet(name: &str, template: &str, sdk_version: Option<String>) -> anyhow::Result<()> {
+    check_package_name(name)?;
+    let template = TemplateSource::parse(template)?;
+
+    let root_manifest = workspace_manifest();
+    let (root, manifest) = match &root_manifest {
+        Some(path) => (
+            path.parent().unwrap_or(Path::new(".")).to_owned(),
+            std::fs::read_to_string(path)
+                .with_context(|| format!("trying to read the workspace manifest {path:?}"))?,
+        ),
+        None => (
+            std::env::current_dir().context("trying to find the current directory")?,
+            String::new(),
+        ),
+    };
+    let manifest_path = root_manifest.unwrap_or_else(|| root.join("Cargo.toml"));
+
+    let dir = root.join(name);
+    if dir.exists() {
+        anyhow::bail!(
+            "{} already exists, pick another name or move it out of the way",
+            dir.display()
+        );
+    }
+
+    let sdk_version = sdk_version.unwrap_or_else(default_sdk_version);
+    let crate_name = name.replace('-', "_");
+    let vars = [
+        ("name", name),
+        ("crate_name", crate_name.as_str()),
+        ("sdk_version", sdk_version.as_str()),
+    ];
+    let files: Vec<(PathBuf, Vec<u8>)> = template
+        .files()?
+        .into_iter()
+        .map(|(path, contents)| (dir.join(path), render(contents, &vars)))
+        .collect();
+    let manifest = add_member(&manifest, name)
+        .with_context(|| format!("trying to add '{name}' to {manifest_path:?}"))?;
+
+    if dry_run() {
+        for (path, contents) in &files {
+            match std::str::from_utf8(contents) {
+                Ok(text) => say!("Would create {}:\n{text}", path.display()),
+                Err(_) => say!("Would create {} ({} bytes)", path.display(), contents.len()),
+            }
+        }
+        say!("Would write {}:\n{manifest}", manifest_path.display());
+        return Ok(());
+    }
+
+    for (path, contents) in &files {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("trying to create directory {parent:?}"))?;
+        }
+        std::fs::write(path, contents).with_context(|| format!("trying to write {path:?}"))?;
+    }
+    std::fs::write(&manifest_path, manifest)
+        .with_context(|| format!("trying to write the workspace manifest {manifest_path:?}"))?;
+
+    let fleet = artifact_file_name(name);
+    let fleet = fleet.trim_end_matches(".wasm");
+    say!("Created fleet '{name}' in {}", dir.display());
+    say!("Next, build it and battle it:");
+    say!("  cargo protologic build");
+    say!("  cargo protologic run --fleet-a {fleet}");
+    Ok(())
+}
+
+/// Checks `name` is something cargo takes as a package name.
+fn check_package_name(name: &str) -> anyhow::Result<()> {
+    let Some(first) = name.chars().next() else {
+        anyhow::bail!("the fleet needs a name");
+    };
+    if first.is_ascii_digit() {
+        anyhow::bail!("'{name}' can't be a package name, they can't start with a digit");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    {
+        anyhow::bail!("'{name}' can't be a package name, they can only have letters, digits, `-` and `_`, not `{c}`");
+    }
+    Ok(())
+}
+
+/// The workspace's root `Cargo.toml`, or `None` when we're not in a cargo project.
+pub fn workspace_manifest() -> Option<PathBuf> {
+    let output = Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// An SDK version requirement that suits the Release's sim, when there's a Release to ask.
+fn default_sdk_version() -> String {
+    let explicit = std::env::var_os("PROTOLOGIC_PATH").map(PathBuf::from);
+    let release = find_protologic_path(explicit).ok().and_then(|path| {
+        let sim = crate::sim::protologic_sim_path(&path);
+        release_version(&path, &sim)
+    });
+
+    match release.as_deref().and_then(sdk_requirement) {
+        Some(requirement) => {
+            say!("Depending on Protologic SDK {requirement}, to match the Release");
+            requirement
+        }
+        None => DEFAULT_SDK_VERSION.to_owned(),
+    }
+}
+
+/// A cargo version requirement for SDKs compatible with `version`, like `0.7` for `0.7.2`.
+fn sdk_requirement(version: &str) -> Option<String> {
+    let mut numbers = version.split(['-', '+']).next()?.split('.');
+    let (major, minor) = (numbers.next()?, numbers.next()?);
+    Some(format!("{major}.{minor}"))
+}
+
+/// Adds `name` to the members of the workspace in `manifest`, keeping the rest of it as it was. An
+/// empty manifest becomes a new workspace. Sets up a wasm-friendly release profile too, if the
+/// workspace doesn't have one, since cargo ignores profiles in members.
+fn add_member(manifest: &str, name: &str) -> anyhow::Result<String> {
+    let mut doc: DocumentMut = manifest.parse().context("trying to parse Cargo.toml")?;
+    let fresh = doc.is_empty();
+    let root_package = doc.contains_key("package");
+
+    let workspace = doc
+        .entry("workspace")
+        .or_insert(Item::Table(Table::new()))
+        .as_table_mut()
+        .context("`workspace` isn't a table")?;
+    if fresh {
+        workspace.insert("resolver", value("2"));
+    }
+
+    let members = array_entry(workspace, "members")?;
+    if members.iter().any(|member| member.as_str() == Some(name)) {
+        anyhow::bail!("'{name}' is already a workspace member");
+    }
+    members.push(name);
+
+    // Without default members, a root package is the only default, while every member of a
+    // virtual workspace is
+    if workspace.contains_key("default-members") {
+        array_entry(workspace, "default-members")?.push(name);
+    } else if fresh {
+        workspace.insert("default-members", value(Array::from_iter([name])));
+    } else if root_package {
+        workspace.insert("default-members", value(Array::from_iter([".", name])));
+    }
+
+    let has_release_profile = doc
+        .get("profile")
+        .and_then(|profile| profile.get("release"))
+        .is_some();
+    if !has_release_profile {
+        let profile = doc
+            .entry("profile")
+            .or_insert(Item::Table(Table::new()))
+            .as_table_mut()
+            .context("`profile` isn't a table")?;
+        profile.set_implicit(true);
+
+        let mut release = Table::new();
+        release.insert("opt-level", value(3));
+        release.insert("lto", value(true));
+        release.insert("codegen-units", value(1));
+        release.insert("panic", value("abort"));
+        release
+            .decor_mut()
+            .set_prefix("\n# Fleets get a limited amount of fuel per tick, so make them fast\n");
+        profile.insert("release", Item::Table(release));
+    }
+
+    Ok(doc.to_string())
+}
+
+/// The `members` style array `key` in the workspace table, made if it's missing.
+fn array_entry<'a>(workspace: &'a mut Table, key: &str) -> anyhow::Result<&'a mut Array> {
+    workspace
+        .entry(key)
+        .or_insert(value(Array::new()))
+        .as_array_mut()
+        .with_context(|| format!("`workspace.{key}` isn't an array"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_member, check_package_name, sdk_requirement};
+
+    #[test]
+    fn add_member_starts_a_workspace_when_there_isnt_one() -> anyhow::Result<()> {
+        assert_eq!(
+            add_member("", "glass-cannon")?,
+            r#"[workspace]
+resolver = "2"
+members = ["glass-cannon"]
+default-members = ["glass-cannon"]
+
+# Fleets get a limited amount of fuel per tick, so make them fast
+[profile.release]
+opt-level = 3
+lto = true
+codegen-units = 1
+panic = "abort"
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn add_member_keeps_the_existing_manifest() -> anyhow::Result<()> {
+        let manifest = r#"[workspace]
+# The fleets, then the helpers
+members = ["iron-armada", "targeting"]
+default-members = ["iron-armada"]
+
+[profile.release]
+opt-level = "s"
+"#;
+        assert_eq!(
+            add_member(manifest, "glass-cannon")?,
+            r#"[workspace]
+# The fleets, then the helpers
+members = ["iron-armada", "targeting", "glass-cannon"]
+default-members = ["iron-armada", "glass-cannon"]
+
+[profile.release]
+opt-level = "s"
+"#
+        );
+
+        let err = add_member(manifest, "iron-armada").unwrap_err();
+        assert!(err.to_string().contains("already a workspace member"));
+        Ok(())
+    }
+
+    #[test]
+    fn add_member_keeps_a_root_package_a_default_member() -> anyhow::Result<()> {
+        let manifest = add_member("[package]\nname = \"iron-armada\"\n", "glass-cannon")?;
+        assert!(manifest.contains(r#"default-members = [".", "glass-cannon"]"#));
+        Ok(())
+    }
+
+    #[test]
+    fn check_package_name_follows_cargo() {
+        assert!(check_package_name("glass_cannon-2").is_ok());
+        assert!(check_package_name("").is_err());
+        assert!(check_package_name("2fast").is_err());
+        assert!(check_package_name("glass cannon").is_err());
+    }
+
+    #[test]
+    fn sdk_requirement_allows_compatible_versions() {
+        assert_eq!(sdk_requirement("0.7.2").as_deref(), Some("0.7"));
+        assert_eq!(sdk_requirement("1.3.0-beta").as_deref(), Some("1.3"));
+    }
+}
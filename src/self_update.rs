@@ -0,0 +1,211 @@
+//! Updating cargo-protologic itself, from crates.io, after showing what's changed since the
+//! installed version.
+
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::process::Command;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::curl::Get;
+use crate::output::{dry_run, say, shell_command};
+use crate::version::compare_versions;
+use crate::ExitError;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+/// Where the changelog is, on the default branch.
+const CHANGELOG_PATH: &str = "HEAD/changelog.md";
+
+/// Arguments for `cargo protologic self-update`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SelfUpdateArgs {
+    /// Only say whether there's a newer version, without installing it. Exits with code 4 when there is.
+    #[arg(long, default_value = "false")]
+    check: bool,
+    /// Install it without asking.
+    #[arg(short, long, default_value = "false")]
+    yes: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct CrateInfo {
+    max_stable_version: Option<String>,
+    max_version: String,
+}
+
+/// Fetches `url`, saying who's asking, as crates.io wants.
+fn fetch(url: &str) -> anyhow::Result<String> {
+    let user_agent = format!("{CRATE_NAME}/{}", env!("CARGO_PKG_VERSION"));
+    let body = Get {
+        purpose: "check for a newer version",
+        user_agent: Some(&user_agent),
+        ..Get::default()
+    }
+    .fetch(url)?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// The newest published version, leaving out pre-releases unless there's nothing else.
+fn latest_version() -> anyhow::Result<String> {
+    let url = format!("https://crates.io/api/v1/crates/{CRATE_NAME}");
+    let response: CrateResponse = serde_json::from_str(&fetch(&url)?)
+        .with_context(|| format!("trying to parse the response from {url}"))?;
+    Ok(response
+        .krate
+        .max_stable_version
+        .unwrap_or(response.krate.max_version))
+}
+
+/// The changelog's sections for the versions after `current`, up to and including `latest`. Ones
+/// whose heading isn't a version, like the next release's, are left out.
+pub fn changelog_since(changelog: &str, current: &str, latest: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut section: Option<String> = None;
+    for line in changelog.lines() {
+        if let Some(heading) = line.strip_prefix("## ") {
+            sections.extend(section.take());
+            let version = heading.trim().trim_start_matches('v');
+            let newer = compare_versions(version, current) == Some(Ordering::Greater)
+                && compare_versions(version, latest) != Some(Ordering::Greater);
+            if newer {
+                section = Some(format!("{line}\n"));
+            }
+        } else if let Some(section) = &mut section {
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    sections.extend(section);
+    sections
+}
+
+/// What's changed since `current`, from the changelog on GitHub.
+fn print_changes(current: &str, latest: &str) {
+    let repository = env!("CARGO_PKG_REPOSITORY");
+    let url = format!(
+        "{}/{CHANGELOG_PATH}",
+        repository.replacen("github.com", "raw.githubusercontent.com", 1)
+    );
+    match fetch(&url) {
+        Ok(changelog) => match changelog_since(&changelog, current, latest).as_slice() {
+            [] => say!("See what's changed in {repository}/blob/{CHANGELOG_PATH}"),
+            sections => {
+                let sections: Vec<&str> =
+                    sections.iter().map(|section| section.trim_end()).collect();
+                say!("\n{}\n", sections.join("\n\n"));
+            }
+        },
+        Err(err) => say!("Warning: couldn't fetch the changelog: {err:#}"),
+    }
+}
+
+/// Checks crates.io for a newer cargo-protologic, shows what's changed, and unless `--check`
+/// installs it with `cargo install` once you say so. Not being able to reach crates.io is only a
+/// warning, so this can run offline.
+pub fn self_update(args: SelfUpdateArgs) -> anyhow::Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = match latest_version() {
+        Ok(latest) => latest,
+        Err(err) => {
+            say!("Warning: couldn't check for a newer {CRATE_NAME}: {err:#}");
+            return Ok(());
+        }
+    };
+    if compare_versions(current, &latest) != Some(Ordering::Less) {
+        say!("You have the latest {CRATE_NAME}, {current}");
+        return Ok(());
+    }
+
+    say!("{CRATE_NAME} {latest} is available, you have {current}");
+    print_changes(current, &latest);
+    if args.check {
+        return Err(ExitError::new(
+            ExitError::UPDATE_AVAILABLE,
+            format!(
+                "{CRATE_NAME} {latest} is available, update with `cargo protologic self-update`"
+            ),
+        )
+        .into());
+    }
+
+    // Run as a cargo subcommand, cargo says which cargo it is
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let mut command = Command::new(cargo);
+    command.args([
+        "install",
+        CRATE_NAME,
+        "--force",
+        "--locked",
+        "--version",
+        &latest,
+    ]);
+    if dry_run() {
+        say!("Would run: {}", shell_command(&command));
+        return Ok(());
+    }
+    if !args.yes {
+        if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+            anyhow::bail!(
+                "not on a terminal to ask whether to update, pass `--yes` to update anyway"
+            );
+        }
+        let confirmed = dialoguer::Confirm::new()
+            .with_prompt(format!("Install {CRATE_NAME} {latest}?"))
+            .default(true)
+            .interact()
+            .context("trying to ask whether to update")?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    say!("Running {}", shell_command(&command));
+    let status = command.status().context("trying to run cargo install")?;
+    if !status.success() {
+        anyhow::bail!("cargo install failed with {status}");
+    }
+    say!("Updated {CRATE_NAME} to {latest}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::changelog_since;
+
+    const CHANGELOG: &str = "# Changelog
+## 0.X.0
+- Something unreleased.
+## 0.4.0
+- Add a thing.
+- Fix another.
+## 0.3.1
+- Fix a thing.
+
+## 0.3.0
+- Old news.
+";
+
+    #[test]
+    fn changelog_since_has_the_versions_in_between() {
+        assert_eq!(
+            changelog_since(CHANGELOG, "0.3.0", "0.4.0"),
+            [
+                "## 0.4.0\n- Add a thing.\n- Fix another.\n",
+                "## 0.3.1\n- Fix a thing.\n\n"
+            ]
+        );
+        assert_eq!(
+            changelog_since(CHANGELOG, "0.3.0", "0.3.1"),
+            ["## 0.3.1\n- Fix a thing.\n\n"]
+        );
+        assert!(changelog_since(CHANGELOG, "0.4.0", "0.4.0").is_empty());
+    }
+}
@@ -0,0 +1,647 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::fleet::Fleet;
+use crate::naming::sanitize_label;
+use crate::outcome::Outcome;
+use crate::output::say;
+use crate::results::BattleRecord;
+use crate::run::{fleet_file_name, play_battle, Battle, BattleOptions};
+use crate::sim::{format_duration, random_seed, scenario_name};
+
+/// A game between the two fleets of a run, which may have been played with sides swapped.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Game {
+    pub record: BattleRecord,
+    /// Whether the second fleet of the run played as fleet A.
+    pub swapped: bool,
+}
+
+/// Who won a game, relative to the fleets of the run rather than the sides of the battle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    First,
+    Second,
+    Draw,
+    Unknown,
+}
+
+impl Game {
+    pub fn result(&self) -> GameResult {
+        match (self.record.outcome, self.swapped) {
+            (Outcome::FleetAWon, false) | (Outcome::FleetBWon, true) => GameResult::First,
+            (Outcome::FleetBWon, false) | (Outcome::FleetAWon, true) => GameResult::Second,
+            (Outcome::Draw, _) => GameResult::Draw,
+            (Outcome::SimCrashed | Outcome::Unknown, _) => GameResult::Unknown,
+        }
+    }
+
+    /// Describes the game with the side each fleet played on, like
+    /// `'a' (fleet A) vs 'b' (fleet B): 'a' won`.
+    pub fn describe(&self) -> String {
+        let BattleRecord {
+            fleet_a, fleet_b, ..
+        } = &self.record;
+        format!(
+            "'{fleet_a}' (fleet A) vs '{fleet_b}' (fleet B): {}",
+            self.record.describe_outcome()
+        )
+    }
+}
+
+/// Sums up a pair of games played with sides swapped, calling out when they disagree.
+pub fn summarize_swapped(first: &str, second: &str, games: &[Game; 2]) -> String {
+    let results = games.each_ref().map(Game::result);
+
+    match results {
+        [GameResult::First, GameResult::First] => format!("'{first}' won both games."),
+        [GameResult::Second, GameResult::Second] => format!("'{second}' won both games."),
+        [GameResult::Draw, GameResult::Draw] => "Both games were draws.".to_owned(),
+        [GameResult::First, GameResult::Second] => {
+            "The games disagree: fleet A won both times, so the side mattered more than the fleet!"
+                .to_owned()
+        }
+        [GameResult::Second, GameResult::First] => {
+            "The games disagree: fleet B won both times, so the side mattered more than the fleet!"
+                .to_owned()
+        }
+        [GameResult::First, GameResult::Draw] | [GameResult::Draw, GameResult::First] => {
+            format!("'{first}' won one game, the other was a draw. The games disagree!")
+        }
+        [GameResult::Second, GameResult::Draw] | [GameResult::Draw, GameResult::Second] => {
+            format!("'{second}' won one game, the other was a draw. The games disagree!")
+        }
+        [GameResult::Unknown, _] | [_, GameResult::Unknown] => {
+            "Couldn't tell who won every game from the sim output.".to_owned()
+        }
+    }
+}
+
+/// Wins, draws, and so on across the games of a series.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub first: u32,
+    pub second: u32,
+    pub draws: u32,
+    pub unknown: u32,
+}
+
+impl Score {
+    pub fn from_games(games: &[Game]) -> Self {
+        let mut score = Self::default();
+        for game in games {
+            match game.result() {
+                GameResult::First => score.first += 1,
+                GameResult::Second => score.second += 1,
+                GameResult::Draw => score.draws += 1,
+                GameResult::Unknown => score.unknown += 1,
+            }
+        }
+        score
+    }
+
+    /// The fraction of decided games won by the first (or second) fleet, with draws as half a win.
+    ///
+    /// Games with an unknown outcome aren't counted. `None` when no game had a known outcome.
+    pub fn win_rate(&self, first: bool) -> Option<f64> {
+        let wins = if first { self.first } else { self.second };
+        let games = self.first + self.second + self.draws;
+
+        (games > 0).then(|| (f64::from(wins) + f64::from(self.draws) / 2.0) / f64::from(games))
+    }
+
+    /// Whoever is ahead, or `None` when tied.
+    pub fn leader(&self) -> Option<GameResult> {
+        match self.first.cmp(&self.second) {
+            std::cmp::Ordering::Greater => Some(GameResult::First),
+            std::cmp::Ordering::Less => Some(GameResult::Second),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// The result of a series of games, written next to the replays of its games.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SeriesRecord {
+    /// Unix timestamp (in seconds) of when the series started.
+    pub timestamp: u64,
+    pub fleets: [String; 2],
+    pub best_of: Option<u32>,
+    pub swap_sides: bool,
+    pub games: Vec<Game>,
+    pub score: Score,
+    /// Name of the fleet that won the series, if it was decided.
+    pub winner: Option<String>,
+    /// The `--label` the series was run with.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// The scenarios every seed was battled on, empty for the sim's default.
+    #[serde(default)]
+    pub scenarios: Vec<String>,
+}
+
+impl SeriesRecord {
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).context("trying to serialize series result")?;
+        std::fs::write(path, json)
+            .with_context(|| format!("trying to write series result to {path:?}"))
+    }
+}
+
+/// Parses `--best-of`, which has to be odd so a series can't end tied on wins.
+pub fn parse_best_of(value: &str) -> Result<u32, String> {
+    let best_of = value
+        .parse::<u32>()
+        .map_err(|err| format!("`{value}` isn't a number: {err}"))?;
+
+    if best_of % 2 == 0 {
+        return Err(format!(
+            "a series needs an odd number of games to have a winner, try {}",
+            best_of + 1
+        ));
+    }
+
+    Ok(best_of)
+}
+
+/// The games to play in a series, before any of them have been played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesPlan {
+    pub seeds: Vec<u64>,
+    /// Stop once a fleet has won the majority of this many games.
+    pub best_of: Option<u32>,
+    pub swap_sides: bool,
+    /// Every seed is battled on each of these, or on the sim's default scenario when empty.
+    pub scenarios: Vec<String>,
+}
+
+impl SeriesPlan {
+    /// A best-of-N series, with a fresh seed per game.
+    pub fn best_of(best_of: u32, swap_sides: bool) -> Self {
+        Self {
+            seeds: (0..best_of).map(|_| random_seed()).collect(),
+            best_of: Some(best_of),
+            swap_sides,
+            scenarios: Vec::new(),
+        }
+    }
+
+    /// Every seed battled in turn, with nothing stopping the series early.
+    pub fn repeat(seeds: Vec<u64>, swap_sides: bool) -> Self {
+        Self {
+            seeds,
+            best_of: None,
+            swap_sides,
+            scenarios: Vec::new(),
+        }
+    }
+
+    /// The seed of each game, and whether the game is played with sides swapped.
+    ///
+    /// Best-of series alternate sides every game, since each game gets a fresh seed anyway.
+    /// Otherwise every seed is battled from both sides, so each seed gets a fair comparison.
+    pub fn schedule(&self) -> Vec<(u64, bool)> {
+        match (self.best_of, self.swap_sides) {
+            (_, false) => self.seeds.iter().map(|&seed| (seed, false)).collect(),
+            (Some(_), true) => self
+                .seeds
+                .iter()
+                .enumerate()
+                .map(|(i, &seed)| (seed, i % 2 == 1))
+                .collect(),
+            (None, true) => self
+                .seeds
+                .iter()
+                .flat_map(|&seed| [(seed, false), (seed, true)])
+                .collect(),
+        }
+    }
+
+    /// Every battle of the series in order, going through the whole schedule on one scenario
+    /// before moving on to the next.
+    pub fn battles<'a>(&'a self, first: &'a Fleet, second: &'a Fleet) -> Vec<Battle<'a>> {
+        let scenarios: Vec<Option<&str>> = if self.scenarios.is_empty() {
+            vec![None]
+        } else {
+            self.scenarios.iter().map(|s| Some(s.as_str())).collect()
+        };
+        let schedule = self.schedule();
+
+        scenarios
+            .into_iter()
+            .flat_map(|scenario| {
+                schedule
+                    .iter()
+                    .map(move |&(seed, swapped)| (seed, swapped, scenario))
+            })
+            .enumerate()
+            .map(|(i, (seed, swapped, scenario))| {
+                let (fleet_a, fleet_b) = if swapped {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                Battle {
+                    fleet_a,
+                    fleet_b,
+                    seed: Some(seed),
+                    scenario,
+                    suffix: Some(format!("game{}", i + 1)),
+                }
+            })
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        match self.best_of {
+            Some(best_of) => format!("best of {best_of}"),
+            None => format!(
+                "{} games",
+                self.schedule().len() * self.scenarios.len().max(1)
+            ),
+        }
+    }
+}
+
+/// Plays the games of a series in order, stopping early once a best-of series is decided.
+pub fn play_series(
+    options: &BattleOptions,
+    first: &Fleet,
+    second: &Fleet,
+    plan: &SeriesPlan,
+    timestamp: u64,
+) -> anyhow::Result<SeriesRecord> {
+    let battles = plan.battles(first, second);
+    let mut games = Vec::new();
+
+    for (i, battle) in battles.iter().enumerate() {
+        let game = i + 1;
+        let seed = battle.seed.unwrap_or_default();
+        match battle.scenario {
+            Some(scenario) => say!(
+                "Starting game {game} of {}, using seed {seed} on scenario {scenario}",
+                plan.describe()
+            ),
+            None => say!(
+                "Starting game {game} of {}, using seed {seed}",
+                plan.describe()
+            ),
+        }
+        let record = play_battle(options, battle)?;
+        games.push(Game {
+            record,
+            swapped: battle.fleet_a.name != first.name,
+        });
+
+        let score = Score::from_games(&games);
+        say!(
+            "Series score: '{}' {} - {} '{}'",
+            first.name,
+            score.first,
+            score.second,
+            second.name
+        );
+
+        if let Some(best_of) = plan.best_of {
+            let majority = best_of / 2 + 1;
+            if score.first >= majority || score.second >= majority {
+                break;
+            }
+        }
+
+        let remaining = battles.len() - game;
+        if remaining > 0 {
+            let total: f64 = games.iter().map(|game| game.record.duration_secs).sum();
+            let average = total / games.len() as f64;
+            // A best-of can finish early, so its estimate is the longest it could take
+            let bound = if plan.best_of.is_some() {
+                "at most "
+            } else {
+                ""
+            };
+            say!(
+                "Games are taking {} on average, so {bound}about {} to go for {remaining} more",
+                format_duration(Duration::from_secs_f64(average)),
+                format_duration(Duration::from_secs_f64(average * remaining as f64))
+            );
+        }
+    }
+
+    let score = Score::from_games(&games);
+    let winner = match score.leader() {
+        Some(GameResult::First) => Some(first.name.clone()),
+        Some(GameResult::Second) => Some(second.name.clone()),
+        _ => None,
+    };
+
+    Ok(SeriesRecord {
+        timestamp,
+        fleets: [first.name.clone(), second.name.clone()],
+        best_of: plan.best_of,
+        swap_sides: plan.swap_sides,
+        games,
+        score,
+        winner,
+        label: options.label.clone(),
+        scenarios: plan.scenarios.clone(),
+    })
+}
+
+/// Prints the games of a series and who came out on top.
+pub fn print_scoreboard(series: &SeriesRecord) {
+    let [first, second] = &series.fleets;
+    let score = &series.score;
+
+    let label = series
+        .label
+        .as_deref()
+        .map(|label| format!(", {label}"))
+        .unwrap_or_default();
+    match series.best_of {
+        Some(best_of) => say!("Series scoreboard (best of {best_of}{label}):"),
+        None => say!("Series scoreboard ({} games{label}):", series.games.len()),
+    }
+    for (i, game) in series.games.iter().enumerate() {
+        let seed = game
+            .record
+            .seed
+            .map(|seed| format!(" [seed {seed}]"))
+            .unwrap_or_default();
+        say!("  Game {}: {}{seed}", i + 1, game.describe());
+    }
+
+    let mut tally = format!("  '{first}' {} - {} '{second}'", score.first, score.second);
+    if score.draws > 0 {
+        tally.push_str(&format!(", {} drawn", score.draws));
+    }
+    if score.unknown > 0 {
+        tally.push_str(&format!(", {} unknown", score.unknown));
+    }
+    say!("{tally}");
+
+    if series.scenarios.len() > 1 {
+        for scenario in &series.scenarios {
+            let games: Vec<Game> = series
+                .games
+                .iter()
+                .filter(|game| game.record.scenario.as_ref() == Some(scenario))
+                .cloned()
+                .collect();
+            let score = Score::from_games(&games);
+            say!(
+                "    on {}: '{first}' {} - {} '{second}', {} drawn",
+                scenario_name(scenario),
+                score.first,
+                score.second,
+                score.draws
+            );
+        }
+    }
+
+    let retries: u32 = series.games.iter().map(|game| game.record.retries).sum();
+    if retries > 0 {
+        say!("  The sim crashed and was retried {retries} times");
+    }
+
+    match &series.winner {
+        Some(winner) => say!("'{winner}' wins the series!"),
+        None => say!("The series ended without a winner."),
+    }
+}
+
+/// Sums up a series in a line, like `'a' 7 - 3 'b', a 70% win rate for 'a'`.
+pub fn summarize_series(series: &SeriesRecord) -> String {
+    let [first, second] = &series.fleets;
+    let score = &series.score;
+
+    let mut summary = format!("'{first}' {} - {} '{second}'", score.first, score.second);
+    if score.draws > 0 {
+        summary.push_str(&format!(", {} drawn", score.draws));
+    }
+    match score.win_rate(true) {
+        Some(win_rate) => summary.push_str(&format!(
+            ", a {:.0}% win rate for '{first}'",
+            win_rate * 100.0
+        )),
+        None => summary.push_str(", no game had a known outcome"),
+    }
+
+    summary
+}
+
+/// Reads a seed file: one seed per line, with `#` starting a comment.
+pub fn read_seed_file(path: &Path) -> anyhow::Result<Vec<u64>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("trying to read seed file {path:?}"))?;
+
+    let seeds = parse_seeds(&contents).with_context(|| format!("in seed file {path:?}"))?;
+    if seeds.is_empty() {
+        anyhow::bail!("seed file {path:?} doesn't contain any seeds");
+    }
+
+    Ok(seeds)
+}
+
+fn parse_seeds(contents: &str) -> anyhow::Result<Vec<u64>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            (!line.is_empty()).then_some((i + 1, line))
+        })
+        .map(|(line_number, seed)| {
+            seed.parse()
+                .with_context(|| format!("line {line_number}: `{seed}` isn't a valid seed"))
+        })
+        .collect()
+}
+
+/// Where the series result JSON goes, next to the replays of its games.
+pub fn series_result_path(
+    options: &BattleOptions,
+    first: &Fleet,
+    second: &Fleet,
+    timestamp: u64,
+) -> anyhow::Result<PathBuf> {
+    let time = options.timestamp_format.format(timestamp);
+    let label = options
+        .label
+        .as_deref()
+        .and_then(sanitize_label)
+        .map(|label| format!("_{label}"))
+        .unwrap_or_default();
+
+    let stem = format!(
+        "{time}_{}_vs_{}{label}_series",
+        fleet_file_name(&first.name),
+        fleet_file_name(&second.name)
+    );
+    let dir = &options.output_dir;
+    let mut path = dir.join(format!("{stem}.result.json"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem}_{n}.result.json"));
+        n += 1;
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        parse_best_of, parse_seeds, summarize_series, summarize_swapped, Game, GameResult, Score,
+        SeriesPlan, SeriesRecord,
+    };
+    use crate::fleet::Fleet;
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+
+    fn game(outcome: Outcome, swapped: bool) -> Game {
+        let (fleet_a, fleet_b) = if swapped { ("b", "a") } else { ("a", "b") };
+        Game {
+            record: BattleRecord {
+                timestamp: 0,
+                fleet_a: fleet_a.to_owned(),
+                fleet_b: fleet_b.to_owned(),
+                seed: Some(1),
+                outcome,
+                winner: BattleRecord::winner_name(outcome, fleet_a, fleet_b),
+                ..Default::default()
+            },
+            swapped,
+        }
+    }
+
+    #[test]
+    fn summarize_swapped_follows_fleets_across_sides() {
+        let games = [
+            game(Outcome::FleetAWon, false),
+            game(Outcome::FleetBWon, true),
+        ];
+        assert_eq!(summarize_swapped("a", "b", &games), "'a' won both games.");
+
+        let games = [
+            game(Outcome::FleetAWon, false),
+            game(Outcome::FleetAWon, true),
+        ];
+        assert!(summarize_swapped("a", "b", &games).contains("disagree"));
+    }
+
+    #[test]
+    fn score_counts_games_by_fleet() {
+        let games = [
+            game(Outcome::FleetAWon, false),
+            game(Outcome::FleetAWon, true),
+            game(Outcome::Draw, false),
+            game(Outcome::FleetBWon, true),
+        ];
+        let score = Score::from_games(&games);
+        assert_eq!((score.first, score.second, score.draws), (2, 1, 1));
+        assert_eq!(score.leader(), Some(GameResult::First));
+        assert_eq!(score.win_rate(true), Some(2.5 / 4.0));
+        assert_eq!(score.win_rate(false), Some(1.5 / 4.0));
+        assert_eq!(Score::default().win_rate(true), None);
+    }
+
+    #[test]
+    fn summarize_series_gives_the_score_and_win_rate() {
+        let games = vec![
+            game(Outcome::FleetAWon, false),
+            game(Outcome::Draw, true),
+            game(Outcome::FleetAWon, true),
+        ];
+        let series = SeriesRecord {
+            timestamp: 0,
+            fleets: ["a".to_owned(), "b".to_owned()],
+            best_of: None,
+            swap_sides: true,
+            score: Score::from_games(&games),
+            games,
+            winner: None,
+            label: None,
+            scenarios: Vec::new(),
+        };
+        assert_eq!(
+            summarize_series(&series),
+            "'a' 1 - 1 'b', 1 drawn, a 50% win rate for 'a'"
+        );
+    }
+
+    #[test]
+    fn parse_best_of_requires_odd() {
+        assert_eq!(parse_best_of("5"), Ok(5));
+        assert!(parse_best_of("4").is_err());
+        assert!(parse_best_of("five").is_err());
+    }
+
+    #[test]
+    fn parse_seeds_skips_comments_and_blank_lines() -> anyhow::Result<()> {
+        let seeds = parse_seeds("# nightly seeds\n12\n\n  34 # the tricky one\n56\n")?;
+        assert_eq!(seeds, vec![12, 34, 56]);
+
+        assert!(parse_seeds("12\nnot a seed\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn series_plan_schedules_swapped_sides() {
+        let repeat = SeriesPlan::repeat(vec![1, 2], true);
+        assert_eq!(
+            repeat.schedule(),
+            vec![(1, false), (1, true), (2, false), (2, true)]
+        );
+
+        let best_of = SeriesPlan {
+            seeds: vec![1, 2, 3],
+            best_of: Some(3),
+            swap_sides: true,
+            scenarios: Vec::new(),
+        };
+        assert_eq!(best_of.schedule(), vec![(1, false), (2, true), (3, false)]);
+    }
+
+    #[test]
+    fn series_plan_battles_every_seed_on_every_scenario() {
+        let fleet = |name: &str| Fleet {
+            name: name.to_owned(),
+            path: format!("{name}.wasm").into(),
+        };
+        let (a, b) = (fleet("a"), fleet("b"));
+        let plan = SeriesPlan {
+            scenarios: vec!["asteroids".to_owned(), "open".to_owned()],
+            ..SeriesPlan::repeat(vec![1, 2], true)
+        };
+
+        let battles: Vec<_> = plan
+            .battles(&a, &b)
+            .into_iter()
+            .map(|battle| {
+                (
+                    battle.fleet_a.name.as_str(),
+                    battle.seed,
+                    battle.scenario,
+                    battle.suffix,
+                )
+            })
+            .collect();
+        assert_eq!(battles.len(), 8);
+        assert_eq!(
+            battles[..2],
+            [
+                ("a", Some(1), Some("asteroids"), Some("game1".to_owned())),
+                ("b", Some(1), Some("asteroids"), Some("game2".to_owned())),
+            ]
+        );
+        assert_eq!(
+            battles[4],
+            ("a", Some(1), Some("open"), Some("game5".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! `serve`: a small HTTP server for the battle output directory, so others on the network can
+//! download replays from a page listing them, without a file share.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use chrono::{DateTime, Local};
+
+use crate::config::find_battle_output_dir;
+use crate::history::configured_history_path;
+use crate::output::{dry_run, say};
+use crate::replay_list::{find_replays, ReplayEntry};
+use crate::tournament_report::escape_html;
+
+/// The files a battle writes that are served, by extension, with their content types. Replays
+/// are zlib streams.
+const SERVED_FILES: &[(&str, &str)] = &[
+    (".json.deflate", "application/zlib"),
+    (".result.json", "application/json"),
+    (".json", "application/json"),
+    (".log", "text/plain; charset=utf-8"),
+];
+/// The most of a request that's read, which is plenty for a GET.
+const MAX_REQUEST: u64 = 16 * 1024;
+/// How long a connection can take to send its request before it's dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Arguments for `cargo protologic serve`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// The port to listen on. 0 picks a free one.
+    #[arg(long, default_value_t = 8080)]
+    port: u16,
+    /// The address to listen on. The default only lets this computer in, `0.0.0.0` lets in the whole network.
+    #[arg(long, value_name = "ADDR", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    bind: IpAddr,
+    /// The battle output directory to serve, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// The battle history to list battles from as well. Defaults to the `run.history-file` setting, or `battle_history.jsonl` in the fleet output directory.
+    #[arg(long, value_name = "PATH")]
+    history_file: Option<PathBuf>,
+}
+
+/// A response to send.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Body,
+}
+
+enum Body {
+    Text(String),
+    File(PathBuf, u64),
+}
+
+impl Response {
+    fn text(status: &'static str, text: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: Body::Text(format!("{text}\n")),
+        }
+    }
+}
+
+/// The content type a file in the battle output directory is served as, or `None` when it isn't
+/// one that's served.
+fn content_type(name: &str) -> Option<&'static str> {
+    SERVED_FILES
+        .iter()
+        .find(|(extension, _)| name.ends_with(extension))
+        .map(|&(_, content_type)| content_type)
+}
+
+/// Decodes `%XX` escapes in a URL path, or `None` for a bad one.
+fn percent_decode(path: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Escapes a file name for a link's URL.
+fn percent_encode(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            write!(encoded, "%{byte:02X}").unwrap();
+        }
+    }
+    encoded
+}
+
+/// The file a request's path is for, which can only be one of the battle files directly in `dir`.
+/// Anything with a directory in it, hidden files and links out of `dir` are `None`, so nothing
+/// outside it can be reached.
+fn served_file(dir: &Path, path: &str) -> Option<PathBuf> {
+    let name = percent_decode(path.strip_prefix('/')?)?;
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', '\0', ':'])
+        || content_type(&name).is_none()
+    {
+        return None;
+    }
+    let dir = dir.canonicalize().ok()?;
+    let file = dir.join(&name).canonicalize().ok()?;
+    (file.parent() == Some(dir.as_path()) && file.is_file()).then_some(file)
+}
+
+/// The index page: every battle whose replay is in `dir`, newest first, with links to download
+/// its replay and result.
+fn index_page(dir: &Path, entries: &[ReplayEntry]) -> String {
+    let mut rows = String::new();
+    for entry in entries {
+        let Some(name) = entry.replay.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let (Some(size), Some(_)) = (entry.size, served_file(dir, &format!("/{name}"))) else {
+            continue;
+        };
+        let date = entry
+            .timestamp
+            .and_then(|timestamp| i64::try_from(timestamp).ok())
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map_or("-".to_owned(), |datetime| {
+                datetime
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            });
+        let result = name
+            .strip_suffix(".json.deflate")
+            .map(|base| format!("{base}.result.json"))
+            .filter(|result| served_file(dir, &format!("/{result}")).is_some())
+            .map_or(String::new(), |result| {
+                format!(" <a href=\"{}\">result</a>", percent_encode(&result))
+            });
+        writeln!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"number\">{}</td><td><a href=\"{}\" download>{}</a>{result}</td></tr>",
+            date,
+            escape_html(&entry.describe_fleets()),
+            escape_html(&entry.describe_winner()),
+            escape_html(entry.label.as_deref().unwrap_or("-")),
+            ByteSize::b(size),
+            percent_encode(name),
+            escape_html(name),
+        )
+        .unwrap();
+    }
+    if rows.is_empty() {
+        rows.push_str("<tr><td colspan=\"6\">No replays yet.</td></tr>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Protologic replays</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.3em 0.8em; text-align: left; border-bottom: 1px solid #ddd; }}
+td.number {{ text-align: right; }}
+</style>
+</head>
+<body>
+<h1>Protologic replays</h1>
+<table>
+<tr><th>Date</th><th>Fleets</th><th>Winner</th><th>Label</th><th>Size</th><th>Replay</th></tr>
+{rows}</table>
+</body>
+</html>
+"#
+    )
+}
+
+/// Answers a request for `path`, a page or a file.
+fn respond(dir: &Path, history: &Path, method: &str, path: &str) -> Response {
+    if method != "GET" && method != "HEAD" {
+        return Response::text("405 Method Not Allowed", "Only GET and HEAD are supported");
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    if path == "/" || path == "/index.html" {
+        return match find_replays(dir, Some(history)) {
+            Ok(entries) => Response {
+                status: "200 OK",
+                content_type: "text/html; charset=utf-8",
+                body: Body::Text(index_page(dir, &entries)),
+            },
+            Err(err) => Response::text(
+                "500 Internal Server Error",
+                &format!("Can't list the replays: {err:#}"),
+            ),
+        };
+    }
+    let Some(file) = served_file(dir, path) else {
+        return Response::text("404 Not Found", "There's no replay here");
+    };
+    match std::fs::metadata(&file) {
+        Ok(metadata) => Response {
+            status: "200 OK",
+            content_type: content_type(&file.to_string_lossy())
+                .unwrap_or("application/octet-stream"),
+            body: Body::File(file, metadata.len()),
+        },
+        Err(_) => Response::text("404 Not Found", "There's no replay here"),
+    }
+}
+
+/// Reads a request's method and path, ignoring its headers.
+fn read_request(stream: &TcpStream) -> anyhow::Result<(String, String)> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("not an HTTP request");
+    };
+    let request = (method.to_owned(), path.to_owned());
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(request);
+        }
+    }
+}
+
+/// Answers one connection, and says what was asked for.
+fn handle_connection(mut stream: TcpStream, dir: &Path, history: &Path) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let peer = stream.peer_addr()?;
+    let (method, path) = read_request(&stream)?;
+    let response = respond(dir, history, &method, &path);
+    say!("{peer} {method} {path} {}", response.status);
+
+    let length = match &response.body {
+        Body::Text(text) => text.len() as u64,
+        Body::File(_, length) => *length,
+    };
+    let mut headers = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {length}\r\nConnection: close\r\n",
+        response.status, response.content_type
+    );
+    if let Body::File(file, _) = &response.body {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        write!(
+            headers,
+            "Content-Disposition: attachment; filename=\"{}\"\r\n",
+            name.replace(['"', '\\'], "_")
+        )
+        .unwrap();
+    }
+    headers.push_str("\r\n");
+    stream.write_all(headers.as_bytes())?;
+    if method != "HEAD" {
+        match response.body {
+            Body::Text(text) => stream.write_all(text.as_bytes())?,
+            Body::File(file, _) => {
+                let mut file = std::fs::File::open(&file)
+                    .with_context(|| format!("trying to open {file:?}"))?;
+                std::io::copy(&mut file, &mut stream)?;
+            }
+        }
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// This computer's address on the network, the one others would reach it at. Connecting a UDP
+/// socket doesn't send anything, it only picks the route.
+fn network_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified() && !ip.is_loopback()).then_some(ip)
+}
+
+/// The URLs to share for a server listening on `addr`.
+fn share_urls(addr: SocketAddr) -> Vec<String> {
+    let url = |ip: IpAddr| format!("http://{}/", SocketAddr::new(ip, addr.port()));
+    if !addr.ip().is_unspecified() {
+        return vec![url(addr.ip())];
+    }
+    let mut urls = vec![url(IpAddr::V4(Ipv4Addr::LOCALHOST))];
+    urls.extend(network_address().map(url));
+    urls
+}
+
+/// Serves the battle output directory over HTTP until stopped with Ctrl-C.
+pub fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    let (dir, _) = find_battle_output_dir(args.output_dir)?;
+    let history = configured_history_path(args.history_file)?;
+    if !dir.is_dir() {
+        anyhow::bail!(
+            "there's no battle output directory at {}, battle with `cargo protologic run` first",
+            dir.display()
+        );
+    }
+    let addr = SocketAddr::new(args.bind, args.port);
+    if dry_run() {
+        say!(
+            "Would serve the replays in {} at http://{addr}/",
+            dir.display()
+        );
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind(addr).with_context(|| {
+        format!("trying to listen on {addr}, pick another port with `--port` if it's in use")
+    })?;
+    let addr = listener.local_addr()?;
+    say!("Serving the replays in {}", dir.display());
+    for url in share_urls(addr) {
+        say!("  {url}");
+    }
+    if addr.ip().is_loopback() {
+        say!("Only this computer can reach it, pass `--bind 0.0.0.0` to share it on your network");
+    }
+    say!("Press Ctrl-C to stop");
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let (dir, history) = (dir.clone(), history.clone());
+        std::thread::spawn(move || {
+            if let Err(err) = handle_connection(stream, &dir, &history) {
+                say!("Warning: a request failed: {err:#}");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::{handle_connection, served_file};
+
+    #[test]
+    fn served_file_stays_in_the_directory() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let battles = dir.path().join("battles");
+        std::fs::create_dir(&battles)?;
+        std::fs::write(battles.join("1718476403_a_vs_b.json.deflate"), b"replay")?;
+        std::fs::write(battles.join("notes.txt"), b"notes")?;
+        std::fs::write(dir.path().join("secret.json"), b"{}")?;
+
+        assert_eq!(
+            served_file(&battles, "/1718476403_a_vs_b.json.deflate"),
+            Some(
+                battles
+                    .canonicalize()?
+                    .join("1718476403_a_vs_b.json.deflate")
+            )
+        );
+        assert!(served_file(&battles, "/1718476403%5Fa_vs_b.json.deflate").is_some());
+        for path in [
+            "/../secret.json",
+            "/..%2Fsecret.json",
+            "/%2E%2E/secret.json",
+            "/notes.txt",
+            "/..",
+            "/",
+            "secret.json",
+        ] {
+            assert_eq!(served_file(&battles, path), None, "{path}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn replays_download_with_their_length_and_type() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("1718476403_a_vs_b.json.deflate"), b"replay")?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let mut client = TcpStream::connect(listener.local_addr()?)?;
+        client.write_all(b"GET /1718476403_a_vs_b.json.deflate HTTP/1.1\r\nHost: x\r\n\r\n")?;
+        let (stream, _) = listener.accept()?;
+        handle_connection(stream, dir.path(), &dir.path().join("history.jsonl"))?;
+
+        let mut response = String::new();
+        client.read_to_string(&mut response)?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: application/zlib\r\n"));
+        assert!(response.contains("Content-Length: 6\r\n"));
+        assert!(response.ends_with("\r\n\r\nreplay"));
+        Ok(())
+    }
+}
@@ -0,0 +1,970 @@
+//! Settings that can be saved instead of passed as flags every time, layered from the least to
+//! the most specific:
+//!
+//! 1. the user config, `config.toml` in the user's config directory,
+//! 2. `[workspace.metadata.protologic]` in the workspace's `Cargo.toml`,
+//! 3. `protologic.toml` at the workspace root,
+//! 4. flags on the command line, which always win.
+//!
+//! Keys are named after the flags they stand in for, with the subcommand's as a table: `output-dir`
+//! for everyone's `--output-dir`, and `keep-last` in `[run]` for `run --keep-last`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use toml::{Table, Value};
+
+use crate::config::UserConfig;
+use crate::fleet::edit_distance;
+use crate::output::{dry_run, say};
+
+/// The workspace settings file, next to the workspace's `Cargo.toml`.
+pub const WORKSPACE_FILE: &str = "protologic.toml";
+
+/// What a setting's value has to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    /// A whole number, at least this.
+    Number(i64),
+    /// A path, relative to the workspace root in workspace files.
+    Path,
+    /// Anything written out, like a name.
+    Text,
+    /// A size, like `2MiB`.
+    Size,
+    /// One of these.
+    Choice(&'static [&'static str]),
+    /// A list of names, given comma separated on the command line.
+    List,
+}
+
+/// A key that can be set.
+struct Setting {
+    key: &'static str,
+    kind: Kind,
+    /// When `config set` can't write it, why not.
+    managed_by: Option<&'static str>,
+}
+
+const fn setting(key: &'static str, kind: Kind) -> Setting {
+    Setting {
+        key,
+        kind,
+        managed_by: None,
+    }
+}
+
+/// Every setting there is. The others in `[run]` are about a single battle, like which fleets or
+/// seed, so they're only flags. `[build]` is for building fleets, wherever they're built, and
+/// `[wasm-opt]` for optimizing release builds. `[package]` is for `package`'s submission bundles,
+/// `[submit]` for `submit`, `[lint]` for `lint`, and `[tournament]` for `tournament`, under its
+/// `--config` file.
+const SETTINGS: &[Setting] = &[
+    setting("protologic-path", Kind::Path),
+    setting("output-dir", Kind::Path),
+    Setting {
+        key: "fleets",
+        kind: Kind::List,
+        managed_by: Some("it's read from [workspace.metadata.protologic] in Cargo.toml, where `cargo protologic init --fleet` saves it"),
+    },
+    setting("reference-index", Kind::Text),
+    setting("run.non-interactive", Kind::Bool),
+    setting("run.player-wine", Kind::Bool),
+    setting("run.keep-last", Kind::Number(1)),
+    setting("run.keep-all", Kind::Bool),
+    setting("run.open-folder", Kind::Bool),
+    setting("run.archive-fleets", Kind::Bool),
+    setting("run.decompress-replay", Kind::Bool),
+    setting("run.notify", Kind::Bool),
+    setting("run.quiet-sim", Kind::Bool),
+    setting("run.discard-sim-output", Kind::Bool),
+    setting("run.split-fleet-logs", Kind::Bool),
+    setting("run.retries", Kind::Number(0)),
+    setting("run.no-crash-capture", Kind::Bool),
+    setting("run.require-version-match", Kind::Bool),
+    setting("run.preflight", Kind::Bool),
+    setting("run.no-preflight", Kind::Bool),
+    setting("run.max-ticks", Kind::Number(1)),
+    setting("run.fuel-limit", Kind::Number(1)),
+    setting("run.quick-max-ticks", Kind::Number(1)),
+    setting("run.quick-sim-args", Kind::List),
+    setting("run.low-priority", Kind::Bool),
+    setting("run.history-file", Kind::Path),
+    setting("run.no-history", Kind::Bool),
+    setting("run.hash-in-name", Kind::Bool),
+    setting("run.timestamp-format", Kind::Choice(&["local", "utc", "epoch"])),
+    setting("run.k-factor", Kind::Number(1)),
+    setting("run.initial-rating", Kind::Number(0)),
+    setting("package.author", Kind::Text),
+    setting("package.team", Kind::Text),
+    setting("package.max-size", Kind::Size),
+    setting("submit.server", Kind::Text),
+    setting("lint.allow", Kind::List),
+    setting("lint.max-memory", Kind::Size),
+    setting("lint.max-data-segment", Kind::Size),
+    setting("build.features", Kind::List),
+    setting("wasm-opt.level", Kind::Choice(&["O2", "O3", "O4", "Os", "Oz"])),
+    setting(
+        "tournament.format",
+        Kind::Choice(&["round-robin", "single-elim", "swiss"]),
+    ),
+    setting("tournament.games", Kind::Number(1)),
+    setting(
+        "tournament.seeding",
+        Kind::Choice(&["given", "name", "random", "standings", "elo"]),
+    ),
+    setting("tournament.jobs", Kind::Number(1)),
+    setting("tournament.max-size", Kind::Size),
+    setting("tournament.unrated", Kind::Bool),
+];
+
+/// Keys this tool keeps in the config files for itself, and that aren't settings.
+const INTERNAL_KEYS: &[&str] = &["release-version"];
+
+fn find_setting(key: &str) -> anyhow::Result<&'static Setting> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.key == key)
+        .with_context(|| {
+            let keys: Vec<&str> = SETTINGS.iter().map(|setting| setting.key).collect();
+            format!(
+                "there's no setting `{key}`, the settings are: {}",
+                keys.join(", ")
+            )
+        })
+}
+
+/// What to say about an unknown key: the setting it's closest to, if it looks like a typo of
+/// one, or otherwise the settings in its section.
+fn suggest_setting(key: &str) -> String {
+    // Close enough to be a typo of the name, swapped letters and all, without suggesting everything
+    let name = key.rsplit('.').next().unwrap_or(key);
+    let max_distance = (name.chars().count() / 3).max(2);
+    let closest = SETTINGS
+        .iter()
+        .map(|setting| (edit_distance(setting.key, key), setting.key))
+        .min();
+    if let Some((_, closest)) = closest.filter(|&(distance, _)| distance <= max_distance) {
+        return format!(", did you mean `{closest}`?");
+    }
+    let section = key.split_once('.').map(|(section, _)| section);
+    let keys: Vec<&str> = SETTINGS
+        .iter()
+        .filter(|setting| setting.key.split_once('.').map(|(section, _)| section) == section)
+        .map(|setting| setting.key)
+        .collect();
+    match section {
+        Some(section) if keys.is_empty() => format!(", and there's no [{section}] section"),
+        Some(section) => format!(", the settings in [{section}] are: {}", keys.join(", ")),
+        None => format!(", the settings outside a section are: {}", keys.join(", ")),
+    }
+}
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Flag,
+    User(PathBuf),
+    WorkspaceMetadata(PathBuf),
+    WorkspaceFile(PathBuf),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag => f.write_str("the command line"),
+            Self::User(path) | Self::WorkspaceFile(path) => write!(f, "{}", path.display()),
+            Self::WorkspaceMetadata(path) => {
+                write!(f, "{} [workspace.metadata.protologic]", path.display())
+            }
+        }
+    }
+}
+
+/// One config file's settings.
+struct Layer {
+    origin: Origin,
+    /// What relative paths in it are relative to.
+    base: PathBuf,
+    values: Table,
+}
+
+/// The settings from every config file, ready to be resolved against flags.
+pub struct Settings {
+    /// From the least specific to the most.
+    layers: Vec<Layer>,
+    /// Keys in the files that aren't settings, which are ignored.
+    pub warnings: Vec<String>,
+}
+
+/// The value at a dotted `key` in `table`.
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (section, key) = match key.split_once('.') {
+        Some((section, key)) => (table.get(section)?.as_table()?, key),
+        None => (table, key),
+    };
+    section.get(key)
+}
+
+/// Checks a value from a config file is what the setting takes.
+fn check_kind(kind: Kind, value: &Value) -> bool {
+    match kind {
+        Kind::Bool => value.is_bool(),
+        Kind::Number(min) => value.as_integer().is_some_and(|number| number >= min),
+        Kind::Path | Kind::Text => value.is_str(),
+        Kind::Size => value
+            .as_str()
+            .is_some_and(|size| size.parse::<ByteSize>().is_ok()),
+        Kind::Choice(choices) => value.as_str().is_some_and(|value| choices.contains(&value)),
+        Kind::List => value
+            .as_array()
+            .is_some_and(|values| values.iter().all(Value::is_str)),
+    }
+}
+
+fn describe_kind(kind: Kind) -> String {
+    match kind {
+        Kind::Bool => "true or false".to_owned(),
+        Kind::Number(min) => format!("a whole number, at least {min}"),
+        Kind::Path => "a path".to_owned(),
+        Kind::Text => "a string".to_owned(),
+        Kind::Size => "a size, like 2MiB".to_owned(),
+        Kind::Choice(choices) => format!("one of {}", choices.join(", ")),
+        Kind::List => "a list of strings".to_owned(),
+    }
+}
+
+/// The dotted keys of every value in `table`.
+fn keys(table: &Table, prefix: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value.as_table() {
+            // Only tables that are sections, settings don't nest any further
+            Some(section) if prefix.is_empty() => {
+                keys.extend(self::keys(section, &format!("{key}.")))
+            }
+            _ => keys.push(key),
+        }
+    }
+    keys
+}
+
+impl Layer {
+    /// Checks the values are what their settings take, returning warnings for keys that aren't
+    /// settings.
+    fn check(&self) -> anyhow::Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        for key in keys(&self.values, "") {
+            if INTERNAL_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            let Ok(setting) = find_setting(&key) else {
+                // Everyone working on the workspace shares its file, so a typo there would
+                // quietly change what everyone gets
+                if let Origin::WorkspaceFile(_) = self.origin {
+                    anyhow::bail!(
+                        "{} has `{key}`, which isn't a setting{}",
+                        self.origin,
+                        suggest_setting(&key)
+                    );
+                }
+                warnings.push(format!(
+                    "{} has `{key}`, which isn't a setting, so it's ignored",
+                    self.origin
+                ));
+                continue;
+            };
+            let value = lookup(&self.values, &key).expect("the key came from the table");
+            if !check_kind(setting.kind, value) {
+                anyhow::bail!(
+                    "`{key}` in {} is {value}, but has to be {}",
+                    self.origin,
+                    describe_kind(setting.kind)
+                );
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+/// The workspace root: the closest directory up from `dir` with a `Cargo.toml` that has a
+/// `[workspace]`, or failing that the closest package. This reads the manifests itself rather than
+/// asking cargo, so it's quick enough for shell completion.
+pub fn find_workspace_root(dir: &Path) -> Option<PathBuf> {
+    let mut package = None;
+    for dir in dir.ancestors() {
+        let Ok(manifest) = std::fs::read_to_string(dir.join("Cargo.toml")) else {
+            continue;
+        };
+        let Ok(manifest) = manifest.parse::<Table>() else {
+            break;
+        };
+        if manifest.contains_key("workspace") {
+            return Some(dir.to_owned());
+        }
+        // Packages can be members of a workspace further up
+        package.get_or_insert_with(|| dir.to_owned());
+    }
+    package
+}
+
+fn read_table(path: &Path) -> anyhow::Result<Option<Table>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("trying to read {path:?}"))?;
+    let table = contents
+        .parse()
+        .with_context(|| format!("trying to parse {path:?}"))?;
+    Ok(Some(table))
+}
+
+impl Settings {
+    fn from_layers(layers: Vec<Layer>) -> anyhow::Result<Self> {
+        let mut warnings = Vec::new();
+        for layer in &layers {
+            warnings.extend(layer.check()?);
+        }
+        Ok(Self { layers, warnings })
+    }
+
+    /// Reads the user config and, with a workspace at `workspace_root`, its settings.
+    pub fn load(workspace_root: Option<&Path>) -> anyhow::Result<Self> {
+        let mut layers = Vec::new();
+        let user = UserConfig::path()?;
+        if let Some(values) = read_table(&user)? {
+            layers.push(Layer {
+                base: user.parent().unwrap_or(Path::new("")).to_owned(),
+                origin: Origin::User(user),
+                values,
+            });
+        }
+
+        if let Some(root) = workspace_root {
+            let manifest = root.join("Cargo.toml");
+            let metadata = read_table(&manifest)?.and_then(|mut manifest| {
+                let workspace = manifest.remove("workspace")?;
+                let values = workspace
+                    .get("metadata")?
+                    .get("protologic")?
+                    .as_table()?
+                    .clone();
+                Some(values)
+            });
+            if let Some(values) = metadata {
+                layers.push(Layer {
+                    origin: Origin::WorkspaceMetadata(manifest),
+                    base: root.to_owned(),
+                    values,
+                });
+            }
+
+            let file = root.join(WORKSPACE_FILE);
+            if let Some(values) = read_table(&file)? {
+                layers.push(Layer {
+                    origin: Origin::WorkspaceFile(file),
+                    base: root.to_owned(),
+                    values,
+                });
+            }
+        }
+        Self::from_layers(layers)
+    }
+
+    /// The value of `key` in the most specific config file that has it, with that file.
+    fn lookup(&self, key: &str) -> Option<(&Value, &Layer)> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| Some((lookup(&layer.values, key)?, layer)))
+    }
+
+    /// The one place it's decided which value of a setting counts: `flag` if it was given on the
+    /// command line, and otherwise the most specific config file's, made into a `T` by `convert`
+    /// along with what the file's relative paths are relative to.
+    pub fn resolve<T>(
+        &self,
+        key: &str,
+        flag: Option<T>,
+        convert: impl FnOnce(&Value, &Path) -> Option<T>,
+    ) -> Option<(T, Origin)> {
+        debug_assert!(find_setting(key).is_ok(), "`{key}` isn't a setting");
+        if let Some(flag) = flag {
+            return Some((flag, Origin::Flag));
+        }
+        let (value, layer) = self.lookup(key)?;
+        Some((convert(value, &layer.base)?, layer.origin.clone()))
+    }
+
+    pub fn path(&self, key: &str, flag: Option<PathBuf>) -> Option<PathBuf> {
+        self.path_with_origin(key, flag).map(|(path, _)| path)
+    }
+
+    pub fn path_with_origin(&self, key: &str, flag: Option<PathBuf>) -> Option<(PathBuf, Origin)> {
+        self.resolve(key, flag, |value, base| Some(base.join(value.as_str()?)))
+    }
+
+    /// A flag that's on when given, or when a config file turns it on.
+    pub fn flag(&self, key: &str, flag: bool) -> bool {
+        self.resolve(key, flag.then_some(true), |value, _| value.as_bool())
+            .is_some_and(|(on, _)| on)
+    }
+
+    pub fn number<T: TryFrom<i64>>(&self, key: &str, flag: Option<T>) -> Option<T> {
+        self.resolve(key, flag, |value, _| value.as_integer()?.try_into().ok())
+            .map(|(number, _)| number)
+    }
+
+    pub fn text(&self, key: &str, flag: Option<String>) -> Option<String> {
+        self.resolve(key, flag, |value, _| Some(value.as_str()?.to_owned()))
+            .map(|(text, _)| text)
+    }
+
+    pub fn list(&self, key: &str, flag: Option<Vec<String>>) -> Option<Vec<String>> {
+        self.resolve(key, flag, |value, _| {
+            value
+                .as_array()?
+                .iter()
+                .map(|value| Some(value.as_str()?.to_owned()))
+                .collect()
+        })
+        .map(|(list, _)| list)
+    }
+
+    pub fn size(&self, key: &str, flag: Option<ByteSize>) -> Option<ByteSize> {
+        self.resolve(key, flag, |value, _| value.as_str()?.parse().ok())
+            .map(|(size, _)| size)
+    }
+
+    /// Every setting that's set in a config file, with its value and the file it's from.
+    pub fn values(&self) -> Vec<(&'static str, Value, Origin)> {
+        SETTINGS
+            .iter()
+            .filter_map(|setting| {
+                let (value, origin) =
+                    self.resolve(setting.key, None, |value, _| Some(value.clone()))?;
+                Some((setting.key, value, origin))
+            })
+            .collect()
+    }
+
+    pub fn choice<T: clap::ValueEnum>(&self, key: &str, flag: Option<T>) -> Option<T> {
+        self.resolve(key, flag, |value, _| {
+            T::from_str(value.as_str()?, true).ok()
+        })
+        .map(|(choice, _)| choice)
+    }
+}
+
+static SETTINGS_FOR_RUN: OnceLock<Settings> = OnceLock::new();
+
+/// The settings for this run, read the first time they're needed, from the workspace around the
+/// current directory.
+pub fn settings() -> anyhow::Result<&'static Settings> {
+    if let Some(settings) = SETTINGS_FOR_RUN.get() {
+        return Ok(settings);
+    }
+    let cwd = std::env::current_dir().context("trying to find the current directory")?;
+    let settings = Settings::load(find_workspace_root(&cwd).as_deref())?;
+    for warning in &settings.warnings {
+        say!("Warning: {warning}");
+    }
+    Ok(SETTINGS_FOR_RUN.get_or_init(|| settings))
+}
+
+/// `config`'s subcommands.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Prints a setting's value, from whichever config file has the final say.
+    Get {
+        key: String,
+        /// Print which config file the value is from, too.
+        #[arg(long, default_value = "false")]
+        show_origin: bool,
+    },
+    /// Saves a setting, like `cargo protologic config set protologic-path ~/Protologic/Release`.
+    ///
+    /// It goes in your user config, unless `--workspace` puts it in the workspace's `protologic.toml`.
+    Set {
+        key: String,
+        value: String,
+        /// Save it in the workspace's `protologic.toml`, for everyone working on it.
+        #[arg(long, default_value = "false")]
+        workspace: bool,
+    },
+    /// Removes a setting from your user config, or with `--workspace` from `protologic.toml`.
+    Unset {
+        key: String,
+        #[arg(long, default_value = "false")]
+        workspace: bool,
+    },
+    /// Prints every setting that's set, with its value.
+    List {
+        /// Print which config file each value is from, too.
+        #[arg(long, default_value = "false")]
+        show_origin: bool,
+    },
+}
+
+/// A value for the command line: strings without quotes, and lists comma separated.
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+/// Parses a value given to `config set` as what the setting takes.
+fn parse_value(setting: &Setting, value: &str) -> anyhow::Result<Value> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "`{}` has to be {}, not {value:?}",
+            setting.key,
+            describe_kind(setting.kind)
+        )
+    };
+    let value = match setting.kind {
+        Kind::Bool => Value::Boolean(value.parse().map_err(|_| invalid())?),
+        Kind::Number(min) => {
+            let number: i64 = value.parse().map_err(|_| invalid())?;
+            if number < min {
+                return Err(invalid());
+            }
+            Value::Integer(number)
+        }
+        Kind::Path | Kind::Text => Value::String(value.to_owned()),
+        Kind::Size => {
+            value.parse::<ByteSize>().map_err(|_| invalid())?;
+            Value::String(value.to_owned())
+        }
+        Kind::Choice(choices) => {
+            let choice = value.to_lowercase();
+            if !choices.contains(&choice.as_str()) {
+                return Err(invalid());
+            }
+            Value::String(choice)
+        }
+        Kind::List => Value::Array(
+            value
+                .split(',')
+                .map(|item| Value::String(item.trim().to_owned()))
+                .collect(),
+        ),
+    };
+    Ok(value)
+}
+
+/// Sets the dotted `key` in `table`, or removes it for `None`. Returns whether it was there.
+fn set_in_table(table: &mut Table, key: &str, value: Option<Value>) -> bool {
+    let (table, key) = match key.split_once('.') {
+        Some((section, key)) => {
+            let section = table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()));
+            let Some(section) = section.as_table_mut() else {
+                return false;
+            };
+            (section, key)
+        }
+        None => (table, key),
+    };
+    match value {
+        Some(value) => table.insert(key.to_owned(), value).is_some(),
+        None => table.remove(key).is_some(),
+    }
+}
+
+/// Sets or unsets a setting in the user config, whose `protologic-path` is kept as an absolute path
+/// and forgets the installed version along with it.
+fn write_user_setting(setting: &Setting, value: Option<&str>) -> anyhow::Result<()> {
+    let mut user_config = UserConfig::read()?;
+    let had = match (setting.key, value) {
+        ("protologic-path", Some(path)) => {
+            user_config.set_protologic_path(path)?;
+            true
+        }
+        ("protologic-path", None) => {
+            user_config.release_version = None;
+            user_config.protologic_path.take().is_some()
+        }
+        (key, Some(value)) => {
+            let mut value = parse_value(setting, value)?;
+            if let (Kind::Path, Value::String(path)) = (setting.kind, &value) {
+                // Relative paths would mean something different from every workspace
+                let path = std::path::absolute(path)
+                    .with_context(|| format!("trying to make {path:?} absolute"))?;
+                value = Value::String(path.to_string_lossy().into_owned());
+            }
+            set_in_table(&mut user_config.settings, key, Some(value));
+            true
+        }
+        (key, None) => set_in_table(&mut user_config.settings, key, None),
+    };
+    if value.is_none() && !had {
+        say!("`{}` isn't set in your user config", setting.key);
+        return Ok(());
+    }
+
+    if dry_run() {
+        say!(
+            "Would write to {}:\n{}",
+            UserConfig::path()?.display(),
+            toml::to_string(&user_config)?
+        );
+        return Ok(());
+    }
+    let path = user_config.write()?;
+    say!("Saved to {}", path.display());
+    Ok(())
+}
+
+/// Sets or unsets a setting in the workspace's `protologic.toml`, keeping the rest of the file as
+/// it was.
+fn write_workspace_setting(setting: &Setting, value: Option<&str>) -> anyhow::Result<()> {
+    let cwd = std::env::current_dir().context("trying to find the current directory")?;
+    let root = find_workspace_root(&cwd).context(
+        "not in a cargo workspace, so there's no protologic.toml to save workspace settings in",
+    )?;
+    let path = root.join(WORKSPACE_FILE);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("trying to read {path:?}")),
+    };
+    let mut doc: toml_edit::DocumentMut = contents
+        .parse()
+        .with_context(|| format!("trying to parse {path:?}"))?;
+
+    let (section, key) = match setting.key.split_once('.') {
+        Some((section, key)) => (Some(section), key),
+        None => (None, setting.key),
+    };
+    let table = match section {
+        Some(section) => doc
+            .entry(section)
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .with_context(|| format!("`{section}` in {path:?} isn't a table"))?,
+        None => doc.as_table_mut(),
+    };
+    match value {
+        Some(value) => {
+            let value: toml_edit::Value = parse_value(setting, value)?
+                .to_string()
+                .parse()
+                .context("trying to write the value as TOML")?;
+            table.insert(key, toml_edit::Item::Value(value));
+        }
+        None => {
+            if table.remove(key).is_none() {
+                say!("`{}` isn't set in {}", setting.key, path.display());
+                return Ok(());
+            }
+        }
+    }
+
+    if dry_run() {
+        say!("Would write to {}:\n{doc}", path.display());
+        return Ok(());
+    }
+    std::fs::write(&path, doc.to_string()).with_context(|| format!("trying to write {path:?}"))?;
+    say!("Saved to {}", path.display());
+    Ok(())
+}
+
+/// Runs a `config` subcommand.
+pub fn config(command: ConfigCommand) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Get { key, show_origin } => {
+            find_setting(&key)?;
+            let settings = settings()?;
+            let (value, origin) = settings
+                .resolve(&key, None, |value, _| Some(value.clone()))
+                .with_context(|| format!("`{key}` isn't set in any config file"))?;
+            if show_origin {
+                println!("{origin}\t{}", display_value(&value));
+            } else {
+                println!("{}", display_value(&value));
+            }
+        }
+        ConfigCommand::List { show_origin } => {
+            for (key, value, origin) in settings()?.values() {
+                if show_origin {
+                    println!("{origin}\t{key} = {value}");
+                } else {
+                    println!("{key} = {value}");
+                }
+            }
+        }
+        ConfigCommand::Set {
+            key,
+            value,
+            workspace,
+        } => {
+            let setting = find_setting(&key)?;
+            if let Some(reason) = setting.managed_by {
+                anyhow::bail!("`{key}` can't be set with `config set`, {reason}");
+            }
+            if workspace {
+                write_workspace_setting(setting, Some(&value))?;
+            } else {
+                write_user_setting(setting, Some(&value))?;
+            }
+        }
+        ConfigCommand::Unset { key, workspace } => {
+            let setting = find_setting(&key)?;
+            if let Some(reason) = setting.managed_by {
+                anyhow::bail!("`{key}` can't be unset with `config unset`, {reason}");
+            }
+            if workspace {
+                write_workspace_setting(setting, None)?;
+            } else {
+                write_user_setting(setting, None)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use clap::ValueEnum;
+    use toml::Table;
+
+    use super::{
+        find_setting, find_workspace_root, parse_value, set_in_table, Kind, Layer, Origin,
+        Settings, SETTINGS,
+    };
+    use crate::bench_opt::Level;
+    use crate::naming::TimestampFormat;
+    use crate::tournament::{Seeding, TournamentFormat};
+
+    fn layer(origin: Origin, base: &str, toml: &str) -> Layer {
+        Layer {
+            origin,
+            base: PathBuf::from(base),
+            values: toml.parse().unwrap(),
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings::from_layers(vec![
+            layer(
+                Origin::User(PathBuf::from("/home/me/.config/cargo-protologic/config.toml")),
+                "/home/me/.config/cargo-protologic",
+                "protologic-path = \"/opt/Release\"\n[run]\nretries = 1\nkeep-last = 5\nquiet-sim = true\n",
+            ),
+            layer(
+                Origin::WorkspaceMetadata(PathBuf::from("/work/Cargo.toml")),
+                "/work",
+                "output-dir = \"battles\"\n[run]\nretries = 2\n",
+            ),
+            layer(
+                Origin::WorkspaceFile(PathBuf::from("/work/protologic.toml")),
+                "/work",
+                "[run]\nretries = 3\ntimestamp-format = \"utc\"\n",
+            ),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve_prefers_flags_then_the_most_specific_file() {
+        let settings = settings();
+        assert_eq!(settings.number("run.retries", Some(7u32)), Some(7));
+        assert_eq!(settings.number::<u32>("run.retries", None), Some(3));
+        assert_eq!(settings.number::<u32>("run.keep-last", None), Some(5));
+        assert_eq!(settings.number::<u64>("run.max-ticks", None), None);
+
+        let origin = |key| settings.resolve(key, None, |_, _| Some(())).unwrap().1;
+        assert_eq!(
+            origin("run.retries"),
+            Origin::WorkspaceFile(PathBuf::from("/work/protologic.toml"))
+        );
+        assert_eq!(
+            origin("output-dir"),
+            Origin::WorkspaceMetadata(PathBuf::from("/work/Cargo.toml"))
+        );
+        assert!(matches!(origin("protologic-path"), Origin::User(_)));
+        assert_eq!(
+            settings.resolve("output-dir", Some(()), |_, _| None),
+            Some(((), Origin::Flag))
+        );
+
+        // A flag can turn a setting on, but not off
+        assert!(settings.flag("run.quiet-sim", false));
+        assert!(settings.flag("run.notify", true));
+        assert!(!settings.flag("run.notify", false));
+        assert_eq!(
+            settings.choice("run.timestamp-format", None),
+            Some(TimestampFormat::Utc)
+        );
+        assert_eq!(
+            settings.choice("run.timestamp-format", Some(TimestampFormat::Epoch)),
+            Some(TimestampFormat::Epoch)
+        );
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_file_they_are_in() {
+        let settings = settings();
+        assert_eq!(
+            settings.path("output-dir", None),
+            Some(PathBuf::from("/work/battles"))
+        );
+        assert_eq!(
+            settings.path("output-dir", Some(PathBuf::from("here"))),
+            Some(PathBuf::from("here"))
+        );
+        assert_eq!(
+            settings.path("protologic-path", None),
+            Some(PathBuf::from("/opt/Release"))
+        );
+    }
+
+    #[test]
+    fn settings_are_checked_when_loaded() {
+        let loaded = Settings::from_layers(vec![layer(
+            Origin::User(PathBuf::from("config.toml")),
+            "",
+            "outptu-dir = \"x\"\nrelease-version = \"v0.7.2\"\n[run]\nkeep-last = 2\nsplines = 3\n",
+        )])
+        .unwrap();
+        assert_eq!(loaded.warnings.len(), 2, "{:?}", loaded.warnings);
+        assert!(loaded.warnings[0].contains("`outptu-dir`"));
+        assert!(loaded.warnings[1].contains("`run.splines`"));
+
+        // The workspace's file is shared, so what isn't a setting there is an error
+        for (unknown, hint) in [
+            ("outptu-dir = \"x\"\n", "did you mean `output-dir`?"),
+            (
+                "[tournament]\ngamse = 2\n",
+                "did you mean `tournament.games`?",
+            ),
+            (
+                "[wasm-opt]\nsplines = 3\n",
+                "the settings in [wasm-opt] are: wasm-opt.level",
+            ),
+            ("[bulid]\nfeatures = []\n", "did you mean `build.features`?"),
+            ("[replay]\nplayer = \"x\"\n", "there's no [replay] section"),
+        ] {
+            let err = Settings::from_layers(vec![layer(
+                Origin::WorkspaceFile(PathBuf::from("protologic.toml")),
+                "",
+                unknown,
+            )])
+            .err()
+            .unwrap();
+            assert!(err.to_string().ends_with(hint), "{err}");
+        }
+
+        for wrong in ["[run]\nkeep-last = 0\n", "[run]\nquiet-sim = \"yes\"\n"] {
+            let err = Settings::from_layers(vec![layer(
+                Origin::WorkspaceFile(PathBuf::from("protologic.toml")),
+                "",
+                wrong,
+            )])
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("has to be"), "{err}");
+        }
+    }
+
+    #[test]
+    fn parse_value_takes_what_the_setting_does() -> anyhow::Result<()> {
+        let parse = |key, value| parse_value(find_setting(key).unwrap(), value);
+        assert_eq!(parse("run.keep-last", "3")?, toml::Value::Integer(3));
+        assert!(parse("run.keep-last", "0").is_err());
+        assert_eq!(parse("run.notify", "true")?, toml::Value::Boolean(true));
+        assert!(parse("run.notify", "yes").is_err());
+        assert_eq!(
+            parse("run.timestamp-format", "UTC")?,
+            toml::Value::String("utc".to_owned())
+        );
+        assert!(parse("run.timestamp-format", "iso").is_err());
+        assert_eq!(
+            parse("package.max-size", "2MiB")?,
+            toml::Value::String("2MiB".to_owned())
+        );
+        assert!(parse("package.max-size", "big").is_err());
+        assert!(find_setting("run.fleet-a").is_err());
+
+        let mut table = Table::new();
+        assert!(!set_in_table(
+            &mut table,
+            "run.retries",
+            Some(parse("run.retries", "2")?)
+        ));
+        assert_eq!(table.to_string(), "[run]\nretries = 2\n");
+        assert!(set_in_table(&mut table, "run.retries", None));
+        Ok(())
+    }
+
+    #[test]
+    fn choices_match_the_flags() {
+        fn names<T: ValueEnum>() -> Vec<String> {
+            T::value_variants()
+                .iter()
+                .map(|value| value.to_possible_value().unwrap().get_name().to_owned())
+                .collect()
+        }
+        for (key, names) in [
+            ("run.timestamp-format", names::<TimestampFormat>()),
+            ("tournament.format", names::<TournamentFormat>()),
+            ("tournament.seeding", names::<Seeding>()),
+            (
+                "wasm-opt.level",
+                Level::ALL.map(|level| level.to_string()).to_vec(),
+            ),
+        ] {
+            let Kind::Choice(choices) = find_setting(key).unwrap().kind else {
+                panic!("{key} isn't a choice");
+            };
+            assert_eq!(choices, names, "{key}");
+        }
+        assert!(SETTINGS
+            .iter()
+            .all(|setting| setting.key.matches('.').count() <= 1));
+    }
+
+    #[test]
+    fn workspace_root_is_the_closest_workspace() -> anyhow::Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::write(workspace.path().join("Cargo.toml"), "[workspace]\n")?;
+        let member = workspace.path().join("my_fleet");
+        std::fs::create_dir_all(member.join("src"))?;
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"my_fleet\"\n",
+        )?;
+        assert_eq!(
+            find_workspace_root(&member.join("src")).as_deref(),
+            Some(workspace.path())
+        );
+
+        let package = tempfile::tempdir()?;
+        std::fs::write(
+            package.path().join("Cargo.toml"),
+            "[package]\nname = \"solo\"\n",
+        )?;
+        assert_eq!(
+            find_workspace_root(package.path()).as_deref(),
+            Some(package.path())
+        );
+        assert_eq!(find_workspace_root(Path::new("/")), None);
+        Ok(())
+    }
+}
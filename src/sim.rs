@@ -0,0 +1,686 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+
+use crate::fleet_lines::FleetTagger;
+use crate::interrupt::{handler_installed, interrupted};
+use crate::naming::add_extension;
+use crate::output::say;
+use crate::priority::{limit_command, pin_child};
+use crate::settings::settings;
+
+/// The Protologic sim from a Protologic/Release checkout.
+#[derive(Debug, Clone)]
+pub struct Sim {
+    pub path: PathBuf,
+    pub debug: bool,
+    /// Extra environment variables for the sim, on top of our own environment.
+    pub env: Vec<(String, String)>,
+    /// Where to run the sim, since it leaves logs and crash dumps in its working directory.
+    /// Defaults to the directory the replay goes in.
+    pub cwd: Option<PathBuf>,
+    pub sink: SimOutputSink,
+    /// Also write what each fleet printed to its own log next to the replay.
+    pub split_fleet_logs: bool,
+    /// End battles after this many ticks.
+    pub max_ticks: Option<u64>,
+    /// How much wasm fuel each fleet gets per tick.
+    pub fuel_limit: Option<u64>,
+    /// Run the sim at a low scheduling priority, to keep the machine usable.
+    pub low_priority: bool,
+    /// The cores to pin the sim to, or `None` to let it run anywhere.
+    pub cpus: Option<Vec<usize>>,
+    /// More arguments for the sim, passed on as they are after all of ours.
+    pub extra_args: Vec<String>,
+}
+
+/// Where the sim's console output goes. It's always kept to work out who won, whatever the sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SimOutputSink {
+    /// Echoed to our own stdout and stderr as it comes in.
+    #[default]
+    Inherit,
+    /// Written to a `.sim.log` next to the replay.
+    Log,
+    /// Thrown away.
+    Discard,
+}
+
+/// What came out of one run of the sim.
+#[derive(Debug)]
+pub struct SimRun {
+    pub status: ExitStatus,
+    /// Every line the sim printed, stdout and stderr together.
+    pub output: Vec<String>,
+    /// Wall-clock time from spawning the sim to it exiting.
+    pub duration: Duration,
+}
+
+impl Sim {
+    pub fn new(protologic_path: &Path, debug: bool) -> Self {
+        Self {
+            path: protologic_sim_path(protologic_path),
+            debug,
+            env: Vec::new(),
+            cwd: None,
+            sink: SimOutputSink::Inherit,
+            split_fleet_logs: false,
+            max_ticks: None,
+            fuel_limit: None,
+            low_priority: false,
+            cpus: None,
+            extra_args: Vec::new(),
+        }
+    }
+
+    /// The sim arguments for the limits that were set.
+    fn limit_args(&self) -> Vec<(&'static str, u64)> {
+        [
+            ("--max-ticks", self.max_ticks),
+            ("--fuel-limit", self.fuel_limit),
+        ]
+        .into_iter()
+        .filter_map(|(flag, value)| Some((flag, value?)))
+        .collect()
+    }
+
+    /// Checks the sim understands the arguments for the limits that were set, by looking for them
+    /// in its `--help`. Older sims fail with an unhelpful unknown argument error otherwise.
+    pub fn check_limits_supported(&self) -> anyhow::Result<()> {
+        let flags: Vec<_> = self
+            .limit_args()
+            .into_iter()
+            .map(|(flag, _)| flag)
+            .collect();
+        if flags.is_empty() {
+            return Ok(());
+        }
+
+        let help = Command::new(&self.path)
+            .arg("--help")
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| {
+                format!("trying to ask the sim at {:?} what it supports", self.path)
+            })?;
+        let help = [help.stdout, help.stderr].concat();
+
+        match unsupported_flags(&String::from_utf8_lossy(&help), &flags)[..] {
+            [] => Ok(()),
+            [flag] => anyhow::bail!(
+                "your sim build doesn't support `{flag}`, try updating your Protologic/Release checkout"
+            ),
+            ref flags => anyhow::bail!(
+                "your sim build doesn't support `{}`, try updating your Protologic/Release checkout",
+                flags.join("` or `")
+            ),
+        }
+    }
+
+    /// Builds the command to battle two fleets. The sim adds the replay extension to `output` itself.
+    ///
+    /// Every path is made absolute, as the sim runs in a different working directory to ours. That
+    /// directory is only created once the sim is run.
+    pub fn command(
+        &self,
+        fleet_a: &Path,
+        fleet_b: &Path,
+        seed: Option<u64>,
+        scenario: Option<&str>,
+        output: &Path,
+    ) -> anyhow::Result<Command> {
+        let output = absolute(output)?;
+        let cwd = match &self.cwd {
+            Some(cwd) => absolute(cwd)?,
+            None => output
+                .parent()
+                .context("the battle output path doesn't have a directory")?
+                .to_owned(),
+        };
+        let mut command = Command::new(absolute(&self.path)?);
+        command
+            .current_dir(cwd)
+            .arg("--fleets")
+            .args([absolute(fleet_a)?, absolute(fleet_b)?])
+            .arg("--debug")
+            .arg(self.debug.to_string())
+            .arg("--output")
+            .arg(output)
+            .envs(self.env.iter().map(|(key, value)| (key, value)));
+
+        if let Some(seed) = seed {
+            command.arg("--seed").arg(seed.to_string());
+        }
+        if let Some(scenario) = scenario {
+            command.arg("--scenario").arg(scenario);
+        }
+        for (flag, value) in self.limit_args() {
+            command.arg(flag).arg(value.to_string());
+        }
+        command.args(&self.extra_args);
+        limit_command(&mut command, self.low_priority, self.cpus.as_deref());
+
+        Ok(command)
+    }
+
+    /// Runs the sim to completion, sending its output to the sink as it goes while also keeping
+    /// hold of it. With [`SimOutputSink::Log`], the output goes to [`sim_log_path`] for `output`.
+    ///
+    /// Lines printed by a fleet are labelled with its name from `fleets` on the way to the sink,
+    /// but kept as the sim printed them.
+    pub fn run(
+        &self,
+        mut command: Command,
+        output: &Path,
+        fleets: [&str; 2],
+    ) -> anyhow::Result<SimRun> {
+        let log = match self.sink {
+            SimOutputSink::Log => {
+                let path = sim_log_path(output);
+                let file = File::create(&path)
+                    .with_context(|| format!("trying to create sim log {path:?}"))?;
+                say!("Writing sim output to {}", path.display());
+                Some(Arc::new(Mutex::new(file)))
+            }
+            SimOutputSink::Inherit | SimOutputSink::Discard => None,
+        };
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        let tagger = Arc::new(FleetTagger::new(
+            fleets,
+            color,
+            self.split_fleet_logs.then_some(output),
+        )?);
+        let sink = self.sink;
+        let echo = move |line: &str, is_stderr: bool| -> std::io::Result<()> {
+            tagger.write_fleet_log(line)?;
+            match (sink, &log) {
+                (SimOutputSink::Inherit, _) if is_stderr => eprintln!("{}", tagger.tag(line, true)),
+                (SimOutputSink::Inherit, _) => say!("{}", tagger.tag(line, true)),
+                (SimOutputSink::Log, Some(log)) => writeln!(
+                    log.lock().expect("sim log lock poisoned"),
+                    "{}",
+                    tagger.tag(line, false)
+                )?,
+                _ => {}
+            }
+            Ok(())
+        };
+
+        if let Some(cwd) = command.get_current_dir() {
+            std::fs::create_dir_all(cwd)
+                .with_context(|| format!("trying to create the sim working directory {cwd:?}"))?;
+        }
+
+        let started = Instant::now();
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("trying to run sim on fleets")?;
+        pin_child(&child, self.cpus.as_deref())
+            .context("trying to pin the sim to the `--cpus` cores")?;
+
+        let stdout = child.stdout.take().context("sim stdout wasn't piped")?;
+        let stderr = child.stderr.take().context("sim stderr wasn't piped")?;
+        let echo_stderr = echo.clone();
+        let stdout = std::thread::spawn(move || tee_lines(stdout, |line| echo(line, false)));
+        let stderr = std::thread::spawn(move || tee_lines(stderr, |line| echo_stderr(line, true)));
+
+        let status = wait_for_sim(&mut child)
+            .context("trying to wait until the protologic sim has finished running")?;
+        let duration = started.elapsed();
+
+        // Anything the sim started could still be holding its output open, and there's no point
+        // waiting on it when the battle's being thrown away
+        if interrupted() {
+            return Ok(SimRun {
+                status,
+                output: Vec::new(),
+                duration,
+            });
+        }
+
+        let mut output = stdout.join().expect("sim stdout reader panicked")?;
+        output.extend(stderr.join().expect("sim stderr reader panicked")?);
+
+        Ok(SimRun {
+            status,
+            output,
+            duration,
+        })
+    }
+}
+
+/// Formats how long something took, like `4m 32s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m {}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    std::path::absolute(path).with_context(|| format!("trying to make {path:?} absolute"))
+}
+
+/// Parses a `KEY=VALUE` environment variable. Only the first `=` separates them, so values can
+/// have `=` in them too.
+pub fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("expected `KEY=VALUE`, not `{value}`")),
+    }
+}
+
+/// Waits for the sim to exit, killing it if Ctrl-C is pressed while it's being caught.
+///
+/// Ctrl-C usually reaches the sim as well, but a sim stuck in a fleet's code may not notice it.
+fn wait_for_sim(child: &mut Child) -> std::io::Result<ExitStatus> {
+    if !handler_installed() {
+        return child.wait();
+    }
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if interrupted() {
+            // It may have exited by itself in the meantime, which is just as good
+            let _ = child.kill();
+            return child.wait();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// The flags that don't show up anywhere in the sim's help text.
+fn unsupported_flags<'a>(help: &str, flags: &[&'a str]) -> Vec<&'a str> {
+    flags
+        .iter()
+        .copied()
+        .filter(|flag| {
+            !help
+                .split(|c: char| c.is_whitespace() || matches!(c, ',' | '=' | '[' | ']'))
+                .any(|word| word == *flag)
+        })
+        .collect()
+}
+
+/// Checks a `--scenario` before any battles, returning what to pass to the sim.
+///
+/// Values that look like a path have to be a file that exists, and are made absolute since the sim
+/// runs in a different directory. Anything else is a scenario name for the sim to look up.
+pub fn resolve_scenario(scenario: &str) -> anyhow::Result<String> {
+    if !is_scenario_path(scenario) {
+        return Ok(scenario.to_owned());
+    }
+
+    let path = Path::new(scenario);
+    if !path.is_file() {
+        anyhow::bail!("couldn't find scenario file {path:?}");
+    }
+    absolute(path)?
+        .into_os_string()
+        .into_string()
+        .map_err(|path| anyhow::anyhow!("the scenario path {path:?} isn't valid UTF-8"))
+}
+
+/// A short name for a scenario to go in file names and summaries: the file name without its
+/// extension for a scenario file, or the scenario name itself.
+pub fn scenario_name(scenario: &str) -> &str {
+    if !is_scenario_path(scenario) {
+        return scenario;
+    }
+
+    Path::new(scenario)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(scenario)
+}
+
+pub fn is_scenario_path(scenario: &str) -> bool {
+    scenario.contains(['/', '\\']) || Path::new(scenario).extension().is_some()
+}
+
+/// Picks a seed for battles that need one but weren't given one.
+///
+/// Kept within 32 bits so seeds are easy to copy around and fit whatever integer the sim parses into.
+pub fn random_seed() -> u64 {
+    u64::from(fastrand::u32(..i32::MAX as u32))
+}
+
+/// Where the sim puts the replay for a given `--output` base path.
+pub fn replay_path(output: &Path) -> PathBuf {
+    add_extension(output, "json.deflate")
+}
+
+/// Where the sim's output is logged with [`SimOutputSink::Log`], for a given `--output` base path.
+pub fn sim_log_path(output: &Path) -> PathBuf {
+    add_extension(output, "sim.log")
+}
+
+fn tee_lines(
+    reader: impl Read,
+    echo: impl Fn(&str) -> std::io::Result<()>,
+) -> anyhow::Result<Vec<String>> {
+    let mut reader = BufReader::new(reader);
+    let mut lines = Vec::new();
+    let mut buf = Vec::new();
+
+    while reader
+        .read_until(b'\n', &mut buf)
+        .context("trying to read sim output")?
+        != 0
+    {
+        let line = String::from_utf8_lossy(&buf).trim_end().to_owned();
+        echo(&line).context("trying to write out sim output")?;
+        lines.push(line);
+        buf.clear();
+    }
+
+    Ok(lines)
+}
+
+/// Picks the Protologic/Release checkout to use: `explicit` if given, then the configured
+/// `protologic-path`, then the first conventional location with a sim in it.
+pub fn find_protologic_path(explicit: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    if let Some(path) = explicit {
+        return Ok(path);
+    }
+
+    let (path, origin) = locate_protologic_path()?;
+    say!(
+        "Using the Protologic Release at {} (from {origin})",
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Where [`find_protologic_path`] finds the Release when it isn't given one, and where it found
+/// it: the configured `protologic-path`, or a conventional location.
+pub fn locate_protologic_path() -> anyhow::Result<(PathBuf, String)> {
+    let mut candidates = Vec::new();
+    let configured = settings()?.path_with_origin("protologic-path", None);
+    if let Some((path, origin)) = &configured {
+        candidates.push((path.clone(), origin.to_string()));
+    }
+    if let Some(home) = dirs::home_dir() {
+        candidates.push((
+            home.join("Protologic/Release"),
+            "your home directory".to_owned(),
+        ));
+    }
+    if let Some(parent) = crate::cargo_metadata()
+        .ok()
+        .and_then(|metadata| Some(metadata.workspace_root.parent()?.to_owned()))
+    {
+        candidates.push((
+            parent.join("Protologic/Release"),
+            "next to the workspace".to_owned(),
+        ));
+        candidates.push((parent.join("Release"), "next to the workspace".to_owned()));
+    }
+
+    if let Some(found) = first_release(&candidates) {
+        return Ok(found.clone());
+    }
+    if let Some((path, origin)) = configured {
+        say!(
+            "Warning: the protologic-path in {origin}, {}, doesn't have a sim in it",
+            path.display()
+        );
+    }
+
+    let searched = candidates
+        .iter()
+        .map(|(path, _)| format!("\n  {}", path.display()))
+        .collect::<String>();
+    anyhow::bail!(
+        "can't find the Protologic Release. Download it from https://github.com/Protologic/Release, then pass its path with `--protologic-path` (or the PROTOLOGIC_PATH environment variable), or save it for next time with `cargo protologic config set protologic-path <PATH>`. Looked for a sim in:{searched}"
+    )
+}
+
+/// The first candidate that's a Protologic Release, going by whether it has a sim.
+fn first_release<T>(candidates: &[(PathBuf, T)]) -> Option<&(PathBuf, T)> {
+    candidates
+        .iter()
+        .find(|(path, _)| protologic_sim_path(path).is_file())
+}
+
+pub fn protologic_sim_path(protologic_path: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        protologic_path.join("Sim/Windows/Protologic.Terminal.exe")
+    }
+
+    // The Release only has Windows and Linux sims, so everywhere else looks for the Linux one and
+    // fails to find it with the usual error.
+    #[cfg(not(target_os = "windows"))]
+    {
+        protologic_path.join("Sim/Linux/Protologic.Terminal")
+    }
+}
+
+/// The Protologic player, and how to launch it.
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub path: PathBuf,
+    /// Whether this is the Windows player, to be run through Wine.
+    pub wine: bool,
+}
+
+/// Finds the Protologic player in a Protologic/Release checkout, failing if it can't be run.
+///
+/// Off Windows, the Windows player is run through Wine when there's no native player, or when
+/// `wine` is set.
+pub fn find_player(protologic_path: &Path, wine: bool) -> anyhow::Result<Player> {
+    let windows_player = protologic_path.join("Player/Windows/PROTOLOGIC.exe");
+
+    #[cfg(target_os = "windows")]
+    {
+        let _ = wine;
+        if !windows_player.is_file() {
+            anyhow::bail!("Can't find the Protologic player at {windows_player:?}! Is `--protologic-path` pointing at a full Protologic/Release checkout?")
+        }
+
+        Ok(Player {
+            path: windows_player,
+            wine: false,
+        })
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        // Although this doesn't currently exist, it's a reasonably guess of what the path will be
+        let path = protologic_path.join("Player/Linux/PROTOLOGIC");
+        if path.is_file() && !wine {
+            return Ok(Player { path, wine: false });
+        }
+
+        if !windows_player.is_file() {
+            anyhow::bail!("Can't find the Protologic player at {windows_player:?}! The player is Windows-only as of 2024-02-08, and is run through Wine on other OSes. Is `--protologic-path` pointing at a full Protologic/Release checkout?")
+        }
+        if find_on_path("wine").is_none() {
+            anyhow::bail!("The Protologic player is Windows-only, and running it on this OS needs Wine, which isn't on your PATH. Install Wine (like `sudo apt install wine`), or copy the replay to a Windows machine to watch it there.")
+        }
+
+        Ok(Player {
+            path: windows_player,
+            wine: true,
+        })
+    }
+}
+
+/// Looks for an executable on the `PATH`.
+pub fn find_on_path(name: &str) -> Option<PathBuf> {
+    let exe = format!("{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&exe))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use std::time::Duration;
+
+    use super::{
+        first_release, format_duration, parse_env_var, protologic_sim_path, resolve_scenario,
+        scenario_name, unsupported_flags, Sim, SimOutputSink,
+    };
+
+    #[test]
+    fn first_release_needs_a_sim() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let empty = dir.path().join("Protologic/Release");
+        let release = dir.path().join("Release");
+        let sim = protologic_sim_path(&release);
+        std::fs::create_dir_all(&empty)?;
+        std::fs::create_dir_all(sim.parent().unwrap())?;
+        std::fs::write(&sim, b"")?;
+
+        let candidates = [
+            (dir.path().join("missing"), 1),
+            (empty, 2),
+            (release.clone(), 3),
+        ];
+        assert_eq!(first_release(&candidates), Some(&(release, 3)));
+        assert_eq!(first_release(&candidates[..2]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_env_var_keeps_equals_in_values() {
+        assert_eq!(
+            parse_env_var("TUNING=a=b"),
+            Ok(("TUNING".to_owned(), "a=b".to_owned()))
+        );
+        assert_eq!(
+            parse_env_var("EMPTY="),
+            Ok(("EMPTY".to_owned(), String::new()))
+        );
+        assert!(parse_env_var("NO_VALUE").is_err());
+        assert!(parse_env_var("=value").is_err());
+    }
+
+    #[test]
+    fn resolve_scenario_checks_files_but_not_names() -> anyhow::Result<()> {
+        assert_eq!(resolve_scenario("asteroid_belt")?, "asteroid_belt");
+        assert!(resolve_scenario("missing/belt.json").is_err());
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("belt.json");
+        std::fs::write(&file, "{}")?;
+        let resolved = resolve_scenario(file.to_str().unwrap())?;
+        assert!(Path::new(&resolved).is_absolute());
+        assert_eq!(scenario_name(&resolved), "belt");
+        assert_eq!(scenario_name("asteroid_belt"), "asteroid_belt");
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_flags_looks_for_whole_flags() {
+        let help = "Options:\n  --fleets <A> <B>\n  --max-ticks=<N>  End the battle early\n  --fuel-limit-scale\n";
+        assert_eq!(
+            unsupported_flags(help, &["--max-ticks", "--fuel-limit"]),
+            ["--fuel-limit"]
+        );
+    }
+
+    #[test]
+    fn format_duration_is_readable() {
+        assert_eq!(format_duration(Duration::from_millis(2345)), "2.3s");
+        assert_eq!(format_duration(Duration::from_secs(272)), "4m 32s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h 2m 3s");
+    }
+
+    #[test]
+    fn command_absolutizes_paths_for_the_sim_cwd() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sim_cwd = dir.path().join("sim_logs");
+        let sim = Sim {
+            path: PathBuf::from("Sim/Linux/Protologic.Terminal"),
+            debug: false,
+            env: Vec::new(),
+            cwd: Some(sim_cwd.clone()),
+            sink: SimOutputSink::Inherit,
+            split_fleet_logs: false,
+            max_ticks: None,
+            fuel_limit: None,
+            low_priority: false,
+            cpus: None,
+            extra_args: Vec::new(),
+        };
+
+        let command = sim.command(
+            Path::new("target/protologic_fleets/a.wasm"),
+            Path::new("../b.wasm"),
+            None,
+            None,
+            &dir.path().join("battle"),
+        )?;
+
+        assert_eq!(command.get_current_dir(), Some(sim_cwd.as_path()));
+        assert!(Path::new(command.get_program()).is_absolute());
+        for arg in command
+            .get_args()
+            .filter(|arg| arg.to_string_lossy().contains('/'))
+        {
+            assert!(Path::new(arg).is_absolute(), "{arg:?} should be absolute");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn command_defaults_to_running_in_the_output_dir() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sim = Sim::new(Path::new("release"), false);
+
+        let command = sim.command(
+            Path::new("a.wasm"),
+            Path::new("b.wasm"),
+            Some(7),
+            None,
+            &dir.path().join("battle"),
+        )?;
+
+        assert_eq!(command.get_current_dir(), Some(dir.path()));
+        Ok(())
+    }
+
+    #[test]
+    fn extra_args_come_after_ours() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let sim = Sim {
+            max_ticks: Some(3000),
+            extra_args: vec!["--arena".to_owned(), "small".to_owned()],
+            ..Sim::new(Path::new("release"), false)
+        };
+
+        let command = sim.command(
+            Path::new("a.wasm"),
+            Path::new("b.wasm"),
+            None,
+            None,
+            &dir.path().join("battle"),
+        )?;
+
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy())
+            .collect();
+        assert_eq!(
+            args[args.len() - 4..],
+            ["--max-ticks", "3000", "--arena", "small"]
+        );
+        Ok(())
+    }
+}
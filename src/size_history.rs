@@ -0,0 +1,414 @@
+//! How each fleet's optimized size has gone over its builds, recorded by `build` and shown by
+//! `report sizes`.
+
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::fleet::extract_fleet_name;
+use crate::git::workspace_git_state;
+use crate::hash::{file_sha256, short_hash};
+use crate::output::say;
+use crate::run::unix_now;
+
+/// The file in a fleet output directory recording each fleet's size over its builds.
+pub const SIZE_HISTORY_FILE: &str = "size_history.jsonl";
+/// How many of the biggest increases to point out.
+const LARGEST_INCREASES: usize = 3;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// `report`'s subcommands.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum ReportCommand {
+    /// Shows how each fleet's optimized size has gone over its builds, with the biggest jumps pointed out.
+    Sizes {
+        /// Only this fleet, by its package or file name.
+        fleet: Option<String>,
+        /// How many of the newest builds to show of each fleet.
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        limit: usize,
+        /// The debug builds' sizes, instead of the release builds'.
+        #[arg(short, long, default_value = "false")]
+        debug: bool,
+        /// Print the history as JSON on stdout, for scripts.
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+}
+
+/// One build of a fleet, as a line of the size history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SizeEntry {
+    /// The fleet's file name, without `.wasm`.
+    pub fleet: String,
+    /// Unix timestamp (in seconds) of the build.
+    pub timestamp: u64,
+    /// The optimized wasm's size in bytes.
+    pub size: u64,
+    /// SHA-256 of the optimized wasm.
+    pub sha256: String,
+    /// The workspace's commit when it was built, if it's in a git repository.
+    pub commit: Option<String>,
+    /// Whether the fleet sources had uncommitted changes, so the commit isn't all of it.
+    #[serde(default)]
+    pub dirty: bool,
+}
+
+/// A build that made a fleet bigger than the one before.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Increase {
+    /// The build's place in the fleet's history, oldest first.
+    pub index: usize,
+    pub bytes: u64,
+}
+
+/// A fleet's history, as `report sizes --json` prints it.
+#[derive(Serialize, Debug, Clone)]
+struct FleetSizes<'a> {
+    fleet: &'a str,
+    builds: &'a [SizeEntry],
+    /// The biggest increases of all the builds, not only those shown, largest first.
+    largest_increases: Vec<Increase>,
+}
+
+/// Records the fleets just optimized into `dir` in its size history. A build that came out the
+/// same as the fleet's last isn't recorded again, so rebuilding without changes doesn't pad it.
+pub fn record_sizes(dir: &Path, fleets: &[PathBuf]) -> anyhow::Result<()> {
+    let path = dir.join(SIZE_HISTORY_FILE);
+    let history = if path.exists() {
+        read_sizes(&path)?
+    } else {
+        Vec::new()
+    };
+    let git = workspace_git_state();
+    let timestamp = unix_now()?;
+
+    let mut lines = String::new();
+    for fleet in fleets {
+        let size = std::fs::metadata(fleet)
+            .with_context(|| format!("trying to find the size of {fleet:?}"))?
+            .len();
+        let entry = SizeEntry {
+            fleet: extract_fleet_name(fleet)?,
+            timestamp,
+            size,
+            sha256: file_sha256(fleet)?,
+            commit: git.as_ref().map(|git| git.commit.clone()),
+            dirty: git.as_ref().is_some_and(|git| git.dirty),
+        };
+        let last = history.iter().rev().find(|last| last.fleet == entry.fleet);
+        if last.is_some_and(|last| last.sha256 == entry.sha256) {
+            continue;
+        }
+        lines.push_str(&serde_json::to_string(&entry).context("trying to serialize size history")?);
+        lines.push('\n');
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("trying to open size history {path:?}"))?;
+    file.lock()
+        .with_context(|| format!("trying to lock size history {path:?}"))?;
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("trying to append to size history {path:?}"))
+}
+
+/// Reads every build in the size history, oldest first. Lines that don't parse are skipped with a
+/// warning, as the battle history's are.
+pub fn read_sizes(path: &Path) -> anyhow::Result<Vec<SizeEntry>> {
+    let history = std::fs::read_to_string(path)
+        .with_context(|| format!("trying to read size history {path:?}"))?;
+
+    let mut skipped = 0;
+    let entries = history
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let entry = serde_json::from_str(line).ok();
+            skipped += usize::from(entry.is_none());
+            entry
+        })
+        .collect();
+    if skipped > 0 {
+        say!("Warning: skipped {skipped} lines of {path:?} that aren't builds");
+    }
+    Ok(entries)
+}
+
+/// The builds that made the fleet bigger than the one before, largest first, at most `count` of
+/// them.
+pub fn largest_increases(builds: &[SizeEntry], count: usize) -> Vec<Increase> {
+    let mut increases: Vec<Increase> = builds
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[1].size > pair[0].size)
+        .map(|(index, pair)| Increase {
+            index: index + 1,
+            bytes: pair[1].size - pair[0].size,
+        })
+        .collect();
+    // The newer of two equal jumps first, it's more likely the one being wondered about
+    increases.sort_by_key(|increase| Reverse((increase.bytes, increase.index)));
+    increases.truncate(count);
+    increases
+}
+
+/// The sizes as a line of bars, from the smallest to the largest of them.
+pub fn sparkline(sizes: &[u64]) -> String {
+    let (Some(&min), Some(&max)) = (sizes.iter().min(), sizes.iter().max()) else {
+        return String::new();
+    };
+    let top = SPARKS.len() as u64 - 1;
+    sizes
+        .iter()
+        .map(|&size| {
+            let level = if max == min {
+                0
+            } else {
+                (size - min) * top / (max - min)
+            };
+            SPARKS[level as usize]
+        })
+        .collect()
+}
+
+/// How a size changed from the build before, like `+12.3 KB (+4.1%)`.
+fn describe_change(before: u64, after: u64) -> String {
+    let (sign, bytes) = if after >= before {
+        ('+', after - before)
+    } else {
+        ('-', before - after)
+    };
+    if bytes == 0 {
+        return "same".to_owned();
+    }
+    let percent = bytes as f64 / before.max(1) as f64 * 100.0;
+    format!("{sign}{} ({sign}{percent:.1}%)", ByteSize::b(bytes))
+}
+
+fn describe_date(timestamp: u64) -> String {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .map_or("-".to_owned(), |datetime| {
+            datetime
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+}
+
+fn describe_commit(entry: &SizeEntry) -> String {
+    entry.commit.as_deref().map_or("-".to_owned(), |commit| {
+        let dirty = if entry.dirty { "+dirty" } else { "" };
+        format!("{}{dirty}", short_hash(commit))
+    })
+}
+
+/// A fleet's newest `limit` builds as a table, with a sparkline of them above it and the biggest
+/// jumps of all its builds flagged.
+pub fn size_table(fleet: &str, builds: &[SizeEntry], limit: usize) -> String {
+    let shown = builds.len().saturating_sub(limit);
+    let increases = largest_increases(builds, LARGEST_INCREASES);
+    let sizes: Vec<u64> = builds[shown..].iter().map(|entry| entry.size).collect();
+    let mut table = format!("{fleet}  {}\n", sparkline(&sizes));
+
+    let rows: Vec<[String; 5]> = builds
+        .iter()
+        .enumerate()
+        .skip(shown)
+        .map(|(index, entry)| {
+            let mut change = index.checked_sub(1).map_or("-".to_owned(), |before| {
+                describe_change(builds[before].size, entry.size)
+            });
+            if increases.iter().any(|increase| increase.index == index) {
+                change.push_str("  <- jump");
+            }
+            [
+                describe_date(entry.timestamp),
+                describe_commit(entry),
+                ByteSize::b(entry.size).to_string(),
+                change,
+                short_hash(&entry.sha256).to_owned(),
+            ]
+        })
+        .collect();
+
+    let headers = ["Date", "Commit", "Size", "Change", "Hash"];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    for row in [headers.map(str::to_owned)].iter().chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("  {cell:<width$}"))
+            .collect();
+        writeln!(table, "{}", line.concat().trim_end()).unwrap();
+    }
+
+    let hidden: Vec<String> = increases
+        .iter()
+        .filter(|increase| increase.index < shown)
+        .map(|increase| {
+            let entry = &builds[increase.index];
+            format!(
+                "+{} at {} ({})",
+                ByteSize::b(increase.bytes),
+                describe_commit(entry),
+                describe_date(entry.timestamp)
+            )
+        })
+        .collect();
+    if !hidden.is_empty() {
+        writeln!(table, "  Bigger jumps before these: {}", hidden.join(", ")).unwrap();
+    }
+    if shown > 0 {
+        writeln!(
+            table,
+            "  Showing the newest {limit} of {} builds, pass `--limit` to see more",
+            builds.len()
+        )
+        .unwrap();
+    }
+    table
+}
+
+/// The builds of each fleet, sorted by name, each oldest first.
+fn by_fleet(entries: Vec<SizeEntry>) -> Vec<(String, Vec<SizeEntry>)> {
+    let mut fleets: Vec<(String, Vec<SizeEntry>)> = Vec::new();
+    for entry in entries {
+        match fleets.iter_mut().find(|(fleet, _)| *fleet == entry.fleet) {
+            Some((_, builds)) => builds.push(entry),
+            None => fleets.push((entry.fleet.clone(), vec![entry])),
+        }
+    }
+    fleets.sort_by(|a, b| a.0.cmp(&b.0));
+    fleets
+}
+
+/// Runs a `report` subcommand.
+pub fn report(command: ReportCommand) -> anyhow::Result<()> {
+    let ReportCommand::Sizes {
+        fleet,
+        limit,
+        debug,
+        json,
+    } = command;
+    let path = crate::fleet_output_path(debug)?.join(SIZE_HISTORY_FILE);
+    let entries = if path.exists() {
+        read_sizes(&path)?
+    } else {
+        Vec::new()
+    };
+
+    let mut fleets = by_fleet(entries);
+    if let Some(name) = &fleet {
+        // Package names have `-` where the file names have `_`
+        let name = name.replace('-', "_");
+        fleets.retain(|(fleet, _)| *fleet == name);
+        if fleets.is_empty() {
+            anyhow::bail!("no builds of '{name}' in the size history {path:?}, it's recorded as `cargo protologic build` builds it");
+        }
+    }
+
+    if json {
+        let fleets: Vec<FleetSizes> = fleets
+            .iter()
+            .map(|(fleet, builds)| FleetSizes {
+                fleet,
+                builds: &builds[builds.len().saturating_sub(limit)..],
+                largest_increases: largest_increases(builds, LARGEST_INCREASES),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&fleets)?);
+        return Ok(());
+    }
+    if fleets.is_empty() {
+        say!("No builds in the size history {path:?} yet, it's recorded as `cargo protologic build` builds fleets");
+        return Ok(());
+    }
+    let tables: Vec<String> = fleets
+        .iter()
+        .map(|(fleet, builds)| size_table(fleet, builds, limit))
+        .collect();
+    say!("{}", tables.join("\n").trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{largest_increases, read_sizes, record_sizes, size_table, sparkline, SizeEntry};
+
+    fn build(size: u64) -> SizeEntry {
+        SizeEntry {
+            fleet: "glass_cannon".to_owned(),
+            timestamp: 1718476403,
+            size,
+            sha256: format!("{size:064x}"),
+            commit: Some("1a2b3c4d5e6f".to_owned()),
+            dirty: false,
+        }
+    }
+
+    #[test]
+    fn largest_increases_are_the_biggest_jumps() {
+        let builds: Vec<SizeEntry> = [100, 150, 140, 400, 410, 460].map(build).into();
+        let increases: Vec<(usize, u64)> = largest_increases(&builds, 3)
+            .into_iter()
+            .map(|increase| (increase.index, increase.bytes))
+            .collect();
+        assert_eq!(increases, [(3, 260), (5, 50), (1, 50)]);
+
+        assert_eq!(sparkline(&[100, 150, 140, 400]), "▁▂▁█");
+        assert_eq!(sparkline(&[7, 7]), "▁▁");
+
+        let table = size_table("glass_cannon", &builds, 2);
+        assert!(table.contains("+50 B (+12.2%)  <- jump"), "{table}");
+        assert!(
+            table.contains("Bigger jumps before these: +260 B at 1a2b3c4d"),
+            "{table}"
+        );
+        assert!(
+            table.contains("Showing the newest 2 of 6 builds"),
+            "{table}"
+        );
+    }
+
+    #[test]
+    fn unchanged_builds_are_not_recorded_again() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fleet: PathBuf = dir.path().join("glass_cannon.wasm");
+        std::fs::write(&fleet, "first")?;
+        record_sizes(dir.path(), std::slice::from_ref(&fleet))?;
+        record_sizes(dir.path(), std::slice::from_ref(&fleet))?;
+        std::fs::write(&fleet, "second build")?;
+        record_sizes(dir.path(), std::slice::from_ref(&fleet))?;
+
+        let history = read_sizes(&dir.path().join(super::SIZE_HISTORY_FILE))?;
+        let sizes: Vec<u64> = history.iter().map(|entry| entry.size).collect();
+        assert_eq!(sizes, [5, 12]);
+        assert!(history.iter().all(|entry| entry.fleet == "glass_cannon"));
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+//! Standings across every tournament kept in the battle output directory.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::config::find_battle_output_dir;
+use crate::hash::short_hash;
+use crate::outcome::Outcome;
+use crate::output::say;
+use crate::ratings::{default_ratings_path, ratings_table, read_ratings};
+use crate::series::Score;
+use crate::tournament::{
+    standings, standings_table, tournaments_dir, Entrant, Standing, TournamentRecord,
+};
+
+/// Arguments for `cargo protologic standings`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct StandingsArgs {
+    /// Show how two fleets did against each other instead, by name or as `name@hash` for one build.
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    vs: Vec<String>,
+    /// Show every fleet build's Elo rating instead, from rated battles and tournaments, with its rated games and peak rating.
+    #[arg(long, default_value = "false", conflicts_with_all = ["vs", "merge_versions", "output_dir"])]
+    elo: bool,
+    /// Count every build of a fleet as the same fleet, instead of telling rebuilds apart by hash.
+    #[arg(long, default_value = "false")]
+    merge_versions: bool,
+    /// The battle output directory the tournaments were run in, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Print the standings (or head-to-head record) as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+}
+
+/// What `--json` prints for the cumulative standings.
+#[derive(Serialize, Debug)]
+struct StandingsReport {
+    tournaments: usize,
+    games: u32,
+    standings: Vec<Standing>,
+}
+
+/// What `--json` prints for `--vs`, with `first` and `second` in the score being the two fleets.
+#[derive(Serialize, Debug)]
+struct HeadToHead {
+    fleets: [String; 2],
+    score: Score,
+    /// Games where neither fleet could be said to have won, from failed battles or crashes.
+    errors: u32,
+}
+
+/// A game from a recorded tournament, with the fleet on each side as its identity and its name.
+struct RecordedGame {
+    fleets: [(String, String); 2],
+    outcome: Option<Outcome>,
+}
+
+/// What a fleet is called in the standings: its name, and the start of its hash unless the
+/// versions are merged, like `glass_cannon@1a2b3c4d`.
+fn identity(entrant: &Entrant, merge_versions: bool) -> String {
+    if merge_versions {
+        entrant.name.clone()
+    } else {
+        format!("{}@{}", entrant.name, short_hash(&entrant.sha256))
+    }
+}
+
+/// Whether `query` picks out the fleet: by its name, which matches every version, or its identity.
+fn matches(query: &str, name: &str, identity: &str) -> bool {
+    query == name || query == identity
+}
+
+/// Every tournament recorded in `dir`, oldest first. Files that can't be read are skipped with a
+/// warning, so one broken file doesn't hide the rest.
+fn read_tournaments(dir: &Path) -> anyhow::Result<Vec<TournamentRecord>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("trying to read {dir:?}"))? {
+        let path = entry
+            .with_context(|| format!("trying to read an entry of {dir:?}"))?
+            .path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        match TournamentRecord::read_json(&path) {
+            Ok(record) => records.push(record),
+            Err(err) => say!("Warning: skipping {}: {err:#}", path.display()),
+        }
+    }
+    records.sort_by_key(|record| record.timestamp);
+    Ok(records)
+}
+
+/// Every game of every tournament.
+fn all_games(records: &[TournamentRecord], merge_versions: bool) -> Vec<RecordedGame> {
+    let mut games = Vec::new();
+    for record in records {
+        let side = |name: &str| {
+            let identity = record
+                .fleets
+                .iter()
+                .find(|entrant| entrant.name == name)
+                .map_or_else(
+                    || name.to_owned(),
+                    |entrant| identity(entrant, merge_versions),
+                );
+            (identity, name.to_owned())
+        };
+        for (a, b, outcome) in record.games() {
+            games.push(RecordedGame {
+                fleets: [side(a), side(b)],
+                outcome,
+            });
+        }
+    }
+    games
+}
+
+/// Every fleet in `games`, in the order they first played, tallied up into standings.
+fn cumulative_standings(games: &[RecordedGame]) -> Vec<Standing> {
+    let mut fleets: Vec<String> = Vec::new();
+    for game in games {
+        for (identity, _) in &game.fleets {
+            if !fleets.contains(identity) {
+                fleets.push(identity.clone());
+            }
+        }
+    }
+    standings(
+        &fleets,
+        games.iter().map(|game| {
+            let [(a, _), (b, _)] = &game.fleets;
+            (a.as_str(), b.as_str(), game.outcome)
+        }),
+    )
+}
+
+/// The names of the fleets that have played in the tournaments in the battle output directory
+/// `output_dir`, best first by their standings across all of them, counting every build together.
+pub fn ranking(output_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let records = read_tournaments(&tournaments_dir(output_dir))?;
+    Ok(cumulative_standings(&all_games(&records, true))
+        .into_iter()
+        .map(|standing| standing.fleet)
+        .collect())
+}
+
+/// Sums up the games between the fleets `query[0]` and `query[1]`, from either side.
+fn head_to_head(games: &[RecordedGame], query: [&str; 2]) -> (Score, u32) {
+    let mut score = Score::default();
+    let mut errors = 0;
+    for game in games {
+        let [(a, a_name), (b, b_name)] = &game.fleets;
+        let swapped = if matches(query[0], a_name, a) && matches(query[1], b_name, b) {
+            false
+        } else if matches(query[1], a_name, a) && matches(query[0], b_name, b) {
+            true
+        } else {
+            continue;
+        };
+        match (game.outcome, swapped) {
+            (Some(Outcome::FleetAWon), false) | (Some(Outcome::FleetBWon), true) => {
+                score.first += 1;
+            }
+            (Some(Outcome::FleetBWon), false) | (Some(Outcome::FleetAWon), true) => {
+                score.second += 1;
+            }
+            (Some(Outcome::Draw), _) => score.draws += 1,
+            (Some(Outcome::SimCrashed | Outcome::Unknown) | None, _) => errors += 1,
+        }
+    }
+    (score, errors)
+}
+
+/// Prints every fleet build's Elo rating, highest first.
+fn show_ratings(json: bool) -> anyhow::Result<()> {
+    let path = default_ratings_path()?;
+    let mut table = read_ratings(&path)?;
+    if table.ratings.is_empty() {
+        anyhow::bail!(
+            "no battles have been rated yet, rate some with `cargo protologic run --rated` or `cargo protologic tournament`"
+        );
+    }
+    if json {
+        table.ratings = table.ranked().into_iter().cloned().collect();
+        println!("{}", serde_json::to_string_pretty(&table)?);
+        return Ok(());
+    }
+    say!("{}", ratings_table(&table));
+    Ok(())
+}
+
+/// Prints the standings across every tournament run in the battle output directory, or with
+/// `--vs` how two fleets did against each other.
+pub fn show_standings(args: StandingsArgs) -> anyhow::Result<()> {
+    if args.elo {
+        return show_ratings(args.json);
+    }
+    let (output_dir, _) = find_battle_output_dir(args.output_dir.clone())?;
+    let dir = tournaments_dir(&output_dir);
+    let records = read_tournaments(&dir)?;
+    if records.is_empty() {
+        anyhow::bail!(
+            "no tournaments have been recorded in {}, run one with `cargo protologic tournament`",
+            dir.display()
+        );
+    }
+    let games = all_games(&records, args.merge_versions);
+
+    if let [first, second] = args.vs.as_slice() {
+        for query in [first, second] {
+            let known = games
+                .iter()
+                .flat_map(|game| &game.fleets)
+                .any(|(identity, name)| matches(query, name, identity));
+            if !known {
+                anyhow::bail!("'{query}' hasn't played in any recorded tournament");
+            }
+        }
+        let (score, errors) = head_to_head(&games, [first, second]);
+        if args.json {
+            let report = HeadToHead {
+                fleets: [first.clone(), second.clone()],
+                score,
+                errors,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+
+        let played = score.first + score.second + score.draws + errors;
+        say!("'{first}' vs '{second}', over {played} games:");
+        say!("  '{first}' won {}", score.first);
+        say!("  '{second}' won {}", score.second);
+        say!("  {} drawn, {errors} errors", score.draws);
+        if let Some(rate) = score.win_rate(true) {
+            say!(
+                "'{first}' wins {:.1}% of the games between them, with draws as half a win",
+                rate * 100.0
+            );
+        }
+        return Ok(());
+    }
+
+    let standings = cumulative_standings(&games);
+    if args.json {
+        let report = StandingsReport {
+            tournaments: records.len(),
+            games: games.len() as u32,
+            standings,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    say!(
+        "{}",
+        standings_table(
+            &format!(
+                "Standings over {} tournaments and {} games",
+                records.len(),
+                games.len()
+            ),
+            &standings
+        )
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{all_games, head_to_head, identity, read_tournaments};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::tournament::{Entrant, Matchup, TournamentFormat, TournamentGame, TournamentRecord};
+
+    fn entrant(name: &str, sha256: &str) -> Entrant {
+        Entrant {
+            name: name.to_owned(),
+            sha256: sha256.to_owned(),
+            ..Entrant::default()
+        }
+    }
+
+    fn record(
+        timestamp: u64,
+        fleets: Vec<Entrant>,
+        matchups: &[(&str, &str, Outcome)],
+    ) -> TournamentRecord {
+        TournamentRecord {
+            timestamp,
+            fleets,
+            games: 1,
+            format: TournamentFormat::RoundRobin,
+            label: None,
+            resumed: false,
+            standings: Vec::new(),
+            matchups: matchups
+                .iter()
+                .map(|&(a, b, outcome)| Matchup {
+                    fleet_a: a.to_owned(),
+                    fleet_b: b.to_owned(),
+                    games: vec![TournamentGame {
+                        game: 1,
+                        result: Some(BattleRecord {
+                            fleet_a: a.to_owned(),
+                            fleet_b: b.to_owned(),
+                            outcome,
+                            ..BattleRecord::default()
+                        }),
+                        error: None,
+                    }],
+                })
+                .collect(),
+            bracket: None,
+            rounds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rebuilt_fleets_are_told_apart_unless_merged() {
+        let records = [
+            record(
+                1,
+                vec![entrant("a", "11111111ff"), entrant("b", "22222222ff")],
+                &[("a", "b", Outcome::FleetAWon), ("b", "a", Outcome::Draw)],
+            ),
+            record(
+                2,
+                vec![entrant("a", "33333333ff"), entrant("b", "22222222ff")],
+                &[("a", "b", Outcome::FleetBWon)],
+            ),
+        ];
+        assert_eq!(identity(&records[0].fleets[0], false), "a@11111111");
+
+        let games = all_games(&records, false);
+        assert_eq!(games[2].fleets[0].0, "a@33333333");
+        let (score, errors) = head_to_head(&games, ["a@11111111", "b"]);
+        assert_eq!(
+            (score.first, score.second, score.draws, errors),
+            (1, 0, 1, 0)
+        );
+        // By name, every version counts
+        let (score, _) = head_to_head(&games, ["b", "a"]);
+        assert_eq!((score.first, score.second, score.draws), (1, 1, 1));
+
+        let merged = all_games(&records, true);
+        assert_eq!(merged[2].fleets[0].0, "a");
+    }
+
+    #[test]
+    fn tournaments_are_read_oldest_first_skipping_broken_files() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        record(20, Vec::new(), &[]).write_json(&dir.path().join("b.json"))?;
+        record(10, Vec::new(), &[]).write_json(&dir.path().join("a.json"))?;
+        std::fs::write(dir.path().join("broken.json"), "{")?;
+        std::fs::write(dir.path().join("notes.txt"), "")?;
+
+        let records = read_tournaments(dir.path())?;
+        let timestamps: Vec<u64> = records.iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, [10, 20]);
+        assert!(read_tournaments(&dir.path().join("missing"))?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,787 @@
+//! Battle statistics from a replay's event stream.
+//!
+//! A replay is a JSON object with the battle's events in an `events` array (or just the array),
+//! each an object naming its `type`. The fleets are numbered 0 and 1 as the sim numbers them, and
+//! events say which they're about:
+//!
+//! - `missile_launched` and `shell_fired`, or `weapon_fired` with a `weapon`, by `fleet`
+//! - `hit`, by `fleet` on `target_fleet`, with the `damage` done and the `weapon` that did it
+//! - `ship_destroyed`, of `fleet`'s, by the fleet `by` (the other one when it's missing)
+//! - `battle_end`, with the `winner`
+//!
+//! Events can have the `time` they happened. Type names are matched whatever their case and
+//! separators, so `ShipDestroyed` works too. Anything else, including event types from newer sims,
+//! is counted and skipped.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufReader;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::aggregate::{aggregate_table, Aggregate};
+use crate::output::{dry_run, say};
+use crate::replay::{battle_replays, find_replay, open_replay};
+use crate::replay_list::parse_battle_name;
+use crate::results::{result_path, BattleRecord};
+use crate::stats_csv::{battles_csv, fleets_csv};
+
+/// What one fleet did in a battle.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FleetStats {
+    pub name: String,
+    pub ships_lost: u64,
+    pub ships_destroyed: u64,
+    pub missiles_fired: u64,
+    pub shells_fired: u64,
+    pub missile_hits: u64,
+    pub shell_hits: u64,
+    pub damage_dealt: f64,
+    pub damage_received: f64,
+    /// When the fleet destroyed its first enemy ship.
+    pub first_kill: Option<f64>,
+    /// When the fleet destroyed its last enemy ship.
+    pub last_kill: Option<f64>,
+}
+
+impl FleetStats {
+    /// The share of missiles that hit, when any were fired.
+    pub fn missile_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.missile_hits, self.missiles_fired)
+    }
+
+    /// The share of shells that hit, when any were fired.
+    pub fn shell_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.shell_hits, self.shells_fired)
+    }
+}
+
+fn hit_rate(hits: u64, fired: u64) -> Option<f64> {
+    (fired > 0).then(|| hits as f64 / fired as f64)
+}
+
+/// A battle summed up from its replay.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BattleStats {
+    pub replay: PathBuf,
+    /// Unix timestamp (in seconds) of when the battle started, when it's known.
+    pub timestamp: Option<u64>,
+    pub fleets: [FleetStats; 2],
+    pub winner: Option<String>,
+    /// When the last event happened, which is how long the battle went on for.
+    pub length: Option<f64>,
+    /// How many events the replay had.
+    pub events: u64,
+    /// How many of them were skipped, by type, for not being understood.
+    pub unrecognized: BTreeMap<String, u64>,
+}
+
+/// A fleet as events refer to it, by the sim's number for it.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum FleetRef {
+    Index(u64),
+    Name(String),
+}
+
+/// The fields of an event that the statistics use. The rest are ignored.
+#[derive(Deserialize, Debug)]
+struct RawEvent {
+    #[serde(rename = "type", alias = "kind", alias = "event")]
+    kind: String,
+    #[serde(default, alias = "tick", alias = "t")]
+    time: Option<f64>,
+    #[serde(default, alias = "team", alias = "owner")]
+    fleet: Option<FleetRef>,
+    #[serde(default, alias = "target_team")]
+    target_fleet: Option<FleetRef>,
+    #[serde(default, alias = "killer", alias = "by_fleet")]
+    by: Option<FleetRef>,
+    #[serde(default, alias = "amount")]
+    damage: Option<f64>,
+    #[serde(default)]
+    weapon: Option<String>,
+    #[serde(default)]
+    winner: Option<FleetRef>,
+}
+
+enum Weapon {
+    Missile,
+    Shell,
+}
+
+impl Weapon {
+    fn of(name: Option<&str>) -> Option<Self> {
+        match normalize(name?).as_str() {
+            "missile" | "torpedo" => Some(Self::Missile),
+            "shell" | "gun" | "turret" | "cannon" => Some(Self::Shell),
+            _ => None,
+        }
+    }
+}
+
+/// An event type with the case and separators taken out, so `ShipDestroyed`, `ship_destroyed` and
+/// `ship-destroyed` are all `shipdestroyed`.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl FleetRef {
+    /// Which side the fleet is, 0 or 1.
+    fn side(&self) -> Option<usize> {
+        let index = match self {
+            Self::Index(index) => *index,
+            Self::Name(name) => name.trim_start_matches("fleet").trim().parse().ok()?,
+        };
+        usize::try_from(index).ok().filter(|&side| side < 2)
+    }
+}
+
+impl BattleStats {
+    fn new(replay: &Path, names: [String; 2]) -> Self {
+        let [a, b] = names;
+        Self {
+            replay: replay.to_owned(),
+            fleets: [
+                FleetStats {
+                    name: a,
+                    ..Default::default()
+                },
+                FleetStats {
+                    name: b,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// Counts one event from the replay, however it's shaped.
+    fn add(&mut self, event: serde_json::Value) {
+        self.events += 1;
+        let Ok(event) = RawEvent::deserialize(&event) else {
+            let kind = event
+                .get("type")
+                .and_then(|kind| kind.as_str())
+                .unwrap_or("(no type)");
+            *self.unrecognized.entry(kind.to_owned()).or_default() += 1;
+            return;
+        };
+        if let Some(time) = event.time {
+            self.length = Some(self.length.map_or(time, |length| length.max(time)));
+        }
+        if !self.tally(&event) {
+            *self.unrecognized.entry(event.kind).or_default() += 1;
+        }
+    }
+
+    /// Adds what the event says to the fleets' numbers. Returns whether it was understood.
+    fn tally(&mut self, event: &RawEvent) -> bool {
+        let side = event.fleet.as_ref().and_then(FleetRef::side);
+        match (normalize(&event.kind).as_str(), side) {
+            ("missilelaunched" | "missilefired", Some(side)) => {
+                self.fleets[side].missiles_fired += 1;
+            }
+            ("shellfired" | "gunfired" | "turretfired", Some(side)) => {
+                self.fleets[side].shells_fired += 1;
+            }
+            ("weaponfired" | "fired", Some(side)) => match Weapon::of(event.weapon.as_deref()) {
+                Some(Weapon::Missile) => self.fleets[side].missiles_fired += 1,
+                Some(Weapon::Shell) => self.fleets[side].shells_fired += 1,
+                None => return false,
+            },
+            ("hit" | "damage" | "shiphit" | "damaged", Some(side)) => {
+                let target = event
+                    .target_fleet
+                    .as_ref()
+                    .and_then(FleetRef::side)
+                    .unwrap_or(1 - side);
+                let damage = event.damage.unwrap_or(0.0);
+                self.fleets[side].damage_dealt += damage;
+                self.fleets[target].damage_received += damage;
+                match Weapon::of(event.weapon.as_deref()) {
+                    Some(Weapon::Missile) => self.fleets[side].missile_hits += 1,
+                    Some(Weapon::Shell) => self.fleets[side].shell_hits += 1,
+                    None => {}
+                }
+            }
+            ("shipdestroyed" | "destroyed" | "shipkilled" | "killed", Some(side)) => {
+                self.fleets[side].ships_lost += 1;
+                let killer = event
+                    .by
+                    .as_ref()
+                    .and_then(FleetRef::side)
+                    .unwrap_or(1 - side);
+                if killer != side {
+                    let fleet = &mut self.fleets[killer];
+                    fleet.ships_destroyed += 1;
+                    if let Some(time) = event.time {
+                        fleet.first_kill = Some(fleet.first_kill.map_or(time, |t| t.min(time)));
+                        fleet.last_kill = Some(fleet.last_kill.map_or(time, |t| t.max(time)));
+                    }
+                }
+            }
+            ("battleend" | "battleover" | "gameover" | "winner", _) => {
+                let winner = event.winner.as_ref().or(event.fleet.as_ref());
+                if let Some(side) = winner.and_then(FleetRef::side) {
+                    self.winner = Some(self.fleets[side].name.clone());
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Goes through a replay's top level, handing each event to the statistics as it's read.
+struct ReplayVisitor<'a>(&'a mut BattleStats);
+
+impl<'de> Visitor<'de> for ReplayVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a replay object, or an array of events")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(event) = seq.next_element::<serde_json::Value>()? {
+            self.0.add(event);
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "events" {
+                map.next_value_seed(ReplayVisitor(&mut *self.0))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ReplayVisitor<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+/// Sums up the battle in `replay`, streaming through it since replays can be hundreds of
+/// megabytes. `names` are what to call the fleets.
+pub fn replay_stats(replay: &Path, names: [String; 2]) -> anyhow::Result<BattleStats> {
+    let mut stats = BattleStats::new(replay, names);
+    let input = BufReader::new(open_replay(replay)?);
+    let mut deserializer = serde_json::Deserializer::from_reader(input);
+    deserializer
+        .deserialize_any(ReplayVisitor(&mut stats))
+        .and_then(|()| deserializer.end())
+        .with_context(|| format!("trying to read the events in replay {replay:?}"))?;
+    Ok(stats)
+}
+
+/// What the result next to a replay (or else its name) says about the battle.
+struct BattleInfo {
+    names: [String; 2],
+    winner: Option<String>,
+    timestamp: Option<u64>,
+}
+
+impl BattleInfo {
+    fn of(replay: &Path) -> Self {
+        let name = replay.file_name().unwrap_or_default().to_string_lossy();
+        let base = name
+            .strip_suffix(".json.deflate")
+            .or_else(|| name.strip_suffix(".json"))
+            .unwrap_or(&name);
+        if let Ok(record) = BattleRecord::read_json(&result_path(&replay.with_file_name(base))) {
+            return Self {
+                names: [record.fleet_a, record.fleet_b],
+                winner: record.winner,
+                timestamp: Some(record.timestamp),
+            };
+        }
+
+        let (timestamp, rest) = parse_battle_name(base);
+        let names = match rest.split_once("_vs_") {
+            Some((a, b)) => [a.to_owned(), b.to_owned()],
+            None => ["fleet 0".to_owned(), "fleet 1".to_owned()],
+        };
+        Self {
+            names,
+            winner: None,
+            timestamp,
+        }
+    }
+}
+
+/// Sums up the battle in `replay`, naming the fleets and the winner as its result does when it has
+/// one.
+pub fn battle_stats(replay: &Path) -> anyhow::Result<BattleStats> {
+    let info = BattleInfo::of(replay);
+    let mut stats = replay_stats(replay, info.names)?;
+    // The sim's own verdict goes first, the replay's is for battles without a result
+    stats.winner = info.winner.or(stats.winner);
+    stats.timestamp = info.timestamp;
+    Ok(stats)
+}
+
+/// The statistics as a table, a column per fleet.
+pub fn stats_table(stats: &BattleStats) -> String {
+    let [a, b] = &stats.fleets;
+    let count = |value: u64| value.to_string();
+    let damage = |value: f64| format!("{value:.1}");
+    let rate =
+        |rate: Option<f64>| rate.map_or("-".to_owned(), |rate| format!("{:.0}%", rate * 100.0));
+    let time = |time: Option<f64>| time.map_or("-".to_owned(), |time| format!("{time}"));
+    let rows: [(&str, String, String); 11] = [
+        ("Ships lost", count(a.ships_lost), count(b.ships_lost)),
+        (
+            "Ships destroyed",
+            count(a.ships_destroyed),
+            count(b.ships_destroyed),
+        ),
+        (
+            "Missiles fired",
+            count(a.missiles_fired),
+            count(b.missiles_fired),
+        ),
+        (
+            "Missile hit rate",
+            rate(a.missile_hit_rate()),
+            rate(b.missile_hit_rate()),
+        ),
+        ("Shells fired", count(a.shells_fired), count(b.shells_fired)),
+        (
+            "Shell hit rate",
+            rate(a.shell_hit_rate()),
+            rate(b.shell_hit_rate()),
+        ),
+        (
+            "Damage dealt",
+            damage(a.damage_dealt),
+            damage(b.damage_dealt),
+        ),
+        (
+            "Damage received",
+            damage(a.damage_received),
+            damage(b.damage_received),
+        ),
+        ("First kill", time(a.first_kill), time(b.first_kill)),
+        ("Last kill", time(a.last_kill), time(b.last_kill)),
+        ("Winner", mark_winner(stats, a), mark_winner(stats, b)),
+    ];
+
+    let label_width = rows
+        .iter()
+        .map(|(label, ..)| label.len())
+        .max()
+        .unwrap_or(0);
+    let width = rows
+        .iter()
+        .map(|(_, a, _)| a.chars().count())
+        .chain([a.name.chars().count()])
+        .max()
+        .unwrap_or(0);
+    let mut table = format!("{:label_width$}  {:<width$}  {}\n", "", a.name, b.name);
+    for (label, a, b) in rows {
+        table.push_str(format!("{label:label_width$}  {a:<width$}  {b}").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn mark_winner(stats: &BattleStats, fleet: &FleetStats) -> String {
+    match &stats.winner {
+        Some(winner) if *winner == fleet.name => "yes".to_owned(),
+        Some(_) => "no".to_owned(),
+        None => "-".to_owned(),
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct StatsArgs {
+    /// The replays to read: files, directories of them, or glob patterns like `replays/*.json.deflate`. Defaults to the newest replay in the battle output directory, as does `latest`.
+    replays: Vec<PathBuf>,
+    /// The battle output directory to find the newest replay in, if not the configured one.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Merge the battles into totals and averages per fleet, instead of showing each one.
+    #[arg(long, default_value = "false")]
+    aggregate: bool,
+    /// How to print the statistics: tables, JSON or CSV, with the last two on stdout for scripts.
+    #[arg(long, value_enum, default_value_t = StatsFormat::Table)]
+    format: StatsFormat,
+    /// Print the statistics as JSON on stdout, the same as `--format json`.
+    #[arg(long, default_value = "false", conflicts_with = "format")]
+    json: bool,
+    /// Also write the statistics as CSV to this file, a row per battle (or per fleet with `--aggregate`).
+    #[arg(long, value_name = "PATH")]
+    csv: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl StatsArgs {
+    /// The format asked for, whichever way it was.
+    pub fn format(&self) -> StatsFormat {
+        if self.json {
+            StatsFormat::Json
+        } else {
+            self.format
+        }
+    }
+}
+
+/// Prints the statistics for replays (by default the newest), each on its own or merged per fleet,
+/// as tables, JSON or CSV, and also as CSV to a file with `csv`. Replays that can't be read are reported and skipped,
+/// unless none can be.
+pub fn stats(args: StatsArgs) -> anyhow::Result<()> {
+    let format = args.format();
+    let replays = find_replays(args.replays, args.output_dir)?;
+    let mut battles = Vec::new();
+    let mut failed = Vec::new();
+    for (replay, result) in replays.iter().zip(parse_replays(&replays)) {
+        match result {
+            Ok(stats) => battles.push(stats),
+            Err(err) => {
+                say!("Warning: skipping {}: {err:#}", replay.display());
+                failed.push(replay.clone());
+            }
+        }
+    }
+    if battles.is_empty() {
+        anyhow::bail!("none of the {} replays could be read", replays.len());
+    }
+
+    if args.aggregate {
+        let aggregate = Aggregate::new(&battles, failed);
+        if let Some(path) = &args.csv {
+            write_csv(path, &fleets_csv(&aggregate))?;
+        }
+        match format {
+            StatsFormat::Table => say!("{}", aggregate_table(&aggregate).trim_end()),
+            StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&aggregate)?),
+            StatsFormat::Csv => print!("{}", fleets_csv(&aggregate)),
+        }
+    } else {
+        if let Some(path) = &args.csv {
+            write_csv(path, &battles_csv(&battles))?;
+        }
+        match format {
+            StatsFormat::Table => print_tables(&battles),
+            StatsFormat::Json => {
+                let json = match battles.as_slice() {
+                    [battle] => serde_json::to_string_pretty(battle)?,
+                    battles => serde_json::to_string_pretty(battles)?,
+                };
+                println!("{json}");
+            }
+            StatsFormat::Csv => print!("{}", battles_csv(&battles)),
+        }
+    }
+
+    let unrecognized: BTreeMap<&str, u64> = battles
+        .iter()
+        .flat_map(|battle| &battle.unrecognized)
+        .fold(BTreeMap::new(), |mut all, (kind, count)| {
+            *all.entry(kind.as_str()).or_default() += count;
+            all
+        });
+    let skipped: u64 = unrecognized.values().sum();
+    if skipped > 0 {
+        let events: u64 = battles.iter().map(|battle| battle.events).sum();
+        let types: Vec<String> = unrecognized
+            .iter()
+            .map(|(kind, count)| format!("{kind} ({count})"))
+            .collect();
+        say!(
+            "Skipped {skipped} of {events} events that aren't understood: {}",
+            types.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Prints a table for each battle, under its replay's path when there are several.
+fn print_tables(battles: &[BattleStats]) {
+    for (i, battle) in battles.iter().enumerate() {
+        if battles.len() > 1 {
+            say!(
+                "{}{}",
+                if i > 0 { "\n" } else { "" },
+                battle.replay.display()
+            );
+        }
+        say!("{}", stats_table(battle).trim_end());
+        if let Some(length) = battle.length {
+            say!("The battle lasted until {length}");
+        }
+    }
+}
+
+/// Writes the CSV to `path`, unless this is a dry run.
+fn write_csv(path: &Path, csv: &str) -> anyhow::Result<()> {
+    if dry_run() {
+        say!("Would write the statistics as CSV to {}", path.display());
+        return Ok(());
+    }
+    std::fs::write(path, csv).with_context(|| format!("trying to write CSV to {path:?}"))?;
+    say!("Wrote the statistics as CSV to {}", path.display());
+    Ok(())
+}
+
+/// The replays the paths name: files as they are, the battle replays in directories, and what glob
+/// patterns match (for shells that don't expand them). No paths, or `latest`, is the newest replay
+/// in the battle output directory.
+fn find_replays(paths: Vec<PathBuf>, output_dir: Option<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(vec![find_replay(None, output_dir)?]);
+    }
+
+    let mut replays = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let found = battle_replays(&path)?;
+            if found.is_empty() {
+                say!("Warning: there are no replays in {}", path.display());
+            }
+            replays.extend(
+                found
+                    .into_iter()
+                    .map(|(_, base)| path.join(format!("{base}.json.deflate"))),
+            );
+        } else if !path.exists() && is_glob(&path) {
+            let matched = glob(&path)?;
+            if matched.is_empty() {
+                anyhow::bail!("nothing matches {}", path.display());
+            }
+            replays.extend(matched);
+        } else {
+            replays.push(find_replay(Some(path), output_dir.clone())?);
+        }
+    }
+    Ok(replays)
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?'])
+}
+
+/// The files matching a pattern with `*` and `?` in its file name, sorted.
+fn glob(pattern: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = match pattern.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    if is_glob(dir) {
+        anyhow::bail!(
+            "only file names can have wildcards in them, not directories like {}",
+            dir.display()
+        );
+    }
+    let name = pattern.file_name().unwrap_or_default().to_string_lossy();
+
+    let mut matched = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("trying to read {dir:?}"))? {
+        let entry = entry.with_context(|| format!("trying to read an entry of {dir:?}"))?;
+        if wildcard_match(&name, &entry.file_name().to_string_lossy()) && entry.path().is_file() {
+            matched.push(entry.path());
+        }
+    }
+    matched.sort();
+    Ok(matched)
+}
+
+/// Whether `text` matches `pattern`, where `*` is any run of characters and `?` any one.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Where the last `*` was, and where in the text it's matched up to, to backtrack to
+    let (mut p, mut t, mut star) = (0, 0, None);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Reads the replays on as many threads as there are cores, since each takes a while to inflate
+/// and parse. The results are in the same order as the replays.
+fn parse_replays(replays: &[PathBuf]) -> Vec<anyhow::Result<BattleStats>> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(replays.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(replays.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(replay) = replays.get(i) else {
+                    break;
+                };
+                let stats = battle_stats(replay);
+                results.lock().unwrap().push((i, stats));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, stats)| stats).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{find_replays, replay_stats, stats_table, wildcard_match};
+
+    #[test]
+    fn replay_stats_sums_up_each_fleet() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replay = dir
+            .path()
+            .join("1718476403_glass_cannon_vs_iron_armada.json.deflate");
+        let json = r#"{
+            "version": 3,
+            "events": [
+                {"type": "MissileLaunched", "time": 1.5, "fleet": 0},
+                {"type": "missile_launched", "time": 2.0, "fleet": 0},
+                {"type": "weapon_fired", "time": 2.5, "fleet": 1, "weapon": "shell"},
+                {"type": "hit", "time": 3.0, "fleet": 0, "target_fleet": 1, "weapon": "missile", "damage": 40},
+                {"type": "ship-destroyed", "time": 3.0, "fleet": 1},
+                {"type": "ship_destroyed", "time": 9.25, "fleet": 1, "by": 0},
+                {"type": "radar_ping", "time": 4.0, "fleet": 1},
+                {"type": "radar_ping", "time": 5.0, "fleet": 0},
+                "garbage",
+                {"type": "battle_end", "winner": 0}
+            ]
+        }"#;
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(std::fs::File::create(&replay)?, Default::default());
+        encoder.write_all(json.as_bytes())?;
+        encoder.finish()?;
+
+        let stats = replay_stats(
+            &replay,
+            ["glass_cannon".to_owned(), "iron_armada".to_owned()],
+        )?;
+        let [a, b] = &stats.fleets;
+        assert_eq!((a.missiles_fired, a.missile_hits), (2, 1));
+        assert_eq!(a.missile_hit_rate(), Some(0.5));
+        assert_eq!(a.ships_destroyed, 2);
+        assert_eq!((a.first_kill, a.last_kill), (Some(3.0), Some(9.25)));
+        assert_eq!(
+            (b.shells_fired, b.ships_lost, b.damage_received),
+            (1, 2, 40.0)
+        );
+        assert_eq!(b.shell_hit_rate(), Some(0.0));
+        assert_eq!(stats.winner.as_deref(), Some("glass_cannon"));
+        assert_eq!(stats.events, 10);
+        assert_eq!(
+            stats.unrecognized.into_iter().collect::<Vec<_>>(),
+            [("(no type)".to_owned(), 1), ("radar_ping".to_owned(), 2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stats_table_has_a_column_per_fleet() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replay = dir.path().join("battle.json");
+        std::fs::write(
+            &replay,
+            r#"[{"type": "ShellFired", "fleet": 1}, {"type": "Hit", "fleet": 1, "weapon": "gun", "damage": 12.5}]"#,
+        )?;
+
+        let stats = replay_stats(&replay, ["a".to_owned(), "b".to_owned()])?;
+        let table = stats_table(&stats);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["a", "b"]);
+        let row = |label: &str| {
+            let line = lines.iter().find(|line| line.starts_with(label)).unwrap();
+            line[label.len()..].split_whitespace().collect::<Vec<_>>()
+        };
+        assert_eq!(row("Shell hit rate"), ["-", "100%"]);
+        assert_eq!(row("Damage received"), ["12.5", "0.0"]);
+        Ok(())
+    }
+
+    #[test]
+    fn wildcard_match_handles_stars_and_question_marks() {
+        assert!(wildcard_match(
+            "*.json.deflate",
+            "1718476403_a_vs_b.json.deflate"
+        ));
+        assert!(wildcard_match(
+            "*_a_vs_?.json*",
+            "1718476403_a_vs_b.json.deflate"
+        ));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match(
+            "*.json.deflate",
+            "1718476403_a_vs_b.sim.log"
+        ));
+        assert!(!wildcard_match("?", "ab"));
+    }
+
+    #[test]
+    fn find_replays_expands_directories_and_globs() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.path();
+        for file in [
+            "1718476403_a_vs_b.json.deflate",
+            "1718476500_a_vs_b.json.deflate",
+            "1718476500_a_vs_b.sim.log",
+            "notes.json.deflate",
+        ] {
+            std::fs::write(dir.join(file), "")?;
+        }
+
+        let mut from_dir = find_replays(vec![dir.to_owned()], None)?;
+        from_dir.sort();
+        assert_eq!(
+            from_dir,
+            [
+                dir.join("1718476403_a_vs_b.json.deflate"),
+                dir.join("1718476500_a_vs_b.json.deflate"),
+            ]
+        );
+        assert_eq!(
+            find_replays(vec![dir.join("*_a_vs_b.json.deflate")], None)?,
+            from_dir
+        );
+        assert!(find_replays(vec![dir.join("*.wasm")], None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! `stats` as CSV, for spreadsheets.
+//!
+//! The columns are part of the interface: spreadsheets refer to them by position, so they're only
+//! ever added to the end, never reordered, renamed or removed.
+
+use chrono::DateTime;
+
+use crate::aggregate::{Aggregate, Spread};
+use crate::stats::{BattleStats, FleetStats};
+
+/// What's counted for each fleet in a battle row, after `fleet_a_` or `fleet_b_`.
+const FLEET_FIELDS: [&str; 8] = [
+    "damage_dealt",
+    "damage_received",
+    "missiles_fired",
+    "missile_hits",
+    "shells_fired",
+    "shell_hits",
+    "ships_lost",
+    "ships_destroyed",
+];
+
+/// The columns of a row per battle, before each fleet's [`FLEET_FIELDS`]. `date` is UTC, in RFC
+/// 3339, and `duration` is when the replay's last event happened.
+const BATTLE_COLUMNS: [&str; 6] = ["replay", "date", "fleet_a", "fleet_b", "winner", "duration"];
+
+/// The columns of a row per fleet, from `--aggregate`.
+const FLEET_COLUMNS: [&str; 20] = [
+    "fleet",
+    "battles",
+    "wins",
+    "losses",
+    "undecided",
+    "ships_destroyed",
+    "ships_lost",
+    "missiles_fired",
+    "missile_hits",
+    "shells_fired",
+    "shell_hits",
+    "damage_dealt",
+    "damage_received",
+    "mean_damage_dealt",
+    "mean_damage_received",
+    "mean_battle_length",
+    "missile_hit_rate_mean",
+    "missile_hit_rate_variance",
+    "shell_hit_rate_mean",
+    "shell_hit_rate_variance",
+];
+
+/// A field quoted when it has to be: when it has a comma, quote or line break in it, or space at
+/// either end, which spreadsheets would otherwise drop.
+fn field(value: &str) -> String {
+    let quote = value.contains([',', '"', '\n', '\r']) || value.trim() != value;
+    if quote {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn row(fields: impl IntoIterator<Item = String>) -> String {
+    let fields: Vec<String> = fields.into_iter().map(|value| field(&value)).collect();
+    fields.join(",") + "\r\n"
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::new(), |value| value.to_string())
+}
+
+/// The header of the row per battle.
+fn battle_header() -> Vec<String> {
+    let mut header: Vec<String> = BATTLE_COLUMNS
+        .iter()
+        .map(|&column| column.to_owned())
+        .collect();
+    for fleet in ["fleet_a", "fleet_b"] {
+        header.extend(FLEET_FIELDS.iter().map(|field| format!("{fleet}_{field}")));
+    }
+    header
+}
+
+fn fleet_fields(fleet: &FleetStats) -> [String; 8] {
+    [
+        fleet.damage_dealt.to_string(),
+        fleet.damage_received.to_string(),
+        fleet.missiles_fired.to_string(),
+        fleet.missile_hits.to_string(),
+        fleet.shells_fired.to_string(),
+        fleet.shell_hits.to_string(),
+        fleet.ships_lost.to_string(),
+        fleet.ships_destroyed.to_string(),
+    ]
+}
+
+/// The battles as CSV, a row each with a header first. Lines end in CRLF, as RFC 4180 has them.
+pub fn battles_csv(battles: &[BattleStats]) -> String {
+    let mut csv = row(battle_header());
+    for battle in battles {
+        let date = battle
+            .timestamp
+            .and_then(|timestamp| i64::try_from(timestamp).ok())
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|date| date.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+        let [a, b] = &battle.fleets;
+        let mut fields = vec![
+            battle.replay.display().to_string(),
+            optional(date),
+            a.name.clone(),
+            b.name.clone(),
+            optional(battle.winner.as_ref()),
+            optional(battle.length),
+        ];
+        fields.extend(fleet_fields(a));
+        fields.extend(fleet_fields(b));
+        csv.push_str(&row(fields));
+    }
+    csv
+}
+
+/// The aggregate as CSV, a row per fleet with a header first.
+pub fn fleets_csv(aggregate: &Aggregate) -> String {
+    let mut csv = row(FLEET_COLUMNS.iter().map(|&column| column.to_owned()));
+    let mean = |spread: Option<Spread>| optional(spread.map(|spread| spread.mean));
+    let variance = |spread: Option<Spread>| optional(spread.map(|spread| spread.variance));
+    for fleet in &aggregate.fleets {
+        csv.push_str(&row([
+            fleet.name.clone(),
+            fleet.battles.to_string(),
+            fleet.wins.to_string(),
+            fleet.losses.to_string(),
+            fleet.undecided.to_string(),
+            fleet.ships_destroyed.to_string(),
+            fleet.ships_lost.to_string(),
+            fleet.missiles_fired.to_string(),
+            fleet.missile_hits.to_string(),
+            fleet.shells_fired.to_string(),
+            fleet.shell_hits.to_string(),
+            fleet.damage_dealt.to_string(),
+            fleet.damage_received.to_string(),
+            fleet.mean_damage_dealt.to_string(),
+            fleet.mean_damage_received.to_string(),
+            optional(fleet.mean_battle_length),
+            mean(fleet.missile_hit_rate),
+            variance(fleet.missile_hit_rate),
+            mean(fleet.shell_hit_rate),
+            variance(fleet.shell_hit_rate),
+        ]));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{battles_csv, field, fleets_csv};
+    use crate::aggregate::Aggregate;
+    use crate::stats::{BattleStats, FleetStats};
+
+    fn battle() -> BattleStats {
+        BattleStats {
+            replay: PathBuf::from("1718476403_a_vs_b.json.deflate"),
+            timestamp: Some(1718476403),
+            fleets: [
+                FleetStats {
+                    name: "Glass Cannon, mk2".to_owned(),
+                    damage_dealt: 40.5,
+                    missiles_fired: 4,
+                    missile_hits: 1,
+                    ..Default::default()
+                },
+                FleetStats {
+                    name: "iron_armada".to_owned(),
+                    damage_received: 40.5,
+                    ships_lost: 1,
+                    ..Default::default()
+                },
+            ],
+            winner: Some("Glass Cannon, mk2".to_owned()),
+            length: Some(120.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn field_quotes_only_when_it_has_to() {
+        assert_eq!(field("iron_armada"), "iron_armada");
+        assert_eq!(field("Glass Cannon, mk2"), "\"Glass Cannon, mk2\"");
+        assert_eq!(field("the \"best\" fleet"), "\"the \"\"best\"\" fleet\"");
+        assert_eq!(field(" padded"), "\" padded\"");
+    }
+
+    // The columns are relied on by position, so changing them has to be on purpose
+    #[test]
+    fn battles_csv_keeps_its_columns() {
+        let csv = battles_csv(&[battle()]);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(
+            lines[0],
+            "replay,date,fleet_a,fleet_b,winner,duration,\
+fleet_a_damage_dealt,fleet_a_damage_received,fleet_a_missiles_fired,fleet_a_missile_hits,\
+fleet_a_shells_fired,fleet_a_shell_hits,fleet_a_ships_lost,fleet_a_ships_destroyed,\
+fleet_b_damage_dealt,fleet_b_damage_received,fleet_b_missiles_fired,fleet_b_missile_hits,\
+fleet_b_shells_fired,fleet_b_shell_hits,fleet_b_ships_lost,fleet_b_ships_destroyed"
+        );
+        assert_eq!(
+            lines[1],
+            "1718476403_a_vs_b.json.deflate,2024-06-15T18:33:23Z,\"Glass Cannon, mk2\",iron_armada,\"Glass Cannon, mk2\",120,\
+40.5,0,4,1,0,0,0,0,0,40.5,0,0,0,0,1,0"
+        );
+        assert_eq!(lines[2], "");
+    }
+
+    #[test]
+    fn fleets_csv_keeps_its_columns() {
+        let csv = fleets_csv(&Aggregate::new(&[battle()], Vec::new()));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "fleet,battles,wins,losses,undecided,ships_destroyed,ships_lost,missiles_fired,\
+missile_hits,shells_fired,shell_hits,damage_dealt,damage_received,mean_damage_dealt,\
+mean_damage_received,mean_battle_length,missile_hit_rate_mean,missile_hit_rate_variance,\
+shell_hit_rate_mean,shell_hit_rate_variance"
+        );
+        assert_eq!(
+            lines[1],
+            "\"Glass Cannon, mk2\",1,1,0,0,0,0,4,1,0,0,40.5,0,40.5,0,120,0.25,0,,"
+        );
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,389 @@
+//! Bundling a fleet up for a tournament: its wasm, what it is and who made it, in one zip.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Serialize;
+
+use crate::git::workspace_git_state;
+use crate::hash::file_sha256;
+use crate::manifest::artifact_file_name;
+use crate::output::{dry_run, say};
+use crate::settings::settings;
+use crate::validate::check_wasm;
+use crate::{MetadataPackage, ParsedMetadata};
+
+/// The metadata file in a submission bundle.
+pub const METADATA_FILE: &str = "fleet.toml";
+
+/// Arguments for `cargo protologic package`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PackageArgs {
+    /// The workspace package to submit, by its package or fleet name.
+    fleet: String,
+    /// Who wrote the fleet, if not the `author` in its `[package.metadata.protologic]`, the
+    /// `package.author` setting, or the first of its package's `authors`.
+    #[arg(long)]
+    author: Option<String>,
+    /// The team the fleet is entered for, if not the `team` in its `[package.metadata.protologic]`
+    /// or the `package.team` setting.
+    #[arg(long)]
+    team: Option<String>,
+    /// A README to put in the bundle, if not the package's own `README.md`.
+    #[arg(long, value_name = "PATH")]
+    readme: Option<PathBuf>,
+    /// The biggest the fleet can be, like `2MiB`, if not the `package.max-size` setting.
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<ByteSize>,
+    /// Where to write the bundle, if not the current directory.
+    #[arg(short, long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+}
+
+/// What `fleet.toml` says about the fleet.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct FleetMetadata {
+    pub name: String,
+    package: String,
+    author: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    team: Option<String>,
+    version: String,
+    sdk_version: String,
+    /// The commit the fleet was built from.
+    commit: String,
+    /// Whether the fleet's sources had changes that aren't in the commit.
+    dirty: bool,
+    /// The fleet's file in the bundle.
+    wasm: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    wasm_sha256: String,
+}
+
+impl FleetMetadata {
+    /// The metadata as text fields named as in `fleet.toml`, for a submission form.
+    pub fn fields(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let toml::Value::Table(table) = toml::Value::try_from(self)? else {
+            anyhow::bail!("the fleet metadata isn't a table");
+        };
+        Ok(table
+            .into_iter()
+            .map(|(key, value)| match value {
+                toml::Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect())
+    }
+}
+
+/// The first of something that isn't blank.
+fn first_given(values: impl IntoIterator<Item = Option<String>>) -> Option<String> {
+    values
+        .into_iter()
+        .flatten()
+        .map(|value| value.trim().to_owned())
+        .find(|value| !value.is_empty())
+}
+
+/// A cargo author without their email, like `Ada Lovelace` for `Ada Lovelace <ada@example.com>`.
+fn author_name(author: &str) -> String {
+    author
+        .split_once('<')
+        .map_or(author, |(name, _)| name)
+        .trim()
+        .to_owned()
+}
+
+/// The file name of the bundle, like `submission_glass_cannon_0.1.0.zip`.
+fn bundle_file_name(fleet: &str, version: &str) -> String {
+    format!("submission_{fleet}_{version}.zip")
+}
+
+/// Works out everything `fleet.toml` has to say but the fleet's hash, failing with what's missing
+/// rather than leaving it out.
+fn fleet_metadata(
+    metadata: &ParsedMetadata,
+    package: &MetadataPackage,
+    args: &PackageArgs,
+    wasm: &str,
+) -> anyhow::Result<FleetMetadata> {
+    let settings = settings()?;
+    let config = package
+        .metadata
+        .clone()
+        .and_then(|metadata| metadata.protologic)
+        .unwrap_or_default();
+
+    let author = first_given([
+        args.author.clone(),
+        config.author,
+        settings.text("package.author", None),
+        package.authors.first().map(|author| author_name(author)),
+    ])
+    .with_context(|| {
+        format!(
+            "'{}' has no author for the submission. Pass `--author`, set `author` in its `[package.metadata.protologic]`, or save yours with `cargo protologic config set package.author NAME`",
+            package.name
+        )
+    })?;
+    let team = first_given([
+        args.team.clone(),
+        config.team,
+        settings.text("package.team", None),
+    ]);
+    let sdk_version = crate::sdk_version(metadata, &package.id).with_context(|| {
+        format!(
+            "can't find which version of the Protologic SDK '{}' is built with, does it depend on it?",
+            package.name
+        )
+    })?;
+    let git = workspace_git_state().with_context(|| {
+        format!(
+            "can't find the commit '{}' is built from, submissions have to come from a git repository",
+            package.name
+        )
+    })?;
+    if git.dirty {
+        say!(
+            "Warning: '{}' has uncommitted changes, so the submission won't match commit {}",
+            package.name,
+            git.commit
+        );
+    }
+
+    Ok(FleetMetadata {
+        name: first_given([config.name]).unwrap_or_else(|| package.name.clone()),
+        package: package.name.clone(),
+        author,
+        team,
+        version: package.version.clone(),
+        sdk_version,
+        commit: git.commit,
+        dirty: git.dirty,
+        wasm: wasm.to_owned(),
+        // Filled in once it's built
+        wasm_sha256: String::new(),
+    })
+}
+
+/// Writes the bundle: the fleet, `fleet.toml` and the README if there is one.
+fn write_bundle(
+    path: &Path,
+    wasm: &Path,
+    metadata: &FleetMetadata,
+    readme: Option<&Path>,
+) -> anyhow::Result<()> {
+    // No timestamps, so bundles of the same fleet hash the same
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+    let file = std::fs::File::create(path).with_context(|| format!("trying to create {path:?}"))?;
+    let mut zip = zip::ZipWriter::new(file);
+
+    let mut add = |name: &str, contents: &[u8]| -> anyhow::Result<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+        Ok(())
+    };
+    add(
+        &metadata.wasm,
+        &std::fs::read(wasm).with_context(|| format!("trying to read fleet {wasm:?}"))?,
+    )?;
+    add(METADATA_FILE, toml::to_string(metadata)?.as_bytes())?;
+    if let Some(readme) = readme {
+        add(
+            "README.md",
+            &std::fs::read(readme).with_context(|| format!("trying to read {readme:?}"))?,
+        )?;
+    }
+    zip.finish()
+        .with_context(|| format!("trying to finish writing {path:?}"))?;
+    Ok(())
+}
+
+/// What goes in a fleet's bundle, worked out before building it.
+struct Plan {
+    package: String,
+    /// The built fleet.
+    wasm: PathBuf,
+    readme: Option<PathBuf>,
+    /// Where the bundle goes.
+    bundle: PathBuf,
+    fleet: FleetMetadata,
+}
+
+/// A bundle that's been written.
+pub struct Bundle {
+    pub path: PathBuf,
+    pub fleet: FleetMetadata,
+    pub sha256: String,
+}
+
+/// Works out what the bundle of `args.fleet` has in it and where it goes in `dir`, failing with
+/// what's missing from its metadata before anything's built.
+fn plan(args: &PackageArgs, dir: &Path) -> anyhow::Result<Plan> {
+    let metadata = crate::cargo_metadata()?;
+    let package = metadata.workspace_package(&args.fleet)?;
+    let wasm_name = artifact_file_name(&package.name);
+    let wasm = crate::fleet_output_path(false)?.join(&wasm_name);
+    let readme = match &args.readme {
+        Some(readme) => Some(readme.clone()),
+        None => package
+            .manifest_path
+            .parent()
+            .map(|dir| dir.join("README.md"))
+            .filter(|readme| readme.is_file()),
+    };
+    let bundle = dir.join(bundle_file_name(
+        wasm_name.trim_end_matches(".wasm"),
+        &package.version,
+    ));
+    let fleet = fleet_metadata(&metadata, package, args, &wasm_name)?;
+    Ok(Plan {
+        package: package.name.clone(),
+        wasm,
+        readme,
+        bundle,
+        fleet,
+    })
+}
+
+/// Builds the planned fleet, checks it's fit to submit, and writes its bundle.
+fn build_bundle(plan: Plan, max_size: Option<ByteSize>) -> anyhow::Result<Bundle> {
+    let Plan {
+        package,
+        wasm,
+        readme,
+        bundle,
+        mut fleet,
+    } = plan;
+    crate::build_fleets(std::slice::from_ref(&package), false)?;
+    let bytes = std::fs::read(&wasm).with_context(|| format!("trying to read fleet {wasm:?}"))?;
+    let max_size = settings()?.size("package.max-size", max_size);
+    let (_, problems) = check_wasm(&bytes, max_size.map(|size| size.as_u64()));
+    if !problems.is_empty() {
+        let problems: Vec<String> = problems
+            .iter()
+            .map(|problem| format!("{}: {}", problem.category, problem.message))
+            .collect();
+        anyhow::bail!(
+            "'{package}' isn't fit to submit:\n    {}",
+            problems.join("\n    ")
+        );
+    }
+
+    fleet.wasm_sha256 = file_sha256(&wasm)?;
+    write_bundle(&bundle, &wasm, &fleet, readme.as_deref())?;
+    say!(
+        "Packaged '{}' {} into {} ({})",
+        fleet.name,
+        fleet.version,
+        bundle.display(),
+        ByteSize::b(std::fs::metadata(&bundle)?.len())
+    );
+    Ok(Bundle {
+        sha256: file_sha256(&bundle)?,
+        path: bundle,
+        fleet,
+    })
+}
+
+/// Builds a fleet and bundles it up in `dir`, as `package` does, for `submit` to upload.
+pub fn bundle(args: &PackageArgs, dir: &Path) -> anyhow::Result<Bundle> {
+    build_bundle(plan(args, dir)?, args.max_size)
+}
+
+/// Builds the fleet, checks it's fit to submit, and bundles it up with its metadata into
+/// `submission_<name>_<version>.zip`, printing the bundle's SHA-256 for the submission form.
+pub fn package(args: PackageArgs) -> anyhow::Result<()> {
+    let dir = args.output_dir.clone().unwrap_or_default();
+    // Before building, so a missing author doesn't wait on a whole build to be found
+    let plan = plan(&args, &dir)?;
+    if dry_run() {
+        say!(
+            "Would build '{}', check it, and bundle it into {:?}{} with {METADATA_FILE}:\n{}",
+            plan.package,
+            plan.bundle,
+            if plan.readme.is_some() {
+                " along with its README"
+            } else {
+                ""
+            },
+            toml::to_string(&plan.fleet)?.trim_end()
+        );
+        return Ok(());
+    }
+
+    let bundle = build_bundle(plan, args.max_size)?;
+    println!("SHA-256: {}", bundle.sha256);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{author_name, bundle_file_name, first_given, write_bundle, FleetMetadata};
+    use crate::hash::file_sha256;
+
+    #[test]
+    fn metadata_fields_skip_blanks_and_emails() {
+        assert_eq!(
+            author_name("Ada Lovelace <ada@example.com>"),
+            "Ada Lovelace"
+        );
+        assert_eq!(author_name("Ada"), "Ada");
+        assert_eq!(
+            first_given([None, Some("  ".to_owned()), Some("Red".to_owned())]),
+            Some("Red".to_owned())
+        );
+        assert_eq!(first_given([Some(String::new())]), None);
+        assert_eq!(
+            bundle_file_name("glass_cannon", "0.1.0"),
+            "submission_glass_cannon_0.1.0.zip"
+        );
+    }
+
+    #[test]
+    fn bundles_have_the_fleet_and_its_metadata_and_hash_the_same() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let wasm = dir.path().join("glass_cannon.wasm");
+        std::fs::write(&wasm, b"\0asm\x01\0\0\0")?;
+        let metadata = FleetMetadata {
+            name: "Glass Cannon".to_owned(),
+            package: "glass-cannon".to_owned(),
+            author: "Ada".to_owned(),
+            team: None,
+            version: "0.1.0".to_owned(),
+            sdk_version: "0.3.0".to_owned(),
+            commit: "1a2b3c".to_owned(),
+            dirty: false,
+            wasm: "glass_cannon.wasm".to_owned(),
+            wasm_sha256: file_sha256(&wasm)?,
+        };
+
+        let bundle = dir.path().join("a.zip");
+        write_bundle(&bundle, &wasm, &metadata, None)?;
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&bundle)?)?;
+        assert_eq!(zip.len(), 2);
+        assert_eq!(zip.by_index(0)?.name()?, "glass_cannon.wasm");
+        let mut toml = String::new();
+        zip.by_name("fleet.toml")?.read_to_string(&mut toml)?;
+        assert!(toml.starts_with("name = \"Glass Cannon\"\n"), "{toml}");
+        assert!(toml.contains("sdk-version = \"0.3.0\"\n"), "{toml}");
+        assert!(!toml.contains("team"), "{toml}");
+
+        let fields = metadata.fields()?;
+        assert!(fields.contains(&("name".to_owned(), "Glass Cannon".to_owned())));
+        assert!(fields.contains(&("dirty".to_owned(), "false".to_owned())));
+        assert!(!fields.iter().any(|(key, _)| key == "team"));
+
+        let again = dir.path().join("b.zip");
+        write_bundle(&again, &wasm, &metadata, None)?;
+        assert_eq!(file_sha256(&bundle)?, file_sha256(&again)?);
+        Ok(())
+    }
+}
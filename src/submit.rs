@@ -0,0 +1,297 @@
+//! Submitting a fleet to a tournament server: its `package` bundle, uploaded with its metadata as
+//! a multipart form.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Deserialize;
+
+use crate::output::{dry_run, say};
+use crate::settings::settings;
+use crate::submission::{bundle, Bundle, PackageArgs};
+use crate::ExitError;
+
+/// The environment variable `--token` is read from when it isn't given.
+const TOKEN_ENV: &str = "PROTOLOGIC_SUBMIT_TOKEN";
+/// curl's exit codes for failures that might go away on their own: not resolving the host or the
+/// proxy, not connecting, timing out, and the connection going wrong partway.
+const RETRYABLE_CURL_EXITS: &[i32] = &[5, 6, 7, 28, 35, 52, 55, 56];
+
+/// Arguments for `cargo protologic submit`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SubmitArgs {
+    #[command(flatten)]
+    package: PackageArgs,
+    /// The tournament server's submission URL, if not the `submit.server` setting.
+    #[arg(long, value_name = "URL")]
+    server: Option<String>,
+    /// The token the server knows you by. Best left to the PROTOLOGIC_SUBMIT_TOKEN environment variable, to keep it out of your shell history.
+    #[arg(long, env = TOKEN_ENV, hide_env_values = true)]
+    token: Option<Token>,
+    /// How many times to try again when the server can't be reached or is having trouble.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    retries: u32,
+}
+
+/// A secret, kept out of what's printed.
+#[derive(Clone)]
+pub struct Token(String);
+
+impl From<String> for Token {
+    fn from(token: String) -> Self {
+        Self(token)
+    }
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Token(..)")
+    }
+}
+
+/// What a server says back, as far as it says it.
+#[derive(Deserialize, Debug, Default)]
+struct ServerResponse {
+    accepted: Option<bool>,
+    message: Option<String>,
+    #[serde(default)]
+    reasons: Vec<String>,
+}
+
+/// What came of an upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Accepted(String),
+    /// Turned down, for these reasons.
+    Rejected(Vec<String>),
+    /// The token was missing or wrong.
+    Unauthorized(String),
+    /// The bundle was bigger than the server takes.
+    TooLarge(String),
+    /// Something that might go away when tried again, like the server being down.
+    Retryable(String),
+}
+
+/// Makes sense of the server's reply: its HTTP status, and a JSON body with `accepted`, `message`
+/// and `reasons` when it has one, or otherwise the body as a message.
+pub fn classify(status: u16, body: &str) -> Reply {
+    let body = body.trim();
+    let response: ServerResponse = serde_json::from_str(body).unwrap_or_else(|_| ServerResponse {
+        message: (!body.is_empty()).then(|| body.to_owned()),
+        ..ServerResponse::default()
+    });
+    let message = response
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("HTTP {status}"));
+    let reasons = if response.reasons.is_empty() {
+        vec![message.clone()]
+    } else {
+        response.reasons.clone()
+    };
+
+    match status {
+        200..=299 if response.accepted == Some(false) => Reply::Rejected(reasons),
+        200..=299 => Reply::Accepted(message),
+        401 | 403 => Reply::Unauthorized(message),
+        413 => Reply::TooLarge(message),
+        408 | 425 | 429 | 500..=599 => Reply::Retryable(format!("HTTP {status}: {message}")),
+        _ => Reply::Rejected(reasons),
+    }
+}
+
+/// Uploads the bundle once with curl, with the token in a header that's passed through stdin so
+/// it stays out of the process list.
+fn upload(server: &str, token: Option<&Token>, bundle: &Bundle) -> anyhow::Result<Reply> {
+    let mut curl = Command::new("curl");
+    curl.args(["--silent", "--show-error", "--max-time", "600"])
+        .args(["--write-out", "\n%{http_code}"])
+        .arg("--form")
+        .arg(format!(
+            "bundle=@\"{}\";type=application/zip",
+            bundle.path.display()
+        ));
+    for (key, value) in bundle.fleet.fields()? {
+        curl.arg("--form-string").arg(format!("{key}={value}"));
+    }
+    curl.arg("--form-string")
+        .arg(format!("bundle-sha256={}", bundle.sha256));
+    if token.is_some() {
+        curl.args(["--header", "@-"]);
+    }
+    let mut child = curl
+        .arg(server)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("trying to run curl to upload the submission, is it installed?")?;
+    let mut stdin = child.stdin.take().context("curl's stdin wasn't piped")?;
+    if let Some(Token(token)) = token {
+        writeln!(stdin, "Authorization: Bearer {token}")
+            .context("trying to pass the token to curl")?;
+    }
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .context("trying to wait for curl to upload the submission")?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    match output.status.code() {
+        Some(0) => {}
+        Some(code) if RETRYABLE_CURL_EXITS.contains(&code) => {
+            return Ok(Reply::Retryable(format!(
+                "couldn't reach {server}: {stderr}"
+            )))
+        }
+        _ => anyhow::bail!("couldn't upload to {server}: {stderr}"),
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let status = status
+        .trim()
+        .parse()
+        .with_context(|| format!("curl didn't say what {server} replied with"))?;
+    Ok(classify(status, body))
+}
+
+/// Builds and bundles a fleet as `package` does, then uploads the bundle with its metadata to the
+/// tournament server, trying again when the network or the server lets it down. A dry run does
+/// all but the upload.
+pub fn submit(args: SubmitArgs) -> anyhow::Result<()> {
+    let server = settings()?.text("submit.server", args.server.clone()).context(
+        "there's no tournament server to submit to, pass `--server URL` or save it with `cargo protologic config set submit.server URL`",
+    )?;
+    if !server.starts_with("https://") && !server.starts_with("http://") {
+        anyhow::bail!("the server should be an http:// or https:// URL, not `{server}`");
+    }
+
+    if !dry_run() {
+        let dir = args.package.output_dir.clone().unwrap_or_default();
+        let bundle = bundle(&args.package, &dir)?;
+        return upload_with_retries(&server, &args, &bundle);
+    }
+
+    // A dry run still builds and checks the bundle, just somewhere it's cleaned up afterwards
+    let dir = std::env::temp_dir().join(format!("cargo-protologic-submit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).with_context(|| format!("trying to create {dir:?}"))?;
+    let bundle = bundle(&args.package, &dir);
+    let result = bundle.and_then(|bundle| {
+        let fields: Vec<String> = bundle
+            .fleet
+            .fields()?
+            .into_iter()
+            .map(|(key, value)| format!("  {key} = {value}"))
+            .collect();
+        say!(
+            "Would upload the bundle ({}, SHA-256 {}) to {server} {}, with:\n{}",
+            ByteSize::b(std::fs::metadata(&bundle.path)?.len()),
+            bundle.sha256,
+            if args.token.is_some() {
+                "with your token"
+            } else {
+                "without a token"
+            },
+            fields.join("\n")
+        );
+        Ok(())
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Uploads the bundle, trying again after a wait when the network or the server lets it down.
+fn upload_with_retries(server: &str, args: &SubmitArgs, bundle: &Bundle) -> anyhow::Result<()> {
+    let size = ByteSize::b(std::fs::metadata(&bundle.path)?.len());
+    say!("Submitting '{}' to {server}", bundle.fleet.name);
+    let mut attempt = 0;
+    let reply = loop {
+        match upload(server, args.token.as_ref(), bundle)? {
+            Reply::Retryable(problem) if attempt < args.retries => {
+                attempt += 1;
+                let wait = Duration::from_secs(1 << (attempt - 1).min(5));
+                say!(
+                    "Warning: {problem}. Trying again in {}s ({attempt} of {})",
+                    wait.as_secs(),
+                    args.retries
+                );
+                std::thread::sleep(wait);
+            }
+            reply => break reply,
+        }
+    };
+
+    match reply {
+        Reply::Accepted(message) => {
+            say!("The server accepted '{}': {message}", bundle.fleet.name);
+            Ok(())
+        }
+        Reply::Rejected(reasons) => Err(ExitError::new(
+            ExitError::SUBMISSION_REJECTED,
+            format!(
+                "the server rejected '{}':\n    {}",
+                bundle.fleet.name,
+                reasons.join("\n    ")
+            ),
+        )
+        .into()),
+        Reply::TooLarge(message) => Err(ExitError::new(
+            ExitError::SUBMISSION_REJECTED,
+            format!(
+                "the server says the bundle is too big at {size}: {message}. `--max-size` checks a fleet against the tournament's cap before submitting"
+            ),
+        )
+        .into()),
+        Reply::Unauthorized(message) => Err(ExitError::new(
+            ExitError::UNAUTHORIZED,
+            format!(
+                "the server didn't take your token: {message}. Pass it with `--token`, or the {TOKEN_ENV} environment variable"
+            ),
+        )
+        .into()),
+        Reply::Retryable(problem) => anyhow::bail!(
+            "couldn't submit after {} tries, the last time: {problem}",
+            args.retries + 1
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, Reply};
+
+    #[test]
+    fn replies_are_told_apart() {
+        assert_eq!(
+            classify(
+                201,
+                r#"{"accepted": true, "message": "entered for round 3"}"#
+            ),
+            Reply::Accepted("entered for round 3".to_owned())
+        );
+        assert_eq!(classify(200, ""), Reply::Accepted("HTTP 200".to_owned()));
+        assert_eq!(
+            classify(
+                200,
+                r#"{"accepted": false, "reasons": ["late", "no author"]}"#
+            ),
+            Reply::Rejected(vec!["late".to_owned(), "no author".to_owned()])
+        );
+        assert_eq!(
+            classify(422, "fleet name taken\n"),
+            Reply::Rejected(vec!["fleet name taken".to_owned()])
+        );
+        assert_eq!(
+            classify(401, r#"{"message": "bad token"}"#),
+            Reply::Unauthorized("bad token".to_owned())
+        );
+        assert_eq!(classify(413, ""), Reply::TooLarge("HTTP 413".to_owned()));
+        assert_eq!(
+            classify(503, "down for maintenance"),
+            Reply::Retryable("HTTP 503: down for maintenance".to_owned())
+        );
+    }
+}
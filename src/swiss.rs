@@ -0,0 +1,186 @@
+//! Swiss-system pairing: each round, every fleet plays one on about the same score that it hasn't
+//! met yet.
+//!
+//! Fleets are known here by their index in the tournament, which breaks ties in the rankings.
+
+/// How a fleet has done in the rounds so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    pub points: u32,
+    /// The fleets it's played, once for each round.
+    pub opponents: Vec<usize>,
+    pub had_bye: bool,
+    /// The games it's played as fleet A, to even the sides out.
+    pub fleet_a_games: u32,
+}
+
+/// Who plays whom in a round, with the fleet that's fleet A in the first game first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Round {
+    pub pairings: Vec<(usize, usize)>,
+    /// The fleet left over when there's an odd number of them, which sits the round out.
+    pub bye: Option<usize>,
+}
+
+/// The sum of the points of everyone the fleet has played, which ranks fleets on the same points
+/// by how tough their opponents were.
+pub fn buchholz(records: &[Record], fleet: usize) -> u32 {
+    records[fleet]
+        .opponents
+        .iter()
+        .map(|&opponent| records[opponent].points)
+        .sum()
+}
+
+/// The fleets best first, by points, then [`buchholz`], then index.
+pub fn rank(records: &[Record]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order
+        .sort_by_key(|&fleet| std::cmp::Reverse((records[fleet].points, buchholz(records, fleet))));
+    order
+}
+
+/// Which of a pair is fleet A in their first game: the one that's played fewer games as fleet A,
+/// or `better` when they're even.
+fn first_side(records: &[Record], better: usize, worse: usize) -> (usize, usize) {
+    if records[worse].fleet_a_games < records[better].fleet_a_games {
+        (worse, better)
+    } else {
+        (better, worse)
+    }
+}
+
+/// Pairs off `order`, a ranking, each fleet with the best ranked fleet after it that it hasn't
+/// played, going back on earlier pairings when that leaves someone with no one new to play.
+fn pair_off(records: &[Record], order: &[usize], pairings: &mut Vec<(usize, usize)>) -> bool {
+    let Some((&first, rest)) = order.split_first() else {
+        return true;
+    };
+    for (i, &other) in rest.iter().enumerate() {
+        if records[first].opponents.contains(&other) {
+            continue;
+        }
+        let left: Vec<usize> = rest
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, &fleet)| fleet)
+            .collect();
+        pairings.push(first_side(records, first, other));
+        if pair_off(records, &left, pairings) {
+            return true;
+        }
+        pairings.pop();
+    }
+    false
+}
+
+/// Pairs the fleets for the next round.
+///
+/// With an odd number of fleets, the lowest ranked fleet that hasn't had a bye gets one. Fleets are
+/// paired with the closest ranked fleet they haven't met, and only when there's no way round it
+/// (with more rounds than fleets) do fleets meet again, paired down the rankings. Each pairing's
+/// first fleet is the one that's played fewer games as fleet A, or the better ranked of the two.
+pub fn pair_round(records: &[Record]) -> Round {
+    let order = rank(records);
+    let bye_candidates: Vec<Option<usize>> = if order.len().is_multiple_of(2) {
+        vec![None]
+    } else {
+        let mut candidates: Vec<usize> = order.iter().rev().copied().collect();
+        // Everyone's had one, so it starts over from the bottom
+        if candidates.iter().all(|&fleet| records[fleet].had_bye) {
+            candidates.sort_by_key(|&fleet| records[fleet].points);
+        } else {
+            candidates.retain(|&fleet| !records[fleet].had_bye);
+        }
+        candidates.into_iter().map(Some).collect()
+    };
+
+    for &bye in &bye_candidates {
+        let order: Vec<usize> = order.iter().copied().filter(|&f| Some(f) != bye).collect();
+        let mut pairings = Vec::new();
+        if pair_off(records, &order, &mut pairings) {
+            return Round { pairings, bye };
+        }
+    }
+
+    let bye = bye_candidates[0];
+    let order: Vec<usize> = order.into_iter().filter(|&f| Some(f) != bye).collect();
+    Round {
+        pairings: order
+            .chunks(2)
+            .map(|pair| first_side(records, pair[0], pair[1]))
+            .collect(),
+        bye,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{buchholz, pair_round, rank, Record};
+
+    fn records(points: &[u32]) -> Vec<Record> {
+        points
+            .iter()
+            .map(|&points| Record {
+                points,
+                ..Record::default()
+            })
+            .collect()
+    }
+
+    fn played(records: &mut [Record], pairings: &[(usize, usize)]) {
+        for &(a, b) in pairings {
+            records[a].opponents.push(b);
+            records[b].opponents.push(a);
+            records[a].fleet_a_games += 1;
+        }
+    }
+
+    #[test]
+    fn fleets_meet_others_on_the_same_score() {
+        let round = pair_round(&records(&[0, 6, 3, 6, 3, 0]));
+        assert_eq!(round.pairings, [(1, 3), (2, 4), (0, 5)]);
+        assert_eq!(round.bye, None);
+    }
+
+    #[test]
+    fn rematches_are_avoided_by_pairing_further_down() {
+        let mut table = records(&[6, 6, 3, 3]);
+        played(&mut table, &[(0, 1), (2, 3)]);
+        let round = pair_round(&table);
+        assert_eq!(round.pairings, [(0, 2), (1, 3)]);
+
+        // 0 has played 1, 2 and 3, so it drops down to 4
+        let mut table = records(&[9, 6, 6, 3, 0, 0]);
+        played(&mut table, &[(1, 0), (2, 0), (3, 0)]);
+        let round = pair_round(&table);
+        assert!(round.pairings.contains(&(0, 4)), "{round:?}");
+        for (a, b) in &round.pairings {
+            assert!(!table[*a].opponents.contains(b), "{round:?}");
+        }
+    }
+
+    #[test]
+    fn the_lowest_fleet_without_a_bye_sits_out() {
+        let mut table = records(&[3, 3, 0]);
+        assert_eq!(pair_round(&table).bye, Some(2));
+        table[2].had_bye = true;
+        let round = pair_round(&table);
+        assert_eq!(round.bye, Some(1));
+        assert_eq!(round.pairings, [(0, 2)]);
+    }
+
+    #[test]
+    fn sides_even_out_and_ties_break_on_opponents_points() {
+        let mut table = records(&[3, 3, 0, 0]);
+        table[0].fleet_a_games = 1;
+        assert_eq!(pair_round(&table).pairings[0], (1, 0));
+
+        // Same points, but 1 played a fleet that's done better
+        let mut table = records(&[3, 3, 6, 0]);
+        played(&mut table, &[(0, 3), (1, 2)]);
+        assert_eq!(buchholz(&table, 1), 6);
+        assert_eq!(rank(&table), [2, 1, 0, 3]);
+    }
+}
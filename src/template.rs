@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+use crate::output::{dry_run, say};
+
+/// A fleet template built into the binary.
+pub struct BuiltinTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Each file's path in the fleet's directory, and its contents.
+    files: &'static [(&'static str, &'static str)],
+}
+
+/// The template `new` starts from when it isn't given one.
+pub const DEFAULT_TEMPLATE: &str = "minimal";
+
+pub const BUILTIN_TEMPLATES: &[BuiltinTemplate] = &[
+    BuiltinTemplate {
+        name: "minimal",
+        description: "The smallest fleet that does something: full speed ahead",
+        files: &[
+            (
+                "Cargo.toml",
+                include_str!("../templates/minimal/Cargo.toml.in"),
+            ),
+            (
+                "src/lib.rs",
+                include_str!("../templates/minimal/src/lib.rs.in"),
+            ),
+        ],
+    },
+    BuiltinTemplate {
+        name: "state-machine",
+        description: "A fleet that runs a state machine every tick, to grow behaviours in",
+        files: &[
+            (
+                "Cargo.toml",
+                include_str!("../templates/state-machine/Cargo.toml.in"),
+            ),
+            (
+                "src/lib.rs",
+                include_str!("../templates/state-machine/src/lib.rs.in"),
+            ),
+        ],
+    },
+    BuiltinTemplate {
+        name: "missile-boat",
+        description: "Closes in, fires once a missile can hit, and backs off when too close. Has targeting helpers, with tests",
+        files: &[
+            (
+                "Cargo.toml",
+                include_str!("../templates/missile-boat/Cargo.toml.in"),
+            ),
+            (
+                "src/lib.rs",
+                include_str!("../templates/missile-boat/src/lib.rs.in"),
+            ),
+            (
+                "src/targeting.rs",
+                include_str!("../templates/missile-boat/src/targeting.rs.in"),
+            ),
+        ],
+    },
+];
+
+/// Where a template comes from.
+pub enum TemplateSource {
+    Builtin(&'static BuiltinTemplate),
+    /// A git repository, fetched into the template cache.
+    Git {
+        url: String,
+        /// Where it's cached, relative to the template cache.
+        cache_key: PathBuf,
+    },
+}
+
+impl TemplateSource {
+    /// Reads `--template`: a built-in template's name, `gh:user/repo` for a GitHub repository,
+    /// or the URL of any git repository.
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        if let Some(repo) = template.strip_prefix("gh:") {
+            let valid = repo.split('/').count() == 2
+                && repo.split('/').all(|part| {
+                    !part.is_empty()
+                        && part != ".."
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                });
+            if !valid {
+                anyhow::bail!("`{template}` isn't a GitHub repository, they look like `gh:someuser/fleet-template`");
+            }
+            return Ok(Self::Git {
+                url: format!("https://github.com/{repo}.git"),
+                cache_key: Path::new("gh").join(repo),
+            });
+        }
+
+        if template.contains("://") || template.starts_with("git@") {
+            let key: String = template
+                .trim_end_matches(".git")
+                .split_once("://")
+                .map_or(template, |(_, rest)| rest)
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            return Ok(Self::Git {
+                url: template.to_owned(),
+                cache_key: Path::new("git").join(key),
+            });
+        }
+
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|builtin| builtin.name == template)
+            .map(Self::Builtin)
+            .with_context(|| {
+                format!(
+                    "no template called '{template}', the built-in ones are: {}. Remote templates look like `gh:someuser/fleet-template`",
+                    BUILTIN_TEMPLATES
+                        .iter()
+                        .map(|builtin| builtin.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    /// The template's files, by their path in the fleet's directory. Remote templates are fetched
+    /// first, or updated if they're already cached.
+    pub fn files(&self) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+        let (url, cache_key) = match self {
+            Self::Builtin(builtin) => {
+                return Ok(builtin
+                    .files
+                    .iter()
+                    .map(|(path, contents)| (PathBuf::from(path), contents.as_bytes().to_vec()))
+                    .collect())
+            }
+            Self::Git { url, cache_key } => (url, cache_key),
+        };
+
+        let dir = template_cache_dir()?.join(cache_key);
+        let cached = dir.join(".git").is_dir();
+        if dry_run() {
+            if !cached {
+                say!("Would fetch the template from {url} into {}", dir.display());
+                return Ok(Vec::new());
+            }
+        } else {
+            fetch(url, &dir, cached)?;
+        }
+
+        let files = read_tree(&dir, &dir)?;
+        if !files
+            .iter()
+            .any(|(path, _)| path == Path::new("Cargo.toml"))
+        {
+            anyhow::bail!(
+                "{url} doesn't look like a fleet template, it has no Cargo.toml at the top"
+            );
+        }
+        Ok(files)
+    }
+}
+
+/// Where fetched templates are kept, so they only need fetching again to update them.
+fn template_cache_dir() -> anyhow::Result<PathBuf> {
+    let dir = dirs::cache_dir().context("can't find your cache directory")?;
+    Ok(dir.join("cargo-protologic").join("templates"))
+}
+
+/// Clones the template, or pulls in changes when it's cached. Being offline is no reason to stop
+/// using a cached template.
+fn fetch(url: &str, dir: &Path, cached: bool) -> anyhow::Result<()> {
+    let mut git = Command::new("git");
+    if cached {
+        say!("Updating the template from {url}");
+        git.arg("-C")
+            .arg(dir)
+            .args(["pull", "--ff-only", "--quiet"]);
+    } else {
+        say!("Fetching the template from {url}");
+        if let Some(parent) = dir.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("trying to create template cache {parent:?}"))?;
+        }
+        git.args(["clone", "--depth", "1", "--quiet", url]).arg(dir);
+    }
+
+    let status = git
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .context("trying to run git to fetch the template, is it installed?")?;
+    match (status.success(), cached) {
+        (true, _) => Ok(()),
+        (false, true) => {
+            say!("Warning: couldn't update the template, using the cached copy");
+            Ok(())
+        }
+        (false, false) => {
+            anyhow::bail!("git couldn't fetch the template from {url}, it exited with {status}")
+        }
+    }
+}
+
+/// Every file under `dir`, by its path relative to `root`, leaving out git's own files and build
+/// output.
+fn read_tree(root: &Path, dir: &Path) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("trying to read template {dir:?}"))?
+    {
+        let path = entry.context("trying to read the template's files")?.path();
+        if path.is_dir() {
+            let name = path.file_name().unwrap_or_default();
+            if name != ".git" && name != "target" {
+                files.extend(read_tree(root, &path)?);
+            }
+        } else {
+            let contents =
+                std::fs::read(&path).with_context(|| format!("trying to read {path:?}"))?;
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_owned();
+            files.push((relative, contents));
+        }
+    }
+
+    // `read_dir` order is platform dependent, keep what's printed about the files stable
+    files.sort();
+    Ok(files)
+}
+
+/// Prints the built-in templates, and the remote ones that have been fetched before.
+pub fn list_templates() -> anyhow::Result<()> {
+    say!("Built-in templates:");
+    let width = BUILTIN_TEMPLATES
+        .iter()
+        .map(|builtin| builtin.name.len())
+        .max()
+        .unwrap_or_default();
+    for builtin in BUILTIN_TEMPLATES {
+        let default = if builtin.name == DEFAULT_TEMPLATE {
+            " (the default)"
+        } else {
+            ""
+        };
+        say!(
+            "  {:<width$}  {}{default}",
+            builtin.name,
+            builtin.description
+        );
+    }
+
+    let cached = cached_templates(&template_cache_dir()?);
+    if cached.is_empty() {
+        say!("Remote templates can be used with `--template gh:someuser/fleet-template`, or a git URL");
+    } else {
+        say!("Fetched templates:");
+        for template in cached {
+            say!("  {template}");
+        }
+    }
+    Ok(())
+}
+
+/// The `--template` to use each cached template again, like `gh:someuser/fleet-template`.
+fn cached_templates(cache: &Path) -> Vec<String> {
+    let repos = |dir: &Path| -> Vec<PathBuf> {
+        let mut repos: Vec<_> = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        repos.sort();
+        repos
+    };
+
+    let mut templates = Vec::new();
+    for user in repos(&cache.join("gh")) {
+        for repo in repos(&user) {
+            if let (Some(user), Some(repo)) = (user.file_name(), repo.file_name()) {
+                templates.push(format!(
+                    "gh:{}/{}",
+                    user.to_string_lossy(),
+                    repo.to_string_lossy()
+                ));
+            }
+        }
+    }
+    for repo in repos(&cache.join("git")) {
+        let url = Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["remote", "get-url", "origin"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned());
+        templates.extend(url);
+    }
+    templates
+}
+
+/// Fills in a template file's `{{name}}` style placeholders. Files that aren't text are left
+/// alone.
+pub fn render(contents: Vec<u8>, vars: &[(&str, &str)]) -> Vec<u8> {
+    let text = match String::from_utf8(contents) {
+        Ok(text) => text,
+        Err(err) => return err.into_bytes(),
+    };
+    vars.iter()
+        .fold(text, |text, (key, value)| {
+            text.replace(&format!("{{{{{key}}}}}"), value)
+        })
+        .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{render, TemplateSource, BUILTIN_TEMPLATES};
+
+    #[test]
+    fn builtin_templates_render_completely() -> anyhow::Result<()> {
+        let vars = [
+            ("name", "glass-cannon"),
+            ("crate_name", "glass_cannon"),
+            ("sdk_version", "0.7"),
+        ];
+        for builtin in BUILTIN_TEMPLATES {
+            let files = TemplateSource::parse(builtin.name)?.files()?;
+            assert!(files
+                .iter()
+                .any(|(path, _)| path == Path::new("Cargo.toml")));
+            for (path, contents) in files {
+                let rendered = String::from_utf8(render(contents, &vars))?;
+                assert!(
+                    !rendered.contains("{{"),
+                    "{}'s {path:?} has placeholders left",
+                    builtin.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_understands_remote_templates() -> anyhow::Result<()> {
+        let TemplateSource::Git { url, cache_key } =
+            TemplateSource::parse("gh:someuser/fleet-template")?
+        else {
+            panic!("expected a git template");
+        };
+        assert_eq!(url, "https://github.com/someuser/fleet-template.git");
+        assert_eq!(cache_key, Path::new("gh/someuser/fleet-template"));
+
+        let TemplateSource::Git { cache_key, .. } =
+            TemplateSource::parse("https://git.example.com/fleets/brawler.git")?
+        else {
+            panic!("expected a git template");
+        };
+        assert_eq!(cache_key, Path::new("git/git_example_com_fleets_brawler"));
+
+        assert!(TemplateSource::parse("gh:../escape").is_err());
+        assert!(TemplateSource::parse("missle-boat").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,2249 @@
+//! Tournaments between built fleets: round-robins, where every fleet plays every other from both
+//! sides, single-elimination brackets, and Swiss tournaments.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+
+use crate::bracket::{
+    bracket_size, champion, describe_bracket, placements, resolve, round_name, sides, went_on,
+    Series, SeriesScore,
+};
+use crate::events::{emit, Event, Phase};
+use crate::fleet::{find_fleet, find_fleets_for_run, Fleet};
+use crate::hash::{file_sha256, short_hash};
+use crate::interrupt::{install_handler, interrupted};
+use crate::naming::add_extension;
+use crate::outcome::Outcome;
+use crate::output::{dry_run, say};
+use crate::ratings::{default_ratings_path, read_ratings};
+use crate::results::BattleRecord;
+use crate::run::{
+    apply_unpruned_battle_settings, battle_options, check_before_battle, play_battle,
+    print_dry_run, rating_options, unix_now, Battle, BattleArgs, BattleOptions,
+};
+use crate::series::parse_best_of;
+use crate::settings::{settings, Settings};
+use crate::sim::{
+    find_protologic_path, format_duration, random_seed, resolve_scenario, SimOutputSink,
+};
+use crate::standings::ranking;
+use crate::swiss;
+use crate::tournament_config::TournamentConfig;
+use crate::tournament_report::{write_report, TournamentReportFormat};
+
+/// Where each tournament's results are kept, in the battle output directory.
+pub const TOURNAMENTS_DIR: &str = "tournaments";
+
+/// The extension of a tournament's state file, after its base path.
+const STATE_EXTENSION: &str = "state.jsonl";
+
+/// Points for a win. A draw gets [`DRAW_POINTS`], and a loss nothing.
+const WIN_POINTS: u32 = 3;
+const DRAW_POINTS: u32 = 1;
+
+/// How the fleets of a tournament meet.
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    /// Every fleet plays every other from both sides, and the most points wins.
+    #[default]
+    RoundRobin,
+    /// A bracket of best-of-`--best-of` series, where losing a series knocks a fleet out.
+    SingleElim,
+    /// `--rounds` rounds, each pairing fleets on about the same points that haven't met, which
+    /// ranks a lot of fleets in far fewer games than a round-robin.
+    Swiss,
+}
+
+/// How the fleets of a bracket are seeded, best first. The best seeds get any byes, and can only
+/// meet each other late on.
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Seeding {
+    /// In the order the fleets are given, or the order they're found in when none are.
+    #[default]
+    Given,
+    /// In alphabetical order of name.
+    Name,
+    /// In a random order, from `--seeding-seed` or a seed that's recorded with the tournament.
+    Random,
+    /// By their standings across the tournaments already played, like `cargo protologic standings`
+    /// with `--merge-versions`. Fleets that haven't played go last.
+    Standings,
+    /// By the Elo rating of each fleet's build, highest first, like `cargo protologic standings
+    /// --elo`. Builds that haven't been rated go last.
+    Elo,
+}
+
+/// Arguments for `cargo protologic tournament`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct TournamentArgs {
+    /// The built fleets to enter, by name. Defaults to the `--config` file's fleets, or all of them.
+    #[arg(value_name = "FLEET")]
+    entrants: Vec<String>,
+    /// A tournament definition, like `tournament.toml`, with the fleets to enter (workspace fleets or wasm files from elsewhere, with their names and authors) and the tournament's settings, which are named like its flags. Flags win over the file.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Whether every fleet plays every other, they're knocked out of a bracket, or they play rounds of Swiss pairings. Defaults to `round-robin`.
+    #[arg(long, value_enum)]
+    format: Option<TournamentFormat>,
+    /// How many games each pair of fleets plays from each side in a round-robin, or each pairing of a `swiss` round plays with the sides swapping every game, with a fresh seed every game. Defaults to 1.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    games: Option<u32>,
+    /// How many games each series of a `single-elim` bracket is the best of, with the sides swapping every game. Defaults to 1.
+    #[arg(long, value_name = "N", value_parser = parse_best_of)]
+    best_of: Option<u32>,
+    /// How the fleets of a `single-elim` bracket are seeded. Defaults to `given`.
+    #[arg(long, value_enum)]
+    seeding: Option<Seeding>,
+    /// The seed to shuffle the fleets with for `--seeding random`, to seed a bracket the same way again.
+    #[arg(long, value_name = "SEED")]
+    seeding_seed: Option<u64>,
+    /// How many rounds a `swiss` tournament has. Defaults to enough to pick out a winner, the base-2 logarithm of the number of fleets rounded up.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    rounds: Option<u32>,
+    /// The seeds every matchup (or series, or Swiss pairing) plays its games on, one game each, instead of a fresh random seed for every game. Sets how many games there are when `--games` or `--best-of` isn't given.
+    #[arg(long, value_name = "SEEDS", value_delimiter = ',')]
+    seeds: Vec<u64>,
+    /// The biggest each fleet's wasm can be, like `2MiB`, as an event caps them. Checked before any battles.
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<ByteSize>,
+    /// Carry on with a tournament that was stopped, from the `.state.jsonl` it left in `tournaments/`, skipping the games it already played. It keeps the fleets, seeds, scenarios and bracket it started with.
+    #[arg(long, value_name = "STATE_FILE", conflicts_with_all = ["entrants", "config", "format", "games", "best_of", "seeding", "seeding_seed", "rounds", "seeds", "scenarios", "label"])]
+    resume: Option<PathBuf>,
+    /// How many battles to run at once. Each sim's output goes to a `.sim.log` next to its replay when this is more than 1, unless it's thrown away with `--discard-sim-output`.
+    #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: u32,
+    /// Let `--resume` carry on when fleets have been rebuilt since the tournament started, mixing results from both builds.
+    #[arg(long, default_value = "false", requires = "resume")]
+    allow_changed: bool,
+    /// Leave the fleets' Elo ratings alone. Tournament games are rated by default, like `run --rated`.
+    #[arg(long, default_value = "false")]
+    unrated: bool,
+    /// Print the standings, the bracket or rounds, and every matchup's games as JSON on stdout, for scripts.
+    #[arg(long, default_value = "false")]
+    pub json: bool,
+    /// Write a report of the tournament to post, with the standings, who beat whom, the longest and shortest battles, and links to every replay. It's HTML or markdown by the file's extension, `.html` or `.md`.
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+    /// How the battles are run and recorded.
+    #[command(flatten)]
+    battle: BattleArgs,
+}
+
+/// One game of a matchup, which either got a result or failed to run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TournamentGame {
+    /// Which game of the matchup it was, from 1.
+    pub game: u32,
+    pub result: Option<BattleRecord>,
+    /// Why the battle couldn't be played.
+    pub error: Option<String>,
+}
+
+/// The games between two fleets: the games one played as fleet A against the other in a
+/// round-robin, or a series of a bracket, where the sides swap every game and each result says
+/// which was which.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Matchup {
+    pub fleet_a: String,
+    pub fleet_b: String,
+    pub games: Vec<TournamentGame>,
+}
+
+/// A fleet entered in a tournament, as it was built when the tournament started.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entrant {
+    pub name: String,
+    /// SHA-256 of the fleet's wasm, to tell builds of the same fleet apart.
+    pub sha256: String,
+    /// Who entered it, from the tournament definition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// The built fleet it is, when it's entered under another name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built: Option<String>,
+    /// Its wasm file, when it's from outside the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm: Option<PathBuf>,
+}
+
+/// How a fleet is doing in the tournament.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Standing {
+    pub fleet: String,
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Games that failed to run, or whose outcome couldn't be told.
+    pub errors: u32,
+    pub points: u32,
+    /// The points of everyone the fleet played, which breaks ties in a Swiss tournament.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buchholz: Option<u32>,
+}
+
+impl Standing {
+    /// The fraction of decided games won, with draws as half a win, or `None` before any were.
+    pub fn win_rate(&self) -> Option<f64> {
+        let games = self.wins + self.losses + self.draws;
+        (games > 0).then(|| (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(games))
+    }
+}
+
+/// How a bracket was seeded and how it went, with fleets as their seed: their index in
+/// [`TournamentRecord::fleets`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BracketRecord {
+    pub seeding: Seeding,
+    /// The seed the fleets were shuffled with, for `--seeding random`.
+    pub seeding_seed: Option<u64>,
+    /// Every series of every round, from the first.
+    pub rounds: Vec<Vec<Series>>,
+    pub placements: Vec<Placement>,
+}
+
+/// Where a fleet finished in a bracket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub place: u32,
+    pub fleet: String,
+}
+
+/// Who played whom in a round of a Swiss tournament, with fleet A of each pairing's first game
+/// first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SwissRound {
+    pub pairings: Vec<[String; 2]>,
+    /// The fleet that sat the round out, scoring as if it had won every game.
+    pub bye: Option<String>,
+}
+
+/// The results of a tournament, kept in [`TOURNAMENTS_DIR`] and printed by `--json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TournamentRecord {
+    /// Unix timestamp (in seconds) of when the tournament started.
+    pub timestamp: u64,
+    #[serde(default)]
+    pub format: TournamentFormat,
+    /// The fleets entered, in seed order for a bracket.
+    pub fleets: Vec<Entrant>,
+    /// Games per matchup, or the most a series of a bracket is the best of.
+    pub games: u32,
+    /// The `--label` the tournament was run with.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Whether the tournament was carried on with `--resume` after being stopped.
+    #[serde(default)]
+    pub resumed: bool,
+    pub standings: Vec<Standing>,
+    pub matchups: Vec<Matchup>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bracket: Option<BracketRecord>,
+    /// The rounds of a Swiss tournament, as far as it got.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rounds: Vec<SwissRound>,
+}
+
+impl TournamentRecord {
+    pub fn read_json(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("trying to read tournament results {path:?}"))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("trying to parse tournament results {path:?}"))
+    }
+
+    pub fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).context("trying to serialize tournament results")?;
+        std::fs::write(path, json)
+            .with_context(|| format!("trying to write tournament results to {path:?}"))
+    }
+
+    /// Every game played, as the fleets on each side and the outcome, or `None` when it failed.
+    pub fn games(&self) -> impl Iterator<Item = (&str, &str, Option<Outcome>)> {
+        self.matchups.iter().flat_map(|matchup| {
+            matchup.games.iter().map(|game| match &game.result {
+                Some(record) => (
+                    record.fleet_a.as_str(),
+                    record.fleet_b.as_str(),
+                    Some(record.outcome),
+                ),
+                None => (matchup.fleet_a.as_str(), matchup.fleet_b.as_str(), None),
+            })
+        })
+    }
+}
+
+/// Where the tournaments are kept for the battle output directory `output_dir`.
+pub fn tournaments_dir(output_dir: &Path) -> PathBuf {
+    output_dir.join(TOURNAMENTS_DIR)
+}
+
+/// Picks an unused base path for a new tournament's files, named by when it started. Its progress
+/// goes in the [`state_path`] as it's played, and its results in the [`record_path`] at the end.
+fn tournament_base_path(options: &BattleOptions, timestamp: u64) -> anyhow::Result<PathBuf> {
+    let dir = tournaments_dir(&options.output_dir);
+    if !dry_run() {
+        std::fs::create_dir_all(&dir).with_context(|| format!("trying to create {dir:?}"))?;
+    }
+    let stem = format!("{}_tournament", options.timestamp_format.format(timestamp));
+    let taken = |base: &Path| record_path(base).exists() || state_path(base).exists();
+
+    let mut base = dir.join(&stem);
+    let mut n = 2;
+    while taken(&base) {
+        base = dir.join(format!("{stem}_{n}"));
+        n += 1;
+    }
+    Ok(base)
+}
+
+/// Where a tournament's results go.
+fn record_path(base: &Path) -> PathBuf {
+    add_extension(base, "json")
+}
+
+/// Where a tournament's progress goes, to `--resume` it from.
+fn state_path(base: &Path) -> PathBuf {
+    add_extension(base, STATE_EXTENSION)
+}
+
+/// The base path of the tournament a state file is for.
+fn base_from_state(state: &Path) -> anyhow::Result<PathBuf> {
+    state
+        .to_str()
+        .and_then(|path| path.strip_suffix(&format!(".{STATE_EXTENSION}")))
+        .map(PathBuf::from)
+        .with_context(|| {
+            format!("{state:?} isn't a tournament state file, they're named like `<time>_tournament.{STATE_EXTENSION}`")
+        })
+}
+
+/// What a tournament is going to play, the first line of its state file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TournamentPlan {
+    /// Unix timestamp (in seconds) of when the tournament started.
+    timestamp: u64,
+    #[serde(default)]
+    format: TournamentFormat,
+    /// The fleets, in seed order for a bracket.
+    fleets: Vec<Entrant>,
+    games: u32,
+    label: Option<String>,
+    /// The scenarios every seed is battled on, empty for the sim's default. A bracket's series go
+    /// through them a game at a time instead.
+    scenarios: Vec<String>,
+    /// The seed of each game of each matchup, in the order of [`pairings`], of each series of a
+    /// bracket, by its id, or of each pairing of each Swiss round, in turn.
+    seeds: Vec<Vec<u64>>,
+    #[serde(default)]
+    seeding: Seeding,
+    #[serde(default)]
+    seeding_seed: Option<u64>,
+    /// How many rounds a Swiss tournament has.
+    #[serde(default)]
+    rounds: u32,
+}
+
+/// A game as it's appended to the state file, once played.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PlayedGame {
+    /// Which matchup it was, in the order of [`pairings`], which series of a bracket, or which
+    /// pairing of a Swiss tournament, counting through each round's in turn.
+    matchup: usize,
+    #[serde(flatten)]
+    game: TournamentGame,
+}
+
+/// Appends a line to a tournament's state file. Each goes out in a single write, so a run that's
+/// killed can only cut off the line it was writing.
+fn append_state(path: &Path, line: &impl Serialize) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let mut line = serde_json::to_string(line).context("trying to serialize tournament state")?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("trying to open tournament state {path:?}"))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("trying to append to tournament state {path:?}"))
+}
+
+/// Reads what a tournament was going to play and the games it got through. A last line that
+/// doesn't parse, half written when the run was killed, is skipped with a warning.
+fn read_state(path: &Path) -> anyhow::Result<(TournamentPlan, Vec<PlayedGame>)> {
+    let state = std::fs::read_to_string(path)
+        .with_context(|| format!("trying to read tournament state {path:?}"))?;
+    let mut lines = state.lines().filter(|line| !line.trim().is_empty());
+    let plan = lines
+        .next()
+        .with_context(|| format!("tournament state {path:?} is empty"))?;
+    let plan: TournamentPlan = serde_json::from_str(plan)
+        .with_context(|| format!("trying to parse the start of tournament state {path:?}"))?;
+
+    let lines: Vec<&str> = lines.collect();
+    let mut played = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(game) => played.push(game),
+            Err(_) if i + 1 == lines.len() => say!(
+                "Warning: skipping the last line of {}, which was cut off",
+                path.display()
+            ),
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "trying to parse line {} of tournament state {path:?}",
+                        i + 2
+                    )
+                })
+            }
+        }
+    }
+    Ok((plan, played))
+}
+
+/// Every ordered pair of fleets, so each pair meets from both sides.
+fn pairings(count: usize) -> Vec<(usize, usize)> {
+    (0..count)
+        .flat_map(|a| (0..count).map(move |b| (a, b)))
+        .filter(|(a, b)| a != b)
+        .collect()
+}
+
+/// Tallies up games, given as the fleets on each side and the outcome, into standings, best first:
+/// by points, then wins, with ties keeping the order of `fleets`.
+pub fn standings<'a>(
+    fleets: &[String],
+    games: impl IntoIterator<Item = (&'a str, &'a str, Option<Outcome>)>,
+) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = fleets
+        .iter()
+        .map(|fleet| Standing {
+            fleet: fleet.clone(),
+            ..Standing::default()
+        })
+        .collect();
+    let mut update = |fleet: &str, change: &dyn Fn(&mut Standing)| {
+        if let Some(standing) = standings.iter_mut().find(|s| s.fleet == fleet) {
+            standing.played += 1;
+            change(standing);
+        }
+    };
+
+    for (a, b, outcome) in games {
+        match outcome {
+            Some(Outcome::FleetAWon) => {
+                update(a, &|s| s.wins += 1);
+                update(b, &|s| s.losses += 1);
+            }
+            Some(Outcome::FleetBWon) => {
+                update(a, &|s| s.losses += 1);
+                update(b, &|s| s.wins += 1);
+            }
+            Some(Outcome::Draw) => {
+                update(a, &|s| s.draws += 1);
+                update(b, &|s| s.draws += 1);
+            }
+            Some(Outcome::SimCrashed | Outcome::Unknown) | None => {
+                update(a, &|s| s.errors += 1);
+                update(b, &|s| s.errors += 1);
+            }
+        }
+    }
+
+    for standing in &mut standings {
+        standing.points = standing.wins * WIN_POINTS + standing.draws * DRAW_POINTS;
+    }
+    standings.sort_by_key(|s| std::cmp::Reverse((s.points, s.wins)));
+    standings
+}
+
+/// The standings as a table to print, under `title`.
+pub fn standings_table(title: &str, standings: &[Standing]) -> String {
+    let width = standings
+        .iter()
+        .map(|standing| standing.fleet.len())
+        .chain(["Fleet".len()])
+        .max()
+        .unwrap_or_default();
+
+    let buchholz = standings.iter().any(|standing| standing.buchholz.is_some());
+
+    let mut table = format!("{title}:\n");
+    write!(
+        table,
+        "  Rank  {:<width$}  Played  Won  Lost  Drawn  Errors  Points  ",
+        "Fleet"
+    )
+    .unwrap();
+    if buchholz {
+        table.push_str("Buchholz  ");
+    }
+    table.push_str("Win-rate\n");
+    for (i, standing) in standings.iter().enumerate() {
+        let win_rate = standing
+            .win_rate()
+            .map_or("unknown".to_owned(), |rate| format!("{:.1}%", rate * 100.0));
+        write!(
+            table,
+            "  {:<4}  {:<width$}  {:<6}  {:<3}  {:<4}  {:<5}  {:<6}  {:<6}  ",
+            i + 1,
+            standing.fleet,
+            standing.played,
+            standing.wins,
+            standing.losses,
+            standing.draws,
+            standing.errors,
+            standing.points
+        )
+        .unwrap();
+        if buchholz {
+            write!(table, "{:<8}  ", standing.buchholz.unwrap_or_default()).unwrap();
+        }
+        writeln!(table, "{win_rate}").unwrap();
+    }
+    table
+}
+
+/// A line per game of the matchup, like `game 1: 'a' won`.
+fn describe_matchup(matchup: &Matchup) -> String {
+    let mut description = format!(
+        "'{}' (fleet A) vs '{}' (fleet B):",
+        matchup.fleet_a, matchup.fleet_b
+    );
+    for game in &matchup.games {
+        let result = match (&game.result, &game.error) {
+            (Some(record), _) => record.describe_outcome(),
+            (None, Some(error)) => format!("failed: {error}"),
+            (None, None) => "not played".to_owned(),
+        };
+        write!(description, "\n  game {}: {result}", game.game).unwrap();
+    }
+    description
+}
+
+/// The fleets entered, in the order given, or the definition file's when it has the say, or every
+/// built fleet.
+fn entrants(
+    args: &TournamentArgs,
+    config: Option<&TournamentConfig>,
+) -> anyhow::Result<Vec<Fleet>> {
+    let mut fleets: Vec<Fleet> = if let Some(config) = config {
+        config.entrant_fleets(args.battle.debug)?
+    } else if args.entrants.is_empty() {
+        find_fleets_for_run(args.battle.debug)?
+    } else {
+        let built = find_fleets_for_run(args.battle.debug)?;
+        args.entrants
+            .iter()
+            .map(|name| find_fleet(&built, name))
+            .collect::<anyhow::Result<_>>()?
+    };
+    let mut seen = std::collections::HashSet::new();
+    fleets.retain(|fleet| seen.insert(fleet.name.clone()));
+
+    if fleets.len() < 2 {
+        anyhow::bail!(
+            "a tournament needs at least two fleets, but there's only {}. Build more with `cargo protologic build`",
+            fleets.len()
+        );
+    }
+    Ok(fleets)
+}
+
+/// Finds the fleets of a tournament being resumed, checking they're the builds it started with.
+fn resume_fleets(
+    plan: &TournamentPlan,
+    debug: bool,
+    allow_changed: bool,
+) -> anyhow::Result<Vec<Fleet>> {
+    let built = find_fleets_for_run(debug)?;
+    let mut fleets = Vec::new();
+    let mut changed = Vec::new();
+    for entrant in &plan.fleets {
+        let fleet = match &entrant.wasm {
+            Some(wasm) => Fleet {
+                name: entrant.name.clone(),
+                path: wasm.clone(),
+            },
+            None => Fleet {
+                name: entrant.name.clone(),
+                ..find_fleet(&built, entrant.built.as_ref().unwrap_or(&entrant.name))?
+            },
+        };
+        let hash = file_sha256(&fleet.path)?;
+        if hash != entrant.sha256 {
+            changed.push(format!(
+                "'{}' was {} when the tournament started, but is {} now",
+                entrant.name,
+                short_hash(&entrant.sha256),
+                short_hash(&hash)
+            ));
+        }
+        fleets.push(fleet);
+    }
+
+    if !changed.is_empty() {
+        if !allow_changed {
+            anyhow::bail!(
+                "fleets have been rebuilt since the tournament started, so its results wouldn't be from the same fleets:\n    {}\nRebuild them as they were, or pass `--allow-changed` to carry on with the new builds anyway",
+                changed.join("\n    ")
+            );
+        }
+        for change in changed {
+            say!("Warning: {change}, carrying on anyway because of `--allow-changed`");
+        }
+    }
+    Ok(fleets)
+}
+
+/// Every matchup's battles, in the order of [`pairings`]. Each seed is battled on every scenario
+/// in turn, like a series, and the replays are named by matchup and game.
+fn schedule<'a>(
+    plan: &'a TournamentPlan,
+    fleets: &'a [Fleet],
+) -> Vec<((usize, usize), Vec<Battle<'a>>)> {
+    let scenarios: Vec<Option<&str>> = if plan.scenarios.is_empty() {
+        vec![None]
+    } else {
+        plan.scenarios.iter().map(|s| Some(s.as_str())).collect()
+    };
+
+    pairings(fleets.len())
+        .into_iter()
+        .zip(&plan.seeds)
+        .map(|((a, b), seeds)| {
+            let battles = scenarios
+                .iter()
+                .flat_map(|&scenario| seeds.iter().map(move |&seed| (seed, scenario)))
+                .enumerate()
+                .map(|(i, (seed, scenario))| Battle {
+                    fleet_a: &fleets[a],
+                    fleet_b: &fleets[b],
+                    seed: Some(seed),
+                    scenario,
+                    suffix: Some(format!("tournament_game{}", i + 1)),
+                })
+                .collect();
+            ((a, b), battles)
+        })
+        .collect()
+}
+
+/// The games left to play in a tournament, and what's needed to play and save them.
+struct Pool<'a> {
+    options: &'a BattleOptions,
+    /// The games still to play, as their matchup, game number and battle.
+    remaining: &'a [(usize, u32, &'a Battle<'a>)],
+    state: &'a Path,
+    /// How many games there are in all, when that's known before they're played.
+    total: Option<usize>,
+    already_played: usize,
+    /// What to say once a game of a matchup has been saved, given every game played so far.
+    after_game: &'a (dyn Fn(&[PlayedGame], usize) -> Option<String> + Sync),
+}
+
+/// What the workers of a [`Pool`] share, behind a lock.
+struct Progress {
+    played: Vec<PlayedGame>,
+    /// The games being played right now, by their index in [`Pool::remaining`].
+    in_flight: Vec<(usize, String)>,
+    finished: usize,
+}
+
+impl Pool<'_> {
+    /// Plays the remaining games on `jobs` threads, each running one sim at a time, and returns
+    /// every game played, including `played` from before.
+    ///
+    /// Each game is saved to the state file as it finishes, one at a time, so the state file stays
+    /// good to resume from whatever order the games finish in. On Ctrl-C, no more games start and it
+    /// returns once the sims in flight have been stopped.
+    fn play(&self, jobs: usize, played: Vec<PlayedGame>) -> anyhow::Result<Vec<PlayedGame>> {
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let progress = Mutex::new(Progress {
+            played,
+            in_flight: Vec::new(),
+            finished: 0,
+        });
+        let started = Instant::now();
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs.min(self.remaining.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let result = self.work(&next, &failed, &progress, started);
+                        if result.is_err() {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        result
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("tournament worker panicked"))
+                .collect::<anyhow::Result<Vec<()>>>()
+        })?;
+
+        Ok(progress.into_inner().unwrap().played)
+    }
+
+    /// Takes games off the queue and plays them until there are none left, Ctrl-C is pressed, or
+    /// a worker has `failed` to save a game.
+    fn work(
+        &self,
+        next: &AtomicUsize,
+        failed: &AtomicBool,
+        progress: &Mutex<Progress>,
+        started: Instant,
+    ) -> anyhow::Result<()> {
+        while !interrupted() && !failed.load(Ordering::SeqCst) {
+            let i = next.fetch_add(1, Ordering::SeqCst);
+            let Some(&(matchup, game, battle)) = self.remaining.get(i) else {
+                break;
+            };
+            let name = format!(
+                "'{}' vs '{}' game {game}",
+                battle.fleet_a.name, battle.fleet_b.name
+            );
+            {
+                let mut progress = progress.lock().unwrap();
+                progress.in_flight.push((i, name.clone()));
+                say!("Starting {name}, {} played", self.count(progress.finished));
+            }
+
+            let result = play_battle(self.options, battle);
+            let mut progress = progress.lock().unwrap();
+            progress.in_flight.retain(|&(flight, _)| flight != i);
+            // A battle cut short by Ctrl-C didn't fail, it just didn't finish
+            if interrupted() {
+                break;
+            }
+            let (result, error) = match result {
+                Ok(record) => (Some(record), None),
+                Err(err) => {
+                    say!("Warning: {name} failed, recording it as an error: {err:#}");
+                    (None, Some(format!("{err:#}")))
+                }
+            };
+            let played = PlayedGame {
+                matchup,
+                game: TournamentGame {
+                    game,
+                    result,
+                    error,
+                },
+            };
+            append_state(self.state, &played)?;
+            progress.played.push(played);
+            progress.finished += 1;
+            self.report(&progress, started, matchup);
+        }
+        Ok(())
+    }
+
+    /// How many games have been played, like `3 of 12 games`.
+    fn count(&self, finished: usize) -> String {
+        let played = self.already_played + finished;
+        match self.total {
+            Some(total) => format!("{played} of {total} games"),
+            None if played == 1 => "1 game".to_owned(),
+            None => format!("{played} games"),
+        }
+    }
+
+    /// Prints how far along the tournament is after a game of `matchup`.
+    fn report(&self, progress: &Progress, started: Instant, matchup: usize) {
+        if let Some(line) = (self.after_game)(&progress.played, matchup) {
+            say!("{line}");
+        }
+
+        let left = self.remaining.len() - progress.finished;
+        let mut line = format!("Played {}", self.count(progress.finished));
+        if !progress.in_flight.is_empty() {
+            let in_flight: Vec<&str> = progress
+                .in_flight
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            write!(line, ", playing {}", in_flight.join(", ")).unwrap();
+        }
+        if left > 0 {
+            // Wall-clock time per game finished, so it takes however many run at once into account
+            let average = started.elapsed().as_secs_f64() / progress.finished as f64;
+            write!(
+                line,
+                ", about {} to go",
+                format_duration(Duration::from_secs_f64(average * left as f64))
+            )
+            .unwrap();
+            // A bracket only knows who plays next once these games are done
+            if self.total.is_none() {
+                line.push_str(" for the games that can be played now");
+            }
+        }
+        say!("{line}");
+    }
+}
+
+/// Checks the flags for a new tournament suit its `--format`.
+fn check_format_flags(args: &TournamentArgs) -> anyhow::Result<()> {
+    use TournamentFormat::{RoundRobin, SingleElim, Swiss};
+
+    let flags: [(&str, bool, &[TournamentFormat]); 5] = [
+        ("--games", args.games.is_some(), &[RoundRobin, Swiss]),
+        ("--best-of", args.best_of.is_some(), &[SingleElim]),
+        ("--seeding", args.seeding.is_some(), &[SingleElim]),
+        ("--seeding-seed", args.seeding_seed.is_some(), &[SingleElim]),
+        ("--rounds", args.rounds.is_some(), &[Swiss]),
+    ];
+    for (flag, given, formats) in flags {
+        if given && !formats.contains(&args.format.unwrap_or_default()) {
+            let formats: Vec<String> = formats
+                .iter()
+                .map(|format| format!("`--format {}`", format_name(*format)))
+                .collect();
+            anyhow::bail!("`{flag}` is only for {}", formats.join(" and "));
+        }
+    }
+    if args.seeding_seed.is_some() && args.seeding != Some(Seeding::Random) {
+        anyhow::bail!("`--seeding-seed` is for `--seeding random`");
+    }
+    Ok(())
+}
+
+/// Fills in the flags that weren't given from a tournament definition.
+fn apply_config(args: &mut TournamentArgs, config: &TournamentConfig) -> anyhow::Result<()> {
+    args.format = args.format.or(config.format);
+    args.games = args.games.or(config.games);
+    args.best_of = args.best_of.or(config.best_of);
+    args.seeding = args.seeding.or(config.seeding);
+    args.seeding_seed = args.seeding_seed.or(config.seeding_seed);
+    args.rounds = args.rounds.or(config.rounds);
+    if args.seeds.is_empty() {
+        args.seeds.clone_from(&config.seeds);
+    }
+    if args.battle.scenarios.is_empty() {
+        args.battle.scenarios.clone_from(&config.scenarios);
+    }
+    if args.max_size.is_none() {
+        args.max_size = config.max_size()?;
+    }
+    args.battle.max_ticks = args.battle.max_ticks.or(config.max_ticks);
+    args.battle.fuel_limit = args.battle.fuel_limit.or(config.fuel_limit);
+    if args.battle.label.is_none() {
+        args.battle.label.clone_from(&config.name);
+    }
+    Ok(())
+}
+
+/// Fills in what neither the flags nor the `--config` file gave from the `[tournament]` settings.
+/// Games and seeding are only filled in for the formats that take them, so a workspace's defaults
+/// don't get in the way of picking another format.
+fn apply_tournament_settings(args: &mut TournamentArgs, settings: &Settings) {
+    use TournamentFormat::{RoundRobin, SingleElim, Swiss};
+
+    if args.resume.is_none() {
+        args.format = settings.choice("tournament.format", args.format);
+        let format = args.format.unwrap_or_default();
+        if matches!(format, RoundRobin | Swiss) && args.seeds.is_empty() {
+            args.games = settings.number("tournament.games", args.games);
+        }
+        if format == SingleElim && args.seeding_seed.is_none() {
+            args.seeding = settings.choice("tournament.seeding", args.seeding);
+        }
+    }
+    // It has a default, so the flag can only be told apart from it by being different
+    let jobs = (args.jobs != 1).then_some(args.jobs);
+    args.jobs = settings.number("tournament.jobs", jobs).unwrap_or(1);
+    args.max_size = settings.size("tournament.max-size", args.max_size);
+    args.unrated = settings.flag("tournament.unrated", args.unrated);
+}
+
+/// How many games each matchup, series or pairing plays: as many as asked for, or one for each of
+/// `--seeds`, which have to agree when both are given.
+fn game_count(format: TournamentFormat, games: Option<u32>, seeds: &[u64]) -> anyhow::Result<u32> {
+    let flag = match format {
+        TournamentFormat::SingleElim => "--best-of",
+        TournamentFormat::RoundRobin | TournamentFormat::Swiss => "--games",
+    };
+    match games {
+        Some(games) if !seeds.is_empty() && seeds.len() != games as usize => anyhow::bail!(
+            "`{flag} {games}` needs as many `--seeds`, one for each game, but there are {}",
+            seeds.len()
+        ),
+        Some(games) => Ok(games),
+        None if format == TournamentFormat::SingleElim
+            && !seeds.is_empty()
+            && seeds.len().is_multiple_of(2) =>
+        {
+            anyhow::bail!(
+                "a series needs an odd number of games to have a winner, but there are {} `--seeds`",
+                seeds.len()
+            )
+        }
+        None => Ok((seeds.len() as u32).max(1)),
+    }
+}
+
+/// Checks no fleet is bigger than `--max-size`, listing every one that is.
+fn check_sizes(fleets: &[Fleet], max_size: Option<ByteSize>) -> anyhow::Result<()> {
+    let Some(max_size) = max_size else {
+        return Ok(());
+    };
+    let mut too_big = Vec::new();
+    for fleet in fleets {
+        let size = std::fs::metadata(&fleet.path)
+            .with_context(|| format!("trying to read {:?}", fleet.path))?
+            .len();
+        if size > max_size.as_u64() {
+            too_big.push(format!("'{}' is {}", fleet.name, ByteSize::b(size)));
+        }
+    }
+    if !too_big.is_empty() {
+        anyhow::bail!(
+            "fleets can be at most {max_size} in this tournament, but:\n    {}",
+            too_big.join("\n    ")
+        );
+    }
+    Ok(())
+}
+
+/// A fleet as it's entered, with what the tournament definition says about it.
+fn entrant(fleet: &Fleet, config: Option<&TournamentConfig>) -> anyhow::Result<Entrant> {
+    let entry = config.and_then(|config| config.entry(&fleet.name));
+    Ok(Entrant {
+        name: fleet.name.clone(),
+        sha256: file_sha256(&fleet.path)?,
+        author: entry.and_then(|entry| entry.author.clone()),
+        built: entry
+            .and_then(|entry| entry.package.clone())
+            .filter(|package| *package != fleet.name),
+        wasm: entry
+            .and_then(|entry| entry.wasm.as_deref())
+            .map(std::path::absolute)
+            .transpose()
+            .context("trying to find the fleet's wasm")?,
+    })
+}
+
+/// What `--format` calls a format, like `single-elim`.
+fn format_name(format: TournamentFormat) -> String {
+    use clap::ValueEnum;
+
+    format
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+/// How many rounds a new Swiss tournament between `count` fleets has, or 0 for the other formats.
+fn swiss_round_count(args: &TournamentArgs, count: usize) -> anyhow::Result<u32> {
+    if args.format != Some(TournamentFormat::Swiss) {
+        return Ok(0);
+    }
+    // With an odd number, each fleet can sit a round out on top of playing every other once
+    let most = if count.is_multiple_of(2) {
+        count - 1
+    } else {
+        count
+    } as u32;
+    let rounds = args
+        .rounds
+        .unwrap_or_else(|| count.next_power_of_two().trailing_zeros().clamp(1, most));
+    if rounds > most {
+        anyhow::bail!(
+            "{count} fleets can only play {most} Swiss rounds without meeting twice, try fewer `--rounds` or a round-robin"
+        );
+    }
+    Ok(rounds)
+}
+
+/// Puts the fleets of a bracket in seed order, best first, shuffling them with `seed` for
+/// [`Seeding::Random`].
+fn seed_fleets(
+    fleets: &mut [Fleet],
+    seeding: Seeding,
+    seed: Option<u64>,
+    output_dir: &Path,
+) -> anyhow::Result<()> {
+    match seeding {
+        Seeding::Given => {}
+        Seeding::Name => fleets.sort_by(|a, b| a.name.cmp(&b.name)),
+        Seeding::Random => fastrand::Rng::with_seed(seed.unwrap_or_default()).shuffle(fleets),
+        Seeding::Standings => {
+            let ranking = ranking(output_dir)?;
+            if ranking.is_empty() {
+                say!("Warning: no tournaments have been recorded to seed by, so the fleets are seeded in the order given");
+            }
+            // A stable sort, so fleets that haven't played stay in the order given
+            fleets.sort_by_key(|fleet| {
+                ranking
+                    .iter()
+                    .position(|name| *name == fleet.name)
+                    .unwrap_or(usize::MAX)
+            });
+        }
+        Seeding::Elo => {
+            let ratings = read_ratings(&default_ratings_path()?)?;
+            let mut rated = Vec::new();
+            for fleet in fleets.iter() {
+                let rating = ratings.get(&fleet.name, &file_sha256(&fleet.path)?);
+                rated.push((rating.map(|rating| rating.rating), fleet.clone()));
+            }
+            if rated.iter().all(|(rating, _)| rating.is_none()) {
+                say!("Warning: none of the fleets have been rated to seed by, so the fleets are seeded in the order given");
+            }
+            // A stable sort, so fleets that haven't been rated stay in the order given
+            rated.sort_by(|(a, _), (b, _)| match (a, b) {
+                (Some(a), Some(b)) => b.total_cmp(a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+            for (slot, (_, fleet)) in fleets.iter_mut().zip(rated) {
+                *slot = fleet;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How a bracket was seeded, like `randomly, with seed 42`.
+fn describe_seeding(plan: &TournamentPlan) -> String {
+    match plan.seeding {
+        Seeding::Given => "in the order given".to_owned(),
+        Seeding::Name => "by name".to_owned(),
+        Seeding::Random => format!(
+            "randomly, with seed {} (pass `--seeding-seed` to seed it the same way again)",
+            plan.seeding_seed.unwrap_or_default()
+        ),
+        Seeding::Standings => "by the standings of the tournaments played before".to_owned(),
+        Seeding::Elo => "by Elo rating".to_owned(),
+    }
+}
+
+/// The bracket as far as the games played so far have decided it.
+fn resolve_bracket(plan: &TournamentPlan, played: &[PlayedGame]) -> Vec<Vec<Series>> {
+    resolve(plan.fleets.len(), plan.games, |id, seeds| {
+        let mut score = SeriesScore::default();
+        for played in played.iter().filter(|played| played.matchup == id) {
+            score.played += 1;
+            let [a, b] = sides(seeds, played.game.game);
+            let winner = match played.game.result.as_ref().map(|record| record.outcome) {
+                Some(Outcome::FleetAWon) => a,
+                Some(Outcome::FleetBWon) => b,
+                _ => continue,
+            };
+            score.wins[if winner == seeds[0] { 0 } else { 1 }] += 1;
+        }
+        score
+    })
+}
+
+/// The next game of every series of the bracket that's ready to play and hasn't been decided, as
+/// the series, the game number and its battle. Its games go through the scenarios in turn.
+fn next_games<'a>(
+    plan: &'a TournamentPlan,
+    fleets: &'a [Fleet],
+    rounds: &[Vec<Series>],
+) -> Vec<(usize, u32, Battle<'a>)> {
+    rounds
+        .iter()
+        .flatten()
+        .filter(|series| series.winner.is_none())
+        .filter_map(|series| {
+            let [Some(a), Some(b)] = series.seeds else {
+                return None;
+            };
+            let game = series.played + 1;
+            let [fleet_a, fleet_b] = sides([a, b], game);
+            let i = (game - 1) as usize;
+            let seeds = &plan.seeds[series.id];
+            let battle = Battle {
+                fleet_a: &fleets[fleet_a],
+                fleet_b: &fleets[fleet_b],
+                seed: Some(seeds[i % seeds.len()]),
+                scenario: (!plan.scenarios.is_empty())
+                    .then(|| plan.scenarios[i % plan.scenarios.len()].as_str()),
+                suffix: Some(format!("tournament_series{}_game{game}", series.id + 1)),
+            };
+            Some((series.id, game, battle))
+        })
+        .collect()
+}
+
+/// The places in a bracket as a list to print, like `  1st  'a'`.
+fn describe_placements(title: &str, placements: &[Placement]) -> String {
+    let mut description = format!("{title}:");
+    for placement in placements {
+        let suffix = match (placement.place % 100, placement.place % 10) {
+            (11..=13, _) => "th",
+            (_, 1) => "st",
+            (_, 2) => "nd",
+            (_, 3) => "rd",
+            _ => "th",
+        };
+        write!(
+            description,
+            "\n  {:<5} '{}'",
+            format!("{}{suffix}", placement.place),
+            placement.fleet
+        )
+        .unwrap();
+    }
+    description
+}
+
+/// How many games a pairing of a Swiss round plays: `--games` on every scenario.
+fn games_per_pairing(plan: &TournamentPlan) -> u32 {
+    plan.games * plan.scenarios.len().max(1) as u32
+}
+
+/// The id of the `pairing`th pairing of a Swiss round, as its games are saved.
+fn pairing_id(plan: &TournamentPlan, round: usize, pairing: usize) -> usize {
+    round * (plan.fleets.len() / 2) + pairing
+}
+
+/// How every fleet of a Swiss tournament has done in `rounds`, as far as they've been played. A
+/// bye scores as a win of every game.
+fn swiss_records(
+    plan: &TournamentPlan,
+    rounds: &[swiss::Round],
+    played: &[PlayedGame],
+) -> Vec<swiss::Record> {
+    let mut records = vec![swiss::Record::default(); plan.fleets.len()];
+    for (r, round) in rounds.iter().enumerate() {
+        if let Some(bye) = round.bye {
+            records[bye].had_bye = true;
+            records[bye].points += games_per_pairing(plan) * WIN_POINTS;
+        }
+        for (k, &(first, second)) in round.pairings.iter().enumerate() {
+            records[first].opponents.push(second);
+            records[second].opponents.push(first);
+            let id = pairing_id(plan, r, k);
+            for played in played.iter().filter(|played| played.matchup == id) {
+                let (a, b) = if played.game.game % 2 == 1 {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                records[a].fleet_a_games += 1;
+                match played.game.result.as_ref().map(|record| record.outcome) {
+                    Some(Outcome::FleetAWon) => records[a].points += WIN_POINTS,
+                    Some(Outcome::FleetBWon) => records[b].points += WIN_POINTS,
+                    Some(Outcome::Draw) => {
+                        records[a].points += DRAW_POINTS;
+                        records[b].points += DRAW_POINTS;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    records
+}
+
+/// Whether every game of the `r`th Swiss round has been played.
+fn round_played(
+    plan: &TournamentPlan,
+    r: usize,
+    round: &swiss::Round,
+    played: &[PlayedGame],
+) -> bool {
+    let ids: Vec<usize> = (0..round.pairings.len())
+        .map(|k| pairing_id(plan, r, k))
+        .collect();
+    let games = played
+        .iter()
+        .filter(|played| ids.contains(&played.matchup))
+        .count();
+    games == round.pairings.len() * games_per_pairing(plan) as usize
+}
+
+/// The rounds of a Swiss tournament paired so far: every round that's been played through, and
+/// the one after, which can only be paired once those results are in.
+fn swiss_rounds(plan: &TournamentPlan, played: &[PlayedGame]) -> Vec<swiss::Round> {
+    let mut rounds = Vec::new();
+    while rounds.len() < plan.rounds as usize {
+        let round = swiss::pair_round(&swiss_records(plan, &rounds, played));
+        let done = round_played(plan, rounds.len(), &round, played);
+        rounds.push(round);
+        if !done {
+            break;
+        }
+    }
+    rounds
+}
+
+/// Every game of the `r`th Swiss round, as its pairing's id, the game number and its battle. Each
+/// seed is battled on every scenario in turn, and the sides swap every game.
+fn swiss_battles<'a>(
+    plan: &'a TournamentPlan,
+    fleets: &'a [Fleet],
+    r: usize,
+    round: &swiss::Round,
+) -> Vec<(usize, u32, Battle<'a>)> {
+    let scenarios: Vec<Option<&str>> = if plan.scenarios.is_empty() {
+        vec![None]
+    } else {
+        plan.scenarios.iter().map(|s| Some(s.as_str())).collect()
+    };
+
+    let mut battles = Vec::new();
+    for (k, &(first, second)) in round.pairings.iter().enumerate() {
+        let id = pairing_id(plan, r, k);
+        let games = scenarios
+            .iter()
+            .flat_map(|&scenario| plan.seeds[id].iter().map(move |&seed| (seed, scenario)));
+        for (i, (seed, scenario)) in games.enumerate() {
+            let game = i as u32 + 1;
+            let (a, b) = if game % 2 == 1 {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            battles.push((
+                id,
+                game,
+                Battle {
+                    fleet_a: &fleets[a],
+                    fleet_b: &fleets[b],
+                    seed: Some(seed),
+                    scenario,
+                    suffix: Some(format!("tournament_round{}_game{game}", r + 1)),
+                },
+            ));
+        }
+    }
+    battles
+}
+
+/// The standings of a Swiss tournament: points with byes counted, and ties broken by Buchholz,
+/// then wins.
+fn swiss_standings<'a>(
+    plan: &TournamentPlan,
+    names: &[String],
+    rounds: &[swiss::Round],
+    played: &[PlayedGame],
+    games: impl IntoIterator<Item = (&'a str, &'a str, Option<Outcome>)>,
+) -> Vec<Standing> {
+    let records = swiss_records(plan, rounds, played);
+    let mut standings = standings(names, games);
+    for standing in &mut standings {
+        if let Some(fleet) = names.iter().position(|name| *name == standing.fleet) {
+            standing.points = records[fleet].points;
+            standing.buchholz = Some(swiss::buchholz(&records, fleet));
+        }
+    }
+    standings.sort_by_key(|s| std::cmp::Reverse((s.points, s.buchholz, s.wins)));
+    standings
+}
+
+/// A round's pairings to print, like `'a' vs 'b'`, with the bye.
+fn describe_round(names: &[String], r: usize, round: &swiss::Round) -> String {
+    let mut description = format!("Round {}:", r + 1);
+    for &(a, b) in &round.pairings {
+        write!(description, "\n  '{}' vs '{}'", names[a], names[b]).unwrap();
+    }
+    if let Some(bye) = round.bye {
+        write!(description, "\n  '{}' has a bye", names[bye]).unwrap();
+    }
+    description
+}
+
+/// A tournament about to be played, or carried on with.
+struct Tournament<'a> {
+    args: &'a TournamentArgs,
+    options: &'a BattleOptions,
+    protologic_path: &'a Path,
+    plan: &'a TournamentPlan,
+    fleets: &'a [Fleet],
+    state: &'a Path,
+}
+
+impl Tournament<'_> {
+    /// Checks every fleet is fit to battle, without checking every pair.
+    fn check_fleets(&self) -> anyhow::Result<()> {
+        for i in (0..self.fleets.len()).step_by(2) {
+            let pair = [&self.fleets[i], &self.fleets[(i + 1) % self.fleets.len()]];
+            check_before_battle(
+                &self.args.battle,
+                true,
+                self.options,
+                self.protologic_path,
+                pair,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Saves the plan of a new tournament, and gets ready to play its games.
+    fn start(&self) -> anyhow::Result<()> {
+        if self.args.resume.is_none() {
+            append_state(self.state, self.plan)?;
+        }
+        install_handler().context("trying to catch Ctrl-C")?;
+        emit(Event::PhaseStarted {
+            phase: Phase::Battle,
+        });
+        Ok(())
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.fleets.iter().map(|fleet| fleet.name.clone()).collect()
+    }
+
+    /// The tournament's record, before its games are filled in.
+    fn record(&self) -> TournamentRecord {
+        TournamentRecord {
+            timestamp: self.plan.timestamp,
+            format: self.plan.format,
+            fleets: self.plan.fleets.clone(),
+            games: self.plan.games,
+            label: self.plan.label.clone(),
+            resumed: self.args.resume.is_some(),
+            standings: Vec::new(),
+            matchups: Vec::new(),
+            bracket: None,
+            rounds: Vec::new(),
+        }
+    }
+
+    /// Plays the games of a round-robin that are left, and ranks the fleets. `None` for a dry run.
+    fn round_robin(&self, played: Vec<PlayedGame>) -> anyhow::Result<Option<TournamentRecord>> {
+        let battles = schedule(self.plan, self.fleets);
+        let total: usize = battles.iter().map(|(_, battles)| battles.len()).sum();
+        let done: HashSet<(usize, u32)> = played
+            .iter()
+            .map(|played| (played.matchup, played.game.game))
+            .collect();
+        let already_played = played.len();
+
+        if self.args.resume.is_some() {
+            say!(
+                "Resuming the round-robin tournament between {} fleets from {}: {already_played} of {total} games were already played",
+                self.fleets.len(),
+                self.state.display()
+            );
+        } else {
+            say!(
+                "Round-robin tournament between {} fleets: {} matchups, {total} games",
+                self.fleets.len(),
+                battles.len()
+            );
+        }
+        self.check_fleets()?;
+        let remaining: Vec<(usize, u32, &Battle)> = battles
+            .iter()
+            .enumerate()
+            .flat_map(|(matchup, (_, battles))| {
+                battles
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, battle)| (matchup, i as u32 + 1, battle))
+            })
+            .filter(|(matchup, game, _)| !done.contains(&(*matchup, *game)))
+            .collect();
+        if dry_run() {
+            let remaining: Vec<Battle> = remaining
+                .iter()
+                .map(|(_, _, battle)| (*battle).clone())
+                .collect();
+            print_dry_run(self.options, &remaining, None)?;
+            return Ok(None);
+        }
+
+        self.start()?;
+        let after_game = |played: &[PlayedGame], matchup: usize| {
+            let finished = &matchups(&battles, played)[matchup];
+            (finished.games.len() == battles[matchup].1.len()).then(|| describe_matchup(finished))
+        };
+        let pool = Pool {
+            options: self.options,
+            remaining: &remaining,
+            state: self.state,
+            total: Some(total),
+            already_played,
+            after_game: &after_game,
+        };
+        let played = pool.play(self.args.jobs as usize, played)?;
+
+        let mut record = self.record();
+        record.matchups = matchups(&battles, &played)
+            .into_iter()
+            .filter(|matchup| !matchup.games.is_empty())
+            .collect();
+        record.standings = standings(&self.names(), record.games());
+        say!(
+            "{}",
+            standings_table(
+                &self.standings_title(already_played, total),
+                &record.standings
+            )
+        );
+        Ok(Some(record))
+    }
+
+    /// Plays a bracket through, or what's left of it, a game of every series that's ready at a
+    /// time, and places the fleets. `None` for a dry run.
+    fn single_elim(&self, mut played: Vec<PlayedGame>) -> anyhow::Result<Option<TournamentRecord>> {
+        let plan = self.plan;
+        let names = self.names();
+        let rounds = resolve_bracket(plan, &played);
+
+        if self.args.resume.is_some() {
+            say!(
+                "Resuming the single-elimination tournament between {} fleets from {}: {} games were already played",
+                self.fleets.len(),
+                self.state.display(),
+                played.len()
+            );
+        } else {
+            let byes = match bracket_size(self.fleets.len()) - self.fleets.len() {
+                0 => String::new(),
+                1 => ", with 1 bye".to_owned(),
+                byes => format!(", with {byes} byes"),
+            };
+            say!(
+                "Single-elimination tournament between {} fleets: {} rounds of best-of-{} series{byes}, seeded {}",
+                self.fleets.len(),
+                rounds.len(),
+                plan.games,
+                describe_seeding(plan)
+            );
+        }
+        say!("{}", describe_bracket(&names, &rounds));
+        self.check_fleets()?;
+        if dry_run() {
+            let battles: Vec<Battle> = next_games(plan, self.fleets, &rounds)
+                .into_iter()
+                .map(|(_, _, battle)| battle)
+                .collect();
+            say!("The games after these depend on who wins them");
+            print_dry_run(self.options, &battles, None)?;
+            return Ok(None);
+        }
+
+        self.start()?;
+        let already_played = played.len();
+        let after_game = |played: &[PlayedGame], id: usize| {
+            let rounds = resolve_bracket(plan, played);
+            let series = rounds.iter().flatten().find(|series| series.id == id)?;
+            let [Some(a), Some(b)] = series.seeds else {
+                return None;
+            };
+            let mut line = format!(
+                "{}: '{}' {} - {} '{}'",
+                round_name(series.round, rounds.len()),
+                names[a],
+                series.wins[0],
+                series.wins[1],
+                names[b]
+            );
+            if let Some(winner) = series.winner {
+                write!(
+                    line,
+                    ", '{}' {}",
+                    names[winner],
+                    went_on(series, rounds.len())
+                )
+                .unwrap();
+            }
+            Some(line)
+        };
+        loop {
+            let rounds = resolve_bracket(plan, &played);
+            let battles = next_games(plan, self.fleets, &rounds);
+            if battles.is_empty() || interrupted() {
+                break;
+            }
+            let remaining: Vec<(usize, u32, &Battle)> = battles
+                .iter()
+                .map(|(id, game, battle)| (*id, *game, battle))
+                .collect();
+            let pool = Pool {
+                options: self.options,
+                remaining: &remaining,
+                state: self.state,
+                total: None,
+                already_played: played.len(),
+                after_game: &after_game,
+            };
+            played = pool.play(self.args.jobs as usize, played)?;
+
+            // The whole bracket is printed at the end
+            let decided = |rounds: &[Vec<Series>]| {
+                rounds
+                    .iter()
+                    .flatten()
+                    .filter(|series| series.winner.is_some())
+                    .count()
+            };
+            let after = resolve_bracket(plan, &played);
+            if decided(&after) > decided(&rounds) && champion(&after).is_none() && !interrupted() {
+                say!("{}", describe_bracket(&names, &after));
+            }
+        }
+
+        let rounds = resolve_bracket(plan, &played);
+        let mut record = self.record();
+        record.matchups = rounds
+            .iter()
+            .flatten()
+            .filter(|series| series.played > 0)
+            .filter_map(|series| {
+                let [Some(a), Some(b)] = series.seeds else {
+                    return None;
+                };
+                let [better, worse] = sides([a, b], 1);
+                let mut games: Vec<TournamentGame> = played
+                    .iter()
+                    .filter(|played| played.matchup == series.id)
+                    .map(|played| played.game.clone())
+                    .collect();
+                games.sort_by_key(|game| game.game);
+                Some(Matchup {
+                    fleet_a: names[better].clone(),
+                    fleet_b: names[worse].clone(),
+                    games,
+                })
+            })
+            .collect();
+        record.standings = standings(&names, record.games());
+        let placed: Vec<Placement> = placements(&rounds)
+            .into_iter()
+            .map(|(place, seed)| Placement {
+                place,
+                fleet: names[seed].clone(),
+            })
+            .collect();
+
+        say!("{}", describe_bracket(&names, &rounds));
+        let title = match (champion(&rounds), record.resumed) {
+            (None, _) => "Placings so far".to_owned(),
+            (Some(_), false) => "Final placings".to_owned(),
+            (Some(_), true) => format!(
+                "Final placings (resumed, with {already_played} of the games played before)"
+            ),
+        };
+        if !placed.is_empty() {
+            say!("{}", describe_placements(&title, &placed));
+        }
+        record.bracket = Some(BracketRecord {
+            seeding: plan.seeding,
+            seeding_seed: plan.seeding_seed,
+            rounds,
+            placements: placed,
+        });
+        Ok(Some(record))
+    }
+    /// Plays the rounds of a Swiss tournament that are left, pairing each once the one before has
+    /// been played, and ranks the fleets. `None` for a dry run.
+    fn swiss(&self, mut played: Vec<PlayedGame>) -> anyhow::Result<Option<TournamentRecord>> {
+        let plan = self.plan;
+        let names = self.names();
+        let pairings = self.fleets.len() / 2;
+        let total = plan.rounds as usize * pairings * games_per_pairing(plan) as usize;
+        let already_played = played.len();
+
+        if self.args.resume.is_some() {
+            say!(
+                "Resuming the Swiss tournament between {} fleets from {}: {already_played} of {total} games were already played",
+                self.fleets.len(),
+                self.state.display()
+            );
+        } else {
+            let byes = if self.fleets.len() % 2 == 1 {
+                ", with a bye each round"
+            } else {
+                ""
+            };
+            let per_round = match pairings {
+                1 => "1 pairing".to_owned(),
+                pairings => format!("{pairings} pairings"),
+            };
+            say!(
+                "Swiss tournament between {} fleets: {} rounds of {per_round}{byes}, {total} games",
+                self.fleets.len(),
+                plan.rounds
+            );
+        }
+        self.check_fleets()?;
+        if dry_run() {
+            let rounds = swiss_rounds(plan, &played);
+            let r = rounds.len() - 1;
+            let done: HashSet<(usize, u32)> = played
+                .iter()
+                .map(|played| (played.matchup, played.game.game))
+                .collect();
+            let battles: Vec<Battle> = swiss_battles(plan, self.fleets, r, &rounds[r])
+                .into_iter()
+                .filter(|(id, game, _)| !done.contains(&(*id, *game)))
+                .map(|(_, _, battle)| battle)
+                .collect();
+            say!("{}", describe_round(&names, r, &rounds[r]));
+            say!("The rounds after it are paired by its results");
+            print_dry_run(self.options, &battles, None)?;
+            return Ok(None);
+        }
+
+        self.start()?;
+        let after_game = |played: &[PlayedGame], id: usize| {
+            let games: Vec<&PlayedGame> = played.iter().filter(|p| p.matchup == id).collect();
+            if games.len() < games_per_pairing(plan) as usize {
+                return None;
+            }
+            let (r, k) = (id / pairings, id % pairings);
+            let (first, second) = swiss_rounds(plan, played)[r].pairings[k];
+            let mut wins = [0, 0];
+            for played in games {
+                let first_won = match played.game.result.as_ref().map(|record| record.outcome) {
+                    Some(Outcome::FleetAWon) => played.game.game % 2 == 1,
+                    Some(Outcome::FleetBWon) => played.game.game % 2 == 0,
+                    _ => continue,
+                };
+                wins[usize::from(!first_won)] += 1;
+            }
+            Some(format!(
+                "Round {}: '{}' {} - {} '{}'",
+                r + 1,
+                names[first],
+                wins[0],
+                wins[1],
+                names[second]
+            ))
+        };
+        loop {
+            let rounds = swiss_rounds(plan, &played);
+            let r = rounds.len() - 1;
+            let done: HashSet<(usize, u32)> = played
+                .iter()
+                .map(|played| (played.matchup, played.game.game))
+                .collect();
+            let battles: Vec<(usize, u32, Battle)> =
+                swiss_battles(plan, self.fleets, r, &rounds[r])
+                    .into_iter()
+                    .filter(|(id, game, _)| !done.contains(&(*id, *game)))
+                    .collect();
+            if battles.is_empty() || interrupted() {
+                break;
+            }
+            say!("{}", describe_round(&names, r, &rounds[r]));
+            let remaining: Vec<(usize, u32, &Battle)> = battles
+                .iter()
+                .map(|(id, game, battle)| (*id, *game, battle))
+                .collect();
+            let pool = Pool {
+                options: self.options,
+                remaining: &remaining,
+                state: self.state,
+                total: Some(total),
+                already_played: played.len(),
+                after_game: &after_game,
+            };
+            played = pool.play(self.args.jobs as usize, played)?;
+
+            // The final standings are printed at the end
+            if r + 1 < plan.rounds as usize && round_played(plan, r, &rounds[r], &played) {
+                let record = self.swiss_record(&names, &played);
+                say!(
+                    "{}",
+                    standings_table(
+                        &format!("Standings after round {}", r + 1),
+                        &record.standings
+                    )
+                );
+            }
+        }
+
+        let record = self.swiss_record(&names, &played);
+        say!(
+            "{}",
+            standings_table(
+                &self.standings_title(already_played, total),
+                &record.standings
+            )
+        );
+        Ok(Some(record))
+    }
+
+    /// The record of a Swiss tournament, as far as it's been played. A round that's been paired
+    /// but not started yet doesn't count.
+    fn swiss_record(&self, names: &[String], played: &[PlayedGame]) -> TournamentRecord {
+        let plan = self.plan;
+        let mut rounds = swiss_rounds(plan, played);
+        let started = |r: usize, round: &swiss::Round| {
+            (0..round.pairings.len()).any(|k| {
+                let id = pairing_id(plan, r, k);
+                played.iter().any(|played| played.matchup == id)
+            })
+        };
+        while let Some(round) = rounds.last() {
+            if started(rounds.len() - 1, round) {
+                break;
+            }
+            rounds.pop();
+        }
+        let mut record = self.record();
+        for (r, round) in rounds.iter().enumerate() {
+            for (k, &(first, second)) in round.pairings.iter().enumerate() {
+                let id = pairing_id(plan, r, k);
+                let mut games: Vec<TournamentGame> = played
+                    .iter()
+                    .filter(|played| played.matchup == id)
+                    .map(|played| played.game.clone())
+                    .collect();
+                if games.is_empty() {
+                    continue;
+                }
+                games.sort_by_key(|game| game.game);
+                record.matchups.push(Matchup {
+                    fleet_a: names[first].clone(),
+                    fleet_b: names[second].clone(),
+                    games,
+                });
+            }
+        }
+        record.standings = swiss_standings(plan, names, &rounds, played, record.games());
+        record.rounds = rounds
+            .iter()
+            .map(|round| SwissRound {
+                pairings: round
+                    .pairings
+                    .iter()
+                    .map(|&(a, b)| [names[a].clone(), names[b].clone()])
+                    .collect(),
+                bye: round.bye.map(|bye| names[bye].clone()),
+            })
+            .collect();
+        record
+    }
+
+    /// The title of the final standings, which say when the tournament was resumed.
+    fn standings_title(&self, already_played: usize, total: usize) -> String {
+        if self.args.resume.is_some() {
+            format!("Tournament standings (resumed, with {already_played} of the {total} games played before)")
+        } else {
+            "Tournament standings".to_owned()
+        }
+    }
+}
+
+/// Plays a tournament between built fleets and records how it went, in a round-robin by default
+/// or knocking fleets out of a bracket with `--format single-elim`.
+///
+/// A battle that fails is recorded as an error rather than ending the tournament, and every game
+/// is saved to the tournament's state file as it finishes, so a tournament stopped by Ctrl-C or a
+/// crash can carry on with `--resume`. With `--jobs`, several games are played at once.
+pub fn tournament(mut args: TournamentArgs) -> anyhow::Result<()> {
+    let config = args
+        .config
+        .as_deref()
+        .map(TournamentConfig::read)
+        .transpose()?;
+    if let Some(config) = &config {
+        apply_config(&mut args, config)?;
+    }
+    apply_tournament_settings(&mut args, settings()?);
+    if args.resume.is_none() {
+        check_format_flags(&args)?;
+    }
+    if let Some(report) = &args.report {
+        TournamentReportFormat::from_path(report)?;
+    }
+    apply_unpruned_battle_settings(&mut args.battle, settings()?, "the tournament's")?;
+    let protologic_path = find_protologic_path(args.battle.protologic_path.clone())?;
+    let mut options = battle_options(&args.battle, &protologic_path)?;
+    if !args.unrated {
+        options.ratings = Some(rating_options(&args.battle)?);
+    }
+    // Several sims at once would all talk over each other in the terminal
+    if args.jobs > 1 && options.sim.sink == SimOutputSink::Inherit {
+        options.sim.sink = SimOutputSink::Log;
+    }
+
+    let (base, plan, played, fleets) = if let Some(state) = &args.resume {
+        let (plan, played) = read_state(state)?;
+        let fleets = resume_fleets(&plan, args.battle.debug, args.allow_changed)?;
+        (base_from_state(state)?, plan, played, fleets)
+    } else {
+        // Fleets named on the command line take the place of the file's
+        let config = config
+            .as_ref()
+            .filter(|config| args.entrants.is_empty() && !config.fleets.is_empty());
+        let mut fleets = entrants(&args, config)?;
+        check_sizes(&fleets, args.max_size)?;
+        let seeding = args.seeding.unwrap_or_default();
+        let seeding_seed =
+            (seeding == Seeding::Random).then(|| args.seeding_seed.unwrap_or_else(random_seed));
+        seed_fleets(&mut fleets, seeding, seeding_seed, &options.output_dir)?;
+        let rounds = swiss_round_count(&args, fleets.len())?;
+        let format = args.format.unwrap_or_default();
+        let (games, matchups) = match format {
+            TournamentFormat::RoundRobin => (args.games, pairings(fleets.len()).len()),
+            TournamentFormat::SingleElim => (args.best_of, bracket_size(fleets.len()) - 1),
+            TournamentFormat::Swiss => (args.games, rounds as usize * (fleets.len() / 2)),
+        };
+        let games = game_count(format, games, &args.seeds)?;
+        let timestamp = unix_now()?;
+        let scenarios = args
+            .battle
+            .scenarios
+            .iter()
+            .map(|scenario| resolve_scenario(scenario))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let plan = TournamentPlan {
+            timestamp,
+            format,
+            fleets: fleets
+                .iter()
+                .map(|fleet| entrant(fleet, config))
+                .collect::<anyhow::Result<_>>()?,
+            games,
+            label: options.label.clone(),
+            scenarios,
+            seeds: (0..matchups)
+                .map(|_| {
+                    if args.seeds.is_empty() {
+                        (0..games).map(|_| random_seed()).collect()
+                    } else {
+                        args.seeds.clone()
+                    }
+                })
+                .collect(),
+            seeding,
+            seeding_seed,
+            rounds,
+        };
+        (
+            tournament_base_path(&options, timestamp)?,
+            plan,
+            Vec::new(),
+            fleets,
+        )
+    };
+    let state = state_path(&base);
+    let tournament = Tournament {
+        args: &args,
+        options: &options,
+        protologic_path: &protologic_path,
+        plan: &plan,
+        fleets: &fleets,
+        state: &state,
+    };
+    let record = match plan.format {
+        TournamentFormat::RoundRobin => tournament.round_robin(played)?,
+        TournamentFormat::SingleElim => tournament.single_elim(played)?,
+        TournamentFormat::Swiss => tournament.swiss(played)?,
+    };
+    let Some(record) = record else {
+        return Ok(());
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&record)?);
+    }
+    if interrupted() {
+        say!(
+            "Carry on where it stopped with `cargo protologic tournament --resume {}`",
+            state.display()
+        );
+        anyhow::bail!("interrupted by Ctrl-C before the tournament finished");
+    }
+
+    let path = record_path(&base);
+    record.write_json(&path)?;
+    say!(
+        "Saved the results to {}, for `cargo protologic standings`",
+        path.display()
+    );
+    if let Some(report) = &args.report {
+        write_report(&record, report)?;
+        say!("Wrote the report to {}", report.display());
+    }
+    Ok(())
+}
+
+/// The games played so far in each matchup, in the order of [`pairings`] and then by game.
+fn matchups(battles: &[((usize, usize), Vec<Battle>)], played: &[PlayedGame]) -> Vec<Matchup> {
+    battles
+        .iter()
+        .enumerate()
+        .map(|(i, (_, matchup_battles))| {
+            let mut games: Vec<TournamentGame> = played
+                .iter()
+                .filter(|played| played.matchup == i)
+                .map(|played| played.game.clone())
+                .collect();
+            games.sort_by_key(|game| game.game);
+            let battle = &matchup_battles[0];
+            Matchup {
+                fleet_a: battle.fleet_a.name.clone(),
+                fleet_b: battle.fleet_b.name.clone(),
+                games,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use clap::Parser;
+
+    use super::{
+        append_state, base_from_state, describe_placements, game_count, next_games, pairings,
+        read_state, resolve_bracket, schedule, standings, standings_table, swiss_battles,
+        swiss_rounds, swiss_standings, Entrant, Matchup, Placement, PlayedGame, Seeding, Standing,
+        TournamentArgs, TournamentFormat, TournamentGame, TournamentPlan, TournamentRecord,
+    };
+    use crate::fleet::Fleet;
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::run::apply_unpruned_battle_settings;
+    use crate::settings::{Settings, WORKSPACE_FILE};
+
+    #[derive(Parser)]
+    struct Tournament {
+        #[command(flatten)]
+        args: TournamentArgs,
+    }
+
+    #[test]
+    fn tournaments_never_prune_their_replays() -> anyhow::Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::write(
+            workspace.path().join(WORKSPACE_FILE),
+            "[run]\nkeep-last = 5\narchive-fleets = true\n",
+        )?;
+        let settings = Settings::load(Some(workspace.path()))?;
+
+        let mut args = Tournament::try_parse_from(["tournament"])?.args;
+        apply_unpruned_battle_settings(&mut args.battle, &settings, "the tournament's")?;
+        assert_eq!(args.battle.keep_last, None);
+        // The rest of `[run]` still counts
+        assert!(args.battle.archive_fleets);
+
+        let mut args = Tournament::try_parse_from(["tournament", "--keep-last", "3"])?.args;
+        let err = apply_unpruned_battle_settings(&mut args.battle, &settings, "the tournament's")
+            .unwrap_err();
+        assert!(err.to_string().contains("`--keep-last`"), "{err}");
+        Ok(())
+    }
+
+    fn game(game: u32, outcome: Option<Outcome>) -> TournamentGame {
+        TournamentGame {
+            game,
+            result: outcome.map(|outcome| BattleRecord {
+                outcome,
+                ..BattleRecord::default()
+            }),
+            error: outcome.is_none().then(|| "sim not found".to_owned()),
+        }
+    }
+
+    fn matchup(a: &str, b: &str, mut games: Vec<TournamentGame>) -> Matchup {
+        for record in games.iter_mut().filter_map(|game| game.result.as_mut()) {
+            record.fleet_a = a.to_owned();
+            record.fleet_b = b.to_owned();
+        }
+        Matchup {
+            fleet_a: a.to_owned(),
+            fleet_b: b.to_owned(),
+            games,
+        }
+    }
+
+    #[test]
+    fn fixed_seeds_set_the_number_of_games() {
+        use TournamentFormat::{RoundRobin, SingleElim};
+
+        assert_eq!(game_count(RoundRobin, None, &[]).unwrap(), 1);
+        assert_eq!(game_count(SingleElim, None, &[]).unwrap(), 1);
+        assert_eq!(game_count(RoundRobin, None, &[4, 5]).unwrap(), 2);
+        assert_eq!(game_count(SingleElim, Some(3), &[4, 5, 6]).unwrap(), 3);
+        assert!(game_count(RoundRobin, Some(3), &[4, 5]).is_err());
+        assert!(game_count(SingleElim, None, &[4, 5]).is_err());
+    }
+
+    #[test]
+    fn every_pair_meets_from_both_sides() {
+        assert_eq!(
+            pairings(3),
+            vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)]
+        );
+        assert!(pairings(1).is_empty());
+    }
+
+    #[test]
+    fn standings_rank_by_points_then_wins() {
+        let fleets = ["a", "b", "c"].map(ToOwned::to_owned);
+        let matchups = [
+            matchup(
+                "a",
+                "b",
+                vec![
+                    game(1, Some(Outcome::FleetAWon)),
+                    game(2, Some(Outcome::Draw)),
+                ],
+            ),
+            matchup(
+                "b",
+                "a",
+                vec![game(1, Some(Outcome::FleetAWon)), game(2, None)],
+            ),
+            matchup(
+                "c",
+                "a",
+                vec![
+                    game(1, Some(Outcome::FleetBWon)),
+                    game(2, Some(Outcome::SimCrashed)),
+                ],
+            ),
+        ];
+
+        let record = TournamentRecord {
+            timestamp: 0,
+            format: TournamentFormat::RoundRobin,
+            fleets: Vec::<Entrant>::new(),
+            games: 2,
+            label: None,
+            resumed: false,
+            standings: Vec::new(),
+            matchups: matchups.to_vec(),
+            bracket: None,
+            rounds: Vec::new(),
+        };
+        let standings = standings(&fleets, record.games());
+        let points: Vec<(&str, u32)> = standings
+            .iter()
+            .map(|s| (s.fleet.as_str(), s.points))
+            .collect();
+        assert_eq!(points, vec![("a", 7), ("b", 4), ("c", 0)]);
+        assert_eq!(
+            standings[0],
+            Standing {
+                fleet: "a".to_owned(),
+                played: 6,
+                wins: 2,
+                losses: 1,
+                draws: 1,
+                errors: 2,
+                points: 7,
+                buchholz: None,
+            }
+        );
+        assert_eq!(standings[2].errors, 1);
+        assert_eq!(standings[2].losses, 1);
+
+        let table = standings_table("Tournament standings", &standings);
+        assert!(
+            table.contains("  1     a      6       2    1     1      2       7       62.5%\n"),
+            "{table}"
+        );
+        assert_eq!(Standing::default().win_rate(), None);
+    }
+
+    fn plan(fleets: &[&str]) -> TournamentPlan {
+        TournamentPlan {
+            timestamp: 1718476403,
+            format: TournamentFormat::RoundRobin,
+            fleets: fleets
+                .iter()
+                .map(|&name| Entrant {
+                    name: name.to_owned(),
+                    sha256: "1a2b3c4d".to_owned(),
+                    ..Entrant::default()
+                })
+                .collect(),
+            games: 2,
+            label: None,
+            scenarios: vec!["belt".to_owned(), "open".to_owned()],
+            seeds: pairings(fleets.len()).iter().map(|_| vec![7, 8]).collect(),
+            seeding: Seeding::Given,
+            seeding_seed: None,
+            rounds: 0,
+        }
+    }
+
+    fn fleets(names: &[&str]) -> Vec<Fleet> {
+        names
+            .iter()
+            .map(|&name| Fleet {
+                name: name.to_owned(),
+                path: PathBuf::from(format!("{name}.wasm")),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn games_are_numbered_across_scenarios() {
+        let plan = plan(&["a", "b"]);
+        let fleets = fleets(&["a", "b"]);
+
+        let battles = schedule(&plan, &fleets);
+        assert_eq!(battles.len(), 2);
+        let ((a, b), games) = &battles[1];
+        assert_eq!((*a, *b), (1, 0));
+        assert_eq!(games[0].fleet_a.name, "b");
+        let games: Vec<(Option<u64>, Option<&str>, Option<&str>)> = games
+            .iter()
+            .map(|battle| (battle.seed, battle.scenario, battle.suffix.as_deref()))
+            .collect();
+        assert_eq!(
+            games,
+            [
+                (Some(7), Some("belt"), Some("tournament_game1")),
+                (Some(8), Some("belt"), Some("tournament_game2")),
+                (Some(7), Some("open"), Some("tournament_game3")),
+                (Some(8), Some("open"), Some("tournament_game4")),
+            ]
+        );
+    }
+
+    #[test]
+    fn state_files_keep_played_games_and_skip_a_cut_off_line() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let state = dir.path().join("2024-06-15_tournament.state.jsonl");
+        append_state(&state, &plan(&["a", "b", "c"]))?;
+        for matchup in [0, 3] {
+            append_state(
+                &state,
+                &PlayedGame {
+                    matchup,
+                    game: game(1, Some(Outcome::Draw)),
+                },
+            )?;
+        }
+
+        let (plan, played) = read_state(&state)?;
+        assert_eq!(plan.fleets.len(), 3);
+        assert_eq!(plan.seeds.len(), 6);
+        let played: Vec<usize> = played.iter().map(|played| played.matchup).collect();
+        assert_eq!(played, [0, 3]);
+
+        let mut contents = std::fs::read_to_string(&state)?;
+        contents.push_str("{\"matchup\": 4, \"ga");
+        std::fs::write(&state, contents)?;
+        assert_eq!(read_state(&state)?.1.len(), 2);
+
+        assert_eq!(
+            base_from_state(&state)?,
+            dir.path().join("2024-06-15_tournament")
+        );
+        assert!(base_from_state(Path::new("results.json")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bracket_series_swap_sides_and_send_the_winner_on() {
+        let mut plan = plan(&["a", "b", "c"]);
+        plan.format = TournamentFormat::SingleElim;
+        plan.games = 3;
+        plan.seeds = vec![vec![7, 8, 9]; 3];
+        let fleets = fleets(&["a", "b", "c"]);
+        let played_game = |game_number, outcome| PlayedGame {
+            matchup: 1,
+            game: game(game_number, Some(outcome)),
+        };
+        // 'b' wins game 1 as fleet A, and 'c' game 2 as fleet A
+        let mut played = vec![
+            played_game(1, Outcome::FleetAWon),
+            played_game(2, Outcome::FleetAWon),
+        ];
+
+        let rounds = resolve_bracket(&plan, &played);
+        assert_eq!(rounds[0][1].wins, [1, 1]);
+        let next = next_games(&plan, &fleets, &rounds);
+        assert_eq!(next.len(), 1);
+        let (series, game_number, battle) = &next[0];
+        assert_eq!((*series, *game_number), (1, 3));
+        assert_eq!(
+            (battle.fleet_a.name.as_str(), battle.fleet_b.name.as_str()),
+            ("b", "c")
+        );
+        assert_eq!((battle.seed, battle.scenario), (Some(9), Some("belt")));
+        assert_eq!(battle.suffix.as_deref(), Some("tournament_series2_game3"));
+
+        played.push(played_game(3, Outcome::FleetBWon));
+        let rounds = resolve_bracket(&plan, &played);
+        assert_eq!(rounds[1][0].seeds, [Some(0), Some(2)]);
+        let next = next_games(&plan, &fleets, &rounds);
+        assert_eq!(next[0].0, 2);
+        assert_eq!(next[0].2.fleet_a.name, "a");
+
+        let placements = [(1, "a"), (2, "c"), (3, "b")].map(|(place, fleet)| Placement {
+            place,
+            fleet: fleet.to_owned(),
+        });
+        assert_eq!(
+            describe_placements("Final placings", &placements),
+            "Final placings:\n  1st   'a'\n  2nd   'c'\n  3rd   'b'"
+        );
+    }
+
+    #[test]
+    fn swiss_rounds_are_paired_once_the_one_before_is_played() {
+        let mut plan = plan(&["a", "b", "c"]);
+        plan.format = TournamentFormat::Swiss;
+        plan.games = 1;
+        plan.rounds = 3;
+        plan.scenarios = Vec::new();
+        plan.seeds = vec![vec![7]; 3];
+        let fleets = fleets(&["a", "b", "c"]);
+
+        let rounds = swiss_rounds(&plan, &[]);
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].pairings, [(0, 1)]);
+        assert_eq!(rounds[0].bye, Some(2));
+
+        // 'a' beats 'b', and 'c' gets a win for its bye, so they meet next with 'b' sitting out
+        let played = [PlayedGame {
+            matchup: 0,
+            game: game(1, Some(Outcome::FleetAWon)),
+        }];
+        let rounds = swiss_rounds(&plan, &played);
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[1].pairings, [(2, 0)]);
+        assert_eq!(rounds[1].bye, Some(1));
+        let battles = swiss_battles(&plan, &fleets, 1, &rounds[1]);
+        let (id, game_number, battle) = &battles[0];
+        assert_eq!((*id, *game_number), (1, 1));
+        assert_eq!(battle.fleet_a.name, "c");
+        assert_eq!(battle.suffix.as_deref(), Some("tournament_round2_game1"));
+
+        let names: Vec<String> = ["a", "b", "c"].map(str::to_owned).to_vec();
+        let standings = swiss_standings(
+            &plan,
+            &names,
+            &rounds[..1],
+            &played,
+            [("a", "b", Some(Outcome::FleetAWon))],
+        );
+        let points: Vec<(&str, u32, Option<u32>)> = standings
+            .iter()
+            .map(|s| (s.fleet.as_str(), s.points, s.buchholz))
+            .collect();
+        assert_eq!(
+            points,
+            [("a", 3, Some(0)), ("c", 3, Some(0)), ("b", 0, Some(3))]
+        );
+        let table = standings_table("Standings after round 1", &standings);
+        assert!(table.contains("Points  Buchholz  Win-rate\n"), "{table}");
+    }
+}
@@ -0,0 +1,276 @@
+//! Tournament definition files, like `tournament.toml`, for events with rules of their own: which
+//! fleets are entered, submissions from outside the workspace included, and how their games are
+//! played. Every setting in one has a flag of the same name, which wins over the file.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Deserialize;
+
+use crate::fleet::{extract_fleet_name, find_fleet, find_fleets_for_run, Fleet};
+use crate::series::parse_best_of;
+use crate::sim::is_scenario_path;
+use crate::tournament::{Seeding, TournamentFormat};
+
+/// A tournament as its definition file describes it, with relative paths made relative to the
+/// file.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TournamentConfig {
+    /// The tournament's name, which is its `--label`.
+    pub name: Option<String>,
+    pub format: Option<TournamentFormat>,
+    pub games: Option<u32>,
+    pub best_of: Option<u32>,
+    pub seeding: Option<Seeding>,
+    pub seeding_seed: Option<u64>,
+    pub rounds: Option<u32>,
+    /// The seeds every matchup's games are played on, one game each.
+    #[serde(default)]
+    pub seeds: Vec<u64>,
+    #[serde(default)]
+    pub scenarios: Vec<String>,
+    /// The biggest a fleet's wasm can be, like `2MiB`.
+    pub max_size: Option<String>,
+    pub max_ticks: Option<u64>,
+    pub fuel_limit: Option<u64>,
+    #[serde(default, rename = "fleet")]
+    pub fleets: Vec<FleetEntry>,
+}
+
+/// A `[[fleet]]` of a definition file: a workspace fleet or an external wasm file.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FleetEntry {
+    /// The built fleet, by package or fleet name.
+    pub package: Option<String>,
+    /// A fleet's wasm file from outside the workspace, like a submission.
+    pub wasm: Option<PathBuf>,
+    /// What the fleet's called in the tournament. Defaults to its package, or its wasm's file name.
+    pub name: Option<String>,
+    pub author: Option<String>,
+}
+
+impl FleetEntry {
+    /// What the fleet's called in the tournament.
+    pub fn display_name(&self) -> anyhow::Result<String> {
+        match (&self.name, &self.package, &self.wasm) {
+            (Some(name), _, _) => Ok(name.clone()),
+            (None, Some(package), _) => Ok(package.clone()),
+            (None, None, Some(wasm)) => extract_fleet_name(wasm),
+            (None, None, None) => anyhow::bail!("a fleet needs a `package` or a `wasm`"),
+        }
+    }
+}
+
+impl TournamentConfig {
+    /// Reads a definition file and checks everything in it that can be checked without the
+    /// fleets being looked up.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("trying to read the tournament definition {path:?}"))?;
+        let mut config: Self = toml::from_str(&contents)
+            .with_context(|| format!("trying to parse the tournament definition {path:?}"))?;
+        config
+            .check()
+            .with_context(|| format!("trying to check the tournament definition {path:?}"))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for fleet in &mut config.fleets {
+            if let Some(wasm) = &mut fleet.wasm {
+                *wasm = dir.join(&*wasm);
+            }
+        }
+        for scenario in &mut config.scenarios {
+            if is_scenario_path(scenario) {
+                *scenario = dir.join(&*scenario).to_string_lossy().into_owned();
+            }
+        }
+        Ok(config)
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        let count = |value: Option<u32>| value.map(u64::from);
+        for (key, value) in [
+            ("games", count(self.games)),
+            ("best-of", count(self.best_of)),
+            ("rounds", count(self.rounds)),
+            ("max-ticks", self.max_ticks),
+            ("fuel-limit", self.fuel_limit),
+        ] {
+            if value == Some(0) {
+                anyhow::bail!("`{key}` has to be at least 1");
+            }
+        }
+        if let Some(best_of) = self.best_of {
+            parse_best_of(&best_of.to_string())
+                .map_err(|err| anyhow::anyhow!("`best-of` is {best_of}, but {err}"))?;
+        }
+        self.max_size()?;
+
+        let mut names = HashSet::new();
+        for (i, fleet) in self.fleets.iter().enumerate() {
+            let which = || format!("fleet {} of the file", i + 1);
+            if fleet.package.is_some() == fleet.wasm.is_some() {
+                anyhow::bail!(
+                    "{} needs either a `package` or a `wasm`, and not both",
+                    which()
+                );
+            }
+            let name = fleet.display_name().with_context(which)?;
+            if !names.insert(name.clone()) {
+                anyhow::bail!(
+                    "there's more than one fleet named '{name}', give them each a `name`"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn max_size(&self) -> anyhow::Result<Option<ByteSize>> {
+        self.max_size
+            .as_deref()
+            .map(|size| {
+                size.parse::<ByteSize>().map_err(|err| {
+                    anyhow::anyhow!("`max-size` is {size:?}, but has to be a size like 2MiB: {err}")
+                })
+            })
+            .transpose()
+    }
+
+    /// The file's fleets, each under its name in the tournament. Every one that can't be found is
+    /// listed at once, so they can all be fixed before trying again.
+    pub fn entrant_fleets(&self, debug: bool) -> anyhow::Result<Vec<Fleet>> {
+        let built = if self.fleets.iter().any(|fleet| fleet.package.is_some()) {
+            find_fleets_for_run(debug)?
+        } else {
+            Vec::new()
+        };
+
+        let mut fleets = Vec::new();
+        let mut missing = Vec::new();
+        for entry in &self.fleets {
+            let name = entry.display_name()?;
+            match (&entry.package, &entry.wasm) {
+                (Some(package), _) => match find_fleet(&built, package) {
+                    Ok(fleet) => fleets.push(Fleet {
+                        name,
+                        path: fleet.path,
+                    }),
+                    Err(err) => missing.push(format!("'{name}': {err:#}")),
+                },
+                (None, Some(wasm)) if wasm.is_file() => fleets.push(Fleet {
+                    name,
+                    path: wasm.clone(),
+                }),
+                (None, Some(wasm)) => missing.push(format!("'{name}': there's no file {wasm:?}")),
+                (None, None) => unreachable!("checked when the file was read"),
+            }
+        }
+
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "couldn't find every fleet in the tournament definition:\n    {}",
+                missing.join("\n    ")
+            );
+        }
+        Ok(fleets)
+    }
+
+    /// The file's entry for the fleet called `name` in the tournament.
+    pub fn entry(&self, name: &str) -> Option<&FleetEntry> {
+        self.fleets
+            .iter()
+            .find(|fleet| fleet.display_name().is_ok_and(|entry| entry == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{FleetEntry, TournamentConfig};
+    use crate::tournament::TournamentFormat;
+
+    fn write(contents: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tournament.toml");
+        std::fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn fleets_and_paths_are_read_relative_to_the_file() {
+        let (dir, path) = write(
+            r#"
+name = "June cup"
+format = "swiss"
+games = 2
+seeds = [11, 12]
+scenarios = ["asteroids", "maps/belt.json"]
+max-size = "2MiB"
+max-ticks = 20000
+
+[[fleet]]
+package = "iron_armada"
+author = "Ann"
+
+[[fleet]]
+wasm = "submissions/swarm.wasm"
+name = "The Swarm"
+"#,
+        );
+        let config = TournamentConfig::read(&path).unwrap();
+        assert_eq!(config.name.as_deref(), Some("June cup"));
+        assert_eq!(config.format, Some(TournamentFormat::Swiss));
+        assert_eq!(config.seeds, [11, 12]);
+        assert_eq!(
+            config.max_size().unwrap().unwrap().as_u64(),
+            2 * 1024 * 1024
+        );
+        assert_eq!(config.scenarios[0], "asteroids");
+        assert_eq!(
+            Path::new(&config.scenarios[1]),
+            dir.path().join("maps/belt.json")
+        );
+        assert_eq!(
+            config.fleets[1].wasm.as_deref(),
+            Some(dir.path().join("submissions/swarm.wasm").as_path())
+        );
+        assert_eq!(
+            config.entry("iron_armada"),
+            Some(&FleetEntry {
+                package: Some("iron_armada".to_owned()),
+                author: Some("Ann".to_owned()),
+                ..FleetEntry::default()
+            })
+        );
+        assert!(config.entry("The Swarm").is_some());
+
+        // Missing external fleets are all listed together
+        let err = config.entrant_fleets(false).unwrap_err();
+        assert!(format!("{err:#}").contains("'The Swarm': there's no file"));
+    }
+
+    #[test]
+    fn mistakes_in_the_file_are_errors() {
+        let err = |contents: &str| {
+            let (_dir, path) = write(contents);
+            format!("{:#}", TournamentConfig::read(&path).unwrap_err())
+        };
+        assert!(err("gmaes = 3").contains("unknown field `gmaes`"));
+        assert!(
+            err("[[fleet]]\npackage = \"a\"\nauthr = \"Ann\"").contains("unknown field `authr`")
+        );
+        assert!(err("best-of = 2").contains("odd number of games"));
+        assert!(err("games = 0").contains("`games` has to be at least 1"));
+        assert!(err("max-size = \"big\"").contains("has to be a size"));
+        assert!(err("[[fleet]]\nname = \"a\"").contains("needs either a `package` or a `wasm`"));
+        assert!(
+            err("[[fleet]]\npackage = \"a\"\n[[fleet]]\nwasm = \"b.wasm\"\nname = \"a\"")
+                .contains("more than one fleet named 'a'")
+        );
+    }
+}
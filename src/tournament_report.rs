@@ -0,0 +1,596 @@
+//! Reports of a tournament to post once it's over: the standings, who beat whom, the longest and
+//! shortest battles, and every game with a link to its replay.
+
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+
+use crate::outcome::Outcome;
+use crate::report::winner;
+use crate::results::BattleRecord;
+use crate::sim::format_duration;
+use crate::template::render;
+use crate::tournament::{Entrant, Placement, Standing, TournamentFormat, TournamentRecord};
+
+const HTML_TEMPLATE: &str = include_str!("../templates/report/tournament.html.in");
+const MARKDOWN_TEMPLATE: &str = include_str!("../templates/report/tournament.md.in");
+
+/// What a report is written as, from its file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentReportFormat {
+    /// A page on its own, with its styles inline and no scripts.
+    Html,
+    /// A document to post in a GitHub discussion.
+    Markdown,
+}
+
+impl TournamentReportFormat {
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html" | "htm") => Ok(Self::Html),
+            Some("md" | "markdown") => Ok(Self::Markdown),
+            _ => anyhow::bail!(
+                "can't tell what kind of report {path:?} is, name it with `.html` or `.md`"
+            ),
+        }
+    }
+}
+
+/// A table cell, which links to a file when there's one to link to.
+enum Cell {
+    Text(String),
+    Number(String),
+    Link { text: String, href: String },
+}
+
+struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|&header| header.to_owned()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let mut table = format!("| {} |\n|", self.headers.join(" | "));
+        table.push_str(&"---|".repeat(self.headers.len()));
+        for row in &self.rows {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Cell::Text(text) | Cell::Number(text) => escape_markdown(text),
+                    Cell::Link { text, href } => {
+                        format!("[{}]({})", escape_markdown(text), href.replace(' ', "%20"))
+                    }
+                })
+                .collect();
+            write!(table, "\n| {} |", cells.join(" | ")).unwrap();
+        }
+        table
+    }
+
+    fn html(&self) -> String {
+        let mut table = "<table>\n<thead><tr>".to_owned();
+        for header in &self.headers {
+            write!(table, "<th>{}</th>", escape_html(header)).unwrap();
+        }
+        table.push_str("</tr></thead>\n<tbody>\n");
+        for row in &self.rows {
+            table.push_str("<tr>");
+            for cell in row {
+                match cell {
+                    Cell::Text(text) => write!(table, "<td>{}</td>", escape_html(text)),
+                    Cell::Number(text) => {
+                        write!(table, "<td class=\"number\">{}</td>", escape_html(text))
+                    }
+                    Cell::Link { text, href } => write!(
+                        table,
+                        "<td><a href=\"{}\">{}</a></td>",
+                        escape_html(&href.replace(' ', "%20")),
+                        escape_html(text)
+                    ),
+                }
+                .unwrap();
+            }
+            table.push_str("</tr>\n");
+        }
+        table.push_str("</tbody>\n</table>");
+        table
+    }
+
+    fn render(&self, format: TournamentReportFormat) -> String {
+        match format {
+            TournamentReportFormat::Html => self.html(),
+            TournamentReportFormat::Markdown => self.markdown(),
+        }
+    }
+}
+
+/// Keeps text from being taken for a table's column break or an HTML tag.
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('<', "\\<")
+}
+
+/// Keeps text from being taken for HTML tags, or from ending an attribute.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The path to `to` from the directory `from`, with `/` between components, so it works as a
+/// link in the report wherever the two are moved together.
+fn relative_link(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let shared = from
+        .iter()
+        .zip(&to)
+        .take_while(|(from, to)| from == to)
+        .count();
+
+    let mut parts: Vec<String> = vec!["..".to_owned(); from.len() - shared];
+    parts.extend(
+        to[shared..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+/// A game of the tournament, as the report shows it.
+struct ReportGame<'a> {
+    /// What the game was in the tournament, like `'a' vs 'b' game 2`.
+    matchup: String,
+    game: u32,
+    record: Option<&'a BattleRecord>,
+    error: Option<&'a str>,
+}
+
+/// When the tournament started, in UTC so the report reads the same wherever it's made.
+fn started(record: &TournamentRecord) -> String {
+    i64::try_from(record.timestamp)
+        .ok()
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+        .map_or("an unknown time".to_owned(), |time| {
+            time.format("%Y-%m-%d %H:%M UTC").to_string()
+        })
+}
+
+/// A sentence on what kind of tournament it was and how it went.
+fn summary(record: &TournamentRecord, games: usize) -> String {
+    let fleets = record.fleets.len();
+    let mut summary = match record.format {
+        TournamentFormat::RoundRobin => format!(
+            "A round-robin between {fleets} fleets, playing {} from each side",
+            plural(record.games, "game")
+        ),
+        TournamentFormat::SingleElim => format!(
+            "A single-elimination bracket of {fleets} fleets, with best-of-{} series",
+            record.games
+        ),
+        TournamentFormat::Swiss => format!(
+            "A Swiss tournament between {fleets} fleets over {}",
+            plural(record.rounds.len() as u32, "round")
+        ),
+    };
+    write!(
+        summary,
+        ", started {}: {} in all.",
+        started(record),
+        plural(games as u32, "game")
+    )
+    .unwrap();
+    if let Some(champion) = record
+        .bracket
+        .as_ref()
+        .and_then(|bracket| bracket.placements.iter().find(|p| p.place == 1))
+    {
+        write!(summary, " Won by {}.", champion.fleet).unwrap();
+    }
+    summary
+}
+
+fn plural(count: u32, thing: &str) -> String {
+    match count {
+        1 => format!("1 {thing}"),
+        _ => format!("{count} {thing}s"),
+    }
+}
+
+/// The standings, with who entered each fleet when the tournament definition says.
+fn standings_table(standings: &[Standing], fleets: &[Entrant]) -> Table {
+    let author = |fleet: &str| {
+        fleets
+            .iter()
+            .find(|entrant| entrant.name == fleet)
+            .and_then(|entrant| entrant.author.clone())
+    };
+    let authors = fleets.iter().any(|entrant| entrant.author.is_some());
+    let buchholz = standings.iter().any(|standing| standing.buchholz.is_some());
+    let mut headers = vec!["Rank", "Fleet"];
+    if authors {
+        headers.push("Author");
+    }
+    headers.extend(["Played", "Won", "Lost", "Drawn", "Errors", "Points"]);
+    if buchholz {
+        headers.push("Buchholz");
+    }
+    headers.push("Win-rate");
+
+    let mut table = Table::new(&headers);
+    for (i, standing) in standings.iter().enumerate() {
+        let mut row = vec![
+            Cell::Number((i + 1).to_string()),
+            Cell::Text(standing.fleet.clone()),
+        ];
+        if authors {
+            row.push(Cell::Text(author(&standing.fleet).unwrap_or_default()));
+        }
+        row.extend(
+            [
+                standing.played,
+                standing.wins,
+                standing.losses,
+                standing.draws,
+                standing.errors,
+                standing.points,
+            ]
+            .map(|n| Cell::Number(n.to_string())),
+        );
+        if buchholz {
+            row.push(Cell::Number(
+                standing.buchholz.unwrap_or_default().to_string(),
+            ));
+        }
+        row.push(Cell::Number(
+            standing
+                .win_rate()
+                .map_or("unknown".to_owned(), |rate| format!("{:.1}%", rate * 100.0)),
+        ));
+        table.rows.push(row);
+    }
+    table
+}
+
+fn placings_table(placements: &[Placement]) -> Table {
+    let mut table = Table::new(&["Place", "Fleet"]);
+    for placement in placements {
+        table.rows.push(vec![
+            Cell::Number(placement.place.to_string()),
+            Cell::Text(placement.fleet.clone()),
+        ]);
+    }
+    table
+}
+
+/// A row per fleet and a column per opponent, with the row's wins, losses and draws against it.
+fn grid_table(record: &TournamentRecord) -> Table {
+    let names: Vec<&str> = record
+        .fleets
+        .iter()
+        .map(|fleet| fleet.name.as_str())
+        .collect();
+    let mut headers = vec![""];
+    headers.extend(&names);
+    let mut table = Table::new(&headers);
+
+    let games: Vec<_> = record.games().collect();
+    for &row in &names {
+        let mut cells = vec![Cell::Text(row.to_owned())];
+        for &column in &names {
+            if row == column {
+                cells.push(Cell::Text("-".to_owned()));
+                continue;
+            }
+            let (mut won, mut lost, mut drawn, mut met) = (0, 0, 0, false);
+            for &(a, b, outcome) in &games {
+                let as_a = (a, b) == (row, column);
+                if !as_a && (a, b) != (column, row) {
+                    continue;
+                }
+                met = true;
+                match outcome {
+                    Some(Outcome::FleetAWon) if as_a => won += 1,
+                    Some(Outcome::FleetBWon) if !as_a => won += 1,
+                    Some(Outcome::FleetAWon | Outcome::FleetBWon) => {
+                        lost += 1;
+                    }
+                    Some(Outcome::Draw) => drawn += 1,
+                    _ => {}
+                }
+            }
+            cells.push(Cell::Number(if met {
+                format!("{won}-{lost}-{drawn}")
+            } else {
+                String::new()
+            }));
+        }
+        table.rows.push(cells);
+    }
+    table
+}
+
+/// The cells for a game's fleets, result, duration and replay.
+fn game_cells(game: &ReportGame, report_dir: &Path) -> Vec<Cell> {
+    match (game.record, game.error) {
+        (Some(record), _) => vec![
+            Cell::Text(record.fleet_a.clone()),
+            Cell::Text(record.fleet_b.clone()),
+            Cell::Text(winner(record)),
+            Cell::Number(format_duration(Duration::from_secs_f64(
+                record.duration_secs,
+            ))),
+            Cell::Link {
+                text: record
+                    .replay
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+                href: relative_link(report_dir, &record.replay),
+            },
+        ],
+        (None, error) => vec![
+            Cell::Text(String::new()),
+            Cell::Text(String::new()),
+            Cell::Text(format!("failed: {}", error.unwrap_or("not played"))),
+            Cell::Text(String::new()),
+            Cell::Text(String::new()),
+        ],
+    }
+}
+
+fn extremes_table(games: &[ReportGame], report_dir: &Path) -> Table {
+    let mut table = Table::new(&[
+        "", "Game", "Fleet A", "Fleet B", "Winner", "Duration", "Replay",
+    ]);
+    let played: Vec<&ReportGame> = games.iter().filter(|game| game.record.is_some()).collect();
+    let duration = |game: &&ReportGame| game.record.map_or(0.0, |record| record.duration_secs);
+    let longest = played
+        .iter()
+        .max_by(|a, b| duration(a).total_cmp(&duration(b)));
+    let shortest = played
+        .iter()
+        .min_by(|a, b| duration(a).total_cmp(&duration(b)));
+
+    for (name, game) in [("Longest", longest), ("Shortest", shortest)] {
+        if let Some(game) = game {
+            let mut row = vec![
+                Cell::Text(name.to_owned()),
+                Cell::Text(format!("{} game {}", game.matchup, game.game)),
+            ];
+            row.extend(game_cells(game, report_dir));
+            table.rows.push(row);
+        }
+    }
+    table
+}
+
+fn games_table(games: &[ReportGame], report_dir: &Path) -> Table {
+    let mut table = Table::new(&[
+        "Matchup", "Game", "Fleet A", "Fleet B", "Winner", "Duration", "Replay",
+    ]);
+    for game in games {
+        let mut row = vec![
+            Cell::Text(game.matchup.clone()),
+            Cell::Number(game.game.to_string()),
+        ];
+        row.extend(game_cells(game, report_dir));
+        table.rows.push(row);
+    }
+    table
+}
+
+/// Renders the report, with replay links relative to `report_dir`, where it's going.
+fn render_report(
+    record: &TournamentRecord,
+    format: TournamentReportFormat,
+    report_dir: &Path,
+) -> String {
+    let games: Vec<ReportGame> = record
+        .matchups
+        .iter()
+        .flat_map(|matchup| {
+            matchup.games.iter().map(|game| ReportGame {
+                matchup: format!("{} vs {}", matchup.fleet_a, matchup.fleet_b),
+                game: game.game,
+                record: game.result.as_ref(),
+                error: game.error.as_deref(),
+            })
+        })
+        .collect();
+
+    let title = match &record.label {
+        Some(label) => format!("Tournament: {label}"),
+        None => format!("Tournament of {}", started(record)),
+    };
+    let placings = match &record.bracket {
+        Some(bracket) if !bracket.placements.is_empty() => {
+            let table = placings_table(&bracket.placements).render(format);
+            match format {
+                TournamentReportFormat::Html => format!("<h2>Placings</h2>\n{table}"),
+                TournamentReportFormat::Markdown => format!("\n## Placings\n\n{table}\n"),
+            }
+        }
+        _ => String::new(),
+    };
+    let escape = match format {
+        TournamentReportFormat::Html => escape_html,
+        TournamentReportFormat::Markdown => escape_markdown,
+    };
+    let vars = [
+        ("title", escape(&title)),
+        ("summary", escape(&summary(record, games.len()))),
+        (
+            "standings",
+            standings_table(&record.standings, &record.fleets).render(format),
+        ),
+        ("placings", placings),
+        ("grid", grid_table(record).render(format)),
+        (
+            "extremes",
+            extremes_table(&games, report_dir).render(format),
+        ),
+        ("games", games_table(&games, report_dir).render(format)),
+    ];
+    let vars: Vec<(&str, &str)> = vars
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .collect();
+
+    let template = match format {
+        TournamentReportFormat::Html => HTML_TEMPLATE,
+        TournamentReportFormat::Markdown => MARKDOWN_TEMPLATE,
+    };
+    String::from_utf8_lossy(&render(template.as_bytes().to_vec(), &vars)).into_owned()
+}
+
+/// Writes a report of the tournament to `path`, as HTML or markdown by its extension.
+pub fn write_report(record: &TournamentRecord, path: &Path) -> anyhow::Result<()> {
+    let format = TournamentReportFormat::from_path(path)?;
+    let path = std::path::absolute(path).with_context(|| format!("trying to find {path:?}"))?;
+    let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+    std::fs::create_dir_all(&dir).with_context(|| format!("trying to create {dir:?}"))?;
+    std::fs::write(&path, render_report(record, format, &dir))
+        .with_context(|| format!("trying to write the tournament report to {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{relative_link, render_report, TournamentReportFormat};
+    use crate::outcome::Outcome;
+    use crate::results::BattleRecord;
+    use crate::tournament::{
+        standings, Entrant, Matchup, TournamentFormat, TournamentGame, TournamentRecord,
+    };
+
+    fn record() -> TournamentRecord {
+        let game = |game, a: &str, b: &str, outcome, duration_secs| TournamentGame {
+            game,
+            result: Some(BattleRecord {
+                fleet_a: a.to_owned(),
+                fleet_b: b.to_owned(),
+                outcome,
+                winner: BattleRecord::winner_name(outcome, a, b),
+                duration_secs,
+                replay: PathBuf::from(format!(
+                    "/events/june/{a}_vs_{b}_tournament_game{game}.json.deflate"
+                )),
+                ..BattleRecord::default()
+            }),
+            error: None,
+        };
+        let matchups = vec![
+            Matchup {
+                fleet_a: "glass_cannon".to_owned(),
+                fleet_b: "iron_armada".to_owned(),
+                games: vec![game(
+                    1,
+                    "glass_cannon",
+                    "iron_armada",
+                    Outcome::FleetAWon,
+                    83.0,
+                )],
+            },
+            Matchup {
+                fleet_a: "iron_armada".to_owned(),
+                fleet_b: "glass_cannon".to_owned(),
+                games: vec![
+                    game(1, "iron_armada", "glass_cannon", Outcome::Draw, 300.0),
+                    TournamentGame {
+                        game: 2,
+                        result: None,
+                        error: Some("sim not found".to_owned()),
+                    },
+                ],
+            },
+        ];
+        let mut record = TournamentRecord {
+            timestamp: 1718476403,
+            format: TournamentFormat::RoundRobin,
+            fleets: ["glass_cannon", "iron_armada"]
+                .map(|name| Entrant {
+                    name: name.to_owned(),
+                    sha256: "1a2b3c4d".to_owned(),
+                    author: (name == "iron_armada").then(|| "Ann".to_owned()),
+                    ..Entrant::default()
+                })
+                .to_vec(),
+            games: 2,
+            label: Some("June <cup>".to_owned()),
+            resumed: false,
+            standings: Vec::new(),
+            matchups,
+            bracket: None,
+            rounds: Vec::new(),
+        };
+        let names = ["glass_cannon", "iron_armada"].map(str::to_owned);
+        record.standings = standings(&names, record.games());
+        record
+    }
+
+    /// Compares a report with its snapshot in `testdata`, rewriting the snapshot instead with
+    /// `UPDATE_SNAPSHOTS=1`.
+    fn assert_snapshot(name: &str, rendered: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/testdata")
+            .join(name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, rendered).unwrap();
+        }
+        let snapshot = std::fs::read_to_string(&path).unwrap_or_default();
+        assert_eq!(
+            rendered, snapshot,
+            "{name} has changed, rerun with UPDATE_SNAPSHOTS=1 if that's on purpose"
+        );
+    }
+
+    #[test]
+    fn markdown_report_matches_its_snapshot() {
+        let report = render_report(
+            &record(),
+            TournamentReportFormat::Markdown,
+            Path::new("/events/june/reports"),
+        );
+        assert_snapshot("tournament_report.md", &report);
+    }
+
+    #[test]
+    fn html_report_matches_its_snapshot() {
+        let report = render_report(
+            &record(),
+            TournamentReportFormat::Html,
+            Path::new("/events/june"),
+        );
+        assert!(!report.contains("{{"));
+        assert_snapshot("tournament_report.html", &report);
+    }
+
+    #[test]
+    fn replay_links_are_relative_to_the_report() {
+        let replay = Path::new("/events/june/a_vs_b.json.deflate");
+        assert_eq!(
+            relative_link(Path::new("/events/june"), replay),
+            "a_vs_b.json.deflate"
+        );
+        assert_eq!(
+            relative_link(Path::new("/events/reports/june"), replay),
+            "../../june/a_vs_b.json.deflate"
+        );
+        assert!(TournamentReportFormat::from_path(Path::new("report.txt")).is_err());
+        assert_eq!(
+            TournamentReportFormat::from_path(Path::new("out/report.md")).unwrap(),
+            TournamentReportFormat::Markdown
+        );
+    }
+}
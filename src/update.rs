@@ -0,0 +1,121 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::install::{default_releases_dir, install_release, installed_version, latest_release};
+use crate::output::say;
+use crate::sim::{find_protologic_path, protologic_sim_path};
+use crate::version::{compare_versions, parse_version, release_version};
+
+/// The Release in use, as far as it can be found.
+struct CurrentRelease {
+    path: PathBuf,
+    version: Option<String>,
+    /// Whether `install` put it there, rather than it being a checkout of someone's own.
+    managed: bool,
+}
+
+/// The Release `run` would use and its version: what `install` recorded for installed Releases,
+/// and a guess from the `VERSION` file (or the sim's `--version`) for checkouts.
+fn current_release(explicit: Option<PathBuf>) -> Option<CurrentRelease> {
+    let path = match explicit {
+        Some(path) => path,
+        None => find_protologic_path(None).ok()?,
+    };
+    if let Some(tag) = installed_version(&path) {
+        return Some(CurrentRelease {
+            version: Some(parse_version(&tag).unwrap_or(tag)),
+            path,
+            managed: true,
+        });
+    }
+    let sim = protologic_sim_path(&path);
+    Some(CurrentRelease {
+        version: release_version(&path, &sim),
+        path,
+        managed: false,
+    })
+}
+
+/// Checks GitHub for a newer Protologic Release than the one in use, and unless `check` installs it
+/// (under `path`, or next to the installed one) and switches to it. Not being able to reach GitHub
+/// is only a warning, so this can run offline.
+pub fn update(
+    check: bool,
+    protologic_path: Option<PathBuf>,
+    path: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let current = current_release(protologic_path);
+    let latest = match latest_release() {
+        Ok(latest) => latest,
+        Err(err) => {
+            say!("Warning: couldn't check for a newer Protologic Release: {err:#}");
+            return Ok(());
+        }
+    };
+    let latest_version = parse_version(&latest.tag_name).unwrap_or_else(|| latest.tag_name.clone());
+
+    match &current {
+        None => say!("{latest_version} available, and you don't have a Protologic Release yet"),
+        Some(CurrentRelease {
+            version: Some(version),
+            ..
+        }) => match compare_versions(version, &latest_version) {
+            Some(Ordering::Less) => say!("{latest_version} available, you have {version}"),
+            Some(_) => {
+                say!("You have the latest Protologic Release, {version}");
+                return Ok(());
+            }
+            None if *version == latest_version => {
+                say!("You have the latest Protologic Release, {version}");
+                return Ok(());
+            }
+            None => {
+                say!("{latest_version} is the latest, and you have {version}, which can't be compared with it");
+                if !check {
+                    say!("Install it with `cargo protologic install` if it's newer");
+                }
+                return Ok(());
+            }
+        },
+        Some(CurrentRelease {
+            path,
+            version: None,
+            ..
+        }) => {
+            say!(
+                "{latest_version} is the latest, but there's no telling which version {} is. Checkouts need a VERSION file, or a sim that supports `--version`",
+                path.display()
+            );
+            if !check {
+                say!("Install it with `cargo protologic install` if it's newer");
+            }
+            return Ok(());
+        }
+    }
+    if check {
+        return Ok(());
+    }
+
+    let root = match (path, &current) {
+        (Some(path), _) => std::path::absolute(&path)
+            .with_context(|| format!("trying to make {path:?} absolute"))?,
+        (None, Some(current)) if current.managed => match current.path.parent() {
+            Some(parent) => parent.to_owned(),
+            None => default_releases_dir()?,
+        },
+        (None, current) => {
+            if let Some(current) = current {
+                say!(
+                    "{} is a checkout of your own, so leaving it be and installing {} with the other installed Releases. Switch back with `cargo protologic config set protologic-path {}`",
+                    current.path.display(),
+                    latest.tag_name,
+                    current.path.display()
+                );
+            }
+            default_releases_dir()?
+        }
+    };
+    install_release(&latest, &root)
+}
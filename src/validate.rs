@@ -0,0 +1,403 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Serialize;
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef, Validator};
+
+use crate::output::say;
+use crate::preflight::ENTRY_POINTS;
+use crate::settings::settings;
+use crate::wasm::SIM_FEATURES;
+
+/// The modules fleets can import from: WASI, and the sim's own API.
+pub const ALLOWED_IMPORT_MODULES: &[&str] = &["wasi_snapshot_preview1", "protologic"];
+/// What the Asyncify pass exports, which the sim uses to pause fleets between ticks.
+const ASYNCIFY_EXPORTS: &[&str] = &[
+    "asyncify_start_unwind",
+    "asyncify_stop_unwind",
+    "asyncify_start_rewind",
+    "asyncify_stop_rewind",
+    "asyncify_get_state",
+];
+const WASM_PAGE_BYTES: u64 = 65536;
+
+/// What kind of problem a fleet has.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// It isn't a wasm module the sim can load.
+    Invalid,
+    Exports,
+    Imports,
+    /// It wasn't optimized with Asyncify, so the sim can't pause it.
+    Asyncify,
+    Memory,
+    /// It's bigger than the size cap.
+    Size,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Invalid => "invalid",
+            Self::Exports => "exports",
+            Self::Imports => "imports",
+            Self::Asyncify => "asyncify",
+            Self::Memory => "memory",
+            Self::Size => "size",
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub category: Category,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(category: Category, message: impl Into<String>) -> Self {
+        Self {
+            category,
+            message: message.into(),
+        }
+    }
+}
+
+/// The memory a fleet declares, in 64 KiB wasm pages.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Memory {
+    pub initial_pages: u64,
+    pub maximum_pages: Option<u64>,
+}
+
+/// What validating one fleet found.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub path: PathBuf,
+    pub size: u64,
+    pub memory: Option<Memory>,
+    pub problems: Vec<Problem>,
+}
+
+/// Checks a fleet's wasm the way the sim will see it: that it validates with the sim's features,
+/// starts and exports its memory, only imports what the sim provides, has been through Asyncify,
+/// and is no bigger than `max_size`.
+pub fn check_wasm(bytes: &[u8], max_size: Option<u64>) -> (Option<Memory>, Vec<Problem>) {
+    let mut problems = Vec::new();
+    if let Some(max_size) = max_size {
+        let size = bytes.len() as u64;
+        if size > max_size {
+            problems.push(Problem::new(
+                Category::Size,
+                format!(
+                    "it's {}, over the {} cap",
+                    ByteSize::b(size),
+                    ByteSize::b(max_size)
+                ),
+            ));
+        }
+    }
+    if !Parser::is_core_wasm(bytes) {
+        problems.push(Problem::new(
+            Category::Invalid,
+            "it's missing the wasm module header",
+        ));
+        return (None, problems);
+    }
+    if let Err(err) = Validator::new_with_features(SIM_FEATURES).validate_all(bytes) {
+        problems.push(Problem::new(
+            Category::Invalid,
+            format!("it doesn't validate with the sim's wasm features: {err}"),
+        ));
+        return (None, problems);
+    }
+
+    let mut memories = Vec::new();
+    let mut exports = Vec::new();
+    for payload in Parser::new(0).parse_all(bytes) {
+        // It validated, so it parses
+        let Ok(payload) = payload else { break };
+        match payload {
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports().flatten() {
+                    let name = format!("{}.{}", import.module, import.name);
+                    match import.ty {
+                        TypeRef::Func(_) if ALLOWED_IMPORT_MODULES.contains(&import.module) => {}
+                        TypeRef::Func(_) => problems.push(Problem::new(
+                            Category::Imports,
+                            format!(
+                                "it imports {name}, but the sim only provides {}",
+                                ALLOWED_IMPORT_MODULES.join(" and ")
+                            ),
+                        )),
+                        TypeRef::Memory(memory) => {
+                            problems.push(Problem::new(
+                                Category::Imports,
+                                format!(
+                                    "it imports its memory as {name}, but has to define its own"
+                                ),
+                            ));
+                            memories.push(memory);
+                        }
+                        _ => problems.push(Problem::new(
+                            Category::Imports,
+                            format!("it imports {name}, but the sim only provides functions"),
+                        )),
+                    }
+                }
+            }
+            Payload::MemorySection(reader) => memories.extend(reader.into_iter().flatten()),
+            Payload::ExportSection(reader) => {
+                exports.extend(
+                    reader
+                        .into_iter()
+                        .flatten()
+                        .map(|export| (export.name, export.kind)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let exported = |name: &str, kind: ExternalKind| exports.contains(&(name, kind));
+    if !ENTRY_POINTS
+        .iter()
+        .any(|name| exported(name, ExternalKind::Func))
+    {
+        problems.push(Problem::new(
+            Category::Exports,
+            format!(
+                "it doesn't export an entry point, one of {}",
+                ENTRY_POINTS.join(" or ")
+            ),
+        ));
+    }
+    if !exported("memory", ExternalKind::Memory) {
+        problems.push(Problem::new(
+            Category::Exports,
+            "it doesn't export its memory as `memory`",
+        ));
+    }
+    let missing: Vec<&str> = ASYNCIFY_EXPORTS
+        .iter()
+        .copied()
+        .filter(|name| !exported(name, ExternalKind::Func))
+        .collect();
+    if !missing.is_empty() {
+        problems.push(Problem::new(
+            Category::Asyncify,
+            format!(
+                "it's missing {}, so it hasn't been through Asyncify. `cargo protologic build` does that",
+                missing.join(", ")
+            ),
+        ));
+    }
+
+    let memory = memories.first().map(|memory| Memory {
+        initial_pages: memory.initial,
+        maximum_pages: memory.maximum,
+    });
+    for memory in &memories {
+        if memory.memory64 || memory.shared {
+            problems.push(Problem::new(
+                Category::Memory,
+                "its memory is 64-bit or shared, which the sim doesn't support",
+            ));
+        }
+    }
+    if memories.len() > 1 {
+        problems.push(Problem::new(
+            Category::Memory,
+            format!(
+                "it has {} memories, the sim only gives fleets one",
+                memories.len()
+            ),
+        ));
+    }
+    (memory, problems)
+}
+
+/// The wasm files the paths name: files as they are, and the `.wasm` files in directories.
+fn wasm_files(paths: Vec<PathBuf>) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(&path).with_context(|| format!("trying to read {path:?}"))? {
+            let entry = entry.with_context(|| format!("trying to read an entry of {path:?}"))?;
+            let file = entry.path();
+            if file.extension().is_some_and(|ext| ext == "wasm") && file.is_file() {
+                found.push(file);
+            }
+        }
+        if found.is_empty() {
+            say!("Warning: there are no .wasm files in {}", path.display());
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+fn describe_memory(memory: Option<Memory>) -> String {
+    let pages = |pages: u64| format!("{pages} pages ({})", ByteSize::b(pages * WASM_PAGE_BYTES));
+    match memory {
+        None => "no memory".to_owned(),
+        Some(Memory {
+            initial_pages,
+            maximum_pages: Some(maximum),
+        }) => format!(
+            "memory of {} up to {}",
+            pages(initial_pages),
+            pages(maximum)
+        ),
+        Some(Memory {
+            initial_pages,
+            maximum_pages: None,
+        }) => format!("memory of {} with no maximum", pages(initial_pages)),
+    }
+}
+
+/// Validates fleet wasm files without building anything, printing what's wrong with each, or with
+/// `json` a report of them all on stdout. Fails if any of them have problems.
+pub fn validate(paths: Vec<PathBuf>, max_size: Option<ByteSize>, json: bool) -> anyhow::Result<()> {
+    let max_size = settings()?
+        .size("package.max-size", max_size)
+        .map(|size| size.as_u64());
+    let mut reports = Vec::new();
+    for path in wasm_files(paths)? {
+        let report = match std::fs::read(&path) {
+            Ok(bytes) => {
+                let (memory, problems) = check_wasm(&bytes, max_size);
+                Report {
+                    size: bytes.len() as u64,
+                    memory,
+                    problems,
+                    path,
+                }
+            }
+            Err(err) => Report {
+                problems: vec![Problem::new(
+                    Category::Invalid,
+                    format!("it can't be read: {err}"),
+                )],
+                size: 0,
+                memory: None,
+                path,
+            },
+        };
+        reports.push(report);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            let size = match max_size {
+                Some(max_size) => {
+                    format!("{} of {}", ByteSize::b(report.size), ByteSize::b(max_size))
+                }
+                None => ByteSize::b(report.size).to_string(),
+            };
+            let mark = if report.problems.is_empty() {
+                '✓'
+            } else {
+                '✗'
+            };
+            say!(
+                "{mark} {}: {size}, {}",
+                report.path.display(),
+                describe_memory(report.memory)
+            );
+            for problem in &report.problems {
+                say!("    {}: {}", problem.category, problem.message);
+            }
+        }
+    }
+
+    let failed = reports
+        .iter()
+        .filter(|report| !report.problems.is_empty())
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} fleets have problems", reports.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_wasm, Category, Memory};
+
+    const ASYNCIFIED: &str = r#"
+        (func (export "asyncify_start_unwind") (param i32))
+        (func (export "asyncify_stop_unwind"))
+        (func (export "asyncify_start_rewind") (param i32))
+        (func (export "asyncify_stop_rewind"))
+        (func (export "asyncify_get_state") (result i32) (i32.const 0))"#;
+
+    fn categories(wat: &str, max_size: Option<u64>) -> anyhow::Result<Vec<Category>> {
+        let (_, problems) = check_wasm(&wat::parse_str(wat)?, max_size);
+        Ok(problems.iter().map(|problem| problem.category).collect())
+    }
+
+    #[test]
+    fn check_wasm_passes_a_built_fleet() -> anyhow::Result<()> {
+        let wat = format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "sched_yield" (func (result i32)))
+                (import "protologic" "engine_set_throttle" (func (param f32)))
+                (memory (export "memory") 17 256)
+                (func (export "_start"))
+                {ASYNCIFIED})"#
+        );
+        let (memory, problems) = check_wasm(&wat::parse_str(&wat)?, None);
+        assert_eq!(problems, []);
+        assert_eq!(
+            memory,
+            Some(Memory {
+                initial_pages: 17,
+                maximum_pages: Some(256)
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn check_wasm_sorts_problems_into_categories() -> anyhow::Result<()> {
+        assert_eq!(
+            categories(
+                r#"(module
+                    (import "env" "cheat" (func))
+                    (memory 1)
+                    (func (export "main")))"#,
+                Some(16)
+            )?,
+            [
+                Category::Size,
+                Category::Imports,
+                Category::Exports,
+                Category::Asyncify
+            ]
+        );
+        assert_eq!(
+            categories(
+                &format!(r#"(module (memory (export "memory") 1) {ASYNCIFIED})"#),
+                None
+            )?,
+            [Category::Exports]
+        );
+        assert_eq!(
+            check_wasm(b"not a wasm file", None).1[0].category,
+            Category::Invalid
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,508 @@
+//! Checking a replay reads all the way through before it's shared or opened, and salvaging what
+//! can be read of one that doesn't.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use flate2::bufread::{DeflateDecoder, ZlibDecoder};
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::output::say;
+use crate::replay::is_zlib_header;
+
+/// Arguments for `cargo protologic verify-replay`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct VerifyReplayArgs {
+    /// The replay to check, a `.json.deflate` as the sim writes them or a decompressed `.json`.
+    replay: PathBuf,
+    /// When it's broken, write what can be read of it to a new replay, ending it after the last whole event.
+    #[arg(long, default_value = "false")]
+    repair: bool,
+    /// Where `--repair` writes the new replay, if not `<name>.repaired.json.deflate` next to it. One ending in `.json` isn't compressed.
+    #[arg(long, value_name = "PATH", requires = "repair")]
+    repair_output: Option<PathBuf>,
+}
+
+/// A replay's file, inflated as it's read, keeping count of how far into the file that's got.
+enum ReplayReader {
+    Json(BufReader<File>),
+    Deflate(DeflateDecoder<BufReader<File>>),
+    Zlib(ZlibDecoder<BufReader<File>>),
+}
+
+impl ReplayReader {
+    fn open(replay: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(replay).with_context(|| format!("trying to open replay {replay:?}"))?;
+        let mut input = BufReader::new(file);
+        if replay
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            return Ok(Self::Json(input));
+        }
+        let header = input
+            .fill_buf()
+            .with_context(|| format!("trying to read replay {replay:?}"))?;
+        Ok(if is_zlib_header(header) {
+            Self::Zlib(ZlibDecoder::new(input))
+        } else {
+            Self::Deflate(DeflateDecoder::new(input))
+        })
+    }
+
+    /// How many bytes of a compressed file have been inflated.
+    fn compressed_offset(&self) -> Option<u64> {
+        match self {
+            Self::Json(_) => None,
+            Self::Deflate(decoder) => Some(decoder.total_in()),
+            Self::Zlib(decoder) => Some(decoder.total_in()),
+        }
+    }
+}
+
+impl Read for ReplayReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Json(reader) => reader.read(buf),
+            Self::Deflate(decoder) => decoder.read(buf),
+            Self::Zlib(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/// Where the JSON read so far could be cut off and closed up into a whole replay.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CutPoint {
+    /// How many bytes of the JSON to keep.
+    pub offset: u64,
+    /// What closes the objects and arrays still open there.
+    pub closing: String,
+}
+
+/// Follows the JSON's nesting byte by byte as it's read, noting the last place a value inside an
+/// object or array ended, which is where a broken replay can be cut off.
+#[derive(Debug, Default)]
+struct Scanner {
+    offset: u64,
+    open: Vec<u8>,
+    in_string: bool,
+    escaped: bool,
+    cut: Option<CutPoint>,
+    /// The cut point before `cut`, for when the byte that ended `cut` is the one that broke.
+    previous_cut: Option<CutPoint>,
+}
+
+impl Scanner {
+    fn scan(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.offset += 1;
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.open.push(byte),
+                b'}' | b']' => {
+                    self.open.pop();
+                    // Only ends of events, or things in the replay's top level, not things in an
+                    // event, so it's cut after a whole one
+                    if self.open.len() > 2 {
+                        continue;
+                    }
+                    let closing = self
+                        .open
+                        .iter()
+                        .rev()
+                        .map(|&open| if open == b'{' { '}' } else { ']' })
+                        .collect();
+                    self.previous_cut = self.cut.replace(CutPoint {
+                        offset: self.offset,
+                        closing,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The last cut point before the byte at `offset`.
+    fn cut_before(&self, offset: u64) -> Option<CutPoint> {
+        [&self.cut, &self.previous_cut]
+            .into_iter()
+            .flatten()
+            .find(|cut| cut.offset < offset)
+            .cloned()
+    }
+}
+
+/// Hands the JSON to the parser while the scanner follows along.
+struct Scanned<R> {
+    inner: R,
+    scanner: Scanner,
+}
+
+impl<R: Read> Read for Scanned<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.scanner.scan(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// What a replay says about itself, as far as it's been read.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReplaySummary {
+    pub fleets: Vec<String>,
+    /// The ticks the replay says it has.
+    pub declared_ticks: Option<f64>,
+    /// The last tick an event happened on.
+    pub last_tick: Option<f64>,
+    pub sim_version: Option<String>,
+    pub events: u64,
+}
+
+impl ReplaySummary {
+    /// Picks up what the replay says about itself from a top-level field, under the few names
+    /// sims have used for each. A number `version` is the replay format's, not the sim's.
+    fn add_field(&mut self, key: &str, value: serde_json::Value) {
+        match (key, value) {
+            ("fleets" | "teams" | "players", serde_json::Value::Array(fleets)) => {
+                self.fleets = fleets
+                    .iter()
+                    .filter_map(|fleet| fleet.as_str().or_else(|| fleet.get("name")?.as_str()))
+                    .map(ToOwned::to_owned)
+                    .collect();
+            }
+            ("sim_version" | "simVersion", serde_json::Value::String(version)) => {
+                self.sim_version = Some(version);
+            }
+            ("version", serde_json::Value::String(version)) if self.sim_version.is_none() => {
+                self.sim_version = Some(version);
+            }
+            ("ticks" | "tick_count" | "tickCount", value) => self.declared_ticks = value.as_f64(),
+            _ => {}
+        }
+    }
+
+    fn add_event(&mut self, event: &serde_json::Value) {
+        self.events += 1;
+        let tick = ["tick", "time", "t"]
+            .into_iter()
+            .find_map(|key| event.get(key)?.as_f64());
+        if let Some(tick) = tick {
+            self.last_tick = Some(self.last_tick.map_or(tick, |last| last.max(tick)));
+        }
+    }
+
+    fn ticks(&self) -> Option<f64> {
+        self.declared_ticks.or(self.last_tick)
+    }
+}
+
+/// Goes through a replay's top level, keeping what it says about itself and counting its events.
+struct SummaryVisitor<'a>(&'a mut ReplaySummary);
+
+impl<'de> Visitor<'de> for SummaryVisitor<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a replay object, or an array of events")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(event) = seq.next_element::<serde_json::Value>()? {
+            self.0.add_event(&event);
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "events" {
+                map.next_value_seed(SummaryVisitor(&mut *self.0))?;
+            } else {
+                let value = map.next_value::<serde_json::Value>()?;
+                self.0.add_field(&key, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for SummaryVisitor<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+/// Where and how a replay broke.
+#[derive(Debug)]
+pub struct Corruption {
+    /// How many bytes of the JSON were read before it broke.
+    pub json_offset: u64,
+    /// How far into the file that was, for a compressed replay.
+    pub file_offset: Option<u64>,
+    /// Whether the replay just ends, like a download that didn't finish.
+    pub truncated: bool,
+    pub problem: String,
+    /// Where it could be cut off to salvage what came before.
+    pub cut: Option<CutPoint>,
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = match self.file_offset {
+            Some(offset) => format!(
+                "around byte {offset} of the file, byte {} of its JSON",
+                self.json_offset
+            ),
+            None => format!("at byte {}", self.json_offset),
+        };
+        if self.truncated {
+            write!(f, "the replay ends early, {at}, so it looks cut short")
+        } else {
+            write!(f, "the replay is corrupt {at}: {}", self.problem)
+        }
+    }
+}
+
+/// Reads a whole replay, inflating it and parsing its JSON to the end, and sums up what it says
+/// about itself. A replay that doesn't read all the way through gives where it broke.
+pub fn verify(replay: &Path) -> anyhow::Result<(ReplaySummary, Result<u64, Corruption>)> {
+    // Buffered underneath the scanner, so it sees exactly what the parser has read
+    let mut input = Scanned {
+        inner: BufReader::new(ReplayReader::open(replay)?),
+        scanner: Scanner::default(),
+    };
+    let mut summary = ReplaySummary::default();
+    let mut deserializer = serde_json::Deserializer::from_reader(&mut input);
+    let result = deserializer
+        .deserialize_any(SummaryVisitor(&mut summary))
+        .and_then(|()| deserializer.end());
+
+    let json_offset = input.scanner.offset;
+    let Err(err) = result else {
+        return Ok((summary, Ok(json_offset)));
+    };
+    // Nothing broken was read when the JSON or the compressed data just stops
+    let read_cleanly = err.is_eof() || err.is_io();
+    let (truncated, problem) = if err.is_io() {
+        let err = std::io::Error::from(err);
+        let truncated = err.kind() == std::io::ErrorKind::UnexpectedEof
+            || err.to_string().contains("incomplete");
+        (
+            truncated,
+            format!("its compressed data doesn't inflate: {err}"),
+        )
+    } else {
+        (err.is_eof(), err.to_string())
+    };
+    let corruption = Corruption {
+        json_offset,
+        file_offset: input.inner.get_ref().compressed_offset(),
+        truncated,
+        problem,
+        cut: input
+            .scanner
+            .cut_before(json_offset + u64::from(read_cleanly)),
+    };
+    Ok((summary, Err(corruption)))
+}
+
+/// Writes the first `cut.offset` bytes of the replay's JSON to `output`, closed up with
+/// `cut.closing`, compressed unless `output` is a `.json`.
+fn write_repaired(replay: &Path, cut: &CutPoint, output: &Path) -> anyhow::Result<()> {
+    let mut input = ReplayReader::open(replay)?.take(cut.offset);
+    let file = File::create(output).with_context(|| format!("trying to create {output:?}"))?;
+    let mut writer: Box<dyn Write> = if output.extension().is_some_and(|ext| ext == "json") {
+        Box::new(BufWriter::new(file))
+    } else {
+        Box::new(flate2::write::DeflateEncoder::new(
+            BufWriter::new(file),
+            flate2::Compression::default(),
+        ))
+    };
+    let copied = std::io::copy(&mut input, &mut writer);
+    // What was read before is read the same way again, unless the file changed in between
+    if !matches!(copied, Ok(copied) if copied == cut.offset) {
+        anyhow::bail!(
+            "couldn't read the first {} bytes of {replay:?} again",
+            cut.offset
+        );
+    }
+    writer.write_all(cut.closing.as_bytes())?;
+    writer
+        .flush()
+        .with_context(|| format!("trying to write {output:?}"))?;
+    Ok(())
+}
+
+/// Where `--repair` writes to without `--repair-output`: next to the replay, as
+/// `<name>.repaired.json.deflate`, or `.json` for one that wasn't compressed.
+fn repaired_path(replay: &Path) -> PathBuf {
+    let name = replay.file_name().unwrap_or_default().to_string_lossy();
+    let (base, extension) = match name.strip_suffix(".json.deflate") {
+        Some(base) => (base, ".json.deflate"),
+        None => match name.strip_suffix(".json") {
+            Some(base) => (base, ".json"),
+            None => (&*name, ".json.deflate"),
+        },
+    };
+    replay.with_file_name(format!("{base}.repaired{extension}"))
+}
+
+fn print_summary(summary: &ReplaySummary) {
+    if !summary.fleets.is_empty() {
+        say!("  Fleets: {}", summary.fleets.join(" vs "));
+    }
+    match (summary.declared_ticks, summary.ticks()) {
+        (Some(ticks), _) => say!("  Ticks: {ticks}"),
+        (None, Some(ticks)) => say!("  Ticks: {ticks}, going by the last event"),
+        (None, None) => {}
+    }
+    if let Some(version) = &summary.sim_version {
+        say!("  Sim version: {version}");
+    }
+    say!("  Events: {}", summary.events);
+}
+
+/// Checks a replay reads all the way through, printing what it says about itself, and fails with
+/// where it broke if it doesn't. With `--repair`, what can be read of a broken one is written to a
+/// new replay.
+pub fn verify_replay(args: VerifyReplayArgs) -> anyhow::Result<()> {
+    let replay = &args.replay;
+    let size = std::fs::metadata(replay)
+        .with_context(|| format!("trying to read replay {replay:?}"))?
+        .len();
+    let (summary, result) = verify(replay)?;
+    let corruption = match result {
+        Ok(json_size) => {
+            say!(
+                "{} reads all the way through ({}, {} of JSON)",
+                replay.display(),
+                ByteSize::b(size),
+                ByteSize::b(json_size)
+            );
+            print_summary(&summary);
+            if args.repair {
+                say!("There's nothing to repair");
+            }
+            return Ok(());
+        }
+        Err(corruption) => corruption,
+    };
+
+    say!("{} is broken. Up to where it broke:", replay.display());
+    print_summary(&summary);
+    if !args.repair {
+        anyhow::bail!("{corruption}. `--repair` saves what can be read of it to a new replay");
+    }
+    let cut = corruption.cut.as_ref().with_context(|| {
+        format!("{corruption}, and it breaks before a single whole event, so there's nothing to salvage")
+    })?;
+    let output = args.repair_output.unwrap_or_else(|| repaired_path(replay));
+    write_repaired(replay, cut, &output)?;
+
+    let (salvaged, result) = verify(&output)?;
+    if let Err(err) = result {
+        anyhow::bail!("the repaired replay {output:?} doesn't read either: {err}");
+    }
+    say!(
+        "Warning: {corruption}. Salvaged the {} events before that into {}",
+        salvaged.events,
+        output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::{repaired_path, verify, write_repaired};
+
+    const REPLAY: &str = r#"{"version": 3, "sim_version": "1.4.2", "fleets": [{"name": "glass_cannon"}, "iron_armada"], "events": [{"type": "spawn", "tick": 0, "pos": {"x": 1}}, {"type": "hit", "tick": 40}, {"type": "battle_end", "tick": 95, "winner": 0}]}"#;
+
+    fn write_deflated(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(std::fs::File::create(path)?, Default::default());
+        encoder.write_all(bytes)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn whole_replays_are_summed_up() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replay = dir.path().join("a_vs_b.json.deflate");
+        write_deflated(&replay, REPLAY.as_bytes())?;
+
+        let (summary, result) = verify(&replay)?;
+        assert_eq!(result.unwrap(), REPLAY.len() as u64);
+        assert_eq!(summary.fleets, ["glass_cannon", "iron_armada"]);
+        assert_eq!(summary.sim_version.as_deref(), Some("1.4.2"));
+        assert_eq!((summary.events, summary.ticks()), (3, Some(95.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn broken_replays_say_where_and_are_salvaged() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let replay = dir.path().join("a_vs_b.json");
+        let second_event = REPLAY.find(r#"{"type": "hit""#).unwrap();
+        let broken = format!("{}#oops", &REPLAY[..second_event + 15]);
+        std::fs::write(&replay, &broken)?;
+
+        let (summary, result) = verify(&replay)?;
+        let corruption = result.unwrap_err();
+        assert!(!corruption.truncated);
+        assert_eq!(corruption.json_offset, second_event as u64 + 16);
+        assert!(corruption.to_string().starts_with(&format!(
+            "the replay is corrupt at byte {}",
+            second_event + 16
+        )));
+        assert_eq!(summary.events, 1);
+
+        let cut = corruption.cut.unwrap();
+        let repaired = repaired_path(&replay);
+        assert_eq!(repaired, dir.path().join("a_vs_b.repaired.json"));
+        write_repaired(&replay, &cut, &repaired)?;
+        let (salvaged, result) = verify(&repaired)?;
+        assert!(result.is_ok());
+        assert_eq!((salvaged.events, salvaged.ticks()), (1, Some(0.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_downloads_are_told_apart() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let whole = dir.path().join("whole.json.deflate");
+        write_deflated(&whole, REPLAY.as_bytes())?;
+        let bytes = std::fs::read(&whole)?;
+        let replay = dir.path().join("a_vs_b.json.deflate");
+        std::fs::write(&replay, &bytes[..bytes.len() * 3 / 4])?;
+
+        let corruption = verify(&replay)?.1.unwrap_err();
+        assert!(corruption.truncated, "{corruption}");
+        assert!(corruption.file_offset.is_some());
+        let repaired = repaired_path(&replay);
+        assert_eq!(repaired, dir.path().join("a_vs_b.repaired.json.deflate"));
+        write_repaired(&replay, &corruption.cut.unwrap(), &repaired)?;
+        assert!(verify(&repaired)?.1.is_ok());
+        Ok(())
+    }
+}
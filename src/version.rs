@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::fleet::Fleet;
+use crate::output::say;
+
+/// Names of the Protologic SDK crates fleets are built with. The SDK and the sim have to agree on
+/// the host functions fleets import, so a fleet built against the wrong SDK can't play properly.
+pub const SDK_PACKAGES: &[&str] = &["protologic_core", "protologic-core"];
+
+/// The version of a Protologic/Release checkout, from its `VERSION` file or the sim's `--version`.
+pub fn release_version(protologic_path: &Path, sim: &Path) -> Option<String> {
+    if let Ok(contents) = std::fs::read_to_string(protologic_path.join("VERSION")) {
+        return parse_version(&contents);
+    }
+
+    let output = Command::new(sim)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The first thing that looks like a version number, like `0.7.2` out of `Protologic v0.7.2`.
+pub fn parse_version(text: &str) -> Option<String> {
+    text.split(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')'))
+        .map(|word| word.strip_prefix(['v', 'V']).unwrap_or(word))
+        .find(|word| {
+            let parts: Vec<_> = word.split(['.', '-', '+']).collect();
+            parts.len() >= 2
+                && parts[..2]
+                    .iter()
+                    .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(ToOwned::to_owned)
+}
+
+/// The numbers of a version, without any pre-release or build suffix.
+fn version_numbers(version: &str) -> Option<Vec<u64>> {
+    version
+        .split(['-', '+'])
+        .next()?
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+/// Whether two versions are compatible by cargo's rules: everything up to and including the first
+/// non-zero number has to match, so `0.7.1` goes with `0.7.3` but not `0.8.0`. `None` when either
+/// isn't a version.
+fn compatible(a: &str, b: &str) -> Option<bool> {
+    let (a, b) = (version_numbers(a)?, version_numbers(b)?);
+
+    // Missing numbers count as zero, so `1.2` is `1.2.0`
+    let number = |version: &[u64], i: usize| version.get(i).copied().unwrap_or(0);
+    for i in 0..a.len().max(b.len()) {
+        let (a, b) = (number(&a, i), number(&b, i));
+        if a != b {
+            return Some(false);
+        }
+        if a != 0 {
+            return Some(true);
+        }
+    }
+    Some(true)
+}
+
+/// Which of two versions is newer. Missing numbers count as zero, and a pre-release like
+/// `1.4.0-beta` comes before `1.4.0`. `None` when either isn't a version.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let (numbers_a, numbers_b) = (version_numbers(a)?, version_numbers(b)?);
+    let len = numbers_a.len().max(numbers_b.len());
+    let padded = |numbers: Vec<u64>| -> Vec<u64> {
+        numbers
+            .into_iter()
+            .chain(std::iter::repeat(0))
+            .take(len)
+            .collect()
+    };
+    let prerelease = |version: &str| version.split('+').next().is_some_and(|v| v.contains('-'));
+    Some(
+        padded(numbers_a)
+            .cmp(&padded(numbers_b))
+            .then(prerelease(b).cmp(&prerelease(a))),
+    )
+}
+
+/// Warns when a fleet was built against an SDK that doesn't suit the Release's sim, which tends to
+/// show up as fleets losing instantly. With `require_match` that's an error instead, as is not
+/// being able to tell.
+pub fn check_versions(
+    release: Option<&str>,
+    fleets: [&Fleet; 2],
+    require_match: bool,
+) -> anyhow::Result<()> {
+    let Some(release) = release else {
+        if require_match {
+            anyhow::bail!("can't tell what version your Protologic/Release checkout is, so can't check it matches the fleets. It needs a VERSION file, or a sim that supports `--version`");
+        }
+        return Ok(());
+    };
+
+    let mut problems = Vec::new();
+    for fleet in fleets {
+        let sdk = fleet.manifest_entry().and_then(|entry| entry.sdk_version);
+        match sdk.as_deref().map(|sdk| (sdk, compatible(sdk, release))) {
+            Some((_, Some(true))) => {}
+            Some((sdk, _)) => problems.push(format!(
+                "'{}' was built against Protologic SDK {sdk}, but the sim is {release}",
+                fleet.name
+            )),
+            None if require_match => problems.push(format!(
+                "'{}' has no recorded SDK version, rebuild it with `cargo protologic build`",
+                fleet.name
+            )),
+            None => {}
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if require_match {
+        anyhow::bail!(
+            "the fleets don't match the sim version: {}",
+            problems.join("; ")
+        );
+    }
+
+    say!("================================================================");
+    say!("WARNING: the fleets may not work with this version of the sim!");
+    for problem in &problems {
+        say!("  {problem}");
+    }
+    say!("Update the SDK dependency and rebuild, or switch to a matching Protologic/Release.");
+    say!("================================================================");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare_versions, compatible, parse_version};
+
+    #[test]
+    fn parse_version_finds_the_version() {
+        assert_eq!(parse_version("0.7.2\n").as_deref(), Some("0.7.2"));
+        assert_eq!(
+            parse_version("Protologic.Terminal v1.3.0-beta (build 42)").as_deref(),
+            Some("1.3.0-beta")
+        );
+        assert_eq!(parse_version("Protologic sim, rev 42"), None);
+    }
+
+    #[test]
+    fn compatible_follows_cargo_rules() {
+        assert_eq!(compatible("0.7.1", "0.7.3"), Some(true));
+        assert_eq!(compatible("0.7.1", "0.8.0"), Some(false));
+        assert_eq!(compatible("1.2.0", "1.9"), Some(true));
+        assert_eq!(compatible("1.2.0", "2.0.0"), Some(false));
+        assert_eq!(compatible("0.0.3", "0.0.4"), Some(false));
+        assert_eq!(compatible("0.7", "nightly"), None);
+    }
+
+    #[test]
+    fn compare_versions_orders_by_number() {
+        assert_eq!(compare_versions("1.3.0", "1.4.2"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.10.0", "1.9.3"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.4", "1.4.0"), Some(Ordering::Equal));
+        assert_eq!(
+            compare_versions("1.4.0-beta", "1.4.0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_versions("1.4.0+42", "1.4.0"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1.4.0", "nightly"), None);
+    }
+}
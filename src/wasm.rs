@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use anyhow::Context;
+use wasmparser::WasmFeatures;
+
+/// What the sim's wasm runtime supports: WebAssembly 2.0, which has the bulk memory and SIMD
+/// `build` enables.
+pub const SIM_FEATURES: WasmFeatures = WasmFeatures::WASM2;
+
+/// Checks that the file at `path` is a wasm module that parses and validates.
+///
+/// This is cheap compared to a sim run, so it's worth doing before handing a file to the sim.
+pub fn validate_wasm_file(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("trying to read wasm file {path:?}"))?;
+
+    validate_wasm(&bytes).with_context(|| format!("{path:?} is not a valid wasm module"))
+}
+
+pub fn validate_wasm(bytes: &[u8]) -> anyhow::Result<()> {
+    if !wasmparser::Parser::is_core_wasm(bytes) {
+        anyhow::bail!("missing the wasm module header");
+    }
+
+    wasmparser::Validator::new()
+        .validate_all(bytes)
+        .context("wasm validation failed")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_wasm;
+
+    #[test]
+    fn validate_wasm_accepts_empty_module_and_rejects_garbage() {
+        let empty_module = b"\0asm\x01\0\0\0";
+        assert!(validate_wasm(empty_module).is_ok());
+
+        assert!(validate_wasm(b"not a wasm file").is_err());
+        assert!(validate_wasm(&empty_module[..6]).is_err());
+    }
+}
@@ -0,0 +1,488 @@
+//! Comparing two fleet builds: which sections, functions, imports and exports changed, and by how
+//! much.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use bytesize::ByteSize;
+use serde::Serialize;
+use wasmparser::{ExternalKind, KnownCustom, Name, Parser, Payload, TypeRef};
+
+use crate::output::say;
+
+/// What's in a module, as far as comparing it goes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ModuleSummary {
+    size: u64,
+    /// The size of each section's contents, in the order they're in the module.
+    sections: Vec<(String, u64)>,
+    /// The size of each function's body, by the name the name section gives it, if it has one.
+    functions: Option<BTreeMap<String, u64>>,
+    imports: BTreeSet<String>,
+    exports: BTreeSet<String>,
+}
+
+/// What a section is called, with custom sections by their name, like `custom "name"`.
+fn section_name(payload: &Payload) -> Option<String> {
+    let name = match payload {
+        Payload::TypeSection(_) => "type",
+        Payload::ImportSection(_) => "import",
+        Payload::FunctionSection(_) => "function",
+        Payload::TableSection(_) => "table",
+        Payload::MemorySection(_) => "memory",
+        Payload::TagSection(_) => "tag",
+        Payload::GlobalSection(_) => "global",
+        Payload::ExportSection(_) => "export",
+        Payload::StartSection { .. } => "start",
+        Payload::ElementSection(_) => "element",
+        Payload::DataCountSection { .. } => "data count",
+        Payload::DataSection(_) => "data",
+        Payload::CodeSectionStart { .. } => "code",
+        Payload::CustomSection(reader) => return Some(format!("custom {:?}", reader.name())),
+        _ => return None,
+    };
+    Some(name.to_owned())
+}
+
+fn describe_import(ty: &TypeRef) -> &'static str {
+    match ty {
+        TypeRef::Func(_) | TypeRef::FuncExact(_) => "function",
+        TypeRef::Table(_) => "table",
+        TypeRef::Memory(_) => "memory",
+        TypeRef::Global(_) => "global",
+        TypeRef::Tag(_) => "tag",
+    }
+}
+
+/// Reads what's in a module in one pass over it, without validating it.
+fn summarize(bytes: &[u8]) -> anyhow::Result<ModuleSummary> {
+    if !Parser::is_core_wasm(bytes) {
+        anyhow::bail!("it's missing the wasm module header");
+    }
+
+    let mut summary = ModuleSummary {
+        size: bytes.len() as u64,
+        ..ModuleSummary::default()
+    };
+    let mut imported_functions = 0;
+    let mut bodies = Vec::new();
+    let mut names = BTreeMap::new();
+    let mut exported_names = BTreeMap::new();
+    for payload in Parser::new(0).parse_all(bytes) {
+        let payload = payload?;
+        if let (Some(name), Some((_, range))) = (section_name(&payload), payload.as_section()) {
+            summary.sections.push((name, range.len() as u64));
+        }
+        match payload {
+            Payload::ImportSection(reader) => {
+                for import in reader.into_imports() {
+                    let import = import?;
+                    if matches!(import.ty, TypeRef::Func(_) | TypeRef::FuncExact(_)) {
+                        imported_functions += 1;
+                    }
+                    summary.imports.insert(format!(
+                        "{}.{} ({})",
+                        import.module,
+                        import.name,
+                        describe_import(&import.ty)
+                    ));
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    if export.kind == ExternalKind::Func {
+                        exported_names.insert(export.index, export.name.to_owned());
+                    }
+                    summary
+                        .exports
+                        .insert(format!("{} ({:?})", export.name, export.kind).to_lowercase());
+                }
+            }
+            Payload::CodeSectionEntry(body) => bodies.push(body.range().len() as u64),
+            Payload::CustomSection(reader) => {
+                let KnownCustom::Name(reader) = reader.as_known() else {
+                    continue;
+                };
+                // A broken name section just means functions can't be told apart
+                for name in reader.into_iter().flatten() {
+                    if let Name::Function(map) = name {
+                        names.extend(
+                            map.into_iter()
+                                .flatten()
+                                .map(|naming| (naming.index, naming.name.to_owned())),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !names.is_empty() {
+        let mut functions = BTreeMap::new();
+        for (i, size) in bodies.into_iter().enumerate() {
+            let index = imported_functions + i as u32;
+            // Indexes shift whenever an import is added, so they're a last resort
+            let name = names
+                .remove(&index)
+                .or_else(|| exported_names.remove(&index))
+                .unwrap_or_else(|| format!("function {index}"));
+            *functions.entry(name).or_default() += size;
+        }
+        summary.functions = Some(functions);
+    }
+    Ok(summary)
+}
+
+/// Something that's in both modules, or one of them.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct SizeChange {
+    name: String,
+    old: Option<u64>,
+    new: Option<u64>,
+}
+
+impl SizeChange {
+    fn delta(&self) -> i64 {
+        self.new.unwrap_or_default() as i64 - self.old.unwrap_or_default() as i64
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+struct ListChange {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl ListChange {
+    fn between(old: &BTreeSet<String>, new: &BTreeSet<String>) -> Self {
+        Self {
+            added: new.difference(old).cloned().collect(),
+            removed: old.difference(new).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct ModuleFile {
+    path: PathBuf,
+    size: u64,
+}
+
+/// How two modules differ.
+#[derive(Serialize, Debug, Clone)]
+struct WasmDiff {
+    old: ModuleFile,
+    new: ModuleFile,
+    /// Every section in either, whether or not it changed.
+    sections: Vec<SizeChange>,
+    /// The functions that were added, removed or resized, biggest change first. `None` unless
+    /// both modules have a name section to match them up by.
+    functions: Option<Vec<SizeChange>>,
+    imports: ListChange,
+    exports: ListChange,
+}
+
+/// Lines up what's in `old` and `new` by name, in the order they come, with what's only in `new`
+/// after whatever it comes after there.
+fn size_changes<'a>(
+    old: impl IntoIterator<Item = (&'a String, u64)>,
+    new: impl IntoIterator<Item = (&'a String, u64)>,
+) -> Vec<SizeChange> {
+    let mut changes: Vec<SizeChange> = old
+        .into_iter()
+        .map(|(name, size)| SizeChange {
+            name: name.clone(),
+            old: Some(size),
+            new: None,
+        })
+        .collect();
+    let mut next = 0;
+    for (name, size) in new {
+        match changes
+            .iter()
+            .position(|change| change.name == *name && change.new.is_none())
+        {
+            Some(i) => {
+                changes[i].new = Some(size);
+                next = i + 1;
+            }
+            None => {
+                changes.insert(
+                    next,
+                    SizeChange {
+                        name: name.clone(),
+                        old: None,
+                        new: Some(size),
+                    },
+                );
+                next += 1;
+            }
+        }
+    }
+    changes
+}
+
+fn diff(old: (&Path, &ModuleSummary), new: (&Path, &ModuleSummary)) -> WasmDiff {
+    let ((old_path, old), (new_path, new)) = (old, new);
+    let sections = size_changes(
+        old.sections.iter().map(|(name, size)| (name, *size)),
+        new.sections.iter().map(|(name, size)| (name, *size)),
+    );
+    let functions = match (&old.functions, &new.functions) {
+        (Some(old), Some(new)) => {
+            // Modules can have thousands of functions, so these are looked up by name rather than
+            // lined up in order like the sections
+            let mut changes = Vec::new();
+            let mut all: BTreeSet<&String> = old.keys().collect();
+            all.extend(new.keys());
+            for name in all {
+                let change = SizeChange {
+                    name: name.clone(),
+                    old: old.get(name).copied(),
+                    new: new.get(name).copied(),
+                };
+                if change.old != change.new {
+                    changes.push(change);
+                }
+            }
+            changes.sort_by_key(|change| std::cmp::Reverse(change.delta().unsigned_abs()));
+            Some(changes)
+        }
+        _ => None,
+    };
+
+    WasmDiff {
+        old: ModuleFile {
+            path: old_path.to_owned(),
+            size: old.size,
+        },
+        new: ModuleFile {
+            path: new_path.to_owned(),
+            size: new.size,
+        },
+        sections,
+        functions,
+        imports: ListChange::between(&old.imports, &new.imports),
+        exports: ListChange::between(&old.exports, &new.exports),
+    }
+}
+
+/// A change in size, like `+60.0 KiB` or `-300 B`.
+fn signed_size(delta: i64) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!("{sign}{}", ByteSize::b(delta.unsigned_abs()))
+}
+
+fn describe_size(size: Option<u64>) -> String {
+    size.map_or("-".to_owned(), |size| ByteSize::b(size).to_string())
+}
+
+/// The diff as text, with at most `limit` functions.
+fn diff_report(diff: &WasmDiff, limit: usize) -> String {
+    let mut lines = Vec::new();
+    let (old, new) = (diff.old.size, diff.new.size);
+    let percent = if old > 0 {
+        format!(", {:+.1}%", (new as f64 - old as f64) / old as f64 * 100.0)
+    } else {
+        String::new()
+    };
+    lines.push(format!(
+        "Size: {} → {} ({}{percent})",
+        ByteSize::b(old),
+        ByteSize::b(new),
+        signed_size(new as i64 - old as i64)
+    ));
+
+    lines.push("Sections:".to_owned());
+    let width = diff
+        .sections
+        .iter()
+        .map(|section| section.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    for section in &diff.sections {
+        let change = match section.delta() {
+            0 => "unchanged".to_owned(),
+            delta => signed_size(delta),
+        };
+        lines.push(format!(
+            "  {:<width$}  {} → {}  {change}",
+            section.name,
+            describe_size(section.old),
+            describe_size(section.new)
+        ));
+    }
+
+    match &diff.functions {
+        None => lines.push(
+            "Functions: can't be compared without a name section in both, build them without stripping names to see them"
+                .to_owned(),
+        ),
+        Some(functions) if functions.is_empty() => {
+            lines.push("Functions: unchanged".to_owned())
+        }
+        Some(functions) => {
+            let count = |pick: fn(&SizeChange) -> bool| functions.iter().filter(|f| pick(f)).count();
+            lines.push(format!(
+                "Functions: {} added, {} removed, {} resized",
+                count(|function| function.old.is_none()),
+                count(|function| function.new.is_none()),
+                count(|function| function.old.is_some() && function.new.is_some()),
+            ));
+            for function in functions.iter().take(limit) {
+                lines.push(match (function.old, function.new) {
+                    (None, new) => format!("  + {}  {}", function.name, describe_size(new)),
+                    (old, None) => format!("  - {}  {}", function.name, describe_size(old)),
+                    (old, new) => format!(
+                        "  ~ {}  {} → {} ({})",
+                        function.name,
+                        describe_size(old),
+                        describe_size(new),
+                        signed_size(function.delta())
+                    ),
+                });
+            }
+            if functions.len() > limit {
+                lines.push(format!(
+                    "  and {} more, `--limit` shows more",
+                    functions.len() - limit
+                ));
+            }
+        }
+    }
+
+    for (kind, change) in [("Imports", &diff.imports), ("Exports", &diff.exports)] {
+        if change.added.is_empty() && change.removed.is_empty() {
+            lines.push(format!("{kind}: unchanged"));
+            continue;
+        }
+        lines.push(format!("{kind}:"));
+        lines.extend(change.added.iter().map(|name| format!("  + {name}")));
+        lines.extend(change.removed.iter().map(|name| format!("  - {name}")));
+    }
+    lines.join("\n")
+}
+
+fn read_summary(path: &Path) -> anyhow::Result<ModuleSummary> {
+    let bytes = std::fs::read(path).with_context(|| format!("trying to read {path:?}"))?;
+    summarize(&bytes).with_context(|| format!("trying to read the wasm module {path:?}"))
+}
+
+/// Compares two wasm modules section by section, function by function when they have names, and
+/// by their imports and exports.
+pub fn wasm_diff(old: PathBuf, new: PathBuf, limit: usize, json: bool) -> anyhow::Result<()> {
+    let diff = diff((&old, &read_summary(&old)?), (&new, &read_summary(&new)?));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        say!("{}", diff_report(&diff, limit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{diff, diff_report, summarize};
+
+    const OLD: &str = r#"(module
+        (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+        (memory (export "memory") 1)
+        (func $steer (drop (call $yield)))
+        (func $aim (drop (i32.add (i32.const 1) (i32.const 2))))
+        (func (export "_start") (call $steer) (call $aim)))"#;
+
+    const NEW: &str = r#"(module
+        (import "wasi_snapshot_preview1" "sched_yield" (func $yield (result i32)))
+        (import "protologic" "gun_fire" (func $fire))
+        (memory (export "memory") 1)
+        (func $steer (drop (call $yield)) (drop (call $yield)) (drop (call $yield)))
+        (func $fire_all (call $fire))
+        (func (export "_start") (call $steer) (call $fire_all))
+        (data (i32.const 0) "hello"))"#;
+
+    #[test]
+    fn summarize_reads_sections_functions_and_names() -> anyhow::Result<()> {
+        let summary = summarize(&wat::parse_str(OLD)?)?;
+        let sections: Vec<&str> = summary
+            .sections
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                "type",
+                "import",
+                "function",
+                "memory",
+                "export",
+                "code",
+                "custom \"name\""
+            ]
+        );
+        let functions = summary.functions.unwrap();
+        assert_eq!(
+            functions.keys().collect::<Vec<_>>(),
+            ["_start", "aim", "steer"]
+        );
+        assert_eq!(
+            summary.imports.iter().collect::<Vec<_>>(),
+            ["wasi_snapshot_preview1.sched_yield (function)"]
+        );
+        assert!(
+            summary.exports.contains("_start (func)"),
+            "{:?}",
+            summary.exports
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn diff_lines_up_both_modules() -> anyhow::Result<()> {
+        let old = summarize(&wat::parse_str(OLD)?)?;
+        let new = summarize(&wat::parse_str(NEW)?)?;
+        let diff = diff((Path::new("old.wasm"), &old), (Path::new("new.wasm"), &new));
+
+        let data = diff.sections.iter().find(|section| section.name == "data");
+        assert_eq!(data.map(|data| data.old), Some(None));
+        let functions = diff.functions.as_ref().unwrap();
+        let names: Vec<(&str, bool, bool)> = functions
+            .iter()
+            .map(|function| {
+                (
+                    function.name.as_str(),
+                    function.old.is_some(),
+                    function.new.is_some(),
+                )
+            })
+            .collect();
+        assert!(names.contains(&("aim", true, false)), "{names:?}");
+        assert!(names.contains(&("fire_all", false, true)), "{names:?}");
+        assert!(names.contains(&("steer", true, true)), "{names:?}");
+        assert_eq!(diff.imports.added, ["protologic.gun_fire (function)"]);
+        assert!(diff.imports.removed.is_empty());
+        assert!(diff.exports.added.is_empty() && diff.exports.removed.is_empty());
+
+        let report = diff_report(&diff, 1);
+        assert!(
+            report.contains("Functions: 1 added, 1 removed, "),
+            "{report}"
+        );
+        assert!(report.contains("more, `--limit` shows more"), "{report}");
+        assert!(report.contains("Exports: unchanged"), "{report}");
+        Ok(())
+    }
+
+    #[test]
+    fn stripped_modules_skip_the_functions() -> anyhow::Result<()> {
+        let stripped = summarize(&wat::parse_str(r#"(module (func (export "_start")))"#)?)?;
+        assert_eq!(stripped.functions, None);
+        assert!(summarize(b"not wasm").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+
+use crate::fleet::Fleet;
+use crate::interrupt::{install_handler, interrupted};
+use crate::output::say;
+use crate::run::{run_battles, RunArgs};
+
+/// How often to look for changed sources.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+/// How long sources have to stay the same before rebuilding, so saving a bunch of files at once
+/// only rebuilds once.
+const DEBOUNCE: Duration = Duration::from_millis(400);
+
+/// A package of the workspace, and whether it's a fleet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WatchedPackage {
+    id: String,
+    dir: PathBuf,
+    fleet: bool,
+}
+
+/// When each watched file was last modified, and how big it is.
+type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+/// Runs the battles, then reruns them whenever the workspace's sources change, rebuilding the
+/// fleets the change affects first. Stops on Ctrl-C, killing the sim if one is running.
+pub fn watch(args: RunArgs, fleet1: Fleet, fleet2: Fleet) -> anyhow::Result<()> {
+    install_handler().context("trying to catch Ctrl-C")?;
+
+    let metadata = crate::cargo_metadata()?;
+    let packages = workspace_packages(&metadata);
+    let mut watched: Vec<PathBuf> = packages
+        .iter()
+        .flat_map(|package| ["src", "Cargo.toml", "build.rs"].map(|name| package.dir.join(name)))
+        .collect();
+    watched.push(metadata.workspace_root.join("Cargo.toml"));
+
+    let rerun = listen_for_enter();
+    let mut sources = snapshot(&watched);
+    let round = || {
+        if let Err(err) = run_battles(args.clone(), fleet1.clone(), fleet2.clone()) {
+            if !interrupted() {
+                say!("Error: {err:#}");
+            }
+        }
+        if !interrupted() {
+            say!("Waiting for changes. Press Enter to rerun, or Ctrl-C to stop.");
+        }
+    };
+
+    say!(
+        "Watching {} packages for changes. Press Enter to rerun, or Ctrl-C to stop.",
+        packages.len()
+    );
+    round();
+
+    loop {
+        if interrupted() {
+            say!("Stopped watching.");
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+
+        if rerun.try_recv().is_ok() {
+            say!("Rerunning...");
+            round();
+            continue;
+        }
+
+        let mut current = snapshot(&watched);
+        if current == sources {
+            continue;
+        }
+        loop {
+            std::thread::sleep(DEBOUNCE);
+            let next = snapshot(&watched);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+
+        let changed = changed_paths(&sources, &current);
+        sources = current;
+        let fleets = affected_fleets(&changed, &packages);
+        say!(
+            "{} changed, rebuilding {} fleets...",
+            describe_changes(&changed),
+            fleets.len()
+        );
+        if let Err(err) = crate::build_fleets(&fleets, args.battle.debug) {
+            if !interrupted() {
+                say!("Error: {err:#}");
+                say!("Waiting for changes. Press Enter to rerun, or Ctrl-C to stop.");
+            }
+            continue;
+        }
+        round();
+    }
+}
+
+/// The packages that are part of the workspace, leaving out dependencies.
+fn workspace_packages(metadata: &crate::ParsedMetadata) -> Vec<WatchedPackage> {
+    let fleets = metadata.fleet_ids();
+    metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .filter_map(|package| {
+            Some(WatchedPackage {
+                id: package.id.clone(),
+                dir: package.manifest_path.parent()?.to_owned(),
+                fleet: fleets.contains(&package.id),
+            })
+        })
+        .collect()
+}
+
+/// Sends a message every time Enter is pressed, when there's a terminal to press it in.
+fn listen_for_enter() -> Receiver<()> {
+    let (sender, receiver) = mpsc::channel();
+    if std::io::stdin().is_terminal() {
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                if line.is_err() || sender.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    receiver
+}
+
+/// Every file under the watched paths, skipping hidden files and anything under `target`.
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    fn visit(path: &Path, snapshot: &mut Snapshot) {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return;
+        };
+        if metadata.is_file() {
+            if let Ok(modified) = metadata.modified() {
+                snapshot.insert(path.to_owned(), (modified, metadata.len()));
+            }
+            return;
+        }
+
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.') && name != "target" {
+                visit(&entry.path(), snapshot);
+            }
+        }
+    }
+
+    let mut snapshot = Snapshot::new();
+    for path in paths {
+        visit(path, &mut snapshot);
+    }
+    snapshot
+}
+
+/// The files that were added, removed, or changed between two snapshots.
+fn changed_paths(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let removed = before.keys().filter(|path| !after.contains_key(*path));
+    let changed = after
+        .iter()
+        .filter(|(path, stamp)| before.get(*path) != Some(stamp))
+        .map(|(path, _)| path);
+
+    removed.chain(changed).cloned().collect()
+}
+
+/// The fleets to rebuild for the changed files. A change to a fleet's own package only rebuilds
+/// that fleet, but a change anywhere else, like a helper crate, could affect them all.
+fn affected_fleets(changed: &[PathBuf], packages: &[WatchedPackage]) -> Vec<String> {
+    let fleets = || {
+        packages
+            .iter()
+            .filter(|package| package.fleet)
+            .map(|package| package.id.clone())
+    };
+
+    let mut affected = Vec::new();
+    for path in changed {
+        // The deepest package wins, since packages can be nested in each other's directories
+        let owner = packages
+            .iter()
+            .filter(|package| path.starts_with(&package.dir))
+            .max_by_key(|package| package.dir.components().count());
+        match owner {
+            Some(package) if package.fleet => affected.push(package.id.clone()),
+            _ => return fleets().collect(),
+        }
+    }
+
+    affected.sort();
+    affected.dedup();
+    affected
+}
+
+fn describe_changes(changed: &[PathBuf]) -> String {
+    match changed {
+        [path] => path.display().to_string(),
+        paths => format!("{} files", paths.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{affected_fleets, WatchedPackage};
+
+    #[test]
+    fn affected_fleets_only_rebuilds_what_changed() {
+        let package = |id: &str, dir: &str, fleet: bool| WatchedPackage {
+            id: id.to_owned(),
+            dir: PathBuf::from(dir),
+            fleet,
+        };
+        let packages = [
+            package("root", "/ws", false),
+            package("glass_cannon", "/ws/fleets/glass_cannon", true),
+            package("iron_armada", "/ws/fleets/iron_armada", true),
+            package("helpers", "/ws/helpers", false),
+        ];
+
+        let changed = [PathBuf::from("/ws/fleets/iron_armada/src/lib.rs")];
+        assert_eq!(affected_fleets(&changed, &packages), ["iron_armada"]);
+
+        let changed = [PathBuf::from("/ws/helpers/src/aim.rs")];
+        assert_eq!(
+            affected_fleets(&changed, &packages),
+            ["glass_cannon", "iron_armada"]
+        );
+    }
+}